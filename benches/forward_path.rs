@@ -0,0 +1,85 @@
+use std::hint::black_box;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::body::Body;
+use bytes::Bytes;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use http::{HeaderMap, HeaderValue, Request};
+use tower::ServiceExt;
+
+use load_balancer::http_client::error::Error;
+use load_balancer::http_client::request::{Request as HttpClientRequest, RequestHeaders};
+use load_balancer::http_client::response::Response as HttpClientResponse;
+use load_balancer::{HttpClient, ServerState, router};
+
+struct EchoHttpClient;
+
+#[async_trait]
+impl HttpClient for EchoHttpClient {
+    async fn execute(&self, request: HttpClientRequest) -> Result<HttpClientResponse, Error> {
+        Ok(HttpClientResponse {
+            status: 200,
+            headers: request.headers,
+            body: request.body,
+        })
+    }
+}
+
+fn request_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("accept", HeaderValue::from_static("application/json"));
+    headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+    headers.insert("content-type", HeaderValue::from_static("application/json"));
+    headers.insert("user-agent", HeaderValue::from_static("criterion/0.5"));
+    headers.insert("x-request-id", HeaderValue::from_static("12345"));
+    headers
+}
+
+fn server_state() -> ServerState {
+    ServerState::new(vec!["http://server1".to_string()], Arc::new(EchoHttpClient))
+}
+
+/// The forward path hands the headers over by value, from the incoming
+/// request to the client and from the client's response back.
+fn header_conversions(c: &mut Criterion) {
+    let headers = request_headers();
+
+    c.bench_function("header_map_into_request_headers", |b| {
+        b.iter_batched(
+            || headers.clone(),
+            |headers| RequestHeaders::from(black_box(headers)),
+            BatchSize::SmallInput,
+        )
+    });
+
+    let request_headers = RequestHeaders::from(&headers);
+
+    c.bench_function("request_headers_into_header_map", |b| {
+        b.iter_batched(
+            || request_headers.clone(),
+            |request_headers| HeaderMap::from(black_box(request_headers)),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn forward(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let router = router(server_state());
+    let body = Bytes::from(vec![b'x'; 4096]);
+
+    c.bench_function("forward_4kb_post", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let mut request = Request::post("/v1/api/user")
+                .body(Body::from(body.clone()))
+                .unwrap();
+            *request.headers_mut() = request_headers();
+
+            router.clone().oneshot(request).await.unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, header_conversions, forward);
+criterion_main!(benches);
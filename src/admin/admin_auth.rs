@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use axum::extract::FromRequestParts;
+use axum::response::{IntoResponse, Response};
+use http::request::Parts;
+use http::{StatusCode, header};
+use ring::digest::{SHA256, digest};
+use tracing::warn;
+
+use crate::ServerState;
+
+/// The bearer token the admin requests changing the load balancer have to
+/// carry, e.g. `Authorization: Bearer s3cr3t`. Without one configured
+/// those requests are refused altogether, the admin API staying read-only.
+#[derive(Clone, Default)]
+pub struct AdminAuth {
+    token: Option<Arc<str>>,
+}
+
+impl AdminAuth {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            token: token.filter(|token| !token.is_empty()).map(Arc::from),
+        }
+    }
+
+    fn check(&self, parts: &Parts) -> Result<(), Rejection> {
+        let token = self.token.as_ref().ok_or(Rejection::Disabled)?;
+
+        let presented = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match presented {
+            Some(presented) if same_token(presented.trim(), token) => Ok(()),
+            _ => {
+                warn!("Refused an unauthenticated {} {}", parts.method, parts.uri);
+                Err(Rejection::Unauthenticated)
+            }
+        }
+    }
+}
+
+/// Why an admin request was refused.
+pub enum Rejection {
+    /// No admin token is configured.
+    Disabled,
+    /// The request carries no admin token, or a wrong one.
+    Unauthenticated,
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection::Disabled => (
+                StatusCode::FORBIDDEN,
+                "Admin changes are disabled without --admin-token",
+            )
+                .into_response(),
+            Rejection::Unauthenticated => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Compares the digests of the tokens byte by byte to the end, so that the
+/// time taken tells nothing about how much of the token was guessed.
+fn same_token(presented: &str, token: &str) -> bool {
+    let presented = digest(&SHA256, presented.as_bytes());
+    let token = digest(&SHA256, token.as_bytes());
+
+    presented
+        .as_ref()
+        .iter()
+        .zip(token.as_ref())
+        .fold(0, |difference, (left, right)| difference | (left ^ right))
+        == 0
+}
+
+/// Extracted by the admin handlers changing the load balancer, which only
+/// run once the request proved it carries the admin token.
+pub struct Authorized;
+
+impl FromRequestParts<ServerState> for Authorized {
+    type Rejection = Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &ServerState,
+    ) -> Result<Self, Self::Rejection> {
+        state.admin_auth.check(parts).map(|_| Authorized)
+    }
+}
@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    ServerState,
+    admin::{admin_auth::Authorized, annotations::Annotation},
+    background_health_checker::health_score::HealthScore,
+    target_url::normalize_target_url,
+};
+
+pub(crate) const HEALTHY_SERVERS_PATH: &str = "/admin/healthy-servers";
+
+/// How long each backend of a pool has to answer its probe when the pool is
+/// verified before a blue/green switch.
+const BLUE_GREEN_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn health_history_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.health_history.snapshot())
+}
+
+async fn health_scores_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.health_history.scores())
+}
+
+async fn healthy_servers_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    let healthy_servers = state
+        .healthy_servers
+        .read()
+        .map(|servers| servers.clone())
+        .unwrap_or_default();
+
+    Json(healthy_servers)
+}
+
+async fn metrics_endpoint(_: Authorized, State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.metrics.snapshot())
+}
+
+async fn usage_endpoint(_: Authorized, State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.usage.snapshot())
+}
+
+#[derive(Deserialize)]
+struct AnnotationRequest {
+    server: String,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceRequest {
+    server: String,
+}
+
+#[derive(Deserialize)]
+struct BlueGreenSwitchRequest {
+    service: String,
+    pool: String,
+    /// Probe every backend of the pool first, and keep the live one if any
+    /// of them fails.
+    #[serde(default)]
+    verify: bool,
+}
+
+#[derive(Serialize)]
+struct ServerStatus {
+    server: String,
+    healthy: bool,
+    maintenance: bool,
+    health_score: Option<HealthScore>,
+    annotation: Option<Annotation>,
+    certificate_days_to_expiry: Option<i64>,
+}
+
+async fn servers_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    let healthy_servers = state
+        .healthy_servers
+        .read()
+        .map(|servers| servers.clone())
+        .unwrap_or_default();
+    let target_servers = state
+        .target_servers
+        .read()
+        .map(|servers| servers.clone())
+        .unwrap_or_default();
+    let mut health_scores = state.health_history.scores();
+
+    let statuses: Vec<ServerStatus> = target_servers
+        .iter()
+        .map(|server| ServerStatus {
+            server: server.clone(),
+            healthy: healthy_servers.contains(server),
+            maintenance: state.maintenance.contains(server),
+            health_score: health_scores.remove(server),
+            annotation: state.annotations.get(server),
+            certificate_days_to_expiry: state.certificate_expiries.days_to_expiry(server),
+        })
+        .collect();
+
+    Json(statuses)
+}
+
+async fn config_endpoint(_: Authorized, State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.effective_config.snapshot())
+}
+
+async fn annotations_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.annotations.snapshot())
+}
+
+async fn set_annotation_endpoint(
+    _: Authorized,
+    State(state): State<ServerState>,
+    Json(request): Json<AnnotationRequest>,
+) -> StatusCode {
+    if !state.is_target_server(&request.server) {
+        return StatusCode::NOT_FOUND;
+    }
+
+    let Some(note) = request.note.filter(|note| !note.trim().is_empty()) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    state.annotations.set(&request.server, note);
+
+    StatusCode::NO_CONTENT
+}
+
+async fn remove_annotation_endpoint(
+    _: Authorized,
+    State(state): State<ServerState>,
+    Json(request): Json<AnnotationRequest>,
+) -> StatusCode {
+    match state.annotations.remove(&request.server) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn maintenance_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.maintenance.snapshot())
+}
+
+async fn start_maintenance_endpoint(
+    _: Authorized,
+    State(state): State<ServerState>,
+    Json(request): Json<MaintenanceRequest>,
+) -> StatusCode {
+    let Ok(server) = normalize_target_url(&request.server) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    if !state.is_target_server(&server) && !state.pools.current().contains_server(&server) {
+        return StatusCode::NOT_FOUND;
+    }
+
+    if state.maintenance.start(&server) {
+        warn!("{} taken out of rotation for maintenance", server);
+    }
+
+    StatusCode::NO_CONTENT
+}
+
+async fn end_maintenance_endpoint(
+    _: Authorized,
+    State(state): State<ServerState>,
+    Json(request): Json<MaintenanceRequest>,
+) -> StatusCode {
+    let Ok(server) = normalize_target_url(&request.server) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    match state.maintenance.end(&server) {
+        true => {
+            info!("{} back in rotation after maintenance", server);
+            StatusCode::NO_CONTENT
+        }
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn blue_green_endpoint(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.pools.current().blue_greens())
+}
+
+async fn switch_blue_green_endpoint(
+    _: Authorized,
+    State(state): State<ServerState>,
+    Json(request): Json<BlueGreenSwitchRequest>,
+) -> Response {
+    let pools = state.pools.current();
+    let Some(pool) = pools.blue_green_pool(&request.service, &request.pool) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if request.verify {
+        let unhealthy_backends = pool
+            .unhealthy_backends(state.probe_client.as_ref(), BLUE_GREEN_PROBE_TIMEOUT)
+            .await;
+
+        if !unhealthy_backends.is_empty() {
+            warn!(
+                "Not switching {} to {}, unhealthy backends: {:?}",
+                request.service, request.pool, unhealthy_backends
+            );
+            return (StatusCode::CONFLICT, Json(unhealthy_backends)).into_response();
+        }
+    }
+
+    // Switched on the pools in use by now, should they have been reloaded
+    // during the probe.
+    if let Some(previous) = state
+        .pools
+        .current()
+        .switch_blue_green(&request.service, &request.pool)
+    {
+        info!(
+            "{} switched from {} to {}",
+            request.service, previous, request.pool
+        );
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+pub(crate) fn admin_router() -> Router<ServerState> {
+    Router::new()
+        .route("/admin/health-history", get(health_history_endpoint))
+        .route("/admin/health-scores", get(health_scores_endpoint))
+        .route(HEALTHY_SERVERS_PATH, get(healthy_servers_endpoint))
+        .route("/admin/metrics", get(metrics_endpoint))
+        .route("/admin/usage", get(usage_endpoint))
+        .route("/admin/servers", get(servers_endpoint))
+        .route("/admin/config", get(config_endpoint))
+        .route(
+            "/admin/annotations",
+            get(annotations_endpoint)
+                .put(set_annotation_endpoint)
+                .delete(remove_annotation_endpoint),
+        )
+        .route(
+            "/admin/maintenance",
+            get(maintenance_endpoint)
+                .put(start_maintenance_endpoint)
+                .delete(end_maintenance_endpoint),
+        )
+        .route(
+            "/admin/blue-green",
+            get(blue_green_endpoint).put(switch_blue_green_endpoint),
+        )
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub note: String,
+    pub updated_at_millis: u64,
+}
+
+/// Free-form notes left by operators on the backends, e.g. why one is
+/// being drained, so they are visible to whoever is on call next.
+#[derive(Default)]
+pub struct Annotations {
+    annotations: RwLock<HashMap<String, Annotation>>,
+}
+
+impl Annotations {
+    pub fn set(&self, server: &str, note: String) {
+        let updated_at_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        if let Ok(mut annotations) = self.annotations.write() {
+            annotations.insert(
+                server.to_string(),
+                Annotation {
+                    note,
+                    updated_at_millis,
+                },
+            );
+        }
+    }
+
+    pub fn remove(&self, server: &str) -> Option<Annotation> {
+        self.annotations
+            .write()
+            .ok()
+            .and_then(|mut annotations| annotations.remove(server))
+    }
+
+    pub fn get(&self, server: &str) -> Option<Annotation> {
+        self.annotations
+            .read()
+            .ok()
+            .and_then(|annotations| annotations.get(server).cloned())
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, Annotation> {
+        self.annotations
+            .read()
+            .map(|annotations| annotations.clone())
+            .unwrap_or_default()
+    }
+}
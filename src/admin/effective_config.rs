@@ -0,0 +1,26 @@
+use std::sync::RwLock;
+
+use serde_json::Value;
+
+/// The configuration the load balancer runs with, as resolved from the
+/// command line, the config file and the defaults, its secrets redacted.
+/// Kept up to date by the reloads of the pools and routes.
+#[derive(Default)]
+pub struct EffectiveConfig {
+    config: RwLock<Value>,
+}
+
+impl EffectiveConfig {
+    pub fn set(&self, config: Value) {
+        if let Ok(mut current) = self.config.write() {
+            *current = config;
+        }
+    }
+
+    pub fn snapshot(&self) -> Value {
+        self.config
+            .read()
+            .map(|config| config.clone())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::BTreeSet, sync::RwLock};
+
+/// Backends taken out of rotation by an operator, e.g. to patch them. They
+/// get no requests, however healthy their checker finds them, until they
+/// are put back.
+#[derive(Default)]
+pub struct Maintenance {
+    servers: RwLock<BTreeSet<String>>,
+}
+
+impl Maintenance {
+    /// Whether the server wasn't in maintenance already.
+    pub fn start(&self, server: &str) -> bool {
+        self.servers
+            .write()
+            .is_ok_and(|mut servers| servers.insert(server.to_string()))
+    }
+
+    /// Whether the server was in maintenance.
+    pub fn end(&self, server: &str) -> bool {
+        self.servers
+            .write()
+            .is_ok_and(|mut servers| servers.remove(server))
+    }
+
+    pub fn contains(&self, server: &str) -> bool {
+        self.servers
+            .read()
+            .is_ok_and(|servers| servers.contains(server))
+    }
+
+    /// The target servers the requests must not be sent to.
+    pub fn excluded_servers(&self, target_servers: &[String]) -> Vec<String> {
+        let Ok(servers) = self.servers.read() else {
+            return Vec::new();
+        };
+
+        if servers.is_empty() {
+            return Vec::new();
+        }
+
+        target_servers
+            .iter()
+            .filter(|server| servers.contains(*server))
+            .cloned()
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<String> {
+        self.servers
+            .read()
+            .map(|servers| servers.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::admin::maintenance::Maintenance;
+
+    #[test]
+    fn excludes_the_servers_in_maintenance_until_they_are_back() {
+        let maintenance = Maintenance::default();
+        let target_servers = vec![
+            "http://server1:8000".to_string(),
+            "http://server2:8000".to_string(),
+        ];
+
+        assert!(maintenance.start("http://server1:8000"));
+        assert!(!maintenance.start("http://server1:8000"));
+        assert_eq!(
+            maintenance.excluded_servers(&target_servers),
+            vec!["http://server1:8000"]
+        );
+
+        assert!(maintenance.end("http://server1:8000"));
+        assert!(!maintenance.end("http://server1:8000"));
+        assert!(maintenance.excluded_servers(&target_servers).is_empty());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod admin_auth;
+pub(crate) mod admin_router;
+pub mod annotations;
+pub mod effective_config;
+pub mod maintenance;
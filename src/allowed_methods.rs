@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+use http::{HeaderValue, Method};
+
+/// Methods accepted by the load balancer, by a pool or by a pool route, so
+/// that simple backends (e.g. serving static files) never see the others.
+/// Empty means every method is accepted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AllowedMethods(pub Vec<Method>);
+
+impl AllowedMethods {
+    pub fn allows(&self, method: &Method) -> bool {
+        self.0.is_empty() || self.0.contains(method)
+    }
+
+    /// Value of the `Allow` header of a 405 response.
+    pub fn allow_header(&self) -> HeaderValue {
+        let methods = self
+            .0
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        HeaderValue::from_str(&methods).unwrap_or(HeaderValue::from_static(""))
+    }
+}
+
+/// Written as `GET|HEAD`, whatever the case.
+impl FromStr for AllowedMethods {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split('|')
+            .map(|method| {
+                method
+                    .trim()
+                    .to_ascii_uppercase()
+                    .parse::<Method>()
+                    .map_err(|_| format!("invalid method {}", method))
+            })
+            .collect::<Result<_, _>>()
+            .map(AllowedMethods)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use crate::allowed_methods::AllowedMethods;
+
+    #[test]
+    fn every_method_is_allowed_by_default() {
+        assert!(AllowedMethods::default().allows(&Method::DELETE));
+    }
+
+    #[test]
+    fn only_the_listed_methods_are_allowed() {
+        let allowed_methods = AllowedMethods(vec![Method::GET, Method::HEAD]);
+
+        assert!(allowed_methods.allows(&Method::HEAD));
+        assert!(!allowed_methods.allows(&Method::POST));
+        assert_eq!(allowed_methods.allow_header(), "GET, HEAD");
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::RwLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::background_health_checker::health_score::HealthScore;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProbeRecord {
+    pub timestamp_millis: u64,
+    pub latency_millis: u64,
+    pub status: Option<u16>,
+    pub healthy: bool,
+}
+
+impl ProbeRecord {
+    pub fn new(latency: Duration, status: Option<u16>, healthy: bool) -> Self {
+        let timestamp_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp_millis,
+            latency_millis: latency.as_millis() as u64,
+            status,
+            healthy,
+        }
+    }
+}
+
+pub struct HealthHistory {
+    capacity: usize,
+    records: RwLock<HashMap<String, VecDeque<ProbeRecord>>>,
+}
+
+impl HealthHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, server: &str, record: ProbeRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let Ok(mut records) = self.records.write() else {
+            return;
+        };
+
+        let server_records = records
+            .entry(server.to_string())
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+
+        if server_records.len() == self.capacity {
+            server_records.pop_front();
+        }
+
+        server_records.push_back(record);
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, Vec<ProbeRecord>> {
+        self.records
+            .read()
+            .map(|records| {
+                records
+                    .iter()
+                    .map(|(server, server_records)| {
+                        (server.clone(), server_records.iter().cloned().collect())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn scores(&self) -> HashMap<String, HealthScore> {
+        self.records
+            .read()
+            .map(|records| {
+                records
+                    .iter()
+                    .filter_map(|(server, server_records)| {
+                        let server_records: Vec<ProbeRecord> =
+                            server_records.iter().cloned().collect();
+
+                        HealthScore::from_records(&server_records)
+                            .map(|score| (server.clone(), score))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::background_health_checker::health_history::{HealthHistory, ProbeRecord};
+
+    #[test]
+    fn keeps_only_the_last_records_per_server() {
+        let history = HealthHistory::new(2);
+
+        history.record(
+            "server1",
+            ProbeRecord::new(Duration::from_millis(1), None, false),
+        );
+        history.record(
+            "server1",
+            ProbeRecord::new(Duration::from_millis(2), Some(503), false),
+        );
+        history.record(
+            "server1",
+            ProbeRecord::new(Duration::from_millis(3), Some(200), true),
+        );
+        history.record(
+            "server2",
+            ProbeRecord::new(Duration::from_millis(4), Some(200), true),
+        );
+
+        let snapshot = history.snapshot();
+
+        let server1 = &snapshot["server1"];
+        assert_eq!(server1.len(), 2);
+        assert_eq!(server1[0].latency_millis, 2);
+        assert_eq!(server1[0].status, Some(503));
+        assert_eq!(server1[1].latency_millis, 3);
+        assert!(server1[1].healthy);
+
+        assert_eq!(snapshot["server2"].len(), 1);
+    }
+
+    #[test]
+    fn records_nothing_when_capacity_is_zero() {
+        let history = HealthHistory::new(0);
+
+        history.record("server1", ProbeRecord::new(Duration::ZERO, Some(200), true));
+
+        assert!(history.snapshot().is_empty());
+    }
+}
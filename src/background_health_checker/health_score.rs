@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::background_health_checker::health_history::ProbeRecord;
+
+/// Continuous health of a server, from 0 (down) to 1 (fully healthy), so
+/// that a degraded but alive server can be told apart from a healthy one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthScore {
+    pub score: f64,
+    pub error_rate: f64,
+    /// Average latency of the newer half of the successful probes over the
+    /// older half: above 1 the server is slowing down.
+    pub latency_trend: f64,
+}
+
+impl HealthScore {
+    pub fn from_records(records: &[ProbeRecord]) -> Option<HealthScore> {
+        if records.is_empty() {
+            return None;
+        }
+
+        let failures = records.iter().filter(|record| !record.healthy).count();
+        let error_rate = failures as f64 / records.len() as f64;
+
+        let latencies: Vec<u64> = records
+            .iter()
+            .filter(|record| record.healthy)
+            .map(|record| record.latency_millis)
+            .collect();
+
+        let latency_trend = Self::latency_trend(&latencies);
+
+        Some(HealthScore {
+            score: (1.0 - error_rate) * (1.0 / latency_trend).min(1.0),
+            error_rate,
+            latency_trend,
+        })
+    }
+
+    fn latency_trend(latencies: &[u64]) -> f64 {
+        if latencies.len() < 2 {
+            return 1.0;
+        }
+
+        let (older, newer) = latencies.split_at(latencies.len() / 2);
+
+        let average = |latencies: &[u64]| {
+            (latencies.iter().sum::<u64>() as f64 / latencies.len() as f64).max(1.0)
+        };
+
+        average(newer) / average(older)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::background_health_checker::{
+        health_history::ProbeRecord, health_score::HealthScore,
+    };
+
+    fn probe(latency_millis: u64, healthy: bool) -> ProbeRecord {
+        ProbeRecord::new(
+            Duration::from_millis(latency_millis),
+            Some(if healthy { 200 } else { 503 }),
+            healthy,
+        )
+    }
+
+    #[test]
+    fn steady_healthy_server_scores_one() {
+        let score = HealthScore::from_records(&[probe(10, true), probe(10, true)]).unwrap();
+
+        assert_eq!(score.score, 1.0);
+        assert_eq!(score.error_rate, 0.0);
+        assert_eq!(score.latency_trend, 1.0);
+    }
+
+    #[test]
+    fn failures_lower_the_score() {
+        let score = HealthScore::from_records(&[
+            probe(10, true),
+            probe(10, false),
+            probe(10, true),
+            probe(10, false),
+        ])
+        .unwrap();
+
+        assert_eq!(score.error_rate, 0.5);
+        assert_eq!(score.score, 0.5);
+    }
+
+    #[test]
+    fn slowing_down_lowers_the_score() {
+        let score = HealthScore::from_records(&[
+            probe(10, true),
+            probe(10, true),
+            probe(40, true),
+            probe(40, true),
+        ])
+        .unwrap();
+
+        assert_eq!(score.latency_trend, 4.0);
+        assert_eq!(score.score, 0.25);
+    }
+
+    #[test]
+    fn getting_faster_is_not_rewarded_beyond_one() {
+        let score = HealthScore::from_records(&[probe(40, true), probe(10, true)]).unwrap();
+
+        assert_eq!(score.score, 1.0);
+    }
+
+    #[test]
+    fn no_records_means_no_score() {
+        assert_eq!(HealthScore::from_records(&[]), None);
+    }
+}
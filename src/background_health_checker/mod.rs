@@ -1,2 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod background_health_checker;
+pub mod health_history;
+pub mod health_score;
 pub mod timed_background_health_checker;
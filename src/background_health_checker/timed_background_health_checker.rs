@@ -1,26 +1,45 @@
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures::future::join_all;
 use std::{
     sync::{Arc, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{Semaphore, watch},
+    time,
 };
-use tokio::time;
 use tracing::{error, info, warn};
 
 use crate::{
-    background_health_checker::background_health_checker::BackgroundChecker,
+    admin::admin_router::HEALTHY_SERVERS_PATH,
+    background_health_checker::{
+        background_health_checker::BackgroundChecker,
+        health_history::{HealthHistory, ProbeRecord},
+    },
+    health_notifier::health_notifier::{HealthEvent, HealthNotifier},
     http_client::{
         http_client::HttpClient,
         request::{Request, RequestHeaders, RequestMethod},
     },
+    leader_election::leader_election::LeaderElection,
 };
 
 pub struct TimedBackgroundChecker {
     http_client: Arc<dyn HttpClient>,
-    all_servers: Vec<String>,
+    all_servers: RwLock<Vec<String>>,
     healthy_servers: Arc<RwLock<Vec<String>>>,
     health_endpoint: String,
     polling_interval: Duration,
+    /// Number of healthy servers after the last round, none before the first.
+    round_completed: watch::Sender<Option<usize>>,
+    health_history: Arc<HealthHistory>,
+    leader_election: Option<Arc<dyn LeaderElection>>,
+    probe_concurrency: usize,
+    health_notifier: Option<Arc<dyn HealthNotifier>>,
+    probe_timeout: Duration,
+    dependencies: Vec<String>,
+    initially_healthy: bool,
 }
 
 impl TimedBackgroundChecker {
@@ -29,52 +48,438 @@ impl TimedBackgroundChecker {
         servers: Vec<String>,
         health_endpoint: String,
         polling_interval: Duration,
+        health_history_size: usize,
     ) -> Self {
         let healthy_servers = Arc::new(RwLock::new(servers.clone()));
         Self {
             http_client,
-            all_servers: servers,
+            all_servers: RwLock::new(servers),
             healthy_servers,
             health_endpoint,
             polling_interval,
+            round_completed: watch::Sender::new(None),
+            health_history: Arc::new(HealthHistory::new(health_history_size)),
+            leader_election: None,
+            probe_concurrency: 1,
+            health_notifier: None,
+            probe_timeout: Duration::from_secs(5),
+            dependencies: Vec::new(),
+            initially_healthy: true,
+        }
+    }
+
+    /// Servers receive no traffic until their first successful probe.
+    pub fn with_servers_initially_unhealthy(mut self) -> Self {
+        self.initially_healthy = false;
+        if let Ok(mut healthy_servers) = self.healthy_servers.write() {
+            healthy_servers.clear();
         }
+        self
+    }
+
+    /// A probe taking longer than this marks the server as unhealthy.
+    pub fn with_probe_timeout(mut self, probe_timeout: Duration) -> Self {
+        self.probe_timeout = probe_timeout;
+        self
+    }
+
+    /// Maximum number of servers probed at the same time.
+    pub fn with_probe_concurrency(mut self, probe_concurrency: usize) -> Self {
+        self.probe_concurrency = probe_concurrency.max(1);
+        self
+    }
+
+    /// Only the elected leader probes the servers, followers copy its
+    /// healthy set instead.
+    pub fn with_leader_election(mut self, leader_election: Arc<dyn LeaderElection>) -> Self {
+        self.leader_election = Some(leader_election);
+        self
+    }
+
+    /// Health URLs of external dependencies, e.g. a database: while any of
+    /// them is failing the whole pool is considered down.
+    pub fn with_dependencies(mut self, dependencies: Vec<String>) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    /// Notified whenever a server goes up or down, or no server is healthy.
+    pub fn with_health_notifier(mut self, health_notifier: Arc<dyn HealthNotifier>) -> Self {
+        self.health_notifier = Some(health_notifier);
+        self
+    }
+
+    /// Starts from the health `previous` last found for the servers both
+    /// probe, and from its history, when both probe the same endpoint, e.g.
+    /// to replace the checker of a reloaded pool without forgetting it.
+    pub fn with_health_of(self, previous: &TimedBackgroundChecker) -> Self {
+        if previous.health_endpoint != self.health_endpoint {
+            return self;
+        }
+
+        let previous_servers = previous.all_servers();
+        let previously_healthy = previous
+            .healthy_servers
+            .read()
+            .map(|healthy_servers| healthy_servers.clone())
+            .unwrap_or_default();
+
+        if let Ok(mut healthy_servers) = self.healthy_servers.write() {
+            *healthy_servers = self
+                .all_servers()
+                .into_iter()
+                .filter(|server| match previous_servers.contains(server) {
+                    true => previously_healthy.contains(server),
+                    false => healthy_servers.contains(server),
+                })
+                .collect();
+        }
+
+        Self {
+            health_history: previous.get_health_history(),
+            ..self
+        }
+    }
+
+    /// Probes `servers` from the next round on. Those already probed keep
+    /// their health, the others start with the initial one.
+    pub fn set_servers(&self, servers: Vec<String>) {
+        let previous_servers = self.all_servers();
+
+        if let Ok(mut healthy_servers) = self.healthy_servers.write() {
+            let kept = servers
+                .iter()
+                .filter(|server| match previous_servers.contains(server) {
+                    true => healthy_servers.contains(server),
+                    false => self.initially_healthy,
+                })
+                .cloned()
+                .collect();
+            *healthy_servers = kept;
+        }
+
+        if let Ok(mut all_servers) = self.all_servers.write() {
+            *all_servers = servers;
+        }
+    }
+
+    fn all_servers(&self) -> Vec<String> {
+        self.all_servers
+            .read()
+            .map(|all_servers| all_servers.clone())
+            .unwrap_or_default()
     }
 
     pub fn get_healthy_servers(&self) -> Arc<RwLock<Vec<String>>> {
         Arc::clone(&self.healthy_servers)
     }
 
+    pub fn get_health_history(&self) -> Arc<HealthHistory> {
+        Arc::clone(&self.health_history)
+    }
+
+    pub async fn wait_for_first_round(&self) {
+        let mut round_completed = self.round_completed.subscribe();
+
+        if round_completed
+            .wait_for(|healthy| healthy.is_some())
+            .await
+            .is_err()
+        {
+            warn!("Background checker stopped before completing the first round");
+        }
+    }
+
+    /// Returns once a round ends with at least `min_healthy` healthy servers,
+    /// or every server when there are fewer of them. The servers still
+    /// unhealthy are logged after every round falling short.
+    pub async fn wait_for_healthy_servers(&self, min_healthy: usize) {
+        let min_healthy = min_healthy.min(self.all_servers().len());
+        let mut round_completed = self.round_completed.subscribe();
+
+        loop {
+            if let Some(healthy) = *round_completed.borrow_and_update() {
+                if healthy >= min_healthy {
+                    return;
+                }
+                info!(
+                    "{} of {} servers healthy, waiting for {}, unhealthy: {}",
+                    healthy,
+                    self.all_servers().len(),
+                    min_healthy,
+                    self.get_unhealthy_servers().join(", ")
+                );
+            }
+
+            if round_completed.changed().await.is_err() {
+                warn!(
+                    "Background checker stopped before {} servers were healthy",
+                    min_healthy
+                );
+                return;
+            }
+        }
+    }
+
+    /// The servers the last round found unhealthy.
+    pub fn get_unhealthy_servers(&self) -> Vec<String> {
+        let healthy_servers = self
+            .healthy_servers
+            .read()
+            .map(|healthy_servers| healthy_servers.clone())
+            .unwrap_or_default();
+
+        self.all_servers()
+            .into_iter()
+            .filter(|server| !healthy_servers.contains(server))
+            .collect()
+    }
+
     async fn is_server_healthy(&self, server: &str) -> bool {
+        self.is_healthy(server, format!("{}{}", server, self.health_endpoint))
+            .await
+    }
+
+    /// Probes `url` and records the outcome under `server` in the history.
+    async fn is_healthy(&self, server: &str, url: String) -> bool {
         let request = Request {
             method: RequestMethod::Get,
-            url: format!("{}{}", server, self.health_endpoint),
+            url,
             headers: RequestHeaders::default(),
-            body: Bytes::new(),
+            body: Bytes::new().into(),
+            timeout: None,
         };
 
-        match tokio::time::timeout(Duration::from_secs(5), self.http_client.execute(request)).await
-        {
+        let started_at = Instant::now();
+        let result =
+            tokio::time::timeout(self.probe_timeout, self.http_client.execute(request)).await;
+        let latency = started_at.elapsed();
+
+        let (status, healthy) = match result {
             Ok(Ok(response)) => {
                 if response.status == 200 {
-                    true
+                    (Some(response.status), true)
                 } else {
                     warn!(
                         "Server {} returned unhealthy status: {}",
                         server, response.status
                     );
-                    false
+                    (Some(response.status), false)
                 }
             }
             Ok(Err(error)) => {
                 warn!("Server {} failed health check: {}", server, error);
-                false
+                (None, false)
             }
             Err(_) => {
                 warn!("Server {} health check timed out", server);
+                (None, false)
+            }
+        };
+
+        self.health_history
+            .record(server, ProbeRecord::new(latency, status, healthy));
+
+        healthy
+    }
+
+    async fn probe<'a>(&self, semaphore: &Semaphore, server: &'a String) -> (&'a String, bool) {
+        let _permit = semaphore.acquire().await;
+
+        (server, self.is_server_healthy(server).await)
+    }
+
+    async fn are_dependencies_healthy(&self, semaphore: &Semaphore) -> bool {
+        let probes = join_all(self.dependencies.iter().map(|dependency| async move {
+            let _permit = semaphore.acquire().await;
+
+            self.is_healthy(dependency, dependency.clone()).await
+        }))
+        .await;
+
+        probes.into_iter().all(|healthy| healthy)
+    }
+
+    async fn check_all_servers(&self) {
+        let all_servers = self.all_servers();
+        if all_servers.is_empty() {
+            warn!("No servers configured to check");
+            return;
+        }
+
+        info!("Checking health of {} servers", all_servers.len());
+
+        let semaphore = Semaphore::new(self.probe_concurrency);
+
+        let dependencies_healthy = self.are_dependencies_healthy(&semaphore).await;
+
+        let probes = join_all(
+            all_servers
+                .iter()
+                .map(|server| self.probe(&semaphore, server)),
+        )
+        .await;
+
+        let mut new_healthy_servers = Vec::new();
+
+        for (server, healthy) in probes {
+            if healthy {
+                new_healthy_servers.push(server.clone());
+                info!("✓ Server {} is healthy", server);
+            } else {
+                info!("✖ Server {} is unhealthy", server);
+            }
+        }
+
+        if !dependencies_healthy {
+            warn!("A dependency of the pool is unhealthy, treating every server as down");
+            new_healthy_servers.clear();
+        }
+
+        let events = self.update_healthy_servers(new_healthy_servers);
+        self.notify(events).await;
+    }
+
+    async fn notify(&self, events: Vec<HealthEvent>) {
+        let Some(health_notifier) = &self.health_notifier else {
+            return;
+        };
+
+        for event in events {
+            if let Err(error) = health_notifier.notify(&event).await {
+                warn!("Failed to notify {:?}: {}", event, error);
+            }
+        }
+    }
+
+    async fn sync_from_leader(&self) -> bool {
+        let Some(leader_election) = &self.leader_election else {
+            return false;
+        };
+
+        if leader_election.is_leader() {
+            return false;
+        }
+
+        let leader_address = match leader_election.leader_address().await {
+            Ok(Some(leader_address)) => leader_address,
+            Ok(None) => {
+                warn!("No leader elected, probing servers locally");
+                return false;
+            }
+            Err(error) => {
+                warn!(
+                    "Failed to find the leader, probing servers locally: {}",
+                    error
+                );
+                return false;
+            }
+        };
+
+        // A leader that stopped answering mustn't hold the followers' rounds.
+        let leader_healthy_servers = match time::timeout(
+            self.probe_timeout,
+            self.fetch_healthy_servers(&leader_address),
+        )
+        .await
+        {
+            Ok(leader_healthy_servers) => leader_healthy_servers,
+            Err(_) => Err(format!("timed out after {:?}", self.probe_timeout)),
+        };
+
+        match leader_healthy_servers {
+            Ok(leader_healthy_servers) => {
+                info!("Synced healthy servers from leader {}", leader_address);
+
+                // The leader already notified about these transitions.
+                self.update_healthy_servers(
+                    self.all_servers()
+                        .into_iter()
+                        .filter(|server| leader_healthy_servers.contains(server))
+                        .collect(),
+                );
+                true
+            }
+            Err(error) => {
+                warn!(
+                    "Failed to sync from leader {}, probing servers locally: {}",
+                    leader_address, error
+                );
                 false
             }
         }
     }
+
+    async fn fetch_healthy_servers(&self, leader_address: &str) -> Result<Vec<String>, String> {
+        let request = Request {
+            method: RequestMethod::Get,
+            url: format!("{}{}", leader_address, HEALTHY_SERVERS_PATH),
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        match self.http_client.execute(request).await {
+            Ok(response) if response.status == 200 => match response.body.collect().await {
+                Ok(body) => {
+                    serde_json::from_slice::<Vec<String>>(&body).map_err(|error| error.to_string())
+                }
+                Err(error) => Err(error.to_string()),
+            },
+            Ok(response) => Err(format!("unexpected status {}", response.status)),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    /// Replaces the healthy set and returns the transitions it caused.
+    fn update_healthy_servers(&self, new_healthy_servers: Vec<String>) -> Vec<HealthEvent> {
+        match self.healthy_servers.write() {
+            Ok(mut guard) => {
+                let mut events: Vec<HealthEvent> = new_healthy_servers
+                    .iter()
+                    .filter(|server| !guard.contains(server))
+                    .map(|server| HealthEvent::ServerUp {
+                        server: server.clone(),
+                    })
+                    .chain(
+                        guard
+                            .iter()
+                            .filter(|server| !new_healthy_servers.contains(server))
+                            .map(|server| HealthEvent::ServerDown {
+                                server: server.clone(),
+                            }),
+                    )
+                    .collect();
+
+                let previously_healthy = guard.len();
+                *guard = new_healthy_servers;
+                let currently_healthy = guard.len();
+
+                if currently_healthy != previously_healthy {
+                    info!(
+                        "Health status changed: {} → {} healthy servers",
+                        previously_healthy, currently_healthy
+                    );
+                }
+
+                info!("Current healthy servers: {:#?}", *guard);
+
+                if guard.is_empty() {
+                    error!("No healthy servers available!");
+
+                    if previously_healthy > 0 {
+                        events.push(HealthEvent::NoHealthyServers);
+                    }
+                }
+
+                events
+            }
+            Err(error) => {
+                error!("Failed to update healthy servers list: {}", error);
+                Vec::new()
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -84,10 +489,11 @@ impl BackgroundChecker for TimedBackgroundChecker {
             "Starting timed background checker with {:?} polling interval",
             self.polling_interval
         );
+        let all_servers = self.all_servers();
         info!(
             "Monitoring {} servers: {:?}",
-            self.all_servers.len(),
-            self.all_servers
+            all_servers.len(),
+            all_servers
         );
 
         let mut interval = time::interval(self.polling_interval);
@@ -95,47 +501,16 @@ impl BackgroundChecker for TimedBackgroundChecker {
         loop {
             interval.tick().await;
 
-            if self.all_servers.is_empty() {
-                warn!("No servers configured to check");
-                continue;
+            if !self.sync_from_leader().await {
+                self.check_all_servers().await;
             }
 
-            info!("Checking health of {} servers", self.all_servers.len());
-
-            let mut new_healthy_servers = Vec::new();
-
-            for server in self.all_servers.iter() {
-                if self.is_server_healthy(server).await {
-                    new_healthy_servers.push(server.clone());
-                    info!("✓ Server {} is healthy", server);
-                } else {
-                    info!("✖ Server {} is unhealthy", server);
-                }
-            }
-
-            match self.healthy_servers.write() {
-                Ok(mut guard) => {
-                    let previously_healthy = guard.len();
-                    *guard = new_healthy_servers;
-                    let currently_healthy = guard.len();
-
-                    if currently_healthy != previously_healthy {
-                        info!(
-                            "Health status changed: {} → {} healthy servers",
-                            previously_healthy, currently_healthy
-                        );
-                    }
-
-                    info!("Current healthy servers: {:#?}", *guard);
-
-                    if guard.is_empty() {
-                        error!("No healthy servers available!");
-                    }
-                }
-                Err(error) => {
-                    error!("Failed to update healthy servers list: {}", error);
-                }
-            }
+            let healthy = self
+                .healthy_servers
+                .read()
+                .map(|healthy_servers| healthy_servers.len())
+                .unwrap_or_default();
+            self.round_completed.send_replace(Some(healthy));
         }
     }
 }
@@ -143,15 +518,44 @@ impl BackgroundChecker for TimedBackgroundChecker {
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::Duration;
 
+    use async_trait::async_trait;
     use bytes::Bytes;
 
+    use crate::background_health_checker::background_health_checker::BackgroundChecker;
     use crate::background_health_checker::timed_background_health_checker::TimedBackgroundChecker;
+    use crate::health_notifier::health_notifier::{HealthEvent, MockHealthNotifier};
     use crate::http_client::error::Error;
     use crate::http_client::http_client::{HttpClient, MockHttpClient};
-    use crate::http_client::request::RequestHeaders;
+    use crate::http_client::request::{Request, RequestHeaders};
     use crate::http_client::response::Response;
+    use crate::leader_election::leader_election::MockLeaderElection;
+
+    #[derive(Default)]
+    struct SlowHttpClient {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HttpClient for SlowHttpClient {
+        async fn execute(&self, _request: Request) -> Result<Response, Error> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+
+            tokio::time::sleep(Duration::from_millis(20)).await;
+
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(Response {
+                status: 200,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        }
+    }
 
     fn make_timed_background_checker(
         http_client: Arc<dyn HttpClient>,
@@ -162,6 +566,7 @@ mod tests {
             servers,
             "/health".to_string(),
             Duration::from_millis(100),
+            10,
         )
     }
 
@@ -172,14 +577,14 @@ mod tests {
             Ok(Response {
                 status: 200,
                 headers: RequestHeaders::default(),
-                body: Bytes::new(),
+                body: Bytes::new().into(),
             })
         });
 
         let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
         let checker = make_timed_background_checker(Arc::new(mock), servers.clone());
 
-        for server in &checker.all_servers {
+        for server in &checker.all_servers() {
             assert!(checker.is_server_healthy(server).await);
         }
 
@@ -197,7 +602,7 @@ mod tests {
                 Ok(Response {
                     status: 200,
                     headers: RequestHeaders::default(),
-                    body: Bytes::new(),
+                    body: Bytes::new().into(),
                 })
             });
 
@@ -207,7 +612,7 @@ mod tests {
                 Ok(Response {
                     status: 503,
                     headers: RequestHeaders::default(),
-                    body: Bytes::new(),
+                    body: Bytes::new().into(),
                 })
             });
 
@@ -226,7 +631,7 @@ mod tests {
             Ok(Response {
                 status: 503,
                 headers: RequestHeaders::default(),
-                body: Bytes::new(),
+                body: Bytes::new().into(),
             })
         });
 
@@ -234,7 +639,7 @@ mod tests {
             Ok(Response {
                 status: 200,
                 headers: RequestHeaders::default(),
-                body: Bytes::new(),
+                body: Bytes::new().into(),
             })
         });
 
@@ -265,14 +670,14 @@ mod tests {
             Ok(Response {
                 status: 500,
                 headers: RequestHeaders::default(),
-                body: Bytes::new(),
+                body: Bytes::new().into(),
             })
         });
 
         let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
         let checker = make_timed_background_checker(Arc::new(mock), servers.clone());
 
-        for server in &checker.all_servers {
+        for server in &checker.all_servers() {
             assert!(!checker.is_server_healthy(server).await);
         }
 
@@ -286,7 +691,7 @@ mod tests {
         let mock = MockHttpClient::new();
         let checker = make_timed_background_checker(Arc::new(mock), vec![]);
 
-        assert_eq!(checker.all_servers.len(), 0);
+        assert_eq!(checker.all_servers().len(), 0);
         let healthy = checker.healthy_servers.read().unwrap();
         assert_eq!(healthy.len(), 0);
     }
@@ -302,7 +707,7 @@ mod tests {
                 Ok(Response {
                     status: 200,
                     headers: RequestHeaders::default(),
-                    body: Bytes::new(),
+                    body: Bytes::new().into(),
                 })
             });
 
@@ -311,4 +716,458 @@ mod tests {
 
         checker.is_server_healthy("http://server1").await;
     }
+
+    #[tokio::test]
+    async fn check_all_servers_keeps_only_healthy_ones() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|req| {
+            Ok(Response {
+                status: if req.url.contains("server1") {
+                    200
+                } else {
+                    503
+                },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = make_timed_background_checker(Arc::new(mock), servers);
+
+        checker.check_all_servers().await;
+
+        let healthy = checker.healthy_servers.read().unwrap();
+        assert_eq!(*healthy, vec!["http://server1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn wait_for_first_round_returns_once_a_round_is_completed() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|_| {
+            Ok(Response {
+                status: 200,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string()];
+        let checker = Arc::new(make_timed_background_checker(Arc::new(mock), servers));
+
+        let background_checker = Arc::clone(&checker);
+        tokio::spawn(async move { background_checker.execute().await });
+
+        tokio::time::timeout(Duration::from_secs(1), checker.wait_for_first_round())
+            .await
+            .expect("first round was never completed");
+    }
+
+    #[tokio::test]
+    async fn wait_for_healthy_servers_returns_once_enough_servers_are_healthy() {
+        let server2_up = Arc::new(AtomicUsize::new(0));
+        let probes = Arc::clone(&server2_up);
+
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(move |req| {
+            let healthy = req.url.contains("server1") || probes.fetch_add(1, Ordering::SeqCst) >= 2;
+
+            Ok(Response {
+                status: if healthy { 200 } else { 503 },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = Arc::new(
+            make_timed_background_checker(Arc::new(mock), servers)
+                .with_servers_initially_unhealthy(),
+        );
+
+        let background_checker = Arc::clone(&checker);
+        tokio::spawn(async move { background_checker.execute().await });
+
+        tokio::time::timeout(Duration::from_secs(5), checker.wait_for_healthy_servers(2))
+            .await
+            .expect("servers never became healthy");
+
+        assert!(server2_up.load(Ordering::SeqCst) >= 3);
+        assert_eq!(checker.healthy_servers.read().unwrap().len(), 2);
+        assert!(checker.get_unhealthy_servers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unhealthy_servers_are_the_ones_the_last_round_failed() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|req| {
+            Ok(Response {
+                status: if req.url.contains("server1") {
+                    200
+                } else {
+                    503
+                },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = Arc::new(
+            make_timed_background_checker(Arc::new(mock), servers)
+                .with_servers_initially_unhealthy(),
+        );
+
+        let background_checker = Arc::clone(&checker);
+        tokio::spawn(async move { background_checker.execute().await });
+
+        let waited = tokio::time::timeout(
+            Duration::from_millis(500),
+            checker.wait_for_healthy_servers(2),
+        )
+        .await;
+
+        assert!(waited.is_err());
+        assert_eq!(checker.get_unhealthy_servers(), vec!["http://server2"]);
+    }
+
+    #[tokio::test]
+    async fn probes_are_recorded_in_the_health_history() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|req| {
+            Ok(Response {
+                status: if req.url.contains("server1") {
+                    200
+                } else {
+                    503
+                },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = make_timed_background_checker(Arc::new(mock), servers);
+
+        checker.check_all_servers().await;
+        checker.check_all_servers().await;
+
+        let history = checker.get_health_history().snapshot();
+
+        let server1 = &history["http://server1"];
+        assert_eq!(server1.len(), 2);
+        assert!(server1.iter().all(|record| record.healthy));
+
+        let server2 = &history["http://server2"];
+        assert_eq!(server2.len(), 2);
+        assert!(server2.iter().all(|record| record.status == Some(503)));
+    }
+
+    #[tokio::test]
+    async fn followers_copy_the_healthy_servers_of_the_leader() {
+        let mut http_client = MockHttpClient::new();
+        http_client
+            .expect_execute()
+            .withf(|req| req.url == "http://leader:3000/admin/healthy-servers")
+            .times(1)
+            .returning(|_| {
+                Ok(Response {
+                    status: 200,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::from(r#"["http://server2","http://unknown"]"#).into(),
+                })
+            });
+
+        let mut leader_election = MockLeaderElection::new();
+        leader_election.expect_is_leader().return_const(false);
+        leader_election
+            .expect_leader_address()
+            .returning(|| Ok(Some("http://leader:3000".to_string())));
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = make_timed_background_checker(Arc::new(http_client), servers)
+            .with_leader_election(Arc::new(leader_election));
+
+        assert!(checker.sync_from_leader().await);
+
+        let healthy = checker.healthy_servers.read().unwrap();
+        assert_eq!(*healthy, vec!["http://server2".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn leader_probes_the_servers_itself() {
+        let mut leader_election = MockLeaderElection::new();
+        leader_election.expect_is_leader().return_const(true);
+
+        let checker = make_timed_background_checker(
+            Arc::new(MockHttpClient::new()),
+            vec!["http://server1".to_string()],
+        )
+        .with_leader_election(Arc::new(leader_election));
+
+        assert!(!checker.sync_from_leader().await);
+    }
+
+    #[tokio::test]
+    async fn followers_probe_locally_when_the_leader_is_unreachable() {
+        let mut http_client = MockHttpClient::new();
+        http_client
+            .expect_execute()
+            .returning(|_| Err(Error::Network("Connection refused".to_string())));
+
+        let mut leader_election = MockLeaderElection::new();
+        leader_election.expect_is_leader().return_const(false);
+        leader_election
+            .expect_leader_address()
+            .returning(|| Ok(Some("http://leader:3000".to_string())));
+
+        let checker = make_timed_background_checker(
+            Arc::new(http_client),
+            vec!["http://server1".to_string()],
+        )
+        .with_leader_election(Arc::new(leader_election));
+
+        assert!(!checker.sync_from_leader().await);
+    }
+
+    #[tokio::test]
+    async fn followers_probe_locally_when_the_leader_is_too_slow() {
+        let mut leader_election = MockLeaderElection::new();
+        leader_election.expect_is_leader().return_const(false);
+        leader_election
+            .expect_leader_address()
+            .returning(|| Ok(Some("http://leader:3000".to_string())));
+
+        let checker = make_timed_background_checker(
+            Arc::new(SlowHttpClient::default()),
+            vec!["http://server1".to_string()],
+        )
+        .with_probe_timeout(Duration::from_millis(5))
+        .with_leader_election(Arc::new(leader_election));
+
+        assert!(!checker.sync_from_leader().await);
+    }
+
+    #[tokio::test]
+    async fn probes_run_concurrently_up_to_the_limit() {
+        let http_client = Arc::new(SlowHttpClient::default());
+
+        let servers = (0..8).map(|i| format!("http://server{}", i)).collect();
+        let checker = make_timed_background_checker(Arc::clone(&http_client) as _, servers)
+            .with_probe_concurrency(3);
+
+        checker.check_all_servers().await;
+
+        assert_eq!(http_client.max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(checker.healthy_servers.read().unwrap().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn probes_are_sequential_by_default() {
+        let http_client = Arc::new(SlowHttpClient::default());
+
+        let servers = (0..3).map(|i| format!("http://server{}", i)).collect();
+        let checker = make_timed_background_checker(Arc::clone(&http_client) as _, servers);
+
+        checker.check_all_servers().await;
+
+        assert_eq!(http_client.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn servers_can_start_unhealthy_until_probed() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|_| {
+            Ok(Response {
+                status: 200,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string()];
+        let checker = make_timed_background_checker(Arc::new(mock), servers.clone())
+            .with_servers_initially_unhealthy();
+
+        assert!(checker.get_healthy_servers().read().unwrap().is_empty());
+
+        checker.check_all_servers().await;
+
+        assert_eq!(*checker.get_healthy_servers().read().unwrap(), servers);
+    }
+
+    #[tokio::test]
+    async fn health_transitions_are_notified() {
+        let mut http_client = MockHttpClient::new();
+        http_client.expect_execute().returning(|req| {
+            Ok(Response {
+                status: if req.url.contains("server1") {
+                    200
+                } else {
+                    503
+                },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let mut health_notifier = MockHealthNotifier::new();
+        health_notifier
+            .expect_notify()
+            .withf(|event| {
+                *event
+                    == HealthEvent::ServerDown {
+                        server: "http://server2".to_string(),
+                    }
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = make_timed_background_checker(Arc::new(http_client), servers)
+            .with_health_notifier(Arc::new(health_notifier));
+
+        checker.check_all_servers().await;
+        checker.check_all_servers().await;
+    }
+
+    #[tokio::test]
+    async fn losing_every_server_is_notified() {
+        let mut http_client = MockHttpClient::new();
+        http_client
+            .expect_execute()
+            .returning(|_| Err(Error::Network("Connection refused".to_string())));
+
+        let mut health_notifier = MockHealthNotifier::new();
+        health_notifier
+            .expect_notify()
+            .withf(|event| matches!(event, HealthEvent::ServerDown { .. }))
+            .times(1)
+            .returning(|_| Ok(()));
+        health_notifier
+            .expect_notify()
+            .withf(|event| *event == HealthEvent::NoHealthyServers)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let servers = vec!["http://server1".to_string()];
+        let checker = make_timed_background_checker(Arc::new(http_client), servers)
+            .with_health_notifier(Arc::new(health_notifier));
+
+        checker.check_all_servers().await;
+        checker.check_all_servers().await;
+    }
+
+    #[tokio::test]
+    async fn failing_dependency_takes_the_whole_pool_down() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|req| {
+            Ok(Response {
+                status: if req.url == "http://database/health" {
+                    503
+                } else {
+                    200
+                },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = make_timed_background_checker(Arc::new(mock), servers)
+            .with_dependencies(vec!["http://database/health".to_string()]);
+
+        checker.check_all_servers().await;
+
+        assert!(checker.healthy_servers.read().unwrap().is_empty());
+        assert_eq!(
+            checker.get_health_history().snapshot()["http://database/health"][0].status,
+            Some(503)
+        );
+    }
+
+    #[tokio::test]
+    async fn healthy_dependencies_keep_the_pool_available() {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|_| {
+            Ok(Response {
+                status: 200,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let servers = vec!["http://server1".to_string()];
+        let checker = make_timed_background_checker(Arc::new(mock), servers)
+            .with_dependencies(vec!["http://database/health".to_string()]);
+
+        checker.check_all_servers().await;
+
+        assert_eq!(
+            *checker.healthy_servers.read().unwrap(),
+            vec!["http://server1".to_string()]
+        );
+    }
+
+    fn server1_healthy_client() -> MockHttpClient {
+        let mut mock = MockHttpClient::new();
+        mock.expect_execute().returning(|req| {
+            Ok(Response {
+                status: if req.url.contains("server1") {
+                    200
+                } else {
+                    503
+                },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+        mock
+    }
+
+    #[tokio::test]
+    async fn replaced_checkers_start_from_the_health_found_before() {
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let previous = make_timed_background_checker(Arc::new(server1_healthy_client()), servers);
+        previous.check_all_servers().await;
+
+        let checker = make_timed_background_checker(
+            Arc::new(MockHttpClient::new()),
+            vec![
+                "http://server1".to_string(),
+                "http://server2".to_string(),
+                "http://server3".to_string(),
+            ],
+        )
+        .with_health_of(&previous);
+
+        assert_eq!(
+            *checker.healthy_servers.read().unwrap(),
+            vec!["http://server1".to_string(), "http://server3".to_string()]
+        );
+        assert_eq!(checker.get_health_history().snapshot().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn set_servers_keeps_the_health_of_the_servers_kept() {
+        let servers = vec!["http://server1".to_string(), "http://server2".to_string()];
+        let checker = make_timed_background_checker(Arc::new(server1_healthy_client()), servers)
+            .with_servers_initially_unhealthy();
+        checker.check_all_servers().await;
+
+        checker.set_servers(vec![
+            "http://server2".to_string(),
+            "http://server1".to_string(),
+            "http://server3".to_string(),
+        ]);
+
+        assert_eq!(
+            *checker.healthy_servers.read().unwrap(),
+            vec!["http://server1".to_string()]
+        );
+        assert_eq!(
+            checker.get_unhealthy_servers(),
+            vec!["http://server2".to_string(), "http://server3".to_string()]
+        );
+    }
 }
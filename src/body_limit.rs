@@ -0,0 +1,200 @@
+use std::error::Error as StdError;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::{Body, HttpBody};
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use http::{HeaderValue, StatusCode, header};
+use http_body_util::LengthLimitError;
+use hyper::body::Frame;
+use tracing::warn;
+
+/// Longest a client is given to finish sending an oversize body.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a streamed request body going over the limit midway is answered.
+/// Bodies announcing their size upfront are refused before being read.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BodyLimitAction {
+    /// Answers 413 right away and closes the connection.
+    #[default]
+    Abort,
+    /// Reads what is left of the body before answering 413, so that the
+    /// client gets the response and the connection can be reused.
+    Drain,
+}
+
+/// Watches a streamed request body for going over the limit, while keeping
+/// hold of it so that it can be drained once the upstream request failed.
+pub struct BodyOverflow {
+    body: Arc<Mutex<Body>>,
+    overflowed: Arc<AtomicBool>,
+}
+
+struct WatchedBody {
+    body: Arc<Mutex<Body>>,
+    overflowed: Arc<AtomicBool>,
+}
+
+impl BodyOverflow {
+    pub fn watch(body: Body) -> (Body, Self) {
+        let body = Arc::new(Mutex::new(body));
+        let overflowed = Arc::new(AtomicBool::new(false));
+
+        let watched = WatchedBody {
+            body: Arc::clone(&body),
+            overflowed: Arc::clone(&overflowed),
+        };
+
+        (Body::new(watched), Self { body, overflowed })
+    }
+
+    pub fn overflowed(&self) -> bool {
+        self.overflowed.load(Ordering::Relaxed)
+    }
+
+    pub async fn reject(self, action: BodyLimitAction) -> Response {
+        let drained = action == BodyLimitAction::Drain
+            && tokio::time::timeout(DRAIN_TIMEOUT, self.drain())
+                .await
+                .is_ok();
+
+        if !drained {
+            warn!("Closing the connection of a client sending an oversize body");
+
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                [(header::CONNECTION, HeaderValue::from_static("close"))],
+            )
+                .into_response();
+        }
+
+        StatusCode::PAYLOAD_TOO_LARGE.into_response()
+    }
+
+    async fn drain(&self) {
+        while futures::future::poll_fn(|cx| match self.body.lock() {
+            Ok(mut body) => Pin::new(&mut *body).poll_frame(cx),
+            Err(_) => Poll::Ready(None),
+        })
+        .await
+        .is_some()
+        {}
+    }
+}
+
+/// Passes every frame through, trailers included.
+impl HttpBody for WatchedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let Ok(mut body) = self.body.lock() else {
+            return Poll::Ready(None);
+        };
+
+        let next = Pin::new(&mut *body).poll_frame(cx);
+
+        if let Poll::Ready(Some(Err(error))) = &next
+            && is_length_limit_error(error)
+        {
+            self.overflowed.store(true, Ordering::Relaxed);
+        }
+
+        next
+    }
+}
+
+fn is_length_limit_error(error: &axum::Error) -> bool {
+    let mut source: Option<&(dyn StdError + 'static)> = error.source();
+
+    while let Some(error) = source {
+        if error.is::<LengthLimitError>() {
+            return true;
+        }
+        source = error.source();
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, to_bytes};
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue, StatusCode, header};
+    use http_body_util::{BodyExt, Limited, StreamBody};
+    use hyper::body::Frame;
+
+    use crate::body_limit::{BodyLimitAction, BodyOverflow};
+
+    fn oversize_body() -> Body {
+        let chunks = futures::stream::iter(
+            ["1234", "5678", "9"].map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk))),
+        );
+
+        Body::new(Limited::new(Body::from_stream(chunks), 8).map_err(axum::Error::new))
+    }
+
+    #[tokio::test]
+    async fn notices_a_body_going_over_the_limit() {
+        let (body, overflow) = BodyOverflow::watch(oversize_body());
+
+        assert!(to_bytes(body, usize::MAX).await.is_err());
+        assert!(overflow.overflowed());
+    }
+
+    #[tokio::test]
+    async fn ignores_other_body_errors() {
+        let chunks = futures::stream::iter([Err::<Bytes, _>(std::io::Error::other("reset"))]);
+        let (body, overflow) = BodyOverflow::watch(Body::from_stream(chunks));
+
+        assert!(to_bytes(body, usize::MAX).await.is_err());
+        assert!(!overflow.overflowed());
+    }
+
+    #[tokio::test]
+    async fn aborting_closes_the_connection() {
+        let (_, overflow) = BodyOverflow::watch(oversize_body());
+
+        let response = overflow.reject(BodyLimitAction::Abort).await;
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()[header::CONNECTION], "close");
+    }
+
+    #[tokio::test]
+    async fn draining_keeps_the_connection_open() {
+        let (body, overflow) = BodyOverflow::watch(oversize_body());
+        assert!(to_bytes(body, usize::MAX).await.is_err());
+
+        let response = overflow.reject(BodyLimitAction::Drain).await;
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(response.headers().get(header::CONNECTION).is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+
+        let (body, overflow) =
+            BodyOverflow::watch(Body::new(StreamBody::new(futures::stream::iter([
+                Ok::<_, std::io::Error>(Frame::data(Bytes::from("1234"))),
+                Ok(Frame::trailers(trailers.clone())),
+            ]))));
+
+        let collected = body.collect().await.unwrap();
+
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert!(!overflow.overflowed());
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use axum::body::{Body, HttpBody};
+use axum::response::Response;
+use bytes::Bytes;
+use hyper::body::{Frame, SizeHint};
+
+/// Caps the requests in flight to each backend, so that a struggling one
+/// isn't buried under more requests than it can take: saturated backends are
+/// skipped until some of their requests are over.
+#[derive(Debug, Default)]
+pub struct Bulkheads {
+    /// Cap of the backends without one of their own, `None` for unlimited.
+    default_cap: Option<usize>,
+    caps: HashMap<String, usize>,
+    in_flight: Mutex<HashMap<String, usize>>,
+}
+
+impl Bulkheads {
+    pub fn new(default_cap: Option<usize>, caps: HashMap<String, usize>) -> Self {
+        Self {
+            default_cap,
+            caps,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cap(&self, server: &str) -> Option<usize> {
+        self.caps.get(server).copied().or(self.default_cap)
+    }
+
+    /// Takes a slot of the backend for a request, `None` while it is
+    /// saturated.
+    pub fn acquire(self: &Arc<Self>, server: &str) -> Option<BulkheadPermit> {
+        let Some(cap) = self.cap(server) else {
+            return Some(BulkheadPermit { held: None });
+        };
+        let mut in_flight = self.in_flight.lock().ok()?;
+
+        let requests = in_flight.entry(server.to_string()).or_default();
+        if *requests >= cap {
+            return None;
+        }
+        *requests += 1;
+
+        Some(BulkheadPermit {
+            held: Some((Arc::clone(self), server.to_string())),
+        })
+    }
+
+    pub fn in_flight(&self, server: &str) -> usize {
+        self.in_flight
+            .lock()
+            .ok()
+            .and_then(|in_flight| in_flight.get(server).copied())
+            .unwrap_or_default()
+    }
+}
+
+/// Slot of a backend, given back when dropped.
+pub struct BulkheadPermit {
+    held: Option<(Arc<Bulkheads>, String)>,
+}
+
+impl BulkheadPermit {
+    /// Keeps the slot until the response body is over, the backend being
+    /// busy with the request until then.
+    pub fn hold_until_sent(self, response: Response) -> Response {
+        match self.held {
+            Some(_) => response.map(|body| {
+                Body::new(PermitBody {
+                    body,
+                    permit: Some(self),
+                })
+            }),
+            None => response,
+        }
+    }
+}
+
+impl Drop for BulkheadPermit {
+    fn drop(&mut self) {
+        if let Some((bulkheads, server)) = &self.held
+            && let Ok(mut in_flight) = bulkheads.in_flight.lock()
+            && let Some(requests) = in_flight.get_mut(server)
+        {
+            *requests = requests.saturating_sub(1);
+        }
+    }
+}
+
+struct PermitBody {
+    body: Body,
+    permit: Option<BulkheadPermit>,
+}
+
+impl HttpBody for PermitBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        let frame = Pin::new(&mut this.body).poll_frame(cx);
+        if matches!(frame, Poll::Ready(None | Some(Err(_)))) {
+            this.permit = None;
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use axum::body::{Body, to_bytes};
+    use axum::response::Response;
+
+    use crate::bulkhead::Bulkheads;
+
+    #[test]
+    fn skips_saturated_backends_until_a_request_is_over() {
+        let bulkheads = Arc::new(Bulkheads::new(Some(2), HashMap::new()));
+
+        let first = bulkheads.acquire("server1").unwrap();
+        let _second = bulkheads.acquire("server1").unwrap();
+
+        assert!(bulkheads.acquire("server1").is_none());
+        assert!(bulkheads.acquire("server2").is_some());
+
+        drop(first);
+
+        assert!(bulkheads.acquire("server1").is_some());
+    }
+
+    #[test]
+    fn caps_of_backends_override_the_default_one() {
+        let bulkheads = Arc::new(Bulkheads::new(
+            None,
+            HashMap::from([("server1".to_string(), 1)]),
+        ));
+
+        let _permit = bulkheads.acquire("server1").unwrap();
+
+        assert!(bulkheads.acquire("server1").is_none());
+        let uncapped = (0..100)
+            .map(|_| bulkheads.acquire("server2"))
+            .collect::<Option<Vec<_>>>();
+        assert!(uncapped.is_some());
+    }
+
+    #[tokio::test]
+    async fn holds_the_slot_until_the_response_body_is_sent() {
+        let bulkheads = Arc::new(Bulkheads::new(Some(1), HashMap::new()));
+
+        let permit = bulkheads.acquire("server1").unwrap();
+        let response = permit.hold_until_sent(Response::new(Body::from("hello")));
+
+        assert_eq!(bulkheads.in_flight("server1"), 1);
+
+        to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        assert_eq!(bulkheads.in_flight("server1"), 0);
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::Url;
+use tracing::warn;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Expiry dates of the certificates presented by the HTTPS backends, so that
+/// an upstream certificate about to expire is noticed before the outage.
+pub struct CertificateExpiries {
+    not_after: RwLock<HashMap<String, i64>>,
+    warning_days: i64,
+}
+
+impl CertificateExpiries {
+    pub fn new(warning_days: i64) -> Self {
+        Self {
+            not_after: RwLock::new(HashMap::new()),
+            warning_days,
+        }
+    }
+
+    /// Records the DER certificate presented by the server behind `url`.
+    pub fn record(&self, url: &str, certificate: &[u8]) {
+        let (Some(origin), Ok((_, certificate))) =
+            (origin(url), X509Certificate::from_der(certificate))
+        else {
+            return;
+        };
+
+        let not_after = certificate.validity().not_after.timestamp();
+
+        let Ok(mut expiries) = self.not_after.write() else {
+            return;
+        };
+
+        if expiries.insert(origin.clone(), not_after) == Some(not_after) {
+            return;
+        }
+
+        let days_to_expiry = days_until(not_after);
+        if days_to_expiry <= self.warning_days {
+            warn!(
+                "The certificate of {} expires in {} days",
+                origin, days_to_expiry
+            );
+        }
+    }
+
+    pub fn days_to_expiry(&self, server: &str) -> Option<i64> {
+        let not_after = *self.not_after.read().ok()?.get(&origin(server)?)?;
+
+        Some(days_until(not_after))
+    }
+}
+
+impl Default for CertificateExpiries {
+    fn default() -> Self {
+        Self::new(14)
+    }
+}
+
+fn origin(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+
+    (url.scheme() == "https").then(|| url.origin().ascii_serialization())
+}
+
+fn days_until(timestamp: i64) -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+
+    (timestamp - now).div_euclid(SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::certificate_expiries::{CertificateExpiries, days_until, origin};
+
+    #[test]
+    fn servers_are_identified_by_their_https_origin() {
+        assert_eq!(
+            origin("https://backend:8443/v1/api?x=1"),
+            Some("https://backend:8443".to_string())
+        );
+        assert_eq!(
+            origin("https://backend:443"),
+            Some("https://backend".to_string())
+        );
+        assert_eq!(origin("http://backend:8080"), None);
+    }
+
+    #[test]
+    fn counts_whole_days_left() {
+        let in_ten_days_and_a_bit = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+            + 10 * 24 * 60 * 60
+            + 60;
+
+        assert_eq!(days_until(in_ten_days_and_a_bit), 10);
+    }
+
+    #[test]
+    fn records_the_expiry_of_the_certificate() {
+        let mut params = rcgen::CertificateParams::new(vec!["backend".to_string()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2100, 1, 1);
+        let certificate = params
+            .self_signed(&rcgen::KeyPair::generate().unwrap())
+            .unwrap();
+
+        let certificate_expiries = CertificateExpiries::default();
+        certificate_expiries.record("https://backend:8443/health", certificate.der());
+
+        let days_to_expiry = certificate_expiries
+            .days_to_expiry("https://backend:8443")
+            .unwrap();
+
+        assert!(days_to_expiry > 365 * 50);
+        assert_eq!(
+            certificate_expiries.days_to_expiry("https://other:8443"),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_what_is_not_a_certificate() {
+        let certificate_expiries = CertificateExpiries::default();
+
+        certificate_expiries.record("https://backend:8443", b"not a certificate");
+
+        assert_eq!(
+            certificate_expiries.days_to_expiry("https://backend:8443"),
+            None
+        );
+    }
+}
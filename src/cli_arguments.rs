@@ -1,124 +1,2196 @@
-use clap::{Parser, ValueEnum, command};
+use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(ValueEnum, Debug, Clone, PartialEq)]
+use clap::{Parser, Subcommand, ValueEnum};
+use http::Method;
+use serde::{Serialize, Serializer};
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
 #[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
 pub(crate) enum RoutingPolicy {
     RoundRobin,
     Random,
 }
 
-#[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None)]
-pub(crate) struct CliArguments {
-    #[arg(short, long, default_value = "3000")]
-    pub(crate) port: u16,
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum UpstreamHttpVersion {
+    Auto,
+    Http1,
+    Http2,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum InitialHealth {
+    Healthy,
+    Unhealthy,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum StateStoreKind {
+    Memory,
+    File,
+    Redis,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum TlsProfileKind {
+    Modern,
+    Intermediate,
+    Old,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+pub(crate) enum TlsMinVersion {
+    #[value(name = "1.2")]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[value(name = "1.3")]
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UnmatchedStatus {
+    #[value(name = "404")]
+    #[serde(rename = "404")]
+    NotFound,
+    #[value(name = "421")]
+    #[serde(rename = "421")]
+    MisdirectedRequest,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub(crate) enum Command {
+    /// Validate the configuration without starting the load balancer
+    Check,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+pub(crate) enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ClientAuthMode {
+    None,
+    Optional,
+    Required,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum BodyLimitActionKind {
+    Abort,
+    Drain,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum CostBudgetActionKind {
+    Reject,
+    Throttle,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum HostHeaderKind {
+    Preserve,
+    Upstream,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum RangeRequestsKind {
+    Pass,
+    Reject,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Clone, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum UpstreamDecodingKind {
+    Passthrough,
+    Recompress,
+}
+
+/// Parses a `TENANT=COST` pair.
+fn parse_cost_budget(value: &str) -> Result<(String, u64), String> {
+    let (tenant, cost) = value
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected TENANT=COST, got {}", value))?;
+    let cost = cost
+        .parse()
+        .map_err(|_| format!("invalid cost in {}", value))?;
+
+    Ok((tenant.to_string(), cost))
+}
+
+/// Parses a `BACKEND=MILLIS` pair.
+fn parse_backend_timeout(value: &str) -> Result<(String, u64), String> {
+    let (backend, millis) = value
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected BACKEND=MILLIS, got {}", value))?;
+    let millis = millis
+        .parse()
+        .map_err(|_| format!("invalid timeout in {}", value))?;
+
+    Ok((backend.to_string(), millis))
+}
+
+fn parse_backend_max_in_flight(value: &str) -> Result<(String, usize), String> {
+    let (backend, count) = value
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected BACKEND=COUNT, got {}", value))?;
+    let count = count
+        .parse()
+        .map_err(|_| format!("invalid count in {}", value))?;
+
+    Ok((backend.to_string(), count))
+}
+
+/// Parses a `DOMAIN=CERT:KEY` triple, the domain being a server name or a
+/// wildcard covering one label, e.g. `*.example.com`.
+pub(crate) fn parse_sni_certificate(value: &str) -> Result<(String, PathBuf, PathBuf), String> {
+    let invalid = || format!("expected DOMAIN=CERT:KEY, got {}", value);
+
+    let (domain, files) = value.split_once('=').ok_or_else(invalid)?;
+    let (certificate, key) = files.split_once(':').ok_or_else(invalid)?;
+    let name = domain.strip_prefix("*.").unwrap_or(domain);
+
+    if name.is_empty() || name.contains('*') || certificate.is_empty() || key.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok((domain.to_ascii_lowercase(), certificate.into(), key.into()))
+}
+
+/// A target server with its settings, written as `URL[;weight=N]`, e.g.
+/// `http://api-1:8080;weight=3`. The weight is its share of the requests
+/// relative to the other target servers, `1` when not given.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct Backend {
+    #[serde(serialize_with = "redact_backend_credentials")]
+    pub(crate) url: String,
+    pub(crate) weight: u32,
+}
+
+impl Backend {
+    pub(crate) fn new(url: &str) -> Self {
+        Backend {
+            url: url.to_string(),
+            weight: 1,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected URL[;weight=N], got {}", value);
+
+        let mut options = value.split(';');
+        let url = options.next().map(str::trim).unwrap_or_default();
+        if url.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut backend = Backend::new(url);
+
+        for option in options {
+            match option.trim().split_once('=') {
+                Some(("weight", weight)) => {
+                    backend.weight = weight
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|weight| *weight > 0)
+                        .ok_or_else(invalid)?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(backend)
+    }
+}
+
+pub(crate) fn parse_method(value: &str) -> Result<Method, String> {
+    Method::from_bytes(value.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("invalid method {}", value))
+}
+
+const REDACTED: &str = "redacted";
+
+fn serialize_methods<S: Serializer>(methods: &[Method], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(methods.iter().map(Method::as_str))
+}
+
+/// Shows that a secret is set without showing it.
+fn redact_secret<S: Serializer>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    value.as_ref().map(|_| REDACTED).serialize(serializer)
+}
+
+/// Whether the URL embeds a user or a password, e.g.
+/// `redis://:s3cr3t@cache:6379`.
+fn has_credentials(value: &str) -> bool {
+    url::Url::parse(value).is_ok_and(|url| !url.username().is_empty() || url.password().is_some())
+}
+
+/// Shows a URL unless it embeds credentials, in which case it is redacted
+/// altogether, like a value that isn't a URL.
+fn redact_url_credentials<S: Serializer>(
+    value: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let redacted = value.as_deref().map(|value| {
+        match url::Url::parse(value).is_ok() && !has_credentials(value) {
+            true => value,
+            false => REDACTED,
+        }
+    });
+
+    redacted.serialize(serializer)
+}
+
+/// Shows the URL of a backend unless it embeds credentials.
+fn redact_backend_credentials<S: Serializer>(
+    value: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match has_credentials(value) {
+        true => REDACTED.serialize(serializer),
+        false => value.serialize(serializer),
+    }
+}
+
+/// The arguments serialize into the effective configuration, the secrets
+/// being redacted and the one-off modes left out.
+#[derive(Parser, Serialize, Debug, Clone)]
+#[command(version, about, long_about = None)]
+pub(crate) struct CliArguments {
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub(crate) command: Option<Command>,
+
+    #[arg(long, global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    #[arg(short, long, default_value = "3000")]
+    pub(crate) port: u16,
+
+    #[clap(short, long, value_parser, num_args = 1.., value_delimiter = ',')]
+    pub(crate) target_servers: Vec<Backend>,
+
+    #[arg(long, conflicts_with = "target_servers")]
+    pub(crate) kubernetes_service: Option<String>,
+
+    #[arg(long, default_value = "5", requires = "kubernetes_service")]
+    pub(crate) kubernetes_retry_seconds: u64,
+
+    #[arg(long, default_value = "http://127.0.0.1:8500")]
+    pub(crate) consul_address: String,
+
+    #[arg(long, env = "WAKANDA_CONSUL_TOKEN", hide_env_values = true)]
+    #[serde(serialize_with = "redact_secret")]
+    pub(crate) consul_token: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_CONSUL_TOKEN_FILE",
+        conflicts_with = "consul_token"
+    )]
+    pub(crate) consul_token_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "30")]
+    pub(crate) srv_polling_seconds: u64,
+
+    #[clap(short, long, value_enum, default_value = "round-robin")]
+    pub(crate) routing_policy: RoutingPolicy,
+
+    #[arg(long, default_value = "/health")]
+    pub(crate) target_servers_health_path: String,
+
+    #[arg(long, default_value = "10")]
+    pub(crate) health_checker_polling_seconds: u64,
+
+    #[arg(long)]
+    pub(crate) wait_for_first_health_check: bool,
+
+    #[arg(long, default_value = "0")]
+    pub(crate) min_healthy_backends: usize,
+
+    #[arg(long, default_value = "300")]
+    pub(crate) min_healthy_backends_timeout_seconds: u64,
+
+    #[arg(long, default_value = "10")]
+    pub(crate) health_history_size: usize,
+
+    #[arg(long, requires = "advertise_address")]
+    pub(crate) leader_lease_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "15", value_parser = clap::value_parser!(u64).range(3..))]
+    pub(crate) leader_lease_seconds: u64,
+
+    #[arg(long)]
+    pub(crate) advertise_address: Option<String>,
+
+    #[arg(long, requires = "leader_lease_file")]
+    pub(crate) coordinated_rollout: bool,
+
+    #[arg(long, default_value = "10")]
+    pub(crate) rollout_polling_seconds: u64,
+
+    #[arg(long, default_value = "60")]
+    pub(crate) rollout_bake_seconds: u64,
+
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub(crate) rollout_max_error_percent: u8,
+
+    #[arg(long)]
+    pub(crate) health_check_ca_cert: Option<PathBuf>,
+
+    #[arg(long)]
+    pub(crate) health_check_sni: Option<String>,
+
+    #[arg(long)]
+    pub(crate) health_check_via_proxy: bool,
+
+    #[arg(long, env = "WAKANDA_UPSTREAM_PROXY", hide_env_values = true)]
+    #[serde(serialize_with = "redact_url_credentials")]
+    pub(crate) upstream_proxy: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_UPSTREAM_PROXY_FILE",
+        conflicts_with = "upstream_proxy"
+    )]
+    pub(crate) upstream_proxy_file: Option<PathBuf>,
+
+    #[arg(long)]
+    pub(crate) upstream_ca_cert: Option<PathBuf>,
+
+    #[arg(long)]
+    pub(crate) upstream_insecure_skip_verify: bool,
+
+    #[arg(long)]
+    pub(crate) upstream_sni: Option<String>,
+
+    #[arg(long, requires = "upstream_client_key")]
+    pub(crate) upstream_client_cert: Option<PathBuf>,
+
+    #[arg(long, requires = "upstream_client_cert")]
+    pub(crate) upstream_client_key: Option<PathBuf>,
+
+    #[arg(long, default_value = "10")]
+    pub(crate) upstream_client_identity_reload_seconds: u64,
+
+    #[arg(long)]
+    pub(crate) upstream_no_proxy: Option<String>,
+
+    #[arg(long)]
+    pub(crate) metrics_snapshot_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "60")]
+    pub(crate) metrics_snapshot_seconds: u64,
+
+    #[arg(long, default_value = "16", value_parser = clap::value_parser!(u16).range(1..))]
+    pub(crate) health_check_concurrency: u16,
+
+    #[arg(long)]
+    pub(crate) tenant_header: Option<String>,
+
+    #[arg(long, default_value = "3600")]
+    pub(crate) usage_bucket_seconds: u64,
+
+    #[arg(long, default_value = "24")]
+    pub(crate) usage_retention_buckets: usize,
+
+    #[clap(long, value_enum, default_value = "healthy")]
+    pub(crate) initial_health: InitialHealth,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value = "memory",
+        requires_if("redis", "state_store_url_source")
+    )]
+    pub(crate) state_store: StateStoreKind,
+
+    #[arg(long, required_if_eq("state_store", "file"))]
+    pub(crate) state_store_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_STATE_STORE_URL",
+        hide_env_values = true,
+        group = "state_store_url_source"
+    )]
+    #[serde(serialize_with = "redact_url_credentials")]
+    pub(crate) state_store_url: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_STATE_STORE_URL_FILE",
+        group = "state_store_url_source"
+    )]
+    pub(crate) state_store_url_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+    pub(crate) acceptors: u16,
+
+    #[arg(long, env = "WAKANDA_HEALTH_WEBHOOK_URL", hide_env_values = true)]
+    #[serde(serialize_with = "redact_secret")]
+    pub(crate) health_webhook_url: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_HEALTH_WEBHOOK_URL_FILE",
+        conflicts_with = "health_webhook_url"
+    )]
+    pub(crate) health_webhook_url_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "2000")]
+    pub(crate) health_check_timeout_ms: u64,
+
+    #[clap(long, value_parser, num_args = 1.., value_delimiter = ',')]
+    pub(crate) dependency_health_urls: Vec<String>,
+
+    #[arg(long, default_value = "0")]
+    pub(crate) retries: u8,
+
+    #[arg(long)]
+    pub(crate) propagate_retry_after: bool,
+
+    #[clap(long, value_parser = parse_method, num_args = 1.., value_delimiter = ',', default_value = "GET,HEAD,PUT,DELETE")]
+    #[serde(serialize_with = "serialize_methods")]
+    pub(crate) retry_methods: Vec<Method>,
+
+    #[clap(long, value_parser = parse_method, num_args = 1.., value_delimiter = ',', default_value = "POST")]
+    #[serde(serialize_with = "serialize_methods")]
+    pub(crate) retry_idempotency_key_methods: Vec<Method>,
+
+    #[arg(long)]
+    pub(crate) trust_forwarded_headers: bool,
+
+    #[clap(long, num_args = 1.., value_delimiter = ',')]
+    pub(crate) trusted_proxies: Vec<String>,
+
+    #[arg(long)]
+    pub(crate) emit_forwarded_header: bool,
+
+    #[arg(long, default_value = "0")]
+    pub(crate) quarantine_seconds: u64,
+
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub(crate) quarantine_traffic_percent: u8,
+
+    #[arg(long, default_value = "0", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub(crate) decision_record_sample_percent: u8,
+
+    #[arg(long)]
+    pub(crate) decision_record_header: bool,
+
+    #[arg(long)]
+    pub(crate) http10_compat: bool,
+
+    #[arg(long, default_value = "5000")]
+    pub(crate) upstream_connect_timeout_ms: u64,
+
+    #[arg(long, default_value = "30000")]
+    pub(crate) upstream_timeout_ms: u64,
+
+    #[arg(long, default_value = "90")]
+    pub(crate) upstream_idle_timeout_seconds: u64,
+
+    #[clap(long, value_parser = parse_backend_timeout, num_args = 1.., value_delimiter = ',')]
+    pub(crate) backend_timeouts: Vec<(String, u64)>,
+
+    #[clap(long, value_parser = parse_method, num_args = 1.., value_delimiter = ',')]
+    #[serde(serialize_with = "serialize_methods")]
+    pub(crate) allowed_methods: Vec<Method>,
+
+    #[arg(long = "time-rule")]
+    pub(crate) time_rules: Vec<String>,
+
+    #[arg(long, default_value = "+00:00", allow_hyphen_values = true)]
+    pub(crate) time_rules_utc_offset: String,
+
+    #[clap(long, value_enum, default_value = "auto")]
+    pub(crate) upstream_http_version: UpstreamHttpVersion,
+
+    #[arg(long)]
+    pub(crate) request_queue_time: bool,
+
+    #[arg(long)]
+    pub(crate) deadline_propagation: bool,
+
+    #[arg(long)]
+    pub(crate) coalesce_requests: bool,
+
+    #[arg(long)]
+    pub(crate) response_cache_entries: Option<usize>,
+
+    #[arg(long, default_value = "1048576")]
+    pub(crate) response_cache_max_body_bytes: u64,
+
+    #[arg(long)]
+    pub(crate) upstream_compression: bool,
+
+    #[clap(long, value_enum, default_value = "passthrough")]
+    pub(crate) upstream_decoding: UpstreamDecodingKind,
+
+    #[arg(long)]
+    pub(crate) response_compression: bool,
+
+    #[arg(long, default_value = "1024")]
+    pub(crate) response_compression_min_bytes: u16,
+
+    #[clap(
+        long,
+        value_parser,
+        num_args = 1..,
+        value_delimiter = ',',
+        default_value = "text/,application/json,application/javascript,application/xml,image/svg+xml"
+    )]
+    pub(crate) response_compression_content_types: Vec<String>,
+
+    #[arg(long, default_value = "14")]
+    pub(crate) certificate_expiry_warning_days: u16,
+
+    #[arg(long)]
+    pub(crate) max_request_body_bytes: Option<usize>,
+
+    #[arg(long, requires = "tls_key")]
+    pub(crate) tls_cert: Option<PathBuf>,
+
+    #[arg(long, requires = "tls_cert")]
+    pub(crate) tls_key: Option<PathBuf>,
+
+    #[arg(long, default_value = "10")]
+    pub(crate) tls_reload_seconds: u64,
+
+    #[clap(long = "tls-sni-cert", value_parser = parse_sni_certificate, num_args = 1.., value_delimiter = ',')]
+    pub(crate) tls_sni_certs: Vec<(String, PathBuf, PathBuf)>,
+
+    #[arg(long)]
+    pub(crate) upstream_max_idle_per_host: Option<usize>,
+
+    #[arg(long, default_value = "15")]
+    pub(crate) upstream_tcp_keepalive_seconds: u64,
+
+    #[arg(long, default_value = "0")]
+    pub(crate) upstream_http2_keepalive_seconds: u64,
+
+    #[clap(long, value_enum, default_value = "intermediate")]
+    pub(crate) tls_profile: TlsProfileKind,
+
+    #[clap(long, value_enum)]
+    pub(crate) tls_min_version: Option<TlsMinVersion>,
+
+    #[clap(long, value_parser, num_args = 1.., value_delimiter = ',')]
+    pub(crate) tls_cipher_suites: Vec<String>,
+
+    #[clap(long, value_enum, default_value = "none")]
+    pub(crate) tls_client_auth: ClientAuthMode,
+
+    #[arg(long)]
+    pub(crate) tls_client_ca: Option<PathBuf>,
+
+    #[arg(long)]
+    pub(crate) tls_ocsp_stapling: bool,
+
+    #[arg(long, default_value = "3600")]
+    pub(crate) tls_ocsp_refresh_seconds: u64,
+
+    #[arg(long = "client-cert-allow")]
+    pub(crate) client_cert_allow: Vec<String>,
+
+    #[arg(long = "client-cert-route")]
+    pub(crate) client_cert_routes: Vec<String>,
+
+    #[clap(long, value_parser, num_args = 1.., value_delimiter = ',')]
+    pub(crate) client_cert_headers: Vec<String>,
+
+    #[clap(long, value_parser, num_args = 1.., value_delimiter = ',')]
+    pub(crate) mirror_servers: Vec<String>,
+
+    #[arg(long, default_value = "100", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub(crate) mirror_percent: u8,
+
+    #[arg(long, default_value = "64")]
+    pub(crate) mirror_max_in_flight: usize,
+
+    #[arg(long)]
+    pub(crate) served_by_header: bool,
+
+    #[arg(long)]
+    pub(crate) rewrite_redirects: bool,
+
+    #[clap(long, value_enum, default_value = "abort")]
+    pub(crate) body_limit_action: BodyLimitActionKind,
+
+    #[arg(long = "error-page")]
+    pub(crate) error_pages: Vec<String>,
+
+    #[arg(long, conflicts_with = "fallback_body")]
+    pub(crate) fallback_file: Option<String>,
+
+    #[arg(long)]
+    pub(crate) fallback_body: Option<String>,
+
+    #[arg(long, default_value = "503")]
+    pub(crate) fallback_status: u16,
+
+    #[arg(long)]
+    pub(crate) fallback_content_type: Option<String>,
+
+    #[arg(long)]
+    pub(crate) dev: bool,
+
+    #[arg(long = "route-rule")]
+    pub(crate) route_rules: Vec<String>,
+
+    #[arg(long = "cost-budget", value_parser = parse_cost_budget)]
+    pub(crate) cost_budgets: Vec<(String, u64)>,
+
+    #[arg(long, default_value = "3600")]
+    pub(crate) cost_budget_window_seconds: u64,
+
+    #[clap(long, value_enum, default_value = "reject")]
+    pub(crate) cost_budget_action: CostBudgetActionKind,
+
+    #[arg(long, default_value = "1000")]
+    pub(crate) cost_throttle_delay_ms: u64,
+
+    #[clap(long, value_enum, default_value = "preserve")]
+    pub(crate) host_header: HostHeaderKind,
+
+    #[arg(long = "path-rule")]
+    pub(crate) path_rules: Vec<String>,
+
+    #[arg(long = "pool")]
+    pub(crate) pools: Vec<String>,
+
+    #[arg(long = "listener")]
+    pub(crate) listeners: Vec<String>,
+
+    #[arg(long)]
+    pub(crate) admin_listener: Option<String>,
+
+    #[arg(long)]
+    pub(crate) geoip_database: Option<PathBuf>,
+
+    #[arg(long = "blue-green")]
+    pub(crate) blue_greens: Vec<String>,
+
+    #[arg(long = "pool-route")]
+    pub(crate) pool_routes: Vec<String>,
+
+    #[arg(long)]
+    pub(crate) default_pool: Option<String>,
+
+    #[clap(long, value_enum, conflicts_with = "default_pool")]
+    pub(crate) unmatched_status: Option<UnmatchedStatus>,
+
+    #[arg(long)]
+    #[serde(skip)]
+    pub(crate) match_route: Option<String>,
+
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "json")]
+    #[serde(skip)]
+    pub(crate) print_config: Option<ConfigFormat>,
+
+    #[arg(long = "grpc-timeout")]
+    pub(crate) grpc_timeouts: Vec<String>,
+
+    #[arg(long)]
+    pub(crate) experiment: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_EXPERIMENT_SALT",
+        hide_env_values = true,
+        requires = "experiment"
+    )]
+    #[serde(serialize_with = "redact_secret")]
+    pub(crate) experiment_salt: Option<String>,
+
+    #[arg(
+        long,
+        env = "WAKANDA_EXPERIMENT_SALT_FILE",
+        requires = "experiment",
+        conflicts_with = "experiment_salt"
+    )]
+    pub(crate) experiment_salt_file: Option<PathBuf>,
+
+    #[arg(long, requires = "experiment")]
+    pub(crate) experiment_cookie: Option<String>,
+
+    #[arg(long = "request-transform")]
+    pub(crate) request_transforms: Vec<String>,
+
+    #[clap(long, value_enum, default_value = "pass")]
+    pub(crate) range_requests: RangeRequestsKind,
+
+    #[arg(long, default_value = "60")]
+    pub(crate) downstream_idle_timeout_seconds: u64,
+
+    #[arg(long, default_value = "30")]
+    pub(crate) downstream_read_timeout_seconds: u64,
+
+    #[arg(long, default_value = "10")]
+    pub(crate) downstream_head_timeout_seconds: u64,
+
+    #[arg(long, default_value = "0")]
+    pub(crate) downstream_max_requests: u64,
+
+    #[arg(long, default_value = "0")]
+    pub(crate) downstream_max_connection_age_seconds: u64,
+
+    #[arg(long)]
+    pub(crate) max_in_flight_per_backend: Option<usize>,
+
+    #[clap(long, value_parser = parse_backend_max_in_flight, num_args = 1.., value_delimiter = ',')]
+    pub(crate) backend_max_in_flight: Vec<(String, usize)>,
+
+    #[arg(long, env = "WAKANDA_ADMIN_TOKEN", hide_env_values = true)]
+    #[serde(serialize_with = "redact_secret")]
+    pub(crate) admin_token: Option<String>,
+
+    #[arg(long, env = "WAKANDA_ADMIN_TOKEN_FILE", conflicts_with = "admin_token")]
+    pub(crate) admin_token_file: Option<PathBuf>,
+
+    #[arg(long)]
+    pub(crate) allow_readable_private_keys: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+    use http::Method;
+
+    use crate::cli_arguments::{
+        Backend, BodyLimitActionKind, CliArguments, ClientAuthMode, Command, ConfigFormat,
+        CostBudgetActionKind, HostHeaderKind, InitialHealth, RangeRequestsKind, RoutingPolicy,
+        StateStoreKind, TlsMinVersion, TlsProfileKind, UnmatchedStatus, UpstreamDecodingKind,
+        UpstreamHttpVersion,
+    };
+
+    #[test]
+    fn test_cli_arguments_long_flags() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "--port",
+            "3000",
+            "--target-servers",
+            "http://localhost:9000,http://localhost:9001",
+            "--routing-policy",
+            "random",
+            "--target-servers-health-path",
+            "/ready",
+            "--health-checker-polling-seconds",
+            "10",
+            "--wait-for-first-health-check",
+            "--health-history-size",
+            "20",
+            "--health-check-ca-cert",
+            "/etc/wakanda/ca.pem",
+            "--health-check-sni",
+            "api.internal",
+            "--health-check-via-proxy",
+            "--upstream-proxy",
+            "socks5h://proxy.corp:1080",
+            "--upstream-no-proxy",
+            "localhost,.internal",
+            "--upstream-ca-cert",
+            "/etc/wakanda/internal-ca.crt",
+            "--upstream-insecure-skip-verify",
+            "--upstream-sni",
+            "api.internal",
+            "--upstream-client-cert",
+            "/etc/wakanda/client.crt",
+            "--upstream-client-key",
+            "/etc/wakanda/client.key",
+            "--upstream-client-identity-reload-seconds",
+            "60",
+            "--metrics-snapshot-file",
+            "/var/lib/wakanda/metrics.json",
+            "--metrics-snapshot-seconds",
+            "30",
+            "--health-check-concurrency",
+            "4",
+            "--tenant-header",
+            "x-api-key",
+            "--usage-bucket-seconds",
+            "60",
+            "--usage-retention-buckets",
+            "48",
+            "--initial-health",
+            "unhealthy",
+            "--state-store",
+            "file",
+            "--state-store-path",
+            "/var/lib/wakanda/state.json",
+            "--state-store-url",
+            "redis://127.0.0.1:6379",
+            "--acceptors",
+            "8",
+            "--health-webhook-url",
+            "https://hooks.slack.com/services/T000/B000/XXXX",
+            "--health-check-timeout-ms",
+            "500",
+            "--dependency-health-urls",
+            "http://database:5432/health,http://cache:6379/health",
+            "--retries",
+            "2",
+            "--propagate-retry-after",
+            "--retry-methods",
+            "get,options",
+            "--retry-idempotency-key-methods",
+            "post,patch",
+            "--trust-forwarded-headers",
+            "--emit-forwarded-header",
+            "--quarantine-seconds",
+            "300",
+            "--quarantine-traffic-percent",
+            "10",
+            "--decision-record-sample-percent",
+            "1",
+            "--decision-record-header",
+            "--http10-compat",
+            "--upstream-connect-timeout-ms",
+            "1000",
+            "--upstream-timeout-ms",
+            "10000",
+            "--upstream-idle-timeout-seconds",
+            "30",
+            "--backend-timeouts",
+            "http://localhost:8080=60000,http://localhost:8081=500",
+            "--allowed-methods",
+            "get,HEAD",
+            "--time-rule",
+            "x-traffic-class=batch@00:00-06:00>http://localhost:8080",
+            "--time-rule",
+            "x-traffic-class=report@22:00-23:00>http://localhost:8081",
+            "--time-rules-utc-offset",
+            "-05:30",
+            "--upstream-http-version",
+            "http2",
+            "--request-queue-time",
+            "--deadline-propagation",
+            "--coalesce-requests",
+            "--response-cache-entries",
+            "10000",
+            "--response-cache-max-body-bytes",
+            "65536",
+            "--upstream-compression",
+            "--response-compression",
+            "--response-compression-min-bytes",
+            "256",
+            "--response-compression-content-types",
+            "text/html,application/json",
+            "--certificate-expiry-warning-days",
+            "30",
+            "--max-request-body-bytes",
+            "10485760",
+            "--tls-cert",
+            "/etc/wakanda/tls.crt",
+            "--tls-key",
+            "/etc/wakanda/tls.key",
+            "--tls-reload-seconds",
+            "30",
+            "--tls-sni-cert",
+            "api.example.com=/etc/wakanda/api.crt:/etc/wakanda/api.key",
+            "--tls-sni-cert",
+            "*.Example.org=/etc/wakanda/org.crt:/etc/wakanda/org.key",
+            "--upstream-max-idle-per-host",
+            "32",
+            "--upstream-tcp-keepalive-seconds",
+            "60",
+            "--upstream-http2-keepalive-seconds",
+            "20",
+            "--tls-profile",
+            "modern",
+            "--tls-min-version",
+            "1.3",
+            "--tls-cipher-suites",
+            "TLS13_AES_256_GCM_SHA384,TLS13_CHACHA20_POLY1305_SHA256",
+            "--tls-client-auth",
+            "required",
+            "--tls-client-ca",
+            "/etc/wakanda/clients.crt",
+            "--tls-ocsp-stapling",
+            "--tls-ocsp-refresh-seconds",
+            "600",
+            "--client-cert-allow",
+            "ou=payments",
+            "--client-cert-allow",
+            "san=*.internal.example.com",
+            "--client-cert-route",
+            "ou=payments>http://localhost:8081",
+            "--client-cert-headers",
+            "cn,ou",
+            "--mirror-servers",
+            "http://localhost:9001,http://localhost:9002",
+            "--mirror-percent",
+            "10",
+            "--served-by-header",
+            "--min-healthy-backends",
+            "2",
+            "--rewrite-redirects",
+            "--body-limit-action",
+            "drain",
+            "--error-page",
+            "503=/srv/maintenance.html",
+            "--error-page",
+            "502=/srv/error.json",
+            "--fallback-file",
+            "/srv/maintenance.html",
+            "--fallback-status",
+            "200",
+            "--fallback-content-type",
+            "text/html",
+            "--dev",
+            "--route-rule",
+            r#"header("x-tier") == "gold"=>http://localhost:9000"#,
+            "--cost-budget",
+            "tenant-a=1000",
+            "--cost-budget",
+            "*=100",
+            "--cost-budget-window-seconds",
+            "60",
+            "--cost-budget-action",
+            "throttle",
+            "--cost-throttle-delay-ms",
+            "250",
+            "--host-header",
+            "upstream",
+            "--path-rule",
+            "/api/v1>http://localhost:9001",
+            "--pool",
+            "api=http://localhost:9001|http://localhost:9002;policy=random",
+            "--geoip-database",
+            "/var/lib/geoip/GeoLite2-Country.mmdb",
+            "--blue-green",
+            "shop=shop-blue,shop-green",
+            "--pool-route",
+            "/api/*=>api",
+            "--default-pool",
+            "api",
+            "--match-route",
+            "/api/users",
+            "--grpc-timeout",
+            "shop.v1.Reports/Export=30000",
+            "--experiment",
+            "control:50,treatment:50",
+            "--experiment-salt",
+            "checkout-2026",
+            "--experiment-cookie",
+            "session",
+            "--request-transform",
+            "set-header:X-Env=prod",
+            "--request-transform",
+            "remove-query:debug",
+            "--range-requests",
+            "reject",
+            "--upstream-decoding",
+            "recompress",
+            "--downstream-idle-timeout-seconds",
+            "120",
+            "--downstream-read-timeout-seconds",
+            "10",
+            "--downstream-head-timeout-seconds",
+            "5",
+            "--downstream-max-requests",
+            "1000",
+            "--downstream-max-connection-age-seconds",
+            "300",
+            "--max-in-flight-per-backend",
+            "100",
+            "--backend-max-in-flight",
+            "http://localhost:8080=10,http://localhost:8081=20",
+        ]);
+
+        assert_eq!(args.port, 3000);
+        assert_eq!(
+            args.target_servers,
+            Vec::from([
+                Backend::new("http://localhost:9000"),
+                Backend::new("http://localhost:9001")
+            ])
+        );
+        assert_eq!(args.routing_policy, RoutingPolicy::Random);
+        assert_eq!(args.target_servers_health_path, "/ready");
+        assert_eq!(args.health_checker_polling_seconds, 10);
+        assert!(args.wait_for_first_health_check);
+        assert_eq!(args.health_history_size, 20);
+        assert_eq!(
+            args.health_check_ca_cert,
+            Some(PathBuf::from("/etc/wakanda/ca.pem"))
+        );
+        assert_eq!(args.health_check_sni, Some("api.internal".to_string()));
+        assert!(args.health_check_via_proxy);
+        assert_eq!(
+            args.upstream_proxy,
+            Some("socks5h://proxy.corp:1080".to_string())
+        );
+        assert_eq!(
+            args.upstream_no_proxy,
+            Some("localhost,.internal".to_string())
+        );
+        assert_eq!(
+            args.upstream_ca_cert,
+            Some(PathBuf::from("/etc/wakanda/internal-ca.crt"))
+        );
+        assert!(args.upstream_insecure_skip_verify);
+        assert_eq!(args.upstream_sni, Some("api.internal".to_string()));
+        assert_eq!(
+            args.upstream_client_cert,
+            Some(PathBuf::from("/etc/wakanda/client.crt"))
+        );
+        assert_eq!(
+            args.upstream_client_key,
+            Some(PathBuf::from("/etc/wakanda/client.key"))
+        );
+        assert_eq!(args.upstream_client_identity_reload_seconds, 60);
+        assert_eq!(
+            args.metrics_snapshot_file,
+            Some(PathBuf::from("/var/lib/wakanda/metrics.json"))
+        );
+        assert_eq!(args.metrics_snapshot_seconds, 30);
+        assert_eq!(args.health_check_concurrency, 4);
+        assert_eq!(args.tenant_header, Some("x-api-key".to_string()));
+        assert_eq!(args.usage_bucket_seconds, 60);
+        assert_eq!(args.usage_retention_buckets, 48);
+        assert_eq!(args.initial_health, InitialHealth::Unhealthy);
+        assert_eq!(args.state_store, StateStoreKind::File);
+        assert_eq!(
+            args.state_store_path,
+            Some(PathBuf::from("/var/lib/wakanda/state.json"))
+        );
+        assert_eq!(
+            args.state_store_url,
+            Some("redis://127.0.0.1:6379".to_string())
+        );
+        assert_eq!(args.acceptors, 8);
+        assert_eq!(
+            args.health_webhook_url,
+            Some("https://hooks.slack.com/services/T000/B000/XXXX".to_string())
+        );
+        assert_eq!(args.health_check_timeout_ms, 500);
+        assert_eq!(
+            args.dependency_health_urls,
+            Vec::from(["http://database:5432/health", "http://cache:6379/health"])
+        );
+        assert_eq!(args.retries, 2);
+        assert!(args.propagate_retry_after);
+        assert_eq!(args.retry_methods, vec![Method::GET, Method::OPTIONS]);
+        assert_eq!(
+            args.retry_idempotency_key_methods,
+            vec![Method::POST, Method::PATCH]
+        );
+        assert!(args.trust_forwarded_headers);
+        assert!(args.emit_forwarded_header);
+        assert_eq!(args.quarantine_seconds, 300);
+        assert_eq!(args.quarantine_traffic_percent, 10);
+        assert_eq!(args.decision_record_sample_percent, 1);
+        assert!(args.decision_record_header);
+        assert!(args.http10_compat);
+        assert_eq!(args.upstream_connect_timeout_ms, 1000);
+        assert_eq!(args.upstream_timeout_ms, 10000);
+        assert_eq!(args.upstream_idle_timeout_seconds, 30);
+        assert_eq!(
+            args.backend_timeouts,
+            Vec::from([
+                ("http://localhost:8080".to_string(), 60000),
+                ("http://localhost:8081".to_string(), 500),
+            ])
+        );
+        assert_eq!(args.allowed_methods, Vec::from([Method::GET, Method::HEAD]));
+        assert_eq!(
+            args.time_rules,
+            Vec::from([
+                "x-traffic-class=batch@00:00-06:00>http://localhost:8080",
+                "x-traffic-class=report@22:00-23:00>http://localhost:8081",
+            ])
+        );
+        assert_eq!(args.time_rules_utc_offset, "-05:30");
+        assert_eq!(args.upstream_http_version, UpstreamHttpVersion::Http2);
+        assert!(args.request_queue_time);
+        assert!(args.deadline_propagation);
+        assert!(args.coalesce_requests);
+        assert_eq!(args.response_cache_entries, Some(10000));
+        assert_eq!(args.response_cache_max_body_bytes, 65536);
+        assert!(args.upstream_compression);
+        assert!(args.response_compression);
+        assert_eq!(args.response_compression_min_bytes, 256);
+        assert_eq!(
+            args.response_compression_content_types,
+            Vec::from(["text/html", "application/json"])
+        );
+        assert_eq!(args.certificate_expiry_warning_days, 30);
+        assert_eq!(args.max_request_body_bytes, Some(10485760));
+        assert_eq!(args.tls_cert, Some(PathBuf::from("/etc/wakanda/tls.crt")));
+        assert_eq!(args.tls_key, Some(PathBuf::from("/etc/wakanda/tls.key")));
+        assert_eq!(args.tls_reload_seconds, 30);
+        assert_eq!(
+            args.tls_sni_certs,
+            vec![
+                (
+                    "api.example.com".to_string(),
+                    PathBuf::from("/etc/wakanda/api.crt"),
+                    PathBuf::from("/etc/wakanda/api.key")
+                ),
+                (
+                    "*.example.org".to_string(),
+                    PathBuf::from("/etc/wakanda/org.crt"),
+                    PathBuf::from("/etc/wakanda/org.key")
+                ),
+            ]
+        );
+        assert_eq!(args.upstream_max_idle_per_host, Some(32));
+        assert_eq!(args.upstream_tcp_keepalive_seconds, 60);
+        assert_eq!(args.upstream_http2_keepalive_seconds, 20);
+        assert_eq!(args.tls_profile, TlsProfileKind::Modern);
+        assert_eq!(args.tls_min_version, Some(TlsMinVersion::Tls13));
+        assert_eq!(
+            args.tls_cipher_suites,
+            Vec::from(["TLS13_AES_256_GCM_SHA384", "TLS13_CHACHA20_POLY1305_SHA256"])
+        );
+        assert_eq!(args.tls_client_auth, ClientAuthMode::Required);
+        assert_eq!(
+            args.tls_client_ca,
+            Some(PathBuf::from("/etc/wakanda/clients.crt"))
+        );
+        assert!(args.tls_ocsp_stapling);
+        assert_eq!(args.tls_ocsp_refresh_seconds, 600);
+        assert_eq!(
+            args.client_cert_allow,
+            Vec::from(["ou=payments", "san=*.internal.example.com"])
+        );
+        assert_eq!(
+            args.client_cert_routes,
+            Vec::from(["ou=payments>http://localhost:8081"])
+        );
+        assert_eq!(args.client_cert_headers, Vec::from(["cn", "ou"]));
+        assert_eq!(
+            args.mirror_servers,
+            Vec::from(["http://localhost:9001", "http://localhost:9002"])
+        );
+        assert_eq!(args.mirror_percent, 10);
+        assert!(args.served_by_header);
+        assert_eq!(args.min_healthy_backends, 2);
+        assert!(args.rewrite_redirects);
+        assert_eq!(args.body_limit_action, BodyLimitActionKind::Drain);
+        assert_eq!(
+            args.error_pages,
+            Vec::from(["503=/srv/maintenance.html", "502=/srv/error.json"])
+        );
+        assert_eq!(
+            args.fallback_file,
+            Some("/srv/maintenance.html".to_string())
+        );
+        assert_eq!(args.fallback_status, 200);
+        assert_eq!(args.fallback_content_type, Some("text/html".to_string()));
+        assert!(args.dev);
+        assert_eq!(
+            args.route_rules,
+            Vec::from([r#"header("x-tier") == "gold"=>http://localhost:9000"#])
+        );
+        assert_eq!(
+            args.cost_budgets,
+            Vec::from([("tenant-a".to_string(), 1000), ("*".to_string(), 100)])
+        );
+        assert_eq!(args.cost_budget_window_seconds, 60);
+        assert_eq!(args.cost_budget_action, CostBudgetActionKind::Throttle);
+        assert_eq!(args.cost_throttle_delay_ms, 250);
+        assert_eq!(args.host_header, HostHeaderKind::Upstream);
+        assert_eq!(
+            args.path_rules,
+            Vec::from(["/api/v1>http://localhost:9001"])
+        );
+        assert_eq!(
+            args.pools,
+            Vec::from(["api=http://localhost:9001|http://localhost:9002;policy=random"])
+        );
+        assert_eq!(
+            args.geoip_database,
+            Some(PathBuf::from("/var/lib/geoip/GeoLite2-Country.mmdb"))
+        );
+        assert_eq!(args.blue_greens, Vec::from(["shop=shop-blue,shop-green"]));
+        assert_eq!(args.pool_routes, Vec::from(["/api/*=>api"]));
+        assert_eq!(args.default_pool, Some("api".to_string()));
+        assert_eq!(args.match_route, Some("/api/users".to_string()));
+        assert_eq!(
+            args.grpc_timeouts,
+            Vec::from(["shop.v1.Reports/Export=30000"])
+        );
+        assert_eq!(args.experiment.as_deref(), Some("control:50,treatment:50"));
+        assert_eq!(args.experiment_salt.as_deref(), Some("checkout-2026"));
+        assert_eq!(args.experiment_cookie.as_deref(), Some("session"));
+        assert_eq!(
+            args.request_transforms,
+            Vec::from(["set-header:X-Env=prod", "remove-query:debug"])
+        );
+        assert_eq!(args.range_requests, RangeRequestsKind::Reject);
+        assert_eq!(args.upstream_decoding, UpstreamDecodingKind::Recompress);
+        assert_eq!(args.downstream_idle_timeout_seconds, 120);
+        assert_eq!(args.downstream_read_timeout_seconds, 10);
+        assert_eq!(args.downstream_head_timeout_seconds, 5);
+        assert_eq!(args.downstream_max_requests, 1000);
+        assert_eq!(args.downstream_max_connection_age_seconds, 300);
+        assert_eq!(args.max_in_flight_per_backend, Some(100));
+        assert_eq!(
+            args.backend_max_in_flight,
+            Vec::from([
+                ("http://localhost:8080".to_string(), 10),
+                ("http://localhost:8081".to_string(), 20),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_cli_arguments_short_flags() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-p",
+            "3000",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+            "-r",
+            "round-robin",
+        ]);
+
+        assert_eq!(args.port, 3000);
+        assert_eq!(
+            args.target_servers,
+            Vec::from([
+                Backend::new("http://localhost:9000"),
+                Backend::new("http://localhost:9001")
+            ])
+        );
+        assert_eq!(args.routing_policy, RoutingPolicy::RoundRobin);
+    }
+
+    #[test]
+    fn routing_policy_should_default_to_round_robin() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.routing_policy, RoutingPolicy::RoundRobin);
+    }
+
+    #[test]
+    fn target_servers_health_path_should_default_to_health() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.target_servers_health_path, "/health");
+    }
+
+    #[test]
+    fn health_checker_polling_seconds_should_default_to_10() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.health_checker_polling_seconds, 10);
+    }
+
+    #[test]
+    fn port_should_default_to_3000() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.port, 3000);
+    }
+
+    #[test]
+    fn wait_for_first_health_check_should_default_to_false() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert!(!args.wait_for_first_health_check);
+    }
+
+    #[test]
+    fn health_history_size_should_default_to_10() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.health_history_size, 10);
+    }
+
+    #[test]
+    fn leader_election_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.leader_lease_file, None);
+        assert_eq!(args.leader_lease_seconds, 15);
+    }
+
+    #[test]
+    fn leader_lease_should_last_at_least_three_seconds() {
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--leader-lease-seconds",
+            "2",
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn leader_lease_file_requires_an_advertise_address() {
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--leader-lease-file",
+            "/shared/wakanda.lease",
+        ]);
+
+        assert!(result.is_err());
+
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--leader-lease-file",
+            "/shared/wakanda.lease",
+            "--advertise-address",
+            "http://10.0.0.1:3000",
+        ]);
+
+        assert_eq!(
+            args.leader_lease_file,
+            Some(PathBuf::from("/shared/wakanda.lease"))
+        );
+        assert_eq!(
+            args.advertise_address,
+            Some("http://10.0.0.1:3000".to_string())
+        );
+    }
+
+    #[test]
+    fn coordinated_rollout_requires_a_leader_lease_file() {
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--coordinated-rollout",
+        ]);
+
+        assert!(result.is_err());
+
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--coordinated-rollout",
+            "--leader-lease-file",
+            "/shared/wakanda.lease",
+            "--advertise-address",
+            "http://10.0.0.1:3000",
+        ]);
+
+        assert!(args.coordinated_rollout);
+        assert_eq!(args.rollout_polling_seconds, 10);
+        assert_eq!(args.rollout_bake_seconds, 60);
+        assert_eq!(args.rollout_max_error_percent, 5);
+    }
+
+    #[test]
+    fn health_check_concurrency_should_default_to_16_and_be_positive() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.health_check_concurrency, 16);
+
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--health-check-concurrency",
+            "0",
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn initial_health_should_default_to_healthy() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.initial_health, InitialHealth::Healthy);
+    }
+
+    #[test]
+    fn state_store_should_default_to_memory() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000,http://localhost:9001",
+        ]);
+
+        assert_eq!(args.state_store, StateStoreKind::Memory);
+    }
+
+    #[test]
+    fn file_state_store_requires_a_path() {
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--state-store",
+            "file",
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn acceptors_should_default_to_1() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.acceptors, 1);
+    }
+
+    #[test]
+    fn health_check_timeout_ms_should_default_to_2000() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.health_check_timeout_ms, 2000);
+    }
+
+    #[test]
+    fn retries_should_default_to_0() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.retries, 0);
+        assert!(!args.propagate_retry_after);
+        assert_eq!(
+            args.retry_methods,
+            vec![Method::GET, Method::HEAD, Method::PUT, Method::DELETE]
+        );
+        assert_eq!(args.retry_idempotency_key_methods, vec![Method::POST]);
+    }
+
+    #[test]
+    fn quarantine_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.quarantine_seconds, 0);
+        assert_eq!(args.quarantine_traffic_percent, 5);
+    }
+
+    #[test]
+    fn decision_records_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.decision_record_sample_percent, 0);
+        assert!(!args.decision_record_header);
+    }
+
+    #[test]
+    fn http10_compat_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.http10_compat);
+    }
+
+    #[test]
+    fn upstream_timeouts_should_have_defaults() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.upstream_connect_timeout_ms, 5000);
+        assert_eq!(args.upstream_timeout_ms, 30000);
+        assert_eq!(args.upstream_idle_timeout_seconds, 90);
+        assert!(args.backend_timeouts.is_empty());
+    }
+
+    #[test]
+    fn target_servers_take_a_weight() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "--target-servers",
+            "http://a:8080;weight=3,http://b:8080",
+        ]);
+
+        assert_eq!(
+            args.target_servers,
+            Vec::from([
+                Backend {
+                    url: "http://a:8080".to_string(),
+                    weight: 3,
+                },
+                Backend::new("http://b:8080"),
+            ])
+        );
+    }
+
+    #[test]
+    fn target_servers_reject_invalid_settings() {
+        for target_server in [
+            ";weight=3",
+            "http://a:8080;weight=0",
+            "http://a:8080;weight=heavy",
+            "http://a:8080;priority=1",
+        ] {
+            let result =
+                CliArguments::try_parse_from(["load-balancer", "--target-servers", target_server]);
+
+            assert!(result.is_err(), "{}", target_server);
+        }
+    }
+
+    #[test]
+    fn kubernetes_service_replaces_the_target_servers() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "--kubernetes-service",
+            "shop/api:http",
+            "--kubernetes-retry-seconds",
+            "2",
+        ]);
+
+        assert_eq!(args.kubernetes_service.as_deref(), Some("shop/api:http"));
+        assert_eq!(args.kubernetes_retry_seconds, 2);
+
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--kubernetes-service",
+            "shop/api",
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn backend_timeouts_require_a_timeout() {
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--backend-timeouts",
+            "http://localhost:9000",
+        ]);
+
+        assert!(result.is_err());
+    }
 
-    #[clap(short, long, value_parser, num_args = 1.., value_delimiter = ',')]
-    pub(crate) target_servers: Vec<String>,
+    #[test]
+    fn every_method_should_be_allowed_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
 
-    #[clap(short, long, value_enum, default_value = "round-robin")]
-    pub(crate) routing_policy: RoutingPolicy,
+        assert!(args.allowed_methods.is_empty());
+    }
 
-    #[arg(long, default_value = "/health")]
-    pub(crate) target_servers_health_path: String,
+    #[test]
+    fn time_rules_should_default_to_utc() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
 
-    #[arg(long, default_value = "10")]
-    pub(crate) health_checker_polling_seconds: u64,
-}
+        assert!(args.time_rules.is_empty());
+        assert_eq!(args.time_rules_utc_offset, "+00:00");
+    }
 
-#[cfg(test)]
-mod test {
-    use clap::Parser;
+    #[test]
+    fn upstream_http_version_should_default_to_auto() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
 
-    use crate::cli_arguments::{CliArguments, RoutingPolicy};
+        assert_eq!(args.upstream_http_version, UpstreamHttpVersion::Auto);
+    }
 
     #[test]
-    fn test_cli_arguments_long_flags() {
-        let args = CliArguments::parse_from([
+    fn request_queue_time_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.request_queue_time);
+    }
+
+    #[test]
+    fn deadline_propagation_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.deadline_propagation);
+    }
+
+    #[test]
+    fn request_coalescing_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.coalesce_requests);
+    }
+
+    #[test]
+    fn response_cache_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.response_cache_entries, None);
+        assert_eq!(args.response_cache_max_body_bytes, 1024 * 1024);
+    }
+
+    #[test]
+    fn upstream_compression_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.upstream_compression);
+    }
+
+    #[test]
+    fn response_compression_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.response_compression);
+        assert_eq!(args.response_compression_min_bytes, 1024);
+        assert_eq!(
+            args.response_compression_content_types,
+            Vec::from([
+                "text/",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "image/svg+xml"
+            ])
+        );
+    }
+
+    #[test]
+    fn certificate_expiry_warning_days_should_default_to_14() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.certificate_expiry_warning_days, 14);
+    }
+
+    #[test]
+    fn request_bodies_should_be_unlimited_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.max_request_body_bytes, None);
+    }
+
+    #[test]
+    fn tls_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.tls_cert, None);
+        assert_eq!(args.tls_key, None);
+        assert_eq!(args.tls_reload_seconds, 10);
+        assert!(args.tls_sni_certs.is_empty());
+    }
+
+    #[test]
+    fn tls_cert_requires_a_key() {
+        let result = CliArguments::try_parse_from([
             "load-balancer",
-            "--port",
-            "3000",
-            "--target-servers",
-            "http://localhost:9000,http://localhost:9001",
-            "--routing-policy",
-            "random",
-            "--target-servers-health-path",
-            "/ready",
-            "--health-checker-polling-seconds",
-            "10",
+            "-t",
+            "http://localhost:9000",
+            "--tls-cert",
+            "/etc/wakanda/tls.crt",
         ]);
 
-        assert_eq!(args.port, 3000);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn tls_sni_cert_should_reject_malformed_domains() {
+        for value in [
+            "api.example.com",
+            "api.example.com=/etc/wakanda/api.crt",
+            "=/etc/wakanda/api.crt:/etc/wakanda/api.key",
+            "*=/etc/wakanda/api.crt:/etc/wakanda/api.key",
+            "api.*.com=/etc/wakanda/api.crt:/etc/wakanda/api.key",
+        ] {
+            let result = CliArguments::try_parse_from([
+                "load-balancer",
+                "-t",
+                "http://localhost:9000",
+                "--tls-sni-cert",
+                value,
+            ]);
+
+            assert!(result.is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn upstream_pool_should_have_defaults() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.upstream_max_idle_per_host, None);
+        assert_eq!(args.upstream_tcp_keepalive_seconds, 15);
+        assert_eq!(args.upstream_http2_keepalive_seconds, 0);
+    }
+
+    #[test]
+    fn tls_policy_should_default_to_the_intermediate_profile() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.tls_profile, TlsProfileKind::Intermediate);
+        assert_eq!(args.tls_min_version, None);
+        assert!(args.tls_cipher_suites.is_empty());
+        assert_eq!(args.tls_client_auth, ClientAuthMode::None);
+        assert_eq!(args.tls_client_ca, None);
+    }
+
+    #[test]
+    fn ocsp_stapling_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.tls_ocsp_stapling);
+        assert_eq!(args.tls_ocsp_refresh_seconds, 3600);
+    }
+
+    #[test]
+    fn client_certificate_rules_should_be_empty_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.client_cert_allow.is_empty());
+        assert!(args.client_cert_routes.is_empty());
+        assert!(args.client_cert_headers.is_empty());
+    }
+
+    #[test]
+    fn mirroring_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.mirror_servers.is_empty());
+        assert_eq!(args.mirror_percent, 100);
+        assert_eq!(args.mirror_max_in_flight, 64);
+    }
+
+    #[test]
+    fn served_by_header_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.served_by_header);
+    }
+
+    #[test]
+    fn min_healthy_backends_should_default_to_zero() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.min_healthy_backends, 0);
+        assert_eq!(args.min_healthy_backends_timeout_seconds, 300);
+    }
+
+    #[test]
+    fn rewrite_redirects_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.rewrite_redirects);
+    }
+
+    #[test]
+    fn body_limit_action_should_default_to_abort() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.body_limit_action, BodyLimitActionKind::Abort);
+    }
+
+    #[test]
+    fn error_pages_should_be_empty_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.error_pages.is_empty());
+    }
+
+    #[test]
+    fn dev_mode_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(!args.dev);
+    }
+
+    #[test]
+    fn route_rules_should_be_empty_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.route_rules.is_empty());
+    }
+
+    #[test]
+    fn cost_budgets_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.cost_budgets.is_empty());
+        assert_eq!(args.cost_budget_window_seconds, 3600);
+        assert_eq!(args.cost_budget_action, CostBudgetActionKind::Reject);
+        assert_eq!(args.cost_throttle_delay_ms, 1000);
+    }
+
+    #[test]
+    fn host_header_should_be_preserved_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.host_header, HostHeaderKind::Preserve);
+    }
+
+    #[test]
+    fn config_file_should_be_none_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.config, None);
+    }
+
+    #[test]
+    fn parse_config_file() {
+        let args = CliArguments::parse_from(["load-balancer", "--config", "/etc/lb/wakanda.toml"]);
+
+        assert_eq!(args.config, Some(PathBuf::from("/etc/lb/wakanda.toml")));
+    }
+
+    #[test]
+    fn path_rules_should_be_empty_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.path_rules.is_empty());
+    }
+
+    #[test]
+    fn pools_should_be_empty_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.pools.is_empty());
+        assert!(args.listeners.is_empty());
+        assert!(args.blue_greens.is_empty());
+        assert_eq!(args.geoip_database, None);
+        assert!(args.pool_routes.is_empty());
+        assert_eq!(args.default_pool, None);
+        assert_eq!(args.unmatched_status, None);
+        assert_eq!(args.match_route, None);
+        assert!(args.grpc_timeouts.is_empty());
+        assert_eq!(args.experiment, None);
+        assert_eq!(args.experiment_salt, None);
+        assert_eq!(args.experiment_cookie, None);
+        assert_eq!(args.consul_address, "http://127.0.0.1:8500");
+        assert_eq!(args.consul_token, None);
+        assert_eq!(args.srv_polling_seconds, 30);
+    }
+
+    #[test]
+    fn checks_the_config_file_given_after_the_command() {
+        let args = CliArguments::parse_from(["load-balancer", "check", "--config", "lb.toml"]);
+
+        assert_eq!(args.command, Some(Command::Check));
+        assert_eq!(args.config, Some(PathBuf::from("lb.toml")));
         assert_eq!(
-            args.target_servers,
-            Vec::from(["http://localhost:9000", "http://localhost:9001"])
+            CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]).command,
+            None
         );
-        assert_eq!(args.routing_policy, RoutingPolicy::Random);
-        assert_eq!(args.target_servers_health_path, "/ready");
-        assert_eq!(args.health_checker_polling_seconds, 10);
     }
 
     #[test]
-    fn test_cli_arguments_short_flags() {
+    fn prints_the_config_as_json_unless_told_otherwise() {
         let args = CliArguments::parse_from([
             "load-balancer",
-            "-p",
-            "3000",
             "-t",
-            "http://localhost:9000,http://localhost:9001",
-            "-r",
-            "round-robin",
+            "http://localhost:9000",
+            "--print-config",
+        ]);
+        assert_eq!(args.print_config, Some(ConfigFormat::Json));
+
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--print-config",
+            "toml",
+        ]);
+        assert_eq!(args.print_config, Some(ConfigFormat::Toml));
+    }
+
+    #[test]
+    fn secrets_can_be_given_as_files_instead() {
+        let args = CliArguments::parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--consul-token-file",
+            "/run/secrets/consul-token",
+            "--health-webhook-url-file",
+            "/run/secrets/webhook-url",
         ]);
 
-        assert_eq!(args.port, 3000);
         assert_eq!(
-            args.target_servers,
-            Vec::from(["http://localhost:9000", "http://localhost:9001"])
+            args.consul_token_file,
+            Some(PathBuf::from("/run/secrets/consul-token"))
         );
-        assert_eq!(args.routing_policy, RoutingPolicy::RoundRobin);
+        assert_eq!(
+            args.health_webhook_url_file,
+            Some(PathBuf::from("/run/secrets/webhook-url"))
+        );
+
+        let both = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--consul-token",
+            "secret",
+            "--consul-token-file",
+            "/run/secrets/consul-token",
+        ]);
+
+        assert!(both.is_err());
     }
 
     #[test]
-    fn routing_policy_should_default_to_round_robin() {
+    fn the_redis_state_store_requires_its_url_or_url_file() {
+        let parse = |arguments: &[&str]| {
+            CliArguments::try_parse_from(
+                [&["load-balancer", "-t", "http://localhost:9000"], arguments].concat(),
+            )
+        };
+
+        assert!(parse(&["--state-store", "redis"]).is_err());
+        assert!(
+            parse(&[
+                "--state-store",
+                "redis",
+                "--state-store-url",
+                "redis://cache"
+            ])
+            .is_ok()
+        );
+        assert!(
+            parse(&[
+                "--state-store",
+                "redis",
+                "--state-store-url-file",
+                "/run/secrets/redis-url"
+            ])
+            .is_ok()
+        );
+        assert!(
+            parse(&[
+                "--state-store-url",
+                "redis://cache",
+                "--state-store-url-file",
+                "/run/secrets/redis-url"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn the_effective_config_redacts_the_secrets() {
         let args = CliArguments::parse_from([
             "load-balancer",
             "-t",
-            "http://localhost:9000,http://localhost:9001",
+            "http://localhost:9000,http://deploy@api:8080",
+            "--consul-token",
+            "secret",
+            "--state-store",
+            "redis",
+            "--state-store-url",
+            "redis://:secret@cache:6379",
+            "--upstream-proxy",
+            "http://proxy:3128",
+            "--allowed-methods",
+            "GET",
+            "--match-route",
+            "/api",
         ]);
 
-        assert_eq!(args.routing_policy, RoutingPolicy::RoundRobin);
+        let config = serde_json::to_value(&args).unwrap();
+
+        assert_eq!(config["consul_token"], "redacted");
+        assert_eq!(config["state_store_url"], "redacted");
+        assert_eq!(config["upstream_proxy"], "http://proxy:3128");
+        assert_eq!(config["target_servers"][0]["url"], "http://localhost:9000");
+        assert_eq!(config["target_servers"][1]["url"], "redacted");
+        assert_eq!(config["experiment_salt"], serde_json::Value::Null);
+        assert_eq!(config["allowed_methods"], serde_json::json!(["GET"]));
+        assert_eq!(config["state_store"], "redis");
+        assert!(config.get("match_route").is_none());
+        assert!(config.get("print_config").is_none());
     }
 
     #[test]
-    fn target_servers_health_path_should_default_to_health() {
+    fn unmatched_requests_get_either_a_default_pool_or_a_status() {
         let args = CliArguments::parse_from([
             "load-balancer",
             "-t",
-            "http://localhost:9000,http://localhost:9001",
+            "http://localhost:9000",
+            "--unmatched-status",
+            "421",
+        ]);
+        let both = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--default-pool",
+            "api",
+            "--unmatched-status",
+            "404",
         ]);
 
-        assert_eq!(args.target_servers_health_path, "/health");
+        assert_eq!(
+            args.unmatched_status,
+            Some(UnmatchedStatus::MisdirectedRequest)
+        );
+        assert!(both.is_err());
     }
 
     #[test]
-    fn health_checker_polling_seconds_should_default_to_10() {
-        let args = CliArguments::parse_from([
+    fn request_transforms_should_be_empty_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert!(args.request_transforms.is_empty());
+    }
+
+    #[test]
+    fn range_requests_should_pass_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.range_requests, RangeRequestsKind::Pass);
+    }
+
+    #[test]
+    fn upstream_decoding_should_pass_through_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.upstream_decoding, UpstreamDecodingKind::Passthrough);
+    }
+
+    #[test]
+    fn downstream_timeouts_should_have_defaults() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.downstream_idle_timeout_seconds, 60);
+        assert_eq!(args.downstream_read_timeout_seconds, 30);
+        assert_eq!(args.downstream_head_timeout_seconds, 10);
+        assert_eq!(args.downstream_max_requests, 0);
+        assert_eq!(args.downstream_max_connection_age_seconds, 0);
+    }
+
+    #[test]
+    fn in_flight_requests_should_be_unlimited_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.max_in_flight_per_backend, None);
+        assert!(args.backend_max_in_flight.is_empty());
+    }
+
+    #[test]
+    fn backend_max_in_flight_require_a_count() {
+        let result = CliArguments::try_parse_from([
             "load-balancer",
             "-t",
-            "http://localhost:9000,http://localhost:9001",
+            "http://localhost:9000",
+            "--backend-max-in-flight",
+            "http://localhost:9000=many",
         ]);
 
-        assert_eq!(args.health_checker_polling_seconds, 10);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn port_should_default_to_3000() {
-        let args = CliArguments::parse_from([
+    fn fallback_response_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.fallback_file, None);
+        assert_eq!(args.fallback_body, None);
+        assert_eq!(args.fallback_status, 503);
+        assert_eq!(args.fallback_content_type, None);
+    }
+
+    #[test]
+    fn fallback_file_and_body_should_be_exclusive() {
+        let result = CliArguments::try_parse_from([
             "load-balancer",
             "-t",
-            "http://localhost:9000,http://localhost:9001",
+            "http://localhost:9000",
+            "--fallback-file",
+            "/srv/maintenance.html",
+            "--fallback-body",
+            "Back soon",
         ]);
 
-        assert_eq!(args.port, 3000);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn upstream_proxy_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.upstream_proxy, None);
+        assert_eq!(args.upstream_no_proxy, None);
+        assert!(!args.health_check_via_proxy);
+    }
+
+    #[test]
+    fn upstream_tls_should_verify_against_the_system_roots_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.upstream_ca_cert, None);
+        assert!(!args.upstream_insecure_skip_verify);
+        assert_eq!(args.upstream_sni, None);
+    }
+
+    #[test]
+    fn upstream_client_identity_should_be_disabled_by_default() {
+        let args = CliArguments::parse_from(["load-balancer", "-t", "http://localhost:9000"]);
+
+        assert_eq!(args.upstream_client_cert, None);
+        assert_eq!(args.upstream_client_key, None);
+        assert_eq!(args.upstream_client_identity_reload_seconds, 10);
+    }
+
+    #[test]
+    fn upstream_client_cert_requires_a_key() {
+        let result = CliArguments::try_parse_from([
+            "load-balancer",
+            "-t",
+            "http://localhost:9000",
+            "--upstream-client-cert",
+            "/etc/wakanda/client.crt",
+        ]);
+
+        assert!(result.is_err());
     }
 }
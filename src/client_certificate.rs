@@ -0,0 +1,405 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use http::{HeaderMap, HeaderValue};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::target_url::normalize_target_url;
+
+pub const X_CLIENT_CERT_CN: &str = "x-client-cert-cn";
+pub const X_CLIENT_CERT_SAN: &str = "x-client-cert-san";
+pub const X_CLIENT_CERT_OU: &str = "x-client-cert-ou";
+
+/// Identity of a client that authenticated with a certificate.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientCertificate {
+    pub common_name: Option<String>,
+    /// DNS names, URIs, e-mails and IP addresses.
+    pub subject_alt_names: Vec<String>,
+    pub organizational_units: Vec<String>,
+}
+
+impl ClientCertificate {
+    pub fn from_der(certificate: &[u8]) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+
+        let subject_alt_names = certificate
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|extension| {
+                extension
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(name)
+                        | GeneralName::URI(name)
+                        | GeneralName::RFC822Name(name) => Some(name.to_string()),
+                        GeneralName::IPAddress(address) => match address.len() {
+                            4 => <[u8; 4]>::try_from(*address).ok().map(IpAddr::from),
+                            _ => <[u8; 16]>::try_from(*address).ok().map(IpAddr::from),
+                        }
+                        .map(|address| address.to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            common_name: certificate
+                .subject()
+                .iter_common_name()
+                .find_map(|name| name.as_str().ok())
+                .map(str::to_string),
+            subject_alt_names,
+            organizational_units: certificate
+                .subject()
+                .iter_organizational_unit()
+                .filter_map(|unit| unit.as_str().ok())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CertificateAttribute {
+    CommonName,
+    SubjectAltName,
+    OrganizationalUnit,
+}
+
+impl CertificateAttribute {
+    fn values<'a>(&self, certificate: &'a ClientCertificate) -> Vec<&'a str> {
+        match self {
+            CertificateAttribute::CommonName => {
+                certificate.common_name.as_deref().into_iter().collect()
+            }
+            CertificateAttribute::SubjectAltName => certificate
+                .subject_alt_names
+                .iter()
+                .map(String::as_str)
+                .collect(),
+            CertificateAttribute::OrganizationalUnit => certificate
+                .organizational_units
+                .iter()
+                .map(String::as_str)
+                .collect(),
+        }
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            CertificateAttribute::CommonName => X_CLIENT_CERT_CN,
+            CertificateAttribute::SubjectAltName => X_CLIENT_CERT_SAN,
+            CertificateAttribute::OrganizationalUnit => X_CLIENT_CERT_OU,
+        }
+    }
+}
+
+impl FromStr for CertificateAttribute {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "cn" => Ok(CertificateAttribute::CommonName),
+            "san" => Ok(CertificateAttribute::SubjectAltName),
+            "ou" => Ok(CertificateAttribute::OrganizationalUnit),
+            _ => Err(format!("expected cn, san or ou, got {}", value)),
+        }
+    }
+}
+
+/// Written as `ATTRIBUTE=VALUE`, e.g. `ou=payments`; a value starting with
+/// `*.` matches any subdomain, e.g. `san=*.internal.example.com`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateMatcher {
+    pub attribute: CertificateAttribute,
+    pub value: String,
+}
+
+impl CertificateMatcher {
+    fn matches(&self, certificate: &ClientCertificate) -> bool {
+        self.attribute.values(certificate).into_iter().any(|value| {
+            match self.value.strip_prefix('*') {
+                Some(suffix) if suffix.starts_with('.') => {
+                    value.len() > suffix.len() && value.ends_with(suffix)
+                }
+                _ => value == self.value,
+            }
+        })
+    }
+}
+
+impl FromStr for CertificateMatcher {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (attribute, matched) = value
+            .split_once('=')
+            .filter(|(_, matched)| !matched.is_empty())
+            .ok_or_else(|| format!("expected ATTRIBUTE=VALUE, got {}", value))?;
+
+        Ok(CertificateMatcher {
+            attribute: attribute.parse()?,
+            value: matched.to_string(),
+        })
+    }
+}
+
+/// Sends the requests of the matching clients to `backends` only.
+///
+/// Written as `ATTRIBUTE=VALUE>BACKEND|BACKEND`, e.g.
+/// `ou=payments>http://payments-1:8080|http://payments-2:8080`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateRoute {
+    pub matcher: CertificateMatcher,
+    pub backends: Vec<String>,
+}
+
+impl FromStr for CertificateRoute {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected ATTRIBUTE=VALUE>BACKENDS, got {}", value);
+
+        let (matcher, backends) = value.split_once('>').ok_or_else(invalid)?;
+
+        let backends = backends
+            .split('|')
+            .filter(|backend| !backend.is_empty())
+            .map(normalize_target_url)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if backends.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(CertificateRoute {
+            matcher: matcher.parse()?,
+            backends,
+        })
+    }
+}
+
+/// Authorization, routing and forwarding based on the client certificate,
+/// when the listener asks for one.
+#[derive(Debug, Clone, Default)]
+pub struct ClientCertificateRules {
+    /// Clients whose certificate matches none of these are refused; everyone
+    /// is let through when empty.
+    pub allow: Vec<CertificateMatcher>,
+    pub routes: Vec<CertificateRoute>,
+    /// Attributes sent to the backends in `X-Client-Cert-*` headers.
+    pub forwarded_attributes: Vec<CertificateAttribute>,
+}
+
+impl ClientCertificateRules {
+    pub fn authorizes(&self, certificate: Option<&ClientCertificate>) -> bool {
+        self.allow.is_empty()
+            || certificate.is_some_and(|certificate| {
+                self.allow
+                    .iter()
+                    .any(|matcher| matcher.matches(certificate))
+            })
+    }
+
+    /// Servers the request must not be sent to: the first route matching
+    /// the certificate keeps only its own backends.
+    pub fn excluded_servers(
+        &self,
+        certificate: Option<&ClientCertificate>,
+        target_servers: &[String],
+    ) -> Vec<String> {
+        let Some(certificate) = certificate else {
+            return Vec::new();
+        };
+
+        match self
+            .routes
+            .iter()
+            .find(|route| route.matcher.matches(certificate))
+        {
+            Some(route) => target_servers
+                .iter()
+                .filter(|server| !route.backends.contains(server))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Replaces whatever `X-Client-Cert-*` headers the client sent with the
+    /// attributes of its actual certificate.
+    pub fn forward(&self, headers: &mut HeaderMap, certificate: Option<&ClientCertificate>) {
+        if self.forwarded_attributes.is_empty() {
+            return;
+        }
+
+        for header in [X_CLIENT_CERT_CN, X_CLIENT_CERT_SAN, X_CLIENT_CERT_OU] {
+            headers.remove(header);
+        }
+
+        let Some(certificate) = certificate else {
+            return;
+        };
+
+        for attribute in &self.forwarded_attributes {
+            let values = attribute.values(certificate);
+
+            if values.is_empty() {
+                continue;
+            }
+
+            if let Ok(value) = HeaderValue::from_str(&values.join(",")) {
+                headers.insert(attribute.header(), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue};
+
+    use crate::client_certificate::{
+        CertificateAttribute, CertificateRoute, ClientCertificate, ClientCertificateRules,
+        X_CLIENT_CERT_CN, X_CLIENT_CERT_OU, X_CLIENT_CERT_SAN,
+    };
+
+    fn payments() -> ClientCertificate {
+        ClientCertificate {
+            common_name: Some("checkout".to_string()),
+            subject_alt_names: vec!["checkout.internal.example.com".to_string()],
+            organizational_units: vec!["payments".to_string()],
+        }
+    }
+
+    fn target_servers() -> Vec<String> {
+        vec!["http://payments".to_string(), "http://shared".to_string()]
+    }
+
+    #[test]
+    fn reads_the_attributes_of_a_certificate() {
+        let mut params = rcgen::CertificateParams::new(vec![
+            "checkout.internal.example.com".to_string(),
+            "10.0.0.1".to_string(),
+        ])
+        .unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "checkout");
+        params
+            .distinguished_name
+            .push(rcgen::DnType::OrganizationalUnitName, "payments");
+        let certificate = params
+            .self_signed(&rcgen::KeyPair::generate().unwrap())
+            .unwrap();
+
+        assert_eq!(
+            ClientCertificate::from_der(certificate.der()),
+            Some(ClientCertificate {
+                common_name: Some("checkout".to_string()),
+                subject_alt_names: vec![
+                    "checkout.internal.example.com".to_string(),
+                    "10.0.0.1".to_string()
+                ],
+                organizational_units: vec!["payments".to_string()],
+            })
+        );
+        assert_eq!(ClientCertificate::from_der(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn parses_routes() {
+        let route: CertificateRoute = "ou=payments>http://payments/|HTTP://Shared:80"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            route.matcher.attribute,
+            CertificateAttribute::OrganizationalUnit
+        );
+        assert_eq!(route.matcher.value, "payments");
+        assert_eq!(route.backends, vec!["http://payments", "http://shared"]);
+
+        assert!("ou=payments".parse::<CertificateRoute>().is_err());
+        assert!(
+            "o=payments>http://payments"
+                .parse::<CertificateRoute>()
+                .is_err()
+        );
+        assert!("ou=>http://payments".parse::<CertificateRoute>().is_err());
+        assert!(
+            "ou=payments>payments:80"
+                .parse::<CertificateRoute>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn authorizes_the_matching_clients_only() {
+        let rules = ClientCertificateRules {
+            allow: vec![
+                "ou=billing".parse().unwrap(),
+                "san=*.internal.example.com".parse().unwrap(),
+            ],
+            ..ClientCertificateRules::default()
+        };
+        let outsider = ClientCertificate {
+            subject_alt_names: vec!["internal.example.com".to_string()],
+            ..ClientCertificate::default()
+        };
+
+        assert!(rules.authorizes(Some(&payments())));
+        assert!(!rules.authorizes(Some(&outsider)));
+        assert!(!rules.authorizes(None));
+        assert!(ClientCertificateRules::default().authorizes(None));
+    }
+
+    #[test]
+    fn keeps_only_the_backends_of_the_matching_route() {
+        let rules = ClientCertificateRules {
+            routes: vec!["ou=payments>http://payments".parse().unwrap()],
+            ..ClientCertificateRules::default()
+        };
+
+        assert_eq!(
+            rules.excluded_servers(Some(&payments()), &target_servers()),
+            vec!["http://shared"]
+        );
+        assert!(
+            rules
+                .excluded_servers(Some(&ClientCertificate::default()), &target_servers())
+                .is_empty()
+        );
+        assert!(rules.excluded_servers(None, &target_servers()).is_empty());
+    }
+
+    #[test]
+    fn forwards_the_attributes_of_the_actual_certificate() {
+        let rules = ClientCertificateRules {
+            forwarded_attributes: vec![
+                CertificateAttribute::OrganizationalUnit,
+                CertificateAttribute::SubjectAltName,
+            ],
+            ..ClientCertificateRules::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(X_CLIENT_CERT_OU, HeaderValue::from_static("admins"));
+        headers.insert(X_CLIENT_CERT_CN, HeaderValue::from_static("root"));
+
+        rules.forward(&mut headers, Some(&payments()));
+
+        assert_eq!(headers[X_CLIENT_CERT_OU], "payments");
+        assert_eq!(headers[X_CLIENT_CERT_SAN], "checkout.internal.example.com");
+        assert!(headers.get(X_CLIENT_CERT_CN).is_none());
+
+        rules.forward(&mut headers, None);
+
+        assert!(headers.is_empty());
+    }
+}
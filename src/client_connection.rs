@@ -0,0 +1,100 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::connect_info::Connected;
+use axum::extract::{ConnectInfo, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::serve::IncomingStream;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::ServerConnection;
+
+use crate::client_certificate::ClientCertificate;
+use crate::downstream_timeouts::{ConnectionActivity, TimedListener};
+use crate::tls::tls_listener::TlsListener;
+
+/// Connect info of a client: its address, the certificate it authenticated
+/// with when the TLS listener asks for one, and what its connection is
+/// doing when the listener enforces timeouts.
+#[derive(Debug, Clone)]
+pub struct ClientConnection {
+    pub address: SocketAddr,
+    pub certificate: Option<Arc<ClientCertificate>>,
+    pub activity: Option<Arc<ConnectionActivity>>,
+}
+
+fn peer_certificate(connection: &ServerConnection) -> Option<Arc<ClientCertificate>> {
+    connection
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .and_then(|certificate| ClientCertificate::from_der(certificate))
+        .map(Arc::new)
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for ClientConnection {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self {
+            address: *stream.remote_addr(),
+            certificate: None,
+            activity: None,
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for ClientConnection {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        let (_, connection) = stream.io().get_ref();
+
+        Self {
+            address: *stream.remote_addr(),
+            certificate: peer_certificate(connection),
+            activity: None,
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, TimedListener<TcpListener>>> for ClientConnection {
+    fn connect_info(stream: IncomingStream<'_, TimedListener<TcpListener>>) -> Self {
+        Self {
+            address: *stream.remote_addr(),
+            certificate: None,
+            activity: Some(stream.io().activity()),
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, TimedListener<TlsListener>>> for ClientConnection {
+    fn connect_info(stream: IncomingStream<'_, TimedListener<TlsListener>>) -> Self {
+        let (_, connection) = stream.io().get_ref().get_ref();
+
+        Self {
+            address: *stream.remote_addr(),
+            certificate: peer_certificate(connection),
+            activity: Some(stream.io().activity()),
+        }
+    }
+}
+
+/// Hands the client address over as `ConnectInfo<SocketAddr>`, its
+/// certificate as an `Arc<ClientCertificate>` extension and the activity of
+/// its connection as an `Arc<ConnectionActivity>` one.
+pub async fn expose(mut request: Request, next: Next) -> Response {
+    if let Some(ConnectInfo(connection)) = request
+        .extensions()
+        .get::<ConnectInfo<ClientConnection>>()
+        .cloned()
+    {
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(connection.address));
+
+        if let Some(certificate) = connection.certificate {
+            request.extensions_mut().insert(certificate);
+        }
+        if let Some(activity) = connection.activity {
+            request.extensions_mut().insert(activity);
+        }
+    }
+
+    next.run(request).await
+}
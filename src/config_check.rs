@@ -0,0 +1,327 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, RwLock};
+
+use futures::FutureExt;
+use load_balancer::certificate_expiries::CertificateExpiries;
+use load_balancer::metrics::metrics::Metrics;
+use load_balancer::state_store::memory_state_store::MemoryStateStore;
+
+use crate::cli_arguments::CliArguments;
+use crate::{
+    check_intervals, consul_services, discovered_pool, kubernetes_service, make_background_checker,
+    make_config_rollout, make_consul_discovery, make_leader_election, make_listeners, make_pools,
+    make_select_server, make_server_state, make_srv_discovery, make_tls_config, make_usage_tracker,
+    srv_records, state_store_location, target_server_weights,
+};
+
+/// Builds the load balancer the way it starts, with the same builders,
+/// without starting it: no task is spawned, no port bound, and neither the
+/// discoveries nor the state store are reached, so the pools they fill in
+/// are checked without their backends. Every part reports the problem it
+/// fails on, startup stopping at the first one.
+pub(crate) async fn check_config(args: &CliArguments) -> Vec<String> {
+    let mut problems = Vec::new();
+    let certificate_expiries = Arc::new(CertificateExpiries::new(
+        args.certificate_expiry_warning_days.into(),
+    ));
+
+    dry_run(&mut problems, || check_intervals(args));
+    let listeners = dry_run(&mut problems, || make_listeners(args));
+    dry_run(&mut problems, || Ok(state_store_location(args)));
+    dry_run(&mut problems, || {
+        make_config_rollout(
+            args,
+            Arc::new(MemoryStateStore::default()),
+            make_leader_election(args),
+            Arc::new(Metrics::default()),
+        )
+    });
+    for service in consul_services(args) {
+        dry_run(&mut problems, || Ok(make_consul_discovery(args, service)));
+    }
+    for name in srv_records(args) {
+        dry_run(&mut problems, || make_srv_discovery(args, name));
+    }
+
+    let discovered = dry_run(&mut problems, || Ok(kubernetes_service(args))).flatten();
+    // The pools are split by listener, whose problems are reported above.
+    if listeners.is_some() {
+        dry_run(&mut problems, || {
+            make_pools(
+                args,
+                discovered.map(|service| discovered_pool(args, &service.name, Vec::new())),
+                &HashMap::new(),
+                &HashMap::new(),
+                None,
+                &certificate_expiries,
+                &HashMap::new(),
+            )
+        });
+    }
+
+    if let Some(background_checker) = dry_run(&mut problems, || {
+        Ok(make_background_checker(args, None, &certificate_expiries))
+    }) {
+        dry_run(&mut problems, || {
+            let weights = Arc::new(RwLock::new(target_server_weights(args)));
+            let select_server = make_select_server(args, &background_checker, weights);
+
+            Ok(make_server_state(
+                args,
+                select_server,
+                &background_checker,
+                Arc::new(Metrics::default()),
+                make_usage_tracker(args),
+                Arc::new(MemoryStateStore::default()),
+                Arc::clone(&certificate_expiries),
+            ))
+        });
+    }
+
+    dry_run_async(&mut problems, make_tls_config(args)).await;
+
+    problems
+}
+
+/// Runs a builder of the startup, recording the problem it fails or panics
+/// on, if any.
+fn dry_run<T>(
+    problems: &mut Vec<String>,
+    builder: impl FnOnce() -> Result<T, String>,
+) -> Option<T> {
+    let built = std::panic::catch_unwind(AssertUnwindSafe(builder));
+
+    record(problems, built)
+}
+
+async fn dry_run_async<T>(
+    problems: &mut Vec<String>,
+    builder: impl Future<Output = T>,
+) -> Option<T> {
+    let built = AssertUnwindSafe(builder).catch_unwind().await.map(Ok);
+
+    record(problems, built)
+}
+
+fn record<T>(
+    problems: &mut Vec<String>,
+    built: Result<Result<T, String>, Box<dyn Any + Send>>,
+) -> Option<T> {
+    let problem = match built {
+        Ok(Ok(built)) => return Some(built),
+        Ok(Err(problem)) => problem,
+        Err(panic) => match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(panic) => panic
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "Invalid configuration".to_string()),
+        },
+    };
+    problems.push(problem);
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+    use uuid::Uuid;
+
+    use crate::cli_arguments::CliArguments;
+    use crate::config_check::check_config;
+
+    async fn problems(arguments: &[&str]) -> Vec<String> {
+        check_config(&CliArguments::parse_from(
+            [&["load-balancer"], arguments].concat(),
+        ))
+        .await
+    }
+
+    #[tokio::test]
+    async fn accepts_a_valid_config() {
+        assert_eq!(
+            problems(&[
+                "-t",
+                "http://localhost:9000",
+                "--pool",
+                "api=http://api-1:8080|http://api-2:8080",
+                "--pool",
+                "static=http://static-1:8080",
+                "--pool-route",
+                "/static/*=>static",
+                "--default-pool",
+                "api",
+            ])
+            .await,
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_the_problem_of_each_part() {
+        let problems = problems(&[
+            "--health-checker-polling-seconds",
+            "0",
+            "--pool",
+            "api=http://api-1:8080",
+            "--pool",
+            "api=http://api-2:8080",
+            "--time-rule",
+            "invalid",
+            "--tls-cert",
+            "/nonexistent/cert.pem",
+            "--tls-key",
+            "/nonexistent/key.pem",
+        ])
+        .await;
+
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert_eq!(
+            problems[0],
+            "--health-checker-polling-seconds must be greater than 0"
+        );
+        assert_eq!(problems[1], "Invalid pools: pool api is defined twice");
+        assert!(problems[2].starts_with("Invalid time rule"));
+        assert!(
+            problems[3].starts_with("Failed to load the TLS certificate /nonexistent/cert.pem")
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_the_pools_of_each_listener() {
+        assert_eq!(
+            problems(&[
+                "--listener",
+                "internal=8081;default-pool=metrics",
+                "--pool",
+                "api=http://api-1:8080",
+                "--pool",
+                "ops=http://ops-1:8080;listener=internal",
+                "--pool-route",
+                "/ops/*=>ops",
+            ])
+            .await,
+            vec!["Invalid pools of listener internal: unknown pool metrics"]
+        );
+        assert_eq!(
+            problems(&["--port", "8081", "--listener", "internal=8081"]).await,
+            vec!["Invalid listener: port 8081 of listener internal is already in use"]
+        );
+        assert_eq!(
+            problems(&["--admin-listener", "internal"]).await,
+            vec!["Unknown admin listener internal"]
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_that_the_rollout_shares_the_state_store() {
+        let rollout = [
+            "--coordinated-rollout",
+            "--leader-lease-file",
+            "/shared/wakanda.lease",
+            "--advertise-address",
+            "http://10.0.0.1:3000",
+        ];
+
+        assert_eq!(
+            problems(&rollout).await,
+            vec!["--coordinated-rollout requires a --state-store shared by the replicas"]
+        );
+        assert_eq!(
+            problems(&[&rollout[..], &["--rollout-polling-seconds", "0"]].concat()).await,
+            vec![
+                "--rollout-polling-seconds must be greater than 0",
+                "--coordinated-rollout requires a --state-store shared by the replicas"
+            ]
+        );
+        assert!(
+            problems(
+                &[
+                    &rollout[..],
+                    &[
+                        "--state-store",
+                        "file",
+                        "--state-store-path",
+                        "/shared/wakanda.state"
+                    ],
+                ]
+                .concat()
+            )
+            .await
+            .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_the_sni_certificates() {
+        let sni_certificate = "api.example.com=/nonexistent/api.crt:/nonexistent/api.key";
+        assert_eq!(
+            problems(&["--tls-sni-cert", sni_certificate]).await,
+            vec!["--tls-sni-cert needs a default certificate, --tls-cert"]
+        );
+
+        let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+        let (certificate, key) = (prefix.with_extension("crt"), prefix.with_extension("key"));
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let self_signed = rcgen::CertificateParams::new(vec!["api.example.com".to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        std::fs::write(&certificate, self_signed.pem()).unwrap();
+        std::fs::write(&key, key_pair.serialize_pem()).unwrap();
+        let path = |path: &PathBuf| path.display().to_string();
+        let sni_certificate = format!("api.example.com={}:{}", path(&certificate), path(&key));
+
+        let problems = problems(&[
+            "--tls-cert",
+            &path(&certificate),
+            "--tls-key",
+            &path(&key),
+            "--tls-sni-cert",
+            &sni_certificate,
+            "--tls-sni-cert",
+            &sni_certificate,
+        ])
+        .await;
+
+        assert_eq!(
+            problems,
+            vec!["Domain api.example.com has several certificates"]
+        );
+
+        let _ = std::fs::remove_file(&certificate);
+        let _ = std::fs::remove_file(&key);
+    }
+
+    #[tokio::test]
+    async fn reports_pools_named_nowhere() {
+        assert_eq!(
+            problems(&[
+                "--pool",
+                "api=http://api-1:8080",
+                "--pool-route",
+                "/static/*=>static",
+            ])
+            .await,
+            vec!["Invalid pools: unknown pool static"]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_the_client_certificates_of_the_pools() {
+        let problems = problems(&[
+            "--pool",
+            "api=https://api-1:8443;client-cert=/nonexistent/api.crt:/nonexistent/api.key",
+        ])
+        .await;
+
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].starts_with("Failed to load the upstream client certificate"));
+    }
+}
@@ -0,0 +1,698 @@
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use clap::ValueEnum;
+use clap::parser::ValueSource;
+use http::Method;
+use serde::Deserialize;
+
+use crate::cli_arguments::{
+    Backend, CliArguments, RoutingPolicy, parse_method, parse_sni_certificate,
+};
+
+/// Settings read from the TOML file of `--config`, for what the flags can't
+/// express comfortably: pools, per-backend settings and long route lists.
+/// Every setting given on the command line or through its environment
+/// variable wins over the file, lists being replaced rather than merged.
+///
+/// ```toml
+/// [listener]
+/// port = 8080
+///
+/// [[backends]]
+/// url = "http://api-1:8080"
+/// weight = 3
+/// timeout_ms = 2000
+/// max_in_flight = 64
+///
+/// [[pools]]
+/// name = "static"
+/// backends = ["http://static-1:8080", "http://static-2:8080"]
+/// policy = "random"
+/// allowed_methods = ["GET", "HEAD"]
+///
+/// [[pools]]
+/// name = "api"
+/// consul = { service = "api", tag = "primary" }
+///
+/// [consul]
+/// address = "http://consul.service:8500"
+///
+/// [routing]
+/// pool_routes = ["/static/*;allow:GET|HEAD=>static"]
+///
+/// [health_check]
+/// path = "/ready"
+/// interval_seconds = 5
+///
+/// [policies]
+/// retries = 2
+/// allowed_methods = ["GET", "HEAD", "POST"]
+/// ```
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    pub(crate) listener: ListenerConfig,
+    /// The target servers, with their own settings.
+    pub(crate) backends: Vec<BackendConfig>,
+    pub(crate) pools: Vec<PoolConfig>,
+    /// Listeners next to the main one, each serving its own pools.
+    pub(crate) listeners: Vec<ListenerDefinitionConfig>,
+    pub(crate) consul: ConsulConfig,
+    pub(crate) routing: RoutingConfig,
+    pub(crate) health_check: HealthCheckConfig,
+    pub(crate) policies: PoliciesConfig,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ListenerConfig {
+    pub(crate) port: Option<u16>,
+    pub(crate) acceptors: Option<u16>,
+    pub(crate) tls_cert: Option<PathBuf>,
+    pub(crate) tls_key: Option<PathBuf>,
+    /// Certificates picked by the server name the clients ask for.
+    pub(crate) sni_certificates: Vec<SniCertificateConfig>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SniCertificateConfig {
+    pub(crate) domain: String,
+    pub(crate) tls_cert: PathBuf,
+    pub(crate) tls_key: PathBuf,
+}
+
+impl SniCertificateConfig {
+    /// The certificate as written with `--tls-sni-cert`.
+    fn definition(&self) -> String {
+        format!(
+            "{}={}:{}",
+            self.domain,
+            self.tls_cert.display(),
+            self.tls_key.display()
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BackendConfig {
+    pub(crate) url: String,
+    pub(crate) weight: Option<u32>,
+    pub(crate) timeout_ms: Option<u64>,
+    pub(crate) max_in_flight: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PoolConfig {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) backends: Vec<String>,
+    /// `round-robin` or `random`.
+    pub(crate) policy: Option<String>,
+    pub(crate) health_path: Option<String>,
+    /// Service of the Consul catalog the backends are taken from, instead
+    /// of `backends`.
+    pub(crate) consul: Option<PoolConsulConfig>,
+    /// SRV record the backends are taken from, instead of `backends`.
+    pub(crate) srv: Option<String>,
+    /// The `listeners` entry serving the pool, instead of the main one.
+    pub(crate) listener: Option<String>,
+    /// The methods the pool accepts, all of them when empty.
+    #[serde(default)]
+    pub(crate) allowed_methods: Vec<String>,
+    /// `auto`, `http1` or `http2`, instead of `upstream_http_version`.
+    pub(crate) http_version: Option<String>,
+    /// Instead of `--rewrite-redirects`.
+    pub(crate) rewrite_redirects: Option<bool>,
+    /// `preserve` or `upstream`, instead of `--host-header`.
+    pub(crate) host_header: Option<String>,
+    /// Server name of the backends, instead of `--upstream-sni`.
+    pub(crate) sni: Option<String>,
+    /// Client certificate presented to the backends, instead of
+    /// `--upstream-client-cert`, along with `client_key`.
+    pub(crate) client_cert: Option<String>,
+    pub(crate) client_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PoolConsulConfig {
+    pub(crate) service: String,
+    pub(crate) tag: Option<String>,
+    pub(crate) datacenter: Option<String>,
+}
+
+impl PoolConfig {
+    /// The pool as written with `--pool`.
+    fn definition(&self) -> String {
+        let mut definition = format!("{}={}", self.name, self.backends.join("|"));
+        if let Some(policy) = &self.policy {
+            definition.push_str(&format!(";policy={}", policy));
+        }
+        if let Some(health_path) = &self.health_path {
+            definition.push_str(&format!(";health-path={}", health_path));
+        }
+        if let Some(consul) = &self.consul {
+            definition.push_str(&format!(";consul={}", consul.service));
+            if let Some(tag) = &consul.tag {
+                definition.push_str(&format!(":{}", tag));
+            }
+            if let Some(datacenter) = &consul.datacenter {
+                definition.push_str(&format!("@{}", datacenter));
+            }
+        }
+        if let Some(srv) = &self.srv {
+            definition.push_str(&format!(";srv={}", srv));
+        }
+        if let Some(listener) = &self.listener {
+            definition.push_str(&format!(";listener={}", listener));
+        }
+        if !self.allowed_methods.is_empty() {
+            definition.push_str(&format!(";allow={}", self.allowed_methods.join("|")));
+        }
+        if let Some(http_version) = &self.http_version {
+            definition.push_str(&format!(";http-version={}", http_version));
+        }
+        if let Some(rewrite_redirects) = self.rewrite_redirects {
+            definition.push_str(&format!(";rewrite-redirects={}", rewrite_redirects));
+        }
+        if let Some(host_header) = &self.host_header {
+            definition.push_str(&format!(";host-header={}", host_header));
+        }
+        if let Some(sni) = &self.sni {
+            definition.push_str(&format!(";sni={}", sni));
+        }
+        if self.client_cert.is_some() || self.client_key.is_some() {
+            definition.push_str(&format!(
+                ";client-cert={}:{}",
+                self.client_cert.as_deref().unwrap_or_default(),
+                self.client_key.as_deref().unwrap_or_default()
+            ));
+        }
+
+        definition
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ListenerDefinitionConfig {
+    pub(crate) name: String,
+    pub(crate) port: u16,
+    pub(crate) default_pool: Option<String>,
+}
+
+impl ListenerDefinitionConfig {
+    /// The listener as written with `--listener`.
+    fn definition(&self) -> String {
+        let mut definition = format!("{}={}", self.name, self.port);
+        if let Some(default_pool) = &self.default_pool {
+            definition.push_str(&format!(";default-pool={}", default_pool));
+        }
+
+        definition
+    }
+}
+
+/// The agent queried for the pools taking their backends from Consul.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ConsulConfig {
+    pub(crate) address: Option<String>,
+    pub(crate) token: Option<String>,
+    /// File holding the token, kept out of the config file.
+    pub(crate) token_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RoutingConfig {
+    pub(crate) pool_routes: Vec<String>,
+    pub(crate) default_pool: Option<String>,
+    pub(crate) path_rules: Vec<String>,
+    pub(crate) route_rules: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct HealthCheckConfig {
+    pub(crate) path: Option<String>,
+    pub(crate) interval_seconds: Option<u64>,
+    pub(crate) timeout_ms: Option<u64>,
+    pub(crate) concurrency: Option<u16>,
+    pub(crate) min_healthy_backends: Option<usize>,
+    pub(crate) min_healthy_backends_timeout_seconds: Option<u64>,
+    pub(crate) wait_for_first: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PoliciesConfig {
+    /// `round-robin` or `random`.
+    pub(crate) routing_policy: Option<String>,
+    pub(crate) retries: Option<u8>,
+    pub(crate) retry_methods: Option<Vec<String>>,
+    pub(crate) allowed_methods: Option<Vec<String>>,
+    pub(crate) upstream_connect_timeout_ms: Option<u64>,
+    pub(crate) upstream_timeout_ms: Option<u64>,
+    pub(crate) max_request_body_bytes: Option<usize>,
+    pub(crate) max_in_flight_per_backend: Option<usize>,
+}
+
+impl ConfigFile {
+    pub(crate) fn load(path: &Path) -> Result<ConfigFile, String> {
+        let content = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+
+        toml::from_str(&content).map_err(|error| error.to_string())
+    }
+
+    /// Fills the arguments not given on the command line of `matches`.
+    pub(crate) fn apply(self, args: &mut CliArguments, matches: &ArgMatches) -> Result<(), String> {
+        let from_file = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        let listener = self.listener;
+        if listener.tls_cert.is_some() != listener.tls_key.is_some() {
+            return Err("listener.tls_cert and listener.tls_key go together".to_string());
+        }
+        set(&mut args.port, listener.port, from_file("port"));
+        set(
+            &mut args.acceptors,
+            listener.acceptors,
+            from_file("acceptors"),
+        );
+        if from_file("tls_cert") && listener.tls_cert.is_some() {
+            args.tls_cert = listener.tls_cert;
+            args.tls_key = listener.tls_key;
+        }
+        set_list(
+            &mut args.tls_sni_certs,
+            listener
+                .sni_certificates
+                .iter()
+                .map(|certificate| parse_sni_certificate(&certificate.definition()))
+                .collect::<Result<_, _>>()?,
+            from_file("tls_sni_certs"),
+        );
+
+        set_list(
+            &mut args.target_servers,
+            self.backends
+                .iter()
+                .map(|backend| Backend {
+                    url: backend.url.clone(),
+                    weight: backend.weight.unwrap_or(1),
+                })
+                .collect(),
+            from_file("target_servers"),
+        );
+        set_list(
+            &mut args.backend_timeouts,
+            self.backends
+                .iter()
+                .filter_map(|backend| Some((backend.url.clone(), backend.timeout_ms?)))
+                .collect(),
+            from_file("backend_timeouts"),
+        );
+        set_list(
+            &mut args.backend_max_in_flight,
+            self.backends
+                .iter()
+                .filter_map(|backend| Some((backend.url.clone(), backend.max_in_flight?)))
+                .collect(),
+            from_file("backend_max_in_flight"),
+        );
+
+        set_list(
+            &mut args.pools,
+            self.pools.iter().map(PoolConfig::definition).collect(),
+            from_file("pools"),
+        );
+        set_list(
+            &mut args.listeners,
+            self.listeners
+                .iter()
+                .map(ListenerDefinitionConfig::definition)
+                .collect(),
+            from_file("listeners"),
+        );
+
+        let consul = self.consul;
+        set(
+            &mut args.consul_address,
+            consul.address,
+            from_file("consul_address"),
+        );
+        if consul.token.is_some() && consul.token_file.is_some() {
+            return Err("consul.token and consul.token_file exclude each other".to_string());
+        }
+        // A token given either way replaces both of the file.
+        let token_from_file = from_file("consul_token") && from_file("consul_token_file");
+        set(
+            &mut args.consul_token,
+            consul.token.map(Some),
+            token_from_file,
+        );
+        set(
+            &mut args.consul_token_file,
+            consul.token_file.map(Some),
+            token_from_file,
+        );
+
+        let routing = self.routing;
+        set_list(
+            &mut args.pool_routes,
+            routing.pool_routes,
+            from_file("pool_routes"),
+        );
+        // A 404 or 421 asked for on the command line replaces the default pool.
+        set(
+            &mut args.default_pool,
+            routing.default_pool.map(Some),
+            from_file("default_pool") && from_file("unmatched_status"),
+        );
+        set_list(
+            &mut args.path_rules,
+            routing.path_rules,
+            from_file("path_rules"),
+        );
+        set_list(
+            &mut args.route_rules,
+            routing.route_rules,
+            from_file("route_rules"),
+        );
+
+        let health_check = self.health_check;
+        set(
+            &mut args.target_servers_health_path,
+            health_check.path,
+            from_file("target_servers_health_path"),
+        );
+        set(
+            &mut args.health_checker_polling_seconds,
+            health_check.interval_seconds,
+            from_file("health_checker_polling_seconds"),
+        );
+        set(
+            &mut args.health_check_timeout_ms,
+            health_check.timeout_ms,
+            from_file("health_check_timeout_ms"),
+        );
+        if health_check.concurrency == Some(0) {
+            return Err("health_check.concurrency must be at least 1".to_string());
+        }
+        set(
+            &mut args.health_check_concurrency,
+            health_check.concurrency,
+            from_file("health_check_concurrency"),
+        );
+        set(
+            &mut args.min_healthy_backends,
+            health_check.min_healthy_backends,
+            from_file("min_healthy_backends"),
+        );
+        set(
+            &mut args.min_healthy_backends_timeout_seconds,
+            health_check.min_healthy_backends_timeout_seconds,
+            from_file("min_healthy_backends_timeout_seconds"),
+        );
+        set(
+            &mut args.wait_for_first_health_check,
+            health_check.wait_for_first,
+            from_file("wait_for_first_health_check"),
+        );
+
+        let policies = self.policies;
+        let routing_policy = policies
+            .routing_policy
+            .map(|policy| RoutingPolicy::from_str(&policy, true))
+            .transpose()?;
+        set(
+            &mut args.routing_policy,
+            routing_policy,
+            from_file("routing_policy"),
+        );
+        set(&mut args.retries, policies.retries, from_file("retries"));
+        set(
+            &mut args.retry_methods,
+            methods(policies.retry_methods)?,
+            from_file("retry_methods"),
+        );
+        set(
+            &mut args.allowed_methods,
+            methods(policies.allowed_methods)?,
+            from_file("allowed_methods"),
+        );
+        set(
+            &mut args.upstream_connect_timeout_ms,
+            policies.upstream_connect_timeout_ms,
+            from_file("upstream_connect_timeout_ms"),
+        );
+        set(
+            &mut args.upstream_timeout_ms,
+            policies.upstream_timeout_ms,
+            from_file("upstream_timeout_ms"),
+        );
+        set(
+            &mut args.max_request_body_bytes,
+            policies.max_request_body_bytes.map(Some),
+            from_file("max_request_body_bytes"),
+        );
+        set(
+            &mut args.max_in_flight_per_backend,
+            policies.max_in_flight_per_backend.map(Some),
+            from_file("max_in_flight_per_backend"),
+        );
+
+        Ok(())
+    }
+}
+
+fn set<T>(argument: &mut T, value: Option<T>, from_file: bool) {
+    if let Some(value) = value.filter(|_| from_file) {
+        *argument = value;
+    }
+}
+
+fn set_list<T>(argument: &mut Vec<T>, values: Vec<T>, from_file: bool) {
+    if from_file && !values.is_empty() {
+        *argument = values;
+    }
+}
+
+fn methods(methods: Option<Vec<String>>) -> Result<Option<Vec<Method>>, String> {
+    methods
+        .map(|methods| methods.iter().map(|method| parse_method(method)).collect())
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::{CommandFactory, FromArgMatches};
+    use http::Method;
+
+    use crate::cli_arguments::{Backend, CliArguments, RoutingPolicy};
+    use crate::config_file::ConfigFile;
+
+    const CONFIG: &str = r#"
+        [listener]
+        port = 8080
+        tls_cert = "/etc/lb/cert.pem"
+        tls_key = "/etc/lb/key.pem"
+
+        [[listener.sni_certificates]]
+        domain = "*.example.com"
+        tls_cert = "/etc/lb/example.pem"
+        tls_key = "/etc/lb/example.key"
+
+        [[backends]]
+        url = "http://api-1:8080"
+        timeout_ms = 2000
+
+        [[backends]]
+        url = "http://api-2:8080"
+        weight = 3
+        max_in_flight = 64
+
+        [[pools]]
+        name = "static"
+        backends = ["http://static-1:8080", "http://static-2:8080"]
+        policy = "random"
+        health_path = "/ready"
+        allowed_methods = ["GET", "HEAD"]
+        sni = "static.internal"
+
+        [[pools]]
+        name = "api"
+        consul = { service = "api", tag = "primary", datacenter = "eu-west" }
+        host_header = "upstream"
+        client_cert = "/etc/lb/api.crt"
+        client_key = "/etc/lb/api.key"
+
+        [[pools]]
+        name = "search"
+        srv = "_http._tcp.search.example.com"
+        rewrite_redirects = true
+
+        [[pools]]
+        name = "ops"
+        backends = ["http://ops-1:8080"]
+        listener = "internal"
+        http_version = "http2"
+
+        [[listeners]]
+        name = "internal"
+        port = 8081
+        default_pool = "ops"
+
+        [consul]
+        address = "http://consul.service:8500"
+        token_file = "/run/secrets/consul-token"
+
+        [routing]
+        pool_routes = ["/static/*;allow:GET|HEAD=>static"]
+
+        [health_check]
+        path = "/ready"
+        interval_seconds = 5
+        wait_for_first = true
+
+        [policies]
+        routing_policy = "random"
+        retries = 2
+        allowed_methods = ["GET", "head", "POST"]
+    "#;
+
+    fn args(config: &str, command_line: &[&str]) -> Result<CliArguments, String> {
+        let matches =
+            CliArguments::command().get_matches_from([&["load-balancer"], command_line].concat());
+        let mut args = CliArguments::from_arg_matches(&matches).unwrap();
+
+        toml::from_str::<ConfigFile>(config)
+            .map_err(|error| error.to_string())?
+            .apply(&mut args, &matches)?;
+
+        Ok(args)
+    }
+
+    #[test]
+    fn fills_the_arguments_from_the_file() {
+        let args = args(CONFIG, &[]).unwrap();
+
+        assert_eq!(args.port, 8080);
+        assert_eq!(args.tls_cert, Some(PathBuf::from("/etc/lb/cert.pem")));
+        assert_eq!(args.tls_key, Some(PathBuf::from("/etc/lb/key.pem")));
+        assert_eq!(
+            args.tls_sni_certs,
+            vec![(
+                "*.example.com".to_string(),
+                PathBuf::from("/etc/lb/example.pem"),
+                PathBuf::from("/etc/lb/example.key")
+            )]
+        );
+        assert_eq!(
+            args.target_servers,
+            vec![
+                Backend::new("http://api-1:8080"),
+                Backend {
+                    url: "http://api-2:8080".to_string(),
+                    weight: 3,
+                },
+            ]
+        );
+        assert_eq!(
+            args.backend_timeouts,
+            vec![("http://api-1:8080".to_string(), 2000)]
+        );
+        assert_eq!(
+            args.backend_max_in_flight,
+            vec![("http://api-2:8080".to_string(), 64)]
+        );
+        assert_eq!(
+            args.pools,
+            vec![
+                "static=http://static-1:8080|http://static-2:8080;policy=random;health-path=/ready;allow=GET|HEAD;sni=static.internal",
+                "api=;consul=api:primary@eu-west;host-header=upstream;client-cert=/etc/lb/api.crt:/etc/lb/api.key",
+                "search=;srv=_http._tcp.search.example.com;rewrite-redirects=true",
+                "ops=http://ops-1:8080;listener=internal;http-version=http2",
+            ]
+        );
+        assert_eq!(args.listeners, vec!["internal=8081;default-pool=ops"]);
+        assert_eq!(args.consul_address, "http://consul.service:8500");
+        assert_eq!(args.consul_token, None);
+        assert_eq!(
+            args.consul_token_file,
+            Some(PathBuf::from("/run/secrets/consul-token"))
+        );
+        assert_eq!(args.pool_routes, vec!["/static/*;allow:GET|HEAD=>static"]);
+        assert_eq!(args.target_servers_health_path, "/ready");
+        assert_eq!(args.health_checker_polling_seconds, 5);
+        assert!(args.wait_for_first_health_check);
+        assert_eq!(args.routing_policy, RoutingPolicy::Random);
+        assert_eq!(args.retries, 2);
+        assert_eq!(
+            args.allowed_methods,
+            vec![Method::GET, Method::HEAD, Method::POST]
+        );
+        assert_eq!(args.upstream_timeout_ms, 30000);
+    }
+
+    #[test]
+    fn command_line_arguments_win_over_the_file() {
+        let args = args(
+            CONFIG,
+            &[
+                "--port",
+                "9090",
+                "--target-servers",
+                "http://local:8080",
+                "--pool-route",
+                "/*=>static",
+                "--retries",
+                "0",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(args.port, 9090);
+        assert_eq!(args.target_servers, vec![Backend::new("http://local:8080")]);
+        assert_eq!(args.pool_routes, vec!["/*=>static"]);
+        assert_eq!(args.retries, 0);
+        assert_eq!(args.health_checker_polling_seconds, 5);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        for config in [
+            "[listener]\nport = \"http\"",
+            "[listener]\nhost = \"0.0.0.0\"",
+            "[listener]\ntls_cert = \"/etc/lb/cert.pem\"",
+            "[[listener.sni_certificates]]\ndomain = \"*\"\ntls_cert = \"a.pem\"\ntls_key = \"a.key\"",
+            "[policies]\nrouting_policy = \"least-connections\"",
+            "[policies]\nallowed_methods = [\"G ET\"]",
+            "[health_check]\nconcurrency = 0",
+            "[consul]\ntoken = \"secret\"\ntoken_file = \"/run/secrets/consul-token\"",
+            "[[pools]]\nname = \"api\"\nconsul = { name = \"api\" }",
+        ] {
+            assert!(args(config, &[]).is_err(), "{}", config);
+        }
+    }
+
+    #[test]
+    fn an_empty_file_changes_nothing() {
+        let args = args("", &[]).unwrap();
+
+        assert_eq!(args.port, 3000);
+        assert!(args.target_servers.is_empty());
+        assert_eq!(args.target_servers_health_path, "/health");
+    }
+}
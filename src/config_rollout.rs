@@ -0,0 +1,371 @@
+use bytes::Bytes;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::leader_election::leader_election::LeaderElection;
+use crate::metrics::metrics::{HTTP_REQUESTS_TOTAL, Metrics};
+use crate::state_store::{error::Error, state_store::StateStore};
+
+/// Counter of the responses the error rate is computed from.
+const ERRORS_TOTAL: &str = "http_responses_5xx_total";
+
+/// How long a replica may take to apply a configuration, on top of the bake
+/// time, before the leader halts the rollout waiting for it.
+const APPLY_MARGIN: Duration = Duration::from_secs(60);
+
+const APPLYING: &str = "applying";
+const HEALTHY: &str = "healthy";
+const UNHEALTHY: &str = "unhealthy";
+
+/// What a replica running an older configuration does next.
+#[derive(Debug, PartialEq)]
+pub enum RolloutTurn {
+    /// Another replica is applying it, or the leader didn't open its rollout.
+    Wait,
+    /// A replica applying it had its error rate go up: it is never applied.
+    Halted,
+    /// This replica holds `slot` and applies it now.
+    Apply(u64),
+}
+
+/// Rolls a new configuration out to the replicas sharing the same config
+/// source and state store one at a time. The leader opens numbered slots,
+/// each claimed by a single replica still running an older configuration;
+/// that replica applies it, watches its own error rate for the bake time and
+/// reports back. The leader opens the next slot once the replica reported a
+/// healthy error rate, and halts the rollout for good otherwise, or when the
+/// replica never reported. Each revision rolls out on its own keys, so a
+/// fixed configuration starts over.
+pub struct ConfigRollout {
+    state_store: Arc<dyn StateStore>,
+    leader_election: Arc<dyn LeaderElection>,
+    metrics: Arc<Metrics>,
+    bake_time: Duration,
+    max_error_percent: u8,
+    /// The slot the leader last found claimed without any report.
+    unreported: Mutex<Option<(String, u64)>>,
+}
+
+impl ConfigRollout {
+    pub fn new(
+        state_store: Arc<dyn StateStore>,
+        leader_election: Arc<dyn LeaderElection>,
+        metrics: Arc<Metrics>,
+        bake_time: Duration,
+        max_error_percent: u8,
+    ) -> Self {
+        Self {
+            state_store,
+            leader_election,
+            metrics,
+            bake_time,
+            max_error_percent,
+            unreported: Mutex::new(None),
+        }
+    }
+
+    /// Moves the rollout of `revision` on when this replica is the leader:
+    /// opens its first slot, or the next one once the replica holding the
+    /// current slot reported a healthy error rate, or halts it.
+    pub async fn lead(&self, revision: &str) -> Result<(), Error> {
+        if !self.leader_election.is_leader() || self.is_halted(revision).await? {
+            return Ok(());
+        }
+
+        let Some(slot) = self.read::<u64>(&key(revision, "slot")).await? else {
+            info!("Rolling out configuration {}", revision);
+            return self.open_next_slot(revision).await;
+        };
+
+        let claims = self
+            .read::<u64>(&slot_key(revision, "claims", slot))
+            .await?
+            .unwrap_or_default();
+        if claims == 0 {
+            return Ok(());
+        }
+
+        let report = self
+            .read::<String>(&slot_key(revision, "report", slot))
+            .await?;
+        let unreported = match report.as_deref() {
+            Some(HEALTHY) => {
+                info!(
+                    "Configuration {} applied by replica {}, rolling it out to the next one",
+                    revision, slot
+                );
+                return self.open_next_slot(revision).await;
+            }
+            Some(UNHEALTHY) => {
+                return self
+                    .halt(
+                        revision,
+                        &format!("replica {} had its error rate go up", slot),
+                    )
+                    .await;
+            }
+            Some(_) => None,
+            None => Some((revision.to_string(), slot)),
+        };
+
+        // A replica reports right after claiming its slot: one that still
+        // didn't on the next round stopped on the way.
+        let stopped = match self.unreported.lock() {
+            Ok(mut last) => {
+                let stopped = unreported.is_some() && *last == unreported;
+                *last = unreported;
+                stopped
+            }
+            Err(_) => false,
+        };
+        if stopped {
+            return self
+                .halt(revision, &format!("replica {} never reported", slot))
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Claims the open slot of `revision`, when no other replica did.
+    pub async fn claim(&self, revision: &str) -> Result<RolloutTurn, Error> {
+        if self.is_halted(revision).await? {
+            return Ok(RolloutTurn::Halted);
+        }
+
+        let Some(slot) = self.read::<u64>(&key(revision, "slot")).await? else {
+            return Ok(RolloutTurn::Wait);
+        };
+
+        let claims = self
+            .state_store
+            .increment(&slot_key(revision, "claims", slot), 1, None)
+            .await?;
+        if claims != 1 {
+            return Ok(RolloutTurn::Wait);
+        }
+
+        self.state_store
+            .set(
+                &slot_key(revision, "report", slot),
+                Bytes::from_static(APPLYING.as_bytes()),
+                Some(self.bake_time + APPLY_MARGIN),
+            )
+            .await?;
+
+        Ok(RolloutTurn::Apply(slot))
+    }
+
+    /// Waits for the bake time, then tells whether the share of 5xx
+    /// responses this replica answered meanwhile stayed within bounds.
+    pub async fn bake(&self) -> bool {
+        let before = self.traffic();
+        tokio::time::sleep(self.bake_time).await;
+        let after = self.traffic();
+
+        let requests = after.0.saturating_sub(before.0);
+        let errors = after.1.saturating_sub(before.1);
+
+        errors * 100 <= requests * u64::from(self.max_error_percent)
+    }
+
+    /// Tells the leader how the configuration went on the replica holding
+    /// `slot`.
+    pub async fn report(&self, revision: &str, slot: u64, healthy: bool) -> Result<(), Error> {
+        let report = if healthy { HEALTHY } else { UNHEALTHY };
+
+        self.state_store
+            .set(
+                &slot_key(revision, "report", slot),
+                Bytes::from_static(report.as_bytes()),
+                None,
+            )
+            .await
+    }
+
+    pub async fn is_halted(&self, revision: &str) -> Result<bool, Error> {
+        Ok(self
+            .state_store
+            .get(&key(revision, "halted"))
+            .await?
+            .is_some())
+    }
+
+    async fn open_next_slot(&self, revision: &str) -> Result<(), Error> {
+        self.state_store
+            .increment(&key(revision, "slot"), 1, None)
+            .await
+            .map(|_| ())
+    }
+
+    async fn halt(&self, revision: &str, reason: &str) -> Result<(), Error> {
+        warn!(
+            "Halting the rollout of configuration {}: {}",
+            revision, reason
+        );
+
+        self.state_store
+            .set(
+                &key(revision, "halted"),
+                Bytes::from(reason.to_string()),
+                None,
+            )
+            .await
+    }
+
+    async fn read<T: FromStr>(&self, key: &str) -> Result<Option<T>, Error> {
+        Ok(self
+            .state_store
+            .get(key)
+            .await?
+            .and_then(|value| std::str::from_utf8(&value).ok()?.parse().ok()))
+    }
+
+    /// The requests answered so far and how many of them were 5xx.
+    fn traffic(&self) -> (u64, u64) {
+        (
+            self.metrics.get(HTTP_REQUESTS_TOTAL),
+            self.metrics.get(ERRORS_TOTAL),
+        )
+    }
+}
+
+fn key(revision: &str, name: &str) -> String {
+    format!("config-rollout:{}:{}", revision, name)
+}
+
+fn slot_key(revision: &str, name: &str, slot: u64) -> String {
+    format!("config-rollout:{}:{}:{}", revision, name, slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::config_rollout::{ConfigRollout, RolloutTurn};
+    use crate::leader_election::leader_election::MockLeaderElection;
+    use crate::metrics::metrics::Metrics;
+    use crate::state_store::memory_state_store::MemoryStateStore;
+    use crate::state_store::state_store::StateStore;
+
+    fn rollout(
+        state_store: &Arc<MemoryStateStore>,
+        is_leader: bool,
+        metrics: &Arc<Metrics>,
+    ) -> ConfigRollout {
+        let mut leader_election = MockLeaderElection::new();
+        leader_election.expect_is_leader().return_const(is_leader);
+
+        ConfigRollout::new(
+            Arc::clone(state_store) as Arc<dyn StateStore>,
+            Arc::new(leader_election),
+            Arc::clone(metrics),
+            Duration::from_millis(50),
+            5,
+        )
+    }
+
+    #[tokio::test]
+    async fn applies_the_configuration_one_replica_at_a_time() {
+        let state_store = Arc::new(MemoryStateStore::default());
+        let metrics = Arc::new(Metrics::default());
+        let leader = rollout(&state_store, true, &metrics);
+        let first = rollout(&state_store, false, &metrics);
+        let second = rollout(&state_store, false, &metrics);
+
+        assert_eq!(first.claim("r1").await.unwrap(), RolloutTurn::Wait);
+
+        leader.lead("r1").await.unwrap();
+        assert_eq!(first.claim("r1").await.unwrap(), RolloutTurn::Apply(1));
+        assert_eq!(second.claim("r1").await.unwrap(), RolloutTurn::Wait);
+
+        leader.lead("r1").await.unwrap();
+        assert_eq!(second.claim("r1").await.unwrap(), RolloutTurn::Wait);
+
+        first.report("r1", 1, true).await.unwrap();
+        leader.lead("r1").await.unwrap();
+        assert_eq!(second.claim("r1").await.unwrap(), RolloutTurn::Apply(2));
+    }
+
+    #[tokio::test]
+    async fn followers_dont_open_slots() {
+        let state_store = Arc::new(MemoryStateStore::default());
+        let metrics = Arc::new(Metrics::default());
+        let follower = rollout(&state_store, false, &metrics);
+
+        follower.lead("r1").await.unwrap();
+
+        assert_eq!(follower.claim("r1").await.unwrap(), RolloutTurn::Wait);
+    }
+
+    #[tokio::test]
+    async fn halts_once_a_replica_had_its_error_rate_go_up() {
+        let state_store = Arc::new(MemoryStateStore::default());
+        let metrics = Arc::new(Metrics::default());
+        let leader = rollout(&state_store, true, &metrics);
+        let first = rollout(&state_store, false, &metrics);
+        let second = rollout(&state_store, false, &metrics);
+
+        leader.lead("r1").await.unwrap();
+        assert_eq!(first.claim("r1").await.unwrap(), RolloutTurn::Apply(1));
+        first.report("r1", 1, false).await.unwrap();
+        leader.lead("r1").await.unwrap();
+
+        assert!(second.is_halted("r1").await.unwrap());
+        assert_eq!(second.claim("r1").await.unwrap(), RolloutTurn::Halted);
+
+        leader.lead("r2").await.unwrap();
+        assert_eq!(second.claim("r2").await.unwrap(), RolloutTurn::Apply(1));
+    }
+
+    #[tokio::test]
+    async fn halts_once_a_replica_never_reported() {
+        let state_store = Arc::new(MemoryStateStore::default());
+        let metrics = Arc::new(Metrics::default());
+        let leader = rollout(&state_store, true, &metrics);
+
+        leader.lead("r1").await.unwrap();
+        assert_eq!(leader.claim("r1").await.unwrap(), RolloutTurn::Apply(1));
+        state_store
+            .delete("config-rollout:r1:report:1")
+            .await
+            .unwrap();
+
+        leader.lead("r1").await.unwrap();
+        assert!(!leader.is_halted("r1").await.unwrap());
+
+        leader.lead("r1").await.unwrap();
+        assert!(leader.is_halted("r1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn bakes_healthy_while_the_errors_stay_within_bounds() {
+        let state_store = Arc::new(MemoryStateStore::default());
+        let metrics = Arc::new(Metrics::default());
+        let rollout = rollout(&state_store, false, &metrics);
+
+        assert!(rollout.bake().await);
+
+        let baking = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            for status in [200; 19].into_iter().chain([503]) {
+                metrics.record_response(status);
+            }
+        };
+        let (healthy, _) = tokio::join!(rollout.bake(), baking);
+        assert!(healthy);
+
+        let baking = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            for status in [200; 9].into_iter().chain([503]) {
+                metrics.record_response(status);
+            }
+        };
+        let (healthy, _) = tokio::join!(rollout.bake(), baking);
+        assert!(!healthy);
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{HeaderValue, Version, header};
+
+use crate::downstream_timeouts::ConnectionActivity;
+
+/// When keep-alive client connections are closed, so that long-lived clients
+/// reconnect and get spread again over the load balancers behind the DNS.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConnectionRecycling {
+    /// Requests answered on a connection before it is closed.
+    pub max_requests: Option<u64>,
+    /// How long a connection is kept before it is closed after its current
+    /// request.
+    pub max_age: Option<Duration>,
+}
+
+impl ConnectionRecycling {
+    fn is_due(&self, activity: &ConnectionActivity) -> bool {
+        self.max_requests
+            .is_some_and(|max_requests| activity.requests() >= max_requests)
+            || self
+                .max_age
+                .is_some_and(|max_age| activity.age() >= max_age)
+    }
+}
+
+/// Answers with `Connection: close` the last request of a connection due to
+/// be recycled. HTTP/2 connections are left alone, the header being
+/// meaningless to them.
+pub async fn recycle(
+    State(recycling): State<ConnectionRecycling>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let http1 = matches!(request.version(), Version::HTTP_10 | Version::HTTP_11);
+    let due = http1
+        && request
+            .extensions()
+            .get::<Arc<ConnectionActivity>>()
+            .is_some_and(|activity| recycling.is_due(activity));
+
+    let mut response = next.run(request).await;
+    if due {
+        response
+            .headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static("close"));
+    }
+    response
+}
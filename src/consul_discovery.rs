@@ -0,0 +1,404 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use http::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::http_client::http_client::HttpClient;
+use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+
+const CONSUL_TOKEN: HeaderName = HeaderName::from_static("x-consul-token");
+const CONSUL_INDEX: &str = "x-consul-index";
+/// How long Consul holds a query open when nothing changes.
+const BLOCKING_WAIT: Duration = Duration::from_secs(300);
+/// Between two queries, so that a catalog changing all the time doesn't
+/// turn into a busy loop.
+const MIN_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A service of the Consul catalog, written as
+/// `SERVICE[:TAG][@DATACENTER]`, e.g. `api:primary@eu-west`. Without a
+/// datacenter the one of the agent is used.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConsulService {
+    pub name: String,
+    pub tag: Option<String>,
+    pub datacenter: Option<String>,
+}
+
+impl FromStr for ConsulService {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected SERVICE[:TAG][@DATACENTER], got {}", value);
+
+        let (service, datacenter) = match value.split_once('@') {
+            Some((service, datacenter)) => (service, Some(datacenter.trim())),
+            None => (value, None),
+        };
+        let (name, tag) = match service.split_once(':') {
+            Some((name, tag)) => (name.trim(), Some(tag.trim())),
+            None => (service.trim(), None),
+        };
+
+        if name.is_empty()
+            || tag.is_some_and(str::is_empty)
+            || datacenter.is_some_and(str::is_empty)
+        {
+            return Err(invalid());
+        }
+
+        Ok(ConsulService {
+            name: name.to_string(),
+            tag: tag.map(str::to_string),
+            datacenter: datacenter.map(str::to_string),
+        })
+    }
+}
+
+impl Display for ConsulService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(datacenter) = &self.datacenter {
+            write!(f, "@{}", datacenter)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceEntry {
+    node: Node,
+    service: Service,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Node {
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Service {
+    #[serde(default)]
+    address: String,
+    port: u16,
+}
+
+/// Keeps the backends of a service in sync with the Consul catalog, through
+/// blocking queries answered as soon as the service changes. Only the
+/// instances whose health checks all pass are kept, as `http://ADDRESS:PORT`,
+/// and a failed query keeps the previous backends.
+pub struct ConsulDiscovery {
+    http_client: Arc<dyn HttpClient>,
+    address: String,
+    token: Option<String>,
+    service: ConsulService,
+    /// `X-Consul-Index` of the last answer, none before the first.
+    index: Mutex<Option<u64>>,
+    backends: watch::Sender<Vec<String>>,
+}
+
+impl ConsulDiscovery {
+    pub fn new(http_client: Arc<dyn HttpClient>, address: String, service: ConsulService) -> Self {
+        Self {
+            http_client,
+            address: address.trim_end_matches('/').to_string(),
+            token: None,
+            service,
+            index: Mutex::new(None),
+            backends: watch::Sender::new(Vec::new()),
+        }
+    }
+
+    /// ACL token allowed to read the service.
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Client profile for the blocking queries, which are held open by
+    /// Consul for up to five minutes.
+    pub fn client_builder() -> reqwest::ClientBuilder {
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(BLOCKING_WAIT + Duration::from_secs(30))
+    }
+
+    pub fn service(&self) -> &ConsulService {
+        &self.service
+    }
+
+    /// The backends found by the last successful query, the current ones
+    /// being marked as seen.
+    pub fn subscribe(&self) -> watch::Receiver<Vec<String>> {
+        self.backends.subscribe()
+    }
+
+    fn url(&self, index: Option<u64>) -> String {
+        let mut url = format!(
+            "{}/v1/health/service/{}?passing=true",
+            self.address, self.service.name
+        );
+        if let Some(tag) = &self.service.tag {
+            url.push_str(&format!("&tag={}", tag));
+        }
+        if let Some(datacenter) = &self.service.datacenter {
+            url.push_str(&format!("&dc={}", datacenter));
+        }
+        if let Some(index) = index {
+            url.push_str(&format!(
+                "&index={}&wait={}s",
+                index,
+                BLOCKING_WAIT.as_secs()
+            ));
+        }
+
+        url
+    }
+
+    /// Queries the healthy instances of the service, publishing and
+    /// returning them sorted. Once a first answer came, the query blocks
+    /// until the service changes or Consul gives up waiting.
+    pub async fn discover(&self) -> Result<Vec<String>, String> {
+        let index = self.index.lock().ok().and_then(|index| *index);
+        let url = self.url(index);
+
+        let mut headers = RequestHeaders::default();
+        if let Some(token) = &self.token {
+            let token =
+                HeaderValue::try_from(token.as_str()).map_err(|_| "invalid token".to_string())?;
+            headers.insert(CONSUL_TOKEN, token);
+        }
+
+        let response = self
+            .http_client
+            .execute(Request {
+                method: RequestMethod::Get,
+                url: url.clone(),
+                headers,
+                body: Default::default(),
+                timeout: None,
+            })
+            .await
+            .map_err(|error| error.to_string())?;
+
+        if response.status != 200 {
+            return Err(format!("{} returned status {}", url, response.status));
+        }
+
+        let next_index = response
+            .headers
+            .get(CONSUL_INDEX)
+            .and_then(|index| index.to_str().ok())
+            .and_then(|index| index.parse::<u64>().ok())
+            // Consul never answers 0, and waiting on it wouldn't block.
+            .filter(|index| *index > 0);
+        let body = response
+            .body
+            .collect()
+            .await
+            .map_err(|error| error.to_string())?;
+        let entries: Vec<ServiceEntry> = serde_json::from_slice(&body)
+            .map_err(|error| format!("{} answered garbage: {}", url, error))?;
+
+        if let Ok(mut index) = self.index.lock() {
+            *index = next_index;
+        }
+
+        let mut backends = entries
+            .into_iter()
+            .map(|entry| {
+                let address = match entry.service.address.is_empty() {
+                    true => entry.node.address,
+                    false => entry.service.address,
+                };
+                match address.contains(':') {
+                    true => format!("http://[{}]:{}", address, entry.service.port),
+                    false => format!("http://{}:{}", address, entry.service.port),
+                }
+            })
+            .collect::<Vec<_>>();
+        backends.sort();
+        backends.dedup();
+
+        self.backends.send_if_modified(|current| {
+            let changed = *current != backends;
+            if changed {
+                info!(
+                    "Consul service {} now has {} healthy backends",
+                    self.service,
+                    backends.len()
+                );
+                current.clone_from(&backends);
+            }
+            changed
+        });
+
+        Ok(backends)
+    }
+
+    /// Keeps querying the catalog forever, publishing the backends whenever
+    /// they change.
+    pub async fn execute(&self) {
+        info!(
+            "Discovering the backends of Consul service {} from {}",
+            self.service, self.address
+        );
+
+        let mut interval = time::interval(MIN_QUERY_INTERVAL);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.discover().await {
+                warn!(
+                    "Keeping the backends of Consul service {}: {}",
+                    self.service, error
+                );
+                time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use http::{HeaderName, HeaderValue};
+
+    use crate::consul_discovery::{ConsulDiscovery, ConsulService};
+    use crate::http_client::http_client::MockHttpClient;
+    use crate::http_client::request::RequestHeaders;
+    use crate::http_client::response::Response;
+
+    const HEALTHY_INSTANCES: &str = r#"[
+        {
+            "Node": {"Node": "node-2", "Address": "10.0.0.2"},
+            "Service": {"ID": "api-2", "Service": "api", "Address": "", "Port": 8080}
+        },
+        {
+            "Node": {"Node": "node-1", "Address": "10.0.0.1"},
+            "Service": {"ID": "api-1", "Service": "api", "Address": "10.1.0.1", "Port": 8080}
+        },
+        {
+            "Node": {"Node": "node-3", "Address": "fd00::3"},
+            "Service": {"ID": "api-3", "Service": "api", "Port": 9090}
+        }
+    ]"#;
+
+    fn respond(http_client: &mut MockHttpClient, url: &'static str, index: &'static str) {
+        http_client
+            .expect_execute()
+            .withf(move |request| {
+                request.url == url
+                    && request
+                        .headers
+                        .get("x-consul-token")
+                        .and_then(|value| value.to_str().ok())
+                        == Some("secret")
+            })
+            .times(1)
+            .returning(move |_| {
+                Ok(Response {
+                    status: 200,
+                    headers: RequestHeaders::from([(
+                        HeaderName::from_static("x-consul-index"),
+                        HeaderValue::from_static(index),
+                    )]),
+                    body: Bytes::from_static(HEALTHY_INSTANCES.as_bytes()).into(),
+                })
+            });
+    }
+
+    #[test]
+    fn parses_services() {
+        let service: ConsulService = "api:primary@eu-west".parse().unwrap();
+
+        assert_eq!(
+            service,
+            ConsulService {
+                name: "api".to_string(),
+                tag: Some("primary".to_string()),
+                datacenter: Some("eu-west".to_string()),
+            }
+        );
+        assert_eq!(service.to_string(), "api:primary@eu-west");
+        assert_eq!(
+            "api".parse::<ConsulService>().unwrap().to_string(),
+            "api".to_string()
+        );
+
+        for service in ["", ":primary", "api:", "api@"] {
+            assert!(service.parse::<ConsulService>().is_err(), "{}", service);
+        }
+    }
+
+    #[tokio::test]
+    async fn discovers_the_healthy_instances_then_blocks_until_they_change() {
+        let mut http_client = MockHttpClient::new();
+        respond(
+            &mut http_client,
+            "http://consul:8500/v1/health/service/api?passing=true&tag=primary",
+            "42",
+        );
+        respond(
+            &mut http_client,
+            "http://consul:8500/v1/health/service/api?passing=true&tag=primary&index=42&wait=300s",
+            "43",
+        );
+        let discovery = ConsulDiscovery::new(
+            Arc::new(http_client),
+            "http://consul:8500/".to_string(),
+            "api:primary".parse().unwrap(),
+        )
+        .with_token("secret".to_string());
+        let mut backends = discovery.subscribe();
+
+        assert_eq!(
+            discovery.discover().await,
+            Ok(vec![
+                "http://10.0.0.2:8080".to_string(),
+                "http://10.1.0.1:8080".to_string(),
+                "http://[fd00::3]:9090".to_string(),
+            ])
+        );
+        assert!(backends.has_changed().unwrap());
+        backends.borrow_and_update();
+
+        discovery.discover().await.unwrap();
+        assert!(!backends.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn fails_on_errors_of_the_agent() {
+        let mut http_client = MockHttpClient::new();
+        http_client.expect_execute().returning(|_| {
+            Ok(Response {
+                status: 403,
+                headers: RequestHeaders::default(),
+                body: Bytes::from_static(b"ACL not found").into(),
+            })
+        });
+        let discovery = ConsulDiscovery::new(
+            Arc::new(http_client),
+            "http://consul:8500".to_string(),
+            "api".parse().unwrap(),
+        );
+
+        assert!(discovery.discover().await.is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::{HeaderMap, HeaderName};
+use tracing::warn;
+
+use crate::state_store::memory_state_store::MemoryStateStore;
+use crate::state_store::state_store::StateStore;
+
+/// Response header in which the backends tell what a request cost.
+pub const X_REQUEST_COST: HeaderName = HeaderName::from_static("x-request-cost");
+
+/// Budget of the tenants without one of their own.
+pub const ANY_TENANT: &str = "*";
+
+/// What happens to the requests of a tenant that spent its budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CostBudgetAction {
+    /// Answers 429 until the window ends.
+    #[default]
+    Reject,
+    /// Forwards the requests after holding each of them for a while.
+    Throttle(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CostVerdict {
+    Allow,
+    Throttle(Duration),
+    Reject { retry_after_seconds: u64 },
+}
+
+/// Sums the costs reported by the backends for each tenant over fixed time
+/// windows, holding back the tenants going over their budget. The cost spent
+/// in a window is a counter of the state store, shared by the replicas using
+/// the same one, which expires with the window.
+pub struct CostBudgets {
+    budgets: HashMap<String, u64>,
+    window: Duration,
+    action: CostBudgetAction,
+    state_store: Arc<dyn StateStore>,
+}
+
+impl Default for CostBudgets {
+    fn default() -> Self {
+        Self::new(
+            HashMap::new(),
+            Duration::default(),
+            CostBudgetAction::default(),
+            Arc::new(MemoryStateStore::default()),
+        )
+    }
+}
+
+impl CostBudgets {
+    pub fn new(
+        budgets: HashMap<String, u64>,
+        window: Duration,
+        action: CostBudgetAction,
+        state_store: Arc<dyn StateStore>,
+    ) -> Self {
+        Self {
+            budgets,
+            window,
+            action,
+            state_store,
+        }
+    }
+
+    fn budget_of(&self, tenant: &str) -> Option<u64> {
+        self.budgets
+            .get(tenant)
+            .or_else(|| self.budgets.get(ANY_TENANT))
+            .copied()
+    }
+
+    /// Tenants are let through when the state store can't be reached.
+    pub async fn check(&self, tenant: &str) -> CostVerdict {
+        self.check_at(now_millis(), tenant).await
+    }
+
+    async fn check_at(&self, now_millis: u64, tenant: &str) -> CostVerdict {
+        let Some(budget) = self.budget_of(tenant) else {
+            return CostVerdict::Allow;
+        };
+
+        let window_start = self.window_start(now_millis);
+        let spent = match self.state_store.get(&key(tenant, window_start)).await {
+            Ok(spent) => spent
+                .and_then(|spent| std::str::from_utf8(&spent).ok()?.parse::<u64>().ok())
+                .unwrap_or_default(),
+            Err(error) => {
+                warn!("Failed to read the cost spent by {}: {}", tenant, error);
+                return CostVerdict::Allow;
+            }
+        };
+
+        if spent < budget {
+            return CostVerdict::Allow;
+        }
+
+        match self.action {
+            CostBudgetAction::Reject => CostVerdict::Reject {
+                retry_after_seconds: (window_start + self.window_millis() - now_millis)
+                    .div_ceil(1000),
+            },
+            CostBudgetAction::Throttle(delay) => CostVerdict::Throttle(delay),
+        }
+    }
+
+    /// Adds the cost the backend reported in the response headers.
+    pub async fn record(&self, tenant: &str, headers: &HeaderMap) {
+        self.record_at(now_millis(), tenant, headers).await;
+    }
+
+    async fn record_at(&self, now_millis: u64, tenant: &str, headers: &HeaderMap) {
+        if self.budget_of(tenant).is_none() {
+            return;
+        }
+
+        let Some(cost) = headers
+            .get(X_REQUEST_COST)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|cost| *cost >= 0)
+        else {
+            return;
+        };
+
+        let window_start = self.window_start(now_millis);
+        let window_left = Duration::from_millis(window_start + self.window_millis() - now_millis);
+
+        if let Err(error) = self
+            .state_store
+            .increment(&key(tenant, window_start), cost, Some(window_left))
+            .await
+        {
+            warn!("Failed to record the cost spent by {}: {}", tenant, error);
+        }
+    }
+
+    fn window_millis(&self) -> u64 {
+        (self.window.as_millis() as u64).max(1)
+    }
+
+    fn window_start(&self, now_millis: u64) -> u64 {
+        now_millis - now_millis % self.window_millis()
+    }
+}
+
+/// Key of the cost spent by `tenant` in the window starting at `window_start`.
+fn key(tenant: &str, window_start: u64) -> String {
+    format!("cost-budget:{}:{}", window_start, tenant)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use http::{HeaderMap, HeaderValue};
+
+    use crate::cost_budget::{
+        ANY_TENANT, CostBudgetAction, CostBudgets, CostVerdict, X_REQUEST_COST, key, now_millis,
+    };
+    use crate::state_store::memory_state_store::MemoryStateStore;
+    use crate::state_store::state_store::StateStore;
+
+    fn cost(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_COST, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[tokio::test]
+    async fn rejects_tenants_over_budget_until_the_window_ends() {
+        let budgets = CostBudgets::new(
+            HashMap::from([("tenant-a".to_string(), 10)]),
+            Duration::from_secs(60),
+            CostBudgetAction::Reject,
+            Arc::new(MemoryStateStore::default()),
+        );
+
+        budgets.record_at(1_000, "tenant-a", &cost("4")).await;
+        assert_eq!(
+            budgets.check_at(2_000, "tenant-a").await,
+            CostVerdict::Allow
+        );
+
+        budgets.record_at(3_000, "tenant-a", &cost("6")).await;
+        assert_eq!(
+            budgets.check_at(30_500, "tenant-a").await,
+            CostVerdict::Reject {
+                retry_after_seconds: 30
+            }
+        );
+
+        assert_eq!(
+            budgets.check_at(60_000, "tenant-a").await,
+            CostVerdict::Allow
+        );
+    }
+
+    #[tokio::test]
+    async fn throttles_tenants_over_budget() {
+        let budgets = CostBudgets::new(
+            HashMap::from([(ANY_TENANT.to_string(), 1)]),
+            Duration::from_secs(60),
+            CostBudgetAction::Throttle(Duration::from_millis(500)),
+            Arc::new(MemoryStateStore::default()),
+        );
+
+        budgets.record_at(0, "tenant-b", &cost("1")).await;
+
+        assert_eq!(
+            budgets.check_at(1_000, "tenant-b").await,
+            CostVerdict::Throttle(Duration::from_millis(500))
+        );
+        assert_eq!(
+            budgets.check_at(1_000, "tenant-c").await,
+            CostVerdict::Allow
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_tenants_without_budget_and_invalid_costs() {
+        let budgets = CostBudgets::new(
+            HashMap::from([("tenant-a".to_string(), 1)]),
+            Duration::from_secs(60),
+            CostBudgetAction::Reject,
+            Arc::new(MemoryStateStore::default()),
+        );
+
+        budgets.record_at(0, "tenant-a", &cost("lots")).await;
+        budgets.record_at(0, "tenant-a", &HeaderMap::new()).await;
+        budgets.record_at(0, "tenant-z", &cost("100")).await;
+
+        assert_eq!(budgets.check_at(0, "tenant-a").await, CostVerdict::Allow);
+        assert_eq!(budgets.check_at(0, "tenant-z").await, CostVerdict::Allow);
+    }
+
+    #[tokio::test]
+    async fn spent_costs_expire_with_their_window() {
+        let state_store = Arc::new(MemoryStateStore::default());
+        let budgets = CostBudgets::new(
+            HashMap::from([(ANY_TENANT.to_string(), 1)]),
+            Duration::from_millis(20),
+            CostBudgetAction::Reject,
+            Arc::clone(&state_store) as _,
+        );
+        let now_millis = now_millis();
+
+        budgets.record_at(now_millis, "tenant-a", &cost("1")).await;
+        let key = key("tenant-a", budgets.window_start(now_millis));
+        assert!(state_store.get(&key).await.unwrap().is_some());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(state_store.get(&key).await.unwrap(), None);
+    }
+}
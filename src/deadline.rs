@@ -0,0 +1,348 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, HttpBody};
+use axum::response::Response;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, header};
+use hyper::body::{Frame, SizeHint};
+use tokio::time::Sleep;
+
+use crate::http_client::request::RequestHeaders;
+
+/// Time left to answer, in the gRPC format: an amount followed by its unit,
+/// e.g. `250m` for 250 milliseconds.
+pub const GRPC_TIMEOUT: HeaderName = HeaderName::from_static("grpc-timeout");
+/// Time left to answer, in milliseconds.
+pub const X_REQUEST_DEADLINE: HeaderName = HeaderName::from_static("x-request-deadline");
+
+/// gRPC caps the amount of its timeouts to 8 digits.
+const GRPC_TIMEOUT_MAX_AMOUNT: u128 = 99_999_999;
+/// The `DEADLINE_EXCEEDED` status code of gRPC.
+const GRPC_DEADLINE_EXCEEDED: &str = "4";
+
+/// When the client gives up on a request, as told by its `grpc-timeout` or
+/// `X-Request-Deadline` header. The backends are given what is left of it,
+/// so that they can stop working on requests nobody waits for anymore.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadline {
+    at: Instant,
+    grpc: bool,
+    milliseconds: bool,
+}
+
+impl Deadline {
+    /// The earliest deadline told by the headers, counted from when the
+    /// request was received.
+    pub fn from_headers(headers: &HeaderMap, received_at: Instant) -> Option<Self> {
+        let grpc = headers
+            .get(GRPC_TIMEOUT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_grpc_timeout);
+        let milliseconds = headers
+            .get(X_REQUEST_DEADLINE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_millis);
+
+        let timeout = match (grpc, milliseconds) {
+            (Some(grpc), Some(milliseconds)) => grpc.min(milliseconds),
+            (timeout, None) | (None, timeout) => timeout?,
+        };
+
+        Some(Self {
+            at: received_at + timeout,
+            grpc: grpc.is_some(),
+            milliseconds: milliseconds.is_some(),
+        })
+    }
+
+    /// A deadline set by us rather than the client, e.g. the timeout of a
+    /// gRPC method, told to the backend in `grpc-timeout`.
+    pub fn grpc_after(timeout: Duration, received_at: Instant) -> Self {
+        Self {
+            at: received_at + timeout,
+            grpc: true,
+            milliseconds: false,
+        }
+    }
+
+    /// The earlier of both, told in the headers of both.
+    pub fn earliest(self, other: Self) -> Self {
+        Self {
+            at: self.at.min(other.at),
+            grpc: self.grpc || other.grpc,
+            milliseconds: self.milliseconds || other.milliseconds,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Cuts the body of `response` once past the deadline, when the backend
+    /// answered in time but is still streaming. The body of a gRPC call then
+    /// ends with a `DEADLINE_EXCEEDED` status in its trailers, the others are
+    /// aborted.
+    pub fn enforce(&self, response: Response<Body>, grpc: bool) -> Response<Body> {
+        let at = self.at;
+
+        response.map(|body| {
+            Body::new(DeadlineBody {
+                body,
+                sleep: Box::pin(tokio::time::sleep_until(at.into())),
+                grpc,
+                expired: false,
+            })
+        })
+    }
+
+    /// Tells the backend the time left, in the headers the client used.
+    pub fn stamp(&self, headers: &mut RequestHeaders) {
+        let remaining = self.remaining();
+
+        if self.grpc
+            && let Ok(value) = HeaderValue::try_from(format_grpc_timeout(remaining))
+        {
+            headers.insert(GRPC_TIMEOUT, value);
+        }
+        if self.milliseconds {
+            headers.insert(
+                X_REQUEST_DEADLINE,
+                HeaderValue::from(u64::try_from(remaining.as_millis()).unwrap_or(u64::MAX)),
+            );
+        }
+    }
+}
+
+/// What a gRPC call past its deadline is answered before the backend did:
+/// gRPC clients read the status of a call from the trailers, which a
+/// response without body carries in its headers.
+pub fn grpc_deadline_exceeded() -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/grpc"),
+    );
+    headers.extend(grpc_deadline_exceeded_status());
+
+    response
+}
+
+fn grpc_deadline_exceeded_status() -> HeaderMap {
+    let mut status = HeaderMap::new();
+    status.insert(
+        "grpc-status",
+        HeaderValue::from_static(GRPC_DEADLINE_EXCEEDED),
+    );
+    status.insert(
+        "grpc-message",
+        HeaderValue::from_static("Deadline Exceeded"),
+    );
+    status
+}
+
+/// A response body cut at the deadline, see `Deadline::enforce`.
+struct DeadlineBody {
+    body: Body,
+    sleep: Pin<Box<Sleep>>,
+    grpc: bool,
+    expired: bool,
+}
+
+impl HttpBody for DeadlineBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.expired {
+            return Poll::Ready(None);
+        }
+
+        if let Poll::Ready(frame) = Pin::new(&mut this.body).poll_frame(cx) {
+            return Poll::Ready(frame);
+        }
+
+        ready!(this.sleep.as_mut().poll(cx));
+        this.expired = true;
+
+        Poll::Ready(Some(match this.grpc {
+            true => Ok(Frame::trailers(grpc_deadline_exceeded_status())),
+            false => Err(axum::Error::new("the deadline of the request passed")),
+        }))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.expired || self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit_at = value.len().checked_sub(1)?;
+    let (amount, unit) = value.split_at(unit_at);
+    if amount.is_empty() || amount.len() > 8 {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+
+    match unit {
+        "H" => Some(Duration::from_secs(amount.checked_mul(3600)?)),
+        "M" => Some(Duration::from_secs(amount.checked_mul(60)?)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// The finest unit the amount fits in, rounded down.
+fn format_grpc_timeout(timeout: Duration) -> String {
+    let units = [
+        (timeout.as_nanos(), "n"),
+        (timeout.as_micros(), "u"),
+        (timeout.as_millis(), "m"),
+        (u128::from(timeout.as_secs()), "S"),
+        (u128::from(timeout.as_secs() / 60), "M"),
+    ];
+
+    units
+        .into_iter()
+        .find(|(amount, _)| *amount <= GRPC_TIMEOUT_MAX_AMOUNT)
+        .map(|(amount, unit)| format!("{}{}", amount, unit))
+        .unwrap_or_else(|| {
+            format!(
+                "{}H",
+                u128::from(timeout.as_secs() / 3600).min(GRPC_TIMEOUT_MAX_AMOUNT)
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use axum::body::Body;
+    use axum::response::Response;
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue};
+    use http_body_util::BodyExt;
+
+    use crate::deadline::{
+        Deadline, GRPC_TIMEOUT, X_REQUEST_DEADLINE, format_grpc_timeout, parse_grpc_timeout,
+    };
+    use crate::http_client::request::RequestHeaders;
+
+    #[test]
+    fn parses_grpc_timeouts() {
+        assert_eq!(parse_grpc_timeout("2H"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_grpc_timeout("3M"), Some(Duration::from_secs(180)));
+        assert_eq!(parse_grpc_timeout("5S"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_grpc_timeout("10u"), Some(Duration::from_micros(10)));
+        assert_eq!(parse_grpc_timeout("7n"), Some(Duration::from_nanos(7)));
+        assert_eq!(parse_grpc_timeout("250"), None);
+        assert_eq!(parse_grpc_timeout("m"), None);
+        assert_eq!(parse_grpc_timeout("123456789m"), None);
+    }
+
+    #[test]
+    fn formats_grpc_timeouts_in_the_finest_unit_that_fits() {
+        assert_eq!(format_grpc_timeout(Duration::from_nanos(1500)), "1500n");
+        assert_eq!(format_grpc_timeout(Duration::from_millis(250)), "250000u");
+        assert_eq!(format_grpc_timeout(Duration::from_secs(300)), "300000m");
+        assert_eq!(format_grpc_timeout(Duration::from_secs(200_000)), "200000S");
+        assert_eq!(format_grpc_timeout(Duration::ZERO), "0n");
+    }
+
+    #[test]
+    fn takes_the_earliest_deadline_of_the_headers() {
+        let received_at = Instant::now();
+        let mut headers = HeaderMap::new();
+        headers.insert(GRPC_TIMEOUT, HeaderValue::from_static("2S"));
+        headers.insert(X_REQUEST_DEADLINE, HeaderValue::from_static("500"));
+
+        let deadline = Deadline::from_headers(&headers, received_at).unwrap();
+
+        assert!(deadline.remaining() <= Duration::from_millis(500));
+        assert!(deadline.remaining() > Duration::from_millis(400));
+    }
+
+    #[test]
+    fn the_earliest_deadline_is_told_in_the_headers_of_both() {
+        let received_at = Instant::now();
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_DEADLINE, HeaderValue::from_static("5000"));
+
+        let deadline = Deadline::from_headers(&headers, received_at)
+            .unwrap()
+            .earliest(Deadline::grpc_after(
+                Duration::from_millis(300),
+                received_at,
+            ));
+
+        let mut forwarded = RequestHeaders::default();
+        deadline.stamp(&mut forwarded);
+
+        assert!(deadline.remaining() <= Duration::from_millis(300));
+        assert!(forwarded.contains_key(GRPC_TIMEOUT.as_str()));
+        assert!(forwarded.contains_key(X_REQUEST_DEADLINE.as_str()));
+    }
+
+    #[tokio::test]
+    async fn aborts_the_bodies_still_streaming_past_the_deadline() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_DEADLINE, HeaderValue::from_static("50"));
+        let deadline = Deadline::from_headers(&headers, Instant::now()).unwrap();
+        let response = Response::new(Body::from_stream(futures::stream::pending::<
+            Result<Bytes, std::io::Error>,
+        >()));
+
+        let collected = deadline
+            .enforce(response, false)
+            .into_body()
+            .collect()
+            .await;
+
+        assert!(collected.is_err());
+    }
+
+    #[test]
+    fn ignores_requests_without_deadline() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_DEADLINE, HeaderValue::from_static("soon"));
+
+        assert_eq!(Deadline::from_headers(&headers, Instant::now()), None);
+    }
+
+    #[test]
+    fn forwards_the_time_left_in_the_headers_of_the_client() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_DEADLINE, HeaderValue::from_static("1000"));
+        let received_at = Instant::now() - Duration::from_millis(400);
+        let deadline = Deadline::from_headers(&headers, received_at).unwrap();
+
+        let mut forwarded = RequestHeaders::default();
+        deadline.stamp(&mut forwarded);
+
+        let remaining: u64 = forwarded[X_REQUEST_DEADLINE.as_str()]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((500..=600).contains(&remaining));
+        assert!(!forwarded.contains_key(GRPC_TIMEOUT.as_str()));
+    }
+}
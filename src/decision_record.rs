@@ -0,0 +1,140 @@
+use axum::response::Response;
+use http::{HeaderName, HeaderValue};
+use rand::Rng;
+use serde::Serialize;
+use tracing::info;
+
+pub const X_LB_DECISION: HeaderName = HeaderName::from_static("x-lb-decision");
+
+/// Records how the load balancer routed a sampled fraction of the requests,
+/// so that routing behavior can be audited in production.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecisionRecords {
+    pub policy: &'static str,
+    pub sample_ratio: f64,
+    /// Attach the record to the response, on top of logging it.
+    pub emit_header: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DecisionRecord {
+    pub policy: &'static str,
+    pub candidates: Vec<String>,
+    pub excluded: Vec<Exclusion>,
+    pub retries: usize,
+    pub selected: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Exclusion {
+    pub server: String,
+    pub reason: String,
+}
+
+impl DecisionRecords {
+    pub fn sample(&self) -> Option<DecisionRecord> {
+        if self.sample_ratio <= 0.0 || !rand::rng().random_bool(self.sample_ratio.min(1.0)) {
+            return None;
+        }
+
+        Some(DecisionRecord {
+            policy: self.policy,
+            ..DecisionRecord::default()
+        })
+    }
+
+    /// Logs the record, and leaves it in the extensions of the response.
+    pub fn finish(&self, record: DecisionRecord, response: &mut Response) {
+        let serialized = match serde_json::to_string(&record) {
+            Ok(serialized) => serialized,
+            Err(_) => return,
+        };
+
+        info!("Routing decision: {}", serialized);
+
+        if self.emit_header
+            && let Ok(value) = HeaderValue::from_str(&serialized)
+        {
+            response.headers_mut().insert(X_LB_DECISION, value);
+        }
+
+        response.extensions_mut().insert(record);
+    }
+}
+
+impl DecisionRecord {
+    /// Healthy servers are the candidates, the other targets are excluded.
+    pub fn consider(&mut self, target_servers: &[String], healthy_servers: &[String]) {
+        self.candidates = healthy_servers.to_vec();
+        self.excluded.extend(
+            target_servers
+                .iter()
+                .filter(|server| !healthy_servers.contains(server))
+                .map(|server| Exclusion {
+                    server: server.clone(),
+                    reason: "unhealthy".to_string(),
+                }),
+        );
+    }
+
+    pub fn exclude(&mut self, server: String, reason: String) {
+        self.excluded.push(Exclusion { server, reason });
+    }
+
+    pub fn retry(&mut self, server: String, reason: String) {
+        self.exclude(server, reason);
+        self.retries += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decision_record::{DecisionRecord, DecisionRecords, Exclusion};
+
+    #[test]
+    fn nothing_is_sampled_by_default() {
+        assert!(DecisionRecords::default().sample().is_none());
+    }
+
+    #[test]
+    fn unhealthy_targets_and_retries_are_excluded() {
+        let mut record = DecisionRecords {
+            policy: "round-robin",
+            sample_ratio: 1.0,
+            emit_header: false,
+        }
+        .sample()
+        .unwrap();
+
+        record.consider(
+            &[
+                "server1".to_string(),
+                "server2".to_string(),
+                "server3".to_string(),
+            ],
+            &["server1".to_string(), "server2".to_string()],
+        );
+        record.retry("server1".to_string(), "status 503".to_string());
+        record.selected = Some("server2".to_string());
+
+        assert_eq!(
+            record,
+            DecisionRecord {
+                policy: "round-robin",
+                candidates: vec!["server1".to_string(), "server2".to_string()],
+                excluded: vec![
+                    Exclusion {
+                        server: "server3".to_string(),
+                        reason: "unhealthy".to_string(),
+                    },
+                    Exclusion {
+                        server: "server1".to_string(),
+                        reason: "status 503".to_string(),
+                    },
+                ],
+                retries: 1,
+                selected: Some("server2".to_string()),
+            }
+        );
+    }
+}
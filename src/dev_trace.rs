@@ -0,0 +1,234 @@
+use std::io::IsTerminal;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{HeaderMap, Method, StatusCode, Uri};
+
+use crate::decision_record::DecisionRecord;
+
+/// The escape codes coloring the trace, all empty when colors are off.
+struct Palette {
+    reset: &'static str,
+    bold: &'static str,
+    dim: &'static str,
+    red: &'static str,
+    green: &'static str,
+    yellow: &'static str,
+    cyan: &'static str,
+}
+
+const COLORS: Palette = Palette {
+    reset: "\x1b[0m",
+    bold: "\x1b[1m",
+    dim: "\x1b[2m",
+    red: "\x1b[31m",
+    green: "\x1b[32m",
+    yellow: "\x1b[33m",
+    cyan: "\x1b[36m",
+};
+
+const NO_COLORS: Palette = Palette {
+    reset: "",
+    bold: "",
+    dim: "",
+    red: "",
+    green: "",
+    yellow: "",
+    cyan: "",
+};
+
+static PALETTE: LazyLock<&Palette> = LazyLock::new(|| match colored_output() {
+    true => &COLORS,
+    false => &NO_COLORS,
+});
+
+/// Whether stdout takes colors: it has to be a terminal, rather than a file
+/// or a pipe, and `NO_COLOR` must not be set (https://no-color.org).
+pub fn colored_output() -> bool {
+    std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Prints the whole lifecycle of every request to stdout, for local
+/// debugging of the routing. It's colored when stdout is a terminal.
+pub async fn trace(request: Request, next: Next) -> Response {
+    let started_at = Instant::now();
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let request_headers = request.headers().clone();
+
+    let response = next.run(request).await;
+
+    println!(
+        "{}",
+        render(
+            &method,
+            &uri,
+            &request_headers,
+            &response,
+            started_at.elapsed(),
+            &PALETTE,
+        )
+    );
+
+    response
+}
+
+fn render(
+    method: &Method,
+    uri: &Uri,
+    request_headers: &HeaderMap,
+    response: &Response,
+    elapsed: Duration,
+    palette: &Palette,
+) -> String {
+    let mut lines = vec![format!(
+        "{}{}→ {} {}{}",
+        palette.bold, palette.cyan, method, uri, palette.reset
+    )];
+    lines.extend(headers(request_headers, '>', palette));
+
+    if let Some(record) = response.extensions().get::<DecisionRecord>() {
+        lines.push(format!(
+            "  {}candidates{} {}",
+            palette.dim,
+            palette.reset,
+            record.candidates.join(", ")
+        ));
+        for exclusion in &record.excluded {
+            lines.push(format!(
+                "  {}excluded{} {} ({})",
+                palette.yellow, palette.reset, exclusion.server, exclusion.reason
+            ));
+        }
+        lines.push(format!(
+            "  {}selected{} {} after {} retries",
+            palette.green,
+            palette.reset,
+            record.selected.as_deref().unwrap_or("none"),
+            record.retries
+        ));
+    }
+
+    lines.push(format!(
+        "{}{}← {}{} in {:.1} ms",
+        palette.bold,
+        status_color(response.status(), palette),
+        response.status(),
+        palette.reset,
+        elapsed.as_secs_f64() * 1000.0
+    ));
+    lines.extend(headers(response.headers(), '<', palette));
+
+    lines.join("\n")
+}
+
+fn headers<'a>(
+    headers: &'a HeaderMap,
+    direction: char,
+    palette: &'a Palette,
+) -> impl Iterator<Item = String> + 'a {
+    headers.iter().map(move |(name, value)| {
+        format!(
+            "  {}{} {}:{} {}",
+            palette.dim,
+            direction,
+            name,
+            palette.reset,
+            value.to_str().unwrap_or("<binary>")
+        )
+    })
+}
+
+fn status_color(status: StatusCode, palette: &Palette) -> &'static str {
+    if status.is_server_error() {
+        palette.red
+    } else if status.is_client_error() {
+        palette.yellow
+    } else if status.is_redirection() {
+        palette.cyan
+    } else {
+        palette.green
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::body::Body;
+    use axum::response::Response;
+    use http::{HeaderMap, HeaderValue, Method, StatusCode, Uri};
+
+    use crate::decision_record::{DecisionRecord, Exclusion};
+    use crate::dev_trace::{COLORS, NO_COLORS, render};
+
+    fn response() -> Response {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::BAD_GATEWAY;
+        response
+            .headers_mut()
+            .insert("content-type", HeaderValue::from_static("text/plain"));
+        response.extensions_mut().insert(DecisionRecord {
+            policy: "round-robin",
+            candidates: vec!["http://server1".to_string(), "http://server2".to_string()],
+            excluded: vec![Exclusion {
+                server: "http://server1".to_string(),
+                reason: "status 503".to_string(),
+            }],
+            retries: 1,
+            selected: Some("http://server2".to_string()),
+        });
+
+        response
+    }
+
+    fn request_headers() -> HeaderMap {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert("accept", HeaderValue::from_static("text/html"));
+
+        request_headers
+    }
+
+    #[test]
+    fn renders_the_routing_decision_and_both_sets_of_headers() {
+        let trace = render(
+            &Method::GET,
+            &Uri::from_static("/orders?id=1"),
+            &request_headers(),
+            &response(),
+            Duration::from_micros(12_340),
+            &COLORS,
+        );
+
+        for expected in [
+            "GET /orders?id=1",
+            "> accept:\x1b[0m text/html",
+            "http://server1 (status 503)",
+            "http://server2 after 1 retries",
+            "502 Bad Gateway\x1b[0m in 12.3 ms",
+            "< content-type:\x1b[0m text/plain",
+        ] {
+            assert!(trace.contains(expected), "{} not in {}", expected, trace);
+        }
+    }
+
+    #[test]
+    fn renders_without_escape_codes_when_colors_are_off() {
+        let trace = render(
+            &Method::GET,
+            &Uri::from_static("/orders?id=1"),
+            &request_headers(),
+            &response(),
+            Duration::from_micros(12_340),
+            &NO_COLORS,
+        );
+
+        assert!(!trace.contains('\x1b'), "{}", trace);
+        assert!(trace.contains("> accept: text/html"), "{}", trace);
+        assert!(trace.contains("← 502 Bad Gateway in 12.3 ms"), "{}", trace);
+    }
+}
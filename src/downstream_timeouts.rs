@@ -0,0 +1,515 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::serve::Listener;
+use bytes::Bytes;
+use futures::task::AtomicWaker;
+use hyper::body::{Frame, SizeHint};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{self, Sleep};
+
+use crate::metrics::metrics::{HEAD_TIMEOUTS_TOTAL, Metrics};
+
+/// Timeouts applied to the connections of the clients, so that slow or
+/// abandoned ones don't hold on to the load balancer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DownstreamTimeouts {
+    /// How long a connection may wait for its next request.
+    pub idle: Option<Duration>,
+    /// How long a client may pause while sending the head or the body of a
+    /// request.
+    pub read: Option<Duration>,
+    /// How long a client may take to send the whole head of a request, however
+    /// steadily it trickles in.
+    pub head: Option<Duration>,
+}
+
+/// What a client connection is doing, shared by its IO, which enforces the
+/// timeouts, and the requests it carries, which tell it when a request is in
+/// flight: a connection waiting for a backend is neither idle nor reading.
+#[derive(Debug)]
+pub struct ConnectionActivity {
+    started: time::Instant,
+    /// Milliseconds from `started` to the last sign of life.
+    last_activity: AtomicU64,
+    /// Milliseconds from `started` to the first byte of the head coming in.
+    head_started: AtomicU64,
+    requests_in_flight: AtomicUsize,
+    /// Requests received over the whole life of the connection.
+    requests_total: AtomicU64,
+    bodies_being_read: AtomicUsize,
+    /// Whether the head of a request is coming in.
+    receiving_head: AtomicBool,
+    /// Wakes the reads left without a deadline once the requests are over.
+    waker: AtomicWaker,
+}
+
+impl ConnectionActivity {
+    fn new() -> Self {
+        Self {
+            started: time::Instant::now(),
+            last_activity: AtomicU64::new(0),
+            head_started: AtomicU64::new(0),
+            requests_in_flight: AtomicUsize::new(0),
+            requests_total: AtomicU64::new(0),
+            bodies_being_read: AtomicUsize::new(0),
+            receiving_head: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Requests received on the connection so far, the current one included.
+    pub fn requests(&self) -> u64 {
+        self.requests_total.load(Ordering::Relaxed)
+    }
+
+    /// How long ago the connection was accepted.
+    pub fn age(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn elapsed_millis(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn touch(&self) {
+        self.last_activity
+            .store(self.elapsed_millis(), Ordering::Relaxed);
+    }
+
+    fn received(&self) {
+        self.touch();
+        if self.requests_in_flight.load(Ordering::Relaxed) == 0
+            && !self.receiving_head.swap(true, Ordering::Relaxed)
+        {
+            self.head_started
+                .store(self.elapsed_millis(), Ordering::Relaxed);
+        }
+    }
+
+    /// When the connection times out unless the client sends something,
+    /// `None` while it waits for the load balancer.
+    fn deadline(&self, timeouts: &DownstreamTimeouts) -> Option<time::Instant> {
+        match (
+            self.activity_deadline(timeouts),
+            self.head_deadline(timeouts),
+        ) {
+            (Some(activity), Some(head)) => Some(activity.min(head)),
+            (activity, head) => activity.or(head),
+        }
+    }
+
+    /// When the head coming in must be complete, however active the client.
+    fn head_deadline(&self, timeouts: &DownstreamTimeouts) -> Option<time::Instant> {
+        let receiving_head = self.requests_in_flight.load(Ordering::Relaxed) == 0
+            && self.receiving_head.load(Ordering::Relaxed);
+        if !receiving_head {
+            return None;
+        }
+        let head_started = Duration::from_millis(self.head_started.load(Ordering::Relaxed));
+
+        Some(self.started + head_started + timeouts.head?)
+    }
+
+    fn activity_deadline(&self, timeouts: &DownstreamTimeouts) -> Option<time::Instant> {
+        let idle = self.requests_in_flight.load(Ordering::Relaxed) == 0;
+        let reading = self.bodies_being_read.load(Ordering::Relaxed) > 0
+            || (idle && self.receiving_head.load(Ordering::Relaxed));
+
+        let timeout = match (idle, reading) {
+            (_, true) => timeouts.read?,
+            (true, false) => timeouts.idle?,
+            (false, false) => return None,
+        };
+        let last_activity = Duration::from_millis(self.last_activity.load(Ordering::Relaxed));
+
+        Some(self.started + last_activity + timeout)
+    }
+}
+
+/// A request or a request body in flight on a connection.
+struct InFlight {
+    activity: Arc<ConnectionActivity>,
+    body: bool,
+}
+
+impl InFlight {
+    fn start(activity: &Arc<ConnectionActivity>, body: bool) -> Self {
+        let counter = match body {
+            true => &activity.bodies_being_read,
+            false => {
+                activity.receiving_head.store(false, Ordering::Relaxed);
+                activity.requests_total.fetch_add(1, Ordering::Relaxed);
+                &activity.requests_in_flight
+            }
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        activity.touch();
+
+        Self {
+            activity: Arc::clone(activity),
+            body,
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let counter = match self.body {
+            true => &self.activity.bodies_being_read,
+            false => &self.activity.requests_in_flight,
+        };
+        counter.fetch_sub(1, Ordering::Relaxed);
+        // The wait for the next request or the next bytes starts now.
+        self.activity.touch();
+        self.activity.waker.wake();
+    }
+}
+
+/// Body that is in flight until its last frame.
+struct Tracked {
+    body: Body,
+    in_flight: Option<InFlight>,
+}
+
+impl HttpBody for Tracked {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        // Asked for more, the client is given a new read timeout.
+        if let Some(in_flight) = &this.in_flight {
+            in_flight.activity.touch();
+        }
+
+        let frame = Pin::new(&mut this.body).poll_frame(cx);
+        if matches!(frame, Poll::Ready(None | Some(Err(_)))) {
+            this.in_flight = None;
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// Keeps the activity of the connection up to date with its requests, from
+/// their head to the end of their response.
+pub async fn track(request: Request, next: Next) -> Response {
+    let Some(activity) = request
+        .extensions()
+        .get::<Arc<ConnectionActivity>>()
+        .cloned()
+    else {
+        return next.run(request).await;
+    };
+
+    let in_flight = InFlight::start(&activity, false);
+    let request = match request.body().is_end_stream() {
+        true => request,
+        false => request.map(|body| {
+            Body::new(Tracked {
+                body,
+                in_flight: Some(InFlight::start(&activity, true)),
+            })
+        }),
+    };
+
+    next.run(request).await.map(|body| {
+        Body::new(Tracked {
+            body,
+            in_flight: Some(in_flight),
+        })
+    })
+}
+
+/// Closes the connections going over their timeouts: reads fail once the
+/// deadline passes, which makes the server drop the connection. Those
+/// dropped for a head too slow to come in are counted as
+/// `HEAD_TIMEOUTS_TOTAL`.
+pub struct TimedIo<I> {
+    io: I,
+    timeouts: DownstreamTimeouts,
+    activity: Arc<ConnectionActivity>,
+    metrics: Arc<Metrics>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<I> TimedIo<I> {
+    pub fn new(io: I, timeouts: DownstreamTimeouts, metrics: Arc<Metrics>) -> Self {
+        Self {
+            io,
+            timeouts,
+            activity: Arc::new(ConnectionActivity::new()),
+            metrics,
+            sleep: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &I {
+        &self.io
+    }
+
+    pub fn activity(&self) -> Arc<ConnectionActivity> {
+        Arc::clone(&self.activity)
+    }
+
+    fn poll_deadline(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Registered first, so that a request ending meanwhile isn't missed.
+        self.activity.waker.register(cx.waker());
+        let Some(deadline) = self.activity.deadline(&self.timeouts) else {
+            return Poll::Pending;
+        };
+
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(time::sleep_until(deadline)));
+        if sleep.deadline() != deadline {
+            sleep.as_mut().reset(deadline);
+        }
+
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                let head_timed_out = self
+                    .activity
+                    .head_deadline(&self.timeouts)
+                    .is_some_and(|head_deadline| head_deadline <= deadline);
+                if head_timed_out {
+                    self.metrics.increment(HEAD_TIMEOUTS_TOTAL);
+                }
+
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "client connection timed out",
+                )))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for TimedIo<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+
+        match Pin::new(&mut this.io).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                if buf.filled().len() > filled {
+                    this.activity.received();
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+            Poll::Pending => this.poll_deadline(cx),
+        }
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for TimedIo<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = Pin::new(&mut this.io).poll_write(cx, buf);
+        if matches!(written, Poll::Ready(Ok(written)) if written > 0) {
+            this.activity.touch();
+        }
+        written
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = Pin::new(&mut this.io).poll_write_vectored(cx, bufs);
+        if matches!(written, Poll::Ready(Ok(written)) if written > 0) {
+            this.activity.touch();
+        }
+        written
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+/// Hands the connections of a listener over with the timeouts enforced.
+pub struct TimedListener<L> {
+    listener: L,
+    timeouts: DownstreamTimeouts,
+    metrics: Arc<Metrics>,
+}
+
+impl<L> TimedListener<L> {
+    pub fn new(listener: L, timeouts: DownstreamTimeouts, metrics: Arc<Metrics>) -> Self {
+        Self {
+            listener,
+            timeouts,
+            metrics,
+        }
+    }
+}
+
+impl<L: Listener> Listener for TimedListener<L> {
+    type Io = TimedIo<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let (io, address) = self.listener.accept().await;
+
+        (
+            TimedIo::new(io, self.timeouts, Arc::clone(&self.metrics)),
+            address,
+        )
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.listener.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+    use tokio::time::{self, Instant};
+
+    use crate::downstream_timeouts::{DownstreamTimeouts, InFlight, TimedIo};
+    use crate::metrics::metrics::{HEAD_TIMEOUTS_TOTAL, Metrics};
+
+    fn connection_with(
+        timeouts: DownstreamTimeouts,
+    ) -> (DuplexStream, TimedIo<DuplexStream>, Arc<Metrics>) {
+        let (client, server) = tokio::io::duplex(1024);
+        let metrics = Arc::new(Metrics::default());
+
+        (
+            client,
+            TimedIo::new(server, timeouts, Arc::clone(&metrics)),
+            metrics,
+        )
+    }
+
+    fn connection() -> (DuplexStream, TimedIo<DuplexStream>) {
+        let (client, server, _) = connection_with(DownstreamTimeouts {
+            idle: Some(Duration::from_millis(200)),
+            read: Some(Duration::from_millis(50)),
+            head: None,
+        });
+
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn closes_idle_connections() {
+        let (_client, mut server) = connection();
+        let started = Instant::now();
+
+        let error = server.read(&mut [0; 64]).await.unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn gives_clients_sending_a_request_the_read_timeout() {
+        let (mut client, mut server) = connection();
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        server.read_exact(&mut [0; 16]).await.unwrap();
+        let started = Instant::now();
+
+        let error = server.read(&mut [0; 64]).await.unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(started.elapsed() < Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn never_times_out_while_the_request_is_in_flight() {
+        let (_client, mut server) = connection();
+        let _in_flight = InFlight::start(&server.activity(), false);
+
+        assert!(
+            time::timeout(Duration::from_millis(400), server.read(&mut [0; 64]))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn drops_heads_trickling_in_past_the_head_timeout() {
+        let (mut client, mut server, metrics) = connection_with(DownstreamTimeouts {
+            idle: None,
+            read: Some(Duration::from_millis(100)),
+            head: Some(Duration::from_millis(200)),
+        });
+        let started = Instant::now();
+
+        // A byte every 50ms keeps the read timeout at bay, not the head one.
+        let error = loop {
+            client.write_all(b"X").await.unwrap();
+            server.read_exact(&mut [0; 1]).await.unwrap();
+            match time::timeout(Duration::from_millis(50), server.read(&mut [0; 64])).await {
+                Ok(result) => break result.unwrap_err(),
+                Err(_) => assert!(started.elapsed() < Duration::from_secs(1)),
+            }
+        };
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert_eq!(metrics.get(HEAD_TIMEOUTS_TOTAL), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_count_other_timeouts_as_head_timeouts() {
+        let (_client, mut server, metrics) = connection_with(DownstreamTimeouts {
+            idle: Some(Duration::from_millis(50)),
+            read: None,
+            head: Some(Duration::from_millis(200)),
+        });
+
+        let error = server.read(&mut [0; 64]).await.unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(metrics.get(HEAD_TIMEOUTS_TOTAL), 0);
+    }
+}
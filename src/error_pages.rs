@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use axum::body::Body;
+use axum::response::Response;
+use bytes::Bytes;
+use http::{HeaderValue, StatusCode, header};
+
+/// Written as `STATUS=PATH`, e.g. `503=/etc/wakanda-lb/maintenance.html`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorPageSource {
+    pub status: StatusCode,
+    pub path: String,
+}
+
+impl FromStr for ErrorPageSource {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (status, path) = value
+            .split_once('=')
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or_else(|| format!("expected STATUS=PATH, got {}", value))?;
+
+        let status = status
+            .parse::<u16>()
+            .ok()
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .filter(|status| status.is_client_error() || status.is_server_error())
+            .ok_or_else(|| format!("expected a 4xx or 5xx status, got {}", status))?;
+
+        Ok(ErrorPageSource {
+            status,
+            path: path.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorPage {
+    pub content_type: HeaderValue,
+    pub body: Bytes,
+}
+
+impl ErrorPage {
+    /// The content type is told by the extension of the file.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content_type = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("html" | "htm") => "text/html; charset=utf-8",
+            Some("json") => "application/json",
+            _ => "text/plain; charset=utf-8",
+        };
+
+        Ok(ErrorPage {
+            content_type: HeaderValue::from_static(content_type),
+            body: Bytes::from(std::fs::read(path)?),
+        })
+    }
+
+    pub fn inline(body: String) -> Self {
+        ErrorPage {
+            content_type: HeaderValue::from_static("text/plain; charset=utf-8"),
+            body: Bytes::from(body),
+        }
+    }
+}
+
+/// Answered instead of the bare 503 while no server is alive, so that end
+/// users get e.g. a friendly maintenance page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FallbackResponse {
+    pub status: StatusCode,
+    pub page: ErrorPage,
+}
+
+impl FallbackResponse {
+    /// Keeps the `Retry-After` of the 503 it stands in for, if it is one.
+    pub fn response(&self, retry_after_seconds: u64) -> Response {
+        let mut response = Response::new(Body::from(self.page.body.clone()));
+        *response.status_mut() = self.status;
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, self.page.content_type.clone());
+        if self.status == StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_seconds));
+        }
+
+        response
+    }
+}
+
+/// Bodies of the errors answered by the load balancer itself, e.g. a
+/// branded page for the 503 sent while no server is healthy.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPages(pub HashMap<StatusCode, ErrorPage>);
+
+impl ErrorPages {
+    pub fn apply(&self, response: Response) -> Response {
+        let Some(page) = self.0.get(&response.status()) else {
+            return response;
+        };
+
+        let (mut parts, _) = response.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, page.content_type.clone());
+
+        Response::from_parts(parts, Body::from(page.body.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::body::to_bytes;
+    use axum::response::IntoResponse;
+    use bytes::Bytes;
+    use http::{HeaderValue, StatusCode, header};
+
+    use crate::error_pages::{ErrorPage, ErrorPageSource, ErrorPages, FallbackResponse};
+
+    #[test]
+    fn parses_status_and_path() {
+        assert_eq!(
+            "503=/srv/maintenance.html".parse(),
+            Ok(ErrorPageSource {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                path: "/srv/maintenance.html".to_string(),
+            })
+        );
+        assert!("200=/srv/ok.html".parse::<ErrorPageSource>().is_err());
+        assert!("503".parse::<ErrorPageSource>().is_err());
+    }
+
+    #[test]
+    fn content_type_follows_the_extension() {
+        let path = std::env::temp_dir().join(format!("wakanda-lb-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"error":"unavailable"}"#).unwrap();
+
+        let page = ErrorPage::load(&path).unwrap();
+
+        assert_eq!(page.content_type, "application/json");
+        assert_eq!(page.body, Bytes::from(r#"{"error":"unavailable"}"#));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn replaces_the_body_of_configured_statuses_only() {
+        let error_pages = ErrorPages(HashMap::from([(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorPage {
+                content_type: HeaderValue::from_static("text/html; charset=utf-8"),
+                body: Bytes::from("<h1>Back soon</h1>"),
+            },
+        )]));
+
+        let response = error_pages.apply(
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "10")],
+                "No one is alive",
+            )
+                .into_response(),
+        );
+
+        assert_eq!(response.headers()[header::RETRY_AFTER], "10");
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+            Bytes::from("<h1>Back soon</h1>")
+        );
+
+        let response =
+            error_pages.apply((StatusCode::BAD_GATEWAY, "Network error").into_response());
+
+        assert_eq!(
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+            Bytes::from("Network error")
+        );
+    }
+
+    #[tokio::test]
+    async fn fallback_response_has_its_own_status_and_content_type() {
+        let fallback = FallbackResponse {
+            status: StatusCode::OK,
+            page: ErrorPage {
+                content_type: HeaderValue::from_static("text/html; charset=utf-8"),
+                body: Bytes::from("<h1>Down for maintenance</h1>"),
+            },
+        };
+
+        let response = fallback.response(30);
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+            Bytes::from("<h1>Down for maintenance</h1>")
+        );
+    }
+
+    #[test]
+    fn fallback_503_keeps_retry_after() {
+        let fallback = FallbackResponse {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            page: ErrorPage::inline("Back soon".to_string()),
+        };
+
+        let response = fallback.response(30);
+
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+    }
+}
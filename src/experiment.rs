@@ -0,0 +1,253 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use http::{HeaderMap, HeaderName, HeaderValue, header};
+
+use crate::forwarded_headers::{TrustedProxies, forwarded_client};
+use crate::pools::pools::fnv1a;
+
+pub const X_EXPERIMENT_VARIANT: HeaderName = HeaderName::from_static("x-experiment-variant");
+
+/// Assigns every client a variant of an A/B experiment, told to the pool
+/// routes and the backends through `X-Experiment-Variant`. A client keeps its
+/// variant across requests and instances: it is picked from a hash of the
+/// salt and the value of the cookie, or of the client address without one,
+/// each variant taking a share of the clients proportional to its weight.
+/// Changing the salt reshuffles the clients.
+///
+/// The variants are written as `VARIANT:WEIGHT,...`, e.g.
+/// `control:50,treatment:50`, the weight being `1` when not given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Experiment {
+    pub variants: Vec<(String, u32)>,
+    pub salt: String,
+    /// A cookie identifying the client, e.g. a session or user id, so that it
+    /// keeps its variant when its address changes.
+    pub cookie: Option<String>,
+    /// Hash the client named by `X-Forwarded-For`, set by a trusted proxy in
+    /// front of us, instead of the peer.
+    pub trust_forwarded_for: bool,
+    pub trusted_proxies: TrustedProxies,
+}
+
+impl Experiment {
+    /// Replaces the variant claimed by the client, if any. Clients without
+    /// the cookie nor a known address are left out of the experiment.
+    pub fn assign(&self, headers: &mut HeaderMap, client: Option<IpAddr>) {
+        headers.remove(X_EXPERIMENT_VARIANT);
+
+        if self.variants.is_empty() {
+            return;
+        }
+
+        let key = match self.cookie_value(headers) {
+            Some(value) => value,
+            None => {
+                let forwarded_client = self
+                    .trust_forwarded_for
+                    .then(|| forwarded_client(headers, &self.trusted_proxies))
+                    .flatten();
+                let Some(client) = forwarded_client.or(client) else {
+                    return;
+                };
+                client.to_string()
+            }
+        };
+
+        if let Ok(variant) = HeaderValue::from_str(self.variant(&key)) {
+            headers.insert(X_EXPERIMENT_VARIANT, variant);
+        }
+    }
+
+    fn cookie_value(&self, headers: &HeaderMap) -> Option<String> {
+        let name = self.cookie.as_deref()?;
+
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(cookie, value)| *cookie == name && !value.is_empty())
+            .map(|(_, value)| value.to_string())
+    }
+
+    fn variant(&self, key: &str) -> &str {
+        let total = self
+            .variants
+            .iter()
+            .map(|(_, weight)| u64::from(*weight))
+            .sum::<u64>();
+        let mut bucket = fnv1a(format!("{}:{}", self.salt, key).as_bytes()) % total;
+
+        for (name, weight) in &self.variants {
+            match bucket.checked_sub(u64::from(*weight)) {
+                Some(rest) => bucket = rest,
+                None => return name,
+            }
+        }
+
+        unreachable!("bucket is below the total weight")
+    }
+}
+
+impl FromStr for Experiment {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected VARIANT[:WEIGHT],..., got {}", value);
+
+        let variants = value
+            .split(',')
+            .map(|variant| {
+                let (name, weight) = match variant.split_once(':') {
+                    Some((name, weight)) => {
+                        (name.trim(), weight.trim().parse().map_err(|_| invalid())?)
+                    }
+                    None => (variant.trim(), 1),
+                };
+
+                if name.is_empty() || HeaderValue::from_str(name).is_err() {
+                    return Err(invalid());
+                }
+
+                Ok((name.to_string(), weight))
+            })
+            .collect::<Result<Vec<(String, u32)>, _>>()?;
+
+        if variants
+            .iter()
+            .map(|(_, weight)| u64::from(*weight))
+            .sum::<u64>()
+            == 0
+        {
+            return Err(invalid());
+        }
+
+        Ok(Experiment {
+            variants,
+            ..Experiment::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use http::{HeaderMap, HeaderValue, header};
+
+    use crate::experiment::{Experiment, X_EXPERIMENT_VARIANT};
+    use crate::forwarded_headers::{TrustedProxies, X_FORWARDED_FOR};
+
+    fn experiment() -> Experiment {
+        Experiment {
+            salt: "checkout-2026".to_string(),
+            cookie: Some("session".to_string()),
+            .."control:50,treatment:50".parse().unwrap()
+        }
+    }
+
+    fn assigned(experiment: &Experiment, cookie: Option<&str>, client: &str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        if let Some(cookie) = cookie {
+            headers.insert(header::COOKIE, HeaderValue::from_str(cookie).unwrap());
+        }
+
+        experiment.assign(&mut headers, client.parse::<IpAddr>().ok());
+
+        headers
+            .get(X_EXPERIMENT_VARIANT)
+            .map(|variant| variant.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn parses_variants_and_weights() {
+        assert_eq!(
+            "control:90, treatment:10"
+                .parse::<Experiment>()
+                .unwrap()
+                .variants,
+            vec![("control".to_string(), 90), ("treatment".to_string(), 10)]
+        );
+        assert_eq!(
+            "a,b".parse::<Experiment>().unwrap().variants,
+            vec![("a".to_string(), 1), ("b".to_string(), 1)]
+        );
+
+        for variants in ["", "a:x", "a:0,b:0", ",b"] {
+            assert!(variants.parse::<Experiment>().is_err(), "{}", variants);
+        }
+    }
+
+    #[test]
+    fn clients_keep_their_variant() {
+        let experiment = experiment();
+
+        for session in 0..20 {
+            let cookie = format!("theme=dark; session={}", session);
+            let variant = assigned(&experiment, Some(&cookie), "10.0.0.1");
+
+            assert!(variant.is_some());
+            assert_eq!(assigned(&experiment, Some(&cookie), "10.0.0.2"), variant);
+        }
+        assert_eq!(
+            assigned(&experiment, None, "10.0.0.1"),
+            assigned(&experiment, Some("session="), "10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn splits_clients_by_weight() {
+        let experiment = Experiment {
+            salt: "checkout-2026".to_string(),
+            .."control:3,treatment:1".parse().unwrap()
+        };
+
+        let treated = (0..1000)
+            .filter(|index| {
+                let client = format!("10.0.{}.{}", index / 256, index % 256);
+                assigned(&experiment, None, &client).as_deref() == Some("treatment")
+            })
+            .count();
+
+        assert!((180..320).contains(&treated), "{}", treated);
+    }
+
+    #[test]
+    fn replaces_the_variant_claimed_by_the_client() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_EXPERIMENT_VARIANT, HeaderValue::from_static("treatment"));
+        let mut unknown_client = headers.clone();
+
+        Experiment::default().assign(&mut headers, "10.0.0.1".parse().ok());
+        experiment().assign(&mut unknown_client, None);
+
+        assert!(headers.is_empty());
+        assert!(unknown_client.is_empty());
+    }
+
+    #[test]
+    fn ignores_the_forwarded_addresses_made_up_by_the_client() {
+        let experiment = Experiment {
+            trust_forwarded_for: true,
+            trusted_proxies: TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]),
+            ..experiment()
+        };
+        let variant = |forwarded_for: &str| {
+            let mut headers = HeaderMap::from_iter([(
+                X_FORWARDED_FOR,
+                HeaderValue::from_str(forwarded_for).unwrap(),
+            )]);
+            experiment.assign(&mut headers, "10.0.0.1".parse().ok());
+            headers[X_EXPERIMENT_VARIANT].clone()
+        };
+
+        let expected = variant("203.0.113.7, 10.0.0.2");
+
+        for spoofed in 0..20 {
+            let forwarded_for = format!("198.51.100.{}, 203.0.113.7, 10.0.0.2", spoofed);
+            assert_eq!(variant(&forwarded_for), expected);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use serde::Serialize;
+use tracing::error;
+
+use crate::http_client::error::Error as HttpClientError;
+use crate::http_client::response::Response as HttpClientResponse;
+
+/// The failed attempts of a request, so that an outage spanning several
+/// backends isn't reported as the failure of the last one only.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct FailedAttempts {
+    pub attempts: Vec<FailedAttempt>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FailedAttempt {
+    pub server: String,
+    pub error: &'static str,
+    pub status: u16,
+    pub elapsed_ms: u64,
+}
+
+impl FailedAttempts {
+    pub fn record(
+        &mut self,
+        server: String,
+        result: &Result<HttpClientResponse, HttpClientError>,
+        elapsed: Duration,
+    ) {
+        let (error, status) = match result {
+            Ok(response) if response.status == StatusCode::TOO_MANY_REQUESTS.as_u16() => {
+                ("rate limited", response.status)
+            }
+            Ok(response) => ("unavailable", response.status),
+            Err(HttpClientError::Network(_)) => ("network", StatusCode::BAD_GATEWAY.as_u16()),
+            Err(HttpClientError::Timeout) => ("timeout", StatusCode::GATEWAY_TIMEOUT.as_u16()),
+            Err(HttpClientError::InvalidRequest(_)) => {
+                ("invalid request", StatusCode::BAD_REQUEST.as_u16())
+            }
+        };
+
+        self.attempts.push(FailedAttempt {
+            server,
+            error,
+            status,
+            elapsed_ms: u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.attempts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attempts.is_empty()
+    }
+
+    /// Answers with the status of the last attempt, listing all of them.
+    pub fn into_response(self) -> Response {
+        let status = self
+            .attempts
+            .last()
+            .and_then(|attempt| StatusCode::from_u16(attempt.status).ok())
+            .unwrap_or(StatusCode::BAD_GATEWAY);
+
+        if let Ok(attempts) = serde_json::to_string(&self.attempts) {
+            error!("Every attempt failed: {}", attempts);
+        }
+
+        (
+            status,
+            Json(serde_json::json!({
+                "error": "Every attempt failed",
+                "attempts": self.attempts,
+            })),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use http::StatusCode;
+
+    use crate::failed_attempts::{FailedAttempt, FailedAttempts};
+    use crate::http_client::error::Error as HttpClientError;
+    use crate::http_client::request::RequestHeaders;
+    use crate::http_client::response::Response as HttpClientResponse;
+
+    #[test]
+    fn classifies_each_attempt() {
+        let mut failed_attempts = FailedAttempts::default();
+
+        failed_attempts.record(
+            "http://first.com".to_string(),
+            &Err(HttpClientError::Network("Connection refused".to_string())),
+            Duration::from_millis(3),
+        );
+        failed_attempts.record(
+            "http://second.com".to_string(),
+            &Ok(HttpClientResponse {
+                status: 503,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            }),
+            Duration::from_millis(12),
+        );
+
+        assert_eq!(
+            failed_attempts.attempts,
+            vec![
+                FailedAttempt {
+                    server: "http://first.com".to_string(),
+                    error: "network",
+                    status: 502,
+                    elapsed_ms: 3,
+                },
+                FailedAttempt {
+                    server: "http://second.com".to_string(),
+                    error: "unavailable",
+                    status: 503,
+                    elapsed_ms: 12,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_with_the_status_of_the_last_attempt() {
+        let mut failed_attempts = FailedAttempts::default();
+        failed_attempts.record(
+            "http://first.com".to_string(),
+            &Err(HttpClientError::Timeout),
+            Duration::from_secs(1),
+        );
+
+        let response = failed_attempts.into_response();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["attempts"][0]["server"], "http://first.com");
+        assert_eq!(body["attempts"][0]["error"], "timeout");
+    }
+}
@@ -0,0 +1,351 @@
+use http::{HeaderMap, HeaderName, HeaderValue, header};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+pub const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+pub const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+pub const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+/// The client named by `X-Forwarded-For`, to be trusted only behind a proxy
+/// setting it. The addresses are walked from the right, the one appended by
+/// the proxy in front, skipping the `trusted_proxies`, so that the first
+/// address they didn't set is taken rather than one the client made up.
+pub fn forwarded_client(headers: &HeaderMap, trusted_proxies: &TrustedProxies) -> Option<IpAddr> {
+    let hops = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+
+    let mut client = None;
+    for hop in hops.into_iter().rev() {
+        let address = hop.trim().parse().ok()?;
+        client = Some(address);
+
+        if !trusted_proxies.contains(address) {
+            break;
+        }
+    }
+
+    client
+}
+
+/// The proxies in front of the load balancer, written as comma-separated
+/// addresses or networks, e.g. `10.0.0.0/8,192.168.1.7`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrustedProxies(pub Vec<Network>);
+
+impl TrustedProxies {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(address))
+    }
+}
+
+/// An address, or a network written as `ADDRESS/PREFIX`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected ADDRESS[/PREFIX], got {}", value);
+
+        let (address, prefix) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value.trim(), None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max_prefix = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None => max_prefix,
+        };
+
+        if prefix > max_prefix {
+            return Err(invalid());
+        }
+
+        Ok(Network { address, prefix })
+    }
+}
+
+/// Tells the backends who the real client is through the `X-Forwarded-*`
+/// headers, and optionally the standard `Forwarded` one.
+#[derive(Debug, Clone, Default)]
+pub struct ForwardedHeaders {
+    /// Keep the values set by a trusted proxy in front of us and append to
+    /// them, instead of overwriting whatever the client sent.
+    pub trust_incoming: bool,
+    /// The only peers whose values are kept.
+    pub trusted_proxies: TrustedProxies,
+    pub emit_forwarded: bool,
+}
+
+impl ForwardedHeaders {
+    /// Sets the headers of a request a client sent to the listener over
+    /// `scheme`, `http` or `https`.
+    pub fn apply(&self, headers: &mut HeaderMap, client: Option<IpAddr>, scheme: &str) {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .map(str::to_owned);
+
+        let trusted = self.trust_incoming
+            && client.is_some_and(|client| self.trusted_proxies.contains(client));
+        let incoming = |headers: &HeaderMap, name: &HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .filter(|_| trusted)
+                .map(str::to_owned)
+        };
+        // The list headers may come split over several lines.
+        let incoming_list = |headers: &HeaderMap, name: &HeaderName| {
+            let values = headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>();
+
+            (trusted && !values.is_empty()).then(|| values.join(", "))
+        };
+
+        let forwarded_for = match (incoming_list(headers, &X_FORWARDED_FOR), client) {
+            (Some(previous), Some(client)) => Some(format!("{}, {}", previous, client)),
+            (Some(previous), None) => Some(previous),
+            (None, Some(client)) => Some(client.to_string()),
+            (None, None) => None,
+        };
+        let proto = incoming(headers, &X_FORWARDED_PROTO).unwrap_or_else(|| scheme.to_string());
+        let forwarded_host = incoming(headers, &X_FORWARDED_HOST).or(host);
+
+        Self::set(headers, X_FORWARDED_FOR, forwarded_for.as_deref());
+        Self::set(headers, X_FORWARDED_PROTO, Some(&proto));
+        Self::set(headers, X_FORWARDED_HOST, forwarded_host.as_deref());
+
+        if self.emit_forwarded {
+            let mut forwarded = Vec::new();
+
+            if let Some(client) = client {
+                forwarded.push(match client {
+                    IpAddr::V4(client) => format!("for={}", client),
+                    IpAddr::V6(client) => format!("for=\"[{}]\"", client),
+                });
+            }
+            if let Some(host) = &forwarded_host {
+                // A host with a port isn't a token, it has to be quoted.
+                forwarded.push(format!(
+                    "host=\"{}\"",
+                    host.replace('\\', "\\\\").replace('"', "\\\"")
+                ));
+            }
+            forwarded.push(format!("proto={}", proto));
+
+            let previous = incoming_list(headers, &header::FORWARDED);
+            let forwarded = forwarded.join(";");
+            let forwarded = match previous {
+                Some(previous) => format!("{}, {}", previous, forwarded),
+                None => forwarded,
+            };
+
+            Self::set(headers, header::FORWARDED, Some(&forwarded));
+        }
+    }
+
+    fn set(headers: &mut HeaderMap, name: HeaderName, value: Option<&str>) {
+        match value.and_then(|value| HeaderValue::from_str(value).ok()) {
+            Some(value) => {
+                headers.insert(name, value);
+            }
+            None => {
+                headers.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use http::{HeaderMap, HeaderValue, header};
+
+    use crate::forwarded_headers::{
+        ForwardedHeaders, Network, TrustedProxies, X_FORWARDED_FOR, X_FORWARDED_HOST,
+        X_FORWARDED_PROTO, forwarded_client,
+    };
+
+    fn client() -> Option<IpAddr> {
+        Some("203.0.113.7".parse().unwrap())
+    }
+
+    fn incoming_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("shop.example.com"));
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("198.51.100.1"));
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static("https"));
+        headers
+    }
+
+    #[test]
+    fn untrusted_values_are_overwritten() {
+        let mut headers = incoming_headers();
+
+        ForwardedHeaders::default().apply(&mut headers, client(), "http");
+
+        assert_eq!(headers[X_FORWARDED_FOR], "203.0.113.7");
+        assert_eq!(headers[X_FORWARDED_PROTO], "http");
+        assert_eq!(headers[X_FORWARDED_HOST], "shop.example.com");
+        assert!(headers.get(header::FORWARDED).is_none());
+    }
+
+    #[test]
+    fn trusted_values_are_appended_to() {
+        let mut headers = incoming_headers();
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("198.51.100.2"));
+
+        ForwardedHeaders {
+            trust_incoming: true,
+            trusted_proxies: trusted_proxies(&["203.0.113.0/24"]),
+            emit_forwarded: false,
+        }
+        .apply(&mut headers, client(), "http");
+
+        assert_eq!(
+            headers[X_FORWARDED_FOR],
+            "198.51.100.1, 198.51.100.2, 203.0.113.7"
+        );
+        assert_eq!(headers[X_FORWARDED_PROTO], "https");
+    }
+
+    #[test]
+    fn only_the_values_of_trusted_proxies_are_kept() {
+        for trusted_proxies in [TrustedProxies::default(), trusted_proxies(&["10.0.0.0/8"])] {
+            let mut headers = incoming_headers();
+
+            ForwardedHeaders {
+                trust_incoming: true,
+                trusted_proxies,
+                emit_forwarded: false,
+            }
+            .apply(&mut headers, client(), "http");
+
+            assert_eq!(headers[X_FORWARDED_FOR], "203.0.113.7");
+            assert_eq!(headers[X_FORWARDED_PROTO], "http");
+        }
+    }
+
+    #[test]
+    fn emits_the_standard_forwarded_header() {
+        let mut headers = incoming_headers();
+
+        ForwardedHeaders {
+            emit_forwarded: true,
+            ..ForwardedHeaders::default()
+        }
+        .apply(&mut headers, client(), "http");
+
+        assert_eq!(
+            headers[header::FORWARDED],
+            "for=203.0.113.7;host=\"shop.example.com\";proto=http"
+        );
+    }
+
+    #[test]
+    fn tells_the_scheme_of_the_listener() {
+        let mut headers = incoming_headers();
+
+        ForwardedHeaders {
+            emit_forwarded: true,
+            ..ForwardedHeaders::default()
+        }
+        .apply(&mut headers, client(), "https");
+
+        assert_eq!(headers[X_FORWARDED_PROTO], "https");
+        assert_eq!(
+            headers[header::FORWARDED],
+            "for=203.0.113.7;host=\"shop.example.com\";proto=https"
+        );
+    }
+
+    fn trusted_proxies(networks: &[&str]) -> TrustedProxies {
+        TrustedProxies(
+            networks
+                .iter()
+                .map(|network| network.parse().unwrap())
+                .collect(),
+        )
+    }
+
+    fn forwarded_for(value: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(X_FORWARDED_FOR, HeaderValue::from_static(value))])
+    }
+
+    #[test]
+    fn takes_the_first_forwarded_address_not_set_by_a_trusted_proxy() {
+        let headers = forwarded_for("198.51.100.1, 203.0.113.7, 10.0.0.2");
+        let client = |networks: &[&str]| forwarded_client(&headers, &trusted_proxies(networks));
+
+        assert_eq!(client(&[]), "10.0.0.2".parse().ok());
+        assert_eq!(client(&["10.0.0.0/8"]), "203.0.113.7".parse().ok());
+        assert_eq!(
+            client(&["10.0.0.0/8", "203.0.113.7"]),
+            "198.51.100.1".parse().ok()
+        );
+        assert_eq!(
+            forwarded_client(
+                &forwarded_for("spoofed, 203.0.113.7"),
+                &TrustedProxies::default()
+            ),
+            "203.0.113.7".parse().ok()
+        );
+        assert_eq!(
+            forwarded_client(
+                &forwarded_for("203.0.113.7, spoofed"),
+                &TrustedProxies::default()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_addresses_against_networks() {
+        let network: Network = "10.1.0.0/16".parse().unwrap();
+        let everything: Network = "::/0".parse().unwrap();
+
+        assert!(network.contains("10.1.200.3".parse().unwrap()));
+        assert!(!network.contains("10.2.0.1".parse().unwrap()));
+        assert!(!network.contains("::1".parse().unwrap()));
+        assert!(everything.contains("2001:db8::1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("proxy".parse::<Network>().is_err());
+    }
+}
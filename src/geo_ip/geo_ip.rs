@@ -0,0 +1,141 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::forwarded_headers::{TrustedProxies, forwarded_client};
+use crate::geo_ip::geo_locator::GeoLocator;
+
+pub const X_GEO_COUNTRY: HeaderName = HeaderName::from_static("x-geo-country");
+pub const X_GEO_CONTINENT: HeaderName = HeaderName::from_static("x-geo-continent");
+
+/// Tells the pool routes and the backends where the client is through the
+/// `X-Geo-Country` and `X-Geo-Continent` headers. Those sent by the client
+/// are dropped, even without a locator, so that nobody picks a pool by
+/// claiming a country.
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    pub locator: Option<Arc<dyn GeoLocator>>,
+    /// Locate the client named by `X-Forwarded-For`, set by a trusted proxy
+    /// in front of us, instead of the peer.
+    pub trust_forwarded_for: bool,
+    pub trusted_proxies: TrustedProxies,
+}
+
+impl GeoIp {
+    pub fn tag(&self, headers: &mut HeaderMap, client: Option<IpAddr>) {
+        headers.remove(X_GEO_COUNTRY);
+        headers.remove(X_GEO_CONTINENT);
+
+        let Some(locator) = &self.locator else {
+            return;
+        };
+
+        let forwarded_client = self
+            .trust_forwarded_for
+            .then(|| forwarded_client(headers, &self.trusted_proxies))
+            .flatten();
+        let Some(location) = forwarded_client
+            .or(client)
+            .and_then(|address| locator.locate(address))
+        else {
+            return;
+        };
+
+        for (name, code) in [
+            (X_GEO_COUNTRY, location.country),
+            (X_GEO_CONTINENT, location.continent),
+        ] {
+            if let Some(value) = code.and_then(|code| HeaderValue::from_str(&code).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::sync::Arc;
+
+    use http::{HeaderMap, HeaderValue};
+
+    use crate::forwarded_headers::TrustedProxies;
+    use crate::geo_ip::geo_ip::{GeoIp, X_GEO_CONTINENT, X_GEO_COUNTRY};
+    use crate::geo_ip::geo_locator::{GeoLocation, MockGeoLocator};
+
+    fn geo_ip(trust_forwarded_for: bool) -> GeoIp {
+        let mut locator = MockGeoLocator::new();
+        locator
+            .expect_locate()
+            .returning(|address| match address.to_string().as_str() {
+                "81.2.69.142" => Some(GeoLocation {
+                    country: Some("GB".to_string()),
+                    continent: Some("EU".to_string()),
+                }),
+                "216.160.83.56" => Some(GeoLocation {
+                    country: Some("US".to_string()),
+                    continent: Some("NA".to_string()),
+                }),
+                _ => None,
+            });
+
+        GeoIp {
+            locator: Some(Arc::new(locator)),
+            trust_forwarded_for,
+            trusted_proxies: TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]),
+        }
+    }
+
+    fn client(address: &str) -> Option<IpAddr> {
+        Some(address.parse().unwrap())
+    }
+
+    #[test]
+    fn tags_the_country_and_continent_of_the_client() {
+        let mut headers = HeaderMap::new();
+
+        geo_ip(false).tag(&mut headers, client("81.2.69.142"));
+
+        assert_eq!(headers[X_GEO_COUNTRY], "GB");
+        assert_eq!(headers[X_GEO_CONTINENT], "EU");
+    }
+
+    #[test]
+    fn drops_the_location_claimed_by_the_client() {
+        let claimed = || {
+            let mut headers = HeaderMap::new();
+            headers.insert(X_GEO_COUNTRY, HeaderValue::from_static("US"));
+            headers.insert(X_GEO_CONTINENT, HeaderValue::from_static("NA"));
+            headers
+        };
+
+        let mut unknown = claimed();
+        geo_ip(false).tag(&mut unknown, client("10.0.0.7"));
+        let mut without_locator = claimed();
+        GeoIp::default().tag(&mut without_locator, client("216.160.83.56"));
+
+        assert!(unknown.is_empty());
+        assert!(without_locator.is_empty());
+    }
+
+    #[test]
+    fn locates_the_forwarded_client_only_when_trusted() {
+        let forwarded = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-forwarded-for", HeaderValue::from_static(value));
+            headers
+        };
+
+        let mut trusted = forwarded("216.160.83.56, 10.0.0.2");
+        geo_ip(true).tag(&mut trusted, client("10.0.0.1"));
+        let mut untrusted = forwarded("216.160.83.56, 10.0.0.2");
+        geo_ip(false).tag(&mut untrusted, client("81.2.69.142"));
+        let mut spoofed = forwarded("216.160.83.56, 81.2.69.142");
+        geo_ip(true).tag(&mut spoofed, client("10.0.0.1"));
+
+        assert_eq!(trusted[X_GEO_COUNTRY], "US");
+        assert_eq!(untrusted[X_GEO_COUNTRY], "GB");
+        assert_eq!(spoofed[X_GEO_COUNTRY], "GB");
+    }
+}
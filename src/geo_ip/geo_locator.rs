@@ -0,0 +1,16 @@
+use std::net::IpAddr;
+
+/// Where an address is, as ISO 3166 country and two-letter continent codes,
+/// e.g. `DE` in `EU`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeoLocation {
+    pub country: Option<String>,
+    pub continent: Option<String>,
+}
+
+/// Looks the client addresses up in a geolocation database.
+#[cfg_attr(test, mockall::automock)]
+pub trait GeoLocator: Send + Sync {
+    /// `None` for addresses the database doesn't know, e.g. private ones.
+    fn locate(&self, address: IpAddr) -> Option<GeoLocation>;
+}
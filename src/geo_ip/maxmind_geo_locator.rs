@@ -0,0 +1,37 @@
+use std::net::IpAddr;
+use std::path::Path;
+
+use maxminddb::{MaxMindDBError, Reader, geoip2};
+
+use crate::geo_ip::geo_locator::{GeoLocation, GeoLocator};
+
+/// Locates the addresses in a MaxMind database, e.g. GeoLite2 Country or
+/// City, read in memory once at startup.
+pub struct MaxMindGeoLocator {
+    reader: Reader<Vec<u8>>,
+}
+
+impl MaxMindGeoLocator {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+}
+
+impl GeoLocator for MaxMindGeoLocator {
+    fn locate(&self, address: IpAddr) -> Option<GeoLocation> {
+        let country = self.reader.lookup::<geoip2::Country>(address).ok()?;
+
+        Some(GeoLocation {
+            country: country
+                .country
+                .and_then(|country| country.iso_code)
+                .map(str::to_string),
+            continent: country
+                .continent
+                .and_then(|continent| continent.code)
+                .map(str::to_string),
+        })
+    }
+}
@@ -0,0 +1,5 @@
+#[allow(clippy::module_inception)]
+pub mod geo_ip;
+pub mod geo_locator;
+#[cfg(feature = "geoip")]
+pub mod maxmind_geo_locator;
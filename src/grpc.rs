@@ -0,0 +1,185 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use http::{HeaderMap, Uri, header};
+
+/// The service and method of a gRPC call, e.g. `shop.v1.Orders` and
+/// `Create`, from its `/shop.v1.Orders/Create` path. Requests without a
+/// gRPC content type aren't calls, whatever their path.
+pub fn call<'a>(uri: &'a Uri, headers: &HeaderMap) -> Option<(&'a str, &'a str)> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|content_type| content_type.starts_with("application/grpc"))?;
+
+    let (service, method) = uri.path().strip_prefix('/')?.split_once('/')?;
+    if service.is_empty() || method.is_empty() || method.contains('/') {
+        return None;
+    }
+
+    Some((service, method))
+}
+
+/// The calls of a service, or of one of its methods only, written as
+/// `pkg.Service` or `pkg.Service/Method`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrpcMatcher {
+    pub service: String,
+    pub method: Option<String>,
+}
+
+impl GrpcMatcher {
+    pub fn matches(&self, uri: &Uri, headers: &HeaderMap) -> bool {
+        call(uri, headers).is_some_and(|(service, method)| {
+            service == self.service && self.method.as_deref().is_none_or(|name| name == method)
+        })
+    }
+}
+
+impl FromStr for GrpcMatcher {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (service, method) = match value.trim().split_once('/') {
+            Some((service, method)) => (service, Some(method)),
+            None => (value.trim(), None),
+        };
+
+        let valid = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || "._".contains(character))
+        };
+        if !valid(service) || !method.is_none_or(valid) {
+            return Err(format!("expected SERVICE[/METHOD], got {}", value));
+        }
+
+        Ok(GrpcMatcher {
+            service: service.to_string(),
+            method: method.map(str::to_string),
+        })
+    }
+}
+
+/// How long the calls of a service or method may take, written as
+/// `SERVICE[/METHOD]=MILLIS`, e.g. `shop.v1.Reports/Export=30000`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrpcTimeout {
+    pub matcher: GrpcMatcher,
+    pub timeout: Duration,
+}
+
+impl FromStr for GrpcTimeout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (matcher, timeout) = value
+            .split_once('=')
+            .ok_or_else(|| format!("expected SERVICE[/METHOD]=MILLIS, got {}", value))?;
+        let timeout = timeout
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid timeout in {}", value))?;
+
+        Ok(GrpcTimeout {
+            matcher: matcher.parse()?,
+            timeout: Duration::from_millis(timeout),
+        })
+    }
+}
+
+/// Timeouts of the gRPC calls, those of a method taking precedence over
+/// those of its whole service.
+#[derive(Debug, Clone, Default)]
+pub struct GrpcTimeouts(pub Vec<GrpcTimeout>);
+
+impl GrpcTimeouts {
+    pub fn timeout(&self, uri: &Uri, headers: &HeaderMap) -> Option<Duration> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        self.0
+            .iter()
+            .filter(|timeout| timeout.matcher.matches(uri, headers))
+            .max_by_key(|timeout| timeout.matcher.method.is_some())
+            .map(|timeout| timeout.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::{HeaderMap, HeaderValue, Uri, header};
+
+    use crate::grpc::{GrpcMatcher, GrpcTimeouts, call};
+
+    fn grpc_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/grpc+proto"),
+        );
+        headers
+    }
+
+    #[test]
+    fn parses_the_service_and_method_of_grpc_calls() {
+        let uri = Uri::from_static("/shop.v1.Orders/Create");
+
+        assert_eq!(
+            call(&uri, &grpc_headers()),
+            Some(("shop.v1.Orders", "Create"))
+        );
+        assert_eq!(call(&uri, &HeaderMap::new()), None);
+        assert_eq!(
+            call(&Uri::from_static("/shop.v1.Orders"), &grpc_headers()),
+            None
+        );
+        assert_eq!(
+            call(&Uri::from_static("/api/users/7"), &grpc_headers()),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_a_service_or_one_of_its_methods() {
+        let service: GrpcMatcher = "shop.v1.Orders".parse().unwrap();
+        let method: GrpcMatcher = "shop.v1.Orders/Create".parse().unwrap();
+        let matches = |matcher: &GrpcMatcher, path: &'static str| {
+            matcher.matches(&Uri::from_static(path), &grpc_headers())
+        };
+
+        assert!(matches(&service, "/shop.v1.Orders/Create"));
+        assert!(matches(&service, "/shop.v1.Orders/List"));
+        assert!(!matches(&service, "/shop.v1.Payments/Create"));
+        assert!(matches(&method, "/shop.v1.Orders/Create"));
+        assert!(!matches(&method, "/shop.v1.Orders/List"));
+
+        for matcher in ["", "shop.v1.Orders/", "/Create", "shop v1.Orders"] {
+            assert!(matcher.parse::<GrpcMatcher>().is_err(), "{}", matcher);
+        }
+    }
+
+    #[test]
+    fn method_timeouts_take_precedence_over_service_ones() {
+        let timeouts = GrpcTimeouts(vec![
+            "shop.v1.Reports=2000".parse().unwrap(),
+            "shop.v1.Reports/Export=30000".parse().unwrap(),
+        ]);
+        let timeout =
+            |path: &'static str| timeouts.timeout(&Uri::from_static(path), &grpc_headers());
+
+        assert_eq!(
+            timeout("/shop.v1.Reports/Export"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            timeout("/shop.v1.Reports/Daily"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(timeout("/shop.v1.Orders/Create"), None);
+    }
+}
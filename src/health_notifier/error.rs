@@ -0,0 +1,8 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to deliver notification: {0}")]
+    Delivery(String),
+
+    #[error("Failed to encode notification: {0}")]
+    Encoding(String),
+}
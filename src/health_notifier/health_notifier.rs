@@ -0,0 +1,18 @@
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::health_notifier::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HealthEvent {
+    ServerUp { server: String },
+    ServerDown { server: String },
+    NoHealthyServers,
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait HealthNotifier: Send + Sync {
+    async fn notify(&self, event: &HealthEvent) -> Result<(), Error>;
+}
@@ -0,0 +1,5 @@
+pub mod error;
+#[allow(clippy::module_inception)]
+pub mod health_notifier;
+pub mod queued_health_notifier;
+pub mod webhook_health_notifier;
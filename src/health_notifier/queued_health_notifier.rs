@@ -0,0 +1,107 @@
+use async_trait::async_trait;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::warn;
+
+use crate::health_notifier::{
+    error::Error,
+    health_notifier::{HealthEvent, HealthNotifier},
+};
+
+/// Hands the events over to a task delivering them one after the other, so
+/// that a slow or unreachable receiver never holds the health checks. Events
+/// overflowing the queue are dropped, deliveries are cut after `timeout`.
+pub struct QueuedHealthNotifier {
+    sender: mpsc::Sender<HealthEvent>,
+}
+
+impl QueuedHealthNotifier {
+    /// Spawns the delivering task, which has to run within a Tokio runtime.
+    pub fn new(notifier: Arc<dyn HealthNotifier>, capacity: usize, timeout: Duration) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<HealthEvent>(capacity);
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                match tokio::time::timeout(timeout, notifier.notify(&event)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(error)) => warn!("Failed to notify {:?}: {}", event, error),
+                    Err(_) => warn!("Notifying {:?} timed out after {:?}", event, timeout),
+                }
+            }
+        });
+
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl HealthNotifier for QueuedHealthNotifier {
+    async fn notify(&self, event: &HealthEvent) -> Result<(), Error> {
+        self.sender
+            .try_send(event.clone())
+            .map_err(|error| match error {
+                TrySendError::Full(_) => Error::Delivery("queue full, event dropped".to_string()),
+                TrySendError::Closed(_) => Error::Delivery("notifier stopped".to_string()),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::Notify;
+
+    use crate::health_notifier::error::Error;
+
+    use crate::health_notifier::health_notifier::{
+        HealthEvent, HealthNotifier, MockHealthNotifier,
+    };
+    use crate::health_notifier::queued_health_notifier::QueuedHealthNotifier;
+
+    #[tokio::test]
+    async fn delivers_the_events_in_the_background() {
+        let delivered = Arc::new(Notify::new());
+        let notified = Arc::clone(&delivered);
+
+        let mut notifier = MockHealthNotifier::new();
+        notifier
+            .expect_notify()
+            .withf(|event| *event == HealthEvent::NoHealthyServers)
+            .times(1)
+            .returning(move |_| {
+                notified.notify_one();
+                Ok(())
+            });
+
+        let queued = QueuedHealthNotifier::new(Arc::new(notifier), 8, Duration::from_secs(1));
+
+        queued.notify(&HealthEvent::NoHealthyServers).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), delivered.notified())
+            .await
+            .expect("event never delivered");
+    }
+
+    #[tokio::test]
+    async fn drops_the_events_overflowing_the_queue() {
+        let queued = QueuedHealthNotifier::new(Arc::new(StuckNotifier), 1, Duration::from_secs(60));
+
+        // The first one is being delivered, the second one waits in the queue.
+        queued.notify(&HealthEvent::NoHealthyServers).await.unwrap();
+        tokio::task::yield_now().await;
+        queued.notify(&HealthEvent::NoHealthyServers).await.unwrap();
+
+        assert!(queued.notify(&HealthEvent::NoHealthyServers).await.is_err());
+    }
+
+    struct StuckNotifier;
+
+    #[async_trait::async_trait]
+    impl HealthNotifier for StuckNotifier {
+        async fn notify(&self, _event: &HealthEvent) -> Result<(), Error> {
+            std::future::pending().await
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderValue, header};
+use std::sync::Arc;
+
+use crate::{
+    health_notifier::{
+        error::Error,
+        health_notifier::{HealthEvent, HealthNotifier},
+    },
+    http_client::{
+        http_client::HttpClient,
+        request::{Request, RequestHeaders, RequestMethod},
+    },
+};
+
+/// POSTs every event as a JSON payload to the configured URL.
+pub struct WebhookHealthNotifier {
+    http_client: Arc<dyn HttpClient>,
+    url: String,
+}
+
+impl WebhookHealthNotifier {
+    pub fn new(http_client: Arc<dyn HttpClient>, url: String) -> Self {
+        Self { http_client, url }
+    }
+}
+
+#[async_trait]
+impl HealthNotifier for WebhookHealthNotifier {
+    async fn notify(&self, event: &HealthEvent) -> Result<(), Error> {
+        let body = serde_json::to_vec(event).map_err(|error| Error::Encoding(error.to_string()))?;
+
+        let request = Request {
+            method: RequestMethod::Post,
+            url: self.url.clone(),
+            headers: RequestHeaders::from([(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            )]),
+            body: Bytes::from(body).into(),
+            timeout: None,
+        };
+
+        let response = self
+            .http_client
+            .execute(request)
+            .await
+            .map_err(|error| Error::Delivery(error.to_string()))?;
+
+        if !(200..300).contains(&response.status) {
+            return Err(Error::Delivery(format!(
+                "webhook returned status {}",
+                response.status
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use crate::health_notifier::health_notifier::{HealthEvent, HealthNotifier};
+    use crate::health_notifier::webhook_health_notifier::WebhookHealthNotifier;
+    use crate::http_client::http_client::MockHttpClient;
+    use crate::http_client::request::{RequestHeaders, RequestMethod};
+    use crate::http_client::response::Response;
+
+    #[tokio::test]
+    async fn posts_the_event_as_json() {
+        let mut http_client = MockHttpClient::new();
+        http_client
+            .expect_execute()
+            .withf(|req| {
+                req.method == RequestMethod::Post
+                    && req.url == "http://hooks.local/wakanda"
+                    && req
+                        .headers
+                        .get("content-type")
+                        .and_then(|value| value.to_str().ok())
+                        == Some("application/json")
+                    && req.body == r#"{"event":"server_down","server":"http://server1"}"#
+            })
+            .times(1)
+            .returning(|_| {
+                Ok(Response {
+                    status: 204,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::new().into(),
+                })
+            });
+
+        let notifier = WebhookHealthNotifier::new(
+            Arc::new(http_client),
+            "http://hooks.local/wakanda".to_string(),
+        );
+
+        notifier
+            .notify(&HealthEvent::ServerDown {
+                server: "http://server1".to_string(),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn unsuccessful_status_is_an_error() {
+        let mut http_client = MockHttpClient::new();
+        http_client.expect_execute().returning(|_| {
+            Ok(Response {
+                status: 500,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        });
+
+        let notifier = WebhookHealthNotifier::new(
+            Arc::new(http_client),
+            "http://hooks.local/wakanda".to_string(),
+        );
+
+        assert!(
+            notifier
+                .notify(&HealthEvent::NoHealthyServers)
+                .await
+                .is_err()
+        );
+    }
+}
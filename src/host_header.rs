@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use http::{HeaderValue, Uri, header};
+
+use crate::http_client::request::RequestHeaders;
+
+/// The `Host` the backends are sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HostHeader {
+    /// The host the client asked for, e.g. for backends serving several
+    /// virtual hosts by name.
+    #[default]
+    Preserve,
+    /// The authority of the backend, e.g. `10.0.0.7:8080`, for backends
+    /// answering to their own name only.
+    Upstream,
+}
+
+impl HostHeader {
+    pub fn apply(&self, headers: &mut RequestHeaders, server: &str) {
+        if *self == HostHeader::Preserve {
+            return;
+        }
+
+        match server
+            .parse::<Uri>()
+            .ok()
+            .and_then(|server| HeaderValue::from_str(server.authority()?.as_str()).ok())
+        {
+            Some(authority) => headers.insert(header::HOST, authority),
+            // Left for the HTTP client to derive from the URL.
+            None => headers.remove(header::HOST.as_str()),
+        }
+    }
+}
+
+/// Written as `preserve` or `upstream`, as with `--host-header`.
+impl FromStr for HostHeader {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "preserve" => Ok(HostHeader::Preserve),
+            "upstream" => Ok(HostHeader::Upstream),
+            _ => Err(format!(
+                "expected a host header of preserve or upstream, got {}",
+                value
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderValue, header};
+
+    use crate::host_header::HostHeader;
+    use crate::http_client::request::RequestHeaders;
+
+    fn client_headers() -> RequestHeaders {
+        RequestHeaders::from([(header::HOST, HeaderValue::from_static("shop.example.com"))])
+    }
+
+    #[test]
+    fn preserves_the_host_of_the_client() {
+        let mut headers = client_headers();
+
+        HostHeader::Preserve.apply(&mut headers, "http://10.0.0.7:8080");
+
+        assert_eq!(headers["host"], "shop.example.com");
+    }
+
+    #[test]
+    fn rewrites_the_host_to_the_backend() {
+        let mut headers = client_headers();
+
+        HostHeader::Upstream.apply(&mut headers, "http://10.0.0.7:8080");
+
+        assert_eq!(headers["host"], "10.0.0.7:8080");
+        assert_eq!(headers.len(), 1);
+    }
+}
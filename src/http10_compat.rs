@@ -0,0 +1,96 @@
+use axum::body::{Body, HttpBody, to_bytes};
+use axum::response::{IntoResponse, Response};
+use http::{HeaderMap, HeaderValue, StatusCode, Uri, header};
+use tracing::error;
+
+/// HTTP/1.0 requests may carry the host in an absolute URI only.
+pub fn synthesize_host(headers: &mut HeaderMap, uri: &Uri) {
+    if headers.contains_key(header::HOST) {
+        return;
+    }
+
+    if let Some(value) = uri
+        .authority()
+        .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+    {
+        headers.insert(header::HOST, value);
+    }
+}
+
+/// HTTP/1.0 clients don't understand chunked responses: bodies of unknown
+/// size are buffered so that they can be sent with a `Content-Length`.
+pub async fn buffer_response(response: Response) -> Response {
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::TRANSFER_ENCODING);
+
+    if body.size_hint().exact().is_some() {
+        return Response::from_parts(parts, body);
+    }
+
+    match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => {
+            parts
+                .headers
+                .insert(header::CONTENT_LENGTH, HeaderValue::from(bytes.len()));
+
+            Response::from_parts(parts, Body::from(bytes))
+        }
+        Err(error) => {
+            error!("Failed to buffer the response: {}", error);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, HttpBody};
+    use axum::response::Response;
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue, Uri, header};
+
+    use crate::http10_compat::{buffer_response, synthesize_host};
+
+    #[test]
+    fn host_is_taken_from_the_absolute_uri() {
+        let mut headers = HeaderMap::new();
+
+        synthesize_host(
+            &mut headers,
+            &"http://legacy.example.com/status".parse::<Uri>().unwrap(),
+        );
+
+        assert_eq!(headers[header::HOST], "legacy.example.com");
+    }
+
+    #[test]
+    fn host_is_kept_when_present() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("shop.example.com"));
+
+        synthesize_host(
+            &mut headers,
+            &"http://legacy.example.com/status".parse::<Uri>().unwrap(),
+        );
+
+        assert_eq!(headers[header::HOST], "shop.example.com");
+    }
+
+    #[tokio::test]
+    async fn streamed_responses_get_a_content_length() {
+        let mut response = Response::new(Body::from_stream(futures::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from("hello ")),
+            Ok(Bytes::from("world")),
+        ])));
+        response.headers_mut().insert(
+            header::TRANSFER_ENCODING,
+            HeaderValue::from_static("chunked"),
+        );
+
+        let response = buffer_response(response).await;
+
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "11");
+        assert!(response.headers().get(header::TRANSFER_ENCODING).is_none());
+        assert_eq!(response.body().size_hint().exact(), Some(11));
+    }
+}
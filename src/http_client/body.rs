@@ -0,0 +1,201 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+use axum::body::{Body as AxumBody, HttpBody, to_bytes};
+use bytes::Bytes;
+use hyper::body::{Frame, SizeHint};
+
+use crate::http_client::error::Error;
+
+/// Body of a request or response going through the HTTP client: either
+/// already in memory, or streamed as it arrives so that large uploads and
+/// downloads are never buffered by the load balancer.
+pub enum Body {
+    Full(Bytes),
+    Streaming(AxumBody),
+}
+
+impl Body {
+    pub fn empty() -> Self {
+        Body::Full(Bytes::new())
+    }
+
+    /// Exact size when known upfront, e.g. from a `Content-Length`.
+    pub fn exact_size(&self) -> Option<u64> {
+        match self {
+            Body::Full(bytes) => Some(bytes.len() as u64),
+            Body::Streaming(body) => body.size_hint().exact(),
+        }
+    }
+
+    /// Only in-memory bodies can be sent more than once.
+    pub fn try_clone(&self) -> Option<Self> {
+        match self {
+            Body::Full(bytes) => Some(Body::Full(bytes.clone())),
+            Body::Streaming(_) => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exact_size() == Some(0)
+    }
+
+    /// Buffers the whole body in memory.
+    pub async fn collect(self) -> Result<Bytes, Error> {
+        match self {
+            Body::Full(bytes) => Ok(bytes),
+            Body::Streaming(body) => to_bytes(body, usize::MAX)
+                .await
+                .map_err(|error| Error::Network(error.to_string())),
+        }
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::empty()
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Full(bytes) => f.debug_tuple("Full").field(bytes).finish(),
+            Body::Streaming(_) => f.write_str("Streaming"),
+        }
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Body::Full(bytes)
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(value: &'static str) -> Self {
+        Body::Full(Bytes::from(value))
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Full(Bytes::from(value))
+    }
+}
+
+impl From<AxumBody> for Body {
+    fn from(body: AxumBody) -> Self {
+        Body::Streaming(body)
+    }
+}
+
+impl From<Body> for AxumBody {
+    fn from(body: Body) -> Self {
+        match body {
+            Body::Full(bytes) => AxumBody::from(bytes),
+            Body::Streaming(body) => body,
+        }
+    }
+}
+
+impl From<Body> for reqwest::Body {
+    fn from(body: Body) -> Self {
+        match body {
+            Body::Full(bytes) => reqwest::Body::from(bytes),
+            Body::Streaming(body) => reqwest::Body::wrap(SyncBody(Mutex::new(body))),
+        }
+    }
+}
+
+/// Hands a streamed body over to `reqwest` frame by frame, so that its
+/// trailers go along, e.g. the `grpc-status` of a gRPC call. `reqwest` wants
+/// bodies that can be shared between threads, which the mutex gives: it is
+/// only ever reached through the exclusive reference of a poll.
+struct SyncBody(Mutex<AxumBody>);
+
+impl HttpBody for SyncBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.get_mut().0.get_mut() {
+            Ok(body) => Pin::new(body).poll_frame(cx),
+            Err(_) => Poll::Ready(None),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.lock().is_ok_and(|body| body.is_end_stream())
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.0
+            .lock()
+            .map(|body| body.size_hint())
+            .unwrap_or_default()
+    }
+}
+
+/// Only in-memory bodies can be compared without consuming them.
+impl PartialEq<Bytes> for Body {
+    fn eq(&self, other: &Bytes) -> bool {
+        matches!(self, Body::Full(bytes) if bytes == other)
+    }
+}
+
+impl PartialEq<&str> for Body {
+    fn eq(&self, other: &&str) -> bool {
+        matches!(self, Body::Full(bytes) if bytes == other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body as AxumBody;
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue};
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::body::Frame;
+
+    use crate::http_client::body::Body;
+
+    #[tokio::test]
+    async fn collects_streaming_bodies() {
+        let body = Body::from(AxumBody::from_stream(futures::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from("hello ")),
+            Ok(Bytes::from("world")),
+        ])));
+
+        assert_eq!(body.exact_size(), None);
+        assert_eq!(body.collect().await.unwrap(), Bytes::from("hello world"));
+    }
+
+    #[test]
+    fn only_full_bodies_compare_equal() {
+        assert_eq!(Body::from("hello"), Bytes::from("hello"));
+        assert_ne!(Body::from(AxumBody::from("hello")), Bytes::from("hello"));
+        assert!(Body::from(AxumBody::empty()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn streams_the_trailers_to_reqwest() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+
+        let body = Body::from(AxumBody::new(StreamBody::new(futures::stream::iter([
+            Ok::<_, std::io::Error>(Frame::data(Bytes::from("hello"))),
+            Ok(Frame::trailers(trailers.clone())),
+        ]))));
+
+        let collected = reqwest::Body::from(body).collect().await.unwrap();
+
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert_eq!(collected.to_bytes(), Bytes::from("hello"));
+    }
+}
@@ -0,0 +1,335 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::http_client::{
+    error::Error as HttpClientError, http_client::HttpClient, request::Request, response::Response,
+};
+use crate::tls::error::Error;
+
+/// PEM files of the certificate and key presented to backends requiring
+/// mutual TLS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientIdentityFiles {
+    pub certificate: PathBuf,
+    pub key: PathBuf,
+}
+
+type MakeClient =
+    dyn Fn(Option<reqwest::Identity>) -> Result<Arc<dyn HttpClient>, HttpClientError> + Send + Sync;
+
+type Pem = (Vec<u8>, Vec<u8>);
+
+/// Authenticates the load balancer to the backends with a client
+/// certificate, through a client built by `make_client` with it. The
+/// identity is reloaded from its PEM files whenever they change, so that
+/// renewals don't need a restart; when it doesn't parse, or the client
+/// can't be built with it, the previous client keeps being used.
+pub struct ClientIdentityHttpClient {
+    files: ClientIdentityFiles,
+    make_client: Box<MakeClient>,
+    poll_interval: Duration,
+    current: RwLock<Arc<dyn HttpClient>>,
+    last_seen: Mutex<Pem>,
+}
+
+impl ClientIdentityHttpClient {
+    pub fn new(
+        files: ClientIdentityFiles,
+        poll_interval: Duration,
+        make_client: impl Fn(Option<reqwest::Identity>) -> Result<Arc<dyn HttpClient>, HttpClientError>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Result<Self, Error> {
+        let pem = read_pair_blocking(&files)?;
+        let client = build(&make_client, &pem)?;
+
+        Ok(Self {
+            files,
+            make_client: Box::new(make_client),
+            poll_interval,
+            current: RwLock::new(client),
+            last_seen: Mutex::new(pem),
+        })
+    }
+
+    /// Rebuilds the client if the identity changed since the last look,
+    /// returning whether a new one is being used.
+    pub async fn reload(&self) -> Result<bool, Error> {
+        let pem = read_pair(&self.files).await?;
+
+        {
+            let Ok(mut last_seen) = self.last_seen.lock() else {
+                return Ok(false);
+            };
+
+            if *last_seen == pem {
+                return Ok(false);
+            }
+
+            // Remembered even when invalid, so that a broken identity is
+            // only reported once instead of on every poll.
+            *last_seen = pem.clone();
+        }
+
+        let client = build(&self.make_client, &pem)?;
+
+        if let Ok(mut current) = self.current.write() {
+            *current = client;
+        }
+
+        Ok(true)
+    }
+
+    /// Polls the identity files for as long as the client is in use, the
+    /// clients of a pool being replaced on every reload.
+    pub async fn watch(http_client: Weak<Self>) {
+        let Some(poll_interval) = http_client.upgrade().map(|client| client.poll_interval) else {
+            return;
+        };
+        let mut interval = time::interval(poll_interval);
+
+        loop {
+            interval.tick().await;
+
+            let Some(http_client) = http_client.upgrade() else {
+                return;
+            };
+
+            match http_client.reload().await {
+                Ok(true) => info!(
+                    "Reloaded the upstream client certificate {}",
+                    http_client.files.certificate.display()
+                ),
+                Ok(false) => {}
+                Err(error) => warn!(
+                    "Keeping the current upstream client certificate {}: {}",
+                    http_client.files.certificate.display(),
+                    error
+                ),
+            }
+        }
+    }
+
+    fn client(&self) -> Option<Arc<dyn HttpClient>> {
+        self.current.read().ok().map(|client| Arc::clone(&client))
+    }
+}
+
+#[async_trait]
+impl HttpClient for ClientIdentityHttpClient {
+    async fn execute(&self, request: Request) -> Result<Response, HttpClientError> {
+        let client = self.client().ok_or_else(|| {
+            HttpClientError::InvalidRequest("No upstream client available".to_string())
+        })?;
+
+        client.execute(request).await
+    }
+}
+
+fn build(make_client: &MakeClient, (certificate, key): &Pem) -> Result<Arc<dyn HttpClient>, Error> {
+    let identity = reqwest::Identity::from_pem(&[certificate.as_slice(), key.as_slice()].concat())
+        .map_err(|error| Error::InvalidKey(error.to_string()))?;
+
+    make_client(Some(identity)).map_err(|error| Error::InvalidConfiguration(error.to_string()))
+}
+
+fn read_blocking(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|error| Error::Read(path.display().to_string(), error.to_string()))
+}
+
+fn read_pair_blocking(files: &ClientIdentityFiles) -> Result<Pem, Error> {
+    Ok((
+        read_blocking(&files.certificate)?,
+        read_blocking(&files.key)?,
+    ))
+}
+
+async fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|error| Error::Read(path.display().to_string(), error.to_string()))
+}
+
+async fn read_pair(files: &ClientIdentityFiles) -> Result<Pem, Error> {
+    Ok((read(&files.certificate).await?, read(&files.key).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use uuid::Uuid;
+
+    use crate::http_client::client_identity_http_client::{
+        ClientIdentityFiles, ClientIdentityHttpClient,
+    };
+    use crate::http_client::error::Error;
+    use crate::http_client::http_client::HttpClient;
+    use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use crate::http_client::response::Response;
+
+    struct Files(ClientIdentityFiles);
+
+    impl Files {
+        fn new() -> Self {
+            let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+
+            Self(ClientIdentityFiles {
+                certificate: prefix.with_extension("crt"),
+                key: prefix.with_extension("key"),
+            })
+        }
+
+        fn write_pair(&self) {
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let certificate = rcgen::CertificateParams::new(vec!["wakanda-lb".to_string()])
+                .unwrap()
+                .self_signed(&key_pair)
+                .unwrap();
+
+            std::fs::write(&self.0.certificate, certificate.pem()).unwrap();
+            std::fs::write(&self.0.key, key_pair.serialize_pem()).unwrap();
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0.certificate);
+            let _ = std::fs::remove_file(&self.0.key);
+        }
+    }
+
+    /// Answers 200 when built with an identity and 401 otherwise.
+    struct IdentityStub {
+        with_identity: bool,
+    }
+
+    #[async_trait]
+    impl HttpClient for IdentityStub {
+        async fn execute(&self, _request: Request) -> Result<Response, Error> {
+            Ok(Response {
+                status: if self.with_identity { 200 } else { 401 },
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        }
+    }
+
+    fn http_client(
+        files: ClientIdentityFiles,
+        builds: &Arc<AtomicUsize>,
+    ) -> Result<ClientIdentityHttpClient, crate::tls::error::Error> {
+        let builds = Arc::clone(builds);
+
+        ClientIdentityHttpClient::new(files, Duration::from_millis(10), move |identity| {
+            builds.fetch_add(1, Ordering::Relaxed);
+
+            Ok(Arc::new(IdentityStub {
+                with_identity: identity.is_some(),
+            }) as Arc<dyn HttpClient>)
+        })
+    }
+
+    async fn status(http_client: &ClientIdentityHttpClient) -> u16 {
+        let request = Request {
+            url: "https://10.0.0.1:8443/users".to_string(),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        http_client.execute(request).await.unwrap().status
+    }
+
+    #[tokio::test]
+    async fn presents_the_identity() {
+        let files = Files::new();
+        files.write_pair();
+
+        let http_client = http_client(files.0.clone(), &Arc::new(AtomicUsize::new(0))).unwrap();
+
+        assert_eq!(status(&http_client).await, 200);
+    }
+
+    #[test]
+    fn rejects_an_invalid_identity() {
+        let files = Files::new();
+        std::fs::write(&files.0.certificate, "not a certificate").unwrap();
+        std::fs::write(&files.0.key, "not a key").unwrap();
+
+        let result = http_client(files.0.clone(), &Arc::new(AtomicUsize::new(0)));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rejects_a_missing_identity() {
+        let result = http_client(
+            ClientIdentityFiles {
+                certificate: PathBuf::from("/nonexistent/client.crt"),
+                key: PathBuf::from("/nonexistent/client.key"),
+            },
+            &Arc::new(AtomicUsize::new(0)),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn rebuilds_the_client_when_the_identity_is_renewed() {
+        let files = Files::new();
+        files.write_pair();
+        let builds = Arc::new(AtomicUsize::new(0));
+        let http_client = http_client(files.0.clone(), &builds).unwrap();
+
+        assert!(!http_client.reload().await.unwrap());
+
+        files.write_pair();
+
+        assert!(http_client.reload().await.unwrap());
+        assert_eq!(builds.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_current_client_when_the_new_identity_is_invalid() {
+        let files = Files::new();
+        files.write_pair();
+        let http_client = http_client(files.0.clone(), &Arc::new(AtomicUsize::new(0))).unwrap();
+
+        std::fs::write(&files.0.certificate, "not a certificate").unwrap();
+
+        assert!(http_client.reload().await.is_err());
+        assert!(!http_client.reload().await.unwrap());
+        assert_eq!(status(&http_client).await, 200);
+    }
+
+    #[tokio::test]
+    async fn stops_watching_once_the_client_is_dropped() {
+        let files = Files::new();
+        files.write_pair();
+        let http_client =
+            Arc::new(http_client(files.0.clone(), &Arc::new(AtomicUsize::new(0))).unwrap());
+        let watcher = tokio::spawn(ClientIdentityHttpClient::watch(Arc::downgrade(
+            &http_client,
+        )));
+
+        drop(http_client);
+
+        tokio::time::timeout(Duration::from_secs(1), watcher)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
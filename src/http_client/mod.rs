@@ -1,5 +1,15 @@
+pub mod body;
+pub mod client_identity_http_client;
 pub mod error;
+#[allow(clippy::module_inception)]
 pub mod http_client;
 pub mod request;
 pub mod reqwest_http_client;
 pub mod response;
+pub mod sni_override_http_client;
+pub mod timeout_override_http_client;
+pub mod upstream_pool;
+pub mod upstream_protocol;
+pub mod upstream_proxy;
+pub mod upstream_timeouts;
+pub mod upstream_tls;
@@ -1,40 +1,86 @@
-use std::{
-    collections::HashMap,
-    fmt::{self, Display},
-    ops::{Deref, DerefMut},
-};
+use std::fmt::{self, Display};
+use std::ops::Index;
+use std::time::Duration;
 
-use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue};
 
-#[derive(Debug, Clone)]
+use crate::http_client::body::Body;
+
+#[derive(Debug)]
 pub struct Request {
     pub method: RequestMethod,
     pub url: String,
     pub headers: RequestHeaders,
-    pub body: Bytes,
+    pub body: Body,
+    /// Overrides the time the response head may take, e.g. for the route.
+    pub timeout: Option<Duration>,
 }
 
+/// Headers kept in the `HeaderMap` they arrived in, so that handing them
+/// over from hyper to reqwest and back converts nothing, and as raw bytes so
+/// that values which aren't UTF-8 go through untouched. A name may be
+/// repeated, e.g. `Set-Cookie`, and is matched case-insensitively.
 #[derive(Debug, Clone, Default, PartialEq)]
-pub struct RequestHeaders(pub HashMap<String, String>);
+pub struct RequestHeaders(pub HeaderMap);
+
+impl RequestHeaders {
+    /// First value of the header.
+    pub fn get(&self, name: &str) -> Option<&HeaderValue> {
+        self.0.get(name)
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a HeaderValue> + 'a {
+        self.0.get_all(name).into_iter()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Replaces every value of the header.
+    pub fn insert(&mut self, name: HeaderName, value: HeaderValue) {
+        self.0.insert(name, value);
+    }
+
+    /// Adds a value, keeping the ones already there.
+    pub fn append(&mut self, name: HeaderName, value: HeaderValue) {
+        self.0.append(name, value);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&HeaderName, &HeaderValue)> {
+        self.0.iter()
+    }
 
-impl Deref for RequestHeaders {
-    type Target = HashMap<String, String>;
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
-impl DerefMut for RequestHeaders {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// First value of the header, panicking when missing like `HeaderMap` does.
+impl Index<&str> for RequestHeaders {
+    type Output = HeaderValue;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no header named {}", name))
     }
 }
 
-impl<const N: usize> From<[(String, String); N]> for RequestHeaders {
-    fn from(arr: [(String, String); N]) -> Self {
-        let map = arr.into_iter().collect();
-        RequestHeaders(map)
+impl<const N: usize> From<[(HeaderName, HeaderValue); N]> for RequestHeaders {
+    fn from(arr: [(HeaderName, HeaderValue); N]) -> Self {
+        let mut headers = HeaderMap::with_capacity(N);
+        for (name, value) in arr {
+            headers.append(name, value);
+        }
+        RequestHeaders(headers)
     }
 }
 
@@ -45,6 +91,12 @@ pub enum RequestMethod {
     Put,
     Delete,
     Patch,
+    Head,
+    Options,
+    Trace,
+    Connect,
+    /// Any other method, passed through as is.
+    Extension(http::Method),
 }
 
 impl Display for RequestMethod {
@@ -55,20 +107,47 @@ impl Display for RequestMethod {
             RequestMethod::Put => "PUT",
             RequestMethod::Delete => "DELETE",
             RequestMethod::Patch => "PATCH",
+            RequestMethod::Head => "HEAD",
+            RequestMethod::Options => "OPTIONS",
+            RequestMethod::Trace => "TRACE",
+            RequestMethod::Connect => "CONNECT",
+            RequestMethod::Extension(method) => method.as_str(),
         };
         write!(f, "{}", s)
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum RequestError {
-    #[error("HTTP method {0} is not supported")]
-    UnsupportedMethod(String),
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::http_client::request::RequestMethod;
+    use http::{HeaderValue, header};
+
+    use crate::http_client::request::{RequestHeaders, RequestMethod};
+
+    #[test]
+    fn keeps_repeated_headers() {
+        let mut headers = RequestHeaders::from([
+            (header::SET_COOKIE, HeaderValue::from_static("a=1")),
+            (header::CONTENT_TYPE, HeaderValue::from_static("text/plain")),
+        ]);
+        headers.append(header::SET_COOKIE, HeaderValue::from_static("b=2"));
+
+        assert_eq!(
+            headers.get("Set-Cookie"),
+            Some(&HeaderValue::from_static("a=1"))
+        );
+        assert_eq!(
+            headers.get_all("SET-COOKIE").collect::<Vec<_>>(),
+            vec!["a=1", "b=2"]
+        );
+
+        headers.insert(header::SET_COOKIE, HeaderValue::from_static("c=3"));
+
+        assert_eq!(
+            headers.get_all("set-cookie").collect::<Vec<_>>(),
+            vec!["c=3"]
+        );
+        assert_eq!(headers.len(), 2);
+    }
 
     #[test]
     fn http_client_request_http_method_to_string() {
@@ -78,9 +157,16 @@ mod tests {
             RequestMethod::Put,
             RequestMethod::Delete,
             RequestMethod::Patch,
+            RequestMethod::Head,
+            RequestMethod::Options,
+            RequestMethod::Trace,
+            RequestMethod::Connect,
+            RequestMethod::Extension(http::Method::from_bytes(b"PURGE").unwrap()),
         ];
 
-        let expected = ["GET", "POST", "PUT", "DELETE", "PATCH"];
+        let expected = [
+            "GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "TRACE", "CONNECT", "PURGE",
+        ];
 
         for (method, &expected_str) in methods.iter().zip(expected.iter()) {
             assert_eq!(method.to_string(), expected_str);
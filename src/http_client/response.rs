@@ -1,10 +1,8 @@
-use bytes::Bytes;
+use crate::http_client::{body::Body, request::RequestHeaders};
 
-use crate::http_client::request::RequestHeaders;
-
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Response {
     pub status: u16,
     pub headers: RequestHeaders,
-    pub body: Bytes,
+    pub body: Body,
 }
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+use hickory_resolver::TokioResolver;
+use reqwest::Url;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::http_client::{
+    error::Error, http_client::HttpClient, request::Request,
+    reqwest_http_client::ReqwestHttpClient, response::Response,
+};
+
+type MakeClientBuilder = dyn Fn() -> reqwest::ClientBuilder + Send + Sync;
+
+/// Sends requests to any server using `server_name` for TLS SNI and the
+/// `Host` header, while still connecting to the server's own addresses.
+/// One client, built from what `make_client_builder` returns, is kept per
+/// server host as the name is pinned to its addresses, so that the servers
+/// only known later on, e.g. once the pools are reloaded, get it too.
+pub struct SniOverrideHttpClient {
+    server_name: String,
+    make_client_builder: Box<MakeClientBuilder>,
+    resolver: TokioResolver,
+    clients: RwLock<HashMap<String, Arc<ReqwestHttpClient>>>,
+}
+
+impl SniOverrideHttpClient {
+    pub fn new(
+        make_client_builder: impl Fn() -> reqwest::ClientBuilder + Send + Sync + 'static,
+        server_name: String,
+    ) -> Result<Self, Error> {
+        let resolver = TokioResolver::builder_tokio()
+            .map_err(|error| {
+                Error::InvalidRequest(format!("can't read the DNS configuration: {}", error))
+            })?
+            .build();
+
+        Ok(Self {
+            server_name,
+            make_client_builder: Box::new(make_client_builder),
+            resolver,
+            clients: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn client_for(&self, url: &Url) -> Result<Arc<ReqwestHttpClient>, Error> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| Error::InvalidRequest(format!("{} has no host", url)))?;
+
+        if let Some(client) = self
+            .clients
+            .read()
+            .ok()
+            .and_then(|clients| clients.get(host).cloned())
+        {
+            return Ok(client);
+        }
+
+        let addresses = ServerAddresses {
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            resolver: self.resolver.clone(),
+        };
+        let client = (self.make_client_builder)()
+            .dns_resolver(Arc::new(addresses))
+            .build()
+            .map_err(|error| Error::InvalidRequest(error.to_string()))?;
+        let client = Arc::new(ReqwestHttpClient::new(client));
+
+        match self.clients.write() {
+            Ok(mut clients) => Ok(Arc::clone(
+                clients.entry(host.to_string()).or_insert(client),
+            )),
+            Err(_) => Ok(client),
+        }
+    }
+
+    fn rewrite_url(&self, url: &str) -> Result<(String, Arc<ReqwestHttpClient>), Error> {
+        let mut url = Url::parse(url).map_err(|error| Error::InvalidRequest(error.to_string()))?;
+        let client = self.client_for(&url)?;
+
+        url.set_host(Some(&self.server_name))
+            .map_err(|error| Error::InvalidRequest(error.to_string()))?;
+
+        Ok((url.to_string(), client))
+    }
+}
+
+#[async_trait]
+impl HttpClient for SniOverrideHttpClient {
+    async fn execute(&self, mut request: Request) -> Result<Response, Error> {
+        let (url, client) = self.rewrite_url(&request.url)?;
+
+        request.url = url;
+        client.execute(request).await
+    }
+}
+
+/// Resolves the server name into the addresses of the server's host on
+/// every new connection, the answers being cached until their TTL expires.
+struct ServerAddresses {
+    host: String,
+    resolver: TokioResolver,
+}
+
+impl Resolve for ServerAddresses {
+    fn resolve(&self, _name: Name) -> Resolving {
+        let host = self.host.clone();
+        let resolver = self.resolver.clone();
+
+        Box::pin(async move {
+            let lookup = resolver.lookup_ip(host.as_str()).await?;
+            // The port is the one of the URL.
+            let addresses = lookup
+                .iter()
+                .map(|address| SocketAddr::new(address, 0))
+                .collect::<Vec<_>>();
+
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http_client::sni_override_http_client::SniOverrideHttpClient;
+
+    #[tokio::test]
+    async fn rewrites_the_host_of_every_server() {
+        let http_client =
+            SniOverrideHttpClient::new(reqwest::Client::builder, "api.internal".to_string())
+                .unwrap();
+
+        for (url, rewritten) in [
+            (
+                "https://127.0.0.1:8443/health",
+                "https://api.internal:8443/health",
+            ),
+            (
+                "https://10.0.0.7/users?id=7",
+                "https://api.internal/users?id=7",
+            ),
+            ("https://[::1]:8443/", "https://api.internal:8443/"),
+        ] {
+            let (url, _) = http_client.rewrite_url(url).unwrap();
+            assert_eq!(url, rewritten);
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_a_client_per_server_host() {
+        let http_client =
+            SniOverrideHttpClient::new(reqwest::Client::builder, "api.internal".to_string())
+                .unwrap();
+
+        for url in [
+            "https://10.0.0.1:8443/a",
+            "https://10.0.0.1:9443/b",
+            "https://10.0.0.2:8443/c",
+        ] {
+            http_client.rewrite_url(url).unwrap();
+        }
+
+        assert_eq!(http_client.clients.read().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_urls() {
+        let http_client =
+            SniOverrideHttpClient::new(reqwest::Client::builder, "api.internal".to_string())
+                .unwrap();
+
+        assert!(http_client.rewrite_url("not a url").is_err());
+    }
+}
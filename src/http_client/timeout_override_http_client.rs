@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::http_client::{
+    error::Error, http_client::HttpClient, request::Request, response::Response,
+};
+
+/// Gives up on the requests whose response head doesn't come in time: the
+/// timeout of the request if it has one, e.g. the one of its route, else the
+/// one of its backend, else `timeout`. Bodies then stream for as long as they
+/// take, however large they are.
+pub struct TimeoutOverrideHttpClient {
+    http_client: Arc<dyn HttpClient>,
+    timeout: Duration,
+    overrides: Vec<(String, Duration)>,
+}
+
+impl TimeoutOverrideHttpClient {
+    pub fn new(
+        http_client: Arc<dyn HttpClient>,
+        timeout: Duration,
+        overrides: Vec<(String, Duration)>,
+    ) -> Self {
+        Self {
+            http_client,
+            timeout,
+            overrides,
+        }
+    }
+
+    fn timeout_for(&self, url: &str) -> Duration {
+        self.overrides
+            .iter()
+            .find(|(server, _)| {
+                url.strip_prefix(server.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+            })
+            .map_or(self.timeout, |(_, timeout)| *timeout)
+    }
+}
+
+#[async_trait]
+impl HttpClient for TimeoutOverrideHttpClient {
+    async fn execute(&self, request: Request) -> Result<Response, Error> {
+        let timeout = request
+            .timeout
+            .unwrap_or_else(|| self.timeout_for(&request.url));
+
+        tokio::time::timeout(timeout, self.http_client.execute(request))
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+}
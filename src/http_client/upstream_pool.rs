@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+/// How the connections to the backends are kept for reuse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpstreamPool {
+    /// Idle connections kept per backend, unlimited when `None`.
+    pub max_idle_per_host: Option<usize>,
+    /// Interval of the TCP keep-alive probes, disabled when `None`.
+    pub tcp_keepalive: Option<Duration>,
+    /// Interval of the HTTP/2 pings keeping idle connections alive, disabled
+    /// when `None`.
+    pub http2_keepalive: Option<Duration>,
+}
+
+impl UpstreamPool {
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        let builder = builder
+            .pool_max_idle_per_host(self.max_idle_per_host.unwrap_or(usize::MAX))
+            .tcp_keepalive(self.tcp_keepalive);
+
+        match self.http2_keepalive {
+            Some(interval) => builder
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_while_idle(true),
+            None => builder,
+        }
+    }
+}
+
+impl Default for UpstreamPool {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: None,
+            tcp_keepalive: Some(Duration::from_secs(15)),
+            http2_keepalive: None,
+        }
+    }
+}
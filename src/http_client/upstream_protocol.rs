@@ -0,0 +1,41 @@
+use std::str::FromStr;
+
+/// HTTP version spoken to the backends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UpstreamProtocol {
+    /// HTTP/2 when the backend offers it through ALPN, HTTP/1.1 otherwise
+    /// (always the case over plain http://).
+    #[default]
+    Auto,
+    Http1,
+    /// HTTP/2 without negotiation, also over plain http:// (h2c): requests
+    /// are multiplexed over a single connection per backend.
+    Http2,
+}
+
+impl UpstreamProtocol {
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        match self {
+            UpstreamProtocol::Auto => builder,
+            UpstreamProtocol::Http1 => builder.http1_only(),
+            UpstreamProtocol::Http2 => builder.http2_prior_knowledge(),
+        }
+    }
+}
+
+/// Written as `auto`, `http1` or `http2`, as with `--upstream-http-version`.
+impl FromStr for UpstreamProtocol {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "auto" => Ok(UpstreamProtocol::Auto),
+            "http1" => Ok(UpstreamProtocol::Http1),
+            "http2" => Ok(UpstreamProtocol::Http2),
+            _ => Err(format!(
+                "expected an HTTP version of auto, http1 or http2, got {}",
+                value
+            )),
+        }
+    }
+}
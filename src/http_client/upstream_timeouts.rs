@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+/// Timeouts applied to the connections to the backends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpstreamTimeouts {
+    pub connect: Duration,
+    /// Covers the exchange up to the response head, the body then streams
+    /// for as long as it takes.
+    pub request: Duration,
+    /// How long an unused pooled connection is kept open.
+    pub idle: Duration,
+}
+
+impl UpstreamTimeouts {
+    /// Applies the connection timeouts, the request one being left to
+    /// `TimeoutOverrideHttpClient` as the client would cut the body too.
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        builder
+            .connect_timeout(self.connect)
+            .pool_idle_timeout(self.idle)
+    }
+}
+
+impl Default for UpstreamTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(5),
+            request: Duration::from_secs(30),
+            idle: Duration::from_secs(90),
+        }
+    }
+}
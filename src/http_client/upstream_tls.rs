@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use crate::tls::error::Error;
+
+/// How the certificates presented by the HTTPS backends are verified.
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamTls {
+    /// Trusted on top of the system roots, for internally signed backends.
+    pub ca_certificates: Vec<reqwest::Certificate>,
+    /// Accepts any certificate, whoever signed it and whatever its names.
+    pub insecure_skip_verify: bool,
+}
+
+impl UpstreamTls {
+    /// Loads the CA certificates of a PEM bundle, which holds one or more.
+    pub fn with_ca_bundle(mut self, path: &Path) -> Result<Self, Error> {
+        let pem = std::fs::read(path)
+            .map_err(|error| Error::Read(path.display().to_string(), error.to_string()))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+
+        if certificates.is_empty() {
+            return Err(Error::InvalidCertificate(format!(
+                "no certificate in {}",
+                path.display()
+            )));
+        }
+
+        self.ca_certificates.extend(certificates);
+
+        Ok(self)
+    }
+
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        self.ca_certificates
+            .iter()
+            .fold(builder, |builder, certificate| {
+                builder.add_root_certificate(certificate.clone())
+            })
+            .danger_accept_invalid_certs(self.insecure_skip_verify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use crate::http_client::upstream_tls::UpstreamTls;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("wakanda-lb-{}.crt", Uuid::new_v4()));
+            std::fs::write(&path, contents).unwrap();
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn ca_pem() -> String {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+
+        params.self_signed(&key_pair).unwrap().pem()
+    }
+
+    #[test]
+    fn loads_every_certificate_of_the_bundle() {
+        let bundle = TempFile::new(&format!("{}{}", ca_pem(), ca_pem()));
+
+        let upstream_tls = UpstreamTls::default().with_ca_bundle(&bundle.0).unwrap();
+
+        assert_eq!(upstream_tls.ca_certificates.len(), 2);
+        assert!(
+            upstream_tls
+                .apply(reqwest::Client::builder())
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn rejects_a_bundle_without_certificates() {
+        let bundle = TempFile::new("not a certificate");
+
+        assert!(UpstreamTls::default().with_ca_bundle(&bundle.0).is_err());
+    }
+
+    #[test]
+    fn rejects_a_missing_bundle() {
+        let result = UpstreamTls::default().with_ca_bundle(&PathBuf::from("/nonexistent/ca.crt"));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,679 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body as AxumBody;
+use futures::StreamExt;
+use http::{HeaderValue, header};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::http_client::http_client::HttpClient;
+use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+use crate::http_client::reqwest_http_client::ReqwestHttpClient;
+use crate::http_client::response::Response;
+
+/// Mounted in every pod running under a service account.
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+const SERVICE_NAME_LABEL: &str = "kubernetes.io%2Fservice-name";
+/// How long the API server keeps a watch open before it is started again.
+const WATCH_TIMEOUT_SECONDS: u64 = 300;
+
+/// A Kubernetes Service, written as `[NAMESPACE/]SERVICE[:PORT]`, e.g.
+/// `shop/api:http`. Without a namespace the one of the pod is used, and
+/// without a port the first one of the service, which may be given by name
+/// or number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KubernetesService {
+    pub namespace: Option<String>,
+    pub name: String,
+    pub port: Option<String>,
+}
+
+impl FromStr for KubernetesService {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected [NAMESPACE/]SERVICE[:PORT], got {}", value);
+
+        let (namespace, service) = match value.split_once('/') {
+            Some((namespace, service)) => (Some(namespace.trim()), service),
+            None => (None, value),
+        };
+        let (name, port) = match service.split_once(':') {
+            Some((name, port)) => (name.trim(), Some(port.trim())),
+            None => (service.trim(), None),
+        };
+
+        if name.is_empty()
+            || namespace.is_some_and(str::is_empty)
+            || port.is_some_and(str::is_empty)
+        {
+            return Err(invalid());
+        }
+
+        Ok(KubernetesService {
+            namespace: namespace.map(str::to_string),
+            name: name.to_string(),
+            port: port.map(str::to_string),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct EndpointSliceList {
+    #[serde(default)]
+    metadata: ListMeta,
+    #[serde(default)]
+    items: Vec<EndpointSlice>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ListMeta {
+    #[serde(default)]
+    resource_version: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointSlice {
+    #[serde(default)]
+    metadata: ObjectMeta,
+    #[serde(default)]
+    address_type: String,
+    #[serde(default)]
+    endpoints: Vec<Endpoint>,
+    #[serde(default)]
+    ports: Vec<EndpointPort>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ObjectMeta {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    resource_version: String,
+}
+
+#[derive(Deserialize)]
+struct Endpoint {
+    #[serde(default)]
+    addresses: Vec<String>,
+    #[serde(default)]
+    conditions: EndpointConditions,
+}
+
+#[derive(Deserialize, Default)]
+struct EndpointConditions {
+    ready: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct EndpointPort {
+    name: Option<String>,
+    port: Option<u16>,
+}
+
+/// A line of a watch: an EndpointSlice `ADDED`, `MODIFIED` or `DELETED`, a
+/// `BOOKMARK` only moving the resource version, or an `ERROR` status.
+#[derive(Deserialize)]
+struct WatchEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    object: serde_json::Value,
+}
+
+#[derive(Deserialize, Default)]
+struct Status {
+    #[serde(default)]
+    code: u16,
+    #[serde(default)]
+    message: String,
+}
+
+/// The EndpointSlices of the service by name, as of `resource_version`.
+#[derive(Default)]
+struct Slices {
+    resource_version: String,
+    slices: HashMap<String, EndpointSlice>,
+}
+
+/// Keeps the backends of a Kubernetes Service in sync with its
+/// EndpointSlices, listed from the API server then watched for changes.
+/// Only the ready endpoints are kept, as `http://ADDRESS:PORT`. A failed
+/// listing or watch keeps the previous backends, and they are listed again
+/// after `retry_interval`.
+pub struct EndpointSliceDiscovery {
+    http_client: Arc<dyn HttpClient>,
+    api_server: String,
+    token_file: Option<PathBuf>,
+    namespace: String,
+    service: KubernetesService,
+    retry_interval: Duration,
+    backends: watch::Sender<Vec<String>>,
+}
+
+impl EndpointSliceDiscovery {
+    pub fn new(
+        http_client: Arc<dyn HttpClient>,
+        api_server: String,
+        namespace: String,
+        service: KubernetesService,
+        retry_interval: Duration,
+    ) -> Self {
+        Self {
+            http_client,
+            api_server,
+            token_file: None,
+            namespace: service.namespace.clone().unwrap_or(namespace),
+            service,
+            retry_interval,
+            backends: watch::Sender::new(Vec::new()),
+        }
+    }
+
+    /// Talks to the API server of the cluster the pod runs in, as its
+    /// service account, which needs to be allowed to list and watch
+    /// EndpointSlices.
+    pub fn in_cluster(
+        service: KubernetesService,
+        retry_interval: Duration,
+    ) -> Result<Self, String> {
+        let host = std::env::var("KUBERNETES_SERVICE_HOST").map_err(|_| {
+            "KUBERNETES_SERVICE_HOST is not set, not running in a cluster".to_string()
+        })?;
+        let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        let host = match host.contains(':') {
+            true => format!("[{}]", host),
+            false => host,
+        };
+
+        let service_account = Path::new(SERVICE_ACCOUNT);
+        let read = |file: &str| {
+            std::fs::read(service_account.join(file)).map_err(|error| {
+                format!(
+                    "can't read {}: {}",
+                    service_account.join(file).display(),
+                    error
+                )
+            })
+        };
+        let certificate = reqwest::Certificate::from_pem(&read("ca.crt")?)
+            .map_err(|error| format!("invalid cluster CA certificate: {}", error))?;
+        let namespace = String::from_utf8_lossy(&read("namespace")?)
+            .trim()
+            .to_string();
+        // A watch lasts until the API server ends it, silent in between when
+        // nothing changes.
+        let client = reqwest::Client::builder()
+            .add_root_certificate(certificate)
+            .connect_timeout(Duration::from_secs(10))
+            .read_timeout(Duration::from_secs(WATCH_TIMEOUT_SECONDS + 10))
+            .build()
+            .map_err(|error| error.to_string())?;
+
+        Ok(Self::new(
+            Arc::new(ReqwestHttpClient::new(client)),
+            format!("https://{}:{}", host, port),
+            namespace,
+            service,
+            retry_interval,
+        )
+        .with_token_file(service_account.join("token")))
+    }
+
+    /// Bearer token sent to the API server, read again for every request
+    /// since the kubelet rotates it.
+    pub fn with_token_file(mut self, token_file: PathBuf) -> Self {
+        self.token_file = Some(token_file);
+        self
+    }
+
+    pub fn service(&self) -> &KubernetesService {
+        &self.service
+    }
+
+    /// The backends found by the last successful listing or change, the
+    /// current ones being marked as seen.
+    pub fn subscribe(&self) -> watch::Receiver<Vec<String>> {
+        self.backends.subscribe()
+    }
+
+    /// Lists the EndpointSlices of the service, publishing and returning
+    /// its ready backends sorted.
+    pub async fn discover(&self) -> Result<Vec<String>, String> {
+        self.list().await.map(|slices| self.publish(&slices))
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/apis/discovery.k8s.io/v1/namespaces/{}/endpointslices?labelSelector={}%3D{}",
+            self.api_server, self.namespace, SERVICE_NAME_LABEL, self.service.name
+        )
+    }
+
+    async fn get(&self, url: &str) -> Result<Response, String> {
+        let mut headers =
+            RequestHeaders::from([(header::ACCEPT, HeaderValue::from_static("application/json"))]);
+        if let Some(token_file) = &self.token_file {
+            let token = tokio::fs::read_to_string(token_file)
+                .await
+                .map_err(|error| format!("can't read {}: {}", token_file.display(), error))?;
+            let authorization = HeaderValue::try_from(format!("Bearer {}", token.trim()))
+                .map_err(|_| format!("invalid token in {}", token_file.display()))?;
+            headers.insert(header::AUTHORIZATION, authorization);
+        }
+
+        let response = self
+            .http_client
+            .execute(Request {
+                method: RequestMethod::Get,
+                url: url.to_string(),
+                headers,
+                body: Default::default(),
+                timeout: None,
+            })
+            .await
+            .map_err(|error| error.to_string())?;
+
+        match response.status {
+            200 => Ok(response),
+            status => Err(format!("{} returned status {}", url, status)),
+        }
+    }
+
+    async fn list(&self) -> Result<Slices, String> {
+        let url = self.url();
+        let body = self
+            .get(&url)
+            .await?
+            .body
+            .collect()
+            .await
+            .map_err(|error| error.to_string())?;
+        let list: EndpointSliceList = serde_json::from_slice(&body)
+            .map_err(|error| format!("{} answered garbage: {}", url, error))?;
+
+        Ok(Slices {
+            resource_version: list.metadata.resource_version,
+            slices: list
+                .items
+                .into_iter()
+                .map(|slice| (slice.metadata.name.clone(), slice))
+                .collect(),
+        })
+    }
+
+    /// Follows the changes of the EndpointSlices since `slices`, publishing
+    /// the backends after each, until the API server ends the watch. Fails
+    /// when it can't, e.g. once the changes since `slices` are gone, for the
+    /// slices to be listed again.
+    async fn watch(&self, slices: &mut Slices) -> Result<(), String> {
+        let url = format!(
+            "{}&watch=true&allowWatchBookmarks=true&timeoutSeconds={}&resourceVersion={}",
+            self.url(),
+            WATCH_TIMEOUT_SECONDS,
+            slices.resource_version
+        );
+        let mut body = AxumBody::from(self.get(&url).await?.body).into_data_stream();
+        let mut buffered = Vec::new();
+
+        while let Some(chunk) = body.next().await {
+            buffered.extend_from_slice(&chunk.map_err(|error| error.to_string())?);
+
+            while let Some(end) = buffered.iter().position(|byte| *byte == b'\n') {
+                let line = buffered.drain(..=end).collect::<Vec<_>>();
+                if line.trim_ascii().is_empty() {
+                    continue;
+                }
+
+                let event: WatchEvent = serde_json::from_slice(&line)
+                    .map_err(|error| format!("{} answered garbage: {}", url, error))?;
+                self.apply(slices, event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, slices: &mut Slices, event: WatchEvent) -> Result<(), String> {
+        if event.kind == "ERROR" {
+            let status = serde_json::from_value::<Status>(event.object).unwrap_or_default();
+            return Err(format!(
+                "the watch failed with status {}: {}",
+                status.code, status.message
+            ));
+        }
+
+        let slice: EndpointSlice = serde_json::from_value(event.object)
+            .map_err(|error| format!("invalid {} EndpointSlice: {}", event.kind, error))?;
+        slices
+            .resource_version
+            .clone_from(&slice.metadata.resource_version);
+
+        match event.kind.as_str() {
+            "ADDED" | "MODIFIED" => {
+                slices.slices.insert(slice.metadata.name.clone(), slice);
+            }
+            "DELETED" => {
+                slices.slices.remove(&slice.metadata.name);
+            }
+            _ => return Ok(()),
+        }
+
+        self.publish(slices);
+        Ok(())
+    }
+
+    /// Publishes the ready backends of `slices`, returning them.
+    fn publish(&self, slices: &Slices) -> Vec<String> {
+        let backends = self.backends_of(slices.slices.values());
+
+        self.backends.send_if_modified(|current| {
+            let changed = *current != backends;
+            if changed {
+                info!(
+                    "Service {} now has {} ready backends",
+                    self.service.name,
+                    backends.len()
+                );
+                current.clone_from(&backends);
+            }
+            changed
+        });
+
+        backends
+    }
+
+    fn backends_of<'a>(&self, slices: impl Iterator<Item = &'a EndpointSlice>) -> Vec<String> {
+        let mut backends = Vec::new();
+
+        for slice in slices {
+            let port = slice.ports.iter().find(|port| match &self.service.port {
+                Some(wanted) => {
+                    port.name.as_deref() == Some(wanted.as_str())
+                        || port.port.is_some_and(|port| port.to_string() == *wanted)
+                }
+                None => true,
+            });
+            let Some(port) = port.and_then(|port| port.port) else {
+                continue;
+            };
+
+            for endpoint in &slice.endpoints {
+                // An unknown readiness is to be taken as ready.
+                if endpoint.conditions.ready == Some(false) {
+                    continue;
+                }
+
+                for address in &endpoint.addresses {
+                    backends.push(match slice.address_type.as_str() {
+                        "IPv6" => format!("http://[{}]:{}", address, port),
+                        _ => format!("http://{}:{}", address, port),
+                    });
+                }
+            }
+        }
+
+        backends.sort();
+        backends.dedup();
+        backends
+    }
+
+    /// Lists the EndpointSlices then watches them, until either fails.
+    async fn list_and_watch(&self) -> Result<(), String> {
+        let mut slices = self.list().await?;
+        self.publish(&slices);
+
+        loop {
+            self.watch(&mut slices).await?;
+        }
+    }
+
+    /// Keeps watching the EndpointSlices forever, publishing the backends
+    /// whenever they change.
+    pub async fn execute(&self) {
+        info!(
+            "Watching the backends of service {} in namespace {}",
+            self.service.name, self.namespace
+        );
+
+        loop {
+            if let Err(error) = self.list_and_watch().await {
+                warn!(
+                    "Keeping the backends of service {}: {}",
+                    self.service.name, error
+                );
+            }
+
+            time::sleep(self.retry_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::body::Body as AxumBody;
+    use bytes::Bytes;
+
+    use crate::http_client::http_client::MockHttpClient;
+    use crate::http_client::request::RequestHeaders;
+    use crate::http_client::response::Response;
+    use crate::kubernetes_discovery::{EndpointSliceDiscovery, KubernetesService};
+
+    const ENDPOINT_SLICES: &str = r#"{
+        "kind": "EndpointSliceList",
+        "metadata": {"resourceVersion": "10"},
+        "items": [
+            {
+                "metadata": {"name": "api-ipv4", "resourceVersion": "8"},
+                "addressType": "IPv4",
+                "endpoints": [
+                    {"addresses": ["10.0.0.2"], "conditions": {"ready": true}},
+                    {"addresses": ["10.0.0.1"]},
+                    {"addresses": ["10.0.0.3"], "conditions": {"ready": false}}
+                ],
+                "ports": [
+                    {"name": "metrics", "port": 9090, "protocol": "TCP"},
+                    {"name": "http", "port": 8080, "protocol": "TCP"}
+                ]
+            },
+            {
+                "metadata": {"name": "api-ipv6", "resourceVersion": "9"},
+                "addressType": "IPv6",
+                "endpoints": [{"addresses": ["fd00::1"], "conditions": {"ready": true}}],
+                "ports": [{"name": "http", "port": 8080, "protocol": "TCP"}]
+            }
+        ]
+    }"#;
+
+    fn discovery(service: &str, status: u16, body: &'static str) -> EndpointSliceDiscovery {
+        let mut http_client = MockHttpClient::new();
+        http_client
+            .expect_execute()
+            .withf(|request| {
+                request.url
+                    == "https://kubernetes.local/apis/discovery.k8s.io/v1/namespaces/shop/endpointslices?labelSelector=kubernetes.io%2Fservice-name%3Dapi"
+            })
+            .returning(move |_| {
+                Ok(Response {
+                    status,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::from_static(body.as_bytes()).into(),
+                })
+            });
+
+        EndpointSliceDiscovery::new(
+            Arc::new(http_client),
+            "https://kubernetes.local".to_string(),
+            "default".to_string(),
+            service.parse().unwrap(),
+            Duration::from_secs(5),
+        )
+    }
+
+    const LIST_URL: &str = "https://kubernetes.local/apis/discovery.k8s.io/v1/namespaces/shop/endpointslices?labelSelector=kubernetes.io%2Fservice-name%3Dapi";
+
+    /// Lists `ENDPOINT_SLICES`, then answers the watch with `events`, in
+    /// chunks cutting them anywhere.
+    fn watching(events: &'static [&'static str]) -> EndpointSliceDiscovery {
+        let mut http_client = MockHttpClient::new();
+        http_client
+            .expect_execute()
+            .withf(|request| request.url == LIST_URL)
+            .returning(|_| {
+                Ok(Response {
+                    status: 200,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::from_static(ENDPOINT_SLICES.as_bytes()).into(),
+                })
+            });
+        http_client
+            .expect_execute()
+            .withf(|request| {
+                request.url
+                    == format!(
+                        "{}&watch=true&allowWatchBookmarks=true&timeoutSeconds=300&resourceVersion=10",
+                        LIST_URL
+                    )
+            })
+            .returning(|_| {
+                let chunks = events
+                    .concat()
+                    .into_bytes()
+                    .chunks(7)
+                    .map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk)))
+                    .collect::<Vec<_>>();
+
+                Ok(Response {
+                    status: 200,
+                    headers: RequestHeaders::default(),
+                    body: AxumBody::from_stream(futures::stream::iter(chunks)).into(),
+                })
+            });
+
+        EndpointSliceDiscovery::new(
+            Arc::new(http_client),
+            "https://kubernetes.local".to_string(),
+            "default".to_string(),
+            "shop/api:http".parse().unwrap(),
+            Duration::from_secs(5),
+        )
+    }
+
+    #[test]
+    fn parses_services() {
+        assert_eq!(
+            "shop/api:http".parse(),
+            Ok(KubernetesService {
+                namespace: Some("shop".to_string()),
+                name: "api".to_string(),
+                port: Some("http".to_string()),
+            })
+        );
+        assert_eq!(
+            "api".parse(),
+            Ok(KubernetesService {
+                namespace: None,
+                name: "api".to_string(),
+                port: None,
+            })
+        );
+
+        for service in ["", "/api", "shop/", "api:"] {
+            assert!(service.parse::<KubernetesService>().is_err(), "{}", service);
+        }
+    }
+
+    #[tokio::test]
+    async fn discovers_the_ready_endpoints_on_the_service_port() {
+        let discovery = discovery("shop/api:http", 200, ENDPOINT_SLICES);
+        let mut backends = discovery.subscribe();
+
+        let discovered = discovery.discover().await.unwrap();
+
+        assert_eq!(
+            discovered,
+            vec![
+                "http://10.0.0.1:8080".to_string(),
+                "http://10.0.0.2:8080".to_string(),
+                "http://[fd00::1]:8080".to_string(),
+            ]
+        );
+        assert!(backends.has_changed().unwrap());
+        assert_eq!(*backends.borrow_and_update(), discovered);
+    }
+
+    #[tokio::test]
+    async fn picks_the_port_by_number_or_the_first_one() {
+        assert_eq!(
+            discovery("shop/api:9090", 200, ENDPOINT_SLICES)
+                .discover()
+                .await
+                .unwrap()[0],
+            "http://10.0.0.1:9090"
+        );
+        assert_eq!(
+            discovery("shop/api", 200, ENDPOINT_SLICES)
+                .discover()
+                .await
+                .unwrap()[0],
+            "http://10.0.0.1:9090"
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_on_errors_of_the_api_server() {
+        assert!(discovery("shop/api", 403, "{}").discover().await.is_err());
+        assert!(
+            discovery("shop/api", 200, "<html>")
+                .discover()
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_the_changes_of_the_watched_slices() {
+        let discovery = watching(&[
+            r#"{"type":"MODIFIED","object":{"metadata":{"name":"api-ipv4","resourceVersion":"11"},"addressType":"IPv4","endpoints":[{"addresses":["10.0.0.5"]}],"ports":[{"name":"http","port":8080}]}}"#,
+            "\n",
+            r#"{"type":"DELETED","object":{"metadata":{"name":"api-ipv6","resourceVersion":"12"}}}"#,
+            "\n\n",
+            r#"{"type":"BOOKMARK","object":{"metadata":{"resourceVersion":"13"}}}"#,
+            "\n",
+        ]);
+        let mut slices = discovery.list().await.unwrap();
+        let mut backends = discovery.subscribe();
+
+        discovery.watch(&mut slices).await.unwrap();
+
+        assert_eq!(
+            *backends.borrow_and_update(),
+            vec!["http://10.0.0.5:8080".to_string()]
+        );
+        assert_eq!(slices.resource_version, "13");
+    }
+
+    #[tokio::test]
+    async fn fails_once_the_watched_changes_are_gone() {
+        let discovery = watching(&[
+            r#"{"type":"ERROR","object":{"kind":"Status","code":410,"message":"too old resource version"}}"#,
+            "\n",
+        ]);
+        let mut slices = discovery.list().await.unwrap();
+
+        let error = discovery.watch(&mut slices).await.unwrap_err();
+
+        assert!(error.contains("410"), "{}", error);
+    }
+}
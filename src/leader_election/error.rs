@@ -0,0 +1,8 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Lease storage error: {0}")]
+    Storage(String),
+
+    #[error("Malformed lease: {0}")]
+    MalformedLease(String),
+}
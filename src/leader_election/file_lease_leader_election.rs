@@ -0,0 +1,299 @@
+use async_trait::async_trait;
+use std::{
+    io::ErrorKind,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::leader_election::{error::Error, leader_election::LeaderElection};
+
+/// The most often the lease is renewed, however short it is.
+const MIN_RENEWAL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, PartialEq)]
+struct Lease {
+    holder: String,
+    expires_at_millis: u128,
+    address: String,
+}
+
+impl Lease {
+    fn parse(content: &str) -> Result<Lease, Error> {
+        let mut fields = content.trim().splitn(3, ' ');
+
+        let (Some(holder), Some(expires_at_millis), Some(address)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(Error::MalformedLease(content.to_string()));
+        };
+
+        let expires_at_millis = expires_at_millis
+            .parse()
+            .map_err(|_| Error::MalformedLease(content.to_string()))?;
+
+        Ok(Lease {
+            holder: holder.to_string(),
+            expires_at_millis,
+            address: address.to_string(),
+        })
+    }
+
+    fn serialize(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.holder, self.expires_at_millis, self.address
+        )
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at_millis <= FileLeaseLeaderElection::now_millis()
+    }
+}
+
+/// Leader election backed by a lease file on a filesystem shared by all the
+/// instances. The lease is held by whoever wrote it last until it expires;
+/// the holder keeps renewing it, any other instance can take it over once
+/// it is expired.
+pub struct FileLeaseLeaderElection {
+    path: PathBuf,
+    holder: String,
+    address: String,
+    lease_duration: Duration,
+    is_leader: AtomicBool,
+}
+
+impl FileLeaseLeaderElection {
+    pub fn new(path: PathBuf, address: String, lease_duration: Duration) -> Self {
+        Self {
+            path,
+            holder: Uuid::new_v4().to_string(),
+            address,
+            lease_duration,
+            is_leader: AtomicBool::new(false),
+        }
+    }
+
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Campaigns forever, renewing the lease three times per lease duration.
+    pub async fn execute(&self) {
+        info!(
+            "Starting file lease leader election on {:?} as {}",
+            self.path, self.holder
+        );
+
+        let mut interval = time::interval((self.lease_duration / 3).max(MIN_RENEWAL_INTERVAL));
+
+        loop {
+            interval.tick().await;
+
+            let was_leader = self.is_leader();
+
+            match self.campaign().await {
+                Ok(true) if !was_leader => info!("Acquired leadership"),
+                Ok(false) if was_leader => warn!("Lost leadership"),
+                Ok(_) => {}
+                Err(error) => warn!("Leader election campaign failed: {}", error),
+            }
+        }
+    }
+
+    fn now_millis() -> u128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default()
+    }
+
+    fn new_lease(&self) -> Lease {
+        Lease {
+            holder: self.holder.clone(),
+            expires_at_millis: Self::now_millis() + self.lease_duration.as_millis(),
+            address: self.address.clone(),
+        }
+    }
+
+    async fn read_lease(&self) -> Result<Option<Lease>, Error> {
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => Lease::parse(&content).map(Some),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(Error::Storage(error.to_string())),
+        }
+    }
+
+    async fn write_lease(&self, lease: &Lease) -> Result<(), Error> {
+        let temporary_path = self.path.with_extension(format!("{}.tmp", self.holder));
+
+        tokio::fs::write(&temporary_path, lease.serialize())
+            .await
+            .map_err(|error| Error::Storage(error.to_string()))?;
+
+        tokio::fs::rename(&temporary_path, &self.path)
+            .await
+            .map_err(|error| Error::Storage(error.to_string()))
+    }
+
+    async fn try_acquire(&self) -> Result<bool, Error> {
+        let current = match self.read_lease().await {
+            Ok(lease) => lease,
+            Err(Error::MalformedLease(content)) => {
+                warn!("Overwriting malformed lease: {}", content);
+                None
+            }
+            Err(error) => return Err(error),
+        };
+
+        let can_acquire = match current {
+            None => true,
+            Some(lease) => lease.holder == self.holder || lease.is_expired(),
+        };
+
+        if !can_acquire {
+            return Ok(false);
+        }
+
+        self.write_lease(&self.new_lease()).await?;
+
+        // Two instances may race on an expired lease: the last rename wins,
+        // so confirm we are the one who is actually holding it.
+        Ok(self
+            .read_lease()
+            .await?
+            .is_some_and(|lease| lease.holder == self.holder))
+    }
+}
+
+#[async_trait]
+impl LeaderElection for FileLeaseLeaderElection {
+    async fn campaign(&self) -> Result<bool, Error> {
+        let result = self.try_acquire().await;
+
+        self.is_leader
+            .store(matches!(result, Ok(true)), Ordering::Relaxed);
+
+        result
+    }
+
+    fn is_leader(&self) -> bool {
+        self.is_leader.load(Ordering::Relaxed)
+    }
+
+    async fn leader_address(&self) -> Result<Option<String>, Error> {
+        Ok(self
+            .read_lease()
+            .await?
+            .filter(|lease| !lease.is_expired())
+            .map(|lease| lease.address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use crate::leader_election::{
+        file_lease_leader_election::{FileLeaseLeaderElection, Lease},
+        leader_election::LeaderElection,
+    };
+
+    fn address() -> String {
+        "http://127.0.0.1:3000".to_string()
+    }
+
+    fn lease_path() -> PathBuf {
+        std::env::temp_dir().join(format!("wakanda-lb-{}.lease", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn first_campaigner_becomes_leader() {
+        let path = lease_path();
+        let election =
+            FileLeaseLeaderElection::new(path.clone(), address(), Duration::from_secs(10));
+
+        assert!(!election.is_leader());
+        assert!(election.campaign().await.unwrap());
+        assert!(election.is_leader());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_one_instance_holds_a_valid_lease() {
+        let path = lease_path();
+        let first = FileLeaseLeaderElection::new(path.clone(), address(), Duration::from_secs(10));
+        let second = FileLeaseLeaderElection::new(path.clone(), address(), Duration::from_secs(10));
+
+        assert!(first.campaign().await.unwrap());
+        assert!(!second.campaign().await.unwrap());
+        assert!(!second.is_leader());
+
+        assert!(first.campaign().await.unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_lease_can_be_taken_over() {
+        let path = lease_path();
+        let first = FileLeaseLeaderElection::new(path.clone(), address(), Duration::from_millis(1));
+        let second = FileLeaseLeaderElection::new(path.clone(), address(), Duration::from_secs(10));
+
+        assert!(first.campaign().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(second.campaign().await.unwrap());
+        assert!(!first.campaign().await.unwrap());
+        assert!(!first.is_leader());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn parses_a_serialized_lease() {
+        let lease = Lease {
+            holder: "holder".to_string(),
+            expires_at_millis: 42,
+            address: address(),
+        };
+
+        assert_eq!(Lease::parse(&lease.serialize()).unwrap(), lease);
+        assert!(Lease::parse("garbage").is_err());
+        assert!(Lease::parse("holder 42").is_err());
+    }
+
+    #[tokio::test]
+    async fn followers_know_the_leader_address() {
+        let path = lease_path();
+        let leader = FileLeaseLeaderElection::new(
+            path.clone(),
+            "http://leader:3000".to_string(),
+            Duration::from_secs(10),
+        );
+        let follower = FileLeaseLeaderElection::new(
+            path.clone(),
+            "http://follower:3000".to_string(),
+            Duration::from_secs(10),
+        );
+
+        assert_eq!(follower.leader_address().await.unwrap(), None);
+
+        assert!(leader.campaign().await.unwrap());
+        assert!(!follower.campaign().await.unwrap());
+
+        assert_eq!(
+            follower.leader_address().await.unwrap(),
+            Some("http://leader:3000".to_string())
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
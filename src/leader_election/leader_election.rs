@@ -0,0 +1,16 @@
+use async_trait::async_trait;
+
+use crate::leader_election::error::Error;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait LeaderElection: Send + Sync {
+    /// Tries to acquire the leadership, or to renew it when already held.
+    /// Returns whether this instance is the leader after the attempt.
+    async fn campaign(&self) -> Result<bool, Error>;
+
+    fn is_leader(&self) -> bool;
+
+    /// Address advertised by the current leader, if there is one.
+    async fn leader_address(&self) -> Result<Option<String>, Error>;
+}
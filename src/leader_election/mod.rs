@@ -0,0 +1,4 @@
+pub mod error;
+pub mod file_lease_leader_election;
+#[allow(clippy::module_inception)]
+pub mod leader_election;
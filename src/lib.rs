@@ -1,26 +1,125 @@
+pub mod admin;
+pub mod allowed_methods;
 pub mod background_health_checker;
+pub mod body_limit;
+pub mod bulkhead;
+pub mod certificate_expiries;
 pub(crate) mod cli_arguments;
+pub mod client_certificate;
+pub mod client_connection;
+pub mod config_rollout;
+pub mod connection_recycling;
+pub mod consul_discovery;
+pub mod cost_budget;
+pub mod deadline;
+pub mod decision_record;
+pub mod dev_trace;
+pub mod downstream_timeouts;
+pub mod error_pages;
+pub mod experiment;
+pub mod failed_attempts;
+pub mod forwarded_headers;
+pub mod geo_ip;
+pub mod grpc;
+pub mod health_notifier;
+pub mod host_header;
+pub mod http10_compat;
 pub mod http_client;
+pub mod kubernetes_discovery;
+pub mod leader_election;
+pub mod listener;
+pub mod location_rewrite;
+pub mod metrics;
+pub mod path_regex;
+pub mod path_rules;
+pub mod pools;
+pub mod range_requests;
+pub mod request_age;
+pub mod request_coalescing;
 pub(crate) mod request_id;
+pub mod request_transforms;
+pub mod response_cache;
+pub mod response_compression;
+pub mod retry_after;
+pub mod retry_policy;
+pub mod routing_rules;
 pub(crate) mod select_server;
+pub mod srv_discovery;
+pub mod state_store;
+pub mod target_url;
+pub mod time_rules;
+pub mod tls;
+pub mod traffic_mirror;
+pub mod upstream_compression;
+pub mod via_headers;
 
+use crate::admin::admin_auth::AdminAuth;
+use crate::admin::admin_router::admin_router;
+use crate::admin::annotations::Annotations;
+use crate::admin::effective_config::EffectiveConfig;
+use crate::admin::maintenance::Maintenance;
+use crate::allowed_methods::AllowedMethods;
+use crate::background_health_checker::health_history::HealthHistory;
+use crate::body_limit::{BodyLimitAction, BodyOverflow};
+use crate::bulkhead::Bulkheads;
+use crate::certificate_expiries::CertificateExpiries;
+use crate::client_certificate::{ClientCertificate, ClientCertificateRules};
+use crate::connection_recycling::ConnectionRecycling;
+use crate::cost_budget::{CostBudgets, CostVerdict};
+use crate::deadline::{Deadline, grpc_deadline_exceeded};
+use crate::decision_record::DecisionRecords;
+use crate::error_pages::{ErrorPages, FallbackResponse};
+use crate::experiment::{Experiment, X_EXPERIMENT_VARIANT};
+use crate::failed_attempts::FailedAttempts;
+use crate::forwarded_headers::ForwardedHeaders;
+use crate::geo_ip::geo_ip::{GeoIp, X_GEO_CONTINENT, X_GEO_COUNTRY};
+use crate::grpc::GrpcTimeouts;
+use crate::host_header::HostHeader;
+use crate::http_client::body::Body as HttpClientBody;
 use crate::http_client::error::Error as HttpClientError;
-use crate::http_client::request::{Request as HttpClientRequest, RequestMethod};
+use crate::http_client::request::{Request as HttpClientRequest, RequestHeaders, RequestMethod};
 use crate::http_client::response::Response as HttpClientResponse;
+use crate::location_rewrite::LocationRewrite;
+use crate::metrics::counted_body::CountedBody;
+use crate::metrics::metrics::{Metrics, RETRIES_TOTAL};
+use crate::metrics::usage_tracker::UsageTracker;
+use crate::path_rules::PathRules;
+use crate::pools::pool::Pool;
+use crate::pools::swappable_pools::SwappablePools;
+use crate::range_requests::RangeRequests;
+use crate::request_age::AcceptedAt;
+use crate::request_coalescing::RequestCoalescing;
 use crate::request_id::{LoadBalancerRequestId, UNKNOWN_REQUEST_ID, X_REQUEST_ID};
+use crate::request_transforms::RequestTransforms;
+use crate::response_cache::ResponseCache;
+use crate::response_compression::ResponseCompression;
+use crate::retry_after::BackendBackoffs;
+use crate::retry_policy::RetryPolicy;
+use crate::routing_rules::header_transform::Direction;
+use crate::routing_rules::routing_rules::RoutingRules;
 use crate::select_server::request::Request as SelectServerRequest;
+use crate::time_rules::TimeRules;
+use crate::traffic_mirror::TrafficMirror;
+use crate::upstream_compression::UpstreamDecoding;
+use crate::via_headers::ViaHeaders;
 
-use axum::body::{Body, to_bytes};
+use axum::body::{Body, HttpBody};
 use axum::extract::Request as AxumRequest;
-use axum::extract::State;
+use axum::extract::{ConnectInfo, State};
+use axum::middleware;
 use axum::response::{IntoResponse, Response};
 use axum::routing::any;
 use axum::{Router, routing::get};
-use http::StatusCode;
-use std::sync::Arc;
+use http::request::Parts;
+use http::{HeaderValue, StatusCode, Version, header};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::request_id::{PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::{DefaultOnResponse, TraceLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub use http_client::http_client::HttpClient;
 pub use http_client::reqwest_http_client::ReqwestHttpClient;
@@ -28,13 +127,170 @@ pub use http_client::reqwest_http_client::ReqwestHttpClient;
 pub use background_health_checker::timed_background_health_checker::TimedBackgroundChecker;
 pub use select_server::select_server::SelectServer;
 
+pub use select_server::priority_select_server::PrioritySelectServer;
+pub use select_server::quarantine_select_server::QuarantineSelectServer;
 pub use select_server::random_select_server::RandomSelectServer;
 pub use select_server::round_robin_select_server::RoundRobinSelectServer;
+pub use select_server::weights::Weights;
 
 #[derive(Clone)]
 pub struct ServerState {
+    /// The backends of the requests routed to no pool, replaced by a reload.
+    pub target_servers: Arc<RwLock<Vec<String>>>,
     pub http_client: Arc<dyn HttpClient + Send + Sync>,
+    /// Probes the backends the way the health checker does, e.g. to verify
+    /// a pool before a blue/green switch.
+    pub probe_client: Arc<dyn HttpClient + Send + Sync>,
     pub select_server: Arc<dyn SelectServer>,
+    pub health_history: Arc<HealthHistory>,
+    pub healthy_servers: Arc<RwLock<Vec<String>>>,
+    pub metrics: Arc<Metrics>,
+    pub usage: Arc<UsageTracker>,
+    pub annotations: Arc<Annotations>,
+    /// Guards the admin requests changing the load balancer.
+    pub admin_auth: AdminAuth,
+    /// Backends an operator took out of rotation, whatever their health.
+    pub maintenance: Arc<Maintenance>,
+    pub retries: usize,
+    pub retry_policy: RetryPolicy,
+    /// Backends whose `Retry-After` is honored while retries are enabled.
+    pub backend_backoffs: Arc<BackendBackoffs>,
+    /// Tell the clients when to come back once every attempt asked to retry later.
+    pub propagate_retry_after: bool,
+    pub forwarded_headers: ForwardedHeaders,
+    /// The scheme clients reach the listener with, `https` when it
+    /// terminates TLS.
+    pub scheme: &'static str,
+    pub decision_records: DecisionRecords,
+    pub http10_compat: bool,
+    pub allowed_methods: AllowedMethods,
+    pub time_rules: TimeRules,
+    /// Send `X-Request-Start` and `X-Request-Queue-Ms` to the backends.
+    pub request_queue_time: bool,
+    /// Give up on the requests past the deadline told by their client, see
+    /// `deadline`.
+    pub deadline_propagation: bool,
+    /// Ask the backends for gzip, decompressing for clients that can't.
+    pub upstream_compression: bool,
+    pub upstream_decoding: UpstreamDecoding,
+    pub response_compression: Option<ResponseCompression>,
+    pub certificate_expiries: Arc<CertificateExpiries>,
+    /// Larger request bodies are refused with a 413 instead of being forwarded.
+    pub max_request_body_bytes: Option<usize>,
+    /// `Retry-After` of the 503 answered while no server is healthy.
+    pub retry_after_seconds: u64,
+    pub client_certificate_rules: ClientCertificateRules,
+    pub traffic_mirror: TrafficMirror,
+    pub via_headers: ViaHeaders,
+    pub location_rewrite: LocationRewrite,
+    /// How a streamed body going over `max_request_body_bytes` is answered.
+    pub body_limit_action: BodyLimitAction,
+    pub error_pages: Arc<ErrorPages>,
+    /// Answered while no server is alive instead of the 503.
+    pub fallback_response: Option<FallbackResponse>,
+    /// Print every request to stdout, see `dev_trace`.
+    pub dev_mode: bool,
+    pub routing_rules: Arc<RoutingRules>,
+    /// Budgets of the tenants told by `usage`, spent by the costs the backends report.
+    pub cost_budgets: Arc<CostBudgets>,
+    pub host_header: HostHeader,
+    pub path_rules: PathRules,
+    pub request_transforms: RequestTransforms,
+    pub range_requests: RangeRequests,
+    pub connection_recycling: ConnectionRecycling,
+    /// Requests in flight to each backend, skipped once at their cap.
+    pub bulkheads: Arc<Bulkheads>,
+    /// Collapses identical GETs in flight into a single upstream request.
+    pub request_coalescing: Option<Arc<RequestCoalescing>>,
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// Whether the listener answers the admin API, which a single one does.
+    pub admin_api: bool,
+    /// Named pools of backends the requests are routed to, before falling
+    /// back to the target servers.
+    pub pools: Arc<SwappablePools>,
+    /// Tags the requests with the location of their client, before routing.
+    pub geo_ip: GeoIp,
+    /// Deadlines of the gRPC calls, told to the backends like those of the
+    /// clients, whether or not `deadline_propagation` is on.
+    pub grpc_timeouts: GrpcTimeouts,
+    /// Tags the requests with the A/B variant of their client, before routing.
+    pub experiment: Experiment,
+    /// Answered by `/admin/config`.
+    pub effective_config: Arc<EffectiveConfig>,
+}
+
+impl ServerState {
+    /// A state round-robining over `target_servers`, all assumed healthy,
+    /// with every optional feature off. Tests and benchmarks change the
+    /// fields they exercise from there.
+    pub fn new(
+        target_servers: Vec<String>,
+        http_client: Arc<dyn HttpClient + Send + Sync>,
+    ) -> Self {
+        let healthy_servers = Arc::new(RwLock::new(target_servers.clone()));
+
+        Self {
+            target_servers: Arc::new(RwLock::new(target_servers)),
+            probe_client: Arc::clone(&http_client),
+            http_client,
+            select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&healthy_servers))),
+            health_history: Arc::new(HealthHistory::new(10)),
+            healthy_servers,
+            metrics: Arc::new(Metrics::default()),
+            usage: Arc::new(UsageTracker::disabled()),
+            annotations: Arc::new(Annotations::default()),
+            admin_auth: AdminAuth::default(),
+            maintenance: Arc::new(Maintenance::default()),
+            retries: 0,
+            retry_policy: RetryPolicy::default(),
+            backend_backoffs: Arc::new(BackendBackoffs::default()),
+            propagate_retry_after: false,
+            forwarded_headers: ForwardedHeaders::default(),
+            scheme: "http",
+            decision_records: DecisionRecords::default(),
+            http10_compat: false,
+            allowed_methods: AllowedMethods::default(),
+            time_rules: TimeRules::default(),
+            request_queue_time: false,
+            deadline_propagation: false,
+            upstream_compression: false,
+            upstream_decoding: UpstreamDecoding::default(),
+            response_compression: None,
+            certificate_expiries: Arc::new(CertificateExpiries::default()),
+            max_request_body_bytes: None,
+            retry_after_seconds: 10,
+            client_certificate_rules: ClientCertificateRules::default(),
+            traffic_mirror: TrafficMirror::default(),
+            via_headers: ViaHeaders::default(),
+            location_rewrite: LocationRewrite::default(),
+            body_limit_action: BodyLimitAction::default(),
+            error_pages: Arc::new(ErrorPages::default()),
+            fallback_response: None,
+            dev_mode: false,
+            routing_rules: Arc::new(RoutingRules::default()),
+            cost_budgets: Arc::new(CostBudgets::default()),
+            host_header: HostHeader::default(),
+            path_rules: PathRules::default(),
+            request_transforms: RequestTransforms::default(),
+            range_requests: RangeRequests::default(),
+            connection_recycling: ConnectionRecycling::default(),
+            bulkheads: Arc::new(Bulkheads::default()),
+            request_coalescing: None,
+            response_cache: None,
+            admin_api: true,
+            pools: Arc::new(SwappablePools::default()),
+            geo_ip: GeoIp::default(),
+            grpc_timeouts: GrpcTimeouts::default(),
+            experiment: Experiment::default(),
+            effective_config: Arc::new(EffectiveConfig::default()),
+        }
+    }
+
+    pub fn is_target_server(&self, server: &str) -> bool {
+        self.target_servers
+            .read()
+            .is_ok_and(|servers| servers.iter().any(|target| target == server))
+    }
 }
 
 async fn health_endpoint() -> impl IntoResponse {
@@ -42,71 +298,693 @@ async fn health_endpoint() -> impl IntoResponse {
     "PONG"
 }
 
-async fn proxy_endpoint(
-    State(state): State<ServerState>,
+async fn proxy_endpoint(State(state): State<ServerState>, request: AxumRequest<Body>) -> Response {
+    let tenant = state.usage.tenant_of(request.headers());
+    let ingress_bytes = Arc::new(AtomicU64::new(0));
+    let request = match tenant {
+        Some(_) => {
+            request.map(|body| Body::new(CountedBody::new(body, Arc::clone(&ingress_bytes))))
+        }
+        None => request,
+    };
+    let http10 = state.http10_compat && request.version() == Version::HTTP_10;
+
+    let verdict = match &tenant {
+        Some(tenant) => state.cost_budgets.check(tenant).await,
+        None => CostVerdict::Allow,
+    };
+
+    let mut response = match verdict {
+        CostVerdict::Allow => forward(&state, request).await,
+        CostVerdict::Throttle(delay) => {
+            tokio::time::sleep(delay).await;
+            forward(&state, request).await
+        }
+        CostVerdict::Reject {
+            retry_after_seconds,
+        } => {
+            warn!("Rejecting a request of a tenant over its cost budget");
+            state.error_pages.apply(
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+                )
+                    .into_response(),
+            )
+        }
+    };
+
+    if let Some(tenant) = &tenant {
+        state.cost_budgets.record(tenant, response.headers()).await;
+    }
+
+    if http10 {
+        response = http10_compat::buffer_response(response).await;
+    }
+
+    state.metrics.record_response(response.status().as_u16());
+
+    // The usage is recorded once the response is sent, the bytes of both
+    // bodies counted as they went through.
+    if let Some(tenant) = tenant {
+        let usage = Arc::clone(&state.usage);
+        let metrics = Arc::clone(&state.metrics);
+
+        response = response.map(|body| {
+            Body::new(CountedBody::new(body, Arc::new(AtomicU64::new(0))).on_drop(
+                move |egress_bytes| {
+                    let ingress_bytes = ingress_bytes.load(Ordering::Relaxed);
+
+                    usage.record(&tenant, ingress_bytes, egress_bytes);
+                    metrics.record_tenant_usage(&tenant, ingress_bytes, egress_bytes);
+                },
+            ))
+        });
+    }
+
+    response
+}
+
+/// Bodies up to this size are buffered so the request can be retried.
+const MAX_REPLAYABLE_BODY_BYTES: u64 = 1024 * 1024;
+
+async fn replayable_body(body: Body) -> Result<HttpClientBody, HttpClientError> {
+    let body = HttpClientBody::from(body);
+
+    match body.exact_size() {
+        Some(size) if size <= MAX_REPLAYABLE_BODY_BYTES => Ok(body.collect().await?.into()),
+        _ => Ok(body),
+    }
+}
+
+fn should_retry(result: &Result<HttpClientResponse, HttpClientError>) -> bool {
+    match result {
+        Ok(response) => {
+            response.status == StatusCode::SERVICE_UNAVAILABLE.as_u16()
+                || retry_after::asks_to_retry_later(response)
+        }
+        Err(error) => matches!(error, HttpClientError::Network(_)),
+    }
+}
+
+fn retry_reason(result: &Result<HttpClientResponse, HttpClientError>) -> String {
+    match result {
+        Ok(response) => format!("status {}", response.status),
+        Err(error) => error.to_string(),
+    }
+}
+
+/// How a request is routed once authorized, decided before it is sent.
+struct Route<'a> {
+    pool: Option<&'a Pool>,
+    client_certificate: Option<Arc<ClientCertificate>>,
+    client: Option<IpAddr>,
+    /// What the cached and coalesced responses are shared by, besides the
+    /// request itself: the pool, the client identity, the geo and experiment
+    /// tags and the rules the request matched. Empty when neither is on.
+    key: String,
+}
+
+impl<'a> Route<'a> {
+    fn new(
+        state: &ServerState,
+        parts: &Parts,
+        pool: Option<&'a Pool>,
+        client_certificate: Option<Arc<ClientCertificate>>,
+        client: Option<IpAddr>,
+    ) -> Self {
+        let key = match state.response_cache.is_some() || state.request_coalescing.is_some() {
+            true => route_key(state, parts, pool, client_certificate.as_deref()),
+            false => String::new(),
+        };
+
+        Self {
+            pool,
+            client_certificate,
+            client,
+            key,
+        }
+    }
+}
+
+fn route_key(
+    state: &ServerState,
+    parts: &Parts,
+    pool: Option<&Pool>,
+    client_certificate: Option<&ClientCertificate>,
+) -> String {
+    let mut key = format!("pool:{}", pool.map_or("", |pool| pool.name.as_str()));
+
+    if let Some(certificate) = client_certificate {
+        key.push_str(&format!(
+            "\ncertificate:{:?} {:?} {:?}",
+            certificate.common_name,
+            certificate.subject_alt_names,
+            certificate.organizational_units
+        ));
+    }
+
+    for name in [X_GEO_COUNTRY, X_GEO_CONTINENT, X_EXPERIMENT_VARIANT] {
+        for value in parts.headers.get_all(&name) {
+            key.push_str(&format!(
+                "\n{}:{}",
+                name,
+                String::from_utf8_lossy(value.as_bytes())
+            ));
+        }
+    }
+
+    if let Some(rule) = state.routing_rules.rules.iter().position(|rule| {
+        rule.expression
+            .matches(&parts.method, &parts.uri, &parts.headers)
+    }) {
+        key.push_str(&format!("\nrouting rule:{}", rule));
+    }
+
+    let target_servers = match pool {
+        Some(pool) => pool.target_servers.to_vec(),
+        None => state
+            .target_servers
+            .read()
+            .map(|servers| servers.clone())
+            .unwrap_or_default(),
+    };
+    let time_rule_exclusions = state
+        .time_rules
+        .excluded_servers(&parts.headers, &target_servers);
+    if !time_rule_exclusions.is_empty() {
+        key.push_str(&format!("\ntime rule:{}", time_rule_exclusions.join(",")));
+    }
+
+    key
+}
+
+async fn forward_cached(
+    state: &ServerState,
+    request: AxumRequest<Body>,
+    route: &Route<'_>,
+) -> Response {
+    let Some((response_cache, base_key)) = state.response_cache.as_ref().and_then(|cache| {
+        cache
+            .base_key(&request, state.scheme, &route.key)
+            .map(|base_key| (cache, base_key))
+    }) else {
+        return forward_coalesced(state, request, route).await;
+    };
+
+    if let Some(response) = response_cache.get(&base_key, request.headers()) {
+        return response;
+    }
+
+    let request_headers = request.headers().clone();
+    let response = forward_coalesced(state, request, route).await;
+
+    response_cache
+        .store(base_key, &request_headers, response)
+        .await
+}
+
+async fn forward_coalesced(
+    state: &ServerState,
     request: AxumRequest<Body>,
-) -> impl IntoResponse {
+    route: &Route<'_>,
+) -> Response {
+    let Some((request_coalescing, key)) = state
+        .request_coalescing
+        .as_ref()
+        .zip(RequestCoalescing::key(&request, &route.key))
+    else {
+        return send(state, request, route).await;
+    };
+
+    request_coalescing
+        .coalesce(key, request, |request| send(state, request, route))
+        .await
+}
+
+fn method_not_allowed(state: &ServerState, allowed_methods: &AllowedMethods) -> Response {
+    state.error_pages.apply(
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, allowed_methods.allow_header())],
+        )
+            .into_response(),
+    )
+}
+
+async fn forward(state: &ServerState, request: AxumRequest<Body>) -> Response {
+    let (mut parts, body) = request.into_parts();
+
+    if !state.allowed_methods.allows(&parts.method) {
+        return method_not_allowed(state, &state.allowed_methods);
+    }
+
+    let client_certificate = parts.extensions.get::<Arc<ClientCertificate>>().cloned();
+    if !state
+        .client_certificate_rules
+        .authorizes(client_certificate.as_deref())
+    {
+        return state
+            .error_pages
+            .apply(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let client = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    state.geo_ip.tag(&mut parts.headers, client);
+    state.experiment.assign(&mut parts.headers, client);
+
+    // Kept for the whole request, even if the pools are swapped meanwhile.
+    let pools = state.pools.current();
+    if let Some(allowed_methods) =
+        pools.disallowed_method(&parts.method, &parts.uri, &parts.headers)
+    {
+        return method_not_allowed(state, allowed_methods);
+    }
+
+    let pool = pools.route(&parts.method, &parts.uri, &parts.headers);
+    if pool.is_none()
+        && let Some(status) = pools.unmatched_status()
+    {
+        return state.error_pages.apply(status.into_response());
+    }
+    if let Some(pool) = pool
+        && !pool.allowed_methods.allows(&parts.method)
+    {
+        return method_not_allowed(state, &pool.allowed_methods);
+    }
+
+    // Cached and coalesced responses only go to the requests authorized and
+    // routed the same way.
+    let route = Route::new(state, &parts, pool, client_certificate, client);
+    forward_cached(state, AxumRequest::from_parts(parts, body), &route).await
+}
+
+/// Sends the request along its route, to one of the backends left.
+async fn send(state: &ServerState, request: AxumRequest<Body>, route: &Route<'_>) -> Response {
     let (parts, body) = request.into_parts();
+    let (pool, client) = (route.pool, route.client);
+    let client_certificate = route.client_certificate.clone();
 
-    let server = match state.select_server.execute(SelectServerRequest {}) {
-        Ok(selected_server) => selected_server.server,
-        Err(error) => {
-            error!("No one is alive: {}", error);
-            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    // Bodies of unknown size can only be found to be too large midway.
+    let (body, body_overflow) = match state.max_request_body_bytes {
+        Some(_) if body.size_hint().exact().is_none() => {
+            let (body, body_overflow) = BodyOverflow::watch(body);
+            (body, Some(body_overflow))
+        }
+        _ => (body, None),
+    };
+    let http_client: &dyn HttpClient = match pool.and_then(|pool| pool.http_client.as_ref()) {
+        Some(http_client) => http_client.as_ref(),
+        None => state.http_client.as_ref(),
+    };
+    let main_target_servers;
+    let (target_servers, healthy_servers, select_server) = match pool {
+        Some(pool) => (
+            pool.target_servers.as_slice(),
+            &pool.healthy_servers,
+            &pool.select_server,
+        ),
+        None => {
+            main_target_servers = state
+                .target_servers
+                .read()
+                .map(|servers| servers.clone())
+                .unwrap_or_default();
+            (
+                main_target_servers.as_slice(),
+                &state.healthy_servers,
+                &state.select_server,
+            )
         }
     };
 
-    let url = format!("{}{}", server, parts.uri.path().to_string());
+    // Forwarded verbatim, keeping the query string and percent-encoding.
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+    let (path_and_query, path_rule_exclusions) =
+        state.path_rules.route(path_and_query, target_servers);
+    let path_and_query = state.request_transforms.apply_query(path_and_query);
+
+    let accepted_at = parts.extensions.get::<AcceptedAt>().copied();
+    let received_at = accepted_at.map_or_else(Instant::now, |accepted_at| accepted_at.instant);
+    let deadline = state
+        .deadline_propagation
+        .then(|| Deadline::from_headers(&parts.headers, received_at))
+        .flatten();
+    let deadline = match state.grpc_timeouts.timeout(&parts.uri, &parts.headers) {
+        Some(timeout) => {
+            let grpc_deadline = Deadline::grpc_after(timeout, received_at);
+            Some(deadline.map_or(grpc_deadline, |deadline| deadline.earliest(grpc_deadline)))
+        }
+        None => deadline,
+    };
+    let grpc_call = grpc::call(&parts.uri, &parts.headers).is_some();
+    let accepted_at = accepted_at.filter(|_| state.request_queue_time);
 
-    let headers = parts.headers.into();
+    let retries = match state.retry_policy.allows(&parts.method, &parts.headers) {
+        true => state.retries,
+        false => 0,
+    };
 
-    let body = match to_bytes(body, usize::MAX).await {
-        Ok(bytes) => bytes,
-        Err(error) => {
-            error!("Failed body conversion into bytes: {}", error);
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    let mut headers = parts.headers;
+    state.range_requests.apply_request(&mut headers);
+    // A range of a gzip body can't be decompressed on its own.
+    let range_request = range_requests::is_range_request(&headers);
+    let ask_for_gzip = state.upstream_compression
+        && !upstream_compression::accepts_gzip(&headers)
+        && !range_request;
+    if ask_for_gzip {
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static(upstream_compression::GZIP),
+        );
+    }
+    let decompress =
+        ask_for_gzip || (state.upstream_decoding == UpstreamDecoding::Recompress && !range_request);
+    if state.http10_compat && parts.version == Version::HTTP_10 {
+        http10_compat::synthesize_host(&mut headers, &parts.uri);
+    }
+    state
+        .forwarded_headers
+        .apply(&mut headers, client, state.scheme);
+    let location_rewrite = pool
+        .and_then(|pool| pool.location_rewrite)
+        .unwrap_or(state.location_rewrite);
+    let external_origin = location_rewrite.external_origin(&headers);
+    state
+        .client_certificate_rules
+        .forward(&mut headers, client_certificate.as_deref());
+    let maintenance_exclusions = state.maintenance.excluded_servers(target_servers);
+    let time_rule_exclusions = state.time_rules.excluded_servers(&headers, target_servers);
+    let certificate_exclusions = state
+        .client_certificate_rules
+        .excluded_servers(client_certificate.as_deref(), target_servers);
+    let routing_rule = state
+        .routing_rules
+        .matching(&parts.method, &parts.uri, &headers);
+    let routing_rule_exclusions = routing_rule
+        .map(|rule| rule.excluded_servers(target_servers))
+        .unwrap_or_default();
+    state.request_transforms.apply_headers(&mut headers);
+    if let Some(rule) = routing_rule {
+        rule.transform_headers(Direction::Request, &mut headers);
+    }
+    let mut headers: RequestHeaders = headers.into();
+
+    let method = RequestMethod::from(&parts.method);
+
+    let mirror = state.traffic_mirror.sample();
+
+    let mut body = if retries > 0 || mirror.is_some() {
+        match replayable_body(body).await {
+            Ok(body) => body,
+            Err(error) => {
+                error!("Failed to read the request body: {}", error);
+                return state
+                    .error_pages
+                    .apply(StatusCode::BAD_REQUEST.into_response());
+            }
         }
+    } else {
+        body.into()
     };
 
-    let method: RequestMethod = match (&parts.method).try_into() {
-        Ok(method) => method,
-        Err(error) => {
-            error!("{}", error.to_string());
-            return error.into_response();
+    // Streamed bodies can't be copied, so those requests aren't mirrored.
+    if let Some(mirror) = mirror
+        && let Some(mirror_body) = body.try_clone()
+    {
+        state.traffic_mirror.send(
+            Arc::clone(&state.http_client),
+            HttpClientRequest {
+                method: method.clone(),
+                url: format!("{}{}", mirror, path_and_query),
+                headers: headers.clone(),
+                body: mirror_body,
+                timeout: None,
+            },
+            &state.metrics,
+        );
+    }
+
+    let mut retries_left = retries;
+    let mut decision = state.decision_records.sample();
+
+    if let Some(decision) = &mut decision
+        && let Ok(healthy_servers) = healthy_servers.read()
+    {
+        decision.consider(target_servers, &healthy_servers);
+
+        for server in &maintenance_exclusions {
+            decision.exclude(server.clone(), "maintenance".to_string());
+        }
+
+        for server in &time_rule_exclusions {
+            decision.exclude(server.clone(), "time rule".to_string());
+        }
+
+        for server in &certificate_exclusions {
+            decision.exclude(server.clone(), "client certificate".to_string());
+        }
+
+        for server in &routing_rule_exclusions {
+            decision.exclude(server.clone(), "routing rule".to_string());
+        }
+
+        for server in &path_rule_exclusions {
+            decision.exclude(server.clone(), "path rule".to_string());
+        }
+    }
+
+    // Steered away from, unless nobody else is left.
+    let mut backoff_exclusions = match state.retries {
+        0 => Vec::new(),
+        _ => state.backend_backoffs.backing_off(),
+    };
+    if let Some(decision) = &mut decision {
+        for server in &backoff_exclusions {
+            decision.exclude(server.clone(), "retry-after".to_string());
         }
+    }
+
+    let mut select_server_request = SelectServerRequest {
+        excluded_servers: [
+            maintenance_exclusions,
+            time_rule_exclusions,
+            certificate_exclusions,
+            routing_rule_exclusions,
+            path_rule_exclusions,
+        ]
+        .concat(),
     };
 
-    let result = state
-        .http_client
-        .execute(HttpClientRequest {
-            method,
-            headers,
-            body,
+    let mut last_attempt = None;
+    let mut failed_attempts = FailedAttempts::default();
+    let mut permit = None;
+    let mut shortest_retry_after = None;
+
+    let (server, result) = loop {
+        let mut attempt_request = select_server_request.clone();
+        attempt_request
+            .excluded_servers
+            .extend(backoff_exclusions.iter().cloned());
+
+        let server = match select_server.execute(attempt_request) {
+            Ok(selected_server) => selected_server.server,
+            Err(_) if !backoff_exclusions.is_empty() => {
+                backoff_exclusions.clear();
+                continue;
+            }
+            Err(error) => match last_attempt.take() {
+                // Every server left was tried: answer with how the last one failed.
+                Some(last_attempt) => break last_attempt,
+                None => {
+                    error!("No one is alive: {}", error);
+                    if let Some(fallback_response) = &state.fallback_response {
+                        return fallback_response.response(state.retry_after_seconds);
+                    }
+                    return state.error_pages.apply(
+                        (
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            [(header::RETRY_AFTER, state.retry_after_seconds.to_string())],
+                        )
+                            .into_response(),
+                    );
+                }
+            },
+        };
+
+        let Some(server_permit) = state.bulkheads.acquire(&server) else {
+            warn!("{} has too many requests in flight, skipping it", server);
+
+            if let Some(decision) = &mut decision {
+                decision.exclude(server.clone(), "bulkhead".to_string());
+            }
+
+            select_server_request.excluded_servers.push(server);
+            continue;
+        };
+        permit = Some(server_permit);
+
+        let mut url = String::with_capacity(server.len() + path_and_query.len());
+        url.push_str(&server);
+        url.push_str(&path_and_query);
+
+        // A streamed body is sent once and cannot be retried.
+        let replay = body.try_clone();
+        if replay.is_none() {
+            retries_left = 0;
+        }
+
+        // The last attempt takes the headers rather than a copy of them.
+        let mut attempt_headers = match retries_left {
+            0 => std::mem::take(&mut headers),
+            _ => headers.clone(),
+        };
+        if let Some(accepted_at) = &accepted_at {
+            accepted_at.stamp(&mut attempt_headers);
+        }
+        pool.and_then(|pool| pool.host_header)
+            .unwrap_or(state.host_header)
+            .apply(&mut attempt_headers, &server);
+        if let Some(deadline) = &deadline {
+            deadline.stamp(&mut attempt_headers);
+        }
+
+        let started_at = Instant::now();
+        let execution = http_client.execute(HttpClientRequest {
+            method: method.clone(),
+            headers: attempt_headers,
+            body: std::mem::take(&mut body),
             url,
-        })
-        .await;
+            timeout: routing_rule.and_then(|rule| rule.timeout),
+        });
+        // Past the deadline the client has given up, the backend too.
+        let result = match deadline.map(|deadline| deadline.remaining()) {
+            Some(Duration::ZERO) => Err(HttpClientError::Timeout),
+            Some(remaining) => tokio::time::timeout(remaining, execution)
+                .await
+                .unwrap_or(Err(HttpClientError::Timeout)),
+            None => execution.await,
+        };
 
-    match result {
+        if should_retry(&result) {
+            failed_attempts.record(server.clone(), &result, started_at.elapsed());
+        }
+
+        if state.retries > 0
+            && should_retry(&result)
+            && let Ok(response) = &result
+            && let Some(retry_after) = retry_after::retry_after(response)
+        {
+            state.backend_backoffs.back_off(&server, retry_after);
+            shortest_retry_after = Some(
+                shortest_retry_after
+                    .map_or(retry_after, |shortest: Duration| shortest.min(retry_after)),
+            );
+        }
+
+        if retries_left > 0 && should_retry(&result) {
+            warn!("Request to {} failed, retrying on another server", server);
+            state.metrics.increment(RETRIES_TOTAL);
+
+            if let Some(decision) = &mut decision {
+                decision.retry(server.clone(), retry_reason(&result));
+            }
+
+            select_server_request.excluded_servers.push(server.clone());
+            retries_left -= 1;
+            permit = None;
+            body = replay.unwrap_or_default();
+            last_attempt = Some((server, result));
+            continue;
+        }
+
+        break (server, result);
+    };
+
+    if let Some(body_overflow) = body_overflow
+        && body_overflow.overflowed()
+    {
+        return state
+            .error_pages
+            .apply(body_overflow.reject(state.body_limit_action).await);
+    }
+
+    let aggregate = failed_attempts.len() > 1 && should_retry(&result);
+    let served = result.is_ok() && !aggregate;
+
+    let mut response = match result {
+        // gRPC clients expect the status of the call, not an HTTP error.
+        Err(HttpClientError::Timeout)
+            if grpc_call && deadline.is_some_and(|deadline| deadline.remaining().is_zero()) =>
+        {
+            grpc_deadline_exceeded()
+        }
+        // Once several servers failed, each failure is reported.
+        _ if aggregate => {
+            let mut response = failed_attempts.into_response();
+            if state.propagate_retry_after
+                && let Some(retry_after) = shortest_retry_after
+            {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, retry_after::header_value(retry_after));
+            }
+
+            state.error_pages.apply(response)
+        }
+        Ok(http_client_response) if decompress => {
+            upstream_compression::decompress(http_client_response.into()).await
+        }
         Ok(http_client_response) => http_client_response.into(),
         Err(error) => {
             let (status, error) = error.into();
             error!("Error: {} Status: {}", error, status);
 
-            (status, error).into_response()
+            state.error_pages.apply((status, error).into_response())
+        }
+    };
+
+    if served {
+        location_rewrite.apply(&mut response, &server, external_origin.as_deref());
+        state.range_requests.apply_response(response.headers_mut());
+
+        if let Some(deadline) = &deadline {
+            response = deadline.enforce(response, grpc_call);
         }
     }
+    state
+        .via_headers
+        .apply(response.headers_mut(), served.then_some(server.as_str()));
+    if let Some(rule) = routing_rule {
+        rule.transform_headers(Direction::Response, response.headers_mut());
+    }
+
+    if let Some(mut decision) = decision {
+        decision.selected = Some(server);
+        state.decision_records.finish(decision, &mut response);
+    }
+
+    match permit {
+        Some(permit) => permit.hold_until_sent(response),
+        None => response,
+    }
 }
 
 impl From<HttpClientResponse> for Response<Body> {
     fn from(value: HttpClientResponse) -> Self {
-        let mut response = Response::builder()
-            .status(StatusCode::from_u16(value.status).unwrap_or(StatusCode::OK));
+        let mut response = Response::new(Body::from(value.body));
 
-        for (k, v) in value.headers.iter() {
-            response = response.header(k, v);
-        }
+        *response.status_mut() = StatusCode::from_u16(value.status).unwrap_or(StatusCode::OK);
+        *response.headers_mut() = value.headers.into();
 
-        response.body(Body::from(value.body)).unwrap()
+        response
     }
 }
 
@@ -121,8 +999,19 @@ impl From<HttpClientError> for (StatusCode, &str) {
 }
 
 pub fn router(server_state: ServerState) -> Router {
-    Router::new()
+    let response_compression = server_state.response_compression.clone();
+    let max_request_body_bytes = server_state.max_request_body_bytes;
+    let dev_mode = server_state.dev_mode;
+    let connection_recycling = server_state.connection_recycling;
+
+    let admin_router = match server_state.admin_api {
+        true => admin_router(),
+        false => Router::new(),
+    };
+
+    let router = Router::new()
         .route("/health", get(health_endpoint))
+        .merge(admin_router)
         .route("/{*path}", any(proxy_endpoint))
         .route("/", any(proxy_endpoint))
         .with_state(server_state)
@@ -149,46 +1038,131 @@ pub fn router(server_state: ServerState) -> Router {
             X_REQUEST_ID,
             LoadBalancerRequestId::default(),
         ))
+        .layer(middleware::from_fn(request_age::accept))
+        .layer(middleware::from_fn_with_state(
+            connection_recycling,
+            connection_recycling::recycle,
+        ))
+        .layer(middleware::from_fn(downstream_timeouts::track))
+        .layer(middleware::from_fn(client_connection::expose));
+
+    let router = match max_request_body_bytes {
+        Some(max_request_body_bytes) => {
+            router.layer(RequestBodyLimitLayer::new(max_request_body_bytes))
+        }
+        None => router,
+    };
+
+    let router = match response_compression {
+        Some(response_compression) => router.layer(response_compression.layer()),
+        None => router,
+    };
+
+    if dev_mode {
+        router.layer(middleware::from_fn(dev_trace::trace))
+    } else {
+        router
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use crate::admin::admin_auth::AdminAuth;
+    use crate::allowed_methods::AllowedMethods;
+    use crate::background_health_checker::health_history::{HealthHistory, ProbeRecord};
+
+    use crate::bulkhead::Bulkheads;
+
+    use crate::client_certificate::{
+        CertificateAttribute, ClientCertificate, ClientCertificateRules,
+    };
+    use crate::client_connection::ClientConnection;
+
+    use crate::cost_budget::{CostBudgetAction, CostBudgets, X_REQUEST_COST};
+    use crate::deadline::{GRPC_TIMEOUT, X_REQUEST_DEADLINE};
+    use crate::decision_record::{DecisionRecords, X_LB_DECISION};
+    use crate::error_pages::{ErrorPage, ErrorPages, FallbackResponse};
+
+    use crate::geo_ip::geo_ip::GeoIp;
+    use crate::geo_ip::geo_locator::{GeoLocation, MockGeoLocator};
+    use crate::grpc::GrpcTimeouts;
+    use crate::host_header::HostHeader;
     use crate::http_client::error::Error as HttpClientError;
-    use crate::http_client::http_client::MockHttpClient;
-    use crate::http_client::request::{RequestHeaders, RequestMethod};
+    use crate::http_client::http_client::{HttpClient, MockHttpClient};
+    use crate::http_client::request::{
+        Request as HttpClientRequest, RequestHeaders, RequestMethod,
+    };
     use crate::http_client::response::Response as HttpClientResponse;
+    use crate::location_rewrite::LocationRewrite;
+    use crate::metrics::metrics::{Metrics, RETRIES_TOTAL};
+    use crate::metrics::usage_tracker::{UsageTracker, pseudonym};
+    use crate::path_rules::PathRules;
+    use crate::pools::pool::Pool;
+    use crate::pools::pools::Pools;
+    use crate::pools::swappable_pools::SwappablePools;
+    use crate::range_requests::RangeRequests;
+    use crate::request_transforms::RequestTransforms;
+    use crate::response_cache::{ResponseCache, X_CACHE};
+    use crate::response_compression::ResponseCompression;
+
+    use crate::retry_policy::IDEMPOTENCY_KEY;
+    use crate::routing_rules::routing_rules::RoutingRules;
     use crate::select_server::error::Error as SelectServerError;
     use crate::select_server::response::Response as SelectServerResponse;
     use crate::select_server::select_server::MockSelectServer;
-    use crate::{ServerState, X_REQUEST_ID, router};
+    use crate::state_store::memory_state_store::MemoryStateStore;
+
+    use crate::traffic_mirror::TrafficMirror;
+    use crate::upstream_compression::UpstreamDecoding;
+    use crate::via_headers::{VIA, ViaHeaders, X_SERVED_BY};
+    use crate::{RoundRobinSelectServer, ServerState, X_REQUEST_ID, router};
     use axum::body::{Body, Bytes};
+    use axum::extract::ConnectInfo;
     use axum::http::{Method, Request, StatusCode};
     use axum::response::Response as AxumResponse;
-    use http::{HeaderMap, HeaderValue};
+    use http::{HeaderMap, HeaderName, HeaderValue, Version, header};
     use mockall::predicate::*;
-    use std::sync::Arc;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, RwLock};
+    use std::time::{Duration, Instant};
     use tower::ServiceExt;
 
     fn target_servers() -> Vec<String> {
         vec![String::from("http://target.com")]
     }
 
-    fn build_router_with_mocks(
+    fn build_server_state_with_mocks(
         target_servers: Vec<String>,
         setup_http_client_mock: impl FnOnce(&mut MockHttpClient),
         setup_select_server_mock: impl FnOnce(&mut MockSelectServer, Vec<String>),
-    ) -> axum::Router {
+    ) -> ServerState {
         let mut http_client_mock = MockHttpClient::default();
         setup_http_client_mock(&mut http_client_mock);
 
         let mut select_server_mock = MockSelectServer::default();
-        setup_select_server_mock(&mut select_server_mock, target_servers);
+        setup_select_server_mock(&mut select_server_mock, target_servers.clone());
 
-        router(ServerState {
-            http_client: Arc::new(http_client_mock),
+        ServerState {
             select_server: Arc::new(select_server_mock),
-        })
+            admin_auth: AdminAuth::new(Some(ADMIN_TOKEN.to_string())),
+            ..ServerState::new(target_servers, Arc::new(http_client_mock))
+        }
+    }
+
+    const ADMIN_TOKEN: &str = "s3cr3t";
+
+    fn build_router_with_mocks(
+        target_servers: Vec<String>,
+        setup_http_client_mock: impl FnOnce(&mut MockHttpClient),
+        setup_select_server_mock: impl FnOnce(&mut MockSelectServer, Vec<String>),
+    ) -> axum::Router {
+        router(build_server_state_with_mocks(
+            target_servers,
+            setup_http_client_mock,
+            setup_select_server_mock,
+        ))
     }
 
     fn build_success_http_client_mock() -> impl FnOnce(&mut MockHttpClient) {
@@ -197,14 +1171,14 @@ mod tests {
                 Ok(HttpClientResponse {
                     status: 200,
                     headers: RequestHeaders::default(),
-                    body: Bytes::from("OK"),
+                    body: Bytes::from("OK").into(),
                 })
             });
         }
     }
     fn first_one_select_server_mock() -> impl FnOnce(&mut MockSelectServer, Vec<String>) {
         |select_server_mock, target_servers| {
-            let first_server = target_servers.clone().get(0).unwrap().clone();
+            let first_server = target_servers.first().unwrap().clone();
 
             select_server_mock.expect_execute().returning(move |_| {
                 Ok(SelectServerResponse {
@@ -252,14 +1226,14 @@ mod tests {
                     .withf(move |req| {
                         req.method == RequestMethod::Get
                             && req.url == "http://target.com/"
-                            && req.body == Bytes::new()
+                            && req.body.is_empty()
                     })
                     .times(1)
                     .returning(|_| {
                         Ok(HttpClientResponse {
                             status: 200,
                             headers: RequestHeaders::default(),
-                            body: Bytes::from("Success"),
+                            body: Bytes::from("Success").into(),
                         })
                     });
             },
@@ -294,13 +1268,13 @@ mod tests {
                     .withf(move |req| {
                         req.method == RequestMethod::Get
                             && req.url == "http://target.com/"
-                            && req.body == Bytes::new()
+                            && req.body.is_empty()
                     })
                     .returning(|_| {
                         Ok(HttpClientResponse {
                             status: 201,
                             headers: RequestHeaders::default(),
-                            body: Bytes::from("Created"),
+                            body: Bytes::from("Created").into(),
                         })
                     });
             },
@@ -324,17 +1298,23 @@ mod tests {
                     .withf(move |req| {
                         req.method == RequestMethod::Get
                             && req.url == "http://target.com/"
-                            && req.body == Bytes::new()
+                            && req.body.is_empty()
                     })
                     .returning(|_| {
                         let mut headers = RequestHeaders::default();
-                        headers.insert("X-Custom-Header".to_string(), "custom-value".to_string());
-                        headers.insert("Content-Type".to_string(), "application/json".to_string());
+                        headers.insert(
+                            HeaderName::from_static("x-custom-header"),
+                            HeaderValue::from_static("custom-value"),
+                        );
+                        headers.insert(
+                            header::CONTENT_TYPE,
+                            HeaderValue::from_static("application/json"),
+                        );
 
                         Ok(HttpClientResponse {
                             status: 200,
                             headers,
-                            body: Bytes::from("{}"),
+                            body: Bytes::from("{}").into(),
                         })
                     });
             },
@@ -357,24 +1337,20 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_preserves_response_body() {
-        let expected_body = r#"{"data": "test"}"#;
+    async fn proxy_endpoint_keeps_repeated_response_headers() {
         let router = build_router_with_mocks(
             target_servers(),
             |mock| {
-                mock.expect_execute()
-                    .withf(move |req| {
-                        req.method == RequestMethod::Get
-                            && req.url == "http://target.com/"
-                            && req.body == Bytes::new()
+                mock.expect_execute().returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::from([
+                            (header::SET_COOKIE, HeaderValue::from_static("session=1")),
+                            (header::SET_COOKIE, HeaderValue::from_static("theme=dark")),
+                        ]),
+                        body: Bytes::new().into(),
                     })
-                    .returning(move |_| {
-                        Ok(HttpClientResponse {
-                            status: 200,
-                            headers: RequestHeaders::default(),
-                            body: Bytes::from(expected_body),
-                        })
-                    });
+                });
             },
             first_one_select_server_mock(),
         );
@@ -384,70 +1360,222 @@ mod tests {
             .await
             .unwrap();
 
-        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
-            .await
-            .unwrap();
-
-        assert_eq!(body_bytes, Bytes::from(expected_body));
+        assert_eq!(
+            response
+                .headers()
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["session=1", "theme=dark"]
+        );
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_sends_request_headers() {
-        let router = build_router_with_mocks(
+    async fn proxy_endpoint_rewrites_redirects_to_the_backend() {
+        let mut state = build_server_state_with_mocks(
             target_servers(),
             |mock| {
-                mock.expect_execute()
-                    .withf(move |req| {
-                        req.method == RequestMethod::Get
-                            && req.url == "http://target.com/"
-                            && req.body == Bytes::new()
-                            && req.headers.get("authorization") == Some(&"Bearer token".to_string())
-                            && req.headers.get("content-type")
-                                == Some(&"application/json".to_string())
+                mock.expect_execute().returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 302,
+                        headers: RequestHeaders::from([(
+                            header::LOCATION,
+                            HeaderValue::from_static("http://target.com/login"),
+                        )]),
+                        body: Bytes::new().into(),
                     })
-                    .returning(|_| {
-                        Ok(HttpClientResponse {
-                            status: 200,
-                            headers: RequestHeaders::default(),
-                            body: Bytes::new(),
-                        })
-                    });
+                });
             },
             first_one_select_server_mock(),
         );
+        state.location_rewrite = LocationRewrite { enabled: true };
 
-        let response = router
+        let response = router(state)
             .oneshot(
                 Request::builder()
-                    .uri("/")
-                    .header("Authorization", "Bearer token")
-                    .header("Content-Type", "application/json")
+                    .uri("/account")
+                    .header("host", "shop.example.com")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "http://shop.example.com/login"
+        );
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_sends_request_body() {
-        let request_body = r#"{"key": "value"}"#;
-        let router = build_router_with_mocks(
+    async fn proxy_endpoint_rewrites_the_redirects_of_the_pools_asking_for_it() {
+        let mut state = build_server_state_with_mocks(
             target_servers(),
             |mock| {
-                mock.expect_execute()
+                mock.expect_execute().returning(|req| {
+                    let location = match req.url.starts_with("http://shop.com/") {
+                        true => "http://shop.com/login",
+                        false => "http://target.com/login",
+                    };
+
+                    Ok(HttpClientResponse {
+                        status: 302,
+                        headers: RequestHeaders::from([(
+                            header::LOCATION,
+                            HeaderValue::from_static(location),
+                        )]),
+                        body: Bytes::new().into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.pools = single_backend_pools(
+            Pool {
+                location_rewrite: Some(LocationRewrite { enabled: true }),
+                ..single_backend_pool("shop", "http://shop.com")
+            },
+            "/shop/*=>shop",
+        );
+        let router = router(state);
+        let location = |uri: &'static str| {
+            let router = router.clone();
+            async move {
+                let response = router
+                    .oneshot(
+                        Request::builder()
+                            .uri(uri)
+                            .header("host", "shop.example.com")
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+
+                response.headers()[header::LOCATION].clone()
+            }
+        };
+
+        assert_eq!(
+            location("/shop/account").await,
+            "http://shop.example.com/login"
+        );
+        assert_eq!(location("/account").await, "http://target.com/login");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_tells_who_served_the_response() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        state.via_headers = ViaHeaders { served_by: true };
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[header::VIA], VIA);
+        assert_eq!(response.headers()[X_SERVED_BY], "http://target.com");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_preserves_response_body() {
+        let expected_body = r#"{"data": "test"}"#;
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(move |req| {
+                        req.method == RequestMethod::Get
+                            && req.url == "http://target.com/"
+                            && req.body.is_empty()
+                    })
+                    .returning(move |_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::from(expected_body).into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert_eq!(body_bytes, Bytes::from(expected_body));
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_sends_request_headers() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
                     .withf(move |req| {
-                        req.method == RequestMethod::Post
+                        req.method == RequestMethod::Get
                             && req.url == "http://target.com/"
-                            && req.body == Bytes::from(request_body)
+                            && req.body.is_empty()
+                            && req.headers.get("authorization")
+                                == Some(&HeaderValue::from_static("Bearer token"))
+                            && req.headers.get("content-type")
+                                == Some(&HeaderValue::from_static("application/json"))
                     })
                     .returning(|_| {
                         Ok(HttpClientResponse {
                             status: 200,
                             headers: RequestHeaders::default(),
-                            body: Bytes::new(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header("Authorization", "Bearer token")
+                    .header("Content-Type", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_sends_request_body() {
+        let request_body = r#"{"key": "value"}"#;
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.method == RequestMethod::Post && req.url == "http://target.com/"
+                    })
+                    .returning(move |req| {
+                        let body = futures::executor::block_on(req.body.collect()).unwrap();
+                        assert_eq!(body, request_body);
+
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
                         })
                     });
             },
@@ -479,7 +1607,7 @@ mod tests {
                         Ok(HttpClientResponse {
                             status: 200,
                             headers: RequestHeaders::default(),
-                            body: Bytes::new(),
+                            body: Bytes::new().into(),
                         })
                     });
             },
@@ -500,25 +1628,24 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_sends_request_method() {
-        for (method, method_str) in [
-            (Method::GET, "GET"),
-            (Method::POST, "POST"),
-            (Method::PUT, "PUT"),
-            (Method::DELETE, "DELETE"),
-            (Method::PATCH, "PATCH"),
+    async fn proxy_endpoint_forwards_path_and_query_verbatim() {
+        for uri in [
+            "/api/v1/users/42/orders",
+            "/search?q=wakanda&page=2",
+            "/files/hello%20world%2Fnested?name=a%26b&empty=",
+            "/?only=query",
         ] {
             let router = build_router_with_mocks(
                 target_servers(),
                 |mock| {
-                    let expected_method = method_str.to_string();
+                    let expected_url = format!("http://target.com{}", uri);
                     mock.expect_execute()
-                        .withf(move |req| req.method.to_string() == expected_method)
+                        .withf(move |req| req.url == expected_url)
                         .returning(|_| {
                             Ok(HttpClientResponse {
                                 status: 200,
                                 headers: RequestHeaders::default(),
-                                body: Bytes::new(),
+                                body: Bytes::new().into(),
                             })
                         });
                 },
@@ -526,38 +1653,82 @@ mod tests {
             );
 
             let response = router
-                .oneshot(
-                    Request::builder()
-                        .method(method)
-                        .uri("/")
-                        .body(Body::empty())
-                        .unwrap(),
-                )
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
                 .await
                 .unwrap();
 
-            assert_eq!(
-                response.status(),
-                StatusCode::OK,
-                "Failed for method {}",
-                method_str
-            );
+            assert_eq!(response.status(), StatusCode::OK, "Failed for uri {}", uri);
         }
     }
 
+    fn build_retrying_server_state(
+        retries: usize,
+        setup_http_client_mock: impl FnOnce(&mut MockHttpClient),
+    ) -> ServerState {
+        let servers = vec![
+            String::from("http://server1.com"),
+            String::from("http://server2.com"),
+        ];
+
+        let mut state =
+            build_server_state_with_mocks(servers.clone(), setup_http_client_mock, |_, _| {});
+        state.select_server = Arc::new(RoundRobinSelectServer::new(Arc::new(RwLock::new(servers))));
+        state.retries = retries;
+        state
+    }
+
     #[tokio::test]
-    async fn proxy_endpoint_handles_network_error() {
-        let router = build_router_with_mocks(
-            target_servers(),
-            |mock| {
-                mock.expect_execute()
-                    .returning(|_| Err(HttpClientError::Network("Connection refused".to_string())));
-            },
-            first_one_select_server_mock(),
-        );
+    async fn proxy_endpoint_retries_on_another_server() {
+        let state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server1.com/" && req.body == "hello")
+                .times(1)
+                .returning(|_| Err(HttpClientError::Network("Connection refused".to_string())));
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server2.com/" && req.body == "hello")
+                .times(1)
+                .returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::from("OK").into(),
+                    })
+                });
+        });
+        let metrics = Arc::clone(&state.metrics);
 
-        let response = router
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/")
+                    .header(IDEMPOTENCY_KEY, "8e03978e")
+                    .body(Body::from("hello"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(metrics.get(RETRIES_TOTAL), 1);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_does_not_retry_posts_without_idempotency_key() {
+        let state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute()
+                .times(1)
+                .returning(|_| Err(HttpClientError::Network("Connection refused".to_string())));
+        });
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/")
+                    .body(Body::from("hello"))
+                    .unwrap(),
+            )
             .await
             .unwrap();
 
@@ -565,139 +1736,623 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_handles_timeout_error() {
-        let router = build_router_with_mocks(
-            target_servers(),
-            |mock| {
-                mock.expect_execute()
-                    .returning(|_| Err(HttpClientError::Timeout));
-            },
-            first_one_select_server_mock(),
+    async fn proxy_endpoint_attaches_the_decision_record() {
+        let mut state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server1.com/")
+                .times(1)
+                .returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 503,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::new().into(),
+                    })
+                });
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server2.com/")
+                .times(1)
+                .returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::new().into(),
+                    })
+                });
+        });
+        state.decision_records = DecisionRecords {
+            policy: "round-robin",
+            sample_ratio: 1.0,
+            emit_header: true,
+        };
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers()[X_LB_DECISION],
+            r#"{"policy":"round-robin","candidates":["http://server1.com","http://server2.com"],"excluded":[{"server":"http://server1.com","reason":"status 503"}],"retries":1,"selected":"http://server2.com"}"#
         );
+    }
 
-        let response = router
+    #[tokio::test]
+    async fn proxy_endpoint_gives_up_when_retries_are_exhausted() {
+        let state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute().times(2).returning(|_| {
+                Ok(HttpClientResponse {
+                    status: 503,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::new().into(),
+                })
+            });
+        });
+
+        let response = router(state)
             .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_handles_invalid_request_error() {
-        let router = build_router_with_mocks(
-            target_servers(),
-            |mock| {
-                mock.expect_execute()
-                    .returning(|_| Err(HttpClientError::InvalidRequest("Bad URL".to_string())));
-            },
-            first_one_select_server_mock(),
-        );
+    async fn proxy_endpoint_reports_the_last_failure_once_every_server_was_tried() {
+        let state = build_retrying_server_state(3, |mock| {
+            mock.expect_execute()
+                .times(2)
+                .returning(|_| Err(HttpClientError::Network("Connection refused".to_string())));
+        });
 
-        let response = router
+        let response = router(state)
             .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_includes_request_id_in_response() {
-        let router = build_router_with_mocks(
-            target_servers(),
-            build_success_http_client_mock(),
-            first_one_select_server_mock(),
-        );
+    async fn proxy_endpoint_reports_every_failed_attempt() {
+        let state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute().times(2).returning(|req| {
+                if req.url.starts_with("http://server1.com") {
+                    return Err(HttpClientError::Network("Connection refused".to_string()));
+                }
 
-        let response = router
+                Ok(HttpClientResponse {
+                    status: 503,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::new().into(),
+                })
+            });
+        });
+
+        let response = router(state)
             .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
             .await
             .unwrap();
 
-        let request_id = response.headers().get(X_REQUEST_ID);
-        assert!(request_id.is_some());
-        assert!(!request_id.unwrap().to_str().unwrap().is_empty());
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let mut attempts = body["attempts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|attempt| (attempt["server"].clone(), attempt["error"].clone()))
+            .collect::<Vec<_>>();
+        attempts.sort_by_key(|(server, _)| server.to_string());
+
+        assert_eq!(
+            attempts,
+            vec![
+                ("http://server1.com".into(), "network".into()),
+                ("http://server2.com".into(), "unavailable".into()),
+            ]
+        );
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_propagates_existing_request_id() {
-        let custom_request_id = "custom-12345";
-        let router = build_router_with_mocks(
+    async fn proxy_endpoint_copies_sampled_requests_to_the_mirror() {
+        let (mirrored, mut mirrored_requests) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut state = build_server_state_with_mocks(
             target_servers(),
-            build_success_http_client_mock(),
+            move |mock| {
+                mock.expect_execute().times(2).returning(move |req| {
+                    if req.url.starts_with("http://mirror.com") {
+                        let _ = mirrored.send((req.url, req.body == "payload"));
+
+                        return Ok(HttpClientResponse {
+                            status: 500,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::from("mirror failure").into(),
+                        });
+                    }
+
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::from("OK").into(),
+                    })
+                });
+            },
             first_one_select_server_mock(),
         );
+        state.traffic_mirror = TrafficMirror::new(vec!["http://mirror.com".to_string()], 1.0);
 
-        let response = router
+        let response = router(state)
             .oneshot(
                 Request::builder()
-                    .uri("/")
-                    .header(X_REQUEST_ID.as_str(), custom_request_id)
-                    .body(Body::empty())
+                    .method(Method::POST)
+                    .uri("/orders?id=1")
+                    .header("content-length", "7")
+                    .body(Body::from("payload"))
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        let request_id = response.headers().get(X_REQUEST_ID).unwrap();
-        assert_eq!(request_id.to_str().unwrap(), custom_request_id);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            mirrored_requests.recv().await,
+            Some(("http://mirror.com/orders?id=1".to_string(), true))
+        );
     }
 
     #[tokio::test]
-    async fn converts_domain_response_to_http_response() {
-        let mut headers = HeaderMap::new();
-        headers.insert("content-type", HeaderValue::from_static("application/json"));
+    async fn proxy_endpoint_does_not_retry_without_retries() {
+        let state = build_retrying_server_state(0, |mock| {
+            mock.expect_execute()
+                .times(1)
+                .returning(|_| Err(HttpClientError::Network("Connection refused".to_string())));
+        });
 
-        let http_client_response = HttpClientResponse {
-            status: 200,
-            headers: headers.into(),
-            body: Bytes::from(r#"{"key":"value"}"#),
-        };
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
 
-        let response: AxumResponse<Body> = http_client_response.into();
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
 
-        assert_eq!(
-            response.headers().get("content-type").unwrap(),
-            "application/json"
+    #[tokio::test]
+    async fn proxy_endpoint_tells_the_backend_who_the_client_is() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.headers
+                            .get("x-forwarded-for")
+                            .and_then(|value| value.to_str().ok())
+                            == Some("203.0.113.7")
+                            && req
+                                .headers
+                                .get("x-forwarded-proto")
+                                .and_then(|value| value.to_str().ok())
+                                == Some("http")
+                            && req
+                                .headers
+                                .get("x-forwarded-host")
+                                .and_then(|value| value.to_str().ok())
+                                == Some("shop.example.com")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
         );
 
-        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        let mut request = Request::builder()
+            .uri("/")
+            .header("host", "shop.example.com")
+            .header("x-forwarded-for", "10.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 51234))));
+
+        let response = router.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_serves_http10_clients() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.url == "http://target.com/status"
+                            && req
+                                .headers
+                                .get("host")
+                                .and_then(|value| value.to_str().ok())
+                                == Some("legacy.example.com")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Body::from_stream(futures::stream::iter([
+                                Ok::<_, std::io::Error>(Bytes::from("O")),
+                                Ok(Bytes::from("K")),
+                            ]))
+                            .into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.http10_compat = true;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .version(Version::HTTP_10)
+                    .uri("http://legacy.example.com/status")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
             .unwrap();
-        assert_eq!(body_bytes, r#"{"key":"value"}"#.as_bytes());
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-length"], "2");
     }
 
-    #[test]
-    fn converts_domain_errors_to_http_error() {
-        let network_error = HttpClientError::Network("Connection refused".into());
-        let invalid_request = HttpClientError::InvalidRequest("Bad data".into());
-        let timeout = HttpClientError::Timeout;
+    #[tokio::test]
+    async fn proxy_endpoint_rejects_methods_the_load_balancer_does_not_accept() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().never();
+            },
+            |_, _| {},
+        );
+        state.allowed_methods = AllowedMethods(vec![Method::GET, Method::HEAD]);
 
-        let (status, msg): (StatusCode, &str) = network_error.into();
-        assert_eq!(status, StatusCode::BAD_GATEWAY);
-        assert_eq!(msg, "Network error");
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::DELETE)
+                    .uri("/")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-        let (status, msg): (StatusCode, &str) = invalid_request.into();
-        assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert_eq!(msg, "Invalid request");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, HEAD");
+    }
 
-        let (status, msg): (StatusCode, &str) = timeout.into();
-        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
-        assert_eq!(msg, "Timeout");
+    fn client_connection(certificate: ClientCertificate) -> ConnectInfo<ClientConnection> {
+        ConnectInfo(ClientConnection {
+            address: SocketAddr::from(([203, 0, 113, 7], 51234)),
+            certificate: Some(Arc::new(certificate)),
+            activity: None,
+        })
     }
 
     #[tokio::test]
-    async fn proxy_endpoint_handles_no_one_is_alive_error() {
+    async fn proxy_endpoint_refuses_clients_without_an_allowed_certificate() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().never();
+            },
+            |_, _| {},
+        );
+        state.client_certificate_rules.allow = vec!["ou=payments".parse().unwrap()];
+        let router = router(state);
+
+        let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(client_connection(ClientCertificate {
+                organizational_units: vec!["marketing".to_string()],
+                ..ClientCertificate::default()
+            }));
+
+        for request in [
+            request,
+            Request::builder().uri("/").body(Body::empty()).unwrap(),
+        ] {
+            let response = router.clone().oneshot(request).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_routes_and_identifies_clients_by_certificate() {
+        let mut state = build_server_state_with_mocks(
+            vec![
+                "http://public.com".to_string(),
+                "http://payments.com".to_string(),
+            ],
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.headers
+                            .get("x-client-cert-cn")
+                            .and_then(|value| value.to_str().ok())
+                            == Some("checkout")
+                            && req
+                                .headers
+                                .get("x-client-cert-ou")
+                                .and_then(|value| value.to_str().ok())
+                                == Some("payments")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            |mock, _| {
+                mock.expect_execute()
+                    .withf(|request| request.excluded_servers == vec!["http://public.com"])
+                    .returning(|_| {
+                        Ok(SelectServerResponse {
+                            server: "http://payments.com".to_string(),
+                        })
+                    });
+            },
+        );
+        state.client_certificate_rules = ClientCertificateRules {
+            allow: vec!["ou=payments".parse().unwrap()],
+            routes: vec!["ou=payments>http://payments.com".parse().unwrap()],
+            forwarded_attributes: vec![
+                CertificateAttribute::CommonName,
+                CertificateAttribute::OrganizationalUnit,
+            ],
+        };
+
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-client-cert-cn", "admin")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(client_connection(ClientCertificate {
+                common_name: Some("checkout".to_string()),
+                organizational_units: vec!["payments".to_string()],
+                ..ClientCertificate::default()
+            }));
+
+        let response = router(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_refuses_oversize_request_bodies() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().times(1).returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::new().into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.max_request_body_bytes = Some(8);
+        let router = router(state);
+
+        for (body, status) in [
+            ("12345678", StatusCode::OK),
+            ("123456789", StatusCode::PAYLOAD_TOO_LARGE),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method(Method::POST)
+                        .uri("/")
+                        .header("content-length", body.len())
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_aborts_streamed_bodies_going_over_the_limit() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().times(1).returning(|req| {
+                    futures::executor::block_on(req.body.collect())?;
+
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::new().into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.max_request_body_bytes = Some(8);
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/")
+                    .body(Body::from_stream(futures::stream::iter(
+                        ["1234", "5678", "9"]
+                            .map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk))),
+                    )))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()[header::CONNECTION], "close");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_decompresses_for_clients_not_accepting_gzip() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.headers
+                            .get("accept-encoding")
+                            .and_then(|value| value.to_str().ok())
+                            == Some("gzip")
+                    })
+                    .returning(|_| {
+                        let mut encoder =
+                            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+                        std::io::Write::write_all(&mut encoder, b"plain text").unwrap();
+
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::from([(
+                                header::CONTENT_ENCODING,
+                                HeaderValue::from_static("gzip"),
+                            )]),
+                            body: encoder.finish().unwrap().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.upstream_compression = true;
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert!(response.headers().get("content-encoding").is_none());
+        assert_eq!(
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+            "plain text"
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_tells_the_backend_how_long_the_request_queued() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.headers.contains_key("x-request-start")
+                            && req.headers.contains_key("x-request-queue-ms")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.request_queue_time = true;
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_sends_request_method() {
+        for (method, method_str) in [
+            (Method::GET, "GET"),
+            (Method::POST, "POST"),
+            (Method::PUT, "PUT"),
+            (Method::DELETE, "DELETE"),
+            (Method::PATCH, "PATCH"),
+            (Method::HEAD, "HEAD"),
+            (Method::OPTIONS, "OPTIONS"),
+            (Method::TRACE, "TRACE"),
+            (Method::from_bytes(b"PURGE").unwrap(), "PURGE"),
+        ] {
+            let router = build_router_with_mocks(
+                target_servers(),
+                |mock| {
+                    let expected_method = method_str.to_string();
+                    mock.expect_execute()
+                        .withf(move |req| req.method.to_string() == expected_method)
+                        .returning(|_| {
+                            Ok(HttpClientResponse {
+                                status: 200,
+                                headers: RequestHeaders::default(),
+                                body: Bytes::new().into(),
+                            })
+                        });
+                },
+                first_one_select_server_mock(),
+            );
+
+            let response = router
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri("/")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                response.status(),
+                StatusCode::OK,
+                "Failed for method {}",
+                method_str
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_handles_network_error() {
         let router = build_router_with_mocks(
             target_servers(),
-            build_success_http_client_mock(),
-            |select_server_mock, _| {
-                select_server_mock
-                    .expect_execute()
-                    .returning(move |_| Err(SelectServerError::NoOneIsAlive));
+            |mock| {
+                mock.expect_execute()
+                    .returning(|_| Err(HttpClientError::Network("Connection refused".to_string())));
             },
+            first_one_select_server_mock(),
         );
 
         let response = router
@@ -705,6 +2360,2442 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_handles_timeout_error() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .returning(|_| Err(HttpClientError::Timeout));
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_handles_invalid_request_error() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .returning(|_| Err(HttpClientError::InvalidRequest("Bad URL".to_string())));
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_includes_request_id_in_response() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let request_id = response.headers().get(X_REQUEST_ID);
+        assert!(request_id.is_some());
+        assert!(!request_id.unwrap().to_str().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_propagates_existing_request_id() {
+        let custom_request_id = "custom-12345";
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(X_REQUEST_ID.as_str(), custom_request_id)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let request_id = response.headers().get(X_REQUEST_ID).unwrap();
+        assert_eq!(request_id.to_str().unwrap(), custom_request_id);
+    }
+
+    #[tokio::test]
+    async fn converts_domain_response_to_http_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+
+        let http_client_response = HttpClientResponse {
+            status: 200,
+            headers: headers.into(),
+            body: Bytes::from(r#"{"key":"value"}"#).into(),
+        };
+
+        let response: AxumResponse<Body> = http_client_response.into();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body_bytes, r#"{"key":"value"}"#.as_bytes());
+    }
+
+    #[test]
+    fn converts_domain_errors_to_http_error() {
+        let network_error = HttpClientError::Network("Connection refused".into());
+        let invalid_request = HttpClientError::InvalidRequest("Bad data".into());
+        let timeout = HttpClientError::Timeout;
+
+        let (status, msg): (StatusCode, &str) = network_error.into();
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(msg, "Network error");
+
+        let (status, msg): (StatusCode, &str) = invalid_request.into();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(msg, "Invalid request");
+
+        let (status, msg): (StatusCode, &str) = timeout.into();
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(msg, "Timeout");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_handles_no_one_is_alive_error() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            |select_server_mock, _| {
+                select_server_mock
+                    .expect_execute()
+                    .returning(move |_| Err(SelectServerError::NoOneIsAlive));
+            },
+        );
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "10");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_its_own_errors_with_the_error_pages() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .returning(|_| Err(HttpClientError::Timeout));
+            },
+            first_one_select_server_mock(),
+        );
+        state.error_pages = Arc::new(ErrorPages(HashMap::from([(
+            StatusCode::GATEWAY_TIMEOUT,
+            ErrorPage {
+                content_type: HeaderValue::from_static("application/json"),
+                body: Bytes::from(r#"{"error":"timeout"}"#),
+            },
+        )])));
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, Bytes::from(r#"{"error":"timeout"}"#));
+    }
+
+    #[tokio::test]
+    async fn admin_health_history_endpoint_returns_recorded_probes() {
+        let health_history = Arc::new(HealthHistory::new(10));
+        health_history.record(
+            "http://target.com",
+            ProbeRecord::new(Duration::from_millis(12), Some(200), true),
+        );
+
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        state.health_history = health_history;
+        let router = router(state);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/health-history")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        let records = body["http://target.com"].as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["latency_millis"], 12);
+        assert_eq!(records[0]["status"], 200);
+        assert_eq!(records[0]["healthy"], true);
+    }
+
+    #[tokio::test]
+    async fn admin_health_scores_endpoint_scores_each_server() {
+        let health_history = Arc::new(HealthHistory::new(10));
+        health_history.record(
+            "http://target.com",
+            ProbeRecord::new(Duration::from_millis(12), Some(200), true),
+        );
+        health_history.record(
+            "http://target.com",
+            ProbeRecord::new(Duration::from_millis(12), Some(503), false),
+        );
+
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        state.health_history = health_history;
+        let router = router(state);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/health-scores")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(body["http://target.com"]["score"], 0.5);
+        assert_eq!(body["http://target.com"]["error_rate"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn admin_annotations_show_up_in_the_servers_status() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::PUT)
+                    .uri("/admin/annotations")
+                    .header("content-type", "application/json")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::from(
+                        r#"{"server":"http://target.com","note":"draining, ticket OPS-123"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/servers")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(body[0]["server"], "http://target.com");
+        assert_eq!(body[0]["healthy"], true);
+        assert_eq!(body[0]["annotation"]["note"], "draining, ticket OPS-123");
+    }
+
+    #[tokio::test]
+    async fn admin_annotations_reject_unknown_servers() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .method(Method::PUT)
+                    .uri("/admin/annotations")
+                    .header("content-type", "application/json")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::from(r#"{"server":"http://unknown","note":"typo"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn annotate(authorization: Option<&str>) -> Request<Body> {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri("/admin/annotations")
+            .header("content-type", "application/json");
+
+        authorization
+            .into_iter()
+            .fold(request, |request, authorization| {
+                request.header("authorization", authorization)
+            })
+            .body(Body::from(r#"{"server":"http://target.com","note":"hi"}"#))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn admin_annotations_require_the_admin_token() {
+        let state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        let router = router(state.clone());
+
+        for authorization in [None, Some("Bearer wrong"), Some("s3cr3t")] {
+            let response = router
+                .clone()
+                .oneshot(annotate(authorization))
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()["www-authenticate"], "Bearer");
+        }
+
+        assert!(state.annotations.snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn admin_metrics_and_usage_require_the_admin_token() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        for uri in ["/admin/metrics", "/admin/usage"] {
+            let response = router
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn admin_changes_are_forbidden_without_an_admin_token() {
+        let state = ServerState {
+            admin_auth: AdminAuth::default(),
+            ..build_server_state_with_mocks(
+                target_servers(),
+                build_success_http_client_mock(),
+                first_one_select_server_mock(),
+            )
+        };
+
+        let response = router(state)
+            .oneshot(annotate(Some("Bearer s3cr3t")))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn admin_maintenance_takes_the_server_out_of_rotation() {
+        let state = build_server_state_with_mocks(
+            vec![
+                "http://patched.com".to_string(),
+                "http://serving.com".to_string(),
+            ],
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| req.url == "http://serving.com/")
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            |mock, _| {
+                mock.expect_execute()
+                    .withf(|request| request.excluded_servers == vec!["http://patched.com"])
+                    .returning(|_| {
+                        Ok(SelectServerResponse {
+                            server: "http://serving.com".to_string(),
+                        })
+                    });
+            },
+        );
+        let router = router(state.clone());
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::PUT)
+                    .uri("/admin/maintenance")
+                    .header("content-type", "application/json")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::from(r#"{"server":"HTTP://Patched.com/"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(state.maintenance.snapshot(), vec!["http://patched.com"]);
+
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/servers")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(body[0]["healthy"], true);
+        assert_eq!(body[0]["maintenance"], true);
+        assert_eq!(body[1]["maintenance"], false);
+    }
+
+    #[tokio::test]
+    async fn admin_maintenance_puts_the_server_back() {
+        let state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        state.maintenance.start("http://target.com");
+        let router = router(state.clone());
+
+        let end_maintenance = || {
+            Request::builder()
+                .method(Method::DELETE)
+                .uri("/admin/maintenance")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                .body(Body::from(r#"{"server":"http://target.com"}"#))
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(end_maintenance()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(state.maintenance.snapshot().is_empty());
+
+        let response = router.oneshot(end_maintenance()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_maintenance_rejects_unknown_servers() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .method(Method::PUT)
+                    .uri("/admin/maintenance")
+                    .header("content-type", "application/json")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::from(r#"{"server":"http://unknown"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_maintenance_requires_the_admin_token() {
+        let state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        state.maintenance.start("http://target.com");
+        let router = router(state.clone());
+
+        for method in [Method::PUT, Method::DELETE] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri("/admin/maintenance")
+                        .header("content-type", "application/json")
+                        .header("authorization", "Bearer wrong")
+                        .body(Body::from(r#"{"server":"http://target.com"}"#))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        assert_eq!(state.maintenance.snapshot(), vec!["http://target.com"]);
+    }
+
+    fn blue_green_pools() -> Arc<SwappablePools> {
+        let pool = |name: &str, server: &str| {
+            let healthy_servers = Arc::new(RwLock::new(vec![server.to_string()]));
+
+            Pool {
+                name: name.to_string(),
+                target_servers: Arc::new(vec![server.to_string()]),
+                select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&healthy_servers))),
+                healthy_servers,
+                health_path: "/health".to_string(),
+                allowed_methods: AllowedMethods::default(),
+                http_client: None,
+                location_rewrite: None,
+                host_header: None,
+            }
+        };
+
+        Arc::new(
+            Pools::new(
+                vec![
+                    pool("shop-blue", "http://blue.com"),
+                    pool("shop-green", "http://green.com"),
+                ],
+                vec!["shop=shop-blue,shop-green".parse().unwrap()],
+                Vec::new(),
+                Some("shop"),
+            )
+            .unwrap()
+            .into(),
+        )
+    }
+
+    fn probe_client_mock(status: u16) -> MockHttpClient {
+        let mut mock = MockHttpClient::default();
+        mock.expect_execute()
+            .withf(|req| req.url == "http://green.com/health")
+            .times(1)
+            .returning(move |_| {
+                Ok(HttpClientResponse {
+                    status,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::new().into(),
+                })
+            });
+
+        mock
+    }
+
+    fn switch_blue_green(pool: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::PUT)
+            .uri("/admin/blue-green")
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .body(Body::from(format!(
+                r#"{{"service":"shop","pool":"{}","verify":true}}"#,
+                pool
+            )))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn admin_blue_green_switches_once_the_pool_is_verified() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| req.url == "http://green.com/")
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.pools = blue_green_pools();
+        state.probe_client = Arc::new(probe_client_mock(200));
+        let router = router(state.clone());
+
+        let response = router
+            .clone()
+            .oneshot(switch_blue_green("shop-green"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(state.pools.current().blue_greens()[0].live, "shop-green");
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn admin_blue_green_keeps_the_live_pool_when_verification_fails() {
+        let mut state =
+            build_server_state_with_mocks(target_servers(), |_| {}, first_one_select_server_mock());
+        state.pools = blue_green_pools();
+        state.probe_client = Arc::new(probe_client_mock(503));
+        let router = router(state.clone());
+
+        let response = router
+            .clone()
+            .oneshot(switch_blue_green("shop-green"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(state.pools.current().blue_greens()[0].live, "shop-blue");
+
+        let response = router
+            .oneshot(switch_blue_green("shop-purple"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_blue_green_requires_the_admin_token() {
+        let mut state =
+            build_server_state_with_mocks(target_servers(), |_| {}, first_one_select_server_mock());
+        state.pools = blue_green_pools();
+
+        let mut request = switch_blue_green("shop-green");
+        request.headers_mut().remove("authorization");
+
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(state.pools.current().blue_greens()[0].live, "shop-blue");
+    }
+
+    #[tokio::test]
+    async fn listeners_without_the_admin_api_forward_admin_paths() {
+        let state = ServerState {
+            admin_api: false,
+            ..build_server_state_with_mocks(
+                target_servers(),
+                |mock| {
+                    mock.expect_execute()
+                        .withf(|req| req.url == "http://target.com/admin/servers")
+                        .times(1)
+                        .returning(|_| {
+                            Ok(HttpClientResponse {
+                                status: 418,
+                                headers: RequestHeaders::default(),
+                                body: Bytes::new().into(),
+                            })
+                        });
+                },
+                first_one_select_server_mock(),
+            )
+        };
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/servers")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+    }
+
+    #[tokio::test]
+    async fn admin_healthy_servers_endpoint_returns_the_healthy_set() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/healthy-servers")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Vec<String> = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(body, target_servers());
+    }
+
+    #[tokio::test]
+    async fn admin_config_endpoint_returns_the_effective_config() {
+        let state = build_server_state_with_mocks(
+            target_servers(),
+            build_success_http_client_mock(),
+            first_one_select_server_mock(),
+        );
+        state
+            .effective_config
+            .set(serde_json::json!({"port": 3000, "consul_token": "redacted"}));
+
+        let router = router(state);
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/config")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/config")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(
+            body,
+            serde_json::json!({"port": 3000, "consul_token": "redacted"})
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_records_response_metrics() {
+        let metrics = Arc::new(Metrics::default());
+
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .returning(|_| Err(HttpClientError::Timeout));
+            },
+            first_one_select_server_mock(),
+        );
+        state.metrics = Arc::clone(&metrics);
+        let router = router(state);
+
+        router
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/metrics")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(body["http_requests_total"], 1);
+        assert_eq!(body["http_responses_5xx_total"], 1);
+    }
+
+    /// Reads the whole request body, then answers `OK` in two chunks.
+    struct ReadingHttpClient;
+
+    #[async_trait::async_trait]
+    impl HttpClient for ReadingHttpClient {
+        async fn execute(
+            &self,
+            request: HttpClientRequest,
+        ) -> Result<HttpClientResponse, HttpClientError> {
+            request.body.collect().await?;
+
+            Ok(HttpClientResponse {
+                status: 200,
+                headers: RequestHeaders::default(),
+                body: Body::from_stream(futures::stream::iter([
+                    Ok::<_, std::io::Error>(Bytes::from("O")),
+                    Ok(Bytes::from("K")),
+                ]))
+                .into(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_accounts_bytes_per_tenant() {
+        let mut state =
+            build_server_state_with_mocks(target_servers(), |_| {}, first_one_select_server_mock());
+        state.http_client = Arc::new(ReadingHttpClient);
+        state.usage = Arc::new(UsageTracker::new(
+            Some("x-api-key".to_string()),
+            Duration::from_secs(3600),
+            24,
+        ));
+        let router = router(state);
+
+        // Both bodies are chunked, their size is only known once streamed.
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/")
+                    .header("x-api-key", "tenant-a")
+                    .body(Body::from_stream(futures::stream::iter([
+                        Ok::<_, std::io::Error>(Bytes::from("hel")),
+                        Ok(Bytes::from("lo")),
+                    ])))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/usage")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        let usage = &body[0]["tenants"][pseudonym("tenant-a")];
+        assert_eq!(usage["requests"], 1);
+        assert_eq!(usage["ingress_bytes"], 5);
+        assert_eq!(usage["egress_bytes"], 2);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_routes_by_expression() {
+        let mut state = build_server_state_with_mocks(
+            vec![
+                "http://gold.com".to_string(),
+                "http://shared.com".to_string(),
+            ],
+            build_success_http_client_mock(),
+            |mock, _| {
+                mock.expect_execute()
+                    .withf(|request| request.excluded_servers == vec!["http://shared.com"])
+                    .returning(|_| {
+                        Ok(SelectServerResponse {
+                            server: "http://gold.com".to_string(),
+                        })
+                    });
+            },
+        );
+        state.routing_rules = Arc::new(RoutingRules {
+            rules: vec![
+                r#"header("x-tier") == "gold" && path_prefix("/api")=>http://gold.com"#
+                    .parse()
+                    .unwrap(),
+            ],
+        });
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/orders")
+                    .header("x-tier", "gold")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_transforms_the_headers_of_the_matching_routing_rule() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        let admin = req.headers.get("x-admin").map(HeaderValue::as_bytes)
+                            == Some(&b"true"[..]);
+
+                        admin == (req.url == "http://target.com/admin/users")
+                    })
+                    .times(2)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::from([(
+                                header::SERVER,
+                                HeaderValue::from_static("nginx"),
+                            )]),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.routing_rules = Arc::new(RoutingRules {
+            rules: vec![
+                r#"path_prefix("/admin")=>*;request:set:X-Admin=true;response:set:X-Frame-Options=DENY;response:remove:Server"#
+                    .parse()
+                    .unwrap(),
+            ],
+        });
+        let router = router(state);
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["x-frame-options"], "DENY");
+        assert!(!response.headers().contains_key(header::SERVER));
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!response.headers().contains_key("x-frame-options"));
+        assert_eq!(response.headers()[header::SERVER], "nginx");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_sends_the_timeout_of_the_matching_routing_rule() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        let timeout = match req.url.as_str() {
+                            "http://target.com/reports/2024" => Some(Duration::from_secs(120)),
+                            _ => None,
+                        };
+
+                        req.timeout == timeout
+                    })
+                    .times(2)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.routing_rules = Arc::new(RoutingRules {
+            rules: vec![r#"path_prefix("/reports")=>*;timeout:120000"#.parse().unwrap()],
+        });
+        let router = router(state);
+
+        for uri in ["/reports/2024", "/users"] {
+            let response = router
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_keeps_headers_that_are_not_utf8() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.headers.get("x-legacy").map(HeaderValue::as_bytes)
+                            == Some(&[0xE9, 0x74, 0xE9][..])
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::from([(
+                                HeaderName::from_static("x-legacy"),
+                                HeaderValue::from_bytes(&[0xFF]).unwrap(),
+                            )]),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(
+                        "x-legacy",
+                        HeaderValue::from_bytes(&[0xE9, 0x74, 0xE9]).unwrap(),
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-legacy"].as_bytes(), [0xFF]);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_rejects_tenants_over_their_cost_budget() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().times(1).returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::from([(
+                            X_REQUEST_COST,
+                            HeaderValue::from_static("5"),
+                        )]),
+                        body: Bytes::new().into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.usage = Arc::new(UsageTracker::new(
+            Some("x-api-key".to_string()),
+            Duration::from_secs(3600),
+            24,
+        ));
+        state.cost_budgets = Arc::new(CostBudgets::new(
+            HashMap::from([(pseudonym("tenant-a"), 5)]),
+            Duration::from_secs(3600),
+            CostBudgetAction::Reject,
+            Arc::new(MemoryStateStore::default()),
+        ));
+        let router = router(state);
+
+        let request = || {
+            Request::builder()
+                .uri("/")
+                .header("x-api-key", "tenant-a")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_passes_the_trailers_through() {
+        let router = build_router_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().returning(|_| {
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Body::new(http_body_util::StreamBody::new(futures::stream::iter([
+                            Ok::<_, std::io::Error>(hyper::body::Frame::data(Bytes::from(
+                                "message",
+                            ))),
+                            Ok(hyper::body::Frame::trailers(trailers)),
+                        ])))
+                        .into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let collected = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap();
+
+        assert_eq!(collected.trailers().unwrap()["grpc-status"], "0");
+        assert_eq!(collected.to_bytes(), Bytes::from("message"));
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_rewrites_the_host_to_the_backend() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.headers
+                            .get("host")
+                            .and_then(|value| value.to_str().ok())
+                            == Some("target.com")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.host_header = HostHeader::Upstream;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::HOST, "shop.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_sends_the_host_the_pool_asks_for() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                for (url, host) in [
+                    ("http://api.com/api/users", "api.com"),
+                    ("http://target.com/", "shop.example.com"),
+                ] {
+                    mock.expect_execute()
+                        .withf(move |req| {
+                            req.url == url
+                                && req
+                                    .headers
+                                    .get("host")
+                                    .and_then(|value| value.to_str().ok())
+                                    == Some(host)
+                        })
+                        .times(1)
+                        .returning(|_| {
+                            Ok(HttpClientResponse {
+                                status: 200,
+                                headers: RequestHeaders::default(),
+                                body: Bytes::new().into(),
+                            })
+                        });
+                }
+            },
+            first_one_select_server_mock(),
+        );
+        state.pools = single_backend_pools(
+            Pool {
+                host_header: Some(HostHeader::Upstream),
+                ..single_backend_pool("api", "http://api.com")
+            },
+            "/api/*=>api",
+        );
+        let router = router(state);
+
+        for uri in ["/api/users", "/"] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header(header::HOST, "shop.example.com")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_strips_the_prefix_of_path_rules() {
+        let mut state = build_server_state_with_mocks(
+            vec![
+                "http://users.com".to_string(),
+                "http://orders.com".to_string(),
+            ],
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| req.url == "http://users.com/7?fields=name")
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            |mock, _| {
+                mock.expect_execute()
+                    .withf(|request| request.excluded_servers == vec!["http://orders.com"])
+                    .returning(|_| {
+                        Ok(SelectServerResponse {
+                            server: "http://users.com".to_string(),
+                        })
+                    });
+            },
+        );
+        state.path_rules = PathRules {
+            rules: vec!["/api/v1/users>http://users.com".parse().unwrap()],
+        };
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/users/7?fields=name")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_routes_to_the_pool_of_the_path() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| req.url == "http://api.com/api/users")
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+                mock.expect_execute()
+                    .withf(|req| req.url == "http://target.com/static/app.js")
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        let api_servers = Arc::new(RwLock::new(vec!["http://api.com".to_string()]));
+        state.pools = Arc::new(
+            Pools::new(
+                vec![Pool {
+                    name: "api".to_string(),
+                    target_servers: Arc::new(vec!["http://api.com".to_string()]),
+                    select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&api_servers))),
+                    healthy_servers: api_servers,
+                    health_path: "/health".to_string(),
+                    allowed_methods: AllowedMethods::default(),
+                    http_client: None,
+                    location_rewrite: None,
+                    host_header: None,
+                }],
+                Vec::new(),
+                vec!["/api/*=>api".parse().unwrap()],
+                None,
+            )
+            .unwrap()
+            .into(),
+        );
+        let router = router(state);
+
+        for uri in ["/api/users", "/static/app.js"] {
+            let response = router
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    /// A pool of a single backend, healthy, routed to by `route`.
+    fn single_backend_pools(pool: Pool, route: &str) -> Arc<SwappablePools> {
+        Arc::new(
+            Pools::new(vec![pool], Vec::new(), vec![route.parse().unwrap()], None)
+                .unwrap()
+                .into(),
+        )
+    }
+
+    fn single_backend_pool(name: &str, server: &str) -> Pool {
+        let healthy_servers = Arc::new(RwLock::new(vec![server.to_string()]));
+
+        Pool {
+            name: name.to_string(),
+            target_servers: Arc::new(vec![server.to_string()]),
+            select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&healthy_servers))),
+            healthy_servers,
+            health_path: "/health".to_string(),
+            allowed_methods: AllowedMethods::default(),
+            http_client: None,
+            location_rewrite: None,
+            host_header: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_sends_the_requests_of_a_pool_through_its_own_client() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().never();
+            },
+            first_one_select_server_mock(),
+        );
+        let mut pool_http_client = MockHttpClient::new();
+        pool_http_client
+            .expect_execute()
+            .withf(|req| req.url == "http://api.com/api/users")
+            .times(1)
+            .returning(|_| {
+                Ok(HttpClientResponse {
+                    status: 200,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::new().into(),
+                })
+            });
+        state.pools = single_backend_pools(
+            Pool {
+                http_client: Some(Arc::new(pool_http_client)),
+                ..single_backend_pool("api", "http://api.com")
+            },
+            "/api/*=>api",
+        );
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_405_to_methods_the_pool_does_not_accept() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| req.url == "http://static.com/static/app.js")
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.pools = single_backend_pools(
+            Pool {
+                allowed_methods: "GET|HEAD".parse().unwrap(),
+                ..single_backend_pool("static", "http://static.com")
+            },
+            "/static/*=>static",
+        );
+        let router = router(state);
+        let request = |method: Method| {
+            Request::builder()
+                .method(method)
+                .uri("/static/app.js")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(request(Method::POST)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, HEAD");
+
+        let response = router.oneshot(request(Method::GET)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_405_to_methods_not_allowed_by_the_pool_route() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().never();
+            },
+            first_one_select_server_mock(),
+        );
+        let static_servers = Arc::new(RwLock::new(vec!["http://static.com".to_string()]));
+        state.pools = Arc::new(
+            Pools::new(
+                vec![Pool {
+                    name: "static".to_string(),
+                    target_servers: Arc::new(vec!["http://static.com".to_string()]),
+                    select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(
+                        &static_servers,
+                    ))),
+                    healthy_servers: static_servers,
+                    health_path: "/health".to_string(),
+                    allowed_methods: AllowedMethods::default(),
+                    http_client: None,
+                    location_rewrite: None,
+                    host_header: None,
+                }],
+                Vec::new(),
+                vec!["/static/*;allow:GET|HEAD=>static".parse().unwrap()],
+                None,
+            )
+            .unwrap()
+            .into(),
+        );
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/static/app.js")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, HEAD");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_the_unmatched_status_to_requests_matching_no_route() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().never();
+            },
+            first_one_select_server_mock(),
+        );
+        let api_servers = Arc::new(RwLock::new(vec!["http://api.com".to_string()]));
+        state.pools = Arc::new(
+            Pools::new(
+                vec![Pool {
+                    name: "api".to_string(),
+                    target_servers: Arc::new(vec!["http://api.com".to_string()]),
+                    select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&api_servers))),
+                    healthy_servers: api_servers,
+                    health_path: "/health".to_string(),
+                    allowed_methods: AllowedMethods::default(),
+                    http_client: None,
+                    location_rewrite: None,
+                    host_header: None,
+                }],
+                Vec::new(),
+                vec!["api.example.com=>api".parse().unwrap()],
+                None,
+            )
+            .unwrap()
+            .with_unmatched_status(StatusCode::MISDIRECTED_REQUEST)
+            .into(),
+        );
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header("host", "unknown.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::MISDIRECTED_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_routes_to_the_pool_of_the_client_continent() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.url == "http://eu.com/"
+                            && req.headers.get("x-geo-country").map(HeaderValue::as_bytes)
+                                == Some(&b"DE"[..])
+                    })
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        let mut locator = MockGeoLocator::new();
+        locator.expect_locate().returning(|_| {
+            Some(GeoLocation {
+                country: Some("DE".to_string()),
+                continent: Some("EU".to_string()),
+            })
+        });
+        state.geo_ip = GeoIp {
+            locator: Some(Arc::new(locator)),
+            ..GeoIp::default()
+        };
+        let eu_servers = Arc::new(RwLock::new(vec!["http://eu.com".to_string()]));
+        state.pools = Arc::new(
+            Pools::new(
+                vec![Pool {
+                    name: "eu".to_string(),
+                    target_servers: Arc::new(vec!["http://eu.com".to_string()]),
+                    select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&eu_servers))),
+                    healthy_servers: eu_servers,
+                    health_path: "/health".to_string(),
+                    allowed_methods: AllowedMethods::default(),
+                    http_client: None,
+                    location_rewrite: None,
+                    host_header: None,
+                }],
+                Vec::new(),
+                vec!["continent:EU=>eu".parse().unwrap()],
+                None,
+            )
+            .unwrap()
+            .into(),
+        );
+
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-geo-country", "US")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([81, 2, 69, 142], 51234))));
+
+        let response = router(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_routes_to_the_pool_of_the_experiment_variant() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.url == "http://treatment.com/"
+                            && req
+                                .headers
+                                .get("x-experiment-variant")
+                                .map(HeaderValue::as_bytes)
+                                == Some(&b"treatment"[..])
+                    })
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.experiment = "treatment".parse().unwrap();
+        let treatment_servers = Arc::new(RwLock::new(vec!["http://treatment.com".to_string()]));
+        state.pools = Arc::new(
+            Pools::new(
+                vec![Pool {
+                    name: "treatment".to_string(),
+                    target_servers: Arc::new(vec!["http://treatment.com".to_string()]),
+                    select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(
+                        &treatment_servers,
+                    ))),
+                    healthy_servers: treatment_servers,
+                    health_path: "/health".to_string(),
+                    allowed_methods: AllowedMethods::default(),
+                    http_client: None,
+                    location_rewrite: None,
+                    host_header: None,
+                }],
+                Vec::new(),
+                vec!["variant:treatment=>treatment".parse().unwrap()],
+                None,
+            )
+            .unwrap()
+            .into(),
+        );
+
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-experiment-variant", "control")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([81, 2, 69, 142], 51234))));
+
+        let response = router(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_transforms_the_forwarded_request() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        req.url == "http://target.com/search?q=lb&env=prod"
+                            && req
+                                .headers
+                                .get("x-env")
+                                .and_then(|value| value.to_str().ok())
+                                == Some("prod")
+                            && !req.headers.contains_key("x-debug")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.request_transforms = RequestTransforms(vec![
+            "set-header:X-Env=prod".parse().unwrap(),
+            "remove-header:X-Debug".parse().unwrap(),
+            "remove-query:debug".parse().unwrap(),
+            "set-query:env=prod".parse().unwrap(),
+        ]);
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/search?q=lb&debug=1")
+                    .header("x-debug", "1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_streams_partial_content_back() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        let header =
+                            |name| req.headers.get(name).and_then(|value| value.to_str().ok());
+
+                        header("range") == Some("bytes=0-3")
+                            && header("if-range") == Some("\"v1\"")
+                            && header("accept-encoding") == Some("identity")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 206,
+                            headers: RequestHeaders::from([
+                                (
+                                    header::CONTENT_RANGE,
+                                    HeaderValue::from_static("bytes 0-3/10"),
+                                ),
+                                (header::ACCEPT_RANGES, HeaderValue::from_static("bytes")),
+                            ]),
+                            body: Body::from_stream(futures::stream::iter([
+                                Ok::<_, std::io::Error>(Bytes::from("wa")),
+                                Ok(Bytes::from("ka")),
+                            ]))
+                            .into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.upstream_compression = true;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/video.mp4")
+                    .header(header::RANGE, "bytes=0-3")
+                    .header(header::IF_RANGE, "\"v1\"")
+                    .header(header::ACCEPT_ENCODING, "identity")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 0-3/10");
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+            "waka"
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_range_requests_in_full_when_ranges_are_rejected() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        !req.headers.contains_key("range") && !req.headers.contains_key("if-range")
+                    })
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::from("wakanda").into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.range_requests = RangeRequests::Reject;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/video.mp4")
+                    .header(header::RANGE, "bytes=0-3")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "none");
+    }
+
+    fn gzip_response(body: &[u8]) -> HttpClientResponse {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut encoder, body).unwrap();
+        let body = encoder.finish().unwrap();
+
+        HttpClientResponse {
+            status: 200,
+            headers: RequestHeaders::from([
+                (header::CONTENT_TYPE, HeaderValue::from_static("text/plain")),
+                (header::CONTENT_ENCODING, HeaderValue::from_static("gzip")),
+                (header::CONTENT_LENGTH, HeaderValue::from(body.len())),
+            ]),
+            body: body.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_passes_encoded_responses_through() {
+        let response = gzip_response(b"wakanda forever");
+        let encoded_length = response.headers["content-length"].clone();
+        let state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute()
+                    .returning(|_| Ok(gzip_response(b"wakanda forever")));
+            },
+            first_one_select_server_mock(),
+        );
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], encoded_length);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_recompresses_encoded_responses_for_the_client() {
+        let recompressing_state = |response_compression| {
+            let mut state = build_server_state_with_mocks(
+                target_servers(),
+                |mock| {
+                    mock.expect_execute().returning(|_| {
+                        Ok(gzip_response("wakanda forever ".repeat(128).as_bytes()))
+                    });
+                },
+                first_one_select_server_mock(),
+            );
+            state.upstream_decoding = UpstreamDecoding::Recompress;
+            state.response_compression = response_compression;
+            state
+        };
+        let request = || {
+            Request::builder()
+                .uri("/")
+                .header(header::ACCEPT_ENCODING, "br")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = router(recompressing_state(Some(ResponseCompression::default())))
+            .oneshot(request())
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+
+        let response = router(recompressing_state(None))
+            .oneshot(request())
+            .await
+            .unwrap();
+
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+            "wakanda forever ".repeat(128)
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_skips_backends_with_too_many_requests_in_flight() {
+        let mut state = build_retrying_server_state(0, |mock| {
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server2.com/")
+                .times(1)
+                .returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::from("OK").into(),
+                    })
+                });
+        });
+        state.bulkheads = Arc::new(Bulkheads::new(Some(1), HashMap::new()));
+        let bulkheads = Arc::clone(&state.bulkheads);
+        let _busy = bulkheads.acquire("http://server1.com").unwrap();
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(bulkheads.in_flight("http://server2.com"), 1);
+
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert_eq!(bulkheads.in_flight("http://server2.com"), 0);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_503_when_every_backend_is_saturated() {
+        let mut state = build_retrying_server_state(0, |mock| {
+            mock.expect_execute().never();
+        });
+        state.bulkheads = Arc::new(Bulkheads::new(Some(1), HashMap::new()));
+        let _busy = [
+            state.bulkheads.acquire("http://server1.com").unwrap(),
+            state.bulkheads.acquire("http://server2.com").unwrap(),
+        ];
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_forwards_the_time_left_before_the_deadline() {
+        let mut state = build_server_state_with_mocks(
+            vec![String::from("http://target.com")],
+            |mock| {
+                mock.expect_execute()
+                    .withf(|req| {
+                        let remaining = req.headers[X_REQUEST_DEADLINE.as_str()]
+                            .to_str()
+                            .unwrap()
+                            .parse::<u64>()
+                            .unwrap();
+                        remaining > 0 && remaining <= 1000
+                    })
+                    .times(1)
+                    .returning(|_| {
+                        Ok(HttpClientResponse {
+                            status: 200,
+                            headers: RequestHeaders::default(),
+                            body: Bytes::new().into(),
+                        })
+                    });
+            },
+            first_one_select_server_mock(),
+        );
+        state.deadline_propagation = true;
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(X_REQUEST_DEADLINE, "1000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Answers after a delay, like a busy backend.
+    struct SlowHttpClient(Duration);
+
+    #[async_trait::async_trait]
+    impl HttpClient for SlowHttpClient {
+        async fn execute(
+            &self,
+            _request: HttpClientRequest,
+        ) -> Result<HttpClientResponse, HttpClientError> {
+            tokio::time::sleep(self.0).await;
+
+            Ok(HttpClientResponse {
+                status: 200,
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_gives_up_on_requests_past_their_deadline() {
+        let mut state = build_server_state_with_mocks(
+            vec![String::from("http://target.com")],
+            |_| {},
+            first_one_select_server_mock(),
+        );
+        state.http_client = Arc::new(SlowHttpClient(Duration::from_secs(5)));
+        state.deadline_propagation = true;
+        let started = Instant::now();
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(GRPC_TIMEOUT, "100m")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_gives_up_on_grpc_calls_past_their_method_timeout() {
+        let mut state = build_server_state_with_mocks(
+            vec![String::from("http://target.com")],
+            |_| {},
+            first_one_select_server_mock(),
+        );
+        state.http_client = Arc::new(SlowHttpClient(Duration::from_secs(5)));
+        state.grpc_timeouts = GrpcTimeouts(vec!["shop.v1.Orders/Create=100".parse().unwrap()]);
+        let started = Instant::now();
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/shop.v1.Orders/Create")
+                    .header(header::CONTENT_TYPE, "application/grpc")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["grpc-status"], "4");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_ends_the_streams_of_grpc_calls_past_their_deadline() {
+        let mut state = build_server_state_with_mocks(
+            vec![String::from("http://target.com")],
+            |mock| {
+                mock.expect_execute().returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Body::new(http_body_util::StreamBody::new(
+                            futures::StreamExt::chain(
+                                futures::stream::iter([Ok::<_, std::io::Error>(
+                                    hyper::body::Frame::data(Bytes::from("message")),
+                                )]),
+                                futures::stream::pending(),
+                            ),
+                        ))
+                        .into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.deadline_propagation = true;
+        let started = Instant::now();
+
+        let response = router(state)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/shop.v1.Orders/Watch")
+                    .header(header::CONTENT_TYPE, "application/grpc")
+                    .header(GRPC_TIMEOUT, "100m")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let collected = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap();
+
+        assert_eq!(collected.trailers().unwrap()["grpc-status"], "4");
+        assert_eq!(collected.to_bytes(), Bytes::from("message"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    fn retry_later_response(status: u16, retry_after: &'static str) -> HttpClientResponse {
+        HttpClientResponse {
+            status,
+            headers: RequestHeaders::from([(
+                header::RETRY_AFTER,
+                HeaderValue::from_static(retry_after),
+            )]),
+            body: Bytes::new().into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_propagates_the_shortest_retry_after_once_every_attempt_failed() {
+        let mut state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server1.com/")
+                .times(1)
+                .returning(|_| Ok(retry_later_response(429, "30")));
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server2.com/")
+                .times(1)
+                .returning(|_| Ok(retry_later_response(503, "10")));
+        });
+        state.propagate_retry_after = true;
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "10");
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_avoids_backends_until_their_retry_after() {
+        let state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server1.com/")
+                .times(1)
+                .returning(|_| Ok(retry_later_response(503, "60")));
+            mock.expect_execute()
+                .withf(|req| req.url == "http://server2.com/")
+                .times(3)
+                .returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: Bytes::new().into(),
+                    })
+                });
+        });
+        let router = router(state);
+
+        for _ in 0..3 {
+            let response = router
+                .clone()
+                .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_falls_back_on_backends_backing_off_when_no_other_is_left() {
+        let state = build_retrying_server_state(1, |mock| {
+            mock.expect_execute()
+                .times(2)
+                .returning(|_| Ok(retry_later_response(503, "60")));
+            mock.expect_execute().times(1).returning(|_| {
+                Ok(HttpClientResponse {
+                    status: 200,
+                    headers: RequestHeaders::default(),
+                    body: Bytes::new().into(),
+                })
+            });
+        });
+        let router = router(state);
+
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = router
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_the_fallback_response_when_no_one_is_alive() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().never();
+            },
+            |mock, _| {
+                mock.expect_execute()
+                    .returning(|_| Err(SelectServerError::NoOneIsAlive));
+            },
+        );
+        state.fallback_response = Some(FallbackResponse {
+            status: StatusCode::OK,
+            page: ErrorPage {
+                content_type: HeaderValue::from_static("text/html; charset=utf-8"),
+                body: Bytes::from("<h1>Down for maintenance</h1>"),
+            },
+        });
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+            "<h1>Down for maintenance</h1>"
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_answers_cacheable_responses_from_the_cache() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().times(1).returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::from([(
+                            header::CACHE_CONTROL,
+                            HeaderValue::from_static("max-age=60"),
+                        )]),
+                        body: Bytes::from("products").into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.response_cache = Some(Arc::new(ResponseCache::new(
+            10,
+            1024,
+            Arc::clone(&state.metrics),
+        )));
+        let router = router(state);
+
+        for expected in ["MISS", "HIT"] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/products")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.headers()[X_CACHE], expected);
+            assert_eq!(
+                axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap(),
+                "products"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_endpoint_only_answers_from_the_cache_the_clients_routed_the_same_way() {
+        let mut state = build_server_state_with_mocks(
+            target_servers(),
+            |mock| {
+                mock.expect_execute().times(2).returning(|_| {
+                    Ok(HttpClientResponse {
+                        status: 200,
+                        headers: RequestHeaders::from([(
+                            header::CACHE_CONTROL,
+                            HeaderValue::from_static("max-age=60"),
+                        )]),
+                        body: Bytes::from("statement").into(),
+                    })
+                });
+            },
+            first_one_select_server_mock(),
+        );
+        state.client_certificate_rules.allow = vec!["ou=payments".parse().unwrap()];
+        state.response_cache = Some(Arc::new(ResponseCache::new(
+            10,
+            1024,
+            Arc::clone(&state.metrics),
+        )));
+        let router = router(state);
+        let request = |certificate: Option<(&str, &str)>| {
+            let mut request = Request::builder()
+                .uri("/statement")
+                .body(Body::empty())
+                .unwrap();
+            if let Some((common_name, organizational_unit)) = certificate {
+                request
+                    .extensions_mut()
+                    .insert(client_connection(ClientCertificate {
+                        common_name: Some(common_name.to_string()),
+                        organizational_units: vec![organizational_unit.to_string()],
+                        ..ClientCertificate::default()
+                    }));
+            }
+            request
+        };
+
+        for (certificate, status, cache) in [
+            (Some(("checkout", "payments")), StatusCode::OK, Some("MISS")),
+            (Some(("checkout", "payments")), StatusCode::OK, Some("HIT")),
+            (
+                Some(("campaigns", "marketing")),
+                StatusCode::FORBIDDEN,
+                None,
+            ),
+            (None, StatusCode::FORBIDDEN, None),
+            (Some(("refunds", "payments")), StatusCode::OK, Some("MISS")),
+        ] {
+            let response = router.clone().oneshot(request(certificate)).await.unwrap();
+
+            assert_eq!(response.status(), status);
+            assert_eq!(
+                response
+                    .headers()
+                    .get(X_CACHE)
+                    .and_then(|value| value.to_str().ok()),
+                cache
+            );
+        }
     }
 }
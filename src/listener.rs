@@ -0,0 +1,72 @@
+use std::{io, net::SocketAddr};
+use tokio::net::{TcpListener, TcpSocket};
+
+const BACKLOG: u32 = 1024;
+
+fn bind(address: SocketAddr, reuse_port: bool) -> io::Result<TcpListener> {
+    let socket = match address {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+
+    socket.set_reuseaddr(true)?;
+    socket.set_reuseport(reuse_port)?;
+    socket.bind(address)?;
+    socket.listen(BACKLOG)
+}
+
+/// Binds `acceptors` listening sockets to the same address with SO_REUSEPORT,
+/// so the kernel spreads the incoming connections across them. When the port
+/// is 0 the one picked for the first socket is reused by the others. A single
+/// acceptor binds without SO_REUSEPORT, so that another process already
+/// listening on the address makes it fail.
+pub fn bind_acceptors(address: SocketAddr, acceptors: usize) -> io::Result<Vec<TcpListener>> {
+    let reuse_port = acceptors > 1;
+    let first = bind(address, reuse_port)?;
+    let address = first.local_addr()?;
+
+    let mut listeners = vec![first];
+
+    for _ in 1..acceptors {
+        listeners.push(bind(address, reuse_port)?);
+    }
+
+    Ok(listeners)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::net::SocketAddr;
+
+    use crate::listener::bind_acceptors;
+
+    #[tokio::test]
+    async fn all_acceptors_share_the_same_address() {
+        let address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+        let listeners = bind_acceptors(address, 4).unwrap();
+
+        assert_eq!(listeners.len(), 4);
+
+        let first_address = listeners[0].local_addr().unwrap();
+        assert_ne!(first_address.port(), 0);
+
+        for listener in &listeners {
+            assert_eq!(listener.local_addr().unwrap(), first_address);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_single_acceptor_doesnt_share_its_address() {
+        let address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+        let listeners = bind_acceptors(address, 1).unwrap();
+        let address = listeners[0].local_addr().unwrap();
+
+        for acceptors in [1, 2] {
+            let error = bind_acceptors(address, acceptors).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::AddrInUse);
+        }
+    }
+}
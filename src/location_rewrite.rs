@@ -0,0 +1,145 @@
+use axum::response::Response;
+use http::{HeaderMap, HeaderValue, Uri, header};
+
+use crate::forwarded_headers::{X_FORWARDED_HOST, X_FORWARDED_PROTO};
+
+/// Points the redirects a backend makes to its own address, e.g.
+/// `http://10.0.0.7:8080/login`, back at the load balancer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LocationRewrite {
+    pub enabled: bool,
+}
+
+impl LocationRewrite {
+    /// Where the client reached the load balancer, as told to the backends
+    /// in the forwarded headers.
+    pub fn external_origin(&self, headers: &HeaderMap) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+        Some(format!(
+            "{}://{}",
+            value(X_FORWARDED_PROTO).unwrap_or("http"),
+            value(X_FORWARDED_HOST)?
+        ))
+    }
+
+    pub fn apply(&self, response: &mut Response, server: &str, external_origin: Option<&str>) {
+        let Some(external_origin) = external_origin else {
+            return;
+        };
+
+        if !response.status().is_redirection() {
+            return;
+        }
+
+        let Some(location) = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| location.parse::<Uri>().ok())
+        else {
+            return;
+        };
+
+        let Ok(server) = server.parse::<Uri>() else {
+            return;
+        };
+
+        if location.scheme() != server.scheme()
+            || !location
+                .authority()
+                .zip(server.authority())
+                .is_some_and(|(location, server)| {
+                    location.as_str().eq_ignore_ascii_case(server.as_str())
+                })
+        {
+            return;
+        }
+
+        let path_and_query = location
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or("/");
+
+        if let Ok(location) =
+            HeaderValue::from_str(&format!("{}{}", external_origin, path_and_query))
+        {
+            response.headers_mut().insert(header::LOCATION, location);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::Response;
+    use http::{HeaderMap, HeaderValue, StatusCode, header};
+
+    use crate::forwarded_headers::{X_FORWARDED_HOST, X_FORWARDED_PROTO};
+    use crate::location_rewrite::LocationRewrite;
+
+    const REWRITE: LocationRewrite = LocationRewrite { enabled: true };
+
+    fn redirect(status: StatusCode, location: &'static str) -> Response {
+        let mut response = Response::new(axum::body::Body::empty());
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(header::LOCATION, HeaderValue::from_static(location));
+        response
+    }
+
+    #[test]
+    fn external_origin_comes_from_the_forwarded_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static("https"));
+        headers.insert(
+            X_FORWARDED_HOST,
+            HeaderValue::from_static("shop.example.com"),
+        );
+
+        assert_eq!(
+            REWRITE.external_origin(&headers).as_deref(),
+            Some("https://shop.example.com")
+        );
+        assert_eq!(LocationRewrite::default().external_origin(&headers), None);
+    }
+
+    #[test]
+    fn rewrites_redirects_to_the_backend() {
+        let mut response = redirect(StatusCode::FOUND, "http://10.0.0.7:8080/login?next=%2F");
+
+        REWRITE.apply(
+            &mut response,
+            "http://10.0.0.7:8080",
+            Some("https://shop.example.com"),
+        );
+
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://shop.example.com/login?next=%2F"
+        );
+    }
+
+    #[test]
+    fn leaves_other_locations_alone() {
+        for (status, location) in [
+            (StatusCode::FOUND, "https://sso.example.com/login"),
+            (StatusCode::MOVED_PERMANENTLY, "/relative"),
+            (StatusCode::CREATED, "http://10.0.0.7:8080/orders/1"),
+        ] {
+            let mut response = redirect(status, location);
+
+            REWRITE.apply(
+                &mut response,
+                "http://10.0.0.7:8080",
+                Some("https://shop.example.com"),
+            );
+
+            assert_eq!(response.headers()[header::LOCATION], location);
+        }
+    }
+}
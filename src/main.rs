@@ -1,19 +1,119 @@
-pub mod background_health_checker;
 pub(crate) mod cli_arguments;
-pub(crate) mod http_client;
-pub mod request_id;
-pub mod select_server;
+pub(crate) mod config_check;
+pub(crate) mod config_file;
+pub(crate) mod secrets;
 
-use crate::cli_arguments::{CliArguments, RoutingPolicy};
-use clap::Parser;
+use crate::cli_arguments::{
+    BodyLimitActionKind, CliArguments, ClientAuthMode, ConfigFormat, CostBudgetActionKind,
+    HostHeaderKind, InitialHealth, RangeRequestsKind, RoutingPolicy, StateStoreKind, TlsMinVersion,
+    TlsProfileKind, UnmatchedStatus, UpstreamDecodingKind, UpstreamHttpVersion,
+};
+use crate::config_check::check_config;
+use crate::config_file::ConfigFile;
+use crate::secrets::{check_private_keys, load_secret_files};
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use futures::FutureExt;
+use futures::future::join_all;
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Uri};
+use load_balancer::admin::admin_auth::AdminAuth;
+use load_balancer::admin::annotations::Annotations;
+use load_balancer::admin::effective_config::EffectiveConfig;
+use load_balancer::admin::maintenance::Maintenance;
+use load_balancer::allowed_methods::AllowedMethods;
 use load_balancer::background_health_checker::background_health_checker::BackgroundChecker;
+use load_balancer::body_limit::BodyLimitAction;
+use load_balancer::bulkhead::Bulkheads;
+use load_balancer::certificate_expiries::CertificateExpiries;
+use load_balancer::client_certificate::ClientCertificateRules;
+use load_balancer::client_connection::ClientConnection;
+use load_balancer::config_rollout::{ConfigRollout, RolloutTurn};
+use load_balancer::connection_recycling::ConnectionRecycling;
+use load_balancer::consul_discovery::{ConsulDiscovery, ConsulService};
+use load_balancer::cost_budget::{ANY_TENANT, CostBudgetAction, CostBudgets};
+use load_balancer::decision_record::DecisionRecords;
+use load_balancer::dev_trace;
+use load_balancer::downstream_timeouts::{DownstreamTimeouts, TimedListener};
+use load_balancer::error_pages::{ErrorPage, ErrorPageSource, ErrorPages, FallbackResponse};
+use load_balancer::experiment::Experiment;
+use load_balancer::forwarded_headers::{ForwardedHeaders, TrustedProxies};
+use load_balancer::geo_ip::geo_ip::GeoIp;
+use load_balancer::geo_ip::geo_locator::GeoLocator;
+#[cfg(feature = "geoip")]
+use load_balancer::geo_ip::maxmind_geo_locator::MaxMindGeoLocator;
+use load_balancer::grpc::GrpcTimeouts;
+use load_balancer::health_notifier::queued_health_notifier::QueuedHealthNotifier;
+use load_balancer::health_notifier::webhook_health_notifier::WebhookHealthNotifier;
+use load_balancer::host_header::HostHeader;
+use load_balancer::http_client::client_identity_http_client::{
+    ClientIdentityFiles, ClientIdentityHttpClient,
+};
+use load_balancer::http_client::error::Error as HttpClientError;
+use load_balancer::http_client::sni_override_http_client::SniOverrideHttpClient;
+use load_balancer::http_client::timeout_override_http_client::TimeoutOverrideHttpClient;
+use load_balancer::http_client::upstream_pool::UpstreamPool;
+use load_balancer::http_client::upstream_protocol::UpstreamProtocol;
+use load_balancer::http_client::upstream_proxy::UpstreamProxy;
+use load_balancer::http_client::upstream_timeouts::UpstreamTimeouts;
+use load_balancer::http_client::upstream_tls::UpstreamTls;
+use load_balancer::kubernetes_discovery::{EndpointSliceDiscovery, KubernetesService};
+use load_balancer::leader_election::file_lease_leader_election::FileLeaseLeaderElection;
+use load_balancer::listener::bind_acceptors;
+use load_balancer::location_rewrite::LocationRewrite;
+use load_balancer::metrics::metrics::Metrics;
+use load_balancer::metrics::metrics_snapshotter::MetricsSnapshotter;
+use load_balancer::metrics::usage_tracker::{UsageTracker, pseudonym};
+use load_balancer::path_rules::PathRules;
+use load_balancer::pools::listener_pools::{ListenerDefinition, check_ports, split_by_listener};
+use load_balancer::pools::pool::{Pool, PoolDefinition, PoolPolicy};
+use load_balancer::pools::pool_route::PoolRoute;
+use load_balancer::pools::pools::Pools;
+use load_balancer::pools::swappable_pools::SwappablePools;
+use load_balancer::range_requests::RangeRequests;
+use load_balancer::request_coalescing::RequestCoalescing;
+use load_balancer::request_transforms::RequestTransforms;
+use load_balancer::response_cache::ResponseCache;
+use load_balancer::response_compression::ResponseCompression;
+use load_balancer::retry_after::BackendBackoffs;
+use load_balancer::retry_policy::RetryPolicy;
+use load_balancer::routing_rules::routing_rules::RoutingRules;
+use load_balancer::srv_discovery::hickory_srv_resolver::HickorySrvResolver;
+use load_balancer::srv_discovery::srv_discovery::{SrvDiscovery, SrvTarget};
+use load_balancer::state_store::file_state_store::FileStateStore;
+use load_balancer::state_store::memory_state_store::MemoryStateStore;
+#[cfg(feature = "redis")]
+use load_balancer::state_store::redis_state_store::RedisStateStore;
+use load_balancer::state_store::state_store::StateStore;
+use load_balancer::target_url::normalize_target_url;
+use load_balancer::time_rules::{DayClock, SystemClock, TimeRules, parse_utc_offset};
+use load_balancer::tls::certificate_reloader::CertificateReloader;
+use load_balancer::tls::ocsp_stapler::OcspStapler;
+use load_balancer::tls::sni_resolver::SniResolver;
+use load_balancer::tls::tls_listener::TlsListener;
+use load_balancer::tls::tls_policy::{ClientAuth, TlsPolicy, TlsProfile, TlsVersion};
+use load_balancer::traffic_mirror::TrafficMirror;
+use load_balancer::upstream_compression::UpstreamDecoding;
+use load_balancer::via_headers::ViaHeaders;
 use load_balancer::{
-    RandomSelectServer, ReqwestHttpClient, RoundRobinSelectServer, SelectServer, ServerState,
-    TimedBackgroundChecker, router,
+    HttpClient, PrioritySelectServer, QuarantineSelectServer, RandomSelectServer,
+    ReqwestHttpClient, RoundRobinSelectServer, SelectServer, ServerState, TimedBackgroundChecker,
+    Weights, router,
 };
-use std::sync::Arc;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use std::iter;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
-use tracing::info;
+#[cfg(unix)]
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_rustls::rustls::ServerConfig;
+use tracing::{error, info, warn};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
@@ -22,70 +122,2252 @@ fn setup_tracing_subscriber() {
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_ansi(dev_trace::colored_output()))
         .init();
 }
 
-fn make_background_checker(args: &CliArguments) -> Arc<TimedBackgroundChecker> {
-    Arc::new(TimedBackgroundChecker::new(
-        Arc::new(ReqwestHttpClient::default()),
-        args.target_servers.clone(),
+fn make_leader_election(args: &CliArguments) -> Option<Arc<FileLeaseLeaderElection>> {
+    let lease_file = args.leader_lease_file.clone()?;
+    let advertise_address = args.advertise_address.clone()?;
+
+    Some(Arc::new(FileLeaseLeaderElection::new(
+        lease_file,
+        advertise_address,
+        Duration::from_secs(args.leader_lease_seconds),
+    )))
+}
+
+/// The replicas coordinate the rollout through the state store, which they
+/// must share: the memory one is refused.
+fn make_config_rollout(
+    args: &CliArguments,
+    state_store: Arc<dyn StateStore>,
+    leader_election: Option<Arc<FileLeaseLeaderElection>>,
+    metrics: Arc<Metrics>,
+) -> Result<Option<ConfigRollout>, String> {
+    if !args.coordinated_rollout {
+        return Ok(None);
+    }
+    if args.state_store == StateStoreKind::Memory {
+        return Err(
+            "--coordinated-rollout requires a --state-store shared by the replicas".to_string(),
+        );
+    }
+
+    Ok(leader_election.map(|leader_election| {
+        ConfigRollout::new(
+            state_store,
+            leader_election,
+            metrics,
+            Duration::from_secs(args.rollout_bake_seconds),
+            args.rollout_max_error_percent,
+        )
+    }))
+}
+
+/// The intervals the load balancer ticks at, and the timeouts that would
+/// fail every request or probe, can't be zero.
+fn check_intervals(args: &CliArguments) -> Result<(), String> {
+    let mut intervals = vec![
+        (
+            "health-checker-polling-seconds",
+            args.health_checker_polling_seconds,
+        ),
+        ("health-check-timeout-ms", args.health_check_timeout_ms),
+        (
+            "upstream-connect-timeout-ms",
+            args.upstream_connect_timeout_ms,
+        ),
+        ("upstream-timeout-ms", args.upstream_timeout_ms),
+        ("usage-bucket-seconds", args.usage_bucket_seconds),
+        (
+            "cost-budget-window-seconds",
+            args.cost_budget_window_seconds,
+        ),
+        ("srv-polling-seconds", args.srv_polling_seconds),
+    ];
+    if args.kubernetes_service.is_some() {
+        intervals.push(("kubernetes-retry-seconds", args.kubernetes_retry_seconds));
+    }
+    if args.metrics_snapshot_file.is_some() {
+        intervals.push(("metrics-snapshot-seconds", args.metrics_snapshot_seconds));
+    }
+    if args.tls_cert.is_some() {
+        intervals.push(("tls-reload-seconds", args.tls_reload_seconds));
+    }
+    if args.tls_ocsp_stapling {
+        intervals.push(("tls-ocsp-refresh-seconds", args.tls_ocsp_refresh_seconds));
+    }
+    if args.coordinated_rollout {
+        intervals.push(("rollout-polling-seconds", args.rollout_polling_seconds));
+    }
+    let pool_identities = args
+        .pools
+        .iter()
+        .filter_map(|pool| pool.parse::<PoolDefinition>().ok())
+        .any(|pool| pool.client_identity.is_some());
+    if args.upstream_client_cert.is_some() || pool_identities {
+        intervals.push((
+            "upstream-client-identity-reload-seconds",
+            args.upstream_client_identity_reload_seconds,
+        ));
+    }
+
+    let zero = intervals
+        .into_iter()
+        .filter(|(_, value)| *value == 0)
+        .map(|(flag, _)| format!("--{}", flag))
+        .collect::<Vec<_>>();
+    if zero.is_empty() {
+        return Ok(());
+    }
+
+    Err(format!("{} must be greater than 0", zero.join(", ")))
+}
+
+fn make_upstream_proxy(args: &CliArguments) -> Option<UpstreamProxy> {
+    let url = args.upstream_proxy.as_deref()?;
+
+    Some(
+        UpstreamProxy::new(url, args.upstream_no_proxy.as_deref()).expect("Invalid upstream proxy"),
+    )
+}
+
+/// The client certificate presented to the backends of `pool`, or to the
+/// target servers, if any.
+fn make_client_identity(
+    args: &CliArguments,
+    pool: Option<&PoolDefinition>,
+) -> Option<ClientIdentityFiles> {
+    pool.and_then(|pool| pool.client_identity.clone())
+        .or_else(|| {
+            args.upstream_client_cert
+                .clone()
+                .zip(args.upstream_client_key.clone())
+                .map(|(certificate, key)| ClientIdentityFiles { certificate, key })
+        })
+}
+
+/// Builds the client through `make_client`, with the client certificate
+/// when one is configured, keeping it up to date with its files for as
+/// long as the client is in use.
+fn with_client_identity(
+    args: &CliArguments,
+    identity: Option<ClientIdentityFiles>,
+    make_client: impl Fn(Option<reqwest::Identity>) -> Result<Arc<dyn HttpClient>, HttpClientError>
+    + Send
+    + Sync
+    + 'static,
+) -> Arc<dyn HttpClient> {
+    let Some(identity) = identity else {
+        return make_client(None).expect("Failed to build the upstream HTTP client");
+    };
+
+    let http_client = Arc::new(
+        ClientIdentityHttpClient::new(
+            identity,
+            Duration::from_secs(args.upstream_client_identity_reload_seconds),
+            make_client,
+        )
+        .expect("Failed to load the upstream client certificate"),
+    );
+
+    tokio::spawn(ClientIdentityHttpClient::watch(Arc::downgrade(
+        &http_client,
+    )));
+
+    http_client
+}
+
+fn make_upstream_tls(args: &CliArguments) -> UpstreamTls {
+    let upstream_tls = UpstreamTls {
+        insecure_skip_verify: args.upstream_insecure_skip_verify,
+        ..UpstreamTls::default()
+    };
+
+    match &args.upstream_ca_cert {
+        Some(path) => upstream_tls
+            .with_ca_bundle(path)
+            .expect("Failed to load the upstream CA certificates"),
+        None => upstream_tls,
+    }
+}
+
+fn make_health_check_client_builder(
+    args: &CliArguments,
+    upstream_tls: &UpstreamTls,
+) -> reqwest::ClientBuilder {
+    let mut builder = upstream_tls.apply(
+        ReqwestHttpClient::probe_client_builder(Duration::from_millis(
+            args.health_check_timeout_ms,
+        ))
+        .tls_info(true),
+    );
+
+    if args.health_check_via_proxy
+        && let Some(proxy) = make_upstream_proxy(args)
+    {
+        builder = proxy.apply(builder);
+    }
+
+    if let Some(ca_cert_path) = &args.health_check_ca_cert {
+        let pem = std::fs::read(ca_cert_path).expect("Failed to read health check CA certificate");
+        let ca_cert =
+            reqwest::Certificate::from_pem(&pem).expect("Invalid health check CA certificate");
+
+        builder = builder.add_root_certificate(ca_cert);
+    }
+
+    builder
+}
+
+/// The client probing the target servers, or the backends of `pool`, whose
+/// server name overrides the ones of the command line.
+fn make_health_check_http_client(
+    args: &CliArguments,
+    certificate_expiries: &Arc<CertificateExpiries>,
+    pool: Option<&PoolDefinition>,
+) -> Arc<dyn HttpClient> {
+    let probe_args = args.clone();
+    let server_name = pool
+        .and_then(|pool| pool.sni.as_ref())
+        .or(args.health_check_sni.as_ref())
+        .or(args.upstream_sni.as_ref())
+        .cloned();
+    let upstream_tls = make_upstream_tls(args);
+    let certificate_expiries = Arc::clone(certificate_expiries);
+
+    with_client_identity(args, make_client_identity(args, pool), move |identity| {
+        let (probe_args, upstream_tls) = (probe_args.clone(), upstream_tls.clone());
+        let make_client_builder = move || {
+            let builder = make_health_check_client_builder(&probe_args, &upstream_tls);
+
+            match &identity {
+                Some(identity) => builder.identity(identity.clone()),
+                None => builder,
+            }
+        };
+
+        Ok(match &server_name {
+            Some(server_name) => Arc::new(SniOverrideHttpClient::new(
+                make_client_builder,
+                server_name.clone(),
+            )?),
+            None => Arc::new(
+                ReqwestHttpClient::new(
+                    make_client_builder()
+                        .build()
+                        .map_err(|error| HttpClientError::InvalidRequest(error.to_string()))?,
+                )
+                .with_certificate_expiries(Arc::clone(&certificate_expiries)),
+            ),
+        })
+    })
+}
+
+fn make_background_checker(
+    args: &CliArguments,
+    leader_election: Option<Arc<FileLeaseLeaderElection>>,
+    certificate_expiries: &Arc<CertificateExpiries>,
+) -> Arc<TimedBackgroundChecker> {
+    let mut background_checker = TimedBackgroundChecker::new(
+        make_health_check_http_client(args, certificate_expiries, None),
+        target_server_urls(args),
         args.target_servers_health_path.clone(),
         Duration::from_secs(args.health_checker_polling_seconds),
-    ))
+        args.health_history_size,
+    )
+    .with_probe_concurrency(args.health_check_concurrency.into())
+    .with_probe_timeout(Duration::from_millis(args.health_check_timeout_ms))
+    .with_dependencies(args.dependency_health_urls.clone());
+
+    for url in args
+        .dependency_health_urls
+        .iter()
+        .chain(&args.health_webhook_url)
+    {
+        // Not repeated, the URL may hold a password.
+        if let Err(error) = url::Url::parse(url) {
+            panic!("Invalid health check URL: {}", error);
+        }
+    }
+
+    if args.initial_health == InitialHealth::Unhealthy {
+        background_checker = background_checker.with_servers_initially_unhealthy();
+    }
+
+    if let Some(webhook_url) = &args.health_webhook_url {
+        let webhook =
+            WebhookHealthNotifier::new(Arc::new(ReqwestHttpClient::default()), webhook_url.clone());
+        background_checker = background_checker.with_health_notifier(Arc::new(
+            QueuedHealthNotifier::new(Arc::new(webhook), 64, Duration::from_secs(5)),
+        ));
+    }
+
+    match leader_election {
+        Some(leader_election) => Arc::new(background_checker.with_leader_election(leader_election)),
+        None => Arc::new(background_checker),
+    }
+}
+
+/// The URLs of the target servers, without their settings.
+fn target_server_urls(args: &CliArguments) -> Vec<String> {
+    args.target_servers
+        .iter()
+        .map(|backend| backend.url.clone())
+        .collect()
+}
+
+fn routing_policy_name(routing_policy: &RoutingPolicy) -> &'static str {
+    match routing_policy {
+        RoutingPolicy::RoundRobin => "round-robin",
+        RoutingPolicy::Random => "random",
+    }
+}
+
+/// The weights of the target servers.
+fn target_server_weights(args: &CliArguments) -> Weights {
+    Weights(
+        args.target_servers
+            .iter()
+            .map(|backend| (backend.url.clone(), backend.weight))
+            .collect(),
+    )
 }
 
 fn make_select_server(
-    routing_policy: &RoutingPolicy,
+    args: &CliArguments,
     background_health_checker: &TimedBackgroundChecker,
+    weights: Arc<RwLock<Weights>>,
 ) -> Arc<dyn SelectServer + Send + Sync> {
-    match routing_policy {
-        RoutingPolicy::RoundRobin => Arc::new(RoundRobinSelectServer::new(
-            background_health_checker.get_healthy_servers(),
-        )),
-        RoutingPolicy::Random => Arc::new(RandomSelectServer::new(
-            background_health_checker.get_healthy_servers(),
+    let select_server: Arc<dyn SelectServer + Send + Sync> = match args.routing_policy {
+        RoutingPolicy::RoundRobin => Arc::new(
+            RoundRobinSelectServer::new(background_health_checker.get_healthy_servers())
+                .with_shared_weights(weights),
+        ),
+        RoutingPolicy::Random => Arc::new(
+            RandomSelectServer::new(background_health_checker.get_healthy_servers())
+                .with_shared_weights(weights),
+        ),
+    };
+
+    if args.quarantine_seconds == 0 {
+        return select_server;
+    }
+
+    Arc::new(QuarantineSelectServer::new(
+        select_server,
+        background_health_checker.get_healthy_servers(),
+        &target_server_urls(args),
+        Duration::from_secs(args.quarantine_seconds),
+        f64::from(args.quarantine_traffic_percent) / 100.0,
+    ))
+}
+
+/// The health checkers of the pools, by pool.
+type PoolCheckers = HashMap<String, Arc<TimedBackgroundChecker>>;
+/// The clients of the pools with upstream settings of their own, by pool.
+type PoolClients = HashMap<String, Arc<dyn HttpClient>>;
+
+/// Builds the pools of the main listener, first, and of each `--listener`,
+/// along with a health checker for each pool to run in the background once
+/// they are in use, and the clients of the pools with upstream settings of
+/// their own. Fails on an invalid pool or route, before any checker
+/// is started, so that a bad reload leaves nothing behind. A pool keeps the
+/// health its `previous` checker found for the backends it still has. The pool of the
+/// discovered backends, if any, gets the requests no route of the main
+/// listener matches unless another default pool is given, and the pools of
+/// Consul services and SRV records get the last backends found.
+fn make_pools(
+    args: &CliArguments,
+    discovered: Option<PoolDefinition>,
+    consul: &HashMap<ConsulService, DiscoveryWatch<String>>,
+    srv: &HashMap<String, DiscoveryWatch<SrvTarget>>,
+    leader_election: Option<Arc<FileLeaseLeaderElection>>,
+    certificate_expiries: &Arc<CertificateExpiries>,
+    previous: &PoolCheckers,
+) -> Result<(Vec<Pools>, PoolCheckers, PoolClients), String> {
+    if args.pools.is_empty()
+        && args.listeners.is_empty()
+        && args.blue_greens.is_empty()
+        && args.pool_routes.is_empty()
+        && args.default_pool.is_none()
+        && args.unmatched_status.is_none()
+        && discovered.is_none()
+    {
+        return Ok((vec![Pools::default()], HashMap::new(), HashMap::new()));
+    }
+
+    let default_pool = args
+        .default_pool
+        .clone()
+        .or_else(|| discovered.as_ref().map(|pool| pool.name.clone()));
+    let definitions = pool_definitions(args, discovered, consul, srv)?;
+    let blue_greens = args
+        .blue_greens
+        .iter()
+        .map(|definition| {
+            definition
+                .parse()
+                .map_err(|error| format!("Invalid blue/green service: {}", error))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let routes = make_pool_routes(args)?;
+    let day_clock = make_day_clock(args)?;
+    let listeners = make_listeners(args)?;
+
+    let http_client = make_health_check_http_client(args, certificate_expiries, None);
+    let mut background_checkers = HashMap::new();
+    let mut http_clients = HashMap::new();
+
+    let pools = definitions
+        .into_iter()
+        .map(|definition| {
+            let listener = definition.listener.clone();
+            // A pool with a server name or a client certificate of its own
+            // is probed with them.
+            let probe_client = if definition.sni.is_some() || definition.client_identity.is_some() {
+                make_health_check_http_client(args, certificate_expiries, Some(&definition))
+            } else {
+                Arc::clone(&http_client)
+            };
+            let mut background_checker = TimedBackgroundChecker::new(
+                probe_client,
+                definition.backends.clone(),
+                pool_health_path(args, &definition),
+                Duration::from_secs(args.health_checker_polling_seconds),
+                args.health_history_size,
+            )
+            .with_probe_concurrency(args.health_check_concurrency.into())
+            .with_probe_timeout(Duration::from_millis(args.health_check_timeout_ms));
+
+            if args.initial_health == InitialHealth::Unhealthy {
+                background_checker = background_checker.with_servers_initially_unhealthy();
+            }
+
+            if let Some(leader_election) = &leader_election {
+                background_checker =
+                    background_checker.with_leader_election(Arc::clone(leader_election) as _);
+            }
+
+            if let Some(previous) = previous.get(&definition.name) {
+                background_checker = background_checker.with_health_of(previous);
+            }
+
+            let pool_http_client = make_pool_http_client(args, &definition, certificate_expiries);
+            let pool = make_pool(
+                args,
+                definition,
+                background_checker.get_healthy_servers(),
+                pool_http_client.clone(),
+                srv,
+            );
+            background_checkers.insert(pool.name.clone(), Arc::new(background_checker));
+            if let Some(pool_http_client) = pool_http_client {
+                http_clients.insert(pool.name.clone(), pool_http_client);
+            }
+
+            (listener, pool)
+        })
+        .collect();
+    let split = split_by_listener(&listeners, pools, blue_greens, routes)
+        .map_err(|error| format!("Invalid pools: {}", error))?;
+
+    let pools = split
+        .into_iter()
+        .enumerate()
+        .map(|(index, listener_pools)| {
+            // The other listeners answer a 404 without a default pool.
+            let (default_pool, unmatched_status, invalid) = match index.checked_sub(1) {
+                None => (
+                    default_pool.clone(),
+                    args.unmatched_status.map(|status| match status {
+                        UnmatchedStatus::NotFound => StatusCode::NOT_FOUND,
+                        UnmatchedStatus::MisdirectedRequest => StatusCode::MISDIRECTED_REQUEST,
+                    }),
+                    "Invalid pools".to_string(),
+                ),
+                Some(listener) => (
+                    listeners[listener].default_pool.clone(),
+                    Some(StatusCode::NOT_FOUND),
+                    format!("Invalid pools of listener {}", listeners[listener].name),
+                ),
+            };
+
+            let pools = Pools::new(
+                listener_pools.pools,
+                listener_pools.blue_greens,
+                listener_pools.routes,
+                default_pool.as_deref(),
+            )
+            .map_err(|error| format!("{}: {}", invalid, error))?
+            .with_day_clock(day_clock.clone());
+
+            Ok(match unmatched_status {
+                Some(status) => pools.with_unmatched_status(status),
+                None => pools,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok((pools, background_checkers, http_clients))
+}
+
+/// The pools defined on the command line and in the file, then the pool of
+/// the discovered backends, if any, the pools of Consul services and SRV
+/// records getting the last backends found.
+fn pool_definitions(
+    args: &CliArguments,
+    discovered: Option<PoolDefinition>,
+    consul: &HashMap<ConsulService, DiscoveryWatch<String>>,
+    srv: &HashMap<String, DiscoveryWatch<SrvTarget>>,
+) -> Result<Vec<PoolDefinition>, String> {
+    args.pools
+        .iter()
+        .map(|definition| {
+            definition
+                .parse::<PoolDefinition>()
+                .map_err(|error| format!("Invalid pool: {}", error))
+        })
+        .chain(discovered.map(Ok))
+        .map(|definition| {
+            let mut definition = definition?;
+            if let Some(watch) = definition
+                .consul
+                .as_ref()
+                .and_then(|service| consul.get(service))
+            {
+                definition.backends = watch.backends.clone();
+            }
+            if let Some(watch) = definition.srv.as_ref().and_then(|name| srv.get(name)) {
+                definition.backends = watch
+                    .backends
+                    .iter()
+                    .map(|target| target.url.clone())
+                    .collect();
+            }
+            Ok(definition)
+        })
+        .collect()
+}
+
+/// The pool of `definition`, picking among the `healthy_servers` its health
+/// checker finds with its policy, by the weight and priority of their SRV
+/// targets if any.
+fn make_pool(
+    args: &CliArguments,
+    definition: PoolDefinition,
+    healthy_servers: Arc<RwLock<Vec<String>>>,
+    http_client: Option<Arc<dyn HttpClient>>,
+    srv: &HashMap<String, DiscoveryWatch<SrvTarget>>,
+) -> Pool {
+    let targets = definition
+        .srv
+        .as_ref()
+        .and_then(|name| srv.get(name))
+        .map(|watch| &watch.backends);
+    let weights = Weights(
+        targets
+            .into_iter()
+            .flatten()
+            .map(|target| (target.url.clone(), target.weight))
+            .collect(),
+    );
+    let select_server: Arc<dyn SelectServer + Send + Sync> = match definition.policy {
+        PoolPolicy::RoundRobin => Arc::new(
+            RoundRobinSelectServer::new(Arc::clone(&healthy_servers)).with_weights(weights),
+        ),
+        PoolPolicy::Random => {
+            Arc::new(RandomSelectServer::new(Arc::clone(&healthy_servers)).with_weights(weights))
+        }
+    };
+    let select_server: Arc<dyn SelectServer> = match targets {
+        Some(targets) => Arc::new(PrioritySelectServer::new(
+            select_server,
+            Arc::clone(&healthy_servers),
+            targets
+                .iter()
+                .map(|target| (target.url.clone(), target.priority))
+                .collect(),
         )),
+        None => select_server,
+    };
+
+    Pool {
+        health_path: pool_health_path(args, &definition),
+        allowed_methods: definition.allowed_methods,
+        http_client,
+        location_rewrite: definition.location_rewrite,
+        host_header: definition.host_header,
+        name: definition.name,
+        target_servers: Arc::new(definition.backends),
+        healthy_servers,
+        select_server,
+    }
+}
+
+/// The client of the backends of a pool with upstream settings of its own,
+/// `None` for the pools sharing the one of the target servers.
+fn make_pool_http_client(
+    args: &CliArguments,
+    definition: &PoolDefinition,
+    certificate_expiries: &Arc<CertificateExpiries>,
+) -> Option<Arc<dyn HttpClient>> {
+    (definition.http_version.is_some()
+        || definition.sni.is_some()
+        || definition.client_identity.is_some())
+    .then(|| make_http_client(args, certificate_expiries, Some(definition)))
+}
+
+/// The path the backends of the pool are probed on, the one of the target
+/// servers unless it has its own.
+fn pool_health_path(args: &CliArguments, definition: &PoolDefinition) -> String {
+    definition
+        .health_path
+        .clone()
+        .unwrap_or_else(|| args.target_servers_health_path.clone())
+}
+
+/// The listeners next to the main one, in order.
+fn make_listeners(args: &CliArguments) -> Result<Vec<ListenerDefinition>, String> {
+    let listeners = args
+        .listeners
+        .iter()
+        .map(|listener| listener.parse())
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|listeners| check_ports(args.port, &listeners).map(|_| listeners))
+        .map_err(|error| format!("Invalid listener: {}", error))?;
+
+    if let Some(admin_listener) = &args.admin_listener
+        && !listeners
+            .iter()
+            .any(|listener| &listener.name == admin_listener)
+    {
+        return Err(format!("Unknown admin listener {}", admin_listener));
+    }
+
+    Ok(listeners)
+}
+
+fn kubernetes_service(args: &CliArguments) -> Option<KubernetesService> {
+    let service = args.kubernetes_service.as_ref()?;
+
+    Some(
+        service
+            .parse()
+            .unwrap_or_else(|error| panic!("Invalid Kubernetes service: {}", error)),
+    )
+}
+
+fn make_kubernetes_discovery(args: &CliArguments) -> Option<EndpointSliceDiscovery> {
+    let service = kubernetes_service(args)?;
+
+    Some(
+        EndpointSliceDiscovery::in_cluster(
+            service,
+            Duration::from_secs(args.kubernetes_retry_seconds),
+        )
+        .unwrap_or_else(|error| panic!("Can't discover Kubernetes services: {}", error)),
+    )
+}
+
+/// The pool of the backends discovered for a Kubernetes service, named
+/// after it and picking among them with the routing policy.
+fn discovered_pool(args: &CliArguments, service: &str, backends: Vec<String>) -> PoolDefinition {
+    PoolDefinition {
+        name: service.to_string(),
+        backends,
+        policy: match args.routing_policy {
+            RoutingPolicy::RoundRobin => PoolPolicy::RoundRobin,
+            RoutingPolicy::Random => PoolPolicy::Random,
+        },
+        health_path: None,
+        consul: None,
+        srv: None,
+        listener: None,
+        allowed_methods: AllowedMethods::default(),
+        http_version: None,
+        location_rewrite: None,
+        host_header: None,
+        sni: None,
+        client_identity: None,
+    }
+}
+
+/// The Consul services the pools take their backends from.
+fn consul_services(args: &CliArguments) -> Vec<ConsulService> {
+    let mut services = Vec::new();
+
+    for definition in &args.pools {
+        if let Ok(PoolDefinition {
+            consul: Some(service),
+            ..
+        }) = definition.parse()
+            && !services.contains(&service)
+        {
+            services.push(service);
+        }
+    }
+
+    services
+}
+
+fn make_consul_discovery(args: &CliArguments, service: ConsulService) -> ConsulDiscovery {
+    if let Err(error) = url::Url::parse(&args.consul_address) {
+        panic!("Invalid Consul address: {}", error);
+    }
+
+    let client = ConsulDiscovery::client_builder()
+        .build()
+        .expect("Failed to build the Consul client");
+    let discovery = ConsulDiscovery::new(
+        Arc::new(ReqwestHttpClient::new(client)),
+        args.consul_address.clone(),
+        service,
+    );
+
+    match &args.consul_token {
+        Some(token) => discovery.with_token(token.clone()),
+        None => discovery,
+    }
+}
+
+/// The SRV records the pools take their backends from.
+fn srv_records(args: &CliArguments) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for definition in &args.pools {
+        if let Ok(PoolDefinition {
+            srv: Some(name), ..
+        }) = definition.parse()
+            && !names.contains(&name)
+        {
+            names.push(name);
+        }
+    }
+
+    names
+}
+
+fn make_srv_discovery(args: &CliArguments, name: String) -> Result<SrvDiscovery, String> {
+    Ok(SrvDiscovery::new(
+        Arc::new(HickorySrvResolver::from_system_config()?),
+        name,
+        Duration::from_secs(args.srv_polling_seconds),
+    ))
+}
+
+fn make_day_clock(args: &CliArguments) -> Result<DayClock, String> {
+    Ok(DayClock {
+        clock: Arc::new(SystemClock),
+        utc_offset_minutes: parse_utc_offset(&args.time_rules_utc_offset)
+            .map_err(|error| format!("Invalid time rules UTC offset: {}", error))?,
+    })
+}
+
+fn make_geo_ip(args: &CliArguments) -> GeoIp {
+    GeoIp {
+        locator: args.geoip_database.as_deref().map(make_geo_locator),
+        trust_forwarded_for: args.trust_forwarded_headers,
+        trusted_proxies: make_trusted_proxies(args),
+    }
+}
+
+fn make_trusted_proxies(args: &CliArguments) -> TrustedProxies {
+    TrustedProxies(
+        args.trusted_proxies
+            .iter()
+            .map(|network| {
+                network
+                    .parse()
+                    .unwrap_or_else(|error| panic!("Invalid trusted proxy: {}", error))
+            })
+            .collect(),
+    )
+}
+
+fn make_experiment(args: &CliArguments) -> Experiment {
+    let Some(variants) = &args.experiment else {
+        return Experiment::default();
+    };
+
+    Experiment {
+        salt: args.experiment_salt.clone().unwrap_or_default(),
+        cookie: args.experiment_cookie.clone(),
+        trust_forwarded_for: args.trust_forwarded_headers,
+        trusted_proxies: make_trusted_proxies(args),
+        ..variants
+            .parse()
+            .unwrap_or_else(|error| panic!("Invalid experiment: {}", error))
+    }
+}
+
+#[cfg(feature = "geoip")]
+fn make_geo_locator(path: &Path) -> Arc<dyn GeoLocator> {
+    Arc::new(
+        MaxMindGeoLocator::open(path).unwrap_or_else(|error| {
+            panic!("Failed to open the GeoIP database {:?}: {}", path, error)
+        }),
+    )
+}
+
+#[cfg(not(feature = "geoip"))]
+fn make_geo_locator(path: &Path) -> Arc<dyn GeoLocator> {
+    panic!("--geoip-database {:?} requires the `geoip` feature", path)
+}
+
+fn make_pool_routes(args: &CliArguments) -> Result<Vec<PoolRoute>, String> {
+    args.pool_routes
+        .iter()
+        .map(|route| {
+            route
+                .parse()
+                .map_err(|error| format!("Invalid pool route: {}", error))
+        })
+        .collect()
+}
+
+fn make_path_rules(args: &CliArguments) -> Result<PathRules, String> {
+    Ok(PathRules {
+        rules: args
+            .path_rules
+            .iter()
+            .map(|rule| {
+                rule.parse()
+                    .map_err(|error| format!("Invalid path rule {}: {}", rule, error))
+            })
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// The settings applied again by a reload, the others keeping the values
+/// the load balancer started with.
+const RELOADED_SETTINGS: [&str; 6] = [
+    "target_servers",
+    "pools",
+    "blue_greens",
+    "pool_routes",
+    "default_pool",
+    "unmatched_status",
+];
+
+/// Identifies the settings a reload applies, the same on every replica
+/// reading the same configuration.
+fn config_revision(args: &CliArguments) -> Result<String, String> {
+    let config = effective_config(args)?;
+    let mut hasher = Sha1::new();
+    for setting in RELOADED_SETTINGS {
+        hasher.update(config[setting].to_string());
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The configuration resolved from the command line, the config file and
+/// the defaults, its secrets redacted.
+fn effective_config(args: &CliArguments) -> Result<serde_json::Value, String> {
+    serde_json::to_value(args).map_err(|error| error.to_string())
+}
+
+/// Prints the effective configuration, to check what the load balancer
+/// would run with without starting it.
+fn print_config(args: &CliArguments, format: ConfigFormat) {
+    let config = match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(args).map_err(|error| error.to_string()),
+        ConfigFormat::Toml => toml::to_string(args).map_err(|error| error.to_string()),
+    };
+
+    println!(
+        "{}",
+        config.unwrap_or_else(|error| panic!("Can't print the configuration: {}", error))
+    );
+}
+
+/// Reports whether the configuration is valid, then exits, with status 1
+/// when it isn't.
+async fn check(matches: &ArgMatches) -> ! {
+    let problems = match arguments(matches) {
+        Ok(args) => {
+            // The problems are reported below, not as panics.
+            std::panic::set_hook(Box::new(|_| {}));
+            check_config(&args).await
+        }
+        Err(error) => vec![error],
+    };
+
+    if problems.is_empty() {
+        println!("Configuration OK");
+        process::exit(0);
+    }
+
+    for problem in &problems {
+        eprintln!("error: {}", problem);
+    }
+    process::exit(1);
+}
+
+/// Prints the pool route and the path rule a sample path or URL, optionally
+/// preceded by its method as in `POST /orders`, matches, to try the rules out
+/// without starting the load balancer.
+fn print_matching_rules(args: &CliArguments, sample: &str) -> Result<(), String> {
+    let (method, uri) = match sample.trim().split_once(' ') {
+        Some((method, uri)) => (method.to_ascii_uppercase(), uri.trim()),
+        None => ("GET".to_string(), sample.trim()),
+    };
+    let method: Method = method
+        .parse()
+        .map_err(|error| format!("Invalid sample method {}: {}", method, error))?;
+    let uri: Uri = uri
+        .parse()
+        .map_err(|error| format!("Invalid sample path {}: {}", uri, error))?;
+
+    // Scheduled routes are tried as they would be right now.
+    let minute_of_day = make_day_clock(args)?.minute_of_day();
+    let pool_routes = make_pool_routes(args)?;
+    let pool_route = pool_routes.iter().position(|route| {
+        route.matches(&method, &uri, &HeaderMap::new())
+            && route
+                .schedule
+                .is_none_or(|window| window.contains(minute_of_day))
+    });
+    match (pool_route, &args.default_pool) {
+        (Some(index), _) if !pool_routes[index].allowed_methods.allows(&method) => println!(
+            "pool route {}: {}, answered 405",
+            index + 1,
+            args.pool_routes[index]
+        ),
+        (Some(index), _) => println!("pool route {}: {}", index + 1, args.pool_routes[index]),
+        (None, Some(default_pool)) => println!("pool route: none, default pool {}", default_pool),
+        (None, None) => match args.unmatched_status {
+            Some(UnmatchedStatus::NotFound) => println!("pool route: none, answered 404"),
+            Some(UnmatchedStatus::MisdirectedRequest) => {
+                println!("pool route: none, answered 421")
+            }
+            None => println!("pool route: none"),
+        },
+    }
+
+    let path_and_query = uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+    match make_path_rules(args)?.matching(path_and_query) {
+        Some((index, rewritten)) => println!(
+            "path rule {}: {}, forwarded as {}",
+            index + 1,
+            args.path_rules[index],
+            rewritten
+        ),
+        None => println!("path rule: none"),
+    }
+
+    Ok(())
+}
+
+async fn make_metrics(args: &CliArguments) -> Arc<Metrics> {
+    let metrics = Arc::new(Metrics::default());
+
+    if let Some(snapshot_file) = &args.metrics_snapshot_file {
+        let snapshotter = MetricsSnapshotter::new(
+            Arc::clone(&metrics),
+            snapshot_file.clone(),
+            Duration::from_secs(args.metrics_snapshot_seconds),
+        );
+
+        if let Err(error) = snapshotter.load().await {
+            error!("Failed to restore metrics snapshot: {}", error);
+        }
+
+        tokio::spawn(async move {
+            snapshotter.execute().await;
+        });
+    }
+
+    metrics
+}
+
+/// Drops the counters of the tenants without usage over the retained usage
+/// buckets, checked once per bucket.
+fn spawn_idle_tenants_expiry(args: &CliArguments, metrics: Arc<Metrics>) {
+    if args.tenant_header.is_none() {
+        return;
+    }
+
+    let bucket = Duration::from_secs(args.usage_bucket_seconds.max(1));
+    let idle = bucket.saturating_mul(args.usage_retention_buckets.max(1) as u32);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(bucket);
+        loop {
+            interval.tick().await;
+            metrics.expire_idle_tenants(idle);
+        }
+    });
+}
+
+fn make_usage_tracker(args: &CliArguments) -> Arc<UsageTracker> {
+    Arc::new(
+        UsageTracker::new(
+            args.tenant_header.clone(),
+            Duration::from_secs(args.usage_bucket_seconds),
+            args.usage_retention_buckets,
+        )
+        .with_named_tenants(args.cost_budgets.iter().map(|(tenant, _)| tenant.as_str())),
+    )
+}
+
+/// Where `--state-store` keeps the state, told apart from opening it so
+/// that `check` doesn't need to reach it.
+enum StateStoreLocation {
+    Memory,
+    File(PathBuf),
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    Redis(String),
+}
+
+fn state_store_location(args: &CliArguments) -> StateStoreLocation {
+    match args.state_store {
+        StateStoreKind::Memory => StateStoreLocation::Memory,
+        StateStoreKind::File => StateStoreLocation::File(
+            args.state_store_path
+                .clone()
+                .expect("--state-store-path is required by the file state store"),
+        ),
+        #[cfg(feature = "redis")]
+        StateStoreKind::Redis => {
+            let url = args.state_store_url.clone().expect(
+                "--state-store-url or --state-store-url-file is required by the redis state store",
+            );
+            // Not repeated, the URL may hold a password.
+            if let Err(error) = url::Url::parse(&url) {
+                panic!("Invalid state store URL: {}", error);
+            }
+
+            StateStoreLocation::Redis(url)
+        }
+        #[cfg(not(feature = "redis"))]
+        StateStoreKind::Redis => panic!("The redis state store requires the `redis` feature"),
+    }
+}
+
+async fn make_state_store(location: StateStoreLocation) -> Arc<dyn StateStore> {
+    match location {
+        StateStoreLocation::Memory => Arc::new(MemoryStateStore::default()),
+        StateStoreLocation::File(path) => Arc::new(
+            FileStateStore::open(path)
+                .await
+                .expect("Failed to open the state store file"),
+        ),
+        #[cfg(feature = "redis")]
+        StateStoreLocation::Redis(url) => Arc::new(
+            RedisStateStore::connect(&url, "wakanda-lb:".to_string())
+                .await
+                .expect("Failed to connect to the redis state store"),
+        ),
+        #[cfg(not(feature = "redis"))]
+        StateStoreLocation::Redis(_) => {
+            unreachable!("The redis state store requires the `redis` feature")
+        }
+    }
+}
+
+/// The client of the target servers, or of the backends of `pool`, whose
+/// settings override the ones of the command line.
+fn make_http_client(
+    args: &CliArguments,
+    certificate_expiries: &Arc<CertificateExpiries>,
+    pool: Option<&PoolDefinition>,
+) -> Arc<dyn HttpClient> {
+    let timeouts = UpstreamTimeouts {
+        connect: Duration::from_millis(args.upstream_connect_timeout_ms),
+        request: Duration::from_millis(args.upstream_timeout_ms),
+        idle: Duration::from_secs(args.upstream_idle_timeout_seconds),
+    };
+    let overrides = args
+        .backend_timeouts
+        .iter()
+        .map(|(backend, millis)| (backend.clone(), Duration::from_millis(*millis)))
+        .collect::<Vec<_>>();
+
+    let protocol =
+        pool.and_then(|pool| pool.http_version)
+            .unwrap_or(match args.upstream_http_version {
+                UpstreamHttpVersion::Auto => UpstreamProtocol::Auto,
+                UpstreamHttpVersion::Http1 => UpstreamProtocol::Http1,
+                UpstreamHttpVersion::Http2 => UpstreamProtocol::Http2,
+            });
+    let upstream_sni = pool
+        .and_then(|pool| pool.sni.clone())
+        .or_else(|| args.upstream_sni.clone());
+    let client_identity = make_client_identity(args, pool);
+
+    let seconds = |seconds: u64| (seconds > 0).then(|| Duration::from_secs(seconds));
+    let pool = UpstreamPool {
+        max_idle_per_host: args.upstream_max_idle_per_host,
+        tcp_keepalive: seconds(args.upstream_tcp_keepalive_seconds),
+        http2_keepalive: seconds(args.upstream_http2_keepalive_seconds),
+    };
+
+    let proxy = make_upstream_proxy(args);
+    let upstream_tls = make_upstream_tls(args);
+    let certificate_expiries = Arc::clone(certificate_expiries);
+
+    if upstream_tls.insecure_skip_verify {
+        warn!(
+            "--upstream-insecure-skip-verify is set: the certificates of the backends are NOT verified, anyone on the path can impersonate them"
+        );
+    }
+
+    with_client_identity(args, client_identity, move |identity| {
+        let (upstream_tls, proxy) = (upstream_tls.clone(), proxy.clone());
+        let make_client_builder = move || {
+            let mut builder = upstream_tls.apply(
+                pool.apply(protocol.apply(ReqwestHttpClient::upstream_client_builder()))
+                    .tls_info(true),
+            );
+
+            if let Some(proxy) = &proxy {
+                builder = proxy.apply(builder);
+            }
+
+            match &identity {
+                Some(identity) => builder.identity(identity.clone()),
+                None => builder,
+            }
+        };
+
+        let http_client: Arc<dyn HttpClient> = match &upstream_sni {
+            Some(server_name) => Arc::new(SniOverrideHttpClient::new(
+                move || timeouts.apply(make_client_builder()),
+                server_name.clone(),
+            )?),
+            None => Arc::new(
+                ReqwestHttpClient::new(
+                    timeouts
+                        .apply(make_client_builder())
+                        .build()
+                        .map_err(|error| HttpClientError::InvalidRequest(error.to_string()))?,
+                )
+                .with_certificate_expiries(Arc::clone(&certificate_expiries)),
+            ),
+        };
+
+        Ok(Arc::new(TimeoutOverrideHttpClient::new(
+            http_client,
+            timeouts.request,
+            overrides.clone(),
+        )) as Arc<dyn HttpClient>)
+    })
+}
+
+fn make_upstream_decoding(args: &CliArguments) -> UpstreamDecoding {
+    match args.upstream_decoding {
+        UpstreamDecodingKind::Passthrough => UpstreamDecoding::Passthrough,
+        UpstreamDecodingKind::Recompress => {
+            if !args.response_compression {
+                warn!(
+                    "--upstream-decoding recompress without --response-compression sends the responses decoded"
+                );
+            }
+            UpstreamDecoding::Recompress
+        }
+    }
+}
+
+fn make_time_rules(args: &CliArguments) -> TimeRules {
+    TimeRules {
+        rules: args
+            .time_rules
+            .iter()
+            .map(|rule| rule.parse().expect("Invalid time rule"))
+            .collect(),
+        utc_offset_minutes: parse_utc_offset(&args.time_rules_utc_offset)
+            .expect("Invalid time rules UTC offset"),
+    }
+}
+
+fn make_routing_rules(args: &CliArguments) -> RoutingRules {
+    RoutingRules {
+        rules: args
+            .route_rules
+            .iter()
+            .map(|rule| {
+                rule.parse()
+                    .unwrap_or_else(|error| panic!("Invalid routing rule {}: {}", rule, error))
+            })
+            .collect(),
+    }
+}
+
+fn make_forwarded_headers(args: &CliArguments) -> ForwardedHeaders {
+    if args.trust_forwarded_headers && args.trusted_proxies.is_empty() {
+        warn!("The incoming X-Forwarded-* headers are overwritten without --trusted-proxies");
+    }
+
+    ForwardedHeaders {
+        trust_incoming: args.trust_forwarded_headers,
+        trusted_proxies: make_trusted_proxies(args),
+        emit_forwarded: args.emit_forwarded_header,
+    }
+}
+
+fn make_cost_budgets(args: &CliArguments, state_store: Arc<dyn StateStore>) -> CostBudgets {
+    if !args.cost_budgets.is_empty() && args.tenant_header.is_none() {
+        warn!("Cost budgets are ignored without --tenant-header");
+    }
+
+    CostBudgets::new(
+        args.cost_budgets
+            .iter()
+            .map(|(tenant, cost)| match tenant.as_str() {
+                ANY_TENANT => (tenant.clone(), *cost),
+                tenant => (pseudonym(tenant), *cost),
+            })
+            .collect(),
+        Duration::from_secs(args.cost_budget_window_seconds),
+        match args.cost_budget_action {
+            CostBudgetActionKind::Reject => CostBudgetAction::Reject,
+            CostBudgetActionKind::Throttle => {
+                CostBudgetAction::Throttle(Duration::from_millis(args.cost_throttle_delay_ms))
+            }
+        },
+        state_store,
+    )
+}
+
+fn make_client_certificate_rules(args: &CliArguments) -> ClientCertificateRules {
+    ClientCertificateRules {
+        allow: args
+            .client_cert_allow
+            .iter()
+            .map(|matcher| matcher.parse().expect("Invalid client certificate matcher"))
+            .collect(),
+        routes: args
+            .client_cert_routes
+            .iter()
+            .map(|route| route.parse().expect("Invalid client certificate route"))
+            .collect(),
+        forwarded_attributes: args
+            .client_cert_headers
+            .iter()
+            .map(|attribute| {
+                attribute
+                    .parse()
+                    .expect("Invalid client certificate attribute")
+            })
+            .collect(),
+    }
+}
+
+fn make_fallback_response(args: &CliArguments) -> Option<FallbackResponse> {
+    let mut page = match (&args.fallback_file, &args.fallback_body) {
+        (Some(path), _) => ErrorPage::load(Path::new(path))
+            .unwrap_or_else(|error| panic!("Failed to read {}: {}", path, error)),
+        (None, Some(body)) => ErrorPage::inline(body.clone()),
+        (None, None) => return None,
+    };
+    if let Some(content_type) = &args.fallback_content_type {
+        page.content_type =
+            HeaderValue::from_str(content_type).expect("Invalid fallback content type");
+    }
+
+    Some(FallbackResponse {
+        status: StatusCode::from_u16(args.fallback_status).expect("Invalid fallback status"),
+        page,
+    })
+}
+
+fn make_error_pages(args: &CliArguments) -> ErrorPages {
+    ErrorPages(
+        args.error_pages
+            .iter()
+            .map(|source| {
+                let source = source
+                    .parse::<ErrorPageSource>()
+                    .expect("Invalid error page");
+                let page = ErrorPage::load(Path::new(&source.path))
+                    .unwrap_or_else(|error| panic!("Failed to read {}: {}", source.path, error));
+
+                (source.status, page)
+            })
+            .collect(),
+    )
+}
+
+fn make_response_cache(args: &CliArguments, metrics: &Arc<Metrics>) -> Option<ResponseCache> {
+    args.response_cache_entries.map(|entries| {
+        ResponseCache::new(
+            entries,
+            args.response_cache_max_body_bytes,
+            Arc::clone(metrics),
+        )
+    })
+}
+
+/// The state of a listener next to the main one, `state`. It shares its
+/// settings but for its pools and its own response cache, while the rules
+/// picking among the target servers, which it never forwards to, are left
+/// to the main listener.
+fn make_listener_state(
+    args: &CliArguments,
+    state: &ServerState,
+    listener: &ListenerDefinition,
+    pools: &Arc<SwappablePools>,
+) -> ServerState {
+    ServerState {
+        pools: Arc::clone(pools),
+        admin_api: args.admin_listener.as_ref() == Some(&listener.name),
+        routing_rules: Arc::new(RoutingRules::default()),
+        path_rules: PathRules::default(),
+        time_rules: TimeRules::default(),
+        response_cache: make_response_cache(args, &state.metrics)
+            .map(|response_cache| Arc::new(response_cache.with_listener(&listener.name))),
+        ..state.clone()
     }
 }
 
-fn make_server_state(select_server: Arc<dyn SelectServer + Send + Sync>) -> ServerState {
-    let http_client = Arc::new(ReqwestHttpClient::default());
+fn make_server_state(
+    args: &CliArguments,
+    select_server: Arc<dyn SelectServer + Send + Sync>,
+    background_health_checker: &TimedBackgroundChecker,
+    metrics: Arc<Metrics>,
+    usage: Arc<UsageTracker>,
+    state_store: Arc<dyn StateStore>,
+    certificate_expiries: Arc<CertificateExpiries>,
+) -> ServerState {
+    let http_client = make_http_client(args, &certificate_expiries, None);
+    let request_coalescing = args
+        .coalesce_requests
+        .then(|| Arc::new(RequestCoalescing::new(Arc::clone(&metrics))));
+    let response_cache = make_response_cache(args, &metrics).map(Arc::new);
     ServerState {
+        target_servers: Arc::new(RwLock::new(target_server_urls(args))),
         http_client,
+        probe_client: make_health_check_http_client(args, &certificate_expiries, None),
         select_server,
+        health_history: background_health_checker.get_health_history(),
+        healthy_servers: background_health_checker.get_healthy_servers(),
+        metrics,
+        usage,
+        annotations: Arc::new(Annotations::default()),
+        admin_auth: AdminAuth::new(args.admin_token.clone()),
+        maintenance: Arc::new(Maintenance::default()),
+        retries: args.retries.into(),
+        backend_backoffs: Arc::new(BackendBackoffs::default()),
+        propagate_retry_after: args.propagate_retry_after,
+        retry_policy: RetryPolicy {
+            idempotent_methods: args.retry_methods.clone(),
+            idempotency_key_methods: args.retry_idempotency_key_methods.clone(),
+        },
+        forwarded_headers: make_forwarded_headers(args),
+        scheme: match args.tls_cert {
+            Some(_) => "https",
+            None => "http",
+        },
+        decision_records: DecisionRecords {
+            policy: routing_policy_name(&args.routing_policy),
+            // Every request is traced in dev mode.
+            sample_ratio: if args.dev {
+                1.0
+            } else {
+                f64::from(args.decision_record_sample_percent) / 100.0
+            },
+            emit_header: args.decision_record_header,
+        },
+        http10_compat: args.http10_compat,
+        allowed_methods: AllowedMethods(args.allowed_methods.clone()),
+        time_rules: make_time_rules(args),
+        request_queue_time: args.request_queue_time,
+        deadline_propagation: args.deadline_propagation,
+        upstream_compression: args.upstream_compression,
+        upstream_decoding: make_upstream_decoding(args),
+        response_compression: args.response_compression.then(|| ResponseCompression {
+            min_size: args.response_compression_min_bytes,
+            content_types: Arc::new(args.response_compression_content_types.clone()),
+        }),
+        certificate_expiries,
+        max_request_body_bytes: args.max_request_body_bytes,
+        retry_after_seconds: args.health_checker_polling_seconds,
+        client_certificate_rules: make_client_certificate_rules(args),
+        traffic_mirror: TrafficMirror::new(
+            args.mirror_servers.clone(),
+            f64::from(args.mirror_percent) / 100.0,
+        )
+        .with_max_in_flight(args.mirror_max_in_flight),
+        via_headers: ViaHeaders {
+            served_by: args.served_by_header,
+        },
+        location_rewrite: LocationRewrite {
+            enabled: args.rewrite_redirects,
+        },
+        body_limit_action: match args.body_limit_action {
+            BodyLimitActionKind::Abort => BodyLimitAction::Abort,
+            BodyLimitActionKind::Drain => BodyLimitAction::Drain,
+        },
+        error_pages: Arc::new(make_error_pages(args)),
+        fallback_response: make_fallback_response(args),
+        dev_mode: args.dev,
+        routing_rules: Arc::new(make_routing_rules(args)),
+        cost_budgets: Arc::new(make_cost_budgets(args, state_store)),
+        host_header: match args.host_header {
+            HostHeaderKind::Preserve => HostHeader::Preserve,
+            HostHeaderKind::Upstream => HostHeader::Upstream,
+        },
+        path_rules: make_path_rules(args).unwrap_or_else(|error| panic!("{}", error)),
+        request_transforms: RequestTransforms(
+            args.request_transforms
+                .iter()
+                .map(|transform| transform.parse().expect("Invalid request transform"))
+                .collect(),
+        ),
+        range_requests: match args.range_requests {
+            RangeRequestsKind::Pass => RangeRequests::Pass,
+            RangeRequestsKind::Reject => RangeRequests::Reject,
+        },
+        request_coalescing,
+        response_cache,
+        admin_api: args.admin_listener.is_none(),
+        bulkheads: Arc::new(Bulkheads::new(
+            args.max_in_flight_per_backend,
+            args.backend_max_in_flight.iter().cloned().collect(),
+        )),
+        connection_recycling: ConnectionRecycling {
+            max_requests: (args.downstream_max_requests > 0)
+                .then_some(args.downstream_max_requests),
+            max_age: (args.downstream_max_connection_age_seconds > 0)
+                .then(|| Duration::from_secs(args.downstream_max_connection_age_seconds)),
+        },
+        pools: Arc::new(SwappablePools::default()),
+        effective_config: Arc::new(EffectiveConfig::default()),
+        geo_ip: make_geo_ip(args),
+        experiment: make_experiment(args),
+        grpc_timeouts: GrpcTimeouts(
+            args.grpc_timeouts
+                .iter()
+                .map(|timeout| {
+                    timeout
+                        .parse()
+                        .unwrap_or_else(|error| panic!("Invalid gRPC timeout: {}", error))
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn make_tls_policy(args: &CliArguments) -> TlsPolicy {
+    TlsPolicy {
+        profile: match args.tls_profile {
+            TlsProfileKind::Modern => TlsProfile::Modern,
+            TlsProfileKind::Intermediate => TlsProfile::Intermediate,
+            TlsProfileKind::Old => TlsProfile::Old,
+        },
+        min_version: args.tls_min_version.as_ref().map(|version| match version {
+            TlsMinVersion::Tls12 => TlsVersion::Tls12,
+            TlsMinVersion::Tls13 => TlsVersion::Tls13,
+        }),
+        cipher_suites: args.tls_cipher_suites.clone(),
+        client_auth: match args.tls_client_auth {
+            ClientAuthMode::None => ClientAuth::None,
+            ClientAuthMode::Optional => ClientAuth::Optional,
+            ClientAuthMode::Required => ClientAuth::Required,
+        },
+        client_ca: args.tls_client_ca.clone(),
     }
 }
 
-fn spawn_background_health_checker(background_health_checker: Arc<TimedBackgroundChecker>) {
+/// The TLS configuration of the listeners, along with their certificates,
+/// to be watched with `spawn_certificate_reloaders`.
+async fn make_tls_config(
+    args: &CliArguments,
+) -> Option<(Arc<ServerConfig>, Vec<Arc<CertificateReloader>>)> {
+    let Some((certificate_path, key_path)) = args.tls_cert.clone().zip(args.tls_key.clone()) else {
+        assert!(
+            args.tls_sni_certs.is_empty(),
+            "--tls-sni-cert needs a default certificate, --tls-cert"
+        );
+        return None;
+    };
+
+    let default = load_certificate(args, certificate_path, key_path).await;
+    let mut certificates: Vec<(String, Arc<CertificateReloader>)> = Vec::new();
+    for (domain, certificate_path, key_path) in &args.tls_sni_certs {
+        assert!(
+            certificates.iter().all(|(other, _)| other != domain),
+            "Domain {} has several certificates",
+            domain
+        );
+        let reloader = load_certificate(args, certificate_path.clone(), key_path.clone()).await;
+        certificates.push((domain.clone(), reloader));
+    }
+
+    let reloaders = iter::once(Arc::clone(&default))
+        .chain(
+            certificates
+                .iter()
+                .map(|(_, reloader)| Arc::clone(reloader)),
+        )
+        .collect();
+    let config = Arc::new(SniResolver::new(default, certificates))
+        .server_config(&make_tls_policy(args))
+        .expect("Failed to build the TLS configuration");
+
+    Some((Arc::new(config), reloaders))
+}
+
+async fn load_certificate(
+    args: &CliArguments,
+    certificate_path: PathBuf,
+    key_path: PathBuf,
+) -> Arc<CertificateReloader> {
+    Arc::new(
+        CertificateReloader::load(
+            certificate_path.clone(),
+            key_path,
+            Duration::from_secs(args.tls_reload_seconds),
+        )
+        .await
+        .unwrap_or_else(|error| {
+            panic!(
+                "Failed to load the TLS certificate {}: {}",
+                certificate_path.display(),
+                error
+            )
+        }),
+    )
+}
+
+/// Watches the certificates of the listeners for renewals, stapling them
+/// with their OCSP response when asked to.
+fn spawn_certificate_reloaders(args: &CliArguments, reloaders: Vec<Arc<CertificateReloader>>) {
+    for certificate_reloader in reloaders {
+        if args.tls_ocsp_stapling {
+            let ocsp_stapler = OcspStapler::new(
+                Arc::clone(&certificate_reloader),
+                Arc::new(ReqwestHttpClient::default()),
+                Duration::from_secs(args.tls_ocsp_refresh_seconds),
+            );
+
+            tokio::spawn(async move {
+                ocsp_stapler.execute().await;
+            });
+        }
+
+        tokio::spawn(async move {
+            certificate_reloader.execute().await;
+        });
+    }
+}
+
+fn spawn_leader_election(leader_election: Arc<FileLeaseLeaderElection>) {
+    tokio::spawn(async move {
+        leader_election.execute().await;
+    });
+}
+
+fn spawn_background_health_checker(
+    background_health_checker: Arc<TimedBackgroundChecker>,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         background_health_checker.execute().await;
+    })
+}
+
+async fn wait_for_first_health_check(background_health_checker: &TimedBackgroundChecker) {
+    info!("Waiting for the first health check round to complete");
+    background_health_checker.wait_for_first_round().await;
+}
+
+/// Holds the listener until `min_healthy_backends` are healthy, at most for
+/// `timeout` when there's one, after which traffic is accepted anyway.
+async fn wait_for_healthy_backends(
+    background_health_checker: &TimedBackgroundChecker,
+    min_healthy_backends: usize,
+    timeout: Option<Duration>,
+) {
+    info!(
+        "Waiting for {} backends to be healthy before accepting traffic",
+        min_healthy_backends
+    );
+    let waiting = background_health_checker.wait_for_healthy_servers(min_healthy_backends);
+
+    let Some(timeout) = timeout else {
+        return waiting.await;
+    };
+
+    if tokio::time::timeout(timeout, waiting).await.is_err() {
+        warn!(
+            "{} backends weren't healthy after {}s, accepting traffic anyway, unhealthy: {}",
+            min_healthy_backends,
+            timeout.as_secs(),
+            background_health_checker.get_unhealthy_servers().join(", ")
+        );
+    }
+}
+
+fn make_downstream_timeouts(args: &CliArguments) -> DownstreamTimeouts {
+    let seconds = |seconds: u64| (seconds > 0).then(|| Duration::from_secs(seconds));
+
+    DownstreamTimeouts {
+        idle: seconds(args.downstream_idle_timeout_seconds),
+        read: seconds(args.downstream_read_timeout_seconds),
+        head: seconds(args.downstream_head_timeout_seconds),
+    }
+}
+
+async fn start_server(
+    port: u16,
+    acceptors: u16,
+    state: ServerState,
+    tls_config: Option<Arc<ServerConfig>>,
+    timeouts: DownstreamTimeouts,
+) {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    let tcp_listeners =
+        bind_acceptors(address, acceptors as usize).expect("Failed to bind TCP listeners");
+
+    info!(
+        "Server started on port {} with {} acceptors",
+        port, acceptors
+    );
+
+    let servers = tcp_listeners.into_iter().map(|tcp_listener| {
+        let service =
+            router(state.clone()).into_make_service_with_connect_info::<ClientConnection>();
+
+        match &tls_config {
+            Some(tls_config) => {
+                let tls_listener = TlsListener::new(tcp_listener, Arc::clone(tls_config))
+                    .expect("Failed to start the TLS listener");
+
+                axum::serve(
+                    TimedListener::new(tls_listener, timeouts, Arc::clone(&state.metrics)),
+                    service,
+                )
+                .into_future()
+                .boxed()
+            }
+            None => axum::serve(
+                TimedListener::new(tcp_listener, timeouts, Arc::clone(&state.metrics)),
+                service,
+            )
+            .into_future()
+            .boxed(),
+        }
     });
+
+    for result in join_all(servers).await {
+        result.expect("Server failed to run");
+    }
 }
 
-async fn start_server(port: u16, state: ServerState) {
-    let tcp_listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
-        .await
-        .expect("Failed to bind TCP listener");
+/// The command line arguments, completed by the `--config` file if any,
+/// read again on every reload.
+fn arguments(matches: &ArgMatches) -> Result<CliArguments, String> {
+    let mut args = CliArguments::from_arg_matches(matches).map_err(|error| error.to_string())?;
 
-    axum::serve(tcp_listener, router(state))
-        .await
-        .expect("Server failed to run");
+    if let Some(path) = args.config.clone() {
+        ConfigFile::load(&path)
+            .and_then(|config| config.apply(&mut args, matches))
+            .map_err(|error| format!("Invalid config file {}: {}", path.display(), error))?;
+    }
 
-    info!("Server started on port {}", port);
+    load_secret_files(&mut args)?;
+    check_private_keys(&args)?;
+    normalize_target_urls(&mut args)?;
+
+    Ok(args)
+}
+
+/// The target and mirror servers and the backends the per-backend settings
+/// apply to, written the same way so that they keep matching.
+fn normalize_target_urls(args: &mut CliArguments) -> Result<(), String> {
+    for backend in &mut args.target_servers {
+        backend.url = normalize_target_url(&backend.url)?;
+    }
+    for (backend, _) in &mut args.backend_timeouts {
+        *backend = normalize_target_url(backend)?;
+    }
+    for (backend, _) in &mut args.backend_max_in_flight {
+        *backend = normalize_target_url(backend)?;
+    }
+    for server in &mut args.mirror_servers {
+        *server = normalize_target_url(server)?;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the pools and routes from the command line, the config file and
+/// the discovered backends, replacing their health checkers, and updates the
+/// target servers and their weights. The pools in use are kept when the new
+/// ones are invalid, or when the listeners, bound once and for all, changed.
+struct PoolsReload {
+    matches: ArgMatches,
+    /// The arguments of the last reload, none before the first.
+    args: Option<CliArguments>,
+    /// The pools of the main listener, then of each of `listeners`.
+    pools: Vec<Arc<SwappablePools>>,
+    listeners: Vec<String>,
+    effective_config: Arc<EffectiveConfig>,
+    target_servers: Arc<RwLock<Vec<String>>>,
+    weights: Arc<RwLock<Weights>>,
+    /// The health checker of the target servers, kept across reloads.
+    background_checker: Arc<TimedBackgroundChecker>,
+    background_checkers: PoolCheckers,
+    http_clients: PoolClients,
+    health_checks: Vec<JoinHandle<()>>,
+    /// The Kubernetes service and its last discovered backends.
+    discovered: Option<(String, Vec<String>)>,
+    consul: HashMap<ConsulService, DiscoveryWatch<String>>,
+    srv: HashMap<String, DiscoveryWatch<SrvTarget>>,
+    leader_election: Option<Arc<FileLeaseLeaderElection>>,
+    certificate_expiries: Arc<CertificateExpiries>,
+}
+
+impl PoolsReload {
+    fn reload(&mut self, args: CliArguments) -> Result<(), String> {
+        if args.listeners != self.listeners {
+            return Err("The listeners can't change without a restart".to_string());
+        }
+        let discovered = self
+            .discovered
+            .as_ref()
+            .map(|(service, backends)| discovered_pool(&args, service, backends.clone()));
+        let (pools, background_checkers, http_clients) = make_pools(
+            &args,
+            discovered,
+            &self.consul,
+            &self.srv,
+            self.leader_election.clone(),
+            &self.certificate_expiries,
+            &self.background_checkers,
+        )?;
+
+        let reloaded = effective_config(&args)?;
+
+        for (swappable_pools, pools) in self.pools.iter().zip(pools) {
+            swappable_pools.swap(pools);
+        }
+        let target_servers = target_server_urls(&args);
+        self.background_checker.set_servers(target_servers.clone());
+        if let Ok(mut weights) = self.weights.write() {
+            *weights = target_server_weights(&args);
+        }
+        if let Ok(mut current) = self.target_servers.write() {
+            *current = target_servers;
+        }
+        let mut config = self.effective_config.snapshot();
+        for setting in RELOADED_SETTINGS {
+            config[setting] = reloaded[setting].clone();
+        }
+        self.effective_config.set(config);
+        for health_check in self.health_checks.drain(..) {
+            health_check.abort();
+        }
+        self.health_checks = background_checkers
+            .values()
+            .cloned()
+            .map(spawn_background_health_checker)
+            .collect();
+        self.background_checkers = background_checkers;
+        self.http_clients = http_clients;
+
+        let services = consul_services(&args);
+        self.consul.retain(|service, watch| {
+            let used = services.contains(service);
+            if !used {
+                watch.task.abort();
+            }
+            used
+        });
+        let names = srv_records(&args);
+        self.srv.retain(|name, watch| {
+            let used = names.contains(name);
+            if !used {
+                watch.task.abort();
+            }
+            used
+        });
+        self.args = Some(args);
+
+        Ok(())
+    }
+
+    /// Gives the `affected` pools the backends last discovered for them,
+    /// keeping the health their checkers found, without reading the
+    /// configuration again nor touching the other pools and the routes.
+    fn update_pools(&self, affected: impl Fn(&PoolDefinition) -> bool) {
+        // The first reload takes every discovered backend.
+        let Some(args) = &self.args else {
+            return;
+        };
+        let discovered = self
+            .discovered
+            .as_ref()
+            .map(|(service, backends)| discovered_pool(args, service, backends.clone()));
+        let definitions = match pool_definitions(args, discovered, &self.consul, &self.srv) {
+            Ok(definitions) => definitions,
+            Err(error) => {
+                warn!("Keeping the current backends: {}", error);
+                return;
+            }
+        };
+
+        for definition in definitions
+            .into_iter()
+            .filter(|definition| affected(definition))
+        {
+            let Some(background_checker) = self.background_checkers.get(&definition.name) else {
+                continue;
+            };
+            background_checker.set_servers(definition.backends.clone());
+            let http_client = self.http_clients.get(&definition.name).cloned();
+            let pool = make_pool(
+                args,
+                definition,
+                background_checker.get_healthy_servers(),
+                http_client,
+                &self.srv,
+            );
+
+            let name = pool.name.clone();
+            if self.pools.iter().any(|pools| pools.swap_pool(pool.clone())) {
+                info!("Updated the backends of pool {}", name);
+            }
+        }
+    }
+}
+
+/// The backends found by a discovery, kept up to date by `task`.
+struct DiscoveryWatch<T> {
+    backends: Vec<T>,
+    task: JoinHandle<()>,
+}
+
+/// Starts watching the Consul services of the pools not watched yet, once
+/// their first query answered, so that the pools built next have backends.
+async fn watch_consul_services(
+    args: &CliArguments,
+    pools_reload: &Arc<Mutex<PoolsReload>>,
+) -> Result<(), String> {
+    for service in consul_services(args) {
+        if pools_reload.lock().await.consul.contains_key(&service) {
+            continue;
+        }
+
+        let discovery = make_consul_discovery(args, service.clone());
+        let backends = discovery.discover().await.map_err(|error| {
+            format!(
+                "Can't discover the backends of Consul service {}: {}",
+                service, error
+            )
+        })?;
+        let task = spawn_consul_discovery(discovery, Arc::clone(pools_reload));
+
+        pools_reload
+            .lock()
+            .await
+            .consul
+            .insert(service, DiscoveryWatch { backends, task });
+    }
+
+    Ok(())
+}
+
+/// Updates the pools of the Consul service whenever its backends change.
+fn spawn_consul_discovery(
+    discovery: ConsulDiscovery,
+    pools_reload: Arc<Mutex<PoolsReload>>,
+) -> JoinHandle<()> {
+    let mut backends = discovery.subscribe();
+
+    tokio::spawn(async move {
+        let changes = async {
+            while backends.changed().await.is_ok() {
+                let backends = backends.borrow_and_update().clone();
+                let mut pools_reload = pools_reload.lock().await;
+
+                if let Some(watch) = pools_reload.consul.get_mut(discovery.service()) {
+                    watch.backends = backends;
+                }
+
+                pools_reload.update_pools(|pool| pool.consul.as_ref() == Some(discovery.service()));
+            }
+        };
+
+        tokio::select! {
+            _ = discovery.execute() => {}
+            _ = changes => {}
+        }
+    })
+}
+
+/// Starts resolving the SRV records of the pools not resolved yet, once
+/// they resolved a first time, so that the pools built next have backends.
+async fn watch_srv_records(
+    args: &CliArguments,
+    pools_reload: &Arc<Mutex<PoolsReload>>,
+) -> Result<(), String> {
+    for name in srv_records(args) {
+        if pools_reload.lock().await.srv.contains_key(&name) {
+            continue;
+        }
+
+        let discovery = make_srv_discovery(args, name.clone())?;
+        let backends = discovery
+            .discover()
+            .await
+            .map_err(|error| format!("Can't resolve the SRV record {}: {}", name, error))?;
+        let task = spawn_srv_discovery(discovery, Arc::clone(pools_reload));
+
+        pools_reload
+            .lock()
+            .await
+            .srv
+            .insert(name, DiscoveryWatch { backends, task });
+    }
+
+    Ok(())
+}
+
+/// Updates the pools of the SRV record whenever its targets change.
+fn spawn_srv_discovery(
+    discovery: SrvDiscovery,
+    pools_reload: Arc<Mutex<PoolsReload>>,
+) -> JoinHandle<()> {
+    let mut targets = discovery.subscribe();
+
+    tokio::spawn(async move {
+        let changes = async {
+            while targets.changed().await.is_ok() {
+                let targets = targets.borrow_and_update().clone();
+                let mut pools_reload = pools_reload.lock().await;
+
+                if let Some(watch) = pools_reload.srv.get_mut(discovery.name()) {
+                    watch.backends = targets;
+                }
+
+                pools_reload.update_pools(|pool| pool.srv.as_deref() == Some(discovery.name()));
+            }
+        };
+
+        tokio::select! {
+            _ = discovery.execute() => {}
+            _ = changes => {}
+        }
+    })
+}
+
+/// Reloads the pools and routes from `args`, once the discoveries they use
+/// answered.
+async fn reload_pools(
+    args: CliArguments,
+    pools_reload: &Arc<Mutex<PoolsReload>>,
+) -> Result<(), String> {
+    watch_consul_services(&args, pools_reload).await?;
+    watch_srv_records(&args, pools_reload).await?;
+    pools_reload.lock().await.reload(args)
+}
+
+/// Reloads the pools and routes on every SIGHUP.
+#[cfg(unix)]
+fn spawn_pools_reload(pools_reload: Arc<Mutex<PoolsReload>>) {
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(error) => {
+                error!("Can't reload the pools on SIGHUP: {}", error);
+                return;
+            }
+        };
+
+        let matches = pools_reload.lock().await.matches.clone();
+
+        while hangups.recv().await.is_some() {
+            let reloaded = match arguments(&matches) {
+                Ok(args) => reload_pools(args, &pools_reload).await,
+                Err(error) => Err(error),
+            };
+
+            match reloaded {
+                Ok(()) => info!("Reloaded the pools and routes"),
+                Err(error) => warn!("Keeping the current pools and routes: {}", error),
+            }
+        }
+    });
+}
+
+/// Reloads the pools and routes once the configuration changed, when this
+/// replica's turn in its rollout comes, instead of on SIGHUP. A replica whose
+/// error rate went up while baking the new configuration goes back to the
+/// one it ran before, and the rollout halts for every replica.
+fn spawn_config_rollout(
+    args: &CliArguments,
+    rollout: ConfigRollout,
+    pools_reload: Arc<Mutex<PoolsReload>>,
+) {
+    let mut interval = time::interval(Duration::from_secs(args.rollout_polling_seconds));
+    let mut applied = config_revision(args).ok();
+
+    tokio::spawn(async move {
+        let matches = pools_reload.lock().await.matches.clone();
+
+        loop {
+            interval.tick().await;
+
+            let (args, revision) =
+                match arguments(&matches).and_then(|args| Ok((config_revision(&args)?, args))) {
+                    Ok((revision, args)) => (args, revision),
+                    Err(error) => {
+                        warn!("Can't read the configuration to roll out: {}", error);
+                        continue;
+                    }
+                };
+
+            if let Err(error) = rollout.lead(&revision).await {
+                warn!(
+                    "Failed to lead the rollout of configuration {}: {}",
+                    revision, error
+                );
+            }
+            if applied.as_ref() == Some(&revision) {
+                continue;
+            }
+
+            match rollout.claim(&revision).await {
+                Ok(RolloutTurn::Apply(slot)) => {
+                    let previous = pools_reload.lock().await.args.clone();
+                    let healthy = match reload_pools(args, &pools_reload).await {
+                        Ok(()) => {
+                            info!("Applied configuration {}, baking it", revision);
+                            rollout.bake().await
+                        }
+                        Err(error) => {
+                            warn!("Can't apply configuration {}: {}", revision, error);
+                            false
+                        }
+                    };
+
+                    if !healthy && let Some(previous) = previous {
+                        match reload_pools(previous, &pools_reload).await {
+                            Ok(()) => warn!("Rolled configuration {} back", revision),
+                            Err(error) => {
+                                error!("Can't roll configuration {} back: {}", revision, error)
+                            }
+                        }
+                    }
+                    if let Err(error) = rollout.report(&revision, slot, healthy).await {
+                        warn!("Failed to report configuration {}: {}", revision, error);
+                    }
+                    applied = Some(revision);
+                }
+                Ok(RolloutTurn::Halted) => {
+                    warn!(
+                        "Not applying configuration {}, its rollout halted",
+                        revision
+                    );
+                    applied = Some(revision);
+                }
+                Ok(RolloutTurn::Wait) => {}
+                Err(error) => warn!("Failed to claim configuration {}: {}", revision, error),
+            }
+        }
+    });
+}
+
+/// Updates the pool of the Kubernetes service whenever its backends change.
+fn spawn_kubernetes_discovery(
+    discovery: EndpointSliceDiscovery,
+    pools_reload: Arc<Mutex<PoolsReload>>,
+) {
+    let mut backends = discovery.subscribe();
+
+    tokio::spawn(async move {
+        discovery.execute().await;
+    });
+
+    tokio::spawn(async move {
+        while backends.changed().await.is_ok() {
+            let backends = backends.borrow_and_update().clone();
+            let mut pools_reload = pools_reload.lock().await;
+
+            let Some((service, discovered)) = &mut pools_reload.discovered else {
+                continue;
+            };
+            *discovered = backends;
+            let service = service.clone();
+
+            pools_reload.update_pools(|pool| {
+                pool.name == service && pool.consul.is_none() && pool.srv.is_none()
+            });
+        }
+    });
 }
 
 #[tokio::main]
 async fn main() {
     setup_tracing_subscriber();
 
-    let args = CliArguments::parse();
+    let matches = CliArguments::command().get_matches();
+
+    if matches.subcommand_name() == Some("check") {
+        check(&matches).await;
+    }
+
+    let args = arguments(&matches).unwrap_or_else(|error| {
+        eprintln!("error: {}", error);
+        process::exit(2);
+    });
+
+    if let Some(sample) = &args.match_route {
+        if let Err(error) = print_matching_rules(&args, sample) {
+            eprintln!("error: {}", error);
+            process::exit(2);
+        }
+        return;
+    }
+
+    if let Some(format) = args.print_config {
+        print_config(&args, format);
+        return;
+    }
+
+    check_intervals(&args).unwrap_or_else(|error| panic!("{}", error));
+    let leader_election = make_leader_election(&args);
+    let certificate_expiries = Arc::new(CertificateExpiries::new(
+        args.certificate_expiry_warning_days.into(),
+    ));
+    let background_checker =
+        make_background_checker(&args, leader_election.clone(), &certificate_expiries);
+    let target_servers = Arc::new(RwLock::new(target_server_urls(&args)));
+    let weights = Arc::new(RwLock::new(target_server_weights(&args)));
+    let select_server = make_select_server(&args, &background_checker, Arc::clone(&weights));
+    let metrics = make_metrics(&args).await;
+    let usage = make_usage_tracker(&args);
+    spawn_idle_tenants_expiry(&args, Arc::clone(&metrics));
+    let state_store = make_state_store(state_store_location(&args)).await;
+    let discovery = make_kubernetes_discovery(&args);
+    let discovered = match &discovery {
+        Some(discovery) => {
+            let service = &discovery.service().name;
+            let backends = discovery.discover().await.unwrap_or_else(|error| {
+                panic!(
+                    "Can't discover the backends of service {}: {}",
+                    service, error
+                )
+            });
+            Some((service.clone(), backends))
+        }
+        None => None,
+    };
+    let listeners = make_listeners(&args).unwrap_or_else(|error| panic!("{}", error));
+    let pools = (0..=listeners.len())
+        .map(|_| Arc::new(SwappablePools::default()))
+        .collect::<Vec<_>>();
+    let config = Arc::new(EffectiveConfig::default());
+    config.set(effective_config(&args).unwrap_or_else(|error| panic!("{}", error)));
+    let pools_reload = Arc::new(Mutex::new(PoolsReload {
+        matches,
+        args: None,
+        pools: pools.clone(),
+        listeners: args.listeners.clone(),
+        effective_config: Arc::clone(&config),
+        target_servers: Arc::clone(&target_servers),
+        weights,
+        background_checker: Arc::clone(&background_checker),
+        background_checkers: HashMap::new(),
+        http_clients: HashMap::new(),
+        health_checks: Vec::new(),
+        discovered,
+        consul: HashMap::new(),
+        srv: HashMap::new(),
+        leader_election: leader_election.clone(),
+        certificate_expiries: Arc::clone(&certificate_expiries),
+    }));
+    watch_consul_services(&args, &pools_reload)
+        .await
+        .unwrap_or_else(|error| panic!("{}", error));
+    watch_srv_records(&args, &pools_reload)
+        .await
+        .unwrap_or_else(|error| panic!("{}", error));
+    pools_reload
+        .lock()
+        .await
+        .reload(args.clone())
+        .unwrap_or_else(|error| panic!("{}", error));
+    let rollout = make_config_rollout(
+        &args,
+        Arc::clone(&state_store),
+        leader_election.clone(),
+        Arc::clone(&metrics),
+    )
+    .unwrap_or_else(|error| panic!("{}", error));
+    match rollout {
+        Some(rollout) => spawn_config_rollout(&args, rollout, Arc::clone(&pools_reload)),
+        #[cfg(unix)]
+        None => spawn_pools_reload(Arc::clone(&pools_reload)),
+        #[cfg(not(unix))]
+        None => {}
+    }
+    if let Some(discovery) = discovery {
+        spawn_kubernetes_discovery(discovery, pools_reload);
+    }
+    let state = ServerState {
+        target_servers,
+        pools: Arc::clone(&pools[0]),
+        effective_config: config,
+        ..make_server_state(
+            &args,
+            select_server,
+            &background_checker,
+            metrics,
+            usage,
+            state_store,
+            certificate_expiries,
+        )
+    };
+
+    if let Some(leader_election) = leader_election {
+        spawn_leader_election(leader_election);
+    }
+
+    spawn_background_health_checker(Arc::clone(&background_checker));
+
+    if args.wait_for_first_health_check {
+        wait_for_first_health_check(&background_checker).await;
+    }
+
+    if args.min_healthy_backends > 0 {
+        let timeout = Some(args.min_healthy_backends_timeout_seconds)
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+        wait_for_healthy_backends(&background_checker, args.min_healthy_backends, timeout).await;
+    }
 
-    let background_checker = make_background_checker(&args);
-    let select_server = make_select_server(&args.routing_policy, &background_checker);
-    let state = make_server_state(select_server);
+    let tls_config = match make_tls_config(&args).await {
+        Some((tls_config, reloaders)) => {
+            spawn_certificate_reloaders(&args, reloaders);
+            Some(tls_config)
+        }
+        None => None,
+    };
+    let timeouts = make_downstream_timeouts(&args);
 
-    spawn_background_health_checker(background_checker);
+    let servers = listeners
+        .iter()
+        .zip(&pools[1..])
+        .map(|(listener, pools)| {
+            (
+                listener.port,
+                make_listener_state(&args, &state, listener, pools),
+            )
+        })
+        .collect::<Vec<_>>();
+    let servers = iter::once((args.port, state))
+        .chain(servers)
+        .map(|(port, state)| {
+            start_server(port, args.acceptors, state, tls_config.clone(), timeouts)
+        });
 
-    start_server(args.port, state).await;
+    join_all(servers).await;
 }
@@ -0,0 +1,99 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+
+use axum::body::{Body, HttpBody};
+use bytes::Bytes;
+use hyper::body::{Frame, SizeHint};
+
+/// Counts the bytes of a body as they stream through, which the size hint
+/// can't tell for the chunked ones. The count is handed to `on_drop`, if
+/// any, once the body is done with, whether fully read or cut short.
+pub struct CountedBody {
+    body: Body,
+    bytes: Arc<AtomicU64>,
+    on_drop: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl CountedBody {
+    pub fn new(body: Body, bytes: Arc<AtomicU64>) -> Self {
+        Self {
+            body,
+            bytes,
+            on_drop: None,
+        }
+    }
+
+    pub fn on_drop(mut self, on_drop: impl FnOnce(u64) + Send + 'static) -> Self {
+        self.on_drop = Some(Box::new(on_drop));
+        self
+    }
+}
+
+impl HttpBody for CountedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        let frame = Pin::new(&mut this.body).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            this.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for CountedBody {
+    fn drop(&mut self) {
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop(self.bytes.load(Ordering::Relaxed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use axum::body::{Body, to_bytes};
+    use bytes::Bytes;
+
+    use crate::metrics::counted_body::CountedBody;
+
+    #[tokio::test]
+    async fn counts_the_bytes_of_chunked_bodies() {
+        let body = Body::from_stream(futures::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from("hello ")),
+            Ok(Bytes::from("world")),
+        ]));
+        let counted = Arc::new(AtomicU64::new(0));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let on_drop = Arc::clone(&dropped);
+
+        let body = Body::new(
+            CountedBody::new(body, Arc::clone(&counted))
+                .on_drop(move |bytes| on_drop.store(bytes, Ordering::SeqCst)),
+        );
+
+        assert_eq!(to_bytes(body, usize::MAX).await.unwrap(), "hello world");
+        assert_eq!(counted.load(Ordering::SeqCst), 11);
+        assert_eq!(dropped.load(Ordering::SeqCst), 11);
+    }
+}
@@ -0,0 +1,8 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Snapshot storage error: {0}")]
+    Storage(String),
+
+    #[error("Malformed snapshot: {0}")]
+    MalformedSnapshot(String),
+}
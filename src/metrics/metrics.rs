@@ -0,0 +1,266 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::usage_tracker::{MAX_TENANTS, OTHER_TENANT};
+
+pub const CACHE_HITS_TOTAL: &str = "cache_hits_total";
+pub const CACHE_MISSES_TOTAL: &str = "cache_misses_total";
+pub const COALESCED_REQUESTS_TOTAL: &str = "coalesced_requests_total";
+pub const HEAD_TIMEOUTS_TOTAL: &str = "head_timeouts_total";
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const MIRRORS_DROPPED_TOTAL: &str = "mirrors_dropped_total";
+pub const RESTARTS_TOTAL: &str = "restarts_total";
+pub const RETRIES_TOTAL: &str = "retries_total";
+
+#[derive(Default)]
+pub struct Metrics {
+    counters: RwLock<BTreeMap<String, u64>>,
+    /// When each tenant last had usage recorded.
+    tenants_seen: RwLock<HashMap<String, Instant>>,
+}
+
+impl Metrics {
+    pub fn increment(&self, counter: &str) {
+        self.add(counter, 1);
+    }
+
+    pub fn add(&self, counter: &str, value: u64) {
+        let Ok(mut counters) = self.counters.write() else {
+            return;
+        };
+
+        match counters.get_mut(counter) {
+            Some(current) => *current = current.saturating_add(value),
+            None => {
+                counters.insert(counter.to_string(), value);
+            }
+        }
+    }
+
+    pub fn get(&self, counter: &str) -> u64 {
+        self.counters
+            .read()
+            .ok()
+            .and_then(|counters| counters.get(counter).copied())
+            .unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.counters
+            .read()
+            .map(|counters| counters.clone())
+            .unwrap_or_default()
+    }
+
+    /// Adds the counters of a previous snapshot on top of the current ones.
+    pub fn restore(&self, snapshot: BTreeMap<String, u64>) {
+        for (counter, value) in snapshot {
+            self.add(&counter, value);
+        }
+    }
+
+    pub fn record_response(&self, status: u16) {
+        self.increment(HTTP_REQUESTS_TOTAL);
+        self.increment(&format!("http_responses_{}xx_total", status / 100));
+    }
+
+    /// Counts the tenants over [`MAX_TENANTS`] as [`OTHER_TENANT`].
+    pub fn record_tenant_usage(&self, tenant: &str, ingress_bytes: u64, egress_bytes: u64) {
+        let tenant = match self.tenants_seen.write() {
+            Ok(mut tenants_seen) => {
+                let tenant =
+                    match tenants_seen.len() < MAX_TENANTS || tenants_seen.contains_key(tenant) {
+                        true => tenant,
+                        false => OTHER_TENANT,
+                    };
+                tenants_seen.insert(tenant.to_string(), Instant::now());
+                tenant
+            }
+            Err(_) => return,
+        };
+
+        for (counter, bytes) in tenant_counters(tenant)
+            .into_iter()
+            .zip([ingress_bytes, egress_bytes])
+        {
+            self.add(&counter, bytes);
+        }
+    }
+
+    /// Drops the counters of the tenants without usage for `idle`, so that
+    /// the ones gone for good don't pile up. The tenants restored from a
+    /// snapshot are idle from the first call on.
+    pub fn expire_idle_tenants(&self, idle: Duration) {
+        let now = Instant::now();
+        let (Ok(mut counters), Ok(mut tenants_seen)) =
+            (self.counters.write(), self.tenants_seen.write())
+        else {
+            return;
+        };
+
+        for counter in counters.keys() {
+            if let Some(tenant) = counter
+                .strip_prefix("tenant_ingress_bytes_total{tenant=\"")
+                .and_then(|tenant| tenant.strip_suffix("\"}"))
+            {
+                tenants_seen.entry(unescape_label(tenant)).or_insert(now);
+            }
+        }
+
+        tenants_seen.retain(|tenant, seen| {
+            let active = now.duration_since(*seen) < idle;
+            if !active {
+                for counter in tenant_counters(tenant) {
+                    counters.remove(&counter);
+                }
+            }
+            active
+        });
+    }
+}
+
+fn tenant_counters(tenant: &str) -> [String; 2] {
+    let tenant = escape_label(tenant);
+
+    [
+        format!("tenant_ingress_bytes_total{{tenant=\"{}\"}}", tenant),
+        format!("tenant_egress_bytes_total{{tenant=\"{}\"}}", tenant),
+    ]
+}
+
+/// Escapes a label value the way the Prometheus text format does.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn unescape_label(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => unescaped.push(char),
+            },
+            char => unescaped.push(char),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use crate::metrics::metrics::{HTTP_REQUESTS_TOTAL, Metrics};
+    use crate::metrics::usage_tracker::{MAX_TENANTS, OTHER_TENANT};
+
+    #[test]
+    fn counts_responses_by_status_class() {
+        let metrics = Metrics::default();
+
+        metrics.record_response(200);
+        metrics.record_response(204);
+        metrics.record_response(502);
+
+        assert_eq!(metrics.get(HTTP_REQUESTS_TOTAL), 3);
+        assert_eq!(metrics.get("http_responses_2xx_total"), 2);
+        assert_eq!(metrics.get("http_responses_5xx_total"), 1);
+        assert_eq!(metrics.get("http_responses_4xx_total"), 0);
+    }
+
+    #[test]
+    fn restore_adds_to_the_current_counters() {
+        let metrics = Metrics::default();
+        metrics.add(HTTP_REQUESTS_TOTAL, 2);
+
+        metrics.restore(BTreeMap::from([
+            (HTTP_REQUESTS_TOTAL.to_string(), 40),
+            ("http_responses_2xx_total".to_string(), 40),
+        ]));
+
+        assert_eq!(metrics.get(HTTP_REQUESTS_TOTAL), 42);
+        assert_eq!(metrics.get("http_responses_2xx_total"), 40);
+    }
+
+    #[test]
+    fn counts_bytes_per_tenant() {
+        let metrics = Metrics::default();
+
+        metrics.record_tenant_usage("tenant-a", 10, 100);
+        metrics.record_tenant_usage("tenant-a", 5, 50);
+
+        assert_eq!(
+            metrics.get(r#"tenant_ingress_bytes_total{tenant="tenant-a"}"#),
+            15
+        );
+        assert_eq!(
+            metrics.get(r#"tenant_egress_bytes_total{tenant="tenant-a"}"#),
+            150
+        );
+    }
+
+    #[test]
+    fn expires_the_counters_of_idle_tenants() {
+        let metrics = Metrics::default();
+        metrics.record_tenant_usage("tenant-a", 10, 100);
+        metrics.increment(HTTP_REQUESTS_TOTAL);
+
+        metrics.expire_idle_tenants(Duration::from_secs(3600));
+        assert_eq!(
+            metrics.get(r#"tenant_ingress_bytes_total{tenant="tenant-a"}"#),
+            10
+        );
+
+        metrics.expire_idle_tenants(Duration::ZERO);
+        assert_eq!(
+            metrics.snapshot(),
+            BTreeMap::from([(HTTP_REQUESTS_TOTAL.to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn escapes_the_tenant_labels() {
+        let metrics = Metrics::default();
+        metrics.record_tenant_usage("a\"} 1\nb", 10, 100);
+
+        assert_eq!(
+            metrics.get(r#"tenant_ingress_bytes_total{tenant="a\"} 1\nb"}"#),
+            10
+        );
+
+        metrics.expire_idle_tenants(Duration::ZERO);
+        assert!(metrics.snapshot().is_empty());
+    }
+
+    #[test]
+    fn counts_the_tenants_over_the_cap_together() {
+        let metrics = Metrics::default();
+
+        for index in 0..MAX_TENANTS + 3 {
+            metrics.record_tenant_usage(&format!("tenant-{}", index), 1, 1);
+        }
+        metrics.record_tenant_usage("tenant-0", 1, 1);
+
+        assert_eq!(metrics.snapshot().len(), 2 * (MAX_TENANTS + 1));
+        assert_eq!(
+            metrics.get(&format!(
+                r#"tenant_ingress_bytes_total{{tenant="{}"}}"#,
+                OTHER_TENANT
+            )),
+            3
+        );
+        assert_eq!(
+            metrics.get(r#"tenant_ingress_bytes_total{tenant="tenant-0"}"#),
+            2
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::BTreeMap, io::ErrorKind, path::PathBuf, sync::Arc, time::Duration};
+use tokio::time;
+use tracing::{error, info};
+
+use crate::metrics::{
+    error::Error,
+    metrics::{Metrics, RESTARTS_TOTAL},
+};
+
+pub struct MetricsSnapshotter {
+    metrics: Arc<Metrics>,
+    path: PathBuf,
+    interval: Duration,
+}
+
+impl MetricsSnapshotter {
+    pub fn new(metrics: Arc<Metrics>, path: PathBuf, interval: Duration) -> Self {
+        Self {
+            metrics,
+            path,
+            interval,
+        }
+    }
+
+    /// Restores the counters of the previous run, if any, and marks the restart.
+    pub async fn load(&self) -> Result<(), Error> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                info!("No metrics snapshot found at {:?}", self.path);
+                return Ok(());
+            }
+            Err(error) => return Err(Error::Storage(error.to_string())),
+        };
+
+        let snapshot: BTreeMap<String, u64> = serde_json::from_str(&content)
+            .map_err(|error| Error::MalformedSnapshot(error.to_string()))?;
+
+        self.metrics.restore(snapshot);
+        self.metrics.increment(RESTARTS_TOTAL);
+
+        info!("Restored metrics snapshot from {:?}", self.path);
+
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<(), Error> {
+        let content = serde_json::to_string(&self.metrics.snapshot())
+            .map_err(|error| Error::MalformedSnapshot(error.to_string()))?;
+
+        let temporary_path = self.path.with_extension("tmp");
+
+        tokio::fs::write(&temporary_path, content)
+            .await
+            .map_err(|error| Error::Storage(error.to_string()))?;
+
+        tokio::fs::rename(&temporary_path, &self.path)
+            .await
+            .map_err(|error| Error::Storage(error.to_string()))
+    }
+
+    pub async fn execute(&self) {
+        info!(
+            "Checkpointing metrics to {:?} every {:?}",
+            self.path, self.interval
+        );
+
+        let mut interval = time::interval(self.interval);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.save().await {
+                error!("Failed to checkpoint metrics: {}", error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use crate::metrics::{
+        metrics::{HTTP_REQUESTS_TOTAL, Metrics, RESTARTS_TOTAL},
+        metrics_snapshotter::MetricsSnapshotter,
+    };
+
+    fn snapshot_path() -> PathBuf {
+        std::env::temp_dir().join(format!("wakanda-lb-{}.metrics.json", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn counters_survive_a_restart() {
+        let path = snapshot_path();
+
+        let before_restart = Arc::new(Metrics::default());
+        before_restart.add(HTTP_REQUESTS_TOTAL, 10);
+        MetricsSnapshotter::new(before_restart, path.clone(), Duration::from_secs(1))
+            .save()
+            .await
+            .unwrap();
+
+        let after_restart = Arc::new(Metrics::default());
+        MetricsSnapshotter::new(
+            Arc::clone(&after_restart),
+            path.clone(),
+            Duration::from_secs(1),
+        )
+        .load()
+        .await
+        .unwrap();
+
+        assert_eq!(after_restart.get(HTTP_REQUESTS_TOTAL), 10);
+        assert_eq!(after_restart.get(RESTARTS_TOTAL), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_snapshot_is_not_an_error() {
+        let metrics = Arc::new(Metrics::default());
+
+        MetricsSnapshotter::new(
+            Arc::clone(&metrics),
+            snapshot_path(),
+            Duration::from_secs(1),
+        )
+        .load()
+        .await
+        .unwrap();
+
+        assert_eq!(metrics.get(RESTARTS_TOTAL), 0);
+    }
+
+    #[tokio::test]
+    async fn malformed_snapshot_is_an_error() {
+        let path = snapshot_path();
+        std::fs::write(&path, "not json").unwrap();
+
+        let result = MetricsSnapshotter::new(
+            Arc::new(Metrics::default()),
+            path.clone(),
+            Duration::from_secs(1),
+        )
+        .load()
+        .await;
+
+        assert!(result.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,6 @@
+pub mod counted_body;
+pub mod error;
+#[allow(clippy::module_inception)]
+pub mod metrics;
+pub mod metrics_snapshotter;
+pub mod usage_tracker;
@@ -0,0 +1,276 @@
+use http::HeaderMap;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::RwLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Usage {
+    pub requests: u64,
+    pub ingress_bytes: u64,
+    pub egress_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageBucket {
+    pub start_millis: u64,
+    pub tenants: HashMap<String, Usage>,
+}
+
+/// How many tenants are told apart in a bucket, the others being counted
+/// together as [`OTHER_TENANT`].
+pub const MAX_TENANTS: usize = 1000;
+
+/// The tenants over [`MAX_TENANTS`].
+pub const OTHER_TENANT: &str = "other";
+
+/// Stands for the value of the tenant header wherever it is kept or shown,
+/// since it is often a credential like an API key.
+pub fn pseudonym(tenant: &str) -> String {
+    let digest = Sha1::digest(tenant.as_bytes());
+
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Aggregates the bytes exchanged by each tenant, identified by the
+/// pseudonym of the value of `tenant_header`, into fixed time buckets. Only
+/// the most recent `retention` buckets are kept. The tenants of `named`
+/// are always told apart, so that a client making up new header values
+/// can't push them into [`OTHER_TENANT`].
+pub struct UsageTracker {
+    tenant_header: Option<String>,
+    bucket_duration: Duration,
+    retention: usize,
+    named: HashSet<String>,
+    buckets: RwLock<BTreeMap<u64, HashMap<String, Usage>>>,
+}
+
+impl UsageTracker {
+    pub fn new(tenant_header: Option<String>, bucket_duration: Duration, retention: usize) -> Self {
+        Self {
+            tenant_header,
+            bucket_duration,
+            retention,
+            named: HashSet::new(),
+            buckets: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Always tells apart the tenants with these header values.
+    pub fn with_named_tenants<'a>(mut self, tenants: impl IntoIterator<Item = &'a str>) -> Self {
+        self.named = tenants.into_iter().map(pseudonym).collect();
+        self
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(None, Duration::from_secs(3600), 0)
+    }
+
+    /// The pseudonym of the tenant of the request, or [`OTHER_TENANT`] when
+    /// the current bucket already tells [`MAX_TENANTS`] apart.
+    pub fn tenant_of(&self, headers: &HeaderMap) -> Option<String> {
+        self.tenant_at(now_millis(), headers)
+    }
+
+    fn tenant_at(&self, now_millis: u64, headers: &HeaderMap) -> Option<String> {
+        let tenant_header = self.tenant_header.as_ref()?;
+
+        let tenant = headers
+            .get(tenant_header)
+            .and_then(|value| value.to_str().ok())
+            .map(pseudonym)?;
+
+        let start_millis = self.bucket_start(now_millis);
+        let admitted = self.buckets.read().is_ok_and(|buckets| {
+            buckets
+                .get(&start_millis)
+                .is_none_or(|tenants| self.admits(tenants, &tenant))
+        });
+
+        Some(match admitted {
+            true => tenant,
+            false => OTHER_TENANT.to_string(),
+        })
+    }
+
+    pub fn record(&self, tenant: &str, ingress_bytes: u64, egress_bytes: u64) {
+        self.record_at(now_millis(), tenant, ingress_bytes, egress_bytes);
+    }
+
+    fn record_at(&self, now_millis: u64, tenant: &str, ingress_bytes: u64, egress_bytes: u64) {
+        if self.retention == 0 {
+            return;
+        }
+
+        let start_millis = self.bucket_start(now_millis);
+
+        let Ok(mut buckets) = self.buckets.write() else {
+            return;
+        };
+
+        let tenants = buckets.entry(start_millis).or_default();
+        let tenant = match self.admits(tenants, tenant) {
+            true => tenant,
+            false => OTHER_TENANT,
+        };
+        let usage = tenants.entry(tenant.to_string()).or_default();
+
+        usage.requests += 1;
+        usage.ingress_bytes += ingress_bytes;
+        usage.egress_bytes += egress_bytes;
+
+        while buckets.len() > self.retention {
+            buckets.pop_first();
+        }
+    }
+
+    fn admits(&self, tenants: &HashMap<String, Usage>, tenant: &str) -> bool {
+        tenants.len() < MAX_TENANTS || tenants.contains_key(tenant) || self.named.contains(tenant)
+    }
+
+    fn bucket_start(&self, now_millis: u64) -> u64 {
+        let bucket_millis = (self.bucket_duration.as_millis() as u64).max(1);
+
+        now_millis - now_millis % bucket_millis
+    }
+
+    pub fn snapshot(&self) -> Vec<UsageBucket> {
+        self.buckets
+            .read()
+            .map(|buckets| {
+                buckets
+                    .iter()
+                    .map(|(start_millis, tenants)| UsageBucket {
+                        start_millis: *start_millis,
+                        tenants: tenants.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::{HeaderMap, HeaderValue};
+
+    use crate::metrics::usage_tracker::{
+        MAX_TENANTS, OTHER_TENANT, Usage, UsageTracker, pseudonym,
+    };
+
+    #[test]
+    fn aggregates_usage_per_tenant_and_bucket() {
+        let tracker = UsageTracker::new(Some("x-api-key".to_string()), Duration::from_secs(60), 10);
+
+        tracker.record_at(1_000, "tenant-a", 10, 100);
+        tracker.record_at(2_000, "tenant-a", 5, 50);
+        tracker.record_at(3_000, "tenant-b", 1, 1);
+        tracker.record_at(61_000, "tenant-a", 7, 70);
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.len(), 2);
+
+        assert_eq!(snapshot[0].start_millis, 0);
+        assert_eq!(
+            snapshot[0].tenants["tenant-a"],
+            Usage {
+                requests: 2,
+                ingress_bytes: 15,
+                egress_bytes: 150,
+            }
+        );
+        assert_eq!(snapshot[0].tenants["tenant-b"].requests, 1);
+
+        assert_eq!(snapshot[1].start_millis, 60_000);
+        assert_eq!(snapshot[1].tenants["tenant-a"].ingress_bytes, 7);
+    }
+
+    #[test]
+    fn keeps_only_the_most_recent_buckets() {
+        let tracker = UsageTracker::new(Some("x-api-key".to_string()), Duration::from_secs(1), 2);
+
+        tracker.record_at(0, "tenant-a", 1, 1);
+        tracker.record_at(1_000, "tenant-a", 1, 1);
+        tracker.record_at(2_000, "tenant-a", 1, 1);
+
+        let starts: Vec<u64> = tracker
+            .snapshot()
+            .iter()
+            .map(|bucket| bucket.start_millis)
+            .collect();
+
+        assert_eq!(starts, vec![1_000, 2_000]);
+    }
+
+    #[test]
+    fn identifies_tenants_only_when_a_header_is_configured() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("tenant-a"));
+
+        let tracker = UsageTracker::new(Some("x-api-key".to_string()), Duration::from_secs(1), 2);
+        assert_eq!(tracker.tenant_of(&headers), Some(pseudonym("tenant-a")));
+        assert_eq!(tracker.tenant_of(&HeaderMap::new()), None);
+
+        assert_eq!(UsageTracker::disabled().tenant_of(&headers), None);
+    }
+
+    #[test]
+    fn never_keeps_the_header_values() {
+        let tenant = pseudonym("s3cr3t-api-key");
+
+        assert_eq!(tenant.len(), 16);
+        assert!(!tenant.contains("s3cr3t"));
+        assert_eq!(tenant, pseudonym("s3cr3t-api-key"));
+        assert_ne!(tenant, pseudonym("another-api-key"));
+    }
+
+    #[test]
+    fn counts_the_tenants_over_the_cap_together() {
+        let tracker = UsageTracker::new(Some("x-api-key".to_string()), Duration::from_secs(60), 2)
+            .with_named_tenants(["tenant-a"]);
+
+        for index in 0..MAX_TENANTS + 5 {
+            tracker.record_at(1_000, &format!("tenant-{}", index), 1, 1);
+        }
+        tracker.record_at(1_000, "tenant-0", 1, 1);
+        tracker.record_at(1_000, &pseudonym("tenant-a"), 1, 1);
+
+        let tenants = &tracker.snapshot()[0].tenants;
+        assert_eq!(tenants.len(), MAX_TENANTS + 2);
+        assert_eq!(tenants[OTHER_TENANT].requests, 5);
+        assert_eq!(tenants["tenant-0"].requests, 2);
+        assert_eq!(tenants[&pseudonym("tenant-a")].requests, 1);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("made-up"));
+        assert_eq!(
+            tracker.tenant_at(1_000, &headers),
+            Some(OTHER_TENANT.to_string())
+        );
+        assert_eq!(
+            tracker.tenant_at(61_000, &headers),
+            Some(pseudonym("made-up"))
+        );
+
+        headers.insert("x-api-key", HeaderValue::from_static("tenant-a"));
+        assert_eq!(
+            tracker.tenant_at(1_000, &headers),
+            Some(pseudonym("tenant-a"))
+        );
+    }
+}
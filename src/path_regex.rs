@@ -0,0 +1,65 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// A regular expression over the path, compiled once when the configuration
+/// is loaded. Paths are matched as sent, percent-encoding included, and
+/// without the query string.
+#[derive(Debug, Clone)]
+pub struct PathRegex(Regex);
+
+impl PathRegex {
+    pub fn is_match(&self, path: &str) -> bool {
+        self.0.is_match(path)
+    }
+
+    /// The path with its first match replaced, `$1` or `$name` standing for
+    /// the captured groups, or `None` when it doesn't match.
+    pub fn replace(&self, path: &str, replacement: &str) -> Option<String> {
+        self.0
+            .is_match(path)
+            .then(|| self.0.replace(path, replacement).into_owned())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for PathRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl FromStr for PathRegex {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Regex::new(value)
+            .map(PathRegex)
+            .map_err(|error| format!("invalid path regex {}: {}", value, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path_regex::PathRegex;
+
+    #[test]
+    fn replaces_the_captured_groups() {
+        let regex: PathRegex = r"^/users/(?P<id>\d+)/orders$".parse().unwrap();
+
+        assert!(regex.is_match("/users/7/orders"));
+        assert_eq!(
+            regex.replace("/users/7/orders", "/v2/orders/$id"),
+            Some("/v2/orders/7".to_string())
+        );
+        assert_eq!(regex.replace("/users/me/orders", "/v2/orders"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!("^/users/(\\d+$".parse::<PathRegex>().is_err());
+    }
+}
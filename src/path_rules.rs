@@ -0,0 +1,276 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::path_regex::PathRegex;
+use crate::target_url::normalize_target_url;
+
+/// The paths a rule applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathMatch {
+    Prefix(String),
+    /// Matched against the path without the query string, which is kept.
+    Regex(PathRegex),
+}
+
+/// Sends the requests under `prefix` to `backends`, with the prefix replaced
+/// by `replacement`, e.g. `/api/v1/users?id=7` forwarded as `/users?id=7`.
+///
+/// Written as `PREFIX[=REPLACEMENT][>BACKEND|BACKEND]`, e.g.
+/// `/api/v1>http://users:8080` strips `/api/v1`, `/api/v1=/v2` rewrites it
+/// and keeps every backend. Paths are compared as sent, percent-encoding
+/// included, so `/api%2Fv1` is not under `/api/v1`.
+///
+/// Paths can also be matched by a regular expression, written as
+/// `regex:PATTERN=REPLACEMENT[>BACKENDS]`, the replacement referring to the
+/// captured groups, e.g. `regex:^/users/(\d+)$=/v2/users/$1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathRule {
+    pub path: PathMatch,
+    pub replacement: String,
+    /// Empty to keep every backend.
+    pub backends: Vec<String>,
+}
+
+impl PathRule {
+    /// The path and query to forward when the rule applies to the path.
+    fn rewrite(&self, path_and_query: &str) -> Option<String> {
+        let rewritten = match &self.path {
+            PathMatch::Prefix(prefix) => {
+                let rest = rest(prefix, path_and_query)?;
+                format!("{}{}", self.replacement, rest)
+            }
+            PathMatch::Regex(regex) => {
+                let (path, query) = match path_and_query.split_once('?') {
+                    Some((path, query)) => (path, Some(query)),
+                    None => (path_and_query, None),
+                };
+                let path = regex.replace(path, &self.replacement)?;
+
+                match query {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path,
+                }
+            }
+        };
+
+        if rewritten.starts_with('/') {
+            Some(rewritten)
+        } else {
+            Some(format!("/{}", rewritten))
+        }
+    }
+}
+
+/// The rest of the path when it is under the prefix, on a segment
+/// boundary: `/api/v1` covers `/api/v1/users` but not `/api/v10`.
+fn rest<'a>(prefix: &str, path_and_query: &'a str) -> Option<&'a str> {
+    let rest = path_and_query.strip_prefix(prefix)?;
+
+    match rest.as_bytes().first() {
+        None | Some(b'/' | b'?') => Some(rest),
+        Some(_) => None,
+    }
+}
+
+impl FromStr for PathRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(rule) = value.strip_prefix("regex:") {
+            let (pattern, rest) = rule.split_once('=').ok_or_else(|| {
+                format!(
+                    "expected regex:PATTERN=REPLACEMENT[>BACKENDS], got {}",
+                    value
+                )
+            })?;
+            let (replacement, backends) = rest.split_once('>').unwrap_or((rest, ""));
+
+            return Ok(PathRule {
+                path: PathMatch::Regex(pattern.parse()?),
+                replacement: replacement.to_string(),
+                backends: parse_backends(backends)?,
+            });
+        }
+
+        let (rule, backends) = value.split_once('>').unwrap_or((value, ""));
+        let (prefix, replacement) = rule.split_once('=').unwrap_or((rule, ""));
+
+        if !prefix.starts_with('/') || (!replacement.is_empty() && !replacement.starts_with('/')) {
+            return Err(format!(
+                "expected PREFIX[=REPLACEMENT][>BACKENDS] with paths starting with /, got {}",
+                value
+            ));
+        }
+
+        Ok(PathRule {
+            // Trailing slashes are left to the rest of the path, so that
+            // `/api/` and `/api` are the same prefix.
+            path: PathMatch::Prefix(prefix.trim_end_matches('/').to_string()),
+            replacement: replacement.trim_end_matches('/').to_string(),
+            backends: parse_backends(backends)?,
+        })
+    }
+}
+
+fn parse_backends(backends: &str) -> Result<Vec<String>, String> {
+    backends
+        .split('|')
+        .filter(|backend| !backend.is_empty())
+        .map(normalize_target_url)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathRules {
+    pub rules: Vec<PathRule>,
+}
+
+impl PathRules {
+    /// The index of the first rule applying to the path, with the path and
+    /// query to forward.
+    pub fn matching(&self, path_and_query: &str) -> Option<(usize, String)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .find_map(|(index, rule)| rule.rewrite(path_and_query).map(|path| (index, path)))
+    }
+
+    /// Path and query to forward, and the servers the request must not be
+    /// sent to, following the first rule the path is under.
+    pub fn route<'a>(
+        &self,
+        path_and_query: &'a str,
+        target_servers: &[String],
+    ) -> (Cow<'a, str>, Vec<String>) {
+        let Some((index, rewritten)) = self.matching(path_and_query) else {
+            return (Cow::Borrowed(path_and_query), Vec::new());
+        };
+        let rule = &self.rules[index];
+
+        let excluded_servers = if rule.backends.is_empty() {
+            Vec::new()
+        } else {
+            target_servers
+                .iter()
+                .filter(|server| !rule.backends.contains(server))
+                .cloned()
+                .collect()
+        };
+
+        (Cow::Owned(rewritten), excluded_servers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path_rules::{PathMatch, PathRule, PathRules};
+
+    fn target_servers() -> Vec<String> {
+        vec![
+            "http://users:8080".to_string(),
+            "http://orders:8080".to_string(),
+        ]
+    }
+
+    fn rewrite(rule: &str, path_and_query: &str) -> String {
+        PathRules {
+            rules: vec![rule.parse().unwrap()],
+        }
+        .route(path_and_query, &target_servers())
+        .0
+        .into_owned()
+    }
+
+    #[test]
+    fn parses_prefix_replacement_and_backends() {
+        assert_eq!(
+            "/api/v1/=/v2>http://users:8080/|HTTP://Orders:8080".parse(),
+            Ok(PathRule {
+                path: PathMatch::Prefix("/api/v1".to_string()),
+                replacement: "/v2".to_string(),
+                backends: target_servers(),
+            })
+        );
+        assert_eq!(
+            "/api/v1".parse(),
+            Ok(PathRule {
+                path: PathMatch::Prefix("/api/v1".to_string()),
+                replacement: String::new(),
+                backends: Vec::new(),
+            })
+        );
+        assert!("api>http://users:8080".parse::<PathRule>().is_err());
+        assert!("/api=v2".parse::<PathRule>().is_err());
+        assert!("/api>users:8080".parse::<PathRule>().is_err());
+    }
+
+    #[test]
+    fn strips_the_prefix() {
+        assert_eq!(rewrite("/api/v1", "/api/v1/users?id=7"), "/users?id=7");
+        assert_eq!(rewrite("/api/v1/", "/api/v1/users"), "/users");
+        assert_eq!(rewrite("/api/v1", "/api/v1"), "/");
+        assert_eq!(rewrite("/api/v1", "/api/v1/"), "/");
+        assert_eq!(rewrite("/api/v1", "/api/v1?page=2"), "/?page=2");
+    }
+
+    #[test]
+    fn replaces_the_prefix() {
+        assert_eq!(rewrite("/api/v1=/v2", "/api/v1/users/"), "/v2/users/");
+        assert_eq!(rewrite("/api/v1=/v2/", "/api/v1"), "/v2");
+        assert_eq!(rewrite("/api/v1=/", "/api/v1/users"), "/users");
+        assert_eq!(rewrite("/=/app", "/index.html"), "/app/index.html");
+    }
+
+    #[test]
+    fn matches_whole_segments_as_sent() {
+        assert_eq!(rewrite("/api/v1", "/api/v10/users"), "/api/v10/users");
+        assert_eq!(rewrite("/api/v1", "/api%2Fv1/users"), "/api%2Fv1/users");
+        assert_eq!(rewrite("/files", "/files/a%20b%2Fc.txt"), "/a%20b%2Fc.txt");
+    }
+
+    #[test]
+    fn parses_regex_rules() {
+        assert_eq!(
+            r"regex:^/users/(\d+)$=/v2/users/$1>http://users:8080".parse(),
+            Ok(PathRule {
+                path: PathMatch::Regex(r"^/users/(\d+)$".parse().unwrap()),
+                replacement: "/v2/users/$1".to_string(),
+                backends: vec!["http://users:8080".to_string()],
+            })
+        );
+        assert!(r"regex:^/users/(\d+)$".parse::<PathRule>().is_err());
+        assert!(r"regex:^/users/(\d+$=/v2".parse::<PathRule>().is_err());
+    }
+
+    #[test]
+    fn rewrites_the_regex_matches_keeping_the_query() {
+        let rule = r"regex:^/users/(?P<id>\d+)/orders$=/orders/by-user/$id";
+
+        assert_eq!(
+            rewrite(rule, "/users/7/orders?page=2"),
+            "/orders/by-user/7?page=2"
+        );
+        assert_eq!(rewrite(rule, "/users/7/orders/3"), "/users/7/orders/3");
+        assert_eq!(rewrite(r"regex:^/legacy=", "/legacy/app.js"), "/app.js");
+    }
+
+    #[test]
+    fn first_matching_rule_keeps_only_its_backends() {
+        let path_rules = PathRules {
+            rules: vec![
+                "/api/v1/users>http://users:8080".parse().unwrap(),
+                "/api/v1=/v1".parse().unwrap(),
+            ],
+        };
+
+        let (path_and_query, excluded_servers) =
+            path_rules.route("/api/v1/users/7", &target_servers());
+        assert_eq!(path_and_query, "/7");
+        assert_eq!(excluded_servers, vec!["http://orders:8080"]);
+
+        let (path_and_query, excluded_servers) =
+            path_rules.route("/api/v1/orders", &target_servers());
+        assert_eq!(path_and_query, "/v1/orders");
+        assert!(excluded_servers.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// A service deployed twice, on a blue and a green pool, only the live one
+/// getting its requests. Written as `NAME=BLUE,GREEN`, e.g.
+/// `shop=shop-blue,shop-green`, the pool routes and the default pool naming
+/// the service instead of a pool. Blue is live on startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlueGreenDefinition {
+    pub name: String,
+    pub blue: String,
+    pub green: String,
+}
+
+impl FromStr for BlueGreenDefinition {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected NAME=BLUE,GREEN, got {}", value);
+
+        let (name, pools) = value.split_once('=').ok_or_else(invalid)?;
+        let (blue, green) = pools.split_once(',').ok_or_else(invalid)?;
+        let (name, blue, green) = (name.trim(), blue.trim(), green.trim());
+
+        if name.is_empty() || blue.is_empty() || green.is_empty() || blue == green {
+            return Err(invalid());
+        }
+
+        Ok(BlueGreenDefinition {
+            name: name.to_string(),
+            blue: blue.to_string(),
+            green: green.to_string(),
+        })
+    }
+}
+
+/// A blue/green service as shown by the admin API.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct BlueGreenStatus {
+    pub service: String,
+    pub blue: String,
+    pub green: String,
+    pub live: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pools::blue_green::BlueGreenDefinition;
+
+    #[test]
+    fn parses_name_and_pools() {
+        assert_eq!(
+            "shop=shop-blue, shop-green".parse(),
+            Ok(BlueGreenDefinition {
+                name: "shop".to_string(),
+                blue: "shop-blue".to_string(),
+                green: "shop-green".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_definitions() {
+        for definition in [
+            "shop",
+            "shop=shop-blue",
+            "shop=shop-blue,",
+            "=shop-blue,shop-green",
+            "shop=shop-blue,shop-blue",
+        ] {
+            assert!(
+                definition.parse::<BlueGreenDefinition>().is_err(),
+                "{}",
+                definition
+            );
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::pools::blue_green::BlueGreenDefinition;
+use crate::pools::pool::Pool;
+use crate::pools::pool_route::PoolRoute;
+
+/// A listener next to the main one, written as
+/// `NAME=PORT[;default-pool=POOL]`, e.g. `internal=8081;default-pool=admin`.
+/// It serves the pools given `;listener=NAME` only, with the routes and
+/// blue/green services leading to them, and answers the requests matching
+/// none of its routes with its default pool, or a 404 without one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenerDefinition {
+    pub name: String,
+    pub port: u16,
+    pub default_pool: Option<String>,
+}
+
+impl FromStr for ListenerDefinition {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected NAME=PORT[;default-pool=POOL], got {}", value);
+
+        let mut options = value.split(';');
+        let (name, port) = options
+            .next()
+            .and_then(|listener| listener.split_once('='))
+            .ok_or_else(invalid)?;
+        let name = name.trim();
+        let port = port.trim().parse().map_err(|_| invalid())?;
+
+        if name.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut definition = ListenerDefinition {
+            name: name.to_string(),
+            port,
+            default_pool: None,
+        };
+
+        for option in options {
+            match option.trim().split_once('=') {
+                Some(("default-pool", pool)) if !pool.trim().is_empty() => {
+                    definition.default_pool = Some(pool.trim().to_string())
+                }
+                _ => return Err(format!("invalid option {} of listener {}", option, name)),
+            }
+        }
+
+        Ok(definition)
+    }
+}
+
+/// Fails on listeners bound to the port of the main one, `port`, or of
+/// another listener, which SO_REUSEPORT would otherwise let them share.
+pub fn check_ports(port: u16, listeners: &[ListenerDefinition]) -> Result<(), String> {
+    for (index, listener) in listeners.iter().enumerate() {
+        if listener.port == port
+            || listeners[..index]
+                .iter()
+                .any(|other| other.port == listener.port)
+        {
+            return Err(format!(
+                "port {} of listener {} is already in use",
+                listener.port, listener.name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The pools of a listener, with the blue/green services and the routes
+/// leading to them.
+#[derive(Default)]
+pub struct ListenerPools {
+    pub pools: Vec<Pool>,
+    pub blue_greens: Vec<BlueGreenDefinition>,
+    pub routes: Vec<PoolRoute>,
+}
+
+/// Splits the pools, each with the listener it was given if any, between
+/// the main listener, first, and `listeners`, in order. Blue/green
+/// services and routes follow their pools, and fail when these are served
+/// by different listeners. Those naming unknown pools are left to the main
+/// listener, which rejects them.
+pub fn split_by_listener(
+    listeners: &[ListenerDefinition],
+    pools: Vec<(Option<String>, Pool)>,
+    blue_greens: Vec<BlueGreenDefinition>,
+    routes: Vec<PoolRoute>,
+) -> Result<Vec<ListenerPools>, String> {
+    let mut split = (0..=listeners.len())
+        .map(|_| ListenerPools::default())
+        .collect::<Vec<_>>();
+    let mut listener_of = HashMap::new();
+
+    for (listener, pool) in pools {
+        let index = match listener {
+            Some(listener) => {
+                listeners
+                    .iter()
+                    .position(|definition| definition.name == listener)
+                    .ok_or_else(|| {
+                        format!(
+                            "pool {} is served by unknown listener {}",
+                            pool.name, listener
+                        )
+                    })?
+                    + 1
+            }
+            None => 0,
+        };
+        listener_of.insert(pool.name.clone(), index);
+        split[index].pools.push(pool);
+    }
+
+    for blue_green in blue_greens {
+        let index = match (
+            listener_of.get(&blue_green.blue),
+            listener_of.get(&blue_green.green),
+        ) {
+            (Some(blue), Some(green)) if blue != green => {
+                return Err(format!(
+                    "blue/green service {} has pools served by different listeners",
+                    blue_green.name
+                ));
+            }
+            (Some(index), _) | (_, Some(index)) => *index,
+            (None, None) => 0,
+        };
+        listener_of.insert(blue_green.name.clone(), index);
+        split[index].blue_greens.push(blue_green);
+    }
+
+    for route in routes {
+        let mut indexes = route
+            .pools
+            .iter()
+            .filter_map(|(name, _)| listener_of.get(name).copied());
+        let index = indexes.next().unwrap_or(0);
+
+        if indexes.any(|other| other != index) {
+            let names = route
+                .pools
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "route to {} leads to pools served by different listeners",
+                names.join(",")
+            ));
+        }
+        split[index].routes.push(route);
+    }
+
+    Ok(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use crate::RoundRobinSelectServer;
+    use crate::allowed_methods::AllowedMethods;
+    use crate::pools::listener_pools::{ListenerDefinition, check_ports, split_by_listener};
+    use crate::pools::pool::Pool;
+
+    fn pool(listener: Option<&str>, name: &str) -> (Option<String>, Pool) {
+        let healthy_servers = Arc::new(RwLock::new(Vec::new()));
+
+        (
+            listener.map(str::to_string),
+            Pool {
+                name: name.to_string(),
+                target_servers: Arc::new(Vec::new()),
+                select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&healthy_servers))),
+                healthy_servers,
+                health_path: "/health".to_string(),
+                allowed_methods: AllowedMethods::default(),
+                http_client: None,
+                location_rewrite: None,
+                host_header: None,
+            },
+        )
+    }
+
+    fn internal() -> Vec<ListenerDefinition> {
+        vec!["internal=8081".parse().unwrap()]
+    }
+
+    #[test]
+    fn parses_listeners() {
+        assert_eq!(
+            "internal=8081;default-pool=admin"
+                .parse::<ListenerDefinition>()
+                .unwrap(),
+            ListenerDefinition {
+                name: "internal".to_string(),
+                port: 8081,
+                default_pool: Some("admin".to_string()),
+            }
+        );
+
+        for definition in [
+            "internal",
+            "=8081",
+            "internal=http",
+            "internal=8081;default-pool=",
+            "internal=8081;tls=off",
+        ] {
+            assert!(
+                definition.parse::<ListenerDefinition>().is_err(),
+                "{}",
+                definition
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_ports_in_use() {
+        let listeners = vec![
+            "internal=8081".parse().unwrap(),
+            "metrics=9090".parse().unwrap(),
+        ];
+
+        assert!(check_ports(3000, &listeners).is_ok());
+        assert!(check_ports(8081, &listeners).is_err());
+        assert!(check_ports(3000, &[listeners[0].clone(), listeners[0].clone()]).is_err());
+    }
+
+    #[test]
+    fn splits_the_pools_and_what_leads_to_them() {
+        let split = split_by_listener(
+            &internal(),
+            vec![
+                pool(None, "api"),
+                pool(Some("internal"), "admin-blue"),
+                pool(Some("internal"), "admin-green"),
+                pool(Some("internal"), "metrics"),
+            ],
+            vec!["admin=admin-blue,admin-green".parse().unwrap()],
+            vec![
+                "/api/*=>api".parse().unwrap(),
+                "/admin/*=>admin".parse().unwrap(),
+                "/metrics=>metrics".parse().unwrap(),
+                "/static/*=>static".parse().unwrap(),
+            ],
+        )
+        .unwrap();
+
+        let names = |pools: &[Pool]| {
+            pools
+                .iter()
+                .map(|pool| pool.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(split.len(), 2);
+        assert_eq!(names(&split[0].pools), vec!["api"]);
+        assert_eq!(
+            names(&split[1].pools),
+            vec!["admin-blue", "admin-green", "metrics"]
+        );
+        assert!(split[0].blue_greens.is_empty());
+        assert_eq!(split[1].blue_greens[0].name, "admin");
+        assert_eq!(split[0].routes.len(), 2);
+        assert_eq!(split[1].routes.len(), 2);
+    }
+
+    #[test]
+    fn rejects_what_spans_several_listeners() {
+        let unknown_listener = split_by_listener(
+            &internal(),
+            vec![pool(Some("public"), "api")],
+            vec![],
+            vec![],
+        );
+        let split_route = split_by_listener(
+            &internal(),
+            vec![pool(None, "api"), pool(Some("internal"), "canary")],
+            vec![],
+            vec!["/*=>api:95,canary:5".parse().unwrap()],
+        );
+        let split_blue_green = split_by_listener(
+            &internal(),
+            vec![
+                pool(None, "shop-blue"),
+                pool(Some("internal"), "shop-green"),
+            ],
+            vec!["shop=shop-blue,shop-green".parse().unwrap()],
+            vec![],
+        );
+
+        assert!(unknown_listener.is_err());
+        assert!(split_route.is_err());
+        assert!(split_blue_green.is_err());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod blue_green;
+pub mod listener_pools;
+pub mod pool;
+pub mod pool_route;
+#[allow(clippy::module_inception)]
+pub mod pools;
+pub mod swappable_pools;
@@ -0,0 +1,328 @@
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::future::join_all;
+
+use crate::allowed_methods::AllowedMethods;
+use crate::consul_discovery::ConsulService;
+use crate::host_header::HostHeader;
+use crate::http_client::client_identity_http_client::ClientIdentityFiles;
+use crate::http_client::http_client::HttpClient;
+use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+use crate::http_client::upstream_protocol::UpstreamProtocol;
+use crate::location_rewrite::LocationRewrite;
+use crate::select_server::select_server::SelectServer;
+use crate::target_url::normalize_target_url;
+
+/// How a pool picks among its healthy backends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PoolPolicy {
+    #[default]
+    RoundRobin,
+    Random,
+}
+
+/// A named group of backends, written as
+/// `NAME=BACKEND|BACKEND[;policy=round-robin|random][;health-path=PATH][;consul=SERVICE][;srv=NAME][;listener=NAME][;allow=METHODS][;http-version=VERSION][;rewrite-redirects=BOOL][;host-header=MODE][;sni=NAME][;client-cert=CERT:KEY]`,
+/// e.g. `api=http://api-1:8080|http://api-2:8080;policy=random`. Without a
+/// health path the pool is probed on the one of the target servers. A pool
+/// allowing some methods only, as in `static=http://cdn:8080;allow=GET|HEAD`,
+/// answers the others with a 405 before reaching its backends. A pool given
+/// an HTTP version, as in `api=http://api-1:8080;http-version=http2`, speaks
+/// it to its backends instead of `--upstream-http-version`, and one given
+/// `rewrite-redirects=true` or `false` points the redirects of its backends
+/// back at the load balancer or not, whatever `--rewrite-redirects` says.
+/// Likewise, `host-header=preserve` or `upstream` sends its backends the host
+/// the client asked for or their own, instead of `--host-header`, and
+/// `sni=NAME` reaches them over TLS under that server name, instead of
+/// `--upstream-sni`. A pool given `client-cert=CERT:KEY` presents that
+/// client certificate to its backends instead of `--upstream-client-cert`.
+/// The backends of a pool taking them from a Consul service or an SRV record
+/// are left out, as in `api=;consul=api:primary` or
+/// `api=;srv=_http._tcp.api.example.com`. A pool given a listener is only
+/// served by it, the others by the main one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolDefinition {
+    pub name: String,
+    pub backends: Vec<String>,
+    pub policy: PoolPolicy,
+    pub health_path: Option<String>,
+    pub consul: Option<ConsulService>,
+    pub srv: Option<String>,
+    pub listener: Option<String>,
+    pub allowed_methods: AllowedMethods,
+    pub http_version: Option<UpstreamProtocol>,
+    pub location_rewrite: Option<LocationRewrite>,
+    pub host_header: Option<HostHeader>,
+    pub sni: Option<String>,
+    pub client_identity: Option<ClientIdentityFiles>,
+}
+
+impl FromStr for PoolDefinition {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "expected NAME=BACKEND|BACKEND[;policy=POLICY][;health-path=PATH][;consul=SERVICE][;srv=NAME][;listener=NAME][;allow=METHODS][;http-version=VERSION][;rewrite-redirects=BOOL][;host-header=MODE][;sni=NAME][;client-cert=CERT:KEY], got {}",
+                value
+            )
+        };
+
+        let mut options = value.split(';');
+        let (name, backends) = options
+            .next()
+            .and_then(|pool| pool.split_once('='))
+            .ok_or_else(invalid)?;
+
+        let name = name.trim();
+        let backends = backends
+            .split('|')
+            .map(str::trim)
+            .filter(|backend| !backend.is_empty())
+            .map(normalize_target_url)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if name.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut definition = PoolDefinition {
+            name: name.to_string(),
+            backends,
+            policy: PoolPolicy::default(),
+            health_path: None,
+            consul: None,
+            srv: None,
+            listener: None,
+            allowed_methods: AllowedMethods::default(),
+            http_version: None,
+            location_rewrite: None,
+            host_header: None,
+            sni: None,
+            client_identity: None,
+        };
+
+        for option in options {
+            match option.trim().split_once('=') {
+                Some(("policy", "round-robin")) => definition.policy = PoolPolicy::RoundRobin,
+                Some(("policy", "random")) => definition.policy = PoolPolicy::Random,
+                Some(("health-path", path)) if path.starts_with('/') => {
+                    definition.health_path = Some(path.to_string())
+                }
+                Some(("consul", service)) => definition.consul = Some(service.parse()?),
+                Some(("srv", name)) if !name.trim().is_empty() => {
+                    definition.srv = Some(name.trim().trim_end_matches('.').to_string())
+                }
+                Some(("listener", listener)) if !listener.trim().is_empty() => {
+                    definition.listener = Some(listener.trim().to_string())
+                }
+                Some(("allow", methods)) => definition.allowed_methods = methods.parse()?,
+                Some(("http-version", version)) => definition.http_version = Some(version.parse()?),
+                Some(("rewrite-redirects", enabled)) => {
+                    definition.location_rewrite = Some(LocationRewrite {
+                        enabled: enabled.trim().parse().map_err(|_| invalid())?,
+                    })
+                }
+                Some(("host-header", mode)) => definition.host_header = Some(mode.parse()?),
+                Some(("sni", name)) if !name.trim().is_empty() => {
+                    definition.sni = Some(name.trim().to_string())
+                }
+                Some(("client-cert", files)) => {
+                    let (certificate, key) = files
+                        .split_once(':')
+                        .filter(|(certificate, key)| !certificate.is_empty() && !key.is_empty())
+                        .ok_or_else(invalid)?;
+                    definition.client_identity = Some(ClientIdentityFiles {
+                        certificate: certificate.into(),
+                        key: key.into(),
+                    })
+                }
+                _ => return Err(format!("invalid option {} of pool {}", option, name)),
+            }
+        }
+
+        let sources = [
+            !definition.backends.is_empty(),
+            definition.consul.is_some(),
+            definition.srv.is_some(),
+        ];
+        if sources.into_iter().filter(|source| *source).count() != 1 {
+            return Err(format!(
+                "pool {} needs either backends, a consul service or an SRV record",
+                name
+            ));
+        }
+
+        Ok(definition)
+    }
+}
+
+/// A pool ready to serve requests, whose health checker keeps
+/// `healthy_servers` up to date and whose `select_server` picks among them.
+#[derive(Clone)]
+pub struct Pool {
+    pub name: String,
+    pub target_servers: Arc<Vec<String>>,
+    pub healthy_servers: Arc<RwLock<Vec<String>>>,
+    pub select_server: Arc<dyn SelectServer>,
+    /// Probed on every backend, also when verifying the pool before a
+    /// blue/green switch.
+    pub health_path: String,
+    pub allowed_methods: AllowedMethods,
+    /// The client of the backends, when the pool has upstream settings of
+    /// its own, rather than the one of the target servers.
+    pub http_client: Option<Arc<dyn HttpClient>>,
+    /// Overrides the one of the load balancer.
+    pub location_rewrite: Option<LocationRewrite>,
+    /// Overrides the one of the load balancer.
+    pub host_header: Option<HostHeader>,
+}
+
+impl Pool {
+    /// Probes every backend right away, regardless of the last round of the
+    /// health checker, returning the ones that didn't answer a 200 in time.
+    pub async fn unhealthy_backends(
+        &self,
+        http_client: &dyn HttpClient,
+        timeout: Duration,
+    ) -> Vec<String> {
+        let probes = self.target_servers.iter().map(|server| async move {
+            let request = Request {
+                method: RequestMethod::Get,
+                url: format!("{}{}", server, self.health_path),
+                headers: RequestHeaders::default(),
+                body: Bytes::new().into(),
+                timeout: None,
+            };
+
+            match tokio::time::timeout(timeout, http_client.execute(request)).await {
+                Ok(Ok(response)) if response.status == 200 => None,
+                _ => Some(server.clone()),
+            }
+        });
+
+        join_all(probes).await.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use crate::allowed_methods::AllowedMethods;
+    use crate::host_header::HostHeader;
+    use crate::http_client::client_identity_http_client::ClientIdentityFiles;
+    use crate::http_client::upstream_protocol::UpstreamProtocol;
+    use crate::location_rewrite::LocationRewrite;
+    use crate::pools::pool::{PoolDefinition, PoolPolicy};
+
+    #[test]
+    fn parses_name_and_backends() {
+        let definition: PoolDefinition =
+            "api=http://api-1:8080/|http://api-2:8080".parse().unwrap();
+
+        assert_eq!(
+            definition,
+            PoolDefinition {
+                name: "api".to_string(),
+                backends: vec![
+                    "http://api-1:8080".to_string(),
+                    "http://api-2:8080".to_string()
+                ],
+                policy: PoolPolicy::RoundRobin,
+                health_path: None,
+                consul: None,
+                srv: None,
+                listener: None,
+                allowed_methods: AllowedMethods::default(),
+                http_version: None,
+                location_rewrite: None,
+                host_header: None,
+                sni: None,
+                client_identity: None,
+            }
+        );
+    }
+
+    #[test]
+    fn takes_the_backends_from_a_consul_service() {
+        let definition: PoolDefinition = "api=;consul=api:primary".parse().unwrap();
+
+        assert!(definition.backends.is_empty());
+        assert_eq!(definition.consul, Some("api:primary".parse().unwrap()));
+    }
+
+    #[test]
+    fn takes_the_backends_from_an_srv_record() {
+        let definition: PoolDefinition = "api=;srv=_http._tcp.api.example.com.".parse().unwrap();
+
+        assert!(definition.backends.is_empty());
+        assert_eq!(
+            definition.srv,
+            Some("_http._tcp.api.example.com".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let definition: PoolDefinition =
+            "static=http://cdn:8080;policy=random;health-path=/ready;listener=internal;allow=get|HEAD;http-version=http2;rewrite-redirects=true;host-header=upstream;sni=cdn.internal;client-cert=/etc/lb/cdn.crt:/etc/lb/cdn.key"
+                .parse()
+                .unwrap();
+
+        assert_eq!(definition.policy, PoolPolicy::Random);
+        assert_eq!(
+            definition.allowed_methods,
+            AllowedMethods(vec![Method::GET, Method::HEAD])
+        );
+        assert_eq!(definition.http_version, Some(UpstreamProtocol::Http2));
+        assert_eq!(
+            definition.location_rewrite,
+            Some(LocationRewrite { enabled: true })
+        );
+        assert_eq!(definition.host_header, Some(HostHeader::Upstream));
+        assert_eq!(definition.sni, Some("cdn.internal".to_string()));
+        assert_eq!(
+            definition.client_identity,
+            Some(ClientIdentityFiles {
+                certificate: "/etc/lb/cdn.crt".into(),
+                key: "/etc/lb/cdn.key".into(),
+            })
+        );
+        assert_eq!(definition.health_path, Some("/ready".to_string()));
+        assert_eq!(definition.listener, Some("internal".to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_definitions() {
+        for definition in [
+            "api",
+            "api=",
+            "=http://api-1:8080",
+            "api=api-1:8080",
+            "api=http://api-1:8080;policy=fastest",
+            "api=http://api-1:8080;health-path=ready",
+            "api=http://api-1:8080;weight=2",
+            "api=http://api-1:8080;consul=api",
+            "api=;consul=",
+            "api=;srv=",
+            "api=http://api-1:8080;listener=",
+            "api=http://api-1:8080;allow=GET|",
+            "api=http://api-1:8080;http-version=h3",
+            "api=http://api-1:8080;rewrite-redirects=yes",
+            "api=http://api-1:8080;host-header=backend",
+            "api=http://api-1:8080;sni=",
+            "api=http://api-1:8080;client-cert=/etc/lb/api.crt",
+            "api=;consul=api;srv=_http._tcp.api.example.com",
+        ] {
+            assert!(
+                definition.parse::<PoolDefinition>().is_err(),
+                "{}",
+                definition
+            );
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use std::str::FromStr;
+
+use http::header::HeaderName;
+use http::uri::Authority;
+use http::{HeaderMap, Method, Uri, header};
+
+use crate::allowed_methods::AllowedMethods;
+use crate::experiment::X_EXPERIMENT_VARIANT;
+use crate::geo_ip::geo_ip::{X_GEO_CONTINENT, X_GEO_COUNTRY};
+use crate::grpc::GrpcMatcher;
+use crate::path_regex::PathRegex;
+use crate::time_rules::TimeWindow;
+
+/// What a request must look like to be sent to a pool.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteCondition {
+    /// The host the client asked for, from `Host` or the HTTP/2
+    /// `:authority`, lowercased and without the port. `*.example.com`
+    /// covers the subdomains of `example.com`, but not `example.com` itself.
+    Host(String),
+    /// Paths under the prefix, on a segment boundary: `/api` covers
+    /// `/api/users` but not `/apis`. Compared as sent, percent-encoding
+    /// included.
+    PathPrefix(String),
+    /// Paths matching the regular expression, anywhere unless anchored.
+    PathRegex(PathRegex),
+    /// Requests made with one of the methods, e.g. reads with `GET` and `HEAD`.
+    Methods(Vec<Method>),
+    /// A header sent with exactly this value, e.g. `X-Canary: true`.
+    Header(HeaderName, String),
+    /// A cookie sent with exactly this value, e.g. `canary=1`.
+    Cookie(String, String),
+    /// Clients located in one of the countries, e.g. `DE` or `FR`, by
+    /// `--geoip-database`. Never met without a database.
+    Countries(Vec<String>),
+    /// Clients located on one of the continents, e.g. `EU`.
+    Continents(Vec<String>),
+    /// gRPC calls of a service, or of one of its methods.
+    Grpc(GrpcMatcher),
+    /// Clients assigned one of the variants by `--experiment`. Never met
+    /// without an experiment.
+    Variants(Vec<String>),
+}
+
+impl RouteCondition {
+    pub fn matches(&self, method: &Method, uri: &Uri, headers: &HeaderMap) -> bool {
+        match self {
+            RouteCondition::Host(pattern) => {
+                host(uri, headers).is_some_and(|host| match pattern.strip_prefix("*.") {
+                    Some(domain) => host
+                        .strip_suffix(domain)
+                        .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+                    None => host == *pattern,
+                })
+            }
+            RouteCondition::PathPrefix(prefix) => uri
+                .path()
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+            RouteCondition::PathRegex(regex) => regex.is_match(uri.path()),
+            RouteCondition::Methods(methods) => methods.contains(method),
+            RouteCondition::Header(name, expected) => headers
+                .get_all(name)
+                .iter()
+                .any(|value| value.as_bytes() == expected.as_bytes()),
+            RouteCondition::Cookie(name, expected) => headers
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(';'))
+                .filter_map(|cookie| cookie.trim().split_once('='))
+                .any(|(cookie, value)| cookie == name && value == expected),
+            RouteCondition::Countries(codes) => located_in(headers, &X_GEO_COUNTRY, codes),
+            RouteCondition::Continents(codes) => located_in(headers, &X_GEO_CONTINENT, codes),
+            RouteCondition::Grpc(matcher) => matcher.matches(uri, headers),
+            RouteCondition::Variants(variants) => {
+                headers.get(X_EXPERIMENT_VARIANT).is_some_and(|variant| {
+                    variants.iter().any(|expected| variant == expected.as_str())
+                })
+            }
+        }
+    }
+}
+
+fn located_in(headers: &HeaderMap, name: &HeaderName, codes: &[String]) -> bool {
+    headers
+        .get(name)
+        .is_some_and(|code| codes.iter().any(|expected| code == expected.as_str()))
+}
+
+fn host(uri: &Uri, headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Authority>().ok())
+        .map(|authority| authority.host().to_ascii_lowercase())
+        .or_else(|| uri.host().map(str::to_ascii_lowercase))
+}
+
+/// Sends the requests matching every condition to one of `pools`, each
+/// taking a share of them proportional to its weight.
+///
+/// Written as `[HOST][PATH][;CONDITION]...=>POOL`, e.g. `/api/*=>api`,
+/// `admin.example.com=>admin` or `*.example.com/static/*=>static`, the
+/// trailing `/*` of the path being optional. The further conditions are
+/// `regex:PATTERN` on the path, `method:METHOD|METHOD`, `header:NAME=VALUE`,
+/// `cookie:NAME=VALUE`, `country:CODE|CODE`, `continent:CODE|CODE`,
+/// `grpc:SERVICE[/METHOD]` and `variant:VARIANT|VARIANT`, e.g.
+/// `regex:^/users/\d+/orders$=>orders`, `method:GET|HEAD=>replicas`,
+/// `header:X-Canary=true=>canary`, `continent:EU=>eu`,
+/// `grpc:shop.v1.Orders=>orders` or `/checkout;variant:treatment=>checkout-v2`.
+/// Unlike `method:`, which lets the other methods try the next routes,
+/// `allow:METHOD|METHOD` answers them with a 405, e.g.
+/// `/static/*;allow:GET|HEAD=>static`. The requests can be
+/// split between pools as `POOL:WEIGHT,POOL:WEIGHT`, e.g.
+/// `/*=>stable:95,canary:5`. A route applies every day during the
+/// `time:HH:MM-HH:MM` window only, when given, e.g.
+/// `time:02:00-03:00=>maintenance`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolRoute {
+    pub conditions: Vec<RouteCondition>,
+    /// The pools with their weights, `1` when not given.
+    pub pools: Vec<(String, u32)>,
+    /// When the route applies, in the timezone of `--time-rules-utc-offset`.
+    pub schedule: Option<TimeWindow>,
+    /// The methods the pools accept, the others answered with a 405.
+    pub allowed_methods: AllowedMethods,
+}
+
+impl PoolRoute {
+    pub fn matches(&self, method: &Method, uri: &Uri, headers: &HeaderMap) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(method, uri, headers))
+    }
+}
+
+impl FromStr for PoolRoute {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "expected [HOST][PATH][;CONDITION]...=>POOL[:WEIGHT],..., got {}",
+                value
+            )
+        };
+
+        let (condition, pools) = value.rsplit_once("=>").ok_or_else(invalid)?;
+
+        let pools = pools
+            .split(',')
+            .map(|pool| {
+                let (name, weight) = match pool.split_once(':') {
+                    Some((name, weight)) => {
+                        (name.trim(), weight.trim().parse().map_err(|_| invalid())?)
+                    }
+                    None => (pool.trim(), 1),
+                };
+
+                if name.is_empty() {
+                    return Err(invalid());
+                }
+
+                Ok((name.to_string(), weight))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if pools
+            .iter()
+            .map(|(_, weight)| u64::from(*weight))
+            .sum::<u64>()
+            == 0
+        {
+            return Err(invalid());
+        }
+
+        let mut location = "";
+        let mut matchers = Vec::new();
+        let mut schedule = None;
+        let mut allowed_methods = AllowedMethods::default();
+
+        for (index, segment) in condition.trim().split(';').map(str::trim).enumerate() {
+            if let Some(pattern) = segment.strip_prefix("regex:") {
+                matchers.push(RouteCondition::PathRegex(pattern.parse()?));
+            } else if let Some(methods) = segment.strip_prefix("method:") {
+                let methods = methods
+                    .split('|')
+                    .map(|method| method.trim().to_ascii_uppercase().parse::<Method>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid())?;
+                matchers.push(RouteCondition::Methods(methods));
+            } else if let Some(header) = segment.strip_prefix("header:") {
+                let (name, expected) = header.split_once('=').ok_or_else(invalid)?;
+                let name = name.trim().parse::<HeaderName>().map_err(|_| invalid())?;
+                matchers.push(RouteCondition::Header(name, expected.trim().to_string()));
+            } else if let Some(cookie) = segment.strip_prefix("cookie:") {
+                let (name, expected) = cookie.split_once('=').ok_or_else(invalid)?;
+                if name.trim().is_empty() {
+                    return Err(invalid());
+                }
+                matchers.push(RouteCondition::Cookie(
+                    name.trim().to_string(),
+                    expected.trim().to_string(),
+                ));
+            } else if let Some(countries) = segment.strip_prefix("country:") {
+                matchers.push(RouteCondition::Countries(
+                    codes(countries).ok_or_else(invalid)?,
+                ));
+            } else if let Some(continents) = segment.strip_prefix("continent:") {
+                matchers.push(RouteCondition::Continents(
+                    codes(continents).ok_or_else(invalid)?,
+                ));
+            } else if let Some(call) = segment.strip_prefix("grpc:") {
+                matchers.push(RouteCondition::Grpc(call.parse()?));
+            } else if let Some(methods) = segment.strip_prefix("allow:")
+                && allowed_methods.0.is_empty()
+            {
+                allowed_methods = methods.parse().map_err(|_| invalid())?;
+            } else if let Some(variants) = segment.strip_prefix("variant:") {
+                let variants = variants
+                    .split('|')
+                    .map(|variant| variant.trim().to_string())
+                    .collect::<Vec<_>>();
+                if variants.iter().any(String::is_empty) {
+                    return Err(invalid());
+                }
+                matchers.push(RouteCondition::Variants(variants));
+            } else if let Some(window) = segment.strip_prefix("time:")
+                && schedule.is_none()
+            {
+                schedule = Some(window.parse()?);
+            } else if index == 0 {
+                location = segment;
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        let (host, path) = match location.find('/') {
+            Some(index) => location.split_at(index),
+            None => (location, ""),
+        };
+
+        if host.is_empty() && path.is_empty() && matchers.is_empty() && schedule.is_none() {
+            return Err(invalid());
+        }
+
+        let mut conditions = Vec::new();
+
+        if !host.is_empty() {
+            let domain = host.strip_prefix("*.").unwrap_or(host);
+            if domain.parse::<Authority>().is_err() || domain.contains(['*', ':']) {
+                return Err(invalid());
+            }
+
+            conditions.push(RouteCondition::Host(host.to_ascii_lowercase()));
+        }
+
+        // `/api/*`, `/api/` and `/api` are the same prefix, `/*` is any path.
+        let prefix = path.trim_end_matches('*').trim_end_matches('/');
+        if !prefix.is_empty() {
+            conditions.push(RouteCondition::PathPrefix(prefix.to_string()));
+        }
+
+        conditions.extend(matchers);
+
+        Ok(PoolRoute {
+            conditions,
+            pools,
+            schedule,
+            allowed_methods,
+        })
+    }
+}
+
+/// Two-letter codes separated by `|`, uppercased.
+fn codes(value: &str) -> Option<Vec<String>> {
+    value
+        .split('|')
+        .map(|code| {
+            let code = code.trim();
+            (code.len() == 2
+                && code
+                    .chars()
+                    .all(|character| character.is_ascii_alphabetic()))
+            .then(|| code.to_ascii_uppercase())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::HeaderName;
+    use http::{HeaderMap, HeaderValue, Method, Uri, header};
+
+    use crate::allowed_methods::AllowedMethods;
+    use crate::experiment::X_EXPERIMENT_VARIANT;
+    use crate::geo_ip::geo_ip::{X_GEO_CONTINENT, X_GEO_COUNTRY};
+    use crate::pools::pool_route::{PoolRoute, RouteCondition};
+    use crate::time_rules::TimeWindow;
+
+    fn matches(route: &str, host: &'static str, uri: &'static str) -> bool {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static(host));
+
+        route
+            .parse::<PoolRoute>()
+            .unwrap()
+            .matches(&Method::GET, &Uri::from_static(uri), &headers)
+    }
+
+    #[test]
+    fn parses_path_and_pool() {
+        assert_eq!(
+            "/api/*=>api".parse(),
+            Ok(PoolRoute {
+                conditions: vec![RouteCondition::PathPrefix("/api".to_string())],
+                pools: vec![("api".to_string(), 1)],
+                schedule: None,
+                allowed_methods: AllowedMethods::default(),
+            })
+        );
+        assert_eq!(
+            "/api/*=>api".parse::<PoolRoute>(),
+            "/api=>api".parse::<PoolRoute>()
+        );
+    }
+
+    #[test]
+    fn parses_host_and_path() {
+        assert_eq!(
+            "Admin.Example.com/static/*=>static".parse(),
+            Ok(PoolRoute {
+                conditions: vec![
+                    RouteCondition::Host("admin.example.com".to_string()),
+                    RouteCondition::PathPrefix("/static".to_string()),
+                ],
+                pools: vec![("static".to_string(), 1)],
+                schedule: None,
+                allowed_methods: AllowedMethods::default(),
+            })
+        );
+        assert_eq!(
+            "admin.example.com/*=>admin".parse::<PoolRoute>(),
+            "admin.example.com=>admin".parse::<PoolRoute>()
+        );
+    }
+
+    #[test]
+    fn parses_weighted_pools() {
+        assert_eq!(
+            "/*=>stable:95, canary:5".parse(),
+            Ok(PoolRoute {
+                conditions: Vec::new(),
+                pools: vec![("stable".to_string(), 95), ("canary".to_string(), 5)],
+                schedule: None,
+                allowed_methods: AllowedMethods::default(),
+            })
+        );
+        assert_eq!(
+            "/api/*=>api,canary"
+                .parse::<PoolRoute>()
+                .map(|route| route.pools),
+            Ok(vec![("api".to_string(), 1), ("canary".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn matches_the_paths_under_the_prefix() {
+        assert!(matches("/api/*=>api", "lb", "/api"));
+        assert!(matches("/api/*=>api", "lb", "/api/users?id=7"));
+        assert!(!matches("/api/*=>api", "lb", "/apis"));
+        assert!(!matches("/api/*=>api", "lb", "/static/app.js"));
+    }
+
+    #[test]
+    fn matches_the_host_without_its_port() {
+        assert!(matches("api.example.com=>api", "API.example.com:8443", "/"));
+        assert!(!matches("api.example.com=>api", "admin.example.com", "/"));
+        assert!(matches("*.example.com=>tenants", "acme.example.com", "/"));
+        assert!(!matches("*.example.com=>tenants", "example.com", "/"));
+        assert!(!matches("*.example.com=>tenants", "badexample.com", "/"));
+        assert!(matches(
+            "admin.example.com/static/*=>static",
+            "admin.example.com",
+            "/static/app.js"
+        ));
+        assert!(!matches(
+            "admin.example.com/static/*=>static",
+            "api.example.com",
+            "/static/app.js"
+        ));
+    }
+
+    #[test]
+    fn matches_the_http2_authority() {
+        let route: PoolRoute = "api.example.com=>api".parse().unwrap();
+
+        assert!(route.matches(
+            &Method::GET,
+            &Uri::from_static("https://api.example.com/users"),
+            &HeaderMap::new()
+        ));
+    }
+
+    #[test]
+    fn parses_header_and_cookie_conditions() {
+        assert_eq!(
+            "/api/*;header:X-Canary=true;cookie:canary=1=>canary".parse(),
+            Ok(PoolRoute {
+                conditions: vec![
+                    RouteCondition::PathPrefix("/api".to_string()),
+                    RouteCondition::Header(HeaderName::from_static("x-canary"), "true".to_string()),
+                    RouteCondition::Cookie("canary".to_string(), "1".to_string()),
+                ],
+                pools: vec![("canary".to_string(), 1)],
+                schedule: None,
+                allowed_methods: AllowedMethods::default(),
+            })
+        );
+        assert_eq!(
+            "header:X-Canary=true=>canary".parse(),
+            Ok(PoolRoute {
+                conditions: vec![RouteCondition::Header(
+                    HeaderName::from_static("x-canary"),
+                    "true".to_string()
+                )],
+                pools: vec![("canary".to_string(), 1)],
+                schedule: None,
+                allowed_methods: AllowedMethods::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn matches_the_header_value() {
+        let route: PoolRoute = "header:X-Canary=true=>canary".parse().unwrap();
+        let matches = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-canary", HeaderValue::from_static(value));
+
+            route.matches(&Method::GET, &Uri::from_static("/"), &headers)
+        };
+
+        assert!(matches("true"));
+        assert!(!matches("false"));
+        assert!(!route.matches(&Method::GET, &Uri::from_static("/"), &HeaderMap::new()));
+    }
+
+    #[test]
+    fn matches_the_cookie_value() {
+        let route: PoolRoute = "cookie:canary=1=>canary".parse().unwrap();
+        let matches = |cookies: &[&'static str]| {
+            let mut headers = HeaderMap::new();
+            for cookie in cookies {
+                headers.append(header::COOKIE, HeaderValue::from_static(cookie));
+            }
+
+            route.matches(&Method::GET, &Uri::from_static("/"), &headers)
+        };
+
+        assert!(matches(&["session=abc; canary=1"]));
+        assert!(matches(&["session=abc", "canary=1"]));
+        assert!(!matches(&["canary=0"]));
+        assert!(!matches(&["nocanary=1"]));
+        assert!(!matches(&[]));
+    }
+
+    #[test]
+    fn matches_the_path_regex() {
+        assert!(matches(
+            r"regex:^/users/\d+/orders$=>orders",
+            "lb",
+            "/users/7/orders"
+        ));
+        assert!(!matches(
+            r"regex:^/users/\d+/orders$=>orders",
+            "lb",
+            "/users/me/orders"
+        ));
+        assert!(matches(
+            r"api.example.com;regex:\.json$=>json",
+            "api.example.com",
+            "/users/7.json?fields=id"
+        ));
+        assert!(!matches(
+            r"api.example.com;regex:\.json$=>json",
+            "admin.example.com",
+            "/users/7.json"
+        ));
+    }
+
+    #[test]
+    fn matches_the_methods() {
+        let route: PoolRoute = "/orders;method:get|HEAD=>replicas".parse().unwrap();
+        let matches = |method: Method, uri: &'static str| {
+            route.matches(&method, &Uri::from_static(uri), &HeaderMap::new())
+        };
+
+        assert_eq!(
+            route.conditions[1],
+            RouteCondition::Methods(vec![Method::GET, Method::HEAD])
+        );
+        assert!(matches(Method::GET, "/orders/7"));
+        assert!(matches(Method::HEAD, "/orders"));
+        assert!(!matches(Method::POST, "/orders"));
+        assert!(!matches(Method::GET, "/users"));
+    }
+
+    #[test]
+    fn matches_the_country_and_continent_of_the_client() {
+        let route: PoolRoute = "country:de|AT;continent:EU=>dach".parse().unwrap();
+        let matches = |country: &'static str, continent: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(X_GEO_COUNTRY, HeaderValue::from_static(country));
+            headers.insert(X_GEO_CONTINENT, HeaderValue::from_static(continent));
+
+            route.matches(&Method::GET, &Uri::from_static("/"), &headers)
+        };
+
+        assert_eq!(
+            route.conditions,
+            vec![
+                RouteCondition::Countries(vec!["DE".to_string(), "AT".to_string()]),
+                RouteCondition::Continents(vec!["EU".to_string()]),
+            ]
+        );
+        assert!(matches("DE", "EU"));
+        assert!(matches("AT", "EU"));
+        assert!(!matches("FR", "EU"));
+        assert!(!route.matches(&Method::GET, &Uri::from_static("/"), &HeaderMap::new()));
+    }
+
+    #[test]
+    fn matches_the_grpc_service_and_method() {
+        let service: PoolRoute = "grpc:shop.v1.Orders=>orders".parse().unwrap();
+        let method: PoolRoute = "grpc:shop.v1.Reports/Export=>batch".parse().unwrap();
+        let matches = |route: &PoolRoute, path: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/grpc"),
+            );
+
+            route.matches(&Method::POST, &Uri::from_static(path), &headers)
+        };
+
+        assert!(matches(&service, "/shop.v1.Orders/Create"));
+        assert!(!matches(&service, "/shop.v1.Payments/Create"));
+        assert!(matches(&method, "/shop.v1.Reports/Export"));
+        assert!(!matches(&method, "/shop.v1.Reports/Daily"));
+        assert!(!service.matches(
+            &Method::POST,
+            &Uri::from_static("/shop.v1.Orders/Create"),
+            &HeaderMap::new()
+        ));
+    }
+
+    #[test]
+    fn matches_the_experiment_variant_of_the_client() {
+        let route: PoolRoute = "/checkout;variant:treatment|holdout=>checkout-v2"
+            .parse()
+            .unwrap();
+        let matches = |variant: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(variant) = variant {
+                headers.insert(X_EXPERIMENT_VARIANT, HeaderValue::from_static(variant));
+            }
+
+            route.matches(&Method::GET, &Uri::from_static("/checkout"), &headers)
+        };
+
+        assert!(matches(Some("treatment")));
+        assert!(matches(Some("holdout")));
+        assert!(!matches(Some("control")));
+        assert!(!matches(None));
+        assert!("variant:a|=>b".parse::<PoolRoute>().is_err());
+    }
+
+    #[test]
+    fn parses_the_allowed_methods() {
+        let route: PoolRoute = "/static/*;allow:get|HEAD=>static".parse().unwrap();
+
+        assert_eq!(
+            route.allowed_methods,
+            AllowedMethods(vec![Method::GET, Method::HEAD])
+        );
+        assert_eq!(
+            route.conditions,
+            vec![RouteCondition::PathPrefix("/static".to_string())]
+        );
+        assert!(route.matches(
+            &Method::POST,
+            &Uri::from_static("/static/app.js"),
+            &HeaderMap::new()
+        ));
+        assert!(
+            "/static/*;allow:GET|G ET=>static"
+                .parse::<PoolRoute>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_the_schedule() {
+        assert_eq!(
+            "time:02:00-03:00=>maintenance".parse(),
+            Ok(PoolRoute {
+                conditions: Vec::new(),
+                pools: vec![("maintenance".to_string(), 1)],
+                schedule: Some(TimeWindow {
+                    start_minute: 2 * 60,
+                    end_minute: 3 * 60,
+                }),
+                allowed_methods: AllowedMethods::default(),
+            })
+        );
+        assert_eq!(
+            "/api/*;time:22:00-06:00=>batch"
+                .parse::<PoolRoute>()
+                .map(|route| route.conditions),
+            Ok(vec![RouteCondition::PathPrefix("/api".to_string())])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_routes() {
+        for route in [
+            "/api/*",
+            "/api/*=>",
+            "=>api",
+            "api.example.com:8080=>api",
+            "a*.example.com=>api",
+            "api example.com=>api",
+            "header:X-Canary=>canary",
+            "header:X Canary=true=>canary",
+            "cookie:=1=>canary",
+            "header:X-Canary=true;/api=>canary",
+            "/api;policy=random=>api",
+            "/*=>stable:95,canary:five",
+            "/*=>stable:0,canary:0",
+            "/*=>stable:95,:5",
+            "/*=>stable:-5",
+            "method:=>primary",
+            "method:GET||HEAD=>replicas",
+            "regex:^/users/(\\d+$=>users",
+            "country:=>eu",
+            "country:GER=>dach",
+            "continent:E1=>eu",
+            "grpc:=>orders",
+            "grpc:shop.v1.Orders/=>orders",
+            "time:02:00=>maintenance",
+            "time:02:00-25:00=>maintenance",
+            "time:02:00-03:00;time:04:00-05:00=>maintenance",
+        ] {
+            assert!(route.parse::<PoolRoute>().is_err(), "{}", route);
+        }
+    }
+}
@@ -0,0 +1,625 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use http::{HeaderMap, Method, StatusCode, Uri};
+
+use crate::allowed_methods::AllowedMethods;
+use crate::pools::blue_green::{BlueGreenDefinition, BlueGreenStatus};
+use crate::pools::pool::Pool;
+use crate::pools::pool_route::PoolRoute;
+use crate::request_id::X_REQUEST_ID;
+use crate::time_rules::DayClock;
+
+/// What a route or the default leads to: a pool, or the live pool of a
+/// blue/green service.
+#[derive(Clone, Copy)]
+enum Target {
+    Pool(usize),
+    BlueGreen(usize),
+}
+
+struct BlueGreen {
+    name: String,
+    /// The blue and the green pool.
+    pools: [usize; 2],
+    /// Which of `pools` gets the requests, flipped as a whole.
+    live: AtomicUsize,
+}
+
+/// The named pools and the routes leading to them, evaluated in order on
+/// every request. Requests matching no route go to the default pool, or get
+/// the unmatched status, or go to the target servers when there is neither.
+#[derive(Default)]
+pub struct Pools {
+    pools: Vec<Pool>,
+    blue_greens: Vec<BlueGreen>,
+    /// Each route with the target and weight of its pools.
+    routes: Vec<(PoolRoute, Vec<(Target, u32)>)>,
+    default_pool: Option<Target>,
+    /// Tells whether the scheduled routes apply.
+    day_clock: DayClock,
+    unmatched_status: Option<StatusCode>,
+}
+
+impl Pools {
+    /// Fails on pools sharing a name, and on routes, a default or
+    /// blue/green services naming pools that don't exist.
+    pub fn new(
+        pools: Vec<Pool>,
+        blue_greens: Vec<BlueGreenDefinition>,
+        routes: Vec<PoolRoute>,
+        default_pool: Option<&str>,
+    ) -> Result<Self, String> {
+        for (index, pool) in pools.iter().enumerate() {
+            if pools[..index].iter().any(|other| other.name == pool.name) {
+                return Err(format!("pool {} is defined twice", pool.name));
+            }
+        }
+
+        let pool_index = |name: &str| {
+            pools
+                .iter()
+                .position(|pool| pool.name == name)
+                .ok_or_else(|| format!("unknown pool {}", name))
+        };
+
+        let blue_greens = blue_greens
+            .into_iter()
+            .map(|definition| {
+                if pools.iter().any(|pool| pool.name == definition.name) {
+                    return Err(format!(
+                        "blue/green service {} is named after a pool",
+                        definition.name
+                    ));
+                }
+
+                Ok(BlueGreen {
+                    pools: [
+                        pool_index(&definition.blue)?,
+                        pool_index(&definition.green)?,
+                    ],
+                    name: definition.name,
+                    live: AtomicUsize::new(0),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let index_of =
+            |name: &str| match blue_greens.iter().position(|service| service.name == name) {
+                Some(index) => Ok(Target::BlueGreen(index)),
+                None => pool_index(name).map(Target::Pool),
+            };
+
+        let routes = routes
+            .into_iter()
+            .map(|route| {
+                let pools = route
+                    .pools
+                    .iter()
+                    .map(|(name, weight)| index_of(name).map(|index| (index, *weight)))
+                    .collect::<Result<_, String>>()?;
+
+                Ok((route, pools))
+            })
+            .collect::<Result<_, String>>()?;
+        let default_pool = default_pool.map(index_of).transpose()?;
+
+        Ok(Self {
+            pools,
+            blue_greens,
+            routes,
+            default_pool,
+            day_clock: DayClock::default(),
+            unmatched_status: None,
+        })
+    }
+
+    /// Answer the requests matching no route with `status`, e.g. a 404 or a
+    /// 421, rather than sending them to the target servers. Moot with a
+    /// default pool.
+    pub fn with_unmatched_status(mut self, status: StatusCode) -> Self {
+        self.unmatched_status = Some(status);
+        self
+    }
+
+    /// The status of the requests `route` found no pool for, if they don't
+    /// go to the target servers.
+    pub fn unmatched_status(&self) -> Option<StatusCode> {
+        self.unmatched_status
+            .filter(|_| self.default_pool.is_none())
+    }
+
+    pub fn with_day_clock(mut self, day_clock: DayClock) -> Self {
+        self.day_clock = day_clock;
+        self
+    }
+
+    /// The same pools and routes with `pool` in place of the pool of the
+    /// same name, e.g. once its backends were discovered again, or `None`
+    /// when there is no such pool. Blue/green services keep their live pool.
+    pub fn with_pool_replaced(&self, pool: Pool) -> Option<Self> {
+        let index = self
+            .pools
+            .iter()
+            .position(|current| current.name == pool.name)?;
+        let mut pools = self.pools.clone();
+        pools[index] = pool;
+
+        Some(Self {
+            pools,
+            blue_greens: self
+                .blue_greens
+                .iter()
+                .map(|service| BlueGreen {
+                    name: service.name.clone(),
+                    pools: service.pools,
+                    live: AtomicUsize::new(service.live.load(Ordering::Relaxed)),
+                })
+                .collect(),
+            routes: self.routes.clone(),
+            default_pool: self.default_pool,
+            day_clock: self.day_clock.clone(),
+            unmatched_status: self.unmatched_status,
+        })
+    }
+
+    /// Whether the server is a backend of one of the pools.
+    pub fn contains_server(&self, server: &str) -> bool {
+        self.pools
+            .iter()
+            .any(|pool| pool.target_servers.iter().any(|backend| backend == server))
+    }
+
+    /// The pool of the first route the request matches, among those
+    /// scheduled for now. Routes splitting their requests between pools
+    /// pick one from the request id, so the same request, retried, goes to
+    /// the same pool.
+    pub fn route(&self, method: &Method, uri: &Uri, headers: &HeaderMap) -> Option<&Pool> {
+        self.matching_route(method, uri, headers)
+            .map(|(_, pools)| weighted_pool(pools, headers))
+            .or(self.default_pool)
+            .map(|target| &self.pools[self.pool_index(target)])
+    }
+
+    /// The methods allowed by the route of the request, when they don't
+    /// include its own, for the `Allow` header of a 405.
+    pub fn disallowed_method(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Option<&AllowedMethods> {
+        self.matching_route(method, uri, headers)
+            .map(|(route, _)| &route.allowed_methods)
+            .filter(|allowed_methods| !allowed_methods.allows(method))
+    }
+
+    fn matching_route(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Option<&(PoolRoute, Vec<(Target, u32)>)> {
+        self.routes.iter().find(|(route, _)| {
+            route.matches(method, uri, headers)
+                && route
+                    .schedule
+                    .is_none_or(|window| window.contains(self.day_clock.minute_of_day()))
+        })
+    }
+
+    fn pool_index(&self, target: Target) -> usize {
+        match target {
+            Target::Pool(index) => index,
+            Target::BlueGreen(index) => {
+                let service = &self.blue_greens[index];
+                service.pools[service.live.load(Ordering::Relaxed)]
+            }
+        }
+    }
+
+    pub fn blue_greens(&self) -> Vec<BlueGreenStatus> {
+        self.blue_greens
+            .iter()
+            .map(|service| BlueGreenStatus {
+                service: service.name.clone(),
+                blue: self.pools[service.pools[0]].name.clone(),
+                green: self.pools[service.pools[1]].name.clone(),
+                live: self.pools[service.pools[service.live.load(Ordering::Relaxed)]]
+                    .name
+                    .clone(),
+            })
+            .collect()
+    }
+
+    /// The blue or green pool of the service, to verify before switching to it.
+    pub fn blue_green_pool(&self, service: &str, pool: &str) -> Option<&Pool> {
+        self.blue_green_side(service, pool)
+            .map(|(service, side)| &self.pools[service.pools[side]])
+    }
+
+    /// Sends every request of the service to its blue or green pool at
+    /// once, returning the pool that was live before.
+    pub fn switch_blue_green(&self, service: &str, pool: &str) -> Option<&str> {
+        self.blue_green_side(service, pool).map(|(service, side)| {
+            let previous = service.live.swap(side, Ordering::Relaxed);
+            self.pools[service.pools[previous]].name.as_str()
+        })
+    }
+
+    fn blue_green_side(&self, service: &str, pool: &str) -> Option<(&BlueGreen, usize)> {
+        let service = self
+            .blue_greens
+            .iter()
+            .find(|blue_green| blue_green.name == service)?;
+        let side = service
+            .pools
+            .iter()
+            .position(|index| self.pools[*index].name == pool)?;
+
+        Some((service, side))
+    }
+}
+
+fn weighted_pool(pools: &[(Target, u32)], headers: &HeaderMap) -> Target {
+    if let [(index, _)] = pools {
+        return *index;
+    }
+
+    let total = pools
+        .iter()
+        .map(|(_, weight)| u64::from(*weight))
+        .sum::<u64>();
+    let mut bucket = match headers.get(X_REQUEST_ID) {
+        Some(request_id) => fnv1a(request_id.as_bytes()) % total,
+        None => rand::random_range(0..total),
+    };
+
+    for (index, weight) in pools {
+        match bucket.checked_sub(u64::from(*weight)) {
+            Some(rest) => bucket = rest,
+            None => return *index,
+        }
+    }
+
+    unreachable!("bucket is below the total weight")
+}
+
+/// Stable across builds and instances, unlike the std hashers.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http::{HeaderMap, HeaderValue, Method, StatusCode, Uri};
+
+    use crate::RoundRobinSelectServer;
+    use crate::allowed_methods::AllowedMethods;
+    use crate::pools::pool::Pool;
+    use crate::pools::pools::Pools;
+    use crate::request_id::X_REQUEST_ID;
+    use crate::time_rules::{DayClock, MockClock};
+
+    fn pool(name: &str) -> Pool {
+        let servers = vec![format!("http://{}:8080", name)];
+        let healthy_servers = Arc::new(RwLock::new(servers.clone()));
+
+        Pool {
+            name: name.to_string(),
+            target_servers: Arc::new(servers),
+            select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&healthy_servers))),
+            healthy_servers,
+            health_path: "/health".to_string(),
+            allowed_methods: AllowedMethods::default(),
+            http_client: None,
+            location_rewrite: None,
+            host_header: None,
+        }
+    }
+
+    fn routed_to(pools: &Pools, uri: &'static str) -> Option<String> {
+        pools
+            .route(&Method::GET, &Uri::from_static(uri), &HeaderMap::new())
+            .map(|pool| pool.name.clone())
+    }
+
+    #[test]
+    fn first_matching_route_picks_the_pool() {
+        let pools = Pools::new(
+            vec![pool("api"), pool("admin")],
+            Vec::new(),
+            vec![
+                "/api/admin/*=>admin".parse().unwrap(),
+                "/api/*=>api".parse().unwrap(),
+            ],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            routed_to(&pools, "/api/admin/users"),
+            Some("admin".to_string())
+        );
+        assert_eq!(routed_to(&pools, "/api/orders"), Some("api".to_string()));
+        assert_eq!(routed_to(&pools, "/static/app.js"), None);
+    }
+
+    #[test]
+    fn methods_not_allowed_by_the_matching_route_are_disallowed() {
+        let pools = Pools::new(
+            vec![pool("static"), pool("api")],
+            Vec::new(),
+            vec![
+                "/static/*;allow:GET|HEAD=>static".parse().unwrap(),
+                "/*=>api".parse().unwrap(),
+            ],
+            None,
+        )
+        .unwrap();
+        let disallowed = |method: Method, uri: &'static str| {
+            pools
+                .disallowed_method(&method, &Uri::from_static(uri), &HeaderMap::new())
+                .map(AllowedMethods::allow_header)
+        };
+
+        assert_eq!(disallowed(Method::GET, "/static/app.js"), None);
+        assert_eq!(
+            disallowed(Method::POST, "/static/app.js"),
+            Some(HeaderValue::from_static("GET, HEAD"))
+        );
+        assert_eq!(disallowed(Method::POST, "/api/orders"), None);
+    }
+
+    #[test]
+    fn unknown_hosts_go_to_the_default_pool() {
+        let pools = Pools::new(
+            vec![pool("api"), pool("www")],
+            Vec::new(),
+            vec!["api.example.com=>api".parse().unwrap()],
+            Some("www"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            routed_to(&pools, "https://api.example.com/users"),
+            Some("api".to_string())
+        );
+        assert_eq!(
+            routed_to(&pools, "https://unknown.example.com/users"),
+            Some("www".to_string())
+        );
+    }
+
+    #[test]
+    fn canary_requests_go_to_the_canary_pool() {
+        let pools = Pools::new(
+            vec![pool("stable"), pool("canary")],
+            Vec::new(),
+            vec!["header:X-Canary=true=>canary".parse().unwrap()],
+            Some("stable"),
+        )
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-canary", HeaderValue::from_static("true"));
+        let canary = pools.route(&Method::GET, &Uri::from_static("/"), &headers);
+
+        assert_eq!(canary.map(|pool| pool.name.as_str()), Some("canary"));
+        assert_eq!(routed_to(&pools, "/"), Some("stable".to_string()));
+    }
+
+    #[test]
+    fn reads_and_writes_go_to_their_pools() {
+        let pools = Pools::new(
+            vec![pool("replicas"), pool("primary")],
+            Vec::new(),
+            vec![
+                "method:GET|HEAD=>replicas".parse().unwrap(),
+                "method:POST|PUT|PATCH|DELETE=>primary".parse().unwrap(),
+            ],
+            None,
+        )
+        .unwrap();
+        let routed_to = |method: Method| {
+            pools
+                .route(&method, &Uri::from_static("/orders"), &HeaderMap::new())
+                .map(|pool| pool.name.as_str())
+        };
+
+        assert_eq!(routed_to(Method::GET), Some("replicas"));
+        assert_eq!(routed_to(Method::HEAD), Some("replicas"));
+        assert_eq!(routed_to(Method::POST), Some("primary"));
+        assert_eq!(routed_to(Method::DELETE), Some("primary"));
+        assert_eq!(routed_to(Method::OPTIONS), None);
+    }
+
+    #[test]
+    fn splits_the_requests_by_weight() {
+        let pools = Pools::new(
+            vec![pool("stable"), pool("canary")],
+            Vec::new(),
+            vec!["/*=>stable:95,canary:5".parse().unwrap()],
+            None,
+        )
+        .unwrap();
+
+        let routed_to = |request_id: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(X_REQUEST_ID, HeaderValue::from_str(request_id).unwrap());
+
+            pools
+                .route(&Method::GET, &Uri::from_static("/"), &headers)
+                .map(|pool| pool.name.clone())
+                .unwrap()
+        };
+
+        let canaries = (0..10_000)
+            .filter(|request| routed_to(&format!("request-{}", request)) == "canary")
+            .count();
+
+        assert!((400..600).contains(&canaries), "{}", canaries);
+        for request in 0..100 {
+            let request_id = format!("request-{}", request);
+            assert_eq!(routed_to(&request_id), routed_to(&request_id));
+        }
+    }
+
+    #[test]
+    fn zero_weight_pools_get_no_requests() {
+        let pools = Pools::new(
+            vec![pool("stable"), pool("canary")],
+            Vec::new(),
+            vec!["/*=>stable:1,canary:0".parse().unwrap()],
+            None,
+        )
+        .unwrap();
+
+        for _ in 0..100 {
+            assert_eq!(routed_to(&pools, "/"), Some("stable".to_string()));
+        }
+    }
+
+    #[test]
+    fn blue_green_switch_moves_every_route_to_the_other_pool() {
+        let pools = Pools::new(
+            vec![pool("shop-blue"), pool("shop-green")],
+            vec!["shop=shop-blue,shop-green".parse().unwrap()],
+            vec!["/shop/*=>shop".parse().unwrap()],
+            Some("shop"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            routed_to(&pools, "/shop/cart"),
+            Some("shop-blue".to_string())
+        );
+        assert_eq!(routed_to(&pools, "/"), Some("shop-blue".to_string()));
+
+        assert_eq!(
+            pools
+                .blue_green_pool("shop", "shop-green")
+                .map(|pool| pool.name.as_str()),
+            Some("shop-green")
+        );
+        assert_eq!(
+            pools.switch_blue_green("shop", "shop-green"),
+            Some("shop-blue")
+        );
+
+        assert_eq!(
+            routed_to(&pools, "/shop/cart"),
+            Some("shop-green".to_string())
+        );
+        assert_eq!(routed_to(&pools, "/"), Some("shop-green".to_string()));
+        assert_eq!(pools.blue_greens()[0].live, "shop-green");
+    }
+
+    #[test]
+    fn blue_green_switch_only_targets_the_pools_of_the_service() {
+        let pools = Pools::new(
+            vec![pool("shop-blue"), pool("shop-green"), pool("api")],
+            vec!["shop=shop-blue,shop-green".parse().unwrap()],
+            Vec::new(),
+            None,
+        )
+        .unwrap();
+
+        assert!(pools.blue_green_pool("shop", "api").is_none());
+        assert_eq!(pools.switch_blue_green("shop", "api"), None);
+        assert_eq!(pools.switch_blue_green("cart", "shop-green"), None);
+        assert_eq!(pools.blue_greens()[0].live, "shop-blue");
+    }
+
+    #[test]
+    fn scheduled_routes_apply_during_their_window_only() {
+        let at = |hour: u64| {
+            let mut clock = MockClock::new();
+            clock
+                .expect_now()
+                .returning(move || UNIX_EPOCH + Duration::from_secs(hour * 3600));
+
+            Pools::new(
+                vec![pool("api"), pool("maintenance")],
+                Vec::new(),
+                vec!["time:02:00-03:00=>maintenance".parse().unwrap()],
+                Some("api"),
+            )
+            .unwrap()
+            .with_day_clock(DayClock {
+                clock: Arc::new(clock),
+                utc_offset_minutes: 0,
+            })
+        };
+
+        assert_eq!(routed_to(&at(1), "/"), Some("api".to_string()));
+        assert_eq!(routed_to(&at(2), "/"), Some("maintenance".to_string()));
+        assert_eq!(routed_to(&at(3), "/"), Some("api".to_string()));
+    }
+
+    #[test]
+    fn unmatched_requests_get_the_unmatched_status_unless_there_is_a_default_pool() {
+        let routes = || vec!["/api/*=>api".parse().unwrap()];
+        let without_default = Pools::new(vec![pool("api")], Vec::new(), routes(), None)
+            .unwrap()
+            .with_unmatched_status(StatusCode::NOT_FOUND);
+        let with_default = Pools::new(vec![pool("api")], Vec::new(), routes(), Some("api"))
+            .unwrap()
+            .with_unmatched_status(StatusCode::NOT_FOUND);
+
+        assert_eq!(routed_to(&without_default, "/static/app.js"), None);
+        assert_eq!(
+            without_default.unmatched_status(),
+            Some(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(with_default.unmatched_status(), None);
+        assert_eq!(Pools::default().unmatched_status(), None);
+    }
+
+    #[test]
+    fn rejects_unknown_pools() {
+        let unknown_route = Pools::new(
+            vec![pool("api")],
+            Vec::new(),
+            vec!["/static/*=>static".parse().unwrap()],
+            None,
+        );
+        let unknown_split = Pools::new(
+            vec![pool("api")],
+            Vec::new(),
+            vec!["/*=>api:95,canary:5".parse().unwrap()],
+            None,
+        );
+        let unknown_default = Pools::new(vec![pool("api")], Vec::new(), Vec::new(), Some("www"));
+        let unknown_blue_green = Pools::new(
+            vec![pool("shop-blue")],
+            vec!["shop=shop-blue,shop-green".parse().unwrap()],
+            Vec::new(),
+            None,
+        );
+
+        assert!(unknown_route.is_err());
+        assert!(unknown_split.is_err());
+        assert!(unknown_default.is_err());
+        assert!(unknown_blue_green.is_err());
+    }
+
+    #[test]
+    fn rejects_pools_sharing_a_name() {
+        let duplicate = Pools::new(
+            vec![pool("api"), pool("www"), pool("api")],
+            Vec::new(),
+            Vec::new(),
+            None,
+        );
+
+        assert_eq!(
+            duplicate.err(),
+            Some("pool api is defined twice".to_string())
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::{Arc, RwLock};
+
+use crate::pools::pool::Pool;
+use crate::pools::pools::Pools;
+
+/// The pools and routes in use, replaced as a whole when the configuration
+/// is reloaded. A request keeps the pools it was routed with, so that a swap
+/// never cuts those in flight.
+#[derive(Default)]
+pub struct SwappablePools {
+    current: RwLock<Arc<Pools>>,
+}
+
+impl SwappablePools {
+    pub fn new(pools: Arc<Pools>) -> Self {
+        Self {
+            current: RwLock::new(pools),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Pools> {
+        match self.current.read() {
+            Ok(current) => Arc::clone(&current),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Blue/green services still defined with the same pools keep their
+    /// live side, instead of going back to blue.
+    pub fn swap(&self, pools: Pools) {
+        let Ok(mut current) = self.current.write() else {
+            return;
+        };
+
+        for service in current.blue_greens() {
+            pools.switch_blue_green(&service.service, &service.live);
+        }
+
+        *current = Arc::new(pools);
+    }
+
+    /// Replaces the pool of the same name, if any, leaving the others and
+    /// the routes as they are. Returns whether there was such a pool.
+    pub fn swap_pool(&self, pool: Pool) -> bool {
+        let Ok(mut current) = self.current.write() else {
+            return false;
+        };
+
+        match current.with_pool_replaced(pool) {
+            Some(pools) => {
+                *current = Arc::new(pools);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl From<Pools> for SwappablePools {
+    fn from(pools: Pools) -> Self {
+        Self::new(Arc::new(pools))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use http::{HeaderMap, Method, Uri};
+
+    use crate::RoundRobinSelectServer;
+    use crate::allowed_methods::AllowedMethods;
+    use crate::pools::pool::Pool;
+    use crate::pools::pools::Pools;
+    use crate::pools::swappable_pools::SwappablePools;
+
+    fn pool(name: &str) -> Pool {
+        let servers = vec![format!("http://{}:8080", name)];
+        let healthy_servers = Arc::new(RwLock::new(servers.clone()));
+
+        Pool {
+            name: name.to_string(),
+            target_servers: Arc::new(servers),
+            select_server: Arc::new(RoundRobinSelectServer::new(Arc::clone(&healthy_servers))),
+            healthy_servers,
+            health_path: "/health".to_string(),
+            allowed_methods: AllowedMethods::default(),
+            http_client: None,
+            location_rewrite: None,
+            host_header: None,
+        }
+    }
+
+    fn shop(routes: &[&str]) -> Pools {
+        Pools::new(
+            vec![pool("shop-blue"), pool("shop-green"), pool("static")],
+            vec!["shop=shop-blue,shop-green".parse().unwrap()],
+            routes.iter().map(|route| route.parse().unwrap()).collect(),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn routed_to(pools: &Pools, uri: &'static str) -> Option<String> {
+        pools
+            .route(&Method::GET, &Uri::from_static(uri), &HeaderMap::new())
+            .map(|pool| pool.name.clone())
+    }
+
+    #[test]
+    fn requests_in_flight_keep_the_pools_they_started_with() {
+        let pools = SwappablePools::from(shop(&["/shop/*=>shop"]));
+        let in_flight = pools.current();
+
+        pools.swap(shop(&["/shop/*=>shop", "/static/*=>static"]));
+
+        assert_eq!(routed_to(&in_flight, "/static/app.js"), None);
+        assert_eq!(
+            routed_to(&pools.current(), "/static/app.js"),
+            Some("static".to_string())
+        );
+    }
+
+    #[test]
+    fn blue_green_services_keep_their_live_pool() {
+        let pools = SwappablePools::from(shop(&["/shop/*=>shop"]));
+        pools.current().switch_blue_green("shop", "shop-green");
+
+        pools.swap(shop(&["/shop/*=>shop"]));
+
+        assert_eq!(
+            routed_to(&pools.current(), "/shop/cart"),
+            Some("shop-green".to_string())
+        );
+    }
+
+    #[test]
+    fn swap_pool_replaces_a_single_pool() {
+        let pools = SwappablePools::from(shop(&["/shop/*=>shop", "/static/*=>static"]));
+        pools.current().switch_blue_green("shop", "shop-green");
+        let mut moved = pool("static");
+        moved.target_servers = Arc::new(vec!["http://static-2:8080".to_string()]);
+
+        assert!(pools.swap_pool(moved));
+        assert!(!pools.swap_pool(pool("unknown")));
+
+        let current = pools.current();
+        assert!(current.contains_server("http://static-2:8080"));
+        assert!(!current.contains_server("http://static:8080"));
+        assert_eq!(
+            routed_to(&current, "/shop/cart"),
+            Some("shop-green".to_string())
+        );
+    }
+}
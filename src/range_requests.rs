@@ -0,0 +1,76 @@
+use http::{HeaderMap, HeaderValue, header};
+
+/// Whether the clients may ask the backends for parts of a resource, e.g. to
+/// seek in a video or resume a download.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RangeRequests {
+    /// `Range` and `If-Range` reach the backends, whose `206 Partial Content`
+    /// answers are streamed back as they are.
+    #[default]
+    Pass,
+    /// For backends that can't serve ranges: the requests are forwarded
+    /// without their ranges, answered in full, and the responses announce
+    /// `Accept-Ranges: none`.
+    Reject,
+}
+
+impl RangeRequests {
+    pub fn apply_request(&self, headers: &mut HeaderMap) {
+        if *self == RangeRequests::Reject {
+            headers.remove(header::RANGE);
+            headers.remove(header::IF_RANGE);
+        }
+    }
+
+    pub fn apply_response(&self, headers: &mut HeaderMap) {
+        if *self == RangeRequests::Reject {
+            headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("none"));
+        }
+    }
+}
+
+/// Whether the client asked for part of the resource only.
+pub fn is_range_request(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::RANGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue, header};
+
+    use crate::range_requests::{RangeRequests, is_range_request};
+
+    fn range_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-1023"));
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"v1\""));
+        headers
+    }
+
+    #[test]
+    fn passes_ranges_through() {
+        let mut headers = range_headers();
+
+        RangeRequests::Pass.apply_request(&mut headers);
+
+        assert_eq!(headers, range_headers());
+        assert!(is_range_request(&headers));
+    }
+
+    #[test]
+    fn rejects_ranges() {
+        let mut headers = range_headers();
+
+        RangeRequests::Reject.apply_request(&mut headers);
+
+        assert!(headers.is_empty());
+        assert!(!is_range_request(&headers));
+
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+        RangeRequests::Reject.apply_response(&mut response_headers);
+
+        assert_eq!(response_headers[header::ACCEPT_RANGES], "none");
+    }
+}
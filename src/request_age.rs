@@ -0,0 +1,83 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{HeaderName, HeaderValue};
+
+use crate::http_client::request::RequestHeaders;
+
+pub const X_REQUEST_START: HeaderName = HeaderName::from_static("x-request-start");
+pub const X_REQUEST_QUEUE_MS: HeaderName = HeaderName::from_static("x-request-queue-ms");
+
+/// When the load balancer accepted the request.
+#[derive(Debug, Clone, Copy)]
+pub struct AcceptedAt {
+    pub instant: Instant,
+    pub time: SystemTime,
+}
+
+impl AcceptedAt {
+    pub fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            time: SystemTime::now(),
+        }
+    }
+
+    /// Tells the backend when the request was accepted and how long it has
+    /// waited in the load balancer before being sent, so that queueing delay
+    /// can be told apart from processing time.
+    pub fn stamp(&self, headers: &mut RequestHeaders) {
+        let start_millis = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+
+        if let Ok(start) = HeaderValue::try_from(format!("t={}", start_millis)) {
+            headers.insert(X_REQUEST_START, start);
+        }
+        headers.insert(
+            X_REQUEST_QUEUE_MS,
+            HeaderValue::from(
+                u64::try_from(self.instant.elapsed().as_millis()).unwrap_or(u64::MAX),
+            ),
+        );
+    }
+}
+
+pub async fn accept(mut request: Request, next: Next) -> Response {
+    request.extensions_mut().insert(AcceptedAt::now());
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    use crate::http_client::request::RequestHeaders;
+    use crate::request_age::{AcceptedAt, X_REQUEST_QUEUE_MS, X_REQUEST_START};
+
+    #[test]
+    fn stamps_the_start_and_the_time_spent_queueing() {
+        let accepted_at = AcceptedAt {
+            instant: Instant::now() - Duration::from_millis(250),
+            time: UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+        };
+        let mut headers = RequestHeaders::default();
+
+        accepted_at.stamp(&mut headers);
+
+        assert_eq!(headers[X_REQUEST_START.as_str()], "t=1700000000123");
+        assert!(
+            headers[X_REQUEST_QUEUE_MS.as_str()]
+                .to_str()
+                .unwrap()
+                .parse::<u64>()
+                .unwrap()
+                >= 250
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use futures::FutureExt;
+use futures::future::{BoxFuture, Shared};
+use http::{HeaderMap, Method, StatusCode, header};
+use tokio::sync::oneshot;
+
+use crate::metrics::metrics::{COALESCED_REQUESTS_TOTAL, Metrics};
+
+/// Larger responses, or those of unknown size, aren't held in memory to be
+/// fanned out: they go to one of the waiters, the others send their own.
+const MAX_COALESCED_BODY_BYTES: u64 = 1024 * 1024;
+
+/// Headers telling apart the responses to requests for the same URL.
+const KEY_HEADERS: [header::HeaderName; 4] = [
+    header::HOST,
+    header::ACCEPT,
+    header::ACCEPT_ENCODING,
+    header::ACCEPT_LANGUAGE,
+];
+
+enum Outcome {
+    Buffered {
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    },
+    /// Kept for the request that was sent, the others send their own.
+    Streamed(Mutex<Option<Response>>),
+    /// The request that was sent was dropped, e.g. by its client going away.
+    Abandoned,
+}
+
+type InFlight = Shared<BoxFuture<'static, Arc<Outcome>>>;
+
+enum Role {
+    Send(oneshot::Sender<Response>, InFlight),
+    Join(InFlight),
+}
+
+/// Collapses identical GETs arriving while one of them is in flight into a
+/// single upstream request, whose response is sent to all of them: a cache
+/// miss of many clients at once then costs the backends a single request.
+#[derive(Default)]
+pub struct RequestCoalescing {
+    in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
+    metrics: Arc<Metrics>,
+}
+
+impl RequestCoalescing {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            in_flight: Arc::default(),
+            metrics,
+        }
+    }
+
+    /// What identical requests share, including the `route` they took once
+    /// authorized, `None` for those never coalesced: the ones changing
+    /// something, or answered for a given user only.
+    pub fn key(request: &Request, route: &str) -> Option<String> {
+        let personal = request.headers().contains_key(header::AUTHORIZATION)
+            || request.headers().contains_key(header::COOKIE);
+        if request.method() != Method::GET || personal || !request.body().is_end_stream() {
+            return None;
+        }
+
+        let mut key = format!("{}\n{}", route, request.uri());
+        for name in &KEY_HEADERS {
+            for value in request.headers().get_all(name) {
+                key.push('\n');
+                key.push_str(name.as_str());
+                key.push(':');
+                key.push_str(value.to_str().ok()?);
+            }
+        }
+
+        Some(key)
+    }
+
+    /// Joins the request in flight with the same key, or sends this one with
+    /// `forward` and lets the next identical ones join it.
+    pub async fn coalesce<F, Fut>(&self, key: String, request: Request, forward: F) -> Response
+    where
+        F: FnOnce(Request) -> Fut,
+        Fut: Future<Output = Response>,
+    {
+        let Some(role) = self.join(key) else {
+            return forward(request).await;
+        };
+
+        match role {
+            Role::Send(sender, in_flight) => {
+                let _ = sender.send(forward(request).await);
+
+                match &*in_flight.await {
+                    Outcome::Streamed(response) => response
+                        .lock()
+                        .ok()
+                        .and_then(|mut response| response.take())
+                        .unwrap_or_else(|| StatusCode::BAD_GATEWAY.into_response()),
+                    outcome => {
+                        buffered(outcome).unwrap_or_else(|| StatusCode::BAD_GATEWAY.into_response())
+                    }
+                }
+            }
+            Role::Join(in_flight) => {
+                self.metrics.increment(COALESCED_REQUESTS_TOTAL);
+
+                match buffered(&*in_flight.await) {
+                    Some(response) => response,
+                    None => forward(request).await,
+                }
+            }
+        }
+    }
+
+    fn join(&self, key: String) -> Option<Role> {
+        let mut in_flight = self.in_flight.lock().ok()?;
+
+        if let Some(shared) = in_flight.get(&key) {
+            return Some(Role::Join(shared.clone()));
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let shared = self.receive(key.clone(), receiver);
+        in_flight.insert(key, shared.clone());
+
+        Some(Role::Send(sender, shared))
+    }
+
+    /// The response of the request that was sent, letting go of its key
+    /// once there.
+    fn receive(&self, key: String, receiver: oneshot::Receiver<Response>) -> InFlight {
+        let in_flight = Arc::clone(&self.in_flight);
+
+        async move {
+            let outcome = match receiver.await {
+                Ok(response) => buffer(response).await,
+                Err(_) => Outcome::Abandoned,
+            };
+            if let Ok(mut in_flight) = in_flight.lock() {
+                in_flight.remove(&key);
+            }
+
+            Arc::new(outcome)
+        }
+        .boxed()
+        .shared()
+    }
+}
+
+fn buffered(outcome: &Outcome) -> Option<Response> {
+    let Outcome::Buffered {
+        status,
+        headers,
+        body,
+    } = outcome
+    else {
+        return None;
+    };
+
+    let mut response = Response::new(Body::from(body.clone()));
+    *response.status_mut() = *status;
+    *response.headers_mut() = headers.clone();
+
+    Some(response)
+}
+
+async fn buffer(response: Response) -> Outcome {
+    let small = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= MAX_COALESCED_BODY_BYTES);
+    if !small {
+        return Outcome::Streamed(Mutex::new(Some(response)));
+    }
+
+    let (parts, body) = response.into_parts();
+    match axum::body::to_bytes(body, MAX_COALESCED_BODY_BYTES as usize).await {
+        Ok(body) => Outcome::Buffered {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        },
+        Err(_) => Outcome::Abandoned,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use axum::body::{Body, to_bytes};
+    use axum::extract::Request;
+    use axum::response::Response;
+    use futures::stream;
+    use http::{Method, header};
+
+    use crate::metrics::metrics::{COALESCED_REQUESTS_TOTAL, Metrics};
+    use crate::request_coalescing::RequestCoalescing;
+
+    fn get(uri: &str) -> Request {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    /// Answers after a while, counting the requests it got.
+    async fn slow_backend(sent: Arc<AtomicUsize>, body: Body) -> Response {
+        sent.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        Response::new(body)
+    }
+
+    #[test]
+    fn only_coalesces_gets_not_tied_to_a_user_routed_the_same_way() {
+        assert!(RequestCoalescing::key(&get("/products"), "").is_some());
+        assert_ne!(
+            RequestCoalescing::key(&get("/products?page=1"), ""),
+            RequestCoalescing::key(&get("/products?page=2"), "")
+        );
+        assert_ne!(
+            RequestCoalescing::key(&get("/products"), "canary"),
+            RequestCoalescing::key(&get("/products"), "")
+        );
+
+        let post = Request::builder()
+            .method(Method::POST)
+            .uri("/products")
+            .body(Body::empty())
+            .unwrap();
+        let with_cookie = Request::builder()
+            .uri("/products")
+            .header(header::COOKIE, "session=1")
+            .body(Body::empty())
+            .unwrap();
+        let gzipped = Request::builder()
+            .uri("/products")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(RequestCoalescing::key(&post, ""), None);
+        assert_eq!(RequestCoalescing::key(&with_cookie, ""), None);
+        assert_ne!(
+            RequestCoalescing::key(&gzipped, ""),
+            RequestCoalescing::key(&get("/products"), "")
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_identical_requests_in_flight_once() {
+        let metrics = Arc::new(Metrics::default());
+        let coalescing = RequestCoalescing::new(Arc::clone(&metrics));
+        let sent = Arc::new(AtomicUsize::new(0));
+
+        let responses = futures::future::join_all((0..5).map(|_| {
+            let request = get("/products");
+            let key = RequestCoalescing::key(&request, "").unwrap();
+            let sent = Arc::clone(&sent);
+
+            coalescing.coalesce(key, request, move |_| {
+                slow_backend(sent, Body::from("products"))
+            })
+        }))
+        .await;
+
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.get(COALESCED_REQUESTS_TOTAL), 4);
+        for response in responses {
+            assert_eq!(
+                to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+                "products"
+            );
+        }
+
+        let request = get("/products");
+        let key = RequestCoalescing::key(&request, "").unwrap();
+        let sent_again = Arc::clone(&sent);
+        coalescing
+            .coalesce(key, request, move |_| {
+                slow_backend(sent_again, Body::from("products"))
+            })
+            .await;
+
+        assert_eq!(sent.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_their_own_requests_when_the_response_is_streamed() {
+        let coalescing = RequestCoalescing::default();
+        let sent = Arc::new(AtomicUsize::new(0));
+
+        let responses = futures::future::join_all((0..3).map(|_| {
+            let request = get("/download");
+            let key = RequestCoalescing::key(&request, "").unwrap();
+            let sent = Arc::clone(&sent);
+
+            coalescing.coalesce(key, request, move |_| {
+                let chunks = stream::iter([Ok::<_, std::io::Error>("down"), Ok("load")]);
+                slow_backend(sent, Body::from_stream(chunks))
+            })
+        }))
+        .await;
+
+        assert_eq!(sent.load(Ordering::Relaxed), 3);
+        for response in responses {
+            assert_eq!(
+                to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+                "download"
+            );
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use http::header::Entry;
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+/// A change made to every forwarded request, written as `ACTION:ARGUMENT`:
+///
+/// - `set-header:X-Env=prod` replaces every value of the header
+/// - `remove-header:X-Debug`
+/// - `rename-header:X-User=X-Client-User` keeps the values
+/// - `set-query:region=eu`, `remove-query:debug`, `rename-query:q=query`
+///   act likewise on the query string, whose values are written as sent,
+///   percent-encoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestTransform {
+    SetHeader(HeaderName, HeaderValue),
+    RemoveHeader(HeaderName),
+    RenameHeader(HeaderName, HeaderName),
+    SetQuery(String, String),
+    RemoveQuery(String),
+    RenameQuery(String, String),
+}
+
+impl FromStr for RequestTransform {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected ACTION:ARGUMENT, got {}", value);
+
+        let (action, argument) = value.split_once(':').ok_or_else(invalid)?;
+        let pair = || {
+            argument
+                .split_once('=')
+                .filter(|(name, _)| !name.is_empty())
+                .ok_or_else(|| format!("expected NAME=VALUE in {}", value))
+        };
+        let header_name = |name: &str| {
+            HeaderName::from_str(name).map_err(|_| format!("invalid header name {}", name))
+        };
+
+        match action {
+            "set-header" => {
+                let (name, header_value) = pair()?;
+                Ok(RequestTransform::SetHeader(
+                    header_name(name)?,
+                    HeaderValue::from_str(header_value)
+                        .map_err(|_| format!("invalid header value in {}", value))?,
+                ))
+            }
+            "remove-header" => Ok(RequestTransform::RemoveHeader(header_name(argument)?)),
+            "rename-header" => {
+                let (from, to) = pair()?;
+                Ok(RequestTransform::RenameHeader(
+                    header_name(from)?,
+                    header_name(to)?,
+                ))
+            }
+            "set-query" => {
+                let (name, query_value) = pair()?;
+                Ok(RequestTransform::SetQuery(
+                    name.to_string(),
+                    query_value.to_string(),
+                ))
+            }
+            "remove-query" if !argument.is_empty() => {
+                Ok(RequestTransform::RemoveQuery(argument.to_string()))
+            }
+            "rename-query" => {
+                let (from, to) = pair()?;
+                Ok(RequestTransform::RenameQuery(
+                    from.to_string(),
+                    to.to_string(),
+                ))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Changes applied in order to the forwarded requests.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTransforms(pub Vec<RequestTransform>);
+
+impl RequestTransforms {
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        for transform in &self.0 {
+            match transform {
+                RequestTransform::SetHeader(name, value) => {
+                    headers.insert(name.clone(), value.clone());
+                }
+                RequestTransform::RemoveHeader(name) => {
+                    headers.remove(name);
+                }
+                RequestTransform::RenameHeader(from, to) => {
+                    if let Entry::Occupied(entry) = headers.entry(from) {
+                        let (_, values) = entry.remove_entry_mult();
+                        let values = values.collect::<Vec<_>>();
+
+                        headers.remove(to);
+                        for value in values {
+                            headers.append(to.clone(), value);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn apply_query<'a>(&self, path_and_query: Cow<'a, str>) -> Cow<'a, str> {
+        if !self.0.iter().any(RequestTransform::is_query) {
+            return path_and_query;
+        }
+
+        let (path, query) = path_and_query
+            .split_once('?')
+            .unwrap_or((&path_and_query, ""));
+
+        let mut pairs = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            // The name, and the rest of the pair as sent: `=VALUE` or nothing.
+            .map(|pair| {
+                let (name, rest) = pair.split_at(pair.find('=').unwrap_or(pair.len()));
+                (name.to_string(), rest.to_string())
+            })
+            .collect::<Vec<_>>();
+
+        for transform in &self.0 {
+            match transform {
+                RequestTransform::SetQuery(name, value) => {
+                    pairs.retain(|(key, _)| key != name);
+                    pairs.push((name.clone(), format!("={}", value)));
+                }
+                RequestTransform::RemoveQuery(name) => pairs.retain(|(key, _)| key != name),
+                RequestTransform::RenameQuery(from, to) => {
+                    pairs.retain(|(key, _)| key != to);
+                    for (key, _) in pairs.iter_mut().filter(|(key, _)| key == from) {
+                        key.clone_from(to);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let query = pairs
+            .iter()
+            .map(|(name, rest)| format!("{}{}", name, rest))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        if query.is_empty() {
+            Cow::Owned(path.to_string())
+        } else {
+            Cow::Owned(format!("{}?{}", path, query))
+        }
+    }
+}
+
+impl RequestTransform {
+    fn is_query(&self) -> bool {
+        matches!(
+            self,
+            RequestTransform::SetQuery(..)
+                | RequestTransform::RemoveQuery(_)
+                | RequestTransform::RenameQuery(..)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use http::{HeaderMap, HeaderName, HeaderValue};
+
+    use crate::request_transforms::{RequestTransform, RequestTransforms};
+
+    fn transforms(transforms: &[&str]) -> RequestTransforms {
+        RequestTransforms(
+            transforms
+                .iter()
+                .map(|transform| transform.parse().unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parses_each_action() {
+        assert_eq!(
+            "set-header:X-Env=prod".parse(),
+            Ok(RequestTransform::SetHeader(
+                HeaderName::from_static("x-env"),
+                HeaderValue::from_static("prod")
+            ))
+        );
+        assert_eq!(
+            "rename-query:q=query".parse(),
+            Ok(RequestTransform::RenameQuery(
+                "q".to_string(),
+                "query".to_string()
+            ))
+        );
+        assert!("remove-header:".parse::<RequestTransform>().is_err());
+        assert!("set-header:X-Env".parse::<RequestTransform>().is_err());
+        assert!("drop-header:X-Debug".parse::<RequestTransform>().is_err());
+    }
+
+    #[test]
+    fn changes_headers_in_order() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-debug", HeaderValue::from_static("1"));
+        headers.append("x-user", HeaderValue::from_static("a"));
+        headers.append("x-user", HeaderValue::from_static("b"));
+        headers.insert("x-env", HeaderValue::from_static("spoofed"));
+
+        transforms(&[
+            "set-header:X-Env=prod",
+            "remove-header:X-Debug",
+            "rename-header:X-User=X-Client-User",
+        ])
+        .apply_headers(&mut headers);
+
+        assert_eq!(headers["x-env"], "prod");
+        assert!(!headers.contains_key("x-debug"));
+        assert!(!headers.contains_key("x-user"));
+        assert_eq!(
+            headers.get_all("x-client-user").iter().collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn changes_the_query_string() {
+        let transforms = transforms(&[
+            "set-query:region=eu",
+            "remove-query:debug",
+            "rename-query:q=query",
+        ]);
+
+        assert_eq!(
+            transforms.apply_query(Cow::Borrowed("/search?q=a%20b&debug=1&region=us&flag")),
+            "/search?query=a%20b&flag&region=eu"
+        );
+        assert_eq!(transforms.apply_query(Cow::Borrowed("/")), "/?region=eu");
+        assert_eq!(
+            transforms.apply_query(Cow::Borrowed("/?debug&region=eu")),
+            "/?region=eu"
+        );
+    }
+
+    #[test]
+    fn leaves_the_query_alone_without_query_transforms() {
+        let path_and_query =
+            transforms(&["remove-header:X-Debug"]).apply_query(Cow::Borrowed("/search?b=2&a=1&&c"));
+
+        assert!(matches!(
+            path_and_query,
+            Cow::Borrowed("/search?b=2&a=1&&c")
+        ));
+    }
+}
@@ -0,0 +1,664 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
+
+use crate::metrics::metrics::{CACHE_HITS_TOTAL, CACHE_MISSES_TOTAL, Metrics};
+
+pub const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// Statuses cacheable by default, per RFC 9111.
+const CACHEABLE_STATUSES: [StatusCode; 6] = [
+    StatusCode::OK,
+    StatusCode::NON_AUTHORITATIVE_INFORMATION,
+    StatusCode::NO_CONTENT,
+    StatusCode::MOVED_PERMANENTLY,
+    StatusCode::NOT_FOUND,
+    StatusCode::GONE,
+];
+
+/// The longest a response is kept, whatever its `Cache-Control` says, so
+/// that its expiry can't overflow.
+const MAX_TTL: Duration = Duration::from_secs(1 << 31);
+
+struct Entry {
+    base_key: String,
+    /// Marked `public`, so that it may also be served to the requests
+    /// carrying cookies.
+    public: bool,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<String, Entry>,
+    /// Keys by when they were last used, the least recently used first.
+    recency: BTreeMap<u64, String>,
+    /// Headers the responses to each method and URL vary on, with how many
+    /// of their variants are kept, so that they go with the last one.
+    vary: HashMap<String, (Vec<HeaderName>, usize)>,
+    clock: u64,
+}
+
+impl Entries {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn insert(&mut self, key: String, vary: Vec<HeaderName>, entry: Entry) {
+        let variants = self
+            .vary
+            .get(&entry.base_key)
+            .map_or(0, |(_, count)| *count);
+
+        self.vary
+            .insert(entry.base_key.clone(), (vary, variants + 1));
+        self.recency.insert(entry.last_used, key.clone());
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &str) {
+        let Some(entry) = self.entries.remove(key) else {
+            return;
+        };
+        self.recency.remove(&entry.last_used);
+
+        if let Some((_, variants)) = self.vary.get_mut(&entry.base_key) {
+            *variants -= 1;
+            if *variants == 0 {
+                self.vary.remove(&entry.base_key);
+            }
+        }
+    }
+
+    fn remove_least_recently_used(&mut self) -> bool {
+        match self.recency.first_key_value() {
+            Some((_, key)) => {
+                let key = key.clone();
+                self.remove(&key);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Keeps the responses of the backends allowed to be cached by their
+/// `Cache-Control`, so that read-heavy APIs don't need another proxy in
+/// front for that. The least recently used responses go first once full.
+pub struct ResponseCache {
+    listener: String,
+    capacity: usize,
+    max_body_bytes: u64,
+    entries: Mutex<Entries>,
+    metrics: Arc<Metrics>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, max_body_bytes: u64, metrics: Arc<Metrics>) -> Self {
+        Self {
+            listener: String::new(),
+            capacity,
+            max_body_bytes,
+            entries: Mutex::default(),
+            metrics,
+        }
+    }
+
+    /// Keys the responses by the listener, when each one has its own cache.
+    pub fn with_listener(mut self, listener: &str) -> Self {
+        self.listener = listener.to_string();
+        self
+    }
+
+    /// The method, scheme, host, listener and path of the requests that may
+    /// be answered from the cache, and the `route` they took once authorized,
+    /// so that only the clients routed the same way share their responses;
+    /// `None` for the others.
+    pub fn base_key(&self, request: &Request, scheme: &str, route: &str) -> Option<String> {
+        let cacheable_method = matches!(*request.method(), Method::GET | Method::HEAD);
+        let bypassed = request.headers().contains_key(header::AUTHORIZATION)
+            || cache_control(request.headers())
+                .any(|directive| directive == "no-cache" || directive == "no-store");
+        let host = request
+            .uri()
+            .authority()
+            .map(|authority| authority.as_str())
+            .or_else(|| {
+                request
+                    .headers()
+                    .get(header::HOST)
+                    .and_then(|host| host.to_str().ok())
+            })
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let path_and_query = request
+            .uri()
+            .path_and_query()
+            .map_or("/", |path_and_query| path_and_query.as_str());
+
+        (cacheable_method && !bypassed).then(|| {
+            format!(
+                "{} {}://{} {} {}\n{}",
+                request.method(),
+                scheme,
+                host,
+                self.listener,
+                path_and_query,
+                route
+            )
+        })
+    }
+
+    pub fn get(&self, base_key: &str, request_headers: &HeaderMap) -> Option<Response> {
+        let Ok(mut entries) = self.entries.lock() else {
+            return None;
+        };
+        let key = entries
+            .vary
+            .get(base_key)
+            .map(|(vary, _)| variant_key(base_key, vary, request_headers))
+            .unwrap_or_else(|| base_key.to_string());
+
+        let now = Instant::now();
+        let with_cookies = request_headers.contains_key(header::COOKIE);
+        let fresh = entries
+            .entries
+            .get(&key)
+            .filter(|entry| entry.public || !with_cookies)
+            .map(|entry| entry.expires_at > now);
+        match fresh {
+            Some(true) => {}
+            Some(false) => {
+                entries.remove(&key);
+                self.metrics.increment(CACHE_MISSES_TOTAL);
+                return None;
+            }
+            None => {
+                self.metrics.increment(CACHE_MISSES_TOTAL);
+                return None;
+            }
+        }
+
+        let last_used = entries.tick();
+        let entry = entries.entries.get_mut(&key)?;
+        let previously_used = std::mem::replace(&mut entry.last_used, last_used);
+
+        let mut response = Response::new(Body::from(entry.body.clone()));
+        *response.status_mut() = entry.status;
+        *response.headers_mut() = entry.headers.clone();
+        response.headers_mut().insert(
+            header::AGE,
+            HeaderValue::from(entry.stored_at.elapsed().as_secs()),
+        );
+        response
+            .headers_mut()
+            .insert(X_CACHE, HeaderValue::from_static("HIT"));
+
+        entries.recency.remove(&previously_used);
+        entries.recency.insert(last_used, key);
+        self.metrics.increment(CACHE_HITS_TOTAL);
+
+        Some(response)
+    }
+
+    /// Keeps the response when its `Cache-Control` allows it, handing it
+    /// back either way.
+    pub async fn store(
+        &self,
+        base_key: String,
+        request_headers: &HeaderMap,
+        mut response: Response,
+    ) -> Response {
+        response
+            .headers_mut()
+            .insert(X_CACHE, HeaderValue::from_static("MISS"));
+
+        let Some((ttl, public)) = self.ttl(&response) else {
+            return response;
+        };
+        // The response may depend on the cookies unless it says otherwise.
+        if request_headers.contains_key(header::COOKIE) && !public {
+            return response;
+        }
+        let Some(expires_at) = Instant::now().checked_add(ttl) else {
+            return response;
+        };
+        let Some(vary) = vary(response.headers()) else {
+            return response;
+        };
+
+        let (parts, body) = response.into_parts();
+        let body = match axum::body::to_bytes(body, self.max_body_bytes as usize).await {
+            Ok(body) => body,
+            Err(_) => return StatusCode::BAD_GATEWAY.into_response(),
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            let key = variant_key(&base_key, &vary, request_headers);
+            entries.remove(&key);
+
+            while entries.entries.len() >= self.capacity {
+                if !entries.remove_least_recently_used() {
+                    break;
+                }
+            }
+
+            let last_used = entries.tick();
+            let mut headers = parts.headers.clone();
+            headers.remove(X_CACHE);
+            entries.insert(
+                key,
+                vary,
+                Entry {
+                    base_key,
+                    public,
+                    status: parts.status,
+                    headers,
+                    body: body.clone(),
+                    stored_at: Instant::now(),
+                    expires_at,
+                    last_used,
+                },
+            );
+        }
+
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    /// How long the response may be served from the cache, and whether it
+    /// is marked `public`, `None` when it may not be stored at all.
+    fn ttl(&self, response: &Response) -> Option<(Duration, bool)> {
+        let small = response
+            .body()
+            .size_hint()
+            .upper()
+            .is_some_and(|size| size <= self.max_body_bytes);
+        if self.capacity == 0
+            || !small
+            || !CACHEABLE_STATUSES.contains(&response.status())
+            || response.headers().contains_key(header::SET_COOKIE)
+        {
+            return None;
+        }
+
+        let mut max_age = None;
+        let mut shared_max_age = None;
+        let mut public = false;
+        for directive in cache_control(response.headers()) {
+            match directive.split_once('=') {
+                Some(("max-age", seconds)) => max_age = seconds.trim_matches('"').parse().ok(),
+                Some(("s-maxage", seconds)) => {
+                    shared_max_age = seconds.trim_matches('"').parse().ok()
+                }
+                None if matches!(directive.as_str(), "no-store" | "no-cache" | "private") => {
+                    return None;
+                }
+                None if directive == "public" => public = true,
+                _ => {}
+            }
+        }
+
+        shared_max_age
+            .or(max_age)
+            .filter(|seconds| *seconds > 0)
+            .map(|seconds| (Duration::from_secs(seconds).min(MAX_TTL), public))
+    }
+}
+
+/// Directives of the `Cache-Control` headers, lowercased.
+fn cache_control(headers: &HeaderMap) -> impl Iterator<Item = String> + '_ {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+}
+
+/// Headers listed by `Vary`, `None` when the response varies on anything.
+fn vary(headers: &HeaderMap) -> Option<Vec<HeaderName>> {
+    let mut names = Vec::new();
+
+    for name in headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if name == "*" {
+            return None;
+        }
+        names.push(HeaderName::try_from(name).ok()?);
+    }
+    names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    Some(names)
+}
+
+fn variant_key(base_key: &str, vary: &[HeaderName], request_headers: &HeaderMap) -> String {
+    let mut key = base_key.to_string();
+
+    for name in vary {
+        key.push('\n');
+        key.push_str(name.as_str());
+        key.push(':');
+        for value in request_headers.get_all(name) {
+            key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+            key.push(',');
+        }
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::body::{Body, to_bytes};
+    use axum::extract::Request;
+    use axum::response::Response;
+    use http::{HeaderMap, HeaderValue, StatusCode, header};
+
+    use crate::metrics::metrics::{CACHE_HITS_TOTAL, CACHE_MISSES_TOTAL, Metrics};
+    use crate::response_cache::{ResponseCache, X_CACHE};
+
+    fn cache(capacity: usize) -> (ResponseCache, Arc<Metrics>) {
+        let metrics = Arc::new(Metrics::default());
+
+        (
+            ResponseCache::new(capacity, 1024, Arc::clone(&metrics)),
+            metrics,
+        )
+    }
+
+    fn response(cache_control: &'static str, body: &'static str) -> Response {
+        Response::builder()
+            .header(header::CACHE_CONTROL, cache_control)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_stored_responses_until_they_expire() {
+        let (cache, metrics) = cache(10);
+        let key = cache.base_key(&get("/products"), "http", "").unwrap();
+
+        assert!(cache.get(&key, &HeaderMap::new()).is_none());
+
+        let stored = cache
+            .store(
+                key.clone(),
+                &HeaderMap::new(),
+                response("max-age=60", "products"),
+            )
+            .await;
+        assert_eq!(stored.headers()[X_CACHE], "MISS");
+
+        let hit = cache.get(&key, &HeaderMap::new()).unwrap();
+        assert_eq!(hit.headers()[X_CACHE], "HIT");
+        assert_eq!(hit.headers()[header::AGE], "0");
+        assert_eq!(
+            to_bytes(hit.into_body(), usize::MAX).await.unwrap(),
+            "products"
+        );
+        assert_eq!(metrics.get(CACHE_HITS_TOTAL), 1);
+        assert_eq!(metrics.get(CACHE_MISSES_TOTAL), 1);
+    }
+
+    #[tokio::test]
+    async fn forgets_expired_responses() {
+        let (cache, _) = cache(10);
+        let key = cache.base_key(&get("/products"), "http", "").unwrap();
+        cache
+            .store(
+                key.clone(),
+                &HeaderMap::new(),
+                response("s-maxage=1, max-age=60", "products"),
+            )
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        assert!(cache.get(&key, &HeaderMap::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn only_stores_what_cache_control_allows() {
+        let (cache, _) = cache(10);
+
+        for cache_control in ["no-store", "private, max-age=60", "no-cache", "public"] {
+            let key = cache.base_key(&get("/products"), "http", "").unwrap();
+            cache
+                .store(
+                    key.clone(),
+                    &HeaderMap::new(),
+                    response(cache_control, "products"),
+                )
+                .await;
+
+            assert!(
+                cache.get(&key, &HeaderMap::new()).is_none(),
+                "{}",
+                cache_control
+            );
+        }
+
+        let mut with_cookie = response("max-age=60", "products");
+        with_cookie
+            .headers_mut()
+            .insert(header::SET_COOKIE, HeaderValue::from_static("session=1"));
+        let key = cache.base_key(&get("/products"), "http", "").unwrap();
+        cache
+            .store(key.clone(), &HeaderMap::new(), with_cookie)
+            .await;
+
+        assert!(cache.get(&key, &HeaderMap::new()).is_none());
+
+        let mut error = response("max-age=60", "oops");
+        *error.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        cache.store(key.clone(), &HeaderMap::new(), error).await;
+
+        assert!(cache.get(&key, &HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn bypasses_requests_asking_for_a_fresh_response() {
+        let no_cache = Request::builder()
+            .uri("/products")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::empty())
+            .unwrap();
+        let authorized = Request::builder()
+            .uri("/products")
+            .header(header::AUTHORIZATION, "Bearer token")
+            .body(Body::empty())
+            .unwrap();
+        let post = Request::builder()
+            .method("POST")
+            .uri("/products")
+            .body(Body::empty())
+            .unwrap();
+
+        let (cache, _) = cache(10);
+
+        assert_eq!(cache.base_key(&no_cache, "http", ""), None);
+        assert_eq!(cache.base_key(&authorized, "http", ""), None);
+        assert_eq!(cache.base_key(&post, "http", ""), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_a_response_per_variant() {
+        let (cache, _) = cache(10);
+        let key = cache.base_key(&get("/products"), "http", "").unwrap();
+        let english =
+            HeaderMap::from_iter([(header::ACCEPT_LANGUAGE, HeaderValue::from_static("en"))]);
+        let french =
+            HeaderMap::from_iter([(header::ACCEPT_LANGUAGE, HeaderValue::from_static("fr"))]);
+
+        let mut response = response("max-age=60", "products");
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept-Language"));
+        cache.store(key.clone(), &english, response).await;
+
+        assert!(cache.get(&key, &english).is_some());
+        assert!(cache.get(&key, &french).is_none());
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used_response() {
+        let (cache, _) = cache(2);
+        let keys = ["/a", "/b", "/c"].map(|uri| cache.base_key(&get(uri), "http", "").unwrap());
+
+        cache
+            .store(
+                keys[0].clone(),
+                &HeaderMap::new(),
+                response("max-age=60", "a"),
+            )
+            .await;
+        cache
+            .store(
+                keys[1].clone(),
+                &HeaderMap::new(),
+                response("max-age=60", "b"),
+            )
+            .await;
+        assert!(cache.get(&keys[0], &HeaderMap::new()).is_some());
+        cache
+            .store(
+                keys[2].clone(),
+                &HeaderMap::new(),
+                response("max-age=60", "c"),
+            )
+            .await;
+
+        assert!(cache.get(&keys[0], &HeaderMap::new()).is_some());
+        assert!(cache.get(&keys[1], &HeaderMap::new()).is_none());
+        assert!(cache.get(&keys[2], &HeaderMap::new()).is_some());
+    }
+
+    #[test]
+    fn keys_the_responses_by_scheme_host_listener_path_and_route() {
+        let (cache, _) = cache(10);
+        let internal =
+            ResponseCache::new(10, 1024, Arc::new(Metrics::default())).with_listener("internal");
+        let request = |host: &str| {
+            Request::builder()
+                .uri("/products?page=2")
+                .header(header::HOST, host)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let key = cache
+            .base_key(&request("shop.example.com"), "https", "")
+            .unwrap();
+
+        assert_eq!(key, "GET https://shop.example.com  /products?page=2\n");
+        assert_ne!(
+            cache.base_key(&request("shop.example.com"), "https", "canary"),
+            Some(key.clone())
+        );
+        assert_ne!(
+            cache.base_key(&request("admin.example.com"), "https", ""),
+            Some(key.clone())
+        );
+        assert_ne!(
+            cache.base_key(&request("shop.example.com"), "http", ""),
+            Some(key.clone())
+        );
+        assert_ne!(
+            internal.base_key(&request("shop.example.com"), "https", ""),
+            Some(key)
+        );
+    }
+
+    #[tokio::test]
+    async fn only_shares_public_responses_with_requests_carrying_cookies() {
+        let (cache, _) = cache(10);
+        let key = cache.base_key(&get("/products"), "http", "").unwrap();
+        let with_cookie =
+            HeaderMap::from_iter([(header::COOKIE, HeaderValue::from_static("session=1"))]);
+
+        cache
+            .store(key.clone(), &with_cookie, response("max-age=60", "mine"))
+            .await;
+        assert!(cache.get(&key, &HeaderMap::new()).is_none());
+
+        cache
+            .store(
+                key.clone(),
+                &HeaderMap::new(),
+                response("max-age=60", "all"),
+            )
+            .await;
+        assert!(cache.get(&key, &HeaderMap::new()).is_some());
+        assert!(cache.get(&key, &with_cookie).is_none());
+
+        cache
+            .store(
+                key.clone(),
+                &with_cookie,
+                response("public, max-age=60", "public"),
+            )
+            .await;
+        assert!(cache.get(&key, &with_cookie).is_some());
+    }
+
+    #[tokio::test]
+    async fn caps_the_time_to_live() {
+        let (cache, _) = cache(10);
+        let key = cache.base_key(&get("/products"), "http", "").unwrap();
+
+        cache
+            .store(
+                key.clone(),
+                &HeaderMap::new(),
+                response("max-age=18446744073709551615", "products"),
+            )
+            .await;
+
+        assert!(cache.get(&key, &HeaderMap::new()).is_some());
+    }
+
+    #[tokio::test]
+    async fn forgets_what_evicted_responses_vary_on() {
+        let (cache, _) = cache(1);
+        let english =
+            HeaderMap::from_iter([(header::ACCEPT_LANGUAGE, HeaderValue::from_static("en"))]);
+
+        for uri in ["/a", "/b", "/c"] {
+            let mut response = response("max-age=60", "varying");
+            response
+                .headers_mut()
+                .insert(header::VARY, HeaderValue::from_static("Accept-Language"));
+            let key = cache.base_key(&get(uri), "http", "").unwrap();
+            cache.store(key, &english, response).await;
+        }
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.entries.len(), 1);
+        assert_eq!(entries.vary.len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use axum::body::HttpBody;
+use http::header;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+
+pub const DEFAULT_CONTENT_TYPES: [&str; 5] = [
+    "text/",
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "image/svg+xml",
+];
+
+/// Compresses the responses with gzip or brotli, as accepted by the client,
+/// so that the backends don't each need to.
+#[derive(Debug, Clone)]
+pub struct ResponseCompression {
+    pub min_size: u16,
+    /// Prefixes of the content types worth compressing.
+    pub content_types: Arc<Vec<String>>,
+}
+
+impl Default for ResponseCompression {
+    fn default() -> Self {
+        Self {
+            min_size: 1024,
+            content_types: Arc::new(DEFAULT_CONTENT_TYPES.map(str::to_string).to_vec()),
+        }
+    }
+}
+
+impl ResponseCompression {
+    pub fn layer(&self) -> CompressionLayer<impl Predicate + use<>> {
+        let predicate = SizeAbove::new(self.min_size)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::SSE)
+            .and(self.clone());
+
+        CompressionLayer::new()
+            .no_deflate()
+            .no_zstd()
+            .compress_when(predicate)
+    }
+}
+
+impl Predicate for ResponseCompression {
+    fn should_compress<B>(&self, response: &http::Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| {
+                self.content_types
+                    .iter()
+                    .any(|allowed| content_type.starts_with(allowed.as_str()))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::body::Body;
+    use axum::routing::get;
+    use http::{Request, header};
+    use tower::ServiceExt;
+
+    use crate::response_compression::ResponseCompression;
+
+    fn router(content_type: &'static str, body: String) -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(move || async move { ([(header::CONTENT_TYPE, content_type)], body) }),
+            )
+            .layer(ResponseCompression::default().layer())
+    }
+
+    async fn content_encoding(router: Router, accept_encoding: &str) -> Option<String> {
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ACCEPT_ENCODING, accept_encoding)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn compresses_large_text_responses() {
+        let body = "wakanda forever ".repeat(256);
+
+        assert_eq!(
+            content_encoding(router("application/json", body.clone()), "br").await,
+            Some("br".to_string())
+        );
+        assert_eq!(
+            content_encoding(router("text/html; charset=utf-8", body), "gzip").await,
+            Some("gzip".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_small_or_binary_responses() {
+        assert_eq!(
+            content_encoding(router("application/json", "{}".to_string()), "gzip").await,
+            None
+        );
+        assert_eq!(
+            content_encoding(router("image/png", "x".repeat(4096)), "gzip").await,
+            None
+        );
+    }
+}
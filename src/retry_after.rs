@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use http::{HeaderValue, StatusCode, header};
+
+use crate::http_client::response::Response as HttpClientResponse;
+
+/// How long the response asks to wait before trying again, from a
+/// `Retry-After` in seconds or as an HTTP date.
+pub fn retry_after(response: &HttpClientResponse) -> Option<Duration> {
+    let value = response
+        .headers
+        .get(header::RETRY_AFTER.as_str())?
+        .to_str()
+        .ok()?;
+
+    match value.trim().parse() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+/// Whether a backend asked for the request to be tried again later, which
+/// is worth doing elsewhere.
+pub fn asks_to_retry_later(response: &HttpClientResponse) -> bool {
+    response.status == StatusCode::TOO_MANY_REQUESTS.as_u16()
+        && response.headers.contains_key(header::RETRY_AFTER.as_str())
+}
+
+/// `Retry-After` of a response, in whole seconds rounded up.
+pub fn header_value(retry_after: Duration) -> HeaderValue {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    HeaderValue::from(seconds)
+}
+
+/// The longest a backend is left alone, whatever its `Retry-After` asks.
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// The backends that asked to be left alone for a while with a
+/// `Retry-After`, and until when.
+#[derive(Debug, Default)]
+pub struct BackendBackoffs {
+    until: Mutex<HashMap<String, Instant>>,
+}
+
+impl BackendBackoffs {
+    pub fn back_off(&self, server: &str, retry_after: Duration) {
+        let Ok(mut until) = self.until.lock() else {
+            return;
+        };
+        let Some(backoff_until) = Instant::now().checked_add(retry_after.min(MAX_BACKOFF)) else {
+            return;
+        };
+
+        until
+            .entry(server.to_string())
+            .and_modify(|until| *until = (*until).max(backoff_until))
+            .or_insert(backoff_until);
+    }
+
+    /// Servers still backing off, forgetting the others.
+    pub fn backing_off(&self) -> Vec<String> {
+        let Ok(mut until) = self.until.lock() else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+
+        until.retain(|_, until| *until > now);
+        until.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, SystemTime};
+
+    use bytes::Bytes;
+    use http::{HeaderValue, header};
+
+    use crate::http_client::request::RequestHeaders;
+    use crate::http_client::response::Response as HttpClientResponse;
+    use crate::retry_after::{BackendBackoffs, asks_to_retry_later, header_value, retry_after};
+
+    fn response(status: u16, retry_after: &str) -> HttpClientResponse {
+        HttpClientResponse {
+            status,
+            headers: RequestHeaders::from([(
+                header::RETRY_AFTER,
+                HeaderValue::try_from(retry_after).unwrap(),
+            )]),
+            body: Bytes::new().into(),
+        }
+    }
+
+    #[test]
+    fn reads_retry_after_in_seconds_or_as_a_date() {
+        assert_eq!(
+            retry_after(&response(503, "120")),
+            Some(Duration::from_secs(120))
+        );
+
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        let delay = retry_after(&response(503, &date)).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+
+        assert_eq!(
+            retry_after(&response(503, "Wed, 21 Oct 2015 07:28:00 GMT")),
+            None
+        );
+        assert_eq!(retry_after(&response(503, "soon")), None);
+    }
+
+    #[test]
+    fn retries_elsewhere_the_requests_of_a_rate_limited_backend() {
+        assert!(asks_to_retry_later(&response(429, "1")));
+        assert!(!asks_to_retry_later(&response(500, "1")));
+    }
+
+    #[test]
+    fn rounds_retry_after_up() {
+        assert_eq!(header_value(Duration::from_millis(1500)), "2");
+        assert_eq!(header_value(Duration::from_secs(3)), "3");
+    }
+
+    #[test]
+    fn forgets_backoffs_once_over() {
+        let backoffs = BackendBackoffs::default();
+
+        backoffs.back_off("http://server1.com", Duration::from_secs(60));
+        backoffs.back_off("http://server2.com", Duration::ZERO);
+
+        assert_eq!(backoffs.backing_off(), vec!["http://server1.com"]);
+    }
+
+    #[test]
+    fn caps_backoffs_at_an_hour() {
+        let backoffs = BackendBackoffs::default();
+
+        backoffs.back_off("http://server1.com", Duration::MAX);
+
+        let until = backoffs.until.lock().unwrap()["http://server1.com"];
+        assert!(until <= Instant::now() + Duration::from_secs(3600));
+        assert_eq!(backoffs.backing_off(), vec!["http://server1.com"]);
+    }
+}
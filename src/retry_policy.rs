@@ -0,0 +1,76 @@
+use http::{HeaderMap, HeaderName, Method};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Which requests may be retried on another backend: sending a request twice
+/// is only safe when doing it once more has no other effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Methods always retried, being idempotent.
+    pub idempotent_methods: Vec<Method>,
+    /// Methods retried only when the client sends an `Idempotency-Key`,
+    /// letting the backends recognize the request they already processed.
+    pub idempotency_key_methods: Vec<Method>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            idempotent_methods: vec![Method::GET, Method::HEAD, Method::PUT, Method::DELETE],
+            idempotency_key_methods: vec![Method::POST],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn allows(&self, method: &Method, headers: &HeaderMap) -> bool {
+        self.idempotent_methods.contains(method)
+            || (self.idempotency_key_methods.contains(method)
+                && headers.contains_key(IDEMPOTENCY_KEY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue, Method};
+
+    use crate::retry_policy::{IDEMPOTENCY_KEY, RetryPolicy};
+
+    #[test]
+    fn retries_idempotent_methods_by_default() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy.allows(&Method::GET, &HeaderMap::new()));
+        assert!(policy.allows(&Method::HEAD, &HeaderMap::new()));
+        assert!(policy.allows(&Method::PUT, &HeaderMap::new()));
+        assert!(policy.allows(&Method::DELETE, &HeaderMap::new()));
+        assert!(!policy.allows(&Method::PATCH, &HeaderMap::new()));
+    }
+
+    #[test]
+    fn retries_post_only_with_an_idempotency_key() {
+        let policy = RetryPolicy::default();
+        let mut headers = HeaderMap::new();
+
+        assert!(!policy.allows(&Method::POST, &headers));
+
+        headers.insert(IDEMPOTENCY_KEY, HeaderValue::from_static("8e03978e"));
+
+        assert!(policy.allows(&Method::POST, &headers));
+        assert!(!policy.allows(&Method::PATCH, &headers));
+    }
+
+    #[test]
+    fn method_classes_are_configurable() {
+        let policy = RetryPolicy {
+            idempotent_methods: vec![Method::GET],
+            idempotency_key_methods: vec![Method::PATCH],
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENCY_KEY, HeaderValue::from_static("8e03978e"));
+
+        assert!(!policy.allows(&Method::PUT, &HeaderMap::new()));
+        assert!(!policy.allows(&Method::POST, &headers));
+        assert!(policy.allows(&Method::PATCH, &headers));
+    }
+}
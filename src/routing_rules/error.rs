@@ -0,0 +1,35 @@
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum Error {
+    #[error("Unexpected '{0}' at offset {1}")]
+    UnexpectedCharacter(char, usize),
+
+    #[error("Unterminated string starting at offset {0}")]
+    UnterminatedString(usize),
+
+    #[error("Expected {expected}, got {found}")]
+    UnexpectedToken {
+        expected: &'static str,
+        found: String,
+    },
+
+    #[error("Unknown function {0}")]
+    UnknownFunction(String),
+
+    #[error("{0} takes {1} argument(s)")]
+    WrongArgumentCount(String, usize),
+
+    #[error("Invalid header name {0}")]
+    InvalidHeaderName(String),
+
+    #[error("Expected EXPRESSION=>BACKENDS[;TRANSFORM]..., got {0}")]
+    InvalidRule(String),
+
+    #[error("Expected request|response:add|set|remove:HEADER[=VALUE], got {0}")]
+    InvalidHeaderTransform(String),
+
+    #[error("{0}")]
+    InvalidBackend(String),
+
+    #[error("Expected timeout:MILLIS over 0, got {0}")]
+    InvalidTimeout(String),
+}
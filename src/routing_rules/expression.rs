@@ -0,0 +1,478 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use http::uri::Authority;
+use http::{HeaderMap, HeaderName, Method, Uri, header};
+
+use crate::routing_rules::error::Error;
+
+/// Functions known to the expressions, with the number of arguments they take.
+const FUNCTIONS: [(&str, usize); 7] = [
+    ("header", 1),
+    ("query", 1),
+    ("method", 0),
+    ("path", 0),
+    ("host", 0),
+    ("path_prefix", 1),
+    ("has_header", 1),
+];
+
+/// A condition over a request, e.g.
+/// `header("x-tier") == "gold" && path_prefix("/api")`.
+///
+/// `header(NAME)`, `query(NAME)`, `method()`, `path()` and `host()` are
+/// compared to a string with `==` or `!=`, while `path_prefix(PREFIX)` and
+/// `has_header(NAME)` are conditions of their own. Conditions are combined
+/// with `!`, `&&` and `||`, binding in that order, and grouped with
+/// parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Equals(Value, String),
+    NotEquals(Value, String),
+    PathPrefix(String),
+    HasHeader(HeaderName),
+}
+
+/// Part of the request an expression compares.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Header(HeaderName),
+    /// Compared as written in the query string, without decoding.
+    Query(String),
+    Method,
+    Path,
+    /// Lowercased, without the port.
+    Host,
+}
+
+impl Expression {
+    pub fn matches(&self, method: &Method, uri: &Uri, headers: &HeaderMap) -> bool {
+        match self {
+            Expression::Or(left, right) => {
+                left.matches(method, uri, headers) || right.matches(method, uri, headers)
+            }
+            Expression::And(left, right) => {
+                left.matches(method, uri, headers) && right.matches(method, uri, headers)
+            }
+            Expression::Not(expression) => !expression.matches(method, uri, headers),
+            Expression::Equals(value, expected) => value
+                .resolve(method, uri, headers)
+                .is_some_and(|value| value == expected.as_str()),
+            // A missing value differs from every string.
+            Expression::NotEquals(value, expected) => value
+                .resolve(method, uri, headers)
+                .is_none_or(|value| value != expected.as_str()),
+            Expression::PathPrefix(prefix) => uri.path().starts_with(prefix.as_str()),
+            Expression::HasHeader(name) => headers.contains_key(name),
+        }
+    }
+}
+
+impl Value {
+    fn resolve<'a>(
+        &self,
+        method: &'a Method,
+        uri: &'a Uri,
+        headers: &'a HeaderMap,
+    ) -> Option<Cow<'a, str>> {
+        match self {
+            Value::Header(name) => headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(Cow::Borrowed),
+            Value::Query(name) => uri
+                .query()?
+                .split('&')
+                .find_map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (key == name).then_some(value)
+                })
+                .map(Cow::Borrowed),
+            Value::Method => Some(Cow::Borrowed(method.as_str())),
+            Value::Path => Some(Cow::Borrowed(uri.path())),
+            Value::Host => headers
+                .get(header::HOST)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<Authority>().ok())
+                .map(|authority| authority.host().to_ascii_lowercase())
+                .or_else(|| uri.host().map(str::to_ascii_lowercase))
+                .map(Cow::Owned),
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(value)?,
+            position: 0,
+        };
+
+        let expression = parser.or()?;
+        parser.expect(Token::End, "the end of the expression")?;
+
+        Ok(expression)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    String(String),
+    OpenParenthesis,
+    CloseParenthesis,
+    Comma,
+    And,
+    Or,
+    Not,
+    Equals,
+    NotEquals,
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Identifier(name) => name.clone(),
+            Token::String(value) => format!("\"{}\"", value),
+            Token::OpenParenthesis => "'('".to_string(),
+            Token::CloseParenthesis => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::And => "'&&'".to_string(),
+            Token::Or => "'||'".to_string(),
+            Token::Not => "'!'".to_string(),
+            Token::Equals => "'=='".to_string(),
+            Token::NotEquals => "'!='".to_string(),
+            Token::End => "the end of the expression".to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut characters = source.char_indices().peekable();
+
+    while let Some((offset, character)) = characters.next() {
+        let mut followed_by =
+            |expected: char| characters.next_if(|(_, next)| *next == expected).is_some();
+
+        let token = match character {
+            character if character.is_whitespace() => continue,
+            '(' => Token::OpenParenthesis,
+            ')' => Token::CloseParenthesis,
+            ',' => Token::Comma,
+            '&' if followed_by('&') => Token::And,
+            '|' if followed_by('|') => Token::Or,
+            '=' if followed_by('=') => Token::Equals,
+            '!' if followed_by('=') => Token::NotEquals,
+            '!' => Token::Not,
+            '"' => {
+                let mut value = String::new();
+
+                loop {
+                    match characters.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match characters.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(Error::UnterminatedString(offset)),
+                        },
+                        Some((_, character)) => value.push(character),
+                        None => return Err(Error::UnterminatedString(offset)),
+                    }
+                }
+
+                Token::String(value)
+            }
+            character if character.is_ascii_alphabetic() || character == '_' => {
+                let mut name = String::from(character);
+
+                while let Some((_, character)) = characters.next_if(|(_, character)| {
+                    character.is_ascii_alphanumeric() || *character == '_'
+                }) {
+                    name.push(character);
+                }
+
+                Token::Identifier(name)
+            }
+            character => return Err(Error::UnexpectedCharacter(character, offset)),
+        };
+
+        tokens.push(token);
+    }
+
+    tokens.push(Token::End);
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, expected: Token, description: &'static str) -> Result<(), Error> {
+        match self.next() {
+            token if token == expected => Ok(()),
+            token => Err(Error::UnexpectedToken {
+                expected: description,
+                found: token.describe(),
+            }),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.and()?;
+
+        while *self.peek() == Token::Or {
+            self.next();
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.not()?;
+
+        while *self.peek() == Token::And {
+            self.next();
+            expression = Expression::And(Box::new(expression), Box::new(self.not()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<Expression, Error> {
+        if *self.peek() == Token::Not {
+            self.next();
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Expression, Error> {
+        match self.next() {
+            Token::OpenParenthesis => {
+                let expression = self.or()?;
+                self.expect(Token::CloseParenthesis, "')'")?;
+                Ok(expression)
+            }
+            Token::Identifier(name) => {
+                let arguments = self.arguments()?;
+                self.call(name, arguments)
+            }
+            token => Err(Error::UnexpectedToken {
+                expected: "a condition",
+                found: token.describe(),
+            }),
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<String>, Error> {
+        self.expect(Token::OpenParenthesis, "'('")?;
+
+        let mut arguments = Vec::new();
+        if *self.peek() == Token::CloseParenthesis {
+            self.next();
+            return Ok(arguments);
+        }
+
+        loop {
+            arguments.push(self.string()?);
+
+            match self.next() {
+                Token::Comma => continue,
+                Token::CloseParenthesis => return Ok(arguments),
+                token => {
+                    return Err(Error::UnexpectedToken {
+                        expected: "',' or ')'",
+                        found: token.describe(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        match self.next() {
+            Token::String(value) => Ok(value),
+            token => Err(Error::UnexpectedToken {
+                expected: "a string",
+                found: token.describe(),
+            }),
+        }
+    }
+
+    fn call(&mut self, name: String, arguments: Vec<String>) -> Result<Expression, Error> {
+        match FUNCTIONS.iter().find(|(function, _)| *function == name) {
+            None => return Err(Error::UnknownFunction(name)),
+            Some((_, count)) if *count != arguments.len() => {
+                return Err(Error::WrongArgumentCount(name, *count));
+            }
+            Some(_) => {}
+        }
+
+        let mut arguments = arguments.into_iter();
+        let mut argument = || arguments.next().unwrap_or_default();
+
+        let value = match name.as_str() {
+            "path_prefix" => return Ok(Expression::PathPrefix(argument())),
+            "has_header" => return Ok(Expression::HasHeader(header_name(argument())?)),
+            "header" => Value::Header(header_name(argument())?),
+            "query" => Value::Query(argument()),
+            "method" => Value::Method,
+            "path" => Value::Path,
+            _ => Value::Host,
+        };
+
+        match self.next() {
+            Token::Equals => Ok(Expression::Equals(value, self.string()?)),
+            Token::NotEquals => Ok(Expression::NotEquals(value, self.string()?)),
+            token => Err(Error::UnexpectedToken {
+                expected: "'==' or '!='",
+                found: token.describe(),
+            }),
+        }
+    }
+}
+
+fn header_name(name: String) -> Result<HeaderName, Error> {
+    HeaderName::from_str(&name).map_err(|_| Error::InvalidHeaderName(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderName, HeaderValue, Method, Uri};
+
+    use crate::routing_rules::error::Error;
+    use crate::routing_rules::expression::{Expression, Value};
+
+    fn matches(expression: &str, method: Method, uri: &str, headers: &[(&str, &str)]) -> bool {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect::<HeaderMap>();
+
+        expression.parse::<Expression>().unwrap().matches(
+            &method,
+            &uri.parse::<Uri>().unwrap(),
+            &headers,
+        )
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            r#"method() == "GET" || path() == "/a" && !has_header("x-debug")"#.parse(),
+            Ok(Expression::Or(
+                Box::new(Expression::Equals(Value::Method, "GET".to_string())),
+                Box::new(Expression::And(
+                    Box::new(Expression::Equals(Value::Path, "/a".to_string())),
+                    Box::new(Expression::Not(Box::new(Expression::HasHeader(
+                        HeaderName::from_static("x-debug")
+                    )))),
+                )),
+            ))
+        );
+    }
+
+    #[test]
+    fn matches_headers_and_paths() {
+        let expression = r#"header("x-tier") == "gold" && path_prefix("/api")"#;
+
+        assert!(matches(
+            expression,
+            Method::GET,
+            "/api/orders",
+            &[("x-tier", "gold")]
+        ));
+        assert!(!matches(
+            expression,
+            Method::GET,
+            "/static/app.js",
+            &[("x-tier", "gold")]
+        ));
+        assert!(!matches(expression, Method::GET, "/api/orders", &[]));
+    }
+
+    #[test]
+    fn matches_query_method_and_host() {
+        assert!(matches(
+            r#"query("beta") == "1" && (method() == "POST" || method() == "PUT")"#,
+            Method::PUT,
+            "/cart?id=7&beta=1",
+            &[]
+        ));
+        assert!(matches(
+            r#"host() == "shop.example.com""#,
+            Method::GET,
+            "/",
+            &[("host", "Shop.Example.com:8443")]
+        ));
+        assert!(matches(
+            r#"header("x-tier") != "gold""#,
+            Method::GET,
+            "/",
+            &[]
+        ));
+        assert!(matches(r#"!(path() == "/a\"b")"#, Method::GET, "/", &[]));
+    }
+
+    #[test]
+    fn reports_invalid_expressions() {
+        assert_eq!(
+            r#"header("x-tier") = "gold""#.parse::<Expression>(),
+            Err(Error::UnexpectedCharacter('=', 17))
+        );
+        assert_eq!(
+            r#"header("x-tier") == "gold"#.parse::<Expression>(),
+            Err(Error::UnterminatedString(20))
+        );
+        assert_eq!(
+            r#"cookie("session") == "1""#.parse::<Expression>(),
+            Err(Error::UnknownFunction("cookie".to_string()))
+        );
+        assert_eq!(
+            r#"path_prefix()"#.parse::<Expression>(),
+            Err(Error::WrongArgumentCount("path_prefix".to_string(), 1))
+        );
+        assert_eq!(
+            r#"header("x-tier")"#.parse::<Expression>(),
+            Err(Error::UnexpectedToken {
+                expected: "'==' or '!='",
+                found: "the end of the expression".to_string(),
+            })
+        );
+        assert_eq!(
+            r#"has_header("x-a") has_header("x-b")"#.parse::<Expression>(),
+            Err(Error::UnexpectedToken {
+                expected: "the end of the expression",
+                found: "has_header".to_string(),
+            })
+        );
+    }
+}
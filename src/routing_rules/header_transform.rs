@@ -0,0 +1,166 @@
+use std::str::FromStr;
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::routing_rules::error::Error;
+
+/// Whether a header transform changes the request sent to the backend or
+/// the response sent back to the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Request,
+    Response,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderAction {
+    /// Keeps the values already there.
+    Add(HeaderName, HeaderValue),
+    /// Replaces every value of the header.
+    Set(HeaderName, HeaderValue),
+    Remove(HeaderName),
+}
+
+/// A header change made by a routing rule on the requests it matches,
+/// written as `DIRECTION:ACTION:ARGUMENT`, e.g.
+/// `response:set:X-Frame-Options=DENY`, `request:add:X-Admin=true` or
+/// `response:remove:Server`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderTransform {
+    pub direction: Direction,
+    pub action: HeaderAction,
+}
+
+impl HeaderTransform {
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        match &self.action {
+            HeaderAction::Add(name, value) => {
+                headers.append(name.clone(), value.clone());
+            }
+            HeaderAction::Set(name, value) => {
+                headers.insert(name.clone(), value.clone());
+            }
+            HeaderAction::Remove(name) => {
+                headers.remove(name);
+            }
+        }
+    }
+}
+
+impl FromStr for HeaderTransform {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidHeaderTransform(value.to_string());
+
+        let mut parts = value.trim().splitn(3, ':');
+        let (Some(direction), Some(action), Some(argument)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        let direction = match direction {
+            "request" => Direction::Request,
+            "response" => Direction::Response,
+            _ => return Err(invalid()),
+        };
+        let header_name = |name: &str| {
+            HeaderName::from_str(name.trim()).map_err(|_| Error::InvalidHeaderName(name.into()))
+        };
+        let pair = || {
+            let (name, header_value) = argument.split_once('=').ok_or_else(invalid)?;
+            let header_value = HeaderValue::from_str(header_value.trim()).map_err(|_| invalid())?;
+
+            Ok::<_, Error>((header_name(name)?, header_value))
+        };
+
+        let action = match action {
+            "add" => {
+                let (name, header_value) = pair()?;
+                HeaderAction::Add(name, header_value)
+            }
+            "set" => {
+                let (name, header_value) = pair()?;
+                HeaderAction::Set(name, header_value)
+            }
+            "remove" => HeaderAction::Remove(header_name(argument)?),
+            _ => return Err(invalid()),
+        };
+
+        Ok(HeaderTransform { direction, action })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderName, HeaderValue};
+
+    use crate::routing_rules::header_transform::{Direction, HeaderAction, HeaderTransform};
+
+    #[test]
+    fn parses_direction_action_and_header() {
+        assert_eq!(
+            "response:set:X-Frame-Options=DENY".parse(),
+            Ok(HeaderTransform {
+                direction: Direction::Response,
+                action: HeaderAction::Set(
+                    HeaderName::from_static("x-frame-options"),
+                    HeaderValue::from_static("DENY")
+                ),
+            })
+        );
+        assert_eq!(
+            "request:remove:X-Debug".parse(),
+            Ok(HeaderTransform {
+                direction: Direction::Request,
+                action: HeaderAction::Remove(HeaderName::from_static("x-debug")),
+            })
+        );
+    }
+
+    #[test]
+    fn adds_sets_and_removes_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("vary", HeaderValue::from_static("accept"));
+        headers.insert("server", HeaderValue::from_static("nginx"));
+        headers.insert("x-frame-options", HeaderValue::from_static("SAMEORIGIN"));
+
+        for transform in [
+            "response:add:Vary=origin",
+            "response:remove:Server",
+            "response:set:X-Frame-Options=DENY",
+        ] {
+            transform
+                .parse::<HeaderTransform>()
+                .unwrap()
+                .apply(&mut headers);
+        }
+
+        assert_eq!(
+            headers.get_all("vary").iter().collect::<Vec<_>>(),
+            vec!["accept", "origin"]
+        );
+        assert!(!headers.contains_key("server"));
+        assert_eq!(headers["x-frame-options"], "DENY");
+    }
+
+    #[test]
+    fn rejects_invalid_transforms() {
+        for transform in [
+            "response",
+            "response:set",
+            "client:set:X-Env=prod",
+            "response:rename:X-A=X-B",
+            "response:set:X-Frame-Options",
+            "request:add:X Env=prod",
+            "request:remove:X Debug",
+        ] {
+            assert!(
+                transform.parse::<HeaderTransform>().is_err(),
+                "{}",
+                transform
+            );
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod error;
+pub mod expression;
+pub mod header_transform;
+#[allow(clippy::module_inception)]
+pub mod routing_rules;
@@ -0,0 +1,304 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use http::{HeaderMap, Method, Uri};
+
+use crate::routing_rules::error::Error;
+use crate::routing_rules::expression::Expression;
+use crate::routing_rules::header_transform::{Direction, HeaderTransform};
+use crate::target_url::normalize_target_url;
+
+/// Sends the requests matching `expression` to `backends` only, changing
+/// their headers and those of their responses as told by
+/// `header_transforms`, and waiting `timeout` for their response heads
+/// instead of the timeout of the backend.
+///
+/// Written as `EXPRESSION=>BACKEND|BACKEND[;TRANSFORM]...[;timeout:MILLIS]`,
+/// e.g. `header("x-tier") == "gold" && path_prefix("/api")=>http://gold:8080`
+/// or `path_prefix("/admin")=>*;response:set:X-Frame-Options=DENY`, `*`
+/// keeping every backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingRule {
+    pub expression: Expression,
+    /// Empty when written as `*`.
+    pub backends: Vec<String>,
+    pub header_transforms: Vec<HeaderTransform>,
+    pub timeout: Option<Duration>,
+}
+
+impl RoutingRule {
+    /// Servers the requests matching the rule must not be sent to.
+    pub fn excluded_servers(&self, target_servers: &[String]) -> Vec<String> {
+        if self.backends.is_empty() {
+            return Vec::new();
+        }
+
+        target_servers
+            .iter()
+            .filter(|server| !self.backends.contains(server))
+            .cloned()
+            .collect()
+    }
+
+    /// Applies the transforms of the direction, in order.
+    pub fn transform_headers(&self, direction: Direction, headers: &mut HeaderMap) {
+        for transform in &self.header_transforms {
+            if transform.direction == direction {
+                transform.apply(headers);
+            }
+        }
+    }
+}
+
+impl FromStr for RoutingRule {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // The last arrow, as the strings of the expression may hold one.
+        let (expression, target) = value
+            .rsplit_once("=>")
+            .ok_or_else(|| Error::InvalidRule(value.to_string()))?;
+
+        // Header values may hold a `;` too, e.g. a Content-Security-Policy:
+        // only the ones starting a direction begin another transform.
+        let mut segments = Vec::<String>::new();
+        for segment in target.split(';') {
+            match segments.last_mut() {
+                Some(last)
+                    if !["request:", "response:", "timeout:"]
+                        .iter()
+                        .any(|prefix| segment.trim_start().starts_with(prefix)) =>
+                {
+                    last.push(';');
+                    last.push_str(segment);
+                }
+                _ => segments.push(segment.to_string()),
+            }
+        }
+
+        let (backends, header_transforms) = segments
+            .split_first()
+            .ok_or_else(|| Error::InvalidRule(value.to_string()))?;
+        let backends = match backends.trim() {
+            "*" => Vec::new(),
+            backends => {
+                let backends = backends
+                    .split('|')
+                    .map(str::trim)
+                    .filter(|backend| !backend.is_empty())
+                    .map(normalize_target_url)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(Error::InvalidBackend)?;
+
+                if backends.is_empty() {
+                    return Err(Error::InvalidRule(value.to_string()));
+                }
+
+                backends
+            }
+        };
+
+        let mut timeout = None;
+        let mut transforms = Vec::new();
+        for segment in header_transforms {
+            match segment.trim().strip_prefix("timeout:") {
+                Some(millis) => {
+                    let millis = millis
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|millis| *millis > 0)
+                        .ok_or_else(|| Error::InvalidTimeout(segment.trim().to_string()))?;
+                    timeout = Some(Duration::from_millis(millis));
+                }
+                None => transforms.push(segment.parse()?),
+            }
+        }
+
+        Ok(RoutingRule {
+            expression: expression.parse()?,
+            backends,
+            header_transforms: transforms,
+            timeout,
+        })
+    }
+}
+
+/// Rules parsed once at startup and evaluated in order on every request.
+#[derive(Debug, Clone, Default)]
+pub struct RoutingRules {
+    pub rules: Vec<RoutingRule>,
+}
+
+impl RoutingRules {
+    /// The first rule matching the request, the only one applied to it.
+    pub fn matching(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Option<&RoutingRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.expression.matches(method, uri, headers))
+    }
+
+    /// Servers the request must not be sent to: the first rule matching the
+    /// request keeps only its own backends.
+    pub fn excluded_servers(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        target_servers: &[String],
+    ) -> Vec<String> {
+        self.matching(method, uri, headers)
+            .map(|rule| rule.excluded_servers(target_servers))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::{HeaderMap, HeaderValue, Method, Uri};
+
+    use crate::routing_rules::error::Error;
+    use crate::routing_rules::header_transform::Direction;
+    use crate::routing_rules::routing_rules::{RoutingRule, RoutingRules};
+
+    fn target_servers() -> Vec<String> {
+        vec![
+            "http://gold:8080".to_string(),
+            "http://silver:8080".to_string(),
+            "http://bronze:8080".to_string(),
+        ]
+    }
+
+    #[test]
+    fn parses_expression_and_backends() {
+        let rule: RoutingRule =
+            r#"header("x-route") == "a=>b" => http://gold:8080|HTTP://Silver:8080/"#
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            rule.backends,
+            vec!["http://gold:8080", "http://silver:8080"]
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert("x-route", HeaderValue::from_static("a=>b"));
+        assert!(
+            rule.expression
+                .matches(&Method::GET, &Uri::from_static("/"), &headers)
+        );
+
+        assert_eq!(
+            r#"path() == "/""#.parse::<RoutingRule>(),
+            Err(Error::InvalidRule(r#"path() == "/""#.to_string()))
+        );
+        assert_eq!(
+            r#"path() == "/"=>"#.parse::<RoutingRule>(),
+            Err(Error::InvalidRule(r#"path() == "/"=>"#.to_string()))
+        );
+        assert!(matches!(
+            r#"path() == "/"=>gold:8080"#.parse::<RoutingRule>(),
+            Err(Error::InvalidBackend(_))
+        ));
+    }
+
+    #[test]
+    fn parses_header_transforms() {
+        let rule: RoutingRule = r#"path_prefix("/admin")=>*;response:set:X-Frame-Options=DENY;response:set:Content-Security-Policy=default-src 'self'; frame-ancestors 'none';request:add:X-Admin=true"#
+            .parse()
+            .unwrap();
+
+        assert!(rule.backends.is_empty());
+        assert_eq!(
+            rule.header_transforms,
+            vec![
+                "response:set:X-Frame-Options=DENY".parse().unwrap(),
+                "response:set:Content-Security-Policy=default-src 'self'; frame-ancestors 'none'"
+                    .parse()
+                    .unwrap(),
+                "request:add:X-Admin=true".parse().unwrap(),
+            ]
+        );
+        assert!(rule.excluded_servers(&target_servers()).is_empty());
+
+        let mut headers = HeaderMap::new();
+        rule.transform_headers(Direction::Response, &mut headers);
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert!(!headers.contains_key("x-admin"));
+
+        assert_eq!(
+            r#"path() == "/"=>http://gold:8080;response:rename:X-A=X-B"#.parse::<RoutingRule>(),
+            Err(Error::InvalidHeaderTransform(
+                "response:rename:X-A=X-B".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_the_timeout_of_the_route() {
+        let rule: RoutingRule = r#"path_prefix("/reports")=>*;request:add:X-Slow=1;timeout:120000"#
+            .parse()
+            .unwrap();
+
+        assert_eq!(rule.timeout, Some(Duration::from_secs(120)));
+        assert_eq!(rule.header_transforms.len(), 1);
+        assert_eq!(
+            r#"path_prefix("/")=>*"#.parse::<RoutingRule>().unwrap().timeout,
+            None
+        );
+        assert_eq!(
+            r#"path_prefix("/")=>*;timeout:0"#.parse::<RoutingRule>(),
+            Err(Error::InvalidTimeout("timeout:0".to_string()))
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_keeps_only_its_backends() {
+        let routing_rules = RoutingRules {
+            rules: vec![
+                r#"header("x-tier") == "gold"=>http://gold:8080"#.parse().unwrap(),
+                r#"path_prefix("/api")=>http://silver:8080|http://bronze:8080"#
+                    .parse()
+                    .unwrap(),
+            ],
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tier", HeaderValue::from_static("gold"));
+
+        assert_eq!(
+            routing_rules.excluded_servers(
+                &Method::GET,
+                &Uri::from_static("/api/orders"),
+                &headers,
+                &target_servers()
+            ),
+            vec!["http://silver:8080", "http://bronze:8080"]
+        );
+        assert_eq!(
+            routing_rules.excluded_servers(
+                &Method::GET,
+                &Uri::from_static("/api/orders"),
+                &HeaderMap::new(),
+                &target_servers()
+            ),
+            vec!["http://gold:8080"]
+        );
+        assert!(
+            routing_rules
+                .excluded_servers(
+                    &Method::GET,
+                    &Uri::from_static("/"),
+                    &HeaderMap::new(),
+                    &target_servers()
+                )
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use load_balancer::pools::pool::PoolDefinition;
+
+use crate::cli_arguments::CliArguments;
+
+/// Replaces the secrets given as files, e.g. `--consul-token-file`, with
+/// their content. Files other users can read are refused.
+pub(crate) fn load_secret_files(args: &mut CliArguments) -> Result<(), String> {
+    let secrets = [
+        (&args.consul_token_file, &mut args.consul_token),
+        (&args.upstream_proxy_file, &mut args.upstream_proxy),
+        (&args.state_store_url_file, &mut args.state_store_url),
+        (&args.health_webhook_url_file, &mut args.health_webhook_url),
+        (&args.experiment_salt_file, &mut args.experiment_salt),
+        (&args.admin_token_file, &mut args.admin_token),
+    ];
+
+    for (path, secret) in secrets {
+        if let Some(path) = path {
+            *secret = Some(read_secret(path)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// The content of the secret file at `path`, without the trailing newline
+/// editors and `echo` leave behind.
+fn read_secret(path: &Path) -> Result<String, String> {
+    if readable_by_others(path)? {
+        return Err(format!(
+            "Secret file {} is readable by other users, restrict it with chmod o-r",
+            path.display()
+        ));
+    }
+
+    let secret = std::fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Refuses the private keys other users can read, like the secret files
+/// are, unless `--allow-readable-private-keys` is given.
+pub(crate) fn check_private_keys(args: &CliArguments) -> Result<(), String> {
+    let exposed_keys = exposed_private_keys(args);
+
+    if args.allow_readable_private_keys || exposed_keys.is_empty() {
+        return Ok(());
+    }
+
+    let keys = exposed_keys
+        .iter()
+        .map(|key| key.display().to_string())
+        .collect::<Vec<_>>();
+
+    Err(format!(
+        "Private key {} readable by other users, restrict it with chmod o-r \
+        or pass --allow-readable-private-keys",
+        keys.join(", ")
+    ))
+}
+
+/// The private keys other users can read.
+pub(crate) fn exposed_private_keys(args: &CliArguments) -> Vec<PathBuf> {
+    let pool_keys = args
+        .pools
+        .iter()
+        .filter_map(|pool| pool.parse::<PoolDefinition>().ok())
+        .filter_map(|pool| pool.client_identity)
+        .map(|identity| identity.key);
+    let keys = args
+        .tls_key
+        .iter()
+        .chain(&args.upstream_client_key)
+        .chain(args.tls_sni_certs.iter().map(|(_, _, key)| key))
+        .cloned()
+        .chain(pool_keys);
+
+    keys.filter(|key| readable_by_others(key).unwrap_or(false))
+        .collect()
+}
+
+#[cfg(unix)]
+fn readable_by_others(path: &Path) -> Result<bool, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = std::fs::metadata(path)
+        .map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+
+    Ok(metadata.permissions().mode() & 0o004 != 0)
+}
+
+#[cfg(not(unix))]
+fn readable_by_others(_path: &Path) -> Result<bool, String> {
+    Ok(false)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    use clap::Parser;
+    use uuid::Uuid;
+
+    use crate::cli_arguments::CliArguments;
+    use crate::secrets::{check_private_keys, exposed_private_keys, load_secret_files};
+
+    struct SecretFile(PathBuf);
+
+    impl SecretFile {
+        fn new(content: &str, mode: u32) -> Self {
+            let path = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+            std::fs::write(&path, content).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for SecretFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn args(arguments: &[&str]) -> CliArguments {
+        CliArguments::parse_from(
+            [&["load-balancer", "-t", "http://localhost:9000"], arguments].concat(),
+        )
+    }
+
+    #[test]
+    fn loads_the_secret_files() {
+        let token = SecretFile::new("s3cr3t\n", 0o600);
+        let webhook = SecretFile::new("https://hooks.local/T0/B0/x", 0o640);
+        let mut args = args(&[
+            "--consul-token-file",
+            token.path(),
+            "--health-webhook-url-file",
+            webhook.path(),
+        ]);
+
+        load_secret_files(&mut args).unwrap();
+
+        assert_eq!(args.consul_token, Some("s3cr3t".to_string()));
+        assert_eq!(
+            args.health_webhook_url,
+            Some("https://hooks.local/T0/B0/x".to_string())
+        );
+        assert_eq!(args.state_store_url, None);
+    }
+
+    #[test]
+    fn refuses_secret_files_other_users_can_read() {
+        let token = SecretFile::new("s3cr3t", 0o644);
+        let mut args = args(&["--consul-token-file", token.path()]);
+
+        let error = load_secret_files(&mut args).unwrap_err();
+
+        assert!(error.contains("readable by other users"), "{}", error);
+        assert_eq!(args.consul_token, None);
+    }
+
+    #[test]
+    fn refuses_missing_secret_files() {
+        let mut args = args(&["--consul-token-file", "/nonexistent/token"]);
+
+        assert!(load_secret_files(&mut args).is_err());
+    }
+
+    #[test]
+    fn finds_the_private_keys_other_users_can_read() {
+        let certificate = SecretFile::new("certificate", 0o644);
+        let restricted = SecretFile::new("key", 0o600);
+        let exposed = SecretFile::new("key", 0o644);
+        let sni_certificate = format!("api.example.com={}:{}", certificate.path(), exposed.path());
+        let args = args(&[
+            "--tls-cert",
+            certificate.path(),
+            "--tls-key",
+            restricted.path(),
+            "--tls-sni-cert",
+            &sni_certificate,
+        ]);
+
+        assert_eq!(exposed_private_keys(&args), vec![exposed.0.clone()]);
+    }
+
+    #[test]
+    fn refuses_the_private_keys_other_users_can_read_unless_allowed() {
+        let certificate = SecretFile::new("certificate", 0o644);
+        let key = SecretFile::new("key", 0o644);
+        let tls = ["--tls-cert", certificate.path(), "--tls-key", key.path()];
+
+        let error = check_private_keys(&args(&tls)).unwrap_err();
+
+        assert!(error.contains(key.path()), "{}", error);
+        assert!(
+            check_private_keys(&args(
+                &[&tls[..], &["--allow-readable-private-keys"]].concat()
+            ))
+            .is_ok()
+        );
+    }
+}
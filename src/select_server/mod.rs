@@ -1,6 +1,10 @@
 pub mod error;
+pub mod priority_select_server;
+pub mod quarantine_select_server;
 pub mod random_select_server;
 pub mod request;
 pub mod response;
 pub mod round_robin_select_server;
+#[allow(clippy::module_inception)]
 pub mod select_server;
+pub mod weights;
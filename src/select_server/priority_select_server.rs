@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::select_server::{
+    error::Error, request::Request, response::Response, select_server::SelectServer,
+};
+
+/// Sends the requests only to the healthy servers of the lowest priority,
+/// e.g. the primary site among SRV targets, falling back to the next
+/// priority once none of them is left. Servers without a priority rank
+/// first.
+pub struct PrioritySelectServer {
+    inner: Arc<dyn SelectServer + Send + Sync>,
+    healthy_servers: Arc<RwLock<Vec<String>>>,
+    priorities: HashMap<String, u16>,
+}
+
+impl PrioritySelectServer {
+    pub fn new(
+        inner: Arc<dyn SelectServer + Send + Sync>,
+        healthy_servers: Arc<RwLock<Vec<String>>>,
+        priorities: HashMap<String, u16>,
+    ) -> PrioritySelectServer {
+        Self {
+            inner,
+            healthy_servers,
+            priorities,
+        }
+    }
+
+    fn priority(&self, server: &str) -> u16 {
+        self.priorities.get(server).copied().unwrap_or(0)
+    }
+
+    /// The healthy servers the request may go to but whose priority is
+    /// above the lowest one.
+    fn lower_priority_servers(&self, request: &Request) -> Result<Vec<String>, Error> {
+        let healthy_servers = self
+            .healthy_servers
+            .read()
+            .map_err(|_| Error::PoisonedRead)?;
+
+        let lowest = healthy_servers
+            .iter()
+            .filter(|server| request.allows(server))
+            .map(|server| self.priority(server))
+            .min();
+
+        Ok(healthy_servers
+            .iter()
+            .filter(|server| lowest.is_some_and(|lowest| self.priority(server) > lowest))
+            .cloned()
+            .collect())
+    }
+}
+
+impl SelectServer for PrioritySelectServer {
+    fn execute(&self, request: Request) -> Result<Response, Error> {
+        let lower_priority_servers = self.lower_priority_servers(&request)?;
+
+        let mut restricted = request;
+        restricted.excluded_servers.extend(lower_priority_servers);
+
+        self.inner.execute(restricted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use crate::select_server::{
+        priority_select_server::PrioritySelectServer, request::Request,
+        round_robin_select_server::RoundRobinSelectServer, select_server::SelectServer,
+    };
+
+    fn priority_select_server(healthy_servers: &Arc<RwLock<Vec<String>>>) -> PrioritySelectServer {
+        PrioritySelectServer::new(
+            Arc::new(RoundRobinSelectServer::new(Arc::clone(healthy_servers))),
+            Arc::clone(healthy_servers),
+            HashMap::from([
+                (String::from("primary-1"), 10),
+                (String::from("primary-2"), 10),
+                (String::from("backup"), 20),
+            ]),
+        )
+    }
+
+    fn selected(select_server: &PrioritySelectServer, request: &Request) -> Vec<String> {
+        let mut selected = (0..4)
+            .map(|_| select_server.execute(request.clone()).unwrap().server)
+            .collect::<Vec<_>>();
+        selected.sort();
+        selected.dedup();
+        selected
+    }
+
+    #[test]
+    fn only_the_lowest_priority_gets_requests() {
+        let healthy_servers = Arc::new(RwLock::new(vec![
+            String::from("backup"),
+            String::from("primary-1"),
+            String::from("primary-2"),
+        ]));
+        let select_server = priority_select_server(&healthy_servers);
+
+        assert_eq!(
+            selected(&select_server, &Request::default()),
+            vec!["primary-1", "primary-2"]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_next_priority() {
+        let healthy_servers = Arc::new(RwLock::new(vec![
+            String::from("backup"),
+            String::from("primary-1"),
+        ]));
+        let select_server = priority_select_server(&healthy_servers);
+        let request = Request {
+            excluded_servers: vec![String::from("primary-1")],
+        };
+
+        assert_eq!(selected(&select_server, &request), vec!["backup"]);
+
+        healthy_servers
+            .write()
+            .unwrap()
+            .retain(|server| server == "backup");
+
+        assert_eq!(
+            selected(&select_server, &Request::default()),
+            vec!["backup"]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::select_server::{
+    error::Error, request::Request, response::Response, select_server::SelectServer,
+};
+
+/// Keeps backends that join the pool after startup, e.g. added at runtime or
+/// discovered, in quarantine for an observation period: while quarantined
+/// they only receive a trickle of the traffic.
+pub struct QuarantineSelectServer {
+    inner: Arc<dyn SelectServer + Send + Sync>,
+    healthy_servers: Arc<RwLock<Vec<String>>>,
+    quarantined_until: Mutex<HashMap<String, Instant>>,
+    observation_period: Duration,
+    trickle_ratio: f64,
+}
+
+impl QuarantineSelectServer {
+    pub fn new(
+        inner: Arc<dyn SelectServer + Send + Sync>,
+        healthy_servers: Arc<RwLock<Vec<String>>>,
+        initial_servers: &[String],
+        observation_period: Duration,
+        trickle_ratio: f64,
+    ) -> QuarantineSelectServer {
+        let now = Instant::now();
+
+        Self {
+            inner,
+            healthy_servers,
+            quarantined_until: Mutex::new(
+                initial_servers
+                    .iter()
+                    .map(|server| (server.clone(), now))
+                    .collect(),
+            ),
+            observation_period,
+            trickle_ratio: trickle_ratio.clamp(0.0, 1.0),
+        }
+    }
+
+    fn quarantined_servers(&self) -> Result<(Vec<String>, Vec<String>), Error> {
+        let healthy_servers = self
+            .healthy_servers
+            .read()
+            .map_err(|_| Error::PoisonedRead)?;
+        let mut quarantined_until = self
+            .quarantined_until
+            .lock()
+            .map_err(|_| Error::PoisonedRead)?;
+
+        let now = Instant::now();
+        let (quarantined, admitted) = healthy_servers.iter().cloned().partition(|server| {
+            let until = *quarantined_until
+                .entry(server.clone())
+                .or_insert_with(|| now + self.observation_period);
+
+            until > now
+        });
+
+        Ok((quarantined, admitted))
+    }
+}
+
+impl SelectServer for QuarantineSelectServer {
+    fn execute(&self, request: Request) -> Result<Response, Error> {
+        let (quarantined, admitted) = self.quarantined_servers()?;
+
+        if quarantined.is_empty() {
+            return self.inner.execute(request);
+        }
+
+        let to_quarantined = rand::rng().random_bool(self.trickle_ratio);
+        let mut restricted = request.clone();
+        restricted.excluded_servers.extend(if to_quarantined {
+            admitted
+        } else {
+            quarantined
+        });
+
+        match self.inner.execute(restricted) {
+            Err(Error::NoOneIsAlive) => self.inner.execute(request),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+
+    use crate::select_server::{
+        quarantine_select_server::QuarantineSelectServer, request::Request,
+        round_robin_select_server::RoundRobinSelectServer, select_server::SelectServer,
+    };
+
+    fn quarantine_select_server(
+        healthy_servers: &Arc<RwLock<Vec<String>>>,
+        observation_period: Duration,
+        trickle_ratio: f64,
+    ) -> QuarantineSelectServer {
+        QuarantineSelectServer::new(
+            Arc::new(RoundRobinSelectServer::new(Arc::clone(healthy_servers))),
+            Arc::clone(healthy_servers),
+            &[String::from("server1")],
+            observation_period,
+            trickle_ratio,
+        )
+    }
+
+    #[test]
+    fn initial_servers_are_never_quarantined() {
+        let healthy_servers = Arc::new(RwLock::new(vec![String::from("server1")]));
+        let select_server =
+            quarantine_select_server(&healthy_servers, Duration::from_secs(60), 0.0);
+
+        for _ in 0..5 {
+            assert_eq!(
+                select_server.execute(Request::default()).unwrap().server,
+                "server1"
+            );
+        }
+    }
+
+    #[test]
+    fn new_servers_only_get_the_trickle_while_quarantined() {
+        let healthy_servers = Arc::new(RwLock::new(vec![String::from("server1")]));
+        let select_server =
+            quarantine_select_server(&healthy_servers, Duration::from_secs(60), 0.0);
+
+        healthy_servers
+            .write()
+            .unwrap()
+            .push(String::from("server2"));
+
+        for _ in 0..5 {
+            assert_eq!(
+                select_server.execute(Request::default()).unwrap().server,
+                "server1"
+            );
+        }
+
+        let select_server =
+            quarantine_select_server(&healthy_servers, Duration::from_secs(60), 1.0);
+
+        for _ in 0..5 {
+            assert_eq!(
+                select_server.execute(Request::default()).unwrap().server,
+                "server2"
+            );
+        }
+    }
+
+    #[test]
+    fn quarantined_servers_are_used_when_nothing_else_is_healthy() {
+        let healthy_servers = Arc::new(RwLock::new(vec![String::from("server2")]));
+        let select_server =
+            quarantine_select_server(&healthy_servers, Duration::from_secs(60), 0.0);
+
+        assert_eq!(
+            select_server.execute(Request::default()).unwrap().server,
+            "server2"
+        );
+    }
+
+    #[test]
+    fn new_servers_are_admitted_after_the_observation_period() {
+        let healthy_servers = Arc::new(RwLock::new(vec![
+            String::from("server1"),
+            String::from("server2"),
+        ]));
+        let select_server = quarantine_select_server(&healthy_servers, Duration::ZERO, 0.0);
+
+        let mut selected = (0..4)
+            .map(|_| select_server.execute(Request::default()).unwrap().server)
+            .collect::<Vec<_>>();
+        selected.sort();
+        selected.dedup();
+
+        assert_eq!(selected, vec!["server1", "server2"]);
+    }
+}
@@ -4,52 +4,82 @@ use rand::Rng;
 
 use crate::select_server::{
     error::Error, request::Request, response::Response, select_server::SelectServer,
+    weights::Weights,
 };
 
 pub struct RandomSelectServer {
     target_servers: Arc<RwLock<Vec<String>>>,
+    weights: Arc<RwLock<Weights>>,
 }
 
 impl RandomSelectServer {
     pub fn new(target_servers: Arc<RwLock<Vec<String>>>) -> RandomSelectServer {
-        Self { target_servers }
+        Self {
+            target_servers,
+            weights: Arc::new(RwLock::new(Weights::default())),
+        }
+    }
+
+    /// Picks each server with a probability proportional to its weight.
+    pub fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = Arc::new(RwLock::new(weights));
+        self
+    }
+
+    /// Like `with_weights`, with weights that can be replaced while in
+    /// use, e.g. by a reload.
+    pub fn with_shared_weights(mut self, weights: Arc<RwLock<Weights>>) -> Self {
+        self.weights = weights;
+        self
     }
 }
 
 impl SelectServer for RandomSelectServer {
-    fn execute(&self, _request: Request) -> Result<Response, Error> {
+    fn execute(&self, request: Request) -> Result<Response, Error> {
         let target_servers = self
             .target_servers
             .read()
             .map_err(|_| Error::PoisonedRead)?;
+        let weights = self.weights.read().map_err(|_| Error::PoisonedRead)?;
 
-        if target_servers.is_empty() {
+        let candidates = || {
+            target_servers
+                .iter()
+                .filter(|server| request.allows(server))
+        };
+
+        let total = weights.total(candidates());
+
+        if total == 0 {
             return Err(Error::NoOneIsAlive);
         }
 
-        let len = target_servers.len();
-        let random_index = rand::rng().random_range(0..len);
+        let random_index = rand::rng().random_range(0..total);
 
         Ok(Response {
-            server: target_servers[random_index].clone(),
+            server: weights.nth(candidates(), random_index).unwrap().clone(),
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::{Arc, RwLock};
 
     use crate::select_server::{
         error::Error, random_select_server::RandomSelectServer, request::Request,
-        select_server::SelectServer,
+        select_server::SelectServer, weights::Weights,
     };
 
     #[test]
     fn should_return_an_error_if_empty_targets() {
         let random_select_server = RandomSelectServer::new(Arc::new(RwLock::new(Vec::new())));
 
-        let error = random_select_server.execute(Request {}).err().unwrap();
+        let error = random_select_server
+            .execute(Request::default())
+            .err()
+            .unwrap();
 
         assert_eq!(error, Error::NoOneIsAlive)
     }
@@ -64,16 +94,55 @@ mod tests {
             server2.clone(),
         ]))));
 
-        let result = random_select_server.execute(Request {});
+        let result = random_select_server.execute(Request::default());
         let selected = result.unwrap().server;
         assert!(selected == server1 || selected == server2);
 
-        let result = random_select_server.execute(Request {});
+        let result = random_select_server.execute(Request::default());
         let selected = result.unwrap().server;
         assert!(selected == server1 || selected == server2);
 
-        let result = random_select_server.execute(Request {});
+        let result = random_select_server.execute(Request::default());
         let selected = result.unwrap().server;
         assert!(selected == server1 || selected == server2);
     }
+
+    #[test]
+    fn should_favour_the_heavier_targets() {
+        let random_select_server = RandomSelectServer::new(Arc::new(RwLock::new(Vec::from([
+            String::from("server1"),
+            String::from("server2"),
+        ]))))
+        .with_weights(Weights(HashMap::from([(String::from("server1"), 1000)])));
+
+        let heavier = (0..1000)
+            .filter(|_| {
+                random_select_server
+                    .execute(Request::default())
+                    .unwrap()
+                    .server
+                    == "server1"
+            })
+            .count();
+
+        assert!(heavier > 950, "{}", heavier);
+    }
+
+    #[test]
+    fn should_skip_the_excluded_targets() {
+        let random_select_server = RandomSelectServer::new(Arc::new(RwLock::new(Vec::from([
+            String::from("server1"),
+            String::from("server2"),
+        ]))));
+
+        let request = Request {
+            excluded_servers: vec![String::from("server2")],
+        };
+
+        for _ in 0..10 {
+            let result = random_select_server.execute(request.clone()).unwrap();
+
+            assert_eq!(result.server, "server1");
+        }
+    }
 }
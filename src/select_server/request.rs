@@ -1 +1,14 @@
-pub struct Request {}
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    /// Servers that must not be picked, e.g. because they just failed.
+    pub excluded_servers: Vec<String>,
+}
+
+impl Request {
+    pub fn allows(&self, server: &str) -> bool {
+        !self
+            .excluded_servers
+            .iter()
+            .any(|excluded| excluded == server)
+    }
+}
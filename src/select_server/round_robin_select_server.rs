@@ -5,11 +5,13 @@ use std::sync::{
 
 use crate::select_server::{
     error::Error, request::Request, response::Response, select_server::SelectServer,
+    weights::Weights,
 };
 
 pub struct RoundRobinSelectServer {
     target_servers: Arc<RwLock<Vec<String>>>,
     current_server_index: AtomicUsize,
+    weights: Arc<RwLock<Weights>>,
 }
 
 impl RoundRobinSelectServer {
@@ -17,23 +19,44 @@ impl RoundRobinSelectServer {
         Self {
             target_servers,
             current_server_index: AtomicUsize::new(0),
+            weights: Arc::new(RwLock::new(Weights::default())),
         }
     }
+
+    /// Picks each server as many times in a row as its weight.
+    pub fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = Arc::new(RwLock::new(weights));
+        self
+    }
+
+    /// Like `with_weights`, with weights that can be replaced while in
+    /// use, e.g. by a reload.
+    pub fn with_shared_weights(mut self, weights: Arc<RwLock<Weights>>) -> Self {
+        self.weights = weights;
+        self
+    }
 }
 
 impl SelectServer for RoundRobinSelectServer {
-    fn execute(&self, _request: Request) -> Result<Response, Error> {
+    fn execute(&self, request: Request) -> Result<Response, Error> {
         let target_servers = match self.target_servers.read() {
             Ok(servers) => servers,
             Err(_) => return Err(Error::PoisonedRead),
         };
+        let weights = self.weights.read().map_err(|_| Error::PoisonedRead)?;
+
+        let candidates = || {
+            target_servers
+                .iter()
+                .filter(|server| request.allows(server))
+        };
+
+        let total = weights.total(candidates());
 
-        if target_servers.is_empty() {
+        if total == 0 {
             return Err(Error::NoOneIsAlive);
         }
 
-        let len = target_servers.len();
-
         let index = self
             .current_server_index
             .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
@@ -41,20 +64,21 @@ impl SelectServer for RoundRobinSelectServer {
             })
             .unwrap();
 
-        let index = index % len;
+        let index = index as u64 % total;
         Ok(Response {
-            server: target_servers[index].clone(),
+            server: weights.nth(candidates(), index).unwrap().clone(),
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::{Arc, RwLock};
 
     use crate::select_server::{
         error::Error, request::Request, round_robin_select_server::RoundRobinSelectServer,
-        select_server::SelectServer,
+        select_server::SelectServer, weights::Weights,
     };
 
     #[test]
@@ -62,7 +86,10 @@ mod tests {
         let round_robin_select_server =
             RoundRobinSelectServer::new(Arc::new(RwLock::new(Vec::new())));
 
-        let error = round_robin_select_server.execute(Request {}).err().unwrap();
+        let error = round_robin_select_server
+            .execute(Request::default())
+            .err()
+            .unwrap();
 
         assert_eq!(error, Error::NoOneIsAlive)
     }
@@ -79,31 +106,111 @@ mod tests {
             ]))));
 
         let mut result = round_robin_select_server
-            .execute(Request {})
+            .execute(Request::default())
             .unwrap()
             .server;
 
         assert_eq!(result, server1);
 
         result = round_robin_select_server
-            .execute(Request {})
+            .execute(Request::default())
             .unwrap()
             .server;
 
         assert_eq!(result, server2);
 
         result = round_robin_select_server
-            .execute(Request {})
+            .execute(Request::default())
             .unwrap()
             .server;
 
         assert_eq!(result, server1);
 
         result = round_robin_select_server
-            .execute(Request {})
+            .execute(Request::default())
             .unwrap()
             .server;
 
         assert_eq!(result, server2);
     }
+
+    #[test]
+    fn should_return_the_targets_as_many_times_as_their_weight() {
+        let round_robin_select_server =
+            RoundRobinSelectServer::new(Arc::new(RwLock::new(Vec::from([
+                String::from("server1"),
+                String::from("server2"),
+            ]))))
+            .with_weights(Weights(HashMap::from([(String::from("server1"), 3)])));
+
+        let servers = (0..8)
+            .map(|_| {
+                round_robin_select_server
+                    .execute(Request::default())
+                    .unwrap()
+                    .server
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            servers,
+            [
+                "server1", "server1", "server1", "server2", "server1", "server1", "server1",
+                "server2"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_follow_the_shared_weights_once_replaced() {
+        let weights = Arc::new(RwLock::new(Weights::default()));
+        let round_robin_select_server =
+            RoundRobinSelectServer::new(Arc::new(RwLock::new(Vec::from([
+                String::from("server1"),
+                String::from("server2"),
+            ]))))
+            .with_shared_weights(Arc::clone(&weights));
+
+        *weights.write().unwrap() = Weights(HashMap::from([(String::from("server2"), 0)]));
+
+        for _ in 0..3 {
+            let result = round_robin_select_server
+                .execute(Request::default())
+                .unwrap()
+                .server;
+
+            assert_eq!(result, "server1");
+        }
+    }
+
+    #[test]
+    fn should_skip_the_excluded_targets() {
+        let round_robin_select_server =
+            RoundRobinSelectServer::new(Arc::new(RwLock::new(Vec::from([
+                String::from("server1"),
+                String::from("server2"),
+            ]))));
+
+        let request = Request {
+            excluded_servers: vec![String::from("server1")],
+        };
+
+        for _ in 0..3 {
+            let result = round_robin_select_server
+                .execute(request.clone())
+                .unwrap()
+                .server;
+
+            assert_eq!(result, "server2");
+        }
+
+        let request = Request {
+            excluded_servers: vec![String::from("server1"), String::from("server2")],
+        };
+
+        assert_eq!(
+            round_robin_select_server.execute(request).err().unwrap(),
+            Error::NoOneIsAlive
+        );
+    }
 }
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+/// Relative shares of the requests the servers get, `1` for those not
+/// listed. A server of weight 3 is picked three times as often as one of
+/// weight 1.
+#[derive(Debug, Clone, Default)]
+pub struct Weights(pub HashMap<String, u32>);
+
+impl Weights {
+    pub fn of(&self, server: &str) -> u64 {
+        self.0.get(server).map_or(1, |weight| u64::from(*weight))
+    }
+
+    pub fn total<'a>(&self, servers: impl Iterator<Item = &'a String>) -> u64 {
+        servers.map(|server| self.of(server)).sum()
+    }
+
+    /// The server at `position` when each is repeated as many times as its
+    /// weight, `position` being below their `total`.
+    pub fn nth<'a>(
+        &self,
+        mut servers: impl Iterator<Item = &'a String>,
+        mut position: u64,
+    ) -> Option<&'a String> {
+        servers.find(|server| match position.checked_sub(self.of(server)) {
+            Some(rest) => {
+                position = rest;
+                false
+            }
+            None => true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::select_server::weights::Weights;
+
+    #[test]
+    fn repeats_each_server_by_its_weight() {
+        let weights = Weights(HashMap::from([("a".to_string(), 3)]));
+        let servers = ["a".to_string(), "b".to_string()];
+
+        assert_eq!(weights.total(servers.iter()), 4);
+        assert_eq!(
+            (0..5)
+                .map(|position| weights.nth(servers.iter(), position).cloned())
+                .collect::<Vec<_>>(),
+            vec![
+                Some("a".to_string()),
+                Some("a".to_string()),
+                Some("a".to_string()),
+                Some("b".to_string()),
+                None,
+            ]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use hickory_resolver::TokioResolver;
+
+use crate::srv_discovery::srv_resolver::{SrvRecord, SrvResolver};
+
+/// Resolves the SRV records with the name servers of the system, caching
+/// the answers for their TTL.
+pub struct HickorySrvResolver(TokioResolver);
+
+impl HickorySrvResolver {
+    pub fn from_system_config() -> Result<Self, String> {
+        TokioResolver::builder_tokio()
+            .map(|builder| HickorySrvResolver(builder.build()))
+            .map_err(|error| format!("can't read the DNS configuration: {}", error))
+    }
+}
+
+#[async_trait]
+impl SrvResolver for HickorySrvResolver {
+    async fn resolve(&self, name: &str) -> Result<Vec<SrvRecord>, String> {
+        let lookup = self
+            .0
+            .srv_lookup(name)
+            .await
+            .map_err(|error| error.to_string())?;
+
+        Ok(lookup
+            .iter()
+            .map(|srv| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                port: srv.port(),
+                target: srv.target().to_utf8(),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,4 @@
+pub mod hickory_srv_resolver;
+#[allow(clippy::module_inception)]
+pub mod srv_discovery;
+pub mod srv_resolver;
@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::srv_discovery::srv_resolver::SrvResolver;
+
+/// A backend found in an SRV record. Only the targets of the lowest
+/// priority among the healthy ones get requests, in proportion to their
+/// weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SrvTarget {
+    pub url: String,
+    pub priority: u16,
+    pub weight: u32,
+}
+
+/// Keeps the backends of a pool in sync with an SRV record, e.g.
+/// `_http._tcp.api.example.com`, resolved again every `polling_interval`.
+/// The targets are reached over HTTPS for `_https` services and HTTP
+/// otherwise, and a failed resolution keeps the previous ones.
+pub struct SrvDiscovery {
+    resolver: Arc<dyn SrvResolver>,
+    name: String,
+    polling_interval: Duration,
+    targets: watch::Sender<Vec<SrvTarget>>,
+}
+
+impl SrvDiscovery {
+    pub fn new(resolver: Arc<dyn SrvResolver>, name: String, polling_interval: Duration) -> Self {
+        Self {
+            resolver,
+            name,
+            polling_interval,
+            targets: watch::Sender::new(Vec::new()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The targets found by the last successful resolution, the current
+    /// ones being marked as seen.
+    pub fn subscribe(&self) -> watch::Receiver<Vec<SrvTarget>> {
+        self.targets.subscribe()
+    }
+
+    /// Resolves the record, publishing and returning its targets sorted.
+    pub async fn discover(&self) -> Result<Vec<SrvTarget>, String> {
+        let scheme = match self.name.starts_with("_https.") {
+            true => "https",
+            false => "http",
+        };
+
+        let mut targets = self
+            .resolver
+            .resolve(&self.name)
+            .await?
+            .into_iter()
+            // A target of "." means that the service isn't available there.
+            .filter(|record| !record.target.trim_end_matches('.').is_empty())
+            .map(|record| SrvTarget {
+                url: format!(
+                    "{}://{}:{}",
+                    scheme,
+                    record.target.trim_end_matches('.').to_ascii_lowercase(),
+                    record.port
+                ),
+                priority: record.priority,
+                // Weight 0 is for targets to pick when the others can't be,
+                // which the priorities already cover.
+                weight: u32::from(record.weight.max(1)),
+            })
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| a.url.cmp(&b.url));
+        targets.dedup_by(|a, b| a.url == b.url);
+
+        self.targets.send_if_modified(|current| {
+            let changed = *current != targets;
+            if changed {
+                info!("SRV record {} now has {} targets", self.name, targets.len());
+                current.clone_from(&targets);
+            }
+            changed
+        });
+
+        Ok(targets)
+    }
+
+    /// Keeps resolving the record forever, publishing the targets whenever
+    /// they change.
+    pub async fn execute(&self) {
+        info!(
+            "Resolving the SRV record {} every {:?}",
+            self.name, self.polling_interval
+        );
+
+        let mut interval = time::interval(self.polling_interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.discover().await {
+                warn!("Keeping the targets of SRV record {}: {}", self.name, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::srv_discovery::srv_discovery::{SrvDiscovery, SrvTarget};
+    use crate::srv_discovery::srv_resolver::{MockSrvResolver, SrvRecord};
+
+    fn record(priority: u16, weight: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port: 8080,
+            target: target.to_string(),
+        }
+    }
+
+    fn discovery(name: &str, records: Result<Vec<SrvRecord>, String>) -> SrvDiscovery {
+        let mut resolver = MockSrvResolver::new();
+        let expected = name.to_string();
+        resolver
+            .expect_resolve()
+            .withf(move |name| name == expected)
+            .times(1)
+            .returning(move |_| records.clone());
+
+        SrvDiscovery::new(
+            Arc::new(resolver),
+            name.to_string(),
+            Duration::from_secs(30),
+        )
+    }
+
+    #[tokio::test]
+    async fn derives_the_targets_from_the_records() {
+        let discovery = discovery(
+            "_http._tcp.api.example.com",
+            Ok(vec![
+                record(20, 0, "API-3.example.com."),
+                record(10, 60, "api-1.example.com."),
+                record(10, 40, "api-2.example.com."),
+                record(10, 40, "."),
+            ]),
+        );
+        let mut targets = discovery.subscribe();
+
+        let discovered = discovery.discover().await.unwrap();
+
+        assert_eq!(
+            discovered,
+            vec![
+                SrvTarget {
+                    url: "http://api-1.example.com:8080".to_string(),
+                    priority: 10,
+                    weight: 60,
+                },
+                SrvTarget {
+                    url: "http://api-2.example.com:8080".to_string(),
+                    priority: 10,
+                    weight: 40,
+                },
+                SrvTarget {
+                    url: "http://api-3.example.com:8080".to_string(),
+                    priority: 20,
+                    weight: 1,
+                },
+            ]
+        );
+        assert!(targets.has_changed().unwrap());
+        assert_eq!(*targets.borrow_and_update(), discovered);
+    }
+
+    #[tokio::test]
+    async fn reaches_https_services_over_https() {
+        let discovery = discovery(
+            "_https._tcp.api.example.com",
+            Ok(vec![record(10, 10, "api-1.example.com.")]),
+        );
+
+        assert_eq!(
+            discovery.discover().await.unwrap()[0].url,
+            "https://api-1.example.com:8080"
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_the_targets_when_the_resolution_fails() {
+        let discovery = discovery("_http._tcp.api.example.com", Err("NXDOMAIN".to_string()));
+        let targets = discovery.subscribe();
+
+        assert!(discovery.discover().await.is_err());
+        assert!(!targets.has_changed().unwrap());
+    }
+}
@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+
+/// A target of an SRV record, e.g. `10 60 8080 api-1.example.com.`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// Resolves the SRV records of a name.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait SrvResolver: Send + Sync {
+    async fn resolve(&self, name: &str) -> Result<Vec<SrvRecord>, String>;
+}
@@ -0,0 +1,8 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("State storage error: {0}")]
+    Storage(String),
+
+    #[error("Value of {0} is not a counter")]
+    NotACounter(String),
+}
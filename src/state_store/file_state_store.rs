@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use crate::state_store::{error::Error, memory_state_store::Entries, state_store::StateStore};
+
+/// Keeps the entries in a JSON file, so the state survives restarts and is
+/// shared by the instances pointed at the same file, e.g. on a shared
+/// volume honouring file locks. Every change re-reads the file while holding
+/// an exclusive lock on `<path>.lock`, then replaces it atomically through a
+/// temporary file of its own.
+pub struct FileStateStore {
+    path: PathBuf,
+}
+
+impl FileStateStore {
+    pub async fn open(path: PathBuf) -> Result<Self, Error> {
+        read(&path).await?;
+
+        Ok(Self { path })
+    }
+
+    /// Applies `change` to the entries of the file, under the lock.
+    async fn update<T: Send + 'static>(
+        &self,
+        change: impl FnOnce(&mut Entries) -> Result<T, Error> + Send + 'static,
+    ) -> Result<T, Error> {
+        let path = self.path.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let lock = File::create(path.with_extension("lock")).map_err(storage_error)?;
+            lock.lock().map_err(storage_error)?;
+
+            let mut entries = read_blocking(&path)?;
+            let result = change(&mut entries)?;
+            entries.evict_expired();
+            write_blocking(&path, &entries)?;
+
+            Ok(result)
+        })
+        .await
+        .map_err(storage_error)?
+    }
+}
+
+async fn read(path: &Path) -> Result<Entries, Error> {
+    match tokio::fs::read(path).await {
+        Ok(content) => parse(&content),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(Entries::default()),
+        Err(error) => Err(storage_error(error)),
+    }
+}
+
+fn read_blocking(path: &Path) -> Result<Entries, Error> {
+    match std::fs::read(path) {
+        Ok(content) => parse(&content),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(Entries::default()),
+        Err(error) => Err(storage_error(error)),
+    }
+}
+
+fn parse(content: &[u8]) -> Result<Entries, Error> {
+    serde_json::from_slice(content).map_err(storage_error)
+}
+
+/// Writes a temporary file no other instance writes to, then renames it
+/// over the file so that readers never see half of it.
+fn write_blocking(path: &Path, entries: &Entries) -> Result<(), Error> {
+    let content = serde_json::to_vec(entries).map_err(storage_error)?;
+    let temporary_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+
+    std::fs::write(&temporary_path, content).map_err(storage_error)?;
+    std::fs::rename(&temporary_path, path).map_err(storage_error)
+}
+
+fn storage_error(error: impl ToString) -> Error {
+    Error::Storage(error.to_string())
+}
+
+#[async_trait]
+impl StateStore for FileStateStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, Error> {
+        Ok(read(&self.path).await?.get(key))
+    }
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Option<Duration>) -> Result<(), Error> {
+        let key = key.to_string();
+
+        self.update(move |entries| {
+            entries.set(&key, value, ttl);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let key = key.to_string();
+
+        self.update(move |entries| {
+            entries.delete(&key);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn increment(&self, key: &str, delta: i64, ttl: Option<Duration>) -> Result<i64, Error> {
+        let key = key.to_string();
+
+        self.update(move |entries| entries.increment(&key, delta, ttl))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use uuid::Uuid;
+
+    use crate::state_store::{file_state_store::FileStateStore, state_store::StateStore};
+
+    fn store_path() -> PathBuf {
+        std::env::temp_dir().join(format!("wakanda-lb-{}.state.json", Uuid::new_v4()))
+    }
+
+    fn remove(path: &Path) {
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(path.with_extension("lock")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn state_survives_reopening_the_store() {
+        let path = store_path();
+
+        let store = FileStateStore::open(path.clone()).await.unwrap();
+        store.set("key", Bytes::from("value"), None).await.unwrap();
+        store.increment("hits", 5, None).await.unwrap();
+        drop(store);
+
+        let store = FileStateStore::open(path.clone()).await.unwrap();
+        assert_eq!(store.get("key").await.unwrap(), Some(Bytes::from("value")));
+        assert_eq!(store.increment("hits", 1, None).await.unwrap(), 6);
+
+        remove(&path);
+    }
+
+    #[tokio::test]
+    async fn instances_sharing_the_file_see_each_others_changes() {
+        let path = store_path();
+
+        let first = Arc::new(FileStateStore::open(path.clone()).await.unwrap());
+        let second = Arc::new(FileStateStore::open(path.clone()).await.unwrap());
+
+        first.set("key", Bytes::from("value"), None).await.unwrap();
+        assert_eq!(second.get("key").await.unwrap(), Some(Bytes::from("value")));
+
+        let increments = (0..20).map(|index| {
+            let store = Arc::clone(if index % 2 == 0 { &first } else { &second });
+            tokio::spawn(async move { store.increment("hits", 1, None).await.unwrap() })
+        });
+        futures::future::join_all(increments).await;
+
+        assert_eq!(first.increment("hits", 0, None).await.unwrap(), 20);
+
+        second.delete("key").await.unwrap();
+        assert_eq!(first.get("key").await.unwrap(), None);
+
+        remove(&path);
+    }
+
+    #[tokio::test]
+    async fn corrupted_file_is_an_error() {
+        let path = store_path();
+        std::fs::write(&path, "not json").unwrap();
+
+        assert!(FileStateStore::open(path.clone()).await.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
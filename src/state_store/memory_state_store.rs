@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::state_store::{error::Error, state_store::StateStore};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    value: Vec<u8>,
+    expires_at_millis: Option<u64>,
+}
+
+/// Entries with their expiry, shared by the in-process stores.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Entries(HashMap<String, Entry>);
+
+impl Entries {
+    fn now_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    fn expires_at_millis(ttl: Option<Duration>) -> Option<u64> {
+        ttl.map(|ttl| Self::now_millis() + ttl.as_millis() as u64)
+    }
+
+    fn live(&self, key: &str) -> Option<&Entry> {
+        self.0.get(key).filter(|entry| {
+            entry
+                .expires_at_millis
+                .is_none_or(|expires_at_millis| expires_at_millis > Self::now_millis())
+        })
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
+        self.live(key)
+            .map(|entry| Bytes::copy_from_slice(&entry.value))
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: Bytes, ttl: Option<Duration>) {
+        self.0.insert(
+            key.to_string(),
+            Entry {
+                value: value.to_vec(),
+                expires_at_millis: Self::expires_at_millis(ttl),
+            },
+        );
+    }
+
+    pub(crate) fn delete(&mut self, key: &str) {
+        self.0.remove(key);
+    }
+
+    pub(crate) fn increment(
+        &mut self,
+        key: &str,
+        delta: i64,
+        ttl: Option<Duration>,
+    ) -> Result<i64, Error> {
+        let (current, expires_at_millis) = match self.live(key) {
+            Some(entry) => {
+                let current = std::str::from_utf8(&entry.value)
+                    .ok()
+                    .and_then(|value| value.parse::<i64>().ok())
+                    .ok_or_else(|| Error::NotACounter(key.to_string()))?;
+
+                (current, entry.expires_at_millis)
+            }
+            None => (0, Self::expires_at_millis(ttl)),
+        };
+
+        let value = current.saturating_add(delta);
+
+        self.0.insert(
+            key.to_string(),
+            Entry {
+                value: value.to_string().into_bytes(),
+                expires_at_millis,
+            },
+        );
+
+        Ok(value)
+    }
+
+    pub(crate) fn evict_expired(&mut self) {
+        let now_millis = Self::now_millis();
+
+        self.0.retain(|_, entry| {
+            entry
+                .expires_at_millis
+                .is_none_or(|expires_at_millis| expires_at_millis > now_millis)
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStateStore {
+    entries: Mutex<Entries>,
+}
+
+impl MemoryStateStore {
+    fn entries(&self) -> Result<std::sync::MutexGuard<'_, Entries>, Error> {
+        self.entries
+            .lock()
+            .map_err(|error| Error::Storage(error.to_string()))
+    }
+}
+
+#[async_trait]
+impl StateStore for MemoryStateStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, Error> {
+        Ok(self.entries()?.get(key))
+    }
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Option<Duration>) -> Result<(), Error> {
+        let mut entries = self.entries()?;
+        entries.evict_expired();
+        entries.set(key, value, ttl);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.entries()?.delete(key);
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str, delta: i64, ttl: Option<Duration>) -> Result<i64, Error> {
+        let mut entries = self.entries()?;
+        entries.evict_expired();
+        entries.increment(key, delta, ttl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use crate::state_store::{
+        error::Error, memory_state_store::MemoryStateStore, state_store::StateStore,
+    };
+
+    #[tokio::test]
+    async fn stores_and_deletes_values() {
+        let store = MemoryStateStore::default();
+
+        assert_eq!(store.get("key").await.unwrap(), None);
+
+        store.set("key", Bytes::from("value"), None).await.unwrap();
+        assert_eq!(store.get("key").await.unwrap(), Some(Bytes::from("value")));
+
+        store.delete("key").await.unwrap();
+        assert_eq!(store.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn values_expire_after_their_ttl() {
+        let store = MemoryStateStore::default();
+
+        store
+            .set("key", Bytes::from("value"), Some(Duration::from_millis(1)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        assert_eq!(store.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn increments_counters() {
+        let store = MemoryStateStore::default();
+
+        assert_eq!(store.increment("hits", 1, None).await.unwrap(), 1);
+        assert_eq!(store.increment("hits", 2, None).await.unwrap(), 3);
+        assert_eq!(store.get("hits").await.unwrap(), Some(Bytes::from("3")));
+
+        store.set("key", Bytes::from("value"), None).await.unwrap();
+        assert!(matches!(
+            store.increment("key", 1, None).await,
+            Err(Error::NotACounter(_))
+        ));
+    }
+}
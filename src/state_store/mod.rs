@@ -0,0 +1,7 @@
+pub mod error;
+pub mod file_state_store;
+pub mod memory_state_store;
+#[cfg(feature = "redis")]
+pub mod redis_state_store;
+#[allow(clippy::module_inception)]
+pub mod state_store;
@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use std::time::Duration;
+
+use crate::state_store::{error::Error, state_store::StateStore};
+
+/// Stores the entries in Redis so that every replica shares the same state.
+pub struct RedisStateStore {
+    connection: ConnectionManager,
+    key_prefix: String,
+}
+
+impl RedisStateStore {
+    pub async fn connect(url: &str, key_prefix: String) -> Result<Self, Error> {
+        let client = redis::Client::open(url)?;
+        let connection = ConnectionManager::new(client).await?;
+
+        Ok(Self {
+            connection,
+            key_prefix,
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+}
+
+impl From<redis::RedisError> for Error {
+    fn from(error: redis::RedisError) -> Self {
+        Error::Storage(error.to_string())
+    }
+}
+
+#[async_trait]
+impl StateStore for RedisStateStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, Error> {
+        let value: Option<Vec<u8>> = self.connection.clone().get(self.key(key)).await?;
+
+        Ok(value.map(Bytes::from))
+    }
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Option<Duration>) -> Result<(), Error> {
+        let mut connection = self.connection.clone();
+
+        match ttl {
+            Some(ttl) => {
+                let _: () = redis::cmd("SET")
+                    .arg(self.key(key))
+                    .arg(value.as_ref())
+                    .arg("PX")
+                    .arg(ttl.as_millis() as u64)
+                    .query_async(&mut connection)
+                    .await?;
+            }
+            None => {
+                let _: () = connection.set(self.key(key), value.as_ref()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let _: () = self.connection.clone().del(self.key(key)).await?;
+
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str, delta: i64, ttl: Option<Duration>) -> Result<i64, Error> {
+        let mut connection = self.connection.clone();
+        let key = self.key(key);
+
+        let value: i64 = connection.incr(&key, delta).await?;
+
+        if let Some(ttl) = ttl.filter(|_| value == delta) {
+            let _: () = connection.pexpire(&key, ttl.as_millis() as i64).await?;
+        }
+
+        Ok(value)
+    }
+}
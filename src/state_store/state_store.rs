@@ -0,0 +1,22 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::time::Duration;
+
+use crate::state_store::error::Error;
+
+/// Key/value storage shared by the stateful features, so each deployment can
+/// pick the durability and consistency it needs. Entries written with a TTL
+/// disappear once it elapses.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, Error>;
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Option<Duration>) -> Result<(), Error>;
+
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+
+    /// Atomically adds `delta` to the counter stored at `key`, creating it
+    /// with the given TTL when missing, and returns the new value.
+    async fn increment(&self, key: &str, delta: i64, ttl: Option<Duration>) -> Result<i64, Error>;
+}
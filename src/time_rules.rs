@@ -0,0 +1,370 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::{HeaderMap, HeaderName};
+
+use crate::target_url::normalize_target_url;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// The current time, a fixed one in tests.
+#[cfg_attr(test, mockall::automock)]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Tells the minute of the day in the timezone given by
+/// `utc_offset_minutes`.
+#[derive(Clone)]
+pub struct DayClock {
+    pub clock: Arc<dyn Clock>,
+    pub utc_offset_minutes: i32,
+}
+
+impl DayClock {
+    pub fn minute_of_day(&self) -> u16 {
+        minute_of_day(self.clock.now(), self.utc_offset_minutes)
+    }
+}
+
+impl Default for DayClock {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+/// The daily window from `start_minute` (inclusive) to `end_minute`
+/// (exclusive), written as `HH:MM-HH:MM`. A window ending before it starts
+/// spans midnight, one ending when it starts spans the whole day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWindow {
+    pub start_minute: u16,
+    pub end_minute: u16,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        match self.start_minute.cmp(&self.end_minute) {
+            Ordering::Less => (self.start_minute..self.end_minute).contains(&minute_of_day),
+            Ordering::Greater => {
+                minute_of_day >= self.start_minute || minute_of_day < self.end_minute
+            }
+            // e.g. 00:00-24:00
+            Ordering::Equal => true,
+        }
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| format!("expected HH:MM-HH:MM, got {}", value))?;
+
+        Ok(TimeWindow {
+            start_minute: parse_minute_of_day(start.trim())?,
+            end_minute: parse_minute_of_day(end.trim())? % (MINUTES_PER_DAY as u16),
+        })
+    }
+}
+
+/// Sends the requests carrying `header: value` to `backends` only, during
+/// the daily `window`.
+///
+/// Written as `HEADER=VALUE@HH:MM-HH:MM>BACKEND|BACKEND`, e.g.
+/// `x-traffic-class=batch@00:00-06:00>http://cheap:8080`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeRule {
+    pub header: HeaderName,
+    pub value: String,
+    pub window: TimeWindow,
+    pub backends: Vec<String>,
+}
+
+impl TimeRule {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(&self.header)
+            .is_some_and(|value| value.as_bytes() == self.value.as_bytes())
+    }
+}
+
+fn parse_minute_of_day(value: &str) -> Result<u16, String> {
+    let (hours, minutes) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected HH:MM, got {}", value))?;
+    let hours: u16 = hours
+        .parse()
+        .map_err(|_| format!("invalid hour in {}", value))?;
+    let minutes: u16 = minutes
+        .parse()
+        .map_err(|_| format!("invalid minutes in {}", value))?;
+
+    if hours > 24 || minutes > 59 || (hours == 24 && minutes > 0) {
+        return Err(format!("{} is not a time of day", value));
+    }
+
+    Ok(hours * 60 + minutes)
+}
+
+/// The minute of the day of `now`, shifted by the UTC offset.
+fn minute_of_day(now: SystemTime, utc_offset_minutes: i32) -> u16 {
+    let minutes = now
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| (elapsed.as_secs() / 60) as i64)
+        .unwrap_or_default();
+
+    (minutes + i64::from(utc_offset_minutes)).rem_euclid(MINUTES_PER_DAY) as u16
+}
+
+impl FromStr for TimeRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected HEADER=VALUE@HH:MM-HH:MM>BACKENDS, got {}", value);
+
+        let (matcher, rest) = value.split_once('@').ok_or_else(invalid)?;
+        let (header, header_value) = matcher.split_once('=').ok_or_else(invalid)?;
+        let (window, backends) = rest.split_once('>').ok_or_else(invalid)?;
+
+        let backends = backends
+            .split('|')
+            .filter(|backend| !backend.is_empty())
+            .map(normalize_target_url)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if backends.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(TimeRule {
+            header: HeaderName::from_str(header).map_err(|_| invalid())?,
+            value: header_value.to_string(),
+            window: window.parse()?,
+            backends,
+        })
+    }
+}
+
+/// Time-of-day rules, evaluated in the timezone given by `utc_offset_minutes`.
+#[derive(Debug, Clone, Default)]
+pub struct TimeRules {
+    pub rules: Vec<TimeRule>,
+    pub utc_offset_minutes: i32,
+}
+
+impl TimeRules {
+    /// Servers the request must not be sent to right now: the first active
+    /// rule matching the request keeps only its own backends.
+    pub fn excluded_servers(&self, headers: &HeaderMap, target_servers: &[String]) -> Vec<String> {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        self.excluded_servers_at(
+            headers,
+            target_servers,
+            self.minute_of_day(SystemTime::now()),
+        )
+    }
+
+    fn excluded_servers_at(
+        &self,
+        headers: &HeaderMap,
+        target_servers: &[String],
+        minute_of_day: u16,
+    ) -> Vec<String> {
+        match self
+            .rules
+            .iter()
+            .find(|rule| rule.window.contains(minute_of_day) && rule.matches(headers))
+        {
+            Some(rule) => target_servers
+                .iter()
+                .filter(|server| !rule.backends.contains(server))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn minute_of_day(&self, now: SystemTime) -> u16 {
+        minute_of_day(now, self.utc_offset_minutes)
+    }
+}
+
+/// Parses a `+HH:MM` or `-HH:MM` UTC offset into minutes.
+pub fn parse_utc_offset(value: &str) -> Result<i32, String> {
+    let (sign, offset) = match value.as_bytes().first() {
+        Some(b'+') => (1, &value[1..]),
+        Some(b'-') => (-1, &value[1..]),
+        _ => return Err(format!("expected +HH:MM or -HH:MM, got {}", value)),
+    };
+
+    Ok(sign * i32::from(parse_minute_of_day(offset)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http::{HeaderMap, HeaderValue};
+
+    use crate::time_rules::{
+        DayClock, MockClock, TimeRule, TimeRules, TimeWindow, parse_utc_offset,
+    };
+
+    fn batch_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-traffic-class", HeaderValue::from_static("batch"));
+        headers
+    }
+
+    fn target_servers() -> Vec<String> {
+        vec!["http://cheap".to_string(), "http://fast".to_string()]
+    }
+
+    fn time_rules(rule: &str) -> TimeRules {
+        TimeRules {
+            rules: vec![rule.parse().unwrap()],
+            utc_offset_minutes: 0,
+        }
+    }
+
+    #[test]
+    fn parses_a_rule() {
+        let rule: TimeRule =
+            "x-traffic-class=batch@22:30-06:00>http://cheap:8080/|HTTP://Cheap:8081"
+                .parse()
+                .unwrap();
+
+        assert_eq!(rule.header, "x-traffic-class");
+        assert_eq!(rule.value, "batch");
+        assert_eq!(
+            rule.window,
+            TimeWindow {
+                start_minute: 22 * 60 + 30,
+                end_minute: 6 * 60,
+            }
+        );
+        assert_eq!(
+            rule.backends,
+            vec!["http://cheap:8080", "http://cheap:8081"]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!(
+            "x-traffic-class=batch>http://cheap"
+                .parse::<TimeRule>()
+                .is_err()
+        );
+        assert!(
+            "x-traffic-class=batch@25:00-06:00>http://cheap"
+                .parse::<TimeRule>()
+                .is_err()
+        );
+        assert!(
+            "x-traffic-class=batch@00:00-06:00>"
+                .parse::<TimeRule>()
+                .is_err()
+        );
+        assert!(
+            "x-traffic-class=batch@00:00-06:00>cheap:8080"
+                .parse::<TimeRule>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn matching_requests_only_go_to_the_rule_backends_during_the_window() {
+        let time_rules = time_rules("x-traffic-class=batch@00:00-06:00>http://cheap");
+
+        assert_eq!(
+            time_rules.excluded_servers_at(&batch_headers(), &target_servers(), 3 * 60),
+            vec!["http://fast"]
+        );
+        assert!(
+            time_rules
+                .excluded_servers_at(&batch_headers(), &target_servers(), 12 * 60)
+                .is_empty()
+        );
+        assert!(
+            time_rules
+                .excluded_servers_at(&HeaderMap::new(), &target_servers(), 3 * 60)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn windows_can_span_midnight() {
+        let time_rules = time_rules("x-traffic-class=batch@22:00-02:00>http://cheap");
+
+        for minute in [23 * 60, 60] {
+            assert_eq!(
+                time_rules.excluded_servers_at(&batch_headers(), &target_servers(), minute),
+                vec!["http://fast"]
+            );
+        }
+    }
+
+    #[test]
+    fn the_time_of_day_is_taken_in_the_configured_timezone() {
+        let time_rules = TimeRules {
+            rules: Vec::new(),
+            utc_offset_minutes: parse_utc_offset("-05:00").unwrap(),
+        };
+
+        let two_am_utc = UNIX_EPOCH + Duration::from_secs(2 * 3600);
+
+        assert_eq!(time_rules.minute_of_day(two_am_utc), 21 * 60);
+    }
+
+    #[test]
+    fn the_day_clock_tells_the_minute_of_the_day_in_its_timezone() {
+        let mut clock = MockClock::new();
+        clock
+            .expect_now()
+            .returning(|| UNIX_EPOCH + Duration::from_secs(2 * 3600 + 30 * 60));
+
+        let day_clock = DayClock {
+            clock: Arc::new(clock),
+            utc_offset_minutes: 60,
+        };
+
+        assert_eq!(day_clock.minute_of_day(), 3 * 60 + 30);
+    }
+
+    #[test]
+    fn windows_contain_their_start_but_not_their_end() {
+        let window: TimeWindow = "02:00-03:00".parse().unwrap();
+
+        assert!(window.contains(2 * 60));
+        assert!(window.contains(2 * 60 + 59));
+        assert!(!window.contains(3 * 60));
+        assert!(
+            "00:00-24:00"
+                .parse::<TimeWindow>()
+                .unwrap()
+                .contains(23 * 60)
+        );
+        assert!("02:00".parse::<TimeWindow>().is_err());
+    }
+}
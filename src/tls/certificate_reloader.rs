@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use tokio::time;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::{CryptoProvider, ring};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tracing::{info, warn};
+
+use crate::tls::error::Error;
+use crate::tls::tls_policy::TlsPolicy;
+
+/// The certificate presented by the listener, reloaded from its PEM files
+/// whenever they change so that renewals don't need a restart. A pair that
+/// doesn't parse, or whose key doesn't match the certificate, is rejected
+/// and the previous one keeps being served.
+#[derive(Debug)]
+pub struct CertificateReloader {
+    certificate_path: PathBuf,
+    key_path: PathBuf,
+    poll_interval: Duration,
+    current: RwLock<Arc<CertifiedKey>>,
+    last_seen: Mutex<(Vec<u8>, Vec<u8>)>,
+}
+
+impl CertificateReloader {
+    pub async fn load(
+        certificate_path: PathBuf,
+        key_path: PathBuf,
+        poll_interval: Duration,
+    ) -> Result<Self, Error> {
+        let (certificate_pem, key_pem) = read_pair(&certificate_path, &key_path).await?;
+        let current = certified_key(&certificate_pem, &key_pem)?;
+
+        Ok(Self {
+            certificate_path,
+            key_path,
+            poll_interval,
+            current: RwLock::new(Arc::new(current)),
+            last_seen: Mutex::new((certificate_pem, key_pem)),
+        })
+    }
+
+    /// A server configuration following `policy` that resolves the
+    /// certificate through this reloader on every handshake.
+    pub fn server_config(self: &Arc<Self>, policy: &TlsPolicy) -> Result<ServerConfig, Error> {
+        policy.server_config(Arc::clone(self) as Arc<dyn ResolvesServerCert>)
+    }
+
+    /// Reloads the certificate if either file changed since the last look,
+    /// returning whether a new one is being served.
+    pub async fn reload(&self) -> Result<bool, Error> {
+        let pair = read_pair(&self.certificate_path, &self.key_path).await?;
+
+        {
+            let Ok(mut last_seen) = self.last_seen.lock() else {
+                return Ok(false);
+            };
+
+            if *last_seen == pair {
+                return Ok(false);
+            }
+
+            // Remembered even when invalid, so that a broken pair is only
+            // reported once instead of on every poll.
+            *last_seen = pair.clone();
+        }
+
+        let certified_key = certified_key(&pair.0, &pair.1)?;
+
+        if let Ok(mut current) = self.current.write() {
+            *current = Arc::new(certified_key);
+        }
+
+        Ok(true)
+    }
+
+    /// Polls the certificate files forever.
+    pub async fn execute(&self) {
+        info!(
+            "Watching the TLS certificate {:?} and key {:?}",
+            self.certificate_path, self.key_path
+        );
+
+        let mut interval = time::interval(self.poll_interval);
+
+        loop {
+            interval.tick().await;
+
+            match self.reload().await {
+                Ok(true) => info!("Reloaded the TLS certificate"),
+                Ok(false) => {}
+                Err(error) => warn!("Keeping the current TLS certificate: {}", error),
+            }
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<CertifiedKey>> {
+        self.current.read().ok().map(|current| Arc::clone(&current))
+    }
+
+    /// Staples the OCSP `response` to the certificate being served, unless
+    /// it was rotated in the meantime and is no longer `certificate`.
+    pub fn staple(&self, certificate: &[u8], response: Vec<u8>) -> bool {
+        self.set_ocsp(certificate, Some(response))
+    }
+
+    /// Stops stapling an OCSP response to `certificate`, if still served.
+    pub fn unstaple(&self, certificate: &[u8]) -> bool {
+        self.set_ocsp(certificate, None)
+    }
+
+    fn set_ocsp(&self, certificate: &[u8], response: Option<Vec<u8>>) -> bool {
+        let Ok(mut current) = self.current.write() else {
+            return false;
+        };
+
+        if current.cert.first().map(|served| served.as_ref()) != Some(certificate) {
+            return false;
+        }
+
+        let mut stapled = CertifiedKey::clone(&current);
+        stapled.ocsp = response;
+        *current = Arc::new(stapled);
+
+        true
+    }
+}
+
+impl ResolvesServerCert for CertificateReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.current()
+    }
+}
+
+fn provider() -> CryptoProvider {
+    ring::default_provider()
+}
+
+async fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|error| Error::Read(path.display().to_string(), error.to_string()))
+}
+
+async fn read_pair(certificate_path: &Path, key_path: &Path) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    Ok((read(certificate_path).await?, read(key_path).await?))
+}
+
+fn certified_key(certificate_pem: &[u8], key_pem: &[u8]) -> Result<CertifiedKey, Error> {
+    let chain = CertificateDer::pem_slice_iter(certificate_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+
+    if chain.is_empty() {
+        return Err(Error::InvalidCertificate(
+            "no certificate in the PEM file".to_string(),
+        ));
+    }
+
+    let key = PrivateKeyDer::from_pem_slice(key_pem)
+        .map_err(|error| Error::InvalidKey(error.to_string()))?;
+
+    CertifiedKey::from_der(chain, key, &provider())
+        .map_err(|error| Error::InvalidKey(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use crate::tls::certificate_reloader::CertificateReloader;
+
+    struct Files {
+        certificate: PathBuf,
+        key: PathBuf,
+    }
+
+    impl Files {
+        fn new() -> Self {
+            let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+
+            Self {
+                certificate: prefix.with_extension("crt"),
+                key: prefix.with_extension("key"),
+            }
+        }
+
+        /// Writes a new self-signed pair, returning the DER certificate.
+        fn write_pair(&self) -> Vec<u8> {
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let certificate = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+                .unwrap()
+                .self_signed(&key_pair)
+                .unwrap();
+
+            std::fs::write(&self.certificate, certificate.pem()).unwrap();
+            std::fs::write(&self.key, key_pair.serialize_pem()).unwrap();
+
+            certificate.der().to_vec()
+        }
+
+        async fn reloader(&self) -> CertificateReloader {
+            CertificateReloader::load(
+                self.certificate.clone(),
+                self.key.clone(),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap()
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.certificate);
+            let _ = std::fs::remove_file(&self.key);
+        }
+    }
+
+    fn served(reloader: &CertificateReloader) -> Vec<u8> {
+        reloader.current().unwrap().cert[0].to_vec()
+    }
+
+    #[tokio::test]
+    async fn serves_the_certificate_on_disk() {
+        let files = Files::new();
+        let certificate = files.write_pair();
+
+        let reloader = files.reloader().await;
+
+        assert_eq!(served(&reloader), certificate);
+        assert!(!reloader.reload().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn picks_up_a_renewed_certificate() {
+        let files = Files::new();
+        files.write_pair();
+        let reloader = files.reloader().await;
+
+        let renewed = files.write_pair();
+
+        assert!(reloader.reload().await.unwrap());
+        assert_eq!(served(&reloader), renewed);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_current_certificate_when_the_new_one_is_invalid() {
+        let files = Files::new();
+        let certificate = files.write_pair();
+        let reloader = files.reloader().await;
+
+        std::fs::write(&files.certificate, "not a certificate").unwrap();
+
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(served(&reloader), certificate);
+        assert!(!reloader.reload().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_current_certificate_when_the_key_does_not_match() {
+        let files = Files::new();
+        let certificate = files.write_pair();
+        let reloader = files.reloader().await;
+
+        let key = std::fs::read(&files.key).unwrap();
+        files.write_pair();
+        std::fs::write(&files.key, key).unwrap();
+
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(served(&reloader), certificate);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_start_without_a_valid_pair() {
+        let files = Files::new();
+        std::fs::write(&files.certificate, "not a certificate").unwrap();
+        std::fs::write(&files.key, "not a key").unwrap();
+
+        let result = CertificateReloader::load(
+            files.certificate.clone(),
+            files.key.clone(),
+            Duration::from_secs(1),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,17 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to read {0}: {1}")]
+    Read(String, String),
+
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("Invalid private key: {0}")]
+    InvalidKey(String),
+
+    #[error("Invalid TLS configuration: {0}")]
+    InvalidConfiguration(String),
+
+    #[error("OCSP stapling failed: {0}")]
+    Ocsp(String),
+}
@@ -0,0 +1,6 @@
+pub mod certificate_reloader;
+pub mod error;
+pub mod ocsp_stapler;
+pub mod sni_resolver;
+pub mod tls_listener;
+pub mod tls_policy;
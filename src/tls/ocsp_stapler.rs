@@ -0,0 +1,501 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use http::{HeaderValue, header};
+use sha1::Sha1;
+use tokio::time;
+use tracing::{info, warn};
+use x509_cert::Certificate;
+use x509_cert::der::{Decode, Encode};
+use x509_ocsp::builder::OcspRequestBuilder;
+use x509_ocsp::{BasicOcspResponse, CertId, OcspResponse, OcspResponseStatus};
+use x509_parser::extensions::{GeneralName, ParsedExtension};
+use x509_parser::oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_OCSP;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::http_client::http_client::HttpClient;
+use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+use crate::tls::certificate_reloader::CertificateReloader;
+use crate::tls::error::Error;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The OCSP response stapled to `certificate`.
+struct Staple {
+    certificate: Vec<u8>,
+    refresh_at: Instant,
+    /// The `nextUpdate` of the response, past which it is no longer stapled.
+    expires_at: Option<SystemTime>,
+}
+
+/// Staples the OCSP response of the listener certificate to the handshakes,
+/// so that clients checking revocation don't have to ask the CA themselves.
+/// The response is fetched again every `refresh_interval`, or halfway to its
+/// `nextUpdate` when sooner, and as soon as the certificate is rotated; a
+/// failed fetch keeps the previous response until its `nextUpdate`.
+pub struct OcspStapler {
+    certificate_reloader: Arc<CertificateReloader>,
+    http_client: Arc<dyn HttpClient>,
+    refresh_interval: Duration,
+    stapled: Mutex<Option<Staple>>,
+}
+
+impl OcspStapler {
+    pub fn new(
+        certificate_reloader: Arc<CertificateReloader>,
+        http_client: Arc<dyn HttpClient>,
+        refresh_interval: Duration,
+    ) -> Self {
+        Self {
+            certificate_reloader,
+            http_client,
+            refresh_interval,
+            stapled: Mutex::new(None),
+        }
+    }
+
+    fn needs_staple(&self, certificate: &[u8]) -> bool {
+        match self.stapled.lock().ok().as_deref() {
+            Some(Some(staple)) => {
+                staple.certificate != certificate || Instant::now() >= staple.refresh_at
+            }
+            _ => true,
+        }
+    }
+
+    /// Stops stapling the response of `certificate` once past its
+    /// `nextUpdate`, since clients would reject the handshake.
+    fn unstaple_expired(&self, certificate: &[u8]) {
+        let Ok(mut stapled) = self.stapled.lock() else {
+            return;
+        };
+
+        let expired = stapled.as_ref().is_some_and(|staple| {
+            staple.certificate == certificate
+                && staple
+                    .expires_at
+                    .is_some_and(|expires_at| SystemTime::now() >= expires_at)
+        });
+
+        if expired && self.certificate_reloader.unstaple(certificate) {
+            warn!("Stopped stapling the OCSP response past its nextUpdate");
+            *stapled = None;
+        }
+    }
+
+    /// Fetches and staples the OCSP response of the certificate being served.
+    pub async fn staple(&self) -> Result<(), Error> {
+        let certified_key = self
+            .certificate_reloader
+            .current()
+            .ok_or_else(|| Error::Ocsp("no certificate is being served".to_string()))?;
+
+        let (certificate, issuer) = match certified_key.cert.as_slice() {
+            [certificate, issuer, ..] => (certificate.as_ref(), issuer.as_ref()),
+            _ => {
+                return Err(Error::Ocsp(
+                    "the certificate chain has no issuer".to_string(),
+                ));
+            }
+        };
+
+        let (url, body, cert_id) = ocsp_request(certificate, issuer)?;
+
+        let response = self
+            .http_client
+            .execute(Request {
+                method: RequestMethod::Post,
+                url: url.clone(),
+                headers: RequestHeaders::from([(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/ocsp-request"),
+                )]),
+                body: body.into(),
+                timeout: None,
+            })
+            .await
+            .map_err(|error| Error::Ocsp(error.to_string()))?;
+
+        if response.status != 200 {
+            return Err(Error::Ocsp(format!(
+                "{} returned status {}",
+                url, response.status
+            )));
+        }
+
+        let response = response
+            .body
+            .collect()
+            .await
+            .map_err(|error| Error::Ocsp(error.to_string()))?
+            .to_vec();
+
+        let expires_at = next_update(&response, &cert_id)
+            .map_err(|error| Error::Ocsp(format!("{} answered {}", url, error)))?;
+        let valid_for = match expires_at {
+            Some(expires_at) => expires_at
+                .duration_since(SystemTime::now())
+                .map_err(|_| Error::Ocsp(format!("{} answered an expired response", url)))?,
+            None => self.refresh_interval,
+        };
+
+        if self.certificate_reloader.staple(certificate, response)
+            && let Ok(mut stapled) = self.stapled.lock()
+        {
+            *stapled = Some(Staple {
+                certificate: certificate.to_vec(),
+                refresh_at: Instant::now() + self.refresh_interval.min(valid_for / 2),
+                expires_at,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the staple fresh forever.
+    pub async fn execute(&self) {
+        info!(
+            "Stapling OCSP responses, refreshed every {:?}",
+            self.refresh_interval
+        );
+
+        let mut interval = time::interval(CHECK_INTERVAL.min(self.refresh_interval));
+
+        loop {
+            interval.tick().await;
+
+            let Some(certified_key) = self.certificate_reloader.current() else {
+                continue;
+            };
+
+            let Some(certificate) = certified_key.cert.first() else {
+                continue;
+            };
+
+            self.unstaple_expired(certificate.as_ref());
+
+            if !self.needs_staple(certificate.as_ref()) {
+                continue;
+            }
+
+            match self.staple().await {
+                Ok(()) => info!("Stapled a fresh OCSP response"),
+                Err(error) => warn!("Keeping the current OCSP staple: {}", error),
+            }
+        }
+    }
+}
+
+/// The OCSP responder of `certificate`, the request asking it about it, and
+/// the id the response refers to it by.
+fn ocsp_request(certificate: &[u8], issuer: &[u8]) -> Result<(String, Vec<u8>, CertId), Error> {
+    let (_, parsed) = X509Certificate::from_der(certificate)
+        .map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+
+    let url =
+        parsed
+            .extensions()
+            .iter()
+            .find_map(|extension| match extension.parsed_extension() {
+                ParsedExtension::AuthorityInfoAccess(access) => access.iter().find_map(|access| {
+                    match (&access.access_method, &access.access_location) {
+                        (method, GeneralName::URI(uri))
+                            if *method == OID_PKIX_ACCESS_DESCRIPTOR_OCSP =>
+                        {
+                            Some(uri.to_string())
+                        }
+                        _ => None,
+                    }
+                }),
+                _ => None,
+            })
+            .ok_or_else(|| Error::Ocsp("the certificate names no OCSP responder".to_string()))?;
+
+    let certificate = Certificate::from_der(certificate)
+        .map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+    let issuer = Certificate::from_der(issuer)
+        .map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+
+    // SHA-1, which every OCSP responder understands.
+    let request = x509_ocsp::Request::from_cert::<Sha1>(&issuer, &certificate)
+        .map_err(|error| Error::Ocsp(error.to_string()))?;
+    let cert_id = request.req_cert.clone();
+    let body = OcspRequestBuilder::default()
+        .with_request(request)
+        .build()
+        .to_der()
+        .map_err(|error| Error::Ocsp(error.to_string()))?;
+
+    Ok((url, body, cert_id))
+}
+
+/// The `nextUpdate` of the successful `response` about `cert_id`, if any.
+fn next_update(response: &[u8], cert_id: &CertId) -> Result<Option<SystemTime>, String> {
+    let response =
+        OcspResponse::from_der(response).map_err(|error| format!("garbage: {}", error))?;
+
+    if response.response_status != OcspResponseStatus::Successful {
+        return Err(format!("with status {:?}", response.response_status));
+    }
+
+    let response_bytes = response
+        .response_bytes
+        .ok_or_else(|| "no response bytes".to_string())?;
+    let basic = BasicOcspResponse::from_der(response_bytes.response.as_bytes())
+        .map_err(|error| format!("garbage: {}", error))?;
+
+    basic
+        .tbs_response_data
+        .responses
+        .iter()
+        .find(|single| single.cert_id == *cert_id)
+        .map(|single| {
+            single
+                .next_update
+                .map(|next_update| next_update.0.to_system_time())
+        })
+        .ok_or_else(|| "nothing about the certificate".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    use bytes::Bytes;
+    use uuid::Uuid;
+    use x509_cert::der::asn1::{BitString, Ia5String};
+    use x509_cert::der::oid::db::rfc5280::ID_AD_OCSP;
+    use x509_cert::der::oid::db::rfc5912::SHA_256_WITH_RSA_ENCRYPTION;
+    use x509_cert::der::{Decode, Encode};
+    use x509_cert::ext::pkix::name::GeneralName;
+    use x509_cert::ext::pkix::{AccessDescription, AuthorityInfoAccessSyntax};
+    use x509_cert::name::Name;
+    use x509_cert::spki::AlgorithmIdentifierOwned;
+    use x509_ocsp::{
+        BasicOcspResponse, CertStatus, OcspGeneralizedTime, OcspRequest, OcspResponse, ResponderId,
+        ResponseData, SingleResponse, Version,
+    };
+
+    use crate::http_client::body::Body;
+    use crate::http_client::http_client::MockHttpClient;
+    use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use crate::http_client::response::Response;
+    use crate::tls::certificate_reloader::CertificateReloader;
+    use crate::tls::ocsp_stapler::OcspStapler;
+
+    /// Writes a certificate issued by a test CA, naming `responder` as its
+    /// OCSP responder, followed by the CA certificate when `with_issuer`.
+    fn write_chain(responder: &str, with_issuer: bool) -> (PathBuf, PathBuf) {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let authority_info_access = AuthorityInfoAccessSyntax(vec![AccessDescription {
+            access_method: ID_AD_OCSP,
+            access_location: GeneralName::UniformResourceIdentifier(
+                Ia5String::new(responder).unwrap(),
+            ),
+        }]);
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.custom_extensions = vec![rcgen::CustomExtension::from_oid_content(
+            &[1, 3, 6, 1, 5, 5, 7, 1, 1],
+            authority_info_access.to_der().unwrap(),
+        )];
+        let key = rcgen::KeyPair::generate().unwrap();
+        let certificate = params
+            .signed_by(&key, &rcgen::Issuer::new(ca_params, ca_key))
+            .unwrap();
+
+        let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+        let (certificate_path, key_path) =
+            (prefix.with_extension("crt"), prefix.with_extension("key"));
+        let chain = match with_issuer {
+            true => format!("{}{}", certificate.pem(), ca.pem()),
+            false => certificate.pem(),
+        };
+        std::fs::write(&certificate_path, chain).unwrap();
+        std::fs::write(&key_path, key.serialize_pem()).unwrap();
+
+        (certificate_path, key_path)
+    }
+
+    async fn stapler(
+        with_issuer: bool,
+        setup_http_client: impl FnOnce(&mut MockHttpClient),
+    ) -> (OcspStapler, Arc<CertificateReloader>) {
+        let (certificate_path, key_path) = write_chain("http://ocsp.local/", with_issuer);
+        let certificate_reloader = Arc::new(
+            CertificateReloader::load(
+                certificate_path.clone(),
+                key_path.clone(),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap(),
+        );
+        let _ = std::fs::remove_file(certificate_path);
+        let _ = std::fs::remove_file(key_path);
+
+        let mut http_client = MockHttpClient::new();
+        setup_http_client(&mut http_client);
+
+        (
+            OcspStapler::new(
+                Arc::clone(&certificate_reloader),
+                Arc::new(http_client),
+                Duration::from_secs(3600),
+            ),
+            certificate_reloader,
+        )
+    }
+
+    /// A good status for the certificate `request` asks about, valid until
+    /// `next_update`. Left unsigned, which the stapler doesn't check.
+    fn good_response(request: &Request, next_update: SystemTime) -> Bytes {
+        let Body::Full(body) = &request.body else {
+            panic!("the OCSP request is in memory");
+        };
+        let request = OcspRequest::from_der(body).unwrap();
+        let now = OcspGeneralizedTime::try_from(SystemTime::now()).unwrap();
+
+        let response = BasicOcspResponse {
+            tbs_response_data: ResponseData {
+                version: Version::V1,
+                responder_id: ResponderId::ByName(Name::default()),
+                produced_at: now,
+                responses: vec![SingleResponse {
+                    cert_id: request.tbs_request.request_list[0].req_cert.clone(),
+                    cert_status: CertStatus::good(),
+                    this_update: now,
+                    next_update: Some(OcspGeneralizedTime::try_from(next_update).unwrap()),
+                    single_extensions: None,
+                }],
+                response_extensions: None,
+            },
+            signature_algorithm: AlgorithmIdentifierOwned {
+                oid: SHA_256_WITH_RSA_ENCRYPTION,
+                parameters: None,
+            },
+            signature: BitString::from_bytes(&[0]).unwrap(),
+            certs: None,
+        };
+
+        OcspResponse::successful(response)
+            .unwrap()
+            .to_der()
+            .unwrap()
+            .into()
+    }
+
+    fn respond_with(
+        response: impl Fn(&Request) -> Bytes + Send + 'static,
+    ) -> impl FnOnce(&mut MockHttpClient) {
+        move |http_client| {
+            http_client
+                .expect_execute()
+                .withf(|req| {
+                    req.method == RequestMethod::Post
+                        && req.url == "http://ocsp.local/"
+                        && req
+                            .headers
+                            .get("content-type")
+                            .and_then(|value| value.to_str().ok())
+                            == Some("application/ocsp-request")
+                })
+                .times(1)
+                .returning(move |req| {
+                    Ok(Response {
+                        status: 200,
+                        headers: RequestHeaders::default(),
+                        body: response(&req).into(),
+                    })
+                });
+        }
+    }
+
+    fn valid_for(duration: Duration) -> impl Fn(&Request) -> Bytes {
+        move |request| good_response(request, SystemTime::now() + duration)
+    }
+
+    #[tokio::test]
+    async fn staples_the_response_of_the_responder() {
+        let (stapler, certificate_reloader) =
+            stapler(true, respond_with(valid_for(Duration::from_secs(86400)))).await;
+
+        stapler.staple().await.unwrap();
+
+        let certified_key = certificate_reloader.current().unwrap();
+        assert!(certified_key.ocsp.is_some());
+        assert!(!stapler.needs_staple(certified_key.cert[0].as_ref()));
+    }
+
+    #[tokio::test]
+    async fn refreshes_halfway_to_the_next_update() {
+        let (stapler, certificate_reloader) =
+            stapler(true, respond_with(valid_for(Duration::from_secs(2)))).await;
+
+        stapler.staple().await.unwrap();
+        let certificate = certificate_reloader.current().unwrap().cert[0].to_vec();
+        assert!(!stapler.needs_staple(&certificate));
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        assert!(stapler.needs_staple(&certificate));
+    }
+
+    #[tokio::test]
+    async fn stops_stapling_past_the_next_update() {
+        let (stapler, certificate_reloader) =
+            stapler(true, respond_with(valid_for(Duration::from_secs(2)))).await;
+
+        stapler.staple().await.unwrap();
+        let certificate = certificate_reloader.current().unwrap().cert[0].to_vec();
+        stapler.unstaple_expired(&certificate);
+        assert!(certificate_reloader.current().unwrap().ocsp.is_some());
+
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        stapler.unstaple_expired(&certificate);
+
+        assert_eq!(certificate_reloader.current().unwrap().ocsp, None);
+        assert!(stapler.needs_staple(&certificate));
+    }
+
+    #[tokio::test]
+    async fn does_not_staple_unsuccessful_responses() {
+        let (stapler, certificate_reloader) = stapler(
+            true,
+            respond_with(|_| OcspResponse::unauthorized().to_der().unwrap().into()),
+        )
+        .await;
+
+        assert!(stapler.staple().await.is_err());
+        assert_eq!(certificate_reloader.current().unwrap().ocsp, None);
+    }
+
+    #[tokio::test]
+    async fn does_not_staple_expired_responses() {
+        let (stapler, certificate_reloader) = stapler(
+            true,
+            respond_with(|request| {
+                good_response(request, SystemTime::now() - Duration::from_secs(60))
+            }),
+        )
+        .await;
+
+        assert!(stapler.staple().await.is_err());
+        assert_eq!(certificate_reloader.current().unwrap().ocsp, None);
+    }
+
+    #[tokio::test]
+    async fn needs_the_issuer_in_the_chain() {
+        let (stapler, _) = stapler(false, |http_client| {
+            http_client.expect_execute().never();
+        })
+        .await;
+
+        assert!(stapler.staple().await.is_err());
+    }
+}
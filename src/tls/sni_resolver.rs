@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+
+use crate::tls::certificate_reloader::CertificateReloader;
+use crate::tls::error::Error;
+use crate::tls::tls_policy::TlsPolicy;
+
+/// Picks the certificate of the listener by the server name the client
+/// sends (SNI), so that one listener terminates TLS for several domains.
+/// Names are matched exactly first, then against the wildcard domains, e.g.
+/// `*.example.com`, which cover a single label. Clients sending no name, or
+/// one no certificate is configured for, get the default certificate.
+#[derive(Debug)]
+pub struct SniResolver {
+    default: Arc<CertificateReloader>,
+    certificates: Vec<(String, Arc<CertificateReloader>)>,
+}
+
+impl SniResolver {
+    pub fn new(
+        default: Arc<CertificateReloader>,
+        certificates: Vec<(String, Arc<CertificateReloader>)>,
+    ) -> Self {
+        Self {
+            default,
+            certificates: certificates
+                .into_iter()
+                .map(|(domain, reloader)| (domain.to_ascii_lowercase(), reloader))
+                .collect(),
+        }
+    }
+
+    /// A server configuration following `policy` that resolves the
+    /// certificate through this resolver on every handshake.
+    pub fn server_config(self: &Arc<Self>, policy: &TlsPolicy) -> Result<ServerConfig, Error> {
+        policy.server_config(Arc::clone(self) as Arc<dyn ResolvesServerCert>)
+    }
+
+    /// The reloader of the certificate served to clients asking for
+    /// `server_name`.
+    pub fn certificate(&self, server_name: Option<&str>) -> &Arc<CertificateReloader> {
+        let Some(server_name) = server_name.map(str::to_ascii_lowercase) else {
+            return &self.default;
+        };
+        let parent = server_name
+            .split_once('.')
+            .map(|(_, parent)| format!("*.{}", parent));
+
+        self.find(&server_name)
+            .or_else(|| parent.and_then(|parent| self.find(&parent)))
+            .unwrap_or(&self.default)
+    }
+
+    fn find(&self, domain: &str) -> Option<&Arc<CertificateReloader>> {
+        self.certificates
+            .iter()
+            .find(|(candidate, _)| candidate == domain)
+            .map(|(_, reloader)| reloader)
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.certificate(client_hello.server_name()).current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use crate::tls::certificate_reloader::CertificateReloader;
+    use crate::tls::sni_resolver::SniResolver;
+
+    struct Files {
+        certificate: PathBuf,
+        key: PathBuf,
+    }
+
+    impl Files {
+        fn new() -> Self {
+            let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+
+            Self {
+                certificate: prefix.with_extension("crt"),
+                key: prefix.with_extension("key"),
+            }
+        }
+
+        async fn reloader(&self, domain: &str) -> Arc<CertificateReloader> {
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let certificate = rcgen::CertificateParams::new(vec![domain.to_string()])
+                .unwrap()
+                .self_signed(&key_pair)
+                .unwrap();
+
+            std::fs::write(&self.certificate, certificate.pem()).unwrap();
+            std::fs::write(&self.key, key_pair.serialize_pem()).unwrap();
+
+            Arc::new(
+                CertificateReloader::load(
+                    self.certificate.clone(),
+                    self.key.clone(),
+                    Duration::from_secs(1),
+                )
+                .await
+                .unwrap(),
+            )
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.certificate);
+            let _ = std::fs::remove_file(&self.key);
+        }
+    }
+
+    #[tokio::test]
+    async fn picks_the_certificate_of_the_server_name() {
+        let files = Files::new();
+        let default = files.reloader("localhost").await;
+        let api = files.reloader("api.example.com").await;
+        let wildcard = files.reloader("*.example.com").await;
+        let resolver = SniResolver::new(
+            Arc::clone(&default),
+            vec![
+                ("*.example.com".to_string(), Arc::clone(&wildcard)),
+                ("API.example.com".to_string(), Arc::clone(&api)),
+            ],
+        );
+
+        let resolved = |server_name| resolver.certificate(server_name);
+
+        assert!(Arc::ptr_eq(resolved(Some("api.example.com")), &api));
+        assert!(Arc::ptr_eq(resolved(Some("Api.Example.com")), &api));
+        assert!(Arc::ptr_eq(resolved(Some("www.example.com")), &wildcard));
+        assert!(Arc::ptr_eq(resolved(Some("a.www.example.com")), &default));
+        assert!(Arc::ptr_eq(resolved(Some("example.com")), &default));
+        assert!(Arc::ptr_eq(resolved(Some("example.org")), &default));
+        assert!(Arc::ptr_eq(resolved(None), &default));
+    }
+}
@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::crypto::{CryptoProvider, ring};
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::server::{ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::{
+    ConfigBuilder, RootCertStore, ServerConfig, SupportedCipherSuite, SupportedProtocolVersion,
+    server::WantsServerCert, version,
+};
+
+use crate::tls::error::Error;
+
+/// Presets after the Mozilla server side TLS recommendations. rustls doesn't
+/// implement TLS 1.0 and 1.1 nor the CBC suites, so `Old` can't go further
+/// than `Intermediate` does.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TlsProfile {
+    /// TLS 1.3 only.
+    Modern,
+    /// TLS 1.2 and 1.3 with forward secret AEAD suites.
+    #[default]
+    Intermediate,
+    Old,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ClientAuth {
+    #[default]
+    None,
+    /// Clients may present a certificate, which is verified when they do.
+    Optional,
+    Required,
+}
+
+/// Versions, cipher suites and client authentication of the listener.
+#[derive(Debug, Clone, Default)]
+pub struct TlsPolicy {
+    pub profile: TlsProfile,
+    /// Overrides the minimum version of the profile.
+    pub min_version: Option<TlsVersion>,
+    /// Names of the allowed cipher suites, e.g. `TLS13_AES_128_GCM_SHA256`;
+    /// every one of the profile when empty.
+    pub cipher_suites: Vec<String>,
+    pub client_auth: ClientAuth,
+    /// PEM bundle of the CAs the client certificates are verified against.
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsPolicy {
+    fn min_version(&self) -> TlsVersion {
+        self.min_version.unwrap_or(match self.profile {
+            TlsProfile::Modern => TlsVersion::Tls13,
+            TlsProfile::Intermediate | TlsProfile::Old => TlsVersion::Tls12,
+        })
+    }
+
+    fn versions(&self) -> Vec<&'static SupportedProtocolVersion> {
+        match self.min_version() {
+            TlsVersion::Tls12 => vec![&version::TLS13, &version::TLS12],
+            TlsVersion::Tls13 => vec![&version::TLS13],
+        }
+    }
+
+    fn provider(&self) -> Result<CryptoProvider, Error> {
+        let mut provider = ring::default_provider();
+
+        if let Some(unknown) = self.cipher_suites.iter().find(|name| {
+            !provider
+                .cipher_suites
+                .iter()
+                .any(|suite| suite_name(suite).eq_ignore_ascii_case(name))
+        }) {
+            return Err(Error::InvalidConfiguration(format!(
+                "unknown cipher suite {}",
+                unknown
+            )));
+        }
+
+        let versions = self.versions();
+
+        provider.cipher_suites.retain(|suite| {
+            versions.contains(&suite.version())
+                && (self.cipher_suites.is_empty()
+                    || self
+                        .cipher_suites
+                        .iter()
+                        .any(|name| suite_name(suite).eq_ignore_ascii_case(name)))
+        });
+
+        if provider.cipher_suites.is_empty() {
+            return Err(Error::InvalidConfiguration(
+                "no cipher suite left for the allowed versions".to_string(),
+            ));
+        }
+
+        Ok(provider)
+    }
+
+    pub fn server_config_builder(
+        &self,
+    ) -> Result<ConfigBuilder<ServerConfig, WantsServerCert>, Error> {
+        let provider = Arc::new(self.provider()?);
+
+        let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_protocol_versions(&self.versions())
+            .map_err(|error| Error::InvalidConfiguration(error.to_string()))?;
+
+        if self.client_auth == ClientAuth::None {
+            return Ok(builder.with_no_client_auth());
+        }
+
+        let mut verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(self.client_roots()?), provider);
+
+        if self.client_auth == ClientAuth::Optional {
+            verifier = verifier.allow_unauthenticated();
+        }
+
+        let verifier = verifier
+            .build()
+            .map_err(|error| Error::InvalidConfiguration(error.to_string()))?;
+
+        Ok(builder.with_client_cert_verifier(verifier))
+    }
+
+    /// A server configuration following this policy that asks `resolver`
+    /// for the certificate on every handshake.
+    pub fn server_config(
+        &self,
+        resolver: Arc<dyn ResolvesServerCert>,
+    ) -> Result<ServerConfig, Error> {
+        let mut config = self.server_config_builder()?.with_cert_resolver(resolver);
+
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(config)
+    }
+
+    fn client_roots(&self) -> Result<RootCertStore, Error> {
+        let path = self.client_ca.as_ref().ok_or_else(|| {
+            Error::InvalidConfiguration("client authentication requires a CA bundle".to_string())
+        })?;
+
+        let mut roots = RootCertStore::empty();
+
+        for certificate in CertificateDer::pem_file_iter(path)
+            .map_err(|error| Error::Read(path.display().to_string(), error.to_string()))?
+        {
+            let certificate =
+                certificate.map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+
+            roots
+                .add(certificate)
+                .map_err(|error| Error::InvalidCertificate(error.to_string()))?;
+        }
+
+        Ok(roots)
+    }
+}
+
+fn suite_name(suite: &SupportedCipherSuite) -> String {
+    format!("{:?}", suite.suite())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_rustls::rustls::version;
+
+    use crate::tls::tls_policy::{ClientAuth, TlsPolicy, TlsProfile, TlsVersion, suite_name};
+
+    fn suites(policy: &TlsPolicy) -> Vec<String> {
+        policy
+            .provider()
+            .unwrap()
+            .cipher_suites
+            .iter()
+            .map(suite_name)
+            .collect()
+    }
+
+    #[test]
+    fn intermediate_allows_tls12_and_tls13() {
+        let policy = TlsPolicy::default();
+
+        assert_eq!(policy.versions(), vec![&version::TLS13, &version::TLS12]);
+        assert!(suites(&policy).contains(&"TLS13_AES_128_GCM_SHA256".to_string()));
+        assert!(suites(&policy).contains(&"TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string()));
+    }
+
+    #[test]
+    fn modern_only_allows_tls13() {
+        let policy = TlsPolicy {
+            profile: TlsProfile::Modern,
+            ..TlsPolicy::default()
+        };
+
+        assert_eq!(policy.versions(), vec![&version::TLS13]);
+        assert!(
+            suites(&policy)
+                .iter()
+                .all(|suite| suite.starts_with("TLS13_"))
+        );
+    }
+
+    #[test]
+    fn min_version_overrides_the_profile() {
+        let policy = TlsPolicy {
+            profile: TlsProfile::Old,
+            min_version: Some(TlsVersion::Tls13),
+            ..TlsPolicy::default()
+        };
+
+        assert_eq!(policy.versions(), vec![&version::TLS13]);
+    }
+
+    #[test]
+    fn restricts_the_cipher_suites() {
+        let policy = TlsPolicy {
+            cipher_suites: vec![
+                "tls13_chacha20_poly1305_sha256".to_string(),
+                "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384".to_string(),
+            ],
+            ..TlsPolicy::default()
+        };
+
+        assert_eq!(
+            suites(&policy),
+            vec![
+                "TLS13_CHACHA20_POLY1305_SHA256",
+                "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_or_unusable_cipher_suites() {
+        let unknown = TlsPolicy {
+            cipher_suites: vec!["TLS_RSA_WITH_RC4_128_MD5".to_string()],
+            ..TlsPolicy::default()
+        };
+        let unusable = TlsPolicy {
+            profile: TlsProfile::Modern,
+            cipher_suites: vec!["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string()],
+            ..TlsPolicy::default()
+        };
+
+        assert!(unknown.server_config_builder().is_err());
+        assert!(unusable.server_config_builder().is_err());
+    }
+
+    #[test]
+    fn client_authentication_requires_a_ca_bundle() {
+        let policy = TlsPolicy {
+            client_auth: ClientAuth::Required,
+            ..TlsPolicy::default()
+        };
+
+        assert!(policy.server_config_builder().is_err());
+    }
+
+    #[test]
+    fn verifies_client_certificates_against_the_ca_bundle() {
+        let ca = rcgen::CertificateParams::new(vec!["clients".to_string()])
+            .unwrap()
+            .self_signed(&rcgen::KeyPair::generate().unwrap())
+            .unwrap();
+        let client_ca =
+            std::env::temp_dir().join(format!("wakanda-lb-{}.ca.crt", uuid::Uuid::new_v4()));
+        std::fs::write(&client_ca, ca.pem()).unwrap();
+
+        let policy = TlsPolicy {
+            client_auth: ClientAuth::Optional,
+            client_ca: Some(client_ca.clone()),
+            ..TlsPolicy::default()
+        };
+
+        assert!(policy.server_config_builder().is_ok());
+
+        let _ = std::fs::remove_file(client_ca);
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+use crate::HttpClient;
+use crate::http_client::request::Request;
+use crate::metrics::metrics::{MIRRORS_DROPPED_TOTAL, Metrics};
+
+/// Mirrored requests in flight at once by default.
+const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+
+/// Copies a sampled fraction of the live requests to a mirror pool, e.g. a
+/// new version of a service, without the clients ever seeing its responses.
+/// Only the requests whose body is buffered, i.e. of a known size up to
+/// 1MB, can be copied. Copies beyond `max_in_flight` are dropped, so that a
+/// slow mirror never piles up tasks.
+#[derive(Debug, Clone)]
+pub struct TrafficMirror {
+    pub targets: Vec<String>,
+    pub sample_ratio: f64,
+    in_flight: Arc<Semaphore>,
+}
+
+impl Default for TrafficMirror {
+    fn default() -> Self {
+        Self::new(Vec::new(), 0.0)
+    }
+}
+
+impl TrafficMirror {
+    pub fn new(targets: Vec<String>, sample_ratio: f64) -> Self {
+        Self {
+            targets,
+            sample_ratio,
+            in_flight: Arc::new(Semaphore::new(DEFAULT_MAX_IN_FLIGHT)),
+        }
+    }
+
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.in_flight = Arc::new(Semaphore::new(max_in_flight));
+        self
+    }
+
+    /// The mirror server a request should be copied to, if it's sampled.
+    pub fn sample(&self) -> Option<&str> {
+        if self.sample_ratio <= 0.0 || !rand::rng().random_bool(self.sample_ratio.min(1.0)) {
+            return None;
+        }
+
+        self.targets.choose(&mut rand::rng()).map(String::as_str)
+    }
+
+    /// Sends the copy in the background, discarding the response. It's
+    /// dropped, and counted, when too many copies are already in flight.
+    pub fn send(
+        &self,
+        http_client: Arc<dyn HttpClient + Send + Sync>,
+        request: Request,
+        metrics: &Metrics,
+    ) {
+        let Ok(permit) = Arc::clone(&self.in_flight).try_acquire_owned() else {
+            debug!(
+                "Too many mirrored requests in flight, dropped {}",
+                request.url
+            );
+            metrics.increment(MIRRORS_DROPPED_TOTAL);
+            return;
+        };
+
+        tokio::spawn(async move {
+            let _permit = permit;
+            let url = request.url.clone();
+
+            match http_client.execute(request).await {
+                Ok(response) => {
+                    debug!("Mirrored request to {} answered {}", url, response.status);
+                    // Read to the end so that the connection can be reused.
+                    let _ = response.body.collect().await;
+                }
+                Err(error) => warn!("Mirrored request to {} failed: {}", url, error),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use bytes::Bytes;
+
+    use crate::HttpClient;
+    use crate::http_client::error::Error;
+    use crate::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use crate::http_client::response::Response;
+    use crate::metrics::metrics::{MIRRORS_DROPPED_TOTAL, Metrics};
+    use crate::traffic_mirror::TrafficMirror;
+
+    struct StuckHttpClient;
+
+    #[async_trait]
+    impl HttpClient for StuckHttpClient {
+        async fn execute(&self, _request: Request) -> Result<Response, Error> {
+            std::future::pending().await
+        }
+    }
+
+    fn request() -> Request {
+        Request {
+            method: RequestMethod::Get,
+            url: "http://mirror.com/orders".to_string(),
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn never_samples_when_disabled() {
+        let mirror = TrafficMirror::new(vec!["http://mirror.com".to_string()], 0.0);
+
+        assert!((0..100).all(|_| mirror.sample().is_none()));
+    }
+
+    #[test]
+    fn samples_every_request_at_full_ratio() {
+        let mirror = TrafficMirror::new(vec!["http://mirror.com".to_string()], 1.0);
+
+        assert!((0..100).all(|_| mirror.sample() == Some("http://mirror.com")));
+    }
+
+    #[test]
+    fn never_samples_without_targets() {
+        let mirror = TrafficMirror::new(Vec::new(), 1.0);
+
+        assert_eq!(mirror.sample(), None);
+    }
+
+    #[tokio::test]
+    async fn drops_the_copies_beyond_the_in_flight_limit() {
+        let mirror =
+            TrafficMirror::new(vec!["http://mirror.com".to_string()], 1.0).with_max_in_flight(2);
+        let metrics = Metrics::default();
+
+        for _ in 0..5 {
+            mirror.send(Arc::new(StuckHttpClient), request(), &metrics);
+        }
+
+        assert_eq!(metrics.get(MIRRORS_DROPPED_TOTAL), 3);
+    }
+}
@@ -0,0 +1,102 @@
+use std::convert::Infallible;
+
+use axum::body::Body;
+use axum::response::Response;
+use http::{HeaderMap, Request, header};
+use tower::{Layer, ServiceExt, service_fn};
+use tower_http::decompression::DecompressionLayer;
+
+pub const GZIP: &str = "gzip";
+
+/// What happens to the responses the backends encoded, e.g. with gzip.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UpstreamDecoding {
+    /// Sent to the client as encoded, with the `Content-Encoding` and
+    /// `Content-Length` of the backend.
+    #[default]
+    Passthrough,
+    /// Decoded by the load balancer, then compressed again for the client
+    /// by the response compression, when enabled.
+    Recompress,
+}
+
+/// Whether the client listed gzip in its `Accept-Encoding` (without `q=0`).
+pub fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut parameters = coding.split(';').map(str::trim);
+            let name = parameters.next().unwrap_or_default();
+
+            (name.eq_ignore_ascii_case(GZIP) || name == "*")
+                && !parameters.any(|parameter| {
+                    parameter
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        == Some(0.0)
+                })
+        })
+}
+
+/// Decompresses a gzip response from a backend for a client that can't
+/// handle it, streaming it as it arrives.
+pub async fn decompress(response: Response) -> Response {
+    let decompression = DecompressionLayer::new().no_deflate().no_br().no_zstd();
+    // The response is handed over through the request the layer forwards.
+    let service = decompression.layer(service_fn(|request: Request<Response>| async move {
+        Ok::<_, Infallible>(request.into_body())
+    }));
+
+    match service.oneshot(Request::new(response)).await {
+        Ok(response) => response.map(Body::new),
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use axum::body::{Body, to_bytes};
+    use axum::response::Response;
+    use http::{HeaderMap, HeaderValue, header};
+
+    use crate::upstream_compression::{accepts_gzip, decompress};
+
+    fn accept_encoding(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn detects_clients_accepting_gzip() {
+        assert!(accepts_gzip(&accept_encoding("br, gzip;q=0.8")));
+        assert!(accepts_gzip(&accept_encoding("*")));
+        assert!(!accepts_gzip(&accept_encoding("gzip;q=0, br")));
+        assert!(!accepts_gzip(&accept_encoding("identity")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn decompresses_gzip_responses() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"hello world").unwrap();
+
+        let mut response = Response::new(Body::from(encoder.finish().unwrap()));
+        response
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+
+        let response = decompress(response).await;
+
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+            "hello world"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use http::{HeaderMap, HeaderName, HeaderValue, header};
+
+pub const X_SERVED_BY: HeaderName = HeaderName::from_static("x-served-by");
+pub const VIA: &str = concat!("wakanda-lb/", env!("CARGO_PKG_VERSION"));
+
+/// Tells who handled a response: the load balancer in `Via`, after the
+/// proxies the backend already listed, and the backend in `X-Served-By`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ViaHeaders {
+    pub served_by: bool,
+}
+
+impl ViaHeaders {
+    pub fn apply(&self, headers: &mut HeaderMap, server: Option<&str>) {
+        headers.append(header::VIA, HeaderValue::from_static(VIA));
+
+        if self.served_by
+            && let Some(value) = server.and_then(|server| HeaderValue::from_str(server).ok())
+        {
+            headers.insert(X_SERVED_BY, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue, header};
+
+    use crate::via_headers::{VIA, ViaHeaders, X_SERVED_BY};
+
+    #[test]
+    fn appends_to_the_via_of_the_backend() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::VIA, HeaderValue::from_static("1.1 cache"));
+
+        ViaHeaders::default().apply(&mut headers, Some("http://target.com"));
+
+        assert_eq!(
+            headers.get_all(header::VIA).iter().collect::<Vec<_>>(),
+            vec!["1.1 cache", VIA]
+        );
+        assert!(!headers.contains_key(X_SERVED_BY));
+    }
+
+    #[test]
+    fn names_the_backend_when_enabled() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_SERVED_BY, HeaderValue::from_static("spoofed"));
+
+        ViaHeaders { served_by: true }.apply(&mut headers, Some("http://target.com"));
+
+        assert_eq!(headers[X_SERVED_BY], "http://target.com");
+    }
+}
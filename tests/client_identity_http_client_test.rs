@@ -0,0 +1,211 @@
+#[cfg(test)]
+mod client_identity_http_client {
+
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::routing::get;
+    use axum::{Extension, Router, middleware};
+    use bytes::Bytes;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    use load_balancer::client_certificate::ClientCertificate;
+    use load_balancer::client_connection::{self, ClientConnection};
+    use load_balancer::http_client::client_identity_http_client::{
+        ClientIdentityFiles, ClientIdentityHttpClient,
+    };
+    use load_balancer::http_client::error::Error;
+    use load_balancer::http_client::http_client::HttpClient;
+    use load_balancer::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use load_balancer::http_client::reqwest_http_client::ReqwestHttpClient;
+    use load_balancer::tls::certificate_reloader::CertificateReloader;
+    use load_balancer::tls::tls_listener::TlsListener;
+    use load_balancer::tls::tls_policy::{ClientAuth, TlsPolicy};
+
+    struct Ca {
+        params: rcgen::CertificateParams,
+        key: rcgen::KeyPair,
+    }
+
+    impl Ca {
+        fn new() -> Self {
+            let key = rcgen::KeyPair::generate().unwrap();
+            let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+
+            Self { params, key }
+        }
+
+        fn pem(&self) -> String {
+            self.params.clone().self_signed(&self.key).unwrap().pem()
+        }
+
+        /// Writes a client certificate named `common_name` and its key.
+        fn issue(&self, common_name: &str, files: &ClientIdentityFiles) {
+            let key = rcgen::KeyPair::generate().unwrap();
+            let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+            params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, common_name);
+            let certificate = params
+                .signed_by(&key, &rcgen::Issuer::new(self.params.clone(), &self.key))
+                .unwrap();
+
+            std::fs::write(&files.certificate, certificate.pem()).unwrap();
+            std::fs::write(&files.key, key.serialize_pem()).unwrap();
+        }
+    }
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("wakanda-lb-{}", Uuid::new_v4()))
+            .with_extension(extension)
+    }
+
+    /// Starts a backend answering with the common name of the client
+    /// certificate, which it requires to be issued by `ca`.
+    async fn mutual_tls_backend(ca: &Ca) -> (String, Vec<PathBuf>) {
+        let certificate_path = temp_path("crt");
+        let key_path = temp_path("key");
+        let client_ca_path = temp_path("ca.crt");
+
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let certificate = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        std::fs::write(&certificate_path, certificate.pem()).unwrap();
+        std::fs::write(&key_path, key_pair.serialize_pem()).unwrap();
+        std::fs::write(&client_ca_path, ca.pem()).unwrap();
+
+        let certificate_reloader = Arc::new(
+            CertificateReloader::load(
+                certificate_path.clone(),
+                key_path.clone(),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap(),
+        );
+        let tls_config = Arc::new(
+            certificate_reloader
+                .server_config(&TlsPolicy {
+                    client_auth: ClientAuth::Required,
+                    client_ca: Some(client_ca_path.clone()),
+                    ..TlsPolicy::default()
+                })
+                .unwrap(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let tls_listener = TlsListener::new(tcp_listener, tls_config).unwrap();
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(
+                    |Extension(certificate): Extension<Arc<ClientCertificate>>| async move {
+                        certificate.common_name.clone().unwrap_or_default()
+                    },
+                ),
+            )
+            .layer(middleware::from_fn(client_connection::expose));
+
+        tokio::spawn(async move {
+            axum::serve(
+                tls_listener,
+                app.into_make_service_with_connect_info::<ClientConnection>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        (
+            format!("https://localhost:{}", address.port()),
+            vec![certificate_path, key_path, client_ca_path],
+        )
+    }
+
+    fn reqwest_http_client(
+        identity: Option<reqwest::Identity>,
+    ) -> Result<Arc<dyn HttpClient>, Error> {
+        let builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .pool_max_idle_per_host(0);
+        let builder = match identity {
+            Some(identity) => builder.identity(identity),
+            None => builder,
+        };
+
+        Ok(Arc::new(ReqwestHttpClient::new(builder.build().map_err(
+            |error| Error::InvalidRequest(error.to_string()),
+        )?)))
+    }
+
+    async fn common_name(http_client: &dyn HttpClient, server: &str) -> Option<String> {
+        let request = Request {
+            url: format!("{}/", server),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let response = http_client.execute(request).await.ok()?;
+        let body = response.body.collect().await.unwrap();
+
+        Some(String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_authenticate_with_the_reloaded_client_certificate() {
+        let ca = Ca::new();
+        let (server, backend_files) = mutual_tls_backend(&ca).await;
+        let files = ClientIdentityFiles {
+            certificate: temp_path("crt"),
+            key: temp_path("key"),
+        };
+        ca.issue("wakanda-lb", &files);
+
+        let http_client = ClientIdentityHttpClient::new(
+            files.clone(),
+            Duration::from_secs(1),
+            reqwest_http_client,
+        )
+        .unwrap();
+
+        assert_eq!(
+            common_name(&http_client, &server).await.as_deref(),
+            Some("wakanda-lb")
+        );
+
+        ca.issue("wakanda-lb-renewed", &files);
+        assert!(http_client.reload().await.unwrap());
+
+        assert_eq!(
+            common_name(&http_client, &server).await.as_deref(),
+            Some("wakanda-lb-renewed")
+        );
+
+        for path in backend_files.iter().chain([&files.certificate, &files.key]) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_be_rejected_without_a_client_certificate() {
+        let ca = Ca::new();
+        let (server, backend_files) = mutual_tls_backend(&ca).await;
+
+        let http_client = reqwest_http_client(None).unwrap();
+
+        assert_eq!(common_name(http_client.as_ref(), &server).await, None);
+
+        for path in backend_files {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(test)]
+mod connection_recycling {
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::routing::get;
+    use axum::{Router, middleware};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use load_balancer::client_connection::{self, ClientConnection};
+    use load_balancer::connection_recycling::{self, ConnectionRecycling};
+    use load_balancer::downstream_timeouts::{self, DownstreamTimeouts, TimedListener};
+    use load_balancer::metrics::metrics::Metrics;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+    async fn start_server(recycling: ConnectionRecycling) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                recycling,
+                connection_recycling::recycle,
+            ))
+            .layer(middleware::from_fn(downstream_timeouts::track))
+            .layer(middleware::from_fn(client_connection::expose));
+
+        tokio::spawn(async move {
+            axum::serve(
+                TimedListener::new(
+                    listener,
+                    DownstreamTimeouts::default(),
+                    Arc::new(Metrics::default()),
+                ),
+                app.into_make_service_with_connect_info::<ClientConnection>(),
+            )
+            .await
+            .unwrap()
+        });
+
+        address
+    }
+
+    /// Reads a whole response, whose body is `ok`.
+    async fn read_response(stream: &mut TcpStream) -> String {
+        let mut received = Vec::new();
+
+        tokio::time::timeout(Duration::from_secs(2), async {
+            let mut buffer = [0; 1024];
+            while !received.ends_with(b"\r\n\r\nok") {
+                let read = stream.read(&mut buffer).await.unwrap();
+                assert_ne!(read, 0, "the server closed the connection");
+                received.extend_from_slice(&buffer[..read]);
+            }
+        })
+        .await
+        .expect("the server didn't answer");
+
+        String::from_utf8(received).unwrap().to_lowercase()
+    }
+
+    async fn assert_closed(stream: &mut TcpStream) {
+        let read = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut [0; 64]))
+            .await
+            .expect("the server kept the connection open");
+
+        assert!(matches!(read, Ok(0) | Err(_)));
+    }
+
+    #[tokio::test]
+    async fn should_close_connections_after_their_last_request() {
+        let address = start_server(ConnectionRecycling {
+            max_requests: Some(2),
+            max_age: None,
+        })
+        .await;
+        let mut stream = TcpStream::connect(address).await.unwrap();
+
+        stream.write_all(REQUEST).await.unwrap();
+        let first = read_response(&mut stream).await;
+        stream.write_all(REQUEST).await.unwrap();
+        let second = read_response(&mut stream).await;
+
+        assert!(!first.contains("connection: close"));
+        assert!(second.contains("connection: close"));
+        assert_closed(&mut stream).await;
+    }
+
+    #[tokio::test]
+    async fn should_close_connections_past_their_max_age() {
+        let address = start_server(ConnectionRecycling {
+            max_requests: None,
+            max_age: Some(Duration::from_millis(200)),
+        })
+        .await;
+        let mut stream = TcpStream::connect(address).await.unwrap();
+
+        stream.write_all(REQUEST).await.unwrap();
+        let first = read_response(&mut stream).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        stream.write_all(REQUEST).await.unwrap();
+        let second = read_response(&mut stream).await;
+
+        assert!(!first.contains("connection: close"));
+        assert!(second.contains("connection: close"));
+        assert_closed(&mut stream).await;
+    }
+
+    #[tokio::test]
+    async fn should_keep_connections_open_without_limits() {
+        let address = start_server(ConnectionRecycling::default()).await;
+        let mut stream = TcpStream::connect(address).await.unwrap();
+
+        for _ in 0..5 {
+            stream.write_all(REQUEST).await.unwrap();
+
+            assert!(
+                !read_response(&mut stream)
+                    .await
+                    .contains("connection: close")
+            );
+        }
+    }
+}
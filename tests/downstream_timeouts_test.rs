@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod downstream_timeouts {
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use axum::routing::get;
+    use axum::{Router, middleware};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use load_balancer::client_connection::{self, ClientConnection};
+    use load_balancer::downstream_timeouts::{self, DownstreamTimeouts, TimedListener};
+    use load_balancer::metrics::metrics::{HEAD_TIMEOUTS_TOTAL, Metrics};
+
+    const IDLE: Duration = Duration::from_millis(300);
+    const READ: Duration = Duration::from_millis(100);
+    const HEAD: Duration = Duration::from_millis(250);
+
+    async fn start_server() -> SocketAddr {
+        start_server_with_metrics(Arc::new(Metrics::default())).await
+    }
+
+    async fn start_server_with_metrics(metrics: Arc<Metrics>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let timeouts = DownstreamTimeouts {
+            idle: Some(IDLE),
+            read: Some(READ),
+            head: Some(HEAD),
+        };
+
+        // Slower than both timeouts, which don't apply while it runs.
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(IDLE * 2).await;
+                    "done"
+                }),
+            )
+            .layer(middleware::from_fn(downstream_timeouts::track))
+            .layer(middleware::from_fn(client_connection::expose));
+
+        tokio::spawn(async move {
+            axum::serve(
+                TimedListener::new(listener, timeouts, metrics),
+                app.into_make_service_with_connect_info::<ClientConnection>(),
+            )
+            .await
+            .unwrap()
+        });
+
+        address
+    }
+
+    /// Reads until the server closes the connection, or fails the test after
+    /// a couple of seconds.
+    async fn read_until_closed(stream: &mut TcpStream) -> String {
+        let mut received = Vec::new();
+
+        tokio::time::timeout(Duration::from_secs(2), async {
+            let mut buffer = [0; 1024];
+            while let Ok(read) = stream.read(&mut buffer).await {
+                if read == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buffer[..read]);
+            }
+        })
+        .await
+        .expect("the server kept the connection open");
+
+        String::from_utf8(received).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_drop_clients_pausing_while_sending_a_request() {
+        let mut stream = TcpStream::connect(start_server().await).await.unwrap();
+        let started = Instant::now();
+
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: lo")
+            .await
+            .unwrap();
+
+        assert_eq!(read_until_closed(&mut stream).await, "");
+        assert!(started.elapsed() < IDLE);
+    }
+
+    #[tokio::test]
+    async fn should_answer_slow_requests_then_drop_the_idle_connection() {
+        let mut stream = TcpStream::connect(start_server().await).await.unwrap();
+
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let started = Instant::now();
+
+        let response = read_until_closed(&mut stream).await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("done"));
+        assert!(started.elapsed() >= IDLE * 3);
+    }
+
+    #[tokio::test]
+    async fn should_drop_connections_that_never_send_a_request() {
+        let mut stream = TcpStream::connect(start_server().await).await.unwrap();
+        let started = Instant::now();
+
+        assert_eq!(read_until_closed(&mut stream).await, "");
+        assert!(started.elapsed() >= IDLE);
+    }
+
+    #[tokio::test]
+    async fn should_drop_and_count_clients_trickling_a_request_head() {
+        let metrics = Arc::new(Metrics::default());
+        let mut stream = TcpStream::connect(start_server_with_metrics(Arc::clone(&metrics)).await)
+            .await
+            .unwrap();
+        let started = Instant::now();
+
+        // A byte at a time, each well within the read timeout.
+        for byte in b"GET /slow HTTP/1.1\r\nHost: localhost\r\nX-Slow: 1" {
+            if stream.write_all(&[*byte]).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(READ / 2).await;
+        }
+
+        assert_eq!(read_until_closed(&mut stream).await, "");
+        assert!(started.elapsed() >= HEAD);
+        assert_eq!(metrics.get(HEAD_TIMEOUTS_TOTAL), 1);
+    }
+}
@@ -0,0 +1,250 @@
+#[cfg(test)]
+mod multipart_upload {
+
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::Router;
+    use axum::extract::{Request, State};
+    use axum::routing::post;
+    use bytes::{Bytes, BytesMut};
+    use http::header::{CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
+    use http_body_util::BodyExt;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    use load_balancer::http_client::reqwest_http_client::ReqwestHttpClient;
+    use load_balancer::{ServerState, router};
+
+    const BOUNDARY: &str = "wakanda-boundary-7MA4YWxkTrZu0gW";
+    const CHUNK_BYTES: usize = 1024 * 1024;
+    const FILE_CHUNKS: usize = 256;
+
+    fn preamble() -> Bytes {
+        Bytes::from(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"large.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            BOUNDARY
+        ))
+    }
+
+    fn epilogue() -> Bytes {
+        Bytes::from(format!("\r\n--{}--\r\n", BOUNDARY))
+    }
+
+    fn file_chunk() -> Bytes {
+        Bytes::from(
+            (0..CHUNK_BYTES)
+                .map(|i| (i % 251) as u8)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn upload_bytes() -> u64 {
+        (preamble().len() + FILE_CHUNKS * CHUNK_BYTES + epilogue().len()) as u64
+    }
+
+    /// What the backend saw of the upload, without keeping it in memory.
+    #[derive(Debug, Default)]
+    struct Received {
+        content_type: Option<String>,
+        content_length: Option<String>,
+        transfer_encoding: Option<String>,
+        bytes: u64,
+        head: BytesMut,
+        tail: BytesMut,
+        chunks_match: bool,
+    }
+
+    #[derive(Clone)]
+    struct Backend {
+        received: Arc<Mutex<Received>>,
+        /// Told when the first bytes arrive, before the upload is over.
+        first_bytes: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    }
+
+    async fn receive(State(backend): State<Backend>, request: Request) -> &'static str {
+        let (parts, mut body) = request.into_parts();
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value: &http::HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let preamble = preamble();
+        let chunk = file_chunk();
+        let mut received = Received {
+            content_type: header(CONTENT_TYPE),
+            content_length: header(CONTENT_LENGTH),
+            transfer_encoding: header(TRANSFER_ENCODING),
+            chunks_match: true,
+            ..Received::default()
+        };
+
+        while let Some(frame) = body.frame().await {
+            let Ok(data) = frame.unwrap().into_data() else {
+                continue;
+            };
+
+            if let Some(first_bytes) = backend.first_bytes.lock().unwrap().take() {
+                let _ = first_bytes.send(());
+            }
+
+            // Compares the file part of the frame with the chunks it was
+            // cut from, a run of bytes at a time.
+            let file_start = preamble.len() as u64;
+            let file_end = file_start + (FILE_CHUNKS * CHUNK_BYTES) as u64;
+            let mut offset = received.bytes.max(file_start);
+            while offset < (received.bytes + data.len() as u64).min(file_end) {
+                let in_chunk = ((offset - file_start) as usize) % CHUNK_BYTES;
+                let in_data = (offset - received.bytes) as usize;
+                let run = (CHUNK_BYTES - in_chunk).min(data.len() - in_data);
+
+                if data[in_data..in_data + run] != chunk[in_chunk..in_chunk + run] {
+                    received.chunks_match = false;
+                }
+                offset += run as u64;
+            }
+
+            if received.head.len() < preamble.len() {
+                let missing = (preamble.len() - received.head.len()).min(data.len());
+                received.head.extend_from_slice(&data[..missing]);
+            }
+            received.tail.extend_from_slice(&data);
+            let keep = received.tail.len().saturating_sub(epilogue().len());
+            let _ = received.tail.split_to(keep);
+
+            received.bytes += data.len() as u64;
+        }
+
+        *backend.received.lock().unwrap() = received;
+        "uploaded"
+    }
+
+    async fn start_backend() -> (String, Backend, oneshot::Receiver<()>) {
+        let (first_bytes, first_bytes_received) = oneshot::channel();
+        let backend = Backend {
+            received: Arc::new(Mutex::new(Received::default())),
+            first_bytes: Arc::new(Mutex::new(Some(first_bytes))),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/upload", post(receive))
+            .with_state(backend.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (address, backend, first_bytes_received)
+    }
+
+    fn server_state(backend: String, retries: usize) -> ServerState {
+        ServerState {
+            retries,
+            ..ServerState::new(vec![backend], Arc::new(ReqwestHttpClient::default()))
+        }
+    }
+
+    async fn start_load_balancer(backend: String, retries: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let app = router(server_state(backend, retries));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        address
+    }
+
+    /// Sends the preamble and a first chunk, then holds the rest of the
+    /// upload back until the backend got those: a load balancer assembling
+    /// the body before forwarding it would never let the upload finish.
+    fn upload(first_bytes_received: oneshot::Receiver<()>) -> reqwest::Body {
+        let parts = futures::stream::unfold(
+            (0, Some(first_bytes_received)),
+            |(part, mut first_bytes_received)| async move {
+                let data = match part {
+                    0 => preamble(),
+                    1 => file_chunk(),
+                    part if part <= FILE_CHUNKS => {
+                        if let Some(first_bytes_received) = first_bytes_received.take() {
+                            first_bytes_received.await.unwrap();
+                        }
+                        file_chunk()
+                    }
+                    part if part == FILE_CHUNKS + 1 => epilogue(),
+                    _ => return None,
+                };
+
+                Some((
+                    Ok::<_, std::io::Error>(data),
+                    (part + 1, first_bytes_received),
+                ))
+            },
+        );
+
+        reqwest::Body::wrap_stream(parts)
+    }
+
+    async fn upload_through_load_balancer(content_length: bool, retries: usize) -> Received {
+        let (backend_address, backend, first_bytes_received) = start_backend().await;
+        let load_balancer = start_load_balancer(backend_address, retries).await;
+
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/upload", load_balancer))
+            .header(
+                CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .body(upload(first_bytes_received));
+        if content_length {
+            request = request.header(CONTENT_LENGTH, upload_bytes());
+        }
+
+        let response = tokio::time::timeout(Duration::from_secs(120), request.send())
+            .await
+            .expect("the upload was not streamed to the backend")
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "uploaded");
+
+        std::mem::take(&mut *backend.received.lock().unwrap())
+    }
+
+    fn assert_upload_intact(received: &Received) {
+        assert_eq!(
+            received.content_type.as_deref(),
+            Some(format!("multipart/form-data; boundary={}", BOUNDARY).as_str())
+        );
+        assert_eq!(received.bytes, upload_bytes());
+        assert_eq!(received.head, preamble());
+        assert_eq!(received.tail, epilogue());
+        assert!(received.chunks_match);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_stream_a_large_upload_keeping_its_content_length() {
+        let received = upload_through_load_balancer(true, 0).await;
+
+        assert_upload_intact(&received);
+        assert_eq!(received.content_length, Some(upload_bytes().to_string()));
+        assert_eq!(received.transfer_encoding, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_stream_a_large_chunked_upload_keeping_it_chunked() {
+        let received = upload_through_load_balancer(false, 0).await;
+
+        assert_upload_intact(&received);
+        assert_eq!(received.content_length, None);
+        assert_eq!(received.transfer_encoding.as_deref(), Some("chunked"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_stream_a_large_upload_even_when_retries_are_enabled() {
+        let received = upload_through_load_balancer(true, 2).await;
+
+        assert_upload_intact(&received);
+        assert_eq!(received.content_length, Some(upload_bytes().to_string()));
+    }
+}
@@ -2,13 +2,19 @@
 mod reqwest_http_client {
 
     use bytes::Bytes;
+    use http::HeaderValue;
+    use http::header::{AUTHORIZATION, CONTENT_TYPE};
 
     use load_balancer::http_client::error::Error;
     use load_balancer::http_client::http_client::HttpClient;
     use load_balancer::http_client::request::{Request, RequestHeaders, RequestMethod};
     use load_balancer::http_client::reqwest_http_client::ReqwestHttpClient;
+    use load_balancer::http_client::upstream_protocol::UpstreamProtocol;
+    use load_balancer::http_client::upstream_proxy::UpstreamProxy;
 
-    use wiremock::matchers::{header, method, path};
+    use load_balancer::http_client::body::Body;
+
+    use wiremock::matchers::{body_bytes, header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -30,19 +36,23 @@ mod reqwest_http_client {
         let http_client = ReqwestHttpClient::default();
 
         let http_client_request = Request {
-            url: format!("{}{}", mock_server.uri(), "/v1/api/user".to_string()),
+            url: format!("{}{}", mock_server.uri(), "/v1/api/user"),
             method: RequestMethod::Get,
             headers: RequestHeaders::from([
-                ("Authorization".to_string(), "Bearer secret".to_string()),
-                ("Content-Type".to_string(), "text/plain".to_string()),
+                (AUTHORIZATION, HeaderValue::from_static("Bearer secret")),
+                (CONTENT_TYPE, HeaderValue::from_static("text/plain")),
             ]),
-            body: Bytes::new(),
+            body: Bytes::new().into(),
+            timeout: None,
         };
 
         let http_client_response = http_client.execute(http_client_request).await.unwrap();
 
         assert_eq!(http_client_response.status, 200);
-        assert_eq!(http_client_response.body, Bytes::from("OK"));
+        assert_eq!(
+            http_client_response.body.collect().await.unwrap(),
+            Bytes::from("OK")
+        );
         assert_eq!(
             http_client_response.headers.get("x-request-id").unwrap(),
             "12345"
@@ -67,13 +77,14 @@ mod reqwest_http_client {
 
         let http_client = ReqwestHttpClient::default();
         let http_client_request = Request {
-            url: format!("{}{}", mock_server.uri(), "/api/data".to_string()),
+            url: format!("{}{}", mock_server.uri(), "/api/data"),
             method: RequestMethod::Post,
             headers: RequestHeaders::from([
-                ("Authorization".to_string(), "Bearer secret".to_string()),
-                ("Content-Type".to_string(), "text/plain".to_string()),
+                (AUTHORIZATION, HeaderValue::from_static("Bearer secret")),
+                (CONTENT_TYPE, HeaderValue::from_static("text/plain")),
             ]),
-            body: Bytes::from("OK"),
+            body: Bytes::from("OK").into(),
+            timeout: None,
         };
 
         let http_client_response = http_client.execute(http_client_request).await.unwrap();
@@ -83,21 +94,21 @@ mod reqwest_http_client {
             http_client_response.headers.get("x-request-id").unwrap(),
             "12345"
         );
-        assert_eq!(http_client_response.body, Bytes::from("Created"));
+        assert_eq!(
+            http_client_response.body.collect().await.unwrap(),
+            Bytes::from("Created")
+        );
     }
 
     #[tokio::test]
     async fn should_detect_a_network_error() {
         let http_client = ReqwestHttpClient::default();
         let http_client_request = Request {
-            url: format!(
-                "{}{}",
-                "http://unknown:1234".to_string(),
-                "/health".to_string()
-            ),
+            url: format!("{}{}", "http://unknown:1234", "/health"),
             method: RequestMethod::Get,
             headers: RequestHeaders::default(),
-            body: Bytes::new(),
+            body: Bytes::new().into(),
+            timeout: None,
         };
 
         let http_client_response = http_client.execute(http_client_request).await;
@@ -128,10 +139,11 @@ mod reqwest_http_client {
         );
 
         let http_client_request = Request {
-            url: format!("{}{}", mock_server.uri(), "/slow".to_string()),
+            url: format!("{}{}", mock_server.uri(), "/slow"),
             method: RequestMethod::Get,
             headers: RequestHeaders::default(),
-            body: Bytes::new(),
+            body: Bytes::new().into(),
+            timeout: None,
         };
 
         let http_client_response = http_client.execute(http_client_request).await;
@@ -150,6 +162,8 @@ mod reqwest_http_client {
             (RequestMethod::Put, "PUT"),
             (RequestMethod::Delete, "DELETE"),
             (RequestMethod::Patch, "PATCH"),
+            (RequestMethod::Head, "HEAD"),
+            (RequestMethod::Options, "OPTIONS"),
         ] {
             Mock::given(method(method_str))
                 .and(path("/health"))
@@ -159,10 +173,11 @@ mod reqwest_http_client {
 
             let http_client = ReqwestHttpClient::default();
             let http_client_request = Request {
-                url: format!("{}{}", mock_server.uri(), "/health".to_string()),
+                url: format!("{}{}", mock_server.uri(), "/health"),
                 method: method_enum,
                 headers: RequestHeaders::default(),
-                body: Bytes::new(),
+                body: Bytes::new().into(),
+                timeout: None,
             };
 
             let http_client_response = http_client.execute(http_client_request).await.unwrap();
@@ -170,4 +185,237 @@ mod reqwest_http_client {
             assert_eq!(http_client_response.status, 200);
         }
     }
+
+    #[tokio::test]
+    async fn probe_client_should_not_follow_redirects() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/elsewhere"))
+            .mount(&mock_server)
+            .await;
+
+        let http_client = ReqwestHttpClient::new(
+            ReqwestHttpClient::probe_client_builder(std::time::Duration::from_secs(1))
+                .build()
+                .unwrap(),
+        );
+
+        let http_client_request = Request {
+            url: format!("{}{}", mock_server.uri(), "/health"),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 302);
+    }
+
+    #[tokio::test]
+    async fn should_stream_the_request_body() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/upload"))
+            .and(body_bytes("first chunk, second chunk"))
+            .respond_with(ResponseTemplate::new(201))
+            .mount(&mock_server)
+            .await;
+
+        let http_client = ReqwestHttpClient::default();
+
+        let chunks = futures::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from("first chunk, ")),
+            Ok(Bytes::from("second chunk")),
+        ]);
+
+        let http_client_request = Request {
+            url: format!("{}{}", mock_server.uri(), "/upload"),
+            method: RequestMethod::Post,
+            headers: RequestHeaders::default(),
+            body: Body::from(axum::body::Body::from_stream(chunks)),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 201);
+    }
+
+    #[tokio::test]
+    async fn should_preserve_the_query_string_and_percent_encoding() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/files/hello%20world"))
+            .and(query_param("name", "a&b"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        let http_client = ReqwestHttpClient::default();
+
+        let http_client_request = Request {
+            url: format!("{}{}", mock_server.uri(), "/files/hello%20world?name=a%26b"),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 200);
+    }
+
+    #[tokio::test]
+    async fn should_speak_http2_with_prior_knowledge() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/h2"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("multiplexed"))
+            .mount(&mock_server)
+            .await;
+
+        let http_client = ReqwestHttpClient::new(
+            UpstreamProtocol::Http2
+                .apply(reqwest::Client::builder())
+                .build()
+                .unwrap(),
+        );
+
+        let http_client_request = Request {
+            url: format!("{}{}", mock_server.uri(), "/h2"),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 200);
+        assert_eq!(
+            http_client_response.body.collect().await.unwrap(),
+            Bytes::from("multiplexed")
+        );
+    }
+
+    #[tokio::test]
+    async fn should_hand_encoded_responses_over_as_encoded() {
+        let mock_server = MockServer::start().await;
+        let encoded = Bytes::from_static(b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03");
+
+        Mock::given(method("GET"))
+            .and(path("/archive"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Encoding", "gzip")
+                    .set_body_raw(encoded.to_vec(), "text/plain"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let http_client = ReqwestHttpClient::default();
+
+        let http_client_request = Request {
+            url: format!("{}{}", mock_server.uri(), "/archive"),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::from([(
+                http::header::ACCEPT_ENCODING,
+                HeaderValue::from_static("gzip"),
+            )]),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(
+            http_client_response
+                .headers
+                .get("content-encoding")
+                .unwrap(),
+            "gzip"
+        );
+        assert_eq!(
+            http_client_response.headers.get("content-length").unwrap(),
+            "10"
+        );
+        assert_eq!(http_client_response.body.collect().await.unwrap(), encoded);
+    }
+
+    #[tokio::test]
+    async fn should_reach_the_target_server_through_the_upstream_proxy() {
+        let proxy_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .and(header("Host", "backend.invalid"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("via proxy"))
+            .expect(1)
+            .mount(&proxy_server)
+            .await;
+
+        let proxy = UpstreamProxy::new(&proxy_server.uri(), None).unwrap();
+        let http_client = ReqwestHttpClient::new(
+            proxy
+                .apply(ReqwestHttpClient::upstream_client_builder())
+                .build()
+                .unwrap(),
+        );
+
+        let http_client_request = Request {
+            url: "http://backend.invalid/items".to_string(),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 200);
+        assert_eq!(
+            http_client_response.body.collect().await.unwrap(),
+            "via proxy"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_bypass_the_upstream_proxy_for_no_proxy_hosts() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let proxy = UpstreamProxy::new("http://127.0.0.1:9", Some("127.0.0.1")).unwrap();
+        let http_client = ReqwestHttpClient::new(
+            proxy
+                .apply(ReqwestHttpClient::upstream_client_builder())
+                .build()
+                .unwrap(),
+        );
+
+        let http_client_request = Request {
+            url: format!("{}/items", mock_server.uri()),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 204);
+    }
 }
@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod sni_override_http_client {
+
+    use bytes::Bytes;
+
+    use load_balancer::http_client::http_client::HttpClient;
+    use load_balancer::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use load_balancer::http_client::sni_override_http_client::SniOverrideHttpClient;
+
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn should_reach_the_server_address_using_the_overridden_name() {
+        let mock_server = MockServer::start().await;
+        let port = mock_server.address().port();
+
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .and(header("Host", format!("api.internal:{}", port)))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        let http_client =
+            SniOverrideHttpClient::new(reqwest::Client::builder, "api.internal".to_string())
+                .unwrap();
+
+        let http_client_request = Request {
+            url: format!("{}{}", mock_server.uri(), "/health"),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 200);
+    }
+
+    #[tokio::test]
+    async fn should_resolve_the_host_of_the_server() {
+        let mock_server = MockServer::start().await;
+        let port = mock_server.address().port();
+
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .and(header("Host", format!("api.internal:{}", port)))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        let http_client =
+            SniOverrideHttpClient::new(reqwest::Client::builder, "api.internal".to_string())
+                .unwrap();
+
+        let http_client_request = Request {
+            url: format!("http://localhost:{}/health", port),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        let http_client_response = http_client.execute(http_client_request).await.unwrap();
+
+        assert_eq!(http_client_response.status, 200);
+    }
+}
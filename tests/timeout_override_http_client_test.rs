@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod timeout_override_http_client {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use load_balancer::http_client::error::Error;
+    use load_balancer::http_client::http_client::HttpClient;
+    use load_balancer::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use load_balancer::http_client::reqwest_http_client::ReqwestHttpClient;
+    use load_balancer::http_client::timeout_override_http_client::TimeoutOverrideHttpClient;
+
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn slow_server() -> MockServer {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(200)))
+            .mount(&mock_server)
+            .await;
+
+        mock_server
+    }
+
+    /// Answers the head right away, then the body after `delay`.
+    async fn slow_body_server(delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 4\r\n\r\n")
+                .await
+                .unwrap();
+            tokio::time::sleep(delay).await;
+            stream.write_all(b"done").await.unwrap();
+        });
+
+        format!("http://{}", address)
+    }
+
+    fn request(server: &str) -> Request {
+        Request {
+            url: format!("{}{}", server, "/slow"),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        }
+    }
+
+    fn http_client(
+        timeout: Duration,
+        overrides: Vec<(String, Duration)>,
+    ) -> TimeoutOverrideHttpClient {
+        TimeoutOverrideHttpClient::new(Arc::new(ReqwestHttpClient::default()), timeout, overrides)
+    }
+
+    #[tokio::test]
+    async fn should_apply_the_timeouts_of_the_backend() {
+        let impatient_server = slow_server().await;
+        let patient_server = slow_server().await;
+
+        let http_client = http_client(
+            Duration::from_secs(30),
+            vec![(impatient_server.uri(), Duration::from_millis(50))],
+        );
+
+        let impatient_response = http_client.execute(request(&impatient_server.uri())).await;
+        let patient_response = http_client.execute(request(&patient_server.uri())).await;
+
+        assert!(matches!(impatient_response.unwrap_err(), Error::Timeout));
+        assert_eq!(patient_response.unwrap().status, 200);
+    }
+
+    #[tokio::test]
+    async fn should_apply_the_timeout_of_the_request_over_the_one_of_the_backend() {
+        let server = slow_server().await;
+
+        let http_client = http_client(
+            Duration::from_secs(30),
+            vec![(server.uri(), Duration::from_millis(50))],
+        );
+
+        let response = http_client
+            .execute(Request {
+                timeout: Some(Duration::from_secs(5)),
+                ..request(&server.uri())
+            })
+            .await;
+
+        assert_eq!(response.unwrap().status, 200);
+    }
+
+    #[tokio::test]
+    async fn should_let_the_body_stream_past_the_timeout() {
+        let server = slow_body_server(Duration::from_millis(200)).await;
+
+        let http_client = http_client(Duration::from_millis(50), Vec::new());
+
+        let response = http_client.execute(request(&server)).await.unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body.collect().await.unwrap(), "done");
+    }
+}
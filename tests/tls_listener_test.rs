@@ -0,0 +1,319 @@
+#[cfg(test)]
+mod tls_listener {
+
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::extract::ConnectInfo;
+    use axum::routing::get;
+    use axum::serve::ListenerExt;
+    use axum::{Extension, Router, middleware};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use uuid::Uuid;
+
+    use load_balancer::client_certificate::ClientCertificate;
+    use load_balancer::client_connection::{self, ClientConnection};
+    use load_balancer::tls::certificate_reloader::CertificateReloader;
+    use load_balancer::tls::sni_resolver::SniResolver;
+    use load_balancer::tls::tls_listener::TlsListener;
+    use load_balancer::tls::tls_policy::{ClientAuth, TlsPolicy};
+
+    fn write_pair(certificate_path: &PathBuf, key_path: &PathBuf) -> Vec<u8> {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let certificate = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+
+        std::fs::write(certificate_path, certificate.pem()).unwrap();
+        std::fs::write(key_path, key_pair.serialize_pem()).unwrap();
+
+        certificate.der().to_vec()
+    }
+
+    async fn peer_certificate(client: &reqwest::Client, url: &str) -> (String, Vec<u8>) {
+        let response = client.get(url).send().await.unwrap();
+        let certificate = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|tls_info| tls_info.peer_certificate())
+            .unwrap()
+            .to_vec();
+
+        (response.text().await.unwrap(), certificate)
+    }
+
+    #[tokio::test]
+    async fn should_serve_the_renewed_certificate_to_new_connections() {
+        let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+        let certificate_path = prefix.with_extension("crt");
+        let key_path = prefix.with_extension("key");
+        let certificate = write_pair(&certificate_path, &key_path);
+
+        let certificate_reloader = Arc::new(
+            CertificateReloader::load(
+                certificate_path.clone(),
+                key_path.clone(),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap(),
+        );
+        let tls_config = Arc::new(
+            certificate_reloader
+                .server_config(&TlsPolicy::default())
+                .unwrap(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let tls_listener = TlsListener::new(tcp_listener, tls_config).unwrap();
+
+        let app =
+            Router::new().route(
+                "/",
+                get(|ConnectInfo(client): ConnectInfo<SocketAddr>| async move {
+                    client.ip().to_string()
+                }),
+            );
+
+        tokio::spawn(async move {
+            axum::serve(
+                tls_listener.tap_io(|_| {}),
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        // A client per connection, as a resumed TLS session carries no
+        // certificate.
+        let client = || {
+            reqwest::Client::builder()
+                .danger_accept_invalid_certs(true)
+                .tls_info(true)
+                .pool_max_idle_per_host(0)
+                .build()
+                .unwrap()
+        };
+        let url = format!("https://localhost:{}/", address.port());
+
+        let (client_ip, served) = peer_certificate(&client(), &url).await;
+        assert_eq!(client_ip, "127.0.0.1");
+        assert_eq!(served, certificate);
+
+        let renewed = write_pair(&certificate_path, &key_path);
+        assert!(certificate_reloader.reload().await.unwrap());
+
+        let (_, served) = peer_certificate(&client(), &url).await;
+        assert_eq!(served, renewed);
+
+        let _ = std::fs::remove_file(&certificate_path);
+        let _ = std::fs::remove_file(&key_path);
+    }
+
+    #[tokio::test]
+    async fn should_serve_the_certificate_of_the_server_name() {
+        let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+        let paths = |name: &str| {
+            (
+                prefix.with_extension(format!("{}.crt", name)),
+                prefix.with_extension(format!("{}.key", name)),
+            )
+        };
+        let mut certificates = Vec::new();
+        let mut reloaders = Vec::new();
+        for name in ["default", "api"] {
+            let (certificate_path, key_path) = paths(name);
+            certificates.push(write_pair(&certificate_path, &key_path));
+            reloaders.push(Arc::new(
+                CertificateReloader::load(certificate_path, key_path, Duration::from_secs(1))
+                    .await
+                    .unwrap(),
+            ));
+        }
+        let api = reloaders.pop().unwrap();
+        let default = reloaders.pop().unwrap();
+        let tls_config = Arc::new(
+            Arc::new(SniResolver::new(
+                default,
+                vec![("api.example.com".to_string(), api)],
+            ))
+            .server_config(&TlsPolicy::default())
+            .unwrap(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let tls_listener = TlsListener::new(tcp_listener, tls_config).unwrap();
+        let app =
+            Router::new().route(
+                "/",
+                get(|ConnectInfo(client): ConnectInfo<SocketAddr>| async move {
+                    client.ip().to_string()
+                }),
+            );
+
+        tokio::spawn(async move {
+            axum::serve(
+                tls_listener.tap_io(|_| {}),
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .tls_info(true)
+            .resolve("api.example.com", address)
+            .resolve("www.example.com", address)
+            .build()
+            .unwrap();
+
+        let (_, served) = peer_certificate(
+            &client,
+            &format!("https://api.example.com:{}/", address.port()),
+        )
+        .await;
+        assert_eq!(served, certificates[1]);
+
+        let (_, served) = peer_certificate(
+            &client,
+            &format!("https://www.example.com:{}/", address.port()),
+        )
+        .await;
+        assert_eq!(served, certificates[0]);
+
+        for name in ["default", "api"] {
+            let (certificate_path, key_path) = paths(name);
+            let _ = std::fs::remove_file(certificate_path);
+            let _ = std::fs::remove_file(key_path);
+        }
+    }
+
+    async fn request(config: ClientConfig, address: SocketAddr) -> std::io::Result<String> {
+        let stream = TcpStream::connect(address).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn should_expose_the_client_certificate() {
+        let prefix = std::env::temp_dir().join(format!("wakanda-lb-{}", Uuid::new_v4()));
+        let certificate_path = prefix.with_extension("crt");
+        let key_path = prefix.with_extension("key");
+        let client_ca_path = prefix.with_extension("ca.crt");
+        let served = write_pair(&certificate_path, &key_path);
+
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        std::fs::write(&client_ca_path, ca.pem()).unwrap();
+
+        let client_key = rcgen::KeyPair::generate().unwrap();
+        let mut client_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        client_params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "checkout");
+        client_params
+            .distinguished_name
+            .push(rcgen::DnType::OrganizationalUnitName, "payments");
+        let client_certificate = client_params
+            .signed_by(&client_key, &rcgen::Issuer::new(ca_params, ca_key))
+            .unwrap();
+
+        let certificate_reloader = Arc::new(
+            CertificateReloader::load(
+                certificate_path.clone(),
+                key_path.clone(),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap(),
+        );
+        let tls_config = Arc::new(
+            certificate_reloader
+                .server_config(&TlsPolicy {
+                    client_auth: ClientAuth::Required,
+                    client_ca: Some(client_ca_path.clone()),
+                    ..TlsPolicy::default()
+                })
+                .unwrap(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let tls_listener = TlsListener::new(tcp_listener, tls_config).unwrap();
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(
+                    |Extension(certificate): Extension<Arc<ClientCertificate>>| async move {
+                        format!(
+                            "{} {}",
+                            certificate.common_name.clone().unwrap_or_default(),
+                            certificate.organizational_units.join(",")
+                        )
+                    },
+                ),
+            )
+            .layer(middleware::from_fn(client_connection::expose));
+
+        tokio::spawn(async move {
+            axum::serve(
+                tls_listener,
+                app.into_make_service_with_connect_info::<ClientConnection>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(served)).unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(
+            tokio_rustls::rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+
+        let authenticated = client_config
+            .clone()
+            .with_client_auth_cert(
+                vec![client_certificate.der().clone()],
+                PrivateKeyDer::from_pem_slice(client_key.serialize_pem().as_bytes()).unwrap(),
+            )
+            .unwrap();
+
+        let response = request(authenticated, address).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("checkout payments"));
+
+        let anonymous = client_config.with_no_client_auth();
+        assert!(request(anonymous, address).await.is_err());
+
+        let _ = std::fs::remove_file(&certificate_path);
+        let _ = std::fs::remove_file(&key_path);
+        let _ = std::fs::remove_file(&client_ca_path);
+    }
+}
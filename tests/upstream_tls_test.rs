@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod upstream_tls {
+
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::Router;
+    use axum::routing::get;
+    use bytes::Bytes;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    use load_balancer::http_client::http_client::HttpClient;
+    use load_balancer::http_client::request::{Request, RequestHeaders, RequestMethod};
+    use load_balancer::http_client::reqwest_http_client::ReqwestHttpClient;
+    use load_balancer::http_client::upstream_tls::UpstreamTls;
+    use load_balancer::tls::certificate_reloader::CertificateReloader;
+    use load_balancer::tls::tls_listener::TlsListener;
+    use load_balancer::tls::tls_policy::TlsPolicy;
+
+    struct InternalBackend {
+        url: String,
+        ca_path: PathBuf,
+        files: Vec<PathBuf>,
+    }
+
+    impl Drop for InternalBackend {
+        fn drop(&mut self) {
+            for path in &self.files {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("wakanda-lb-{}", Uuid::new_v4()))
+            .with_extension(extension)
+    }
+
+    /// Starts an HTTPS backend whose certificate is signed by a CA of its
+    /// own, written next to it.
+    async fn internal_backend() -> InternalBackend {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "Internal CA");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "localhost");
+        let certificate = params
+            .signed_by(&key_pair, &rcgen::Issuer::new(ca_params, ca_key))
+            .unwrap();
+
+        let (certificate_path, key_path, ca_path) =
+            (temp_path("crt"), temp_path("key"), temp_path("ca.crt"));
+        std::fs::write(&certificate_path, certificate.pem()).unwrap();
+        std::fs::write(&key_path, key_pair.serialize_pem()).unwrap();
+        std::fs::write(&ca_path, ca.pem()).unwrap();
+
+        let certificate_reloader = Arc::new(
+            CertificateReloader::load(
+                certificate_path.clone(),
+                key_path.clone(),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap(),
+        );
+        let tls_config = Arc::new(
+            certificate_reloader
+                .server_config(&TlsPolicy::default())
+                .unwrap(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = tcp_listener.local_addr().unwrap();
+        let tls_listener = TlsListener::new(tcp_listener, tls_config).unwrap();
+
+        tokio::spawn(async move {
+            axum::serve(
+                tls_listener,
+                Router::new().route("/", get(|| async { "OK" })),
+            )
+            .await
+            .unwrap();
+        });
+
+        InternalBackend {
+            url: format!("https://localhost:{}/", address.port()),
+            ca_path: ca_path.clone(),
+            files: vec![certificate_path, key_path, ca_path],
+        }
+    }
+
+    async fn status(upstream_tls: &UpstreamTls, url: &str) -> Option<u16> {
+        let http_client = ReqwestHttpClient::new(
+            upstream_tls
+                .apply(ReqwestHttpClient::upstream_client_builder())
+                .build()
+                .unwrap(),
+        );
+
+        let request = Request {
+            url: url.to_string(),
+            method: RequestMethod::Get,
+            headers: RequestHeaders::default(),
+            body: Bytes::new().into(),
+            timeout: None,
+        };
+
+        http_client
+            .execute(request)
+            .await
+            .ok()
+            .map(|response| response.status)
+    }
+
+    #[tokio::test]
+    async fn should_reject_an_internally_signed_backend_by_default() {
+        let backend = internal_backend().await;
+
+        assert_eq!(status(&UpstreamTls::default(), &backend.url).await, None);
+    }
+
+    #[tokio::test]
+    async fn should_trust_the_configured_ca_bundle() {
+        let backend = internal_backend().await;
+
+        let upstream_tls = UpstreamTls::default()
+            .with_ca_bundle(&backend.ca_path)
+            .unwrap();
+
+        assert_eq!(status(&upstream_tls, &backend.url).await, Some(200));
+    }
+
+    #[tokio::test]
+    async fn should_accept_any_certificate_when_skipping_verification() {
+        let backend = internal_backend().await;
+
+        let upstream_tls = UpstreamTls {
+            insecure_skip_verify: true,
+            ..UpstreamTls::default()
+        };
+
+        assert_eq!(status(&upstream_tls, &backend.url).await, Some(200));
+    }
+}